@@ -0,0 +1,36 @@
+use mcerv::api::{Error, InstanceManager, ServerFork};
+
+/// Lists the servers mcerv manages and the mods of the modded ones, using only
+/// `mcerv::api`.
+fn main() -> Result<(), Error> {
+    let manager = InstanceManager::new();
+    for instance in manager.list()? {
+        let info = match instance.info() {
+            Ok(info) => info,
+            Err(e) => {
+                println!("{}: {e}", instance.name());
+                continue;
+            }
+        };
+        let fork = match info.fork {
+            ServerFork::Vanilla => "Vanilla",
+            ServerFork::Fabric => "Fabric",
+            ServerFork::Forge => "Forge",
+            // Forks added later
+            _ => "another fork",
+        };
+        println!("{} ({fork} {})", instance.name(), info.game_version);
+
+        match instance.mods().installed() {
+            Ok(mods) => {
+                for installed in mods {
+                    let version = installed.version.as_deref().unwrap_or("unknown version");
+                    println!("  {} ({version})", installed.file_name);
+                }
+            }
+            Err(Error::Vanilla(_)) => {}
+            Err(e) => println!("  {e}"),
+        }
+    }
+    Ok(())
+}
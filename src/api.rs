@@ -0,0 +1,380 @@
+use crate::{
+    install_mod, last_known_server_info, plan_mod_updates, server_dir, server_fork, server_names,
+    system::{
+        config::Config,
+        installer::ServerInstaller,
+        jar_parser, mods,
+        server_info::ServerInfo,
+        server_name::{self, InvalidServerNameError},
+        update_plan::ModUpdatePlan,
+    },
+};
+use std::{
+    error::Error as StdError,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+pub use crate::network::mirror::MirrorRule;
+pub use crate::system::forks::ServerFork;
+pub use reqwest::Client;
+
+/// Sends the requests the rules match to their mirrors, like `mirrors` of the global config.
+/// This holds for the whole process.
+pub fn set_mirrors(rules: Vec<MirrorRule>) {
+    crate::network::mirror::set_mirrors(rules);
+}
+
+/// What the API fails with. More kinds may be added, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    InvalidName(InvalidServerNameError),
+    /// No server has the name.
+    NotFound(String),
+    /// The server is vanilla, which doesn't load mods.
+    Vanilla(String),
+    /// Anything else, like a failed download or an unreadable jar.
+    Other(anyhow::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidName(e) => write!(f, "{e}"),
+            Error::NotFound(name) => write!(f, "No server is named {name}"),
+            Error::Vanilla(name) => write!(f, "{name} is a vanilla server, which has no mods"),
+            Error::Other(e) => write!(f, "{e:#}"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::InvalidName(e) => Some(e),
+            Error::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Error::Other(e)
+    }
+}
+
+impl From<InvalidServerNameError> for Error {
+    fn from(e: InvalidServerNameError) -> Self {
+        Error::InvalidName(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The servers in the data directory, see [`crate::data_dir`].
+#[derive(Debug, Clone, Default)]
+pub struct InstanceManager {
+    _private: (),
+}
+
+impl InstanceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Manages the servers in `dir` instead, like `--data-dir`. This holds for the whole
+    /// process.
+    pub fn with_data_dir(dir: impl Into<PathBuf>) -> Self {
+        crate::set_data_dir(Some(dir.into()));
+        Self::default()
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        crate::data_dir()
+    }
+
+    /// The servers, sorted by name. Registered ones are listed even if their directory is
+    /// gone, for `mcerv doctor` to report.
+    pub fn list(&self) -> Result<Vec<Instance>> {
+        server_names()?
+            .into_iter()
+            .map(|name| {
+                Ok(Instance {
+                    dir: server_dir(&name)?,
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Instance> {
+        server_name::validate(name)?;
+        let dir = server_dir(name)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(name.to_string()));
+        }
+        Ok(Instance {
+            name: name.to_string(),
+            dir,
+        })
+    }
+
+    /// Installs a new server of the fork, see [`ForkInstaller`].
+    pub fn installer(&self, fork: ServerFork, name: &str) -> ForkInstaller {
+        ForkInstaller::new(fork, name)
+    }
+}
+
+/// A server mcerv manages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    name: String,
+    dir: PathBuf,
+}
+
+/// What a server runs, and how it's started.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct InstanceInfo {
+    pub fork: ServerFork,
+    pub game_version: String,
+    pub jar_name: String,
+    pub max_memory: String,
+    pub min_memory: String,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+}
+
+impl Instance {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The fork of the server, from the config if it was detected before.
+    pub fn fork(&self) -> Result<ServerFork> {
+        Ok(server_fork(&self.name).map_err(anyhow::Error::from)?)
+    }
+
+    /// Detects the fork and game version from the jar, or takes them from the config if
+    /// they were detected before.
+    pub fn info(&self) -> Result<InstanceInfo> {
+        let config = Config::load_or_create(&self.name)?;
+        let info = ServerInfo::cached_or_detect(&self.name)?;
+        Ok(InstanceInfo {
+            fork: info.server_fork,
+            game_version: info.game_version,
+            jar_name: config.jar_name,
+            max_memory: config.max_memory,
+            min_memory: config.min_memory,
+            tags: config.tags,
+            note: config.note,
+        })
+    }
+
+    pub fn mods(&self) -> ModManager {
+        ModManager {
+            instance: self.clone(),
+        }
+    }
+}
+
+/// A jar in the mods directory of a server.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct InstalledMod {
+    pub file_name: String,
+    /// From the jar's metadata, `None` if it has none.
+    pub mod_id: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A mod with a newer version on Modrinth for the server's loader and game version.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ModUpdate {
+    pub file_name: String,
+    /// The Modrinth project slug.
+    pub project: String,
+    pub version_id: String,
+    pub version_name: String,
+}
+
+/// The mods of a server.
+#[derive(Debug, Clone)]
+pub struct ModManager {
+    instance: Instance,
+}
+
+impl ModManager {
+    fn mods_dir(&self) -> PathBuf {
+        self.instance.dir.join("mods")
+    }
+
+    fn server_info(&self) -> Result<ServerInfo> {
+        let info = last_known_server_info(&self.instance.name).map_err(anyhow::Error::from)?;
+        if info.server_fork == ServerFork::Vanilla {
+            return Err(Error::Vanilla(self.instance.name.clone()));
+        }
+        Ok(info)
+    }
+
+    /// The mod jars, a server without a mods directory has none.
+    pub fn installed(&self) -> Result<Vec<InstalledMod>> {
+        let info = self.server_info()?;
+        let mods_dir = self.mods_dir();
+        if !mods_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut mods = Vec::new();
+        for path in mods::mod_jars(&mods_dir, &info.game_version).map_err(anyhow::Error::from)? {
+            let metadata = jar_parser::read_mod_metadata(&path).ok().flatten();
+            mods.push(InstalledMod {
+                file_name: mods::file_name(&path),
+                mod_id: metadata.as_ref().map(|metadata| metadata.id.clone()),
+                version: metadata.map(|metadata| metadata.version),
+            });
+        }
+        Ok(mods)
+    }
+
+    /// Looks the mods up on Modrinth without changing anything. Pinned mods are only
+    /// updated within their constraint.
+    pub async fn check_updates(&self, client: &Client) -> Result<Vec<ModUpdate>> {
+        self.server_info()?;
+        let mods_dir = self.mods_dir();
+        if !mods_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let plan = self.plan(client).await?;
+        Ok(plan
+            .updates()
+            .map(|(planned, update)| ModUpdate {
+                file_name: planned.installed.file_name(),
+                project: planned.slug.clone(),
+                version_id: update.id.clone(),
+                version_name: update.version_name.clone(),
+            })
+            .collect())
+    }
+
+    /// What [`ModManager::check_updates`] found, with the mods it couldn't look up.
+    pub(crate) async fn plan(&self, client: &Client) -> Result<ModUpdatePlan> {
+        let info = self.server_info()?;
+        Ok(plan_mod_updates(&self.instance.name, &info, &self.mods_dir(), client).await?)
+    }
+
+    /// Downloads the Modrinth version into the mods directory.
+    pub async fn install(&self, version_id: &str, client: &Client) -> Result<()> {
+        self.server_info()?;
+        Ok(install_mod(&self.instance.name, version_id, client).await?)
+    }
+}
+
+/// Installs a new server. The latest stable versions are installed unless others are
+/// chosen, like:
+///
+/// ```no_run
+/// # async fn install(client: &mcerv::api::Client) -> mcerv::api::Result<()> {
+/// use mcerv::api::{ForkInstaller, ServerFork};
+///
+/// let instance = ForkInstaller::new(ServerFork::Fabric, "smp")
+///     .game("1.21.8")
+///     .eula_accepted(true)
+///     .install(client)
+///     .await?;
+/// println!("Installed {}", instance.dir().display());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ForkInstaller {
+    installer: ServerInstaller,
+    name: String,
+}
+
+impl ForkInstaller {
+    pub fn new(fork: ServerFork, name: &str) -> Self {
+        Self {
+            installer: ServerInstaller::new(fork).server_name(name),
+            name: name.to_string(),
+        }
+    }
+
+    /// Names the server of an installer whose fork and versions are already chosen.
+    pub(crate) fn from_installer(installer: ServerInstaller, name: &str) -> Self {
+        Self {
+            installer: installer.server_name(name),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn game(mut self, version: impl Into<String>) -> Self {
+        self.installer = self.installer.game(version);
+        self
+    }
+
+    /// The loader version of Fabric, or the Forge version.
+    pub fn loader(mut self, version: impl Into<String>) -> Self {
+        self.installer = self.installer.loader(version);
+        self
+    }
+
+    /// Accepts the Minecraft EULA for the server, which it needs to start.
+    pub fn eula_accepted(mut self, eula_accepted: bool) -> Self {
+        self.installer = self.installer.eula_accepted(eula_accepted);
+        self
+    }
+
+    /// Modrinth projects to install with it, by slug or ID.
+    pub fn with_mods(mut self, mods: Vec<String>) -> Self {
+        self.installer = self.installer.with_mods(mods);
+        self
+    }
+
+    /// Launches the server once after installing, to generate its files.
+    pub fn bootstrap(mut self, bootstrap: bool) -> Self {
+        self.installer = self.installer.bootstrap(bootstrap);
+        self
+    }
+
+    /// Also downloads the server mappings of the game version.
+    pub fn with_mappings(mut self, with_mappings: bool) -> Self {
+        self.installer = self.installer.with_mappings(with_mappings);
+        self
+    }
+
+    /// Installs the server into this directory instead of the data directory.
+    pub fn location(mut self, location: impl Into<PathBuf>) -> Self {
+        self.installer = self.installer.location(location);
+        self
+    }
+
+    pub async fn install(self, client: &Client) -> Result<Instance> {
+        server_name::validate(&self.name)?;
+        self.installer.install(client).await?;
+        InstanceManager::new().get(&self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        let error = InstanceManager::new().get("../escape").unwrap_err();
+        assert!(matches!(error, Error::InvalidName(_)));
+        assert!(error.source().is_some());
+        assert_eq!(
+            Error::Vanilla("smp".to_string()).to_string(),
+            "smp is a vanilla server, which has no mods"
+        );
+    }
+}
@@ -1,26 +1,107 @@
+pub mod api;
 mod network;
 pub mod system;
 
 use crate::{
-    network::modrinth::{self, SearchIndex},
+    network::{
+        modrinth::{self, DependencyType, ModVersion, SearchIndex},
+        ping::{self, ServerStatus},
+        vanilla_meta,
+    },
     system::{
-        cli::{Cli, Versions},
-        config::Config,
-        forks::{self, Fork, InstallCommand, ServerFork},
-        jar_parser,
-        server_info::ServerInfo,
+        bootstrap,
+        bundle::{self, BundleManifest},
+        cache::DownloadCache,
+        cancel::{self, CancelledError},
+        clean,
+        cli::{Cli, SupervisionArgs},
+        config::{self, Config, ScriptTarget, SystemdUnit},
+        console,
+        crash_report::{self, CrashReport},
+        docker::{self, DockerServer},
+        doctor::{self, CheckStatus},
+        eula::{self, EulaNotAcceptedError},
+        forks::{self, ComponentUpdate, Fork, LatestVersions, ServerFork},
+        format,
+        global_config::GlobalConfig,
+        group::ServerGroup,
+        history::{self, ModChange},
+        install_manifest::{InstallManifest, ManifestServer},
+        jar_parser::{self, ModMetadata},
+        lock::InstanceLock,
+        metrics::{self, Collector},
+        mod_lock::{MOD_LOCK_FILE, ModLock},
+        modgraph::{Dependency, ModGraph, ModNode},
+        mods::{self, ClientOnlyMod, ModConflict, ModSort, ModStatus},
+        notify::{Notification, NotificationSettings, Notifier},
+        pins::{PINS_FILE, Pin, Pins, VersionConstraint},
+        process::{self, PidFile, ServerProcessError},
+        progress::{JsonSink, ProgressBarSink},
+        prompt::{self, Danger},
+        properties::ServerProperties,
+        registry::{REGISTRY_FILE, Registry},
+        rollback::{self, OldJar},
+        server_info::{DowngradeError, JarDetection, ServerInfo, UnknownForkError},
+        server_name::{self, InvalidServerNameError, ServerName},
+        start_template::StartTemplate,
+        supervisor,
+        template::{self, Template, TemplateMod},
+        trash::{self, TrashBatch},
+        update_plan::{self, ModUpdatePlan, PlannedMod},
+        verify::{self, ExpectedMod, Problem, VerifyError},
+        version,
+        watch::{self, KnownUpdates, WATCH_STATE_FILE, WatchState},
+        wizard,
+        world::{self, LevelInfo},
     },
 };
 use clap::CommandFactory;
-use dialoguer::Confirm;
+use dialoguer::{Input, Select};
 use directories::ProjectDirs;
+use futures::StreamExt;
+use prettytable::{Table, row};
 use reqwest::Client;
-use std::{error::Error, ffi::OsString, fmt::Display, fs, io::Write, path::PathBuf, time::Instant};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsString,
+    fmt::Display,
+    fs,
+    io::{self, IsTerminal, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+static NO_DETECTION_CACHE: AtomicBool = AtomicBool::new(false);
+static WAIT_FOR_LOCK: AtomicBool = AtomicBool::new(false);
+static PROGRESS_JSON: AtomicBool = AtomicBool::new(false);
+static FOLLOW_SYMLINK: AtomicBool = AtomicBool::new(false);
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Overrides the default data directory, unless `--data-dir` is passed.
+pub const DATA_DIR_VAR: &str = "MCERV_DATA_DIR";
+static EVENT_SINK: LazyLock<RwLock<Arc<dyn EventSink>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(ProgressBarSink::default())));
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DirectoryError {
     ServerDirDoesNotExist(PathBuf),
     ModsDirDoesNotExist(PathBuf),
+    ModsDirNotADirectory(PathBuf),
+    /// A symlinked mods directory, which mcerv doesn't change the mods through unless told
+    /// to, and where it links to.
+    ModsDirSymlinked(PathBuf, PathBuf),
+    InvalidServerName(InvalidServerNameError),
 }
 
 impl Display for DirectoryError {
@@ -32,387 +113,4397 @@ impl Display for DirectoryError {
             DirectoryError::ModsDirDoesNotExist(path) => {
                 write!(f, "Mods directory does not exist: {:?}", path)
             }
+            DirectoryError::ModsDirNotADirectory(path) => {
+                write!(
+                    f,
+                    "{:?} should be the mods directory, but isn't a directory",
+                    path
+                )
+            }
+            DirectoryError::ModsDirSymlinked(path, target) => write!(
+                f,
+                "{:?} is a symlink to {:?}, which other servers may share. \
+                 Pass --follow-symlink to change the mods through it",
+                path, target
+            ),
+            DirectoryError::InvalidServerName(e) => write!(f, "{e}"),
         }
     }
 }
 
 impl Error for DirectoryError {}
 
-/// List the directories in the instances directory
-pub fn list_servers() {
-    let instances_dir = instances_dir();
-    let mut entries = std::fs::read_dir(&instances_dir)
-        .expect("Unable to read instances directory")
-        .peekable();
-
-    // If there are no entries, print a message
-    if entries.peek().is_none() {
-        println!("No servers found.");
+impl From<InvalidServerNameError> for DirectoryError {
+    fn from(e: InvalidServerNameError) -> Self {
+        DirectoryError::InvalidServerName(e)
     }
+}
 
-    for entry in entries {
-        let entry = entry.expect("Unable to read entry");
-        if entry.path().is_dir() {
-            println!("{}", entry.file_name().to_string_lossy());
-        }
+/// What [`run_without_command`] fails with after printing the help, so the binary exits like
+/// clap does for a missing command.
+#[derive(Debug)]
+pub struct MissingCommandError;
+
+impl Display for MissingCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No command was given")
     }
 }
 
-/// Lists the installed mods in the target server's mods directory.
-/// Also checks for updates on Modrinth.
-/// If updates are available, prompts the user to confirm updating.
-/// If the instance is vanilla and has no mods directory, displays a message to inform the user.
-pub async fn list_mods(server_name: &str, update_arg: bool, client: &Client) -> anyhow::Result<()> {
-    // Check if the server is vanilla
-    if is_vanilla(server_name)? {
-        println!("{server_name} is a vanilla server and should not have any mods installed.");
-        return Ok(());
-    }
+impl Error for MissingCommandError {}
 
-    // Process mods
-    let mods_dir = try_mods_dir(server_name)?;
+/// Disables all prompts for the rest of the process, for `--non-interactive`.
+pub fn set_non_interactive(non_interactive: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::Relaxed);
+}
 
-    let jar_paths = fs::read_dir(&mods_dir)?
-        .map(|entry| entry.expect("Failed to read entry").path())
-        .filter(|path| path.extension().expect("Failed to get extension") == "jar")
-        .collect::<Vec<_>>();
+/// Whether mcerv may prompt the user.
+pub fn is_interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && std::io::stdin().is_terminal()
+}
 
-    let mut jar_files = jar_paths
-        .iter()
-        .map(fs::File::open)
-        .collect::<Result<Vec<_>, _>>()?;
+/// Answers yes to the safe and destructive prompts for the rest of the process, for `--yes`.
+/// See [`prompt::Danger`].
+pub fn set_assume_yes(assume_yes: bool) {
+    ASSUME_YES.store(assume_yes, Ordering::Relaxed);
+}
 
-    let jar_hashes = jar_files
-        .iter_mut()
-        .map(jar_parser::calculate_hash)
-        .collect::<Result<Vec<_>, _>>()?;
+pub(crate) fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
 
-    let server_info = ServerInfo::new(server_name)?;
-    let game_versions = [server_info.game_version.as_str()];
+/// Lets mcerv change the mods through a symlinked mods directory without asking, for
+/// `--follow-symlink`.
+pub fn set_follow_symlink(follow_symlink: bool) {
+    FOLLOW_SYMLINK.store(follow_symlink, Ordering::Relaxed);
+}
 
-    let (latest_versions_res, old_versions_res) = tokio::join!(
-        modrinth::get_latest_versions(client, &jar_hashes, &game_versions),
-        modrinth::get_versions(client, &jar_hashes)
-    );
+/// Makes [`ServerInfo::cached_or_detect`] always open the jar, for `--no-cache`.
+pub fn set_no_cache(no_cache: bool) {
+    NO_DETECTION_CACHE.store(no_cache, Ordering::Relaxed);
+}
 
-    let latest_versions = latest_versions_res?;
-    let old_versions = old_versions_res?;
+/// Whether the fork and game version detected from the server jar may be cached in its config.
+pub(crate) fn use_detection_cache() -> bool {
+    !NO_DETECTION_CACHE.load(Ordering::Relaxed)
+}
 
-    let slug_map =
-        modrinth::get_project_slug_map(client, old_versions.iter().map(|v| v.project_id.as_str()))
-            .await?;
+/// Makes operations wait for the lock of a server another mcerv process holds, for `--wait`.
+pub fn set_wait_for_lock(wait: bool) {
+    WAIT_FOR_LOCK.store(wait, Ordering::Relaxed);
+}
 
-    let mut available_updates = Vec::new();
+/// Locks the server directory for an operation that changes the server.
+fn lock_server(server_dir: &Path, operation: &str) -> anyhow::Result<InstanceLock> {
+    Ok(InstanceLock::acquire(
+        server_dir,
+        operation,
+        WAIT_FOR_LOCK.load(Ordering::Relaxed),
+    )?)
+}
 
-    for ((latest_version, old_version), jar_path) in latest_versions
-        .into_iter()
-        .zip(old_versions.into_iter())
-        .zip(jar_paths.iter())
-    {
-        let project_slug = slug_map.get(&old_version.project_id).unwrap();
-        print!("{}: `{}` ", project_slug, old_version.version_name);
+/// What long-running operations report about their progress. Serialized for
+/// `--progress-json`, like `{"event":"phase","name":"installing forge"}`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// A step of the operation started.
+    Phase {
+        name: String,
+    },
+    /// `done` of the `total` bytes of `file` are downloaded. The total is unknown when the
+    /// server doesn't send it.
+    DownloadProgress {
+        file: String,
+        done: u64,
+        total: Option<u64>,
+    },
+    DownloadDone {
+        file: String,
+        bytes: u64,
+    },
+    DownloadFailed {
+        file: String,
+        error: String,
+    },
+}
 
-        if latest_version.hash == old_version.hash {
-            println!("[OK] up-to-date");
-        } else {
-            println!("-> `{}`", latest_version.version_name);
-            available_updates.push((jar_path, latest_version));
-        }
+/// Where the [`ProgressEvent`]s go: the progress bars, or the JSON lines of `--progress-json`.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: ProgressEvent);
+}
+
+/// Emits the events as newline-delimited JSON to stderr, for `--progress-json`.
+pub fn set_progress_json(progress_json: bool) {
+    PROGRESS_JSON.store(progress_json, Ordering::Relaxed);
+    if progress_json {
+        set_event_sink(Arc::new(JsonSink::new(std::io::stderr())));
     }
+}
+
+/// Whether the events are emitted as JSON, so nothing else may draw on stderr.
+pub(crate) fn progress_json() -> bool {
+    PROGRESS_JSON.load(Ordering::Relaxed)
+}
 
-    println!("You have {} mods installed.", jar_files.len());
-    println!("You have {} available updates:", available_updates.len());
+pub fn set_event_sink(sink: Arc<dyn EventSink>) {
+    *EVENT_SINK.write().unwrap() = sink;
+}
 
-    if available_updates.is_empty() {
+/// Sends the event to the sink set with [`set_event_sink`], the progress bars by default.
+pub(crate) fn emit(event: ProgressEvent) {
+    let sink = EVENT_SINK.read().unwrap().clone();
+    sink.emit(event);
+}
+
+pub(crate) fn phase(name: &str) {
+    emit(ProgressEvent::Phase {
+        name: name.to_string(),
+    });
+}
+
+/// List the directories in the instances directory, only the ones with the tag if given.
+pub(crate) fn list_servers(
+    detailed: bool,
+    tag: Option<&str>,
+    fork: Option<ServerFork>,
+) -> anyhow::Result<()> {
+    let mut server_names = tagged_server_names(tag)?;
+    if let Some(fork) = fork {
+        // Servers whose fork can't be told aren't of any
+        let manager = api::InstanceManager::new();
+        server_names.retain(|server_name| {
+            manager
+                .get(server_name)
+                .and_then(|instance| instance.fork())
+                .is_ok_and(|f| f == fork)
+        });
+    }
+    if server_names.is_empty() {
+        println!("No servers found.");
         return Ok(());
     }
 
-    let should_update = update_arg
-        || Confirm::new()
-            .with_prompt("Do you want to update the mods?")
-            .interact()?;
-
-    if !should_update {
+    if !detailed {
+        for server_name in server_names {
+            println!("{server_name}");
+        }
         return Ok(());
     }
 
-    println!("Updating mods...");
+    let mut table = Table::new();
+    table.add_row(row![
+        "Server",
+        "Fork",
+        "Version",
+        "Created",
+        "Last Started",
+        "Tags",
+        "Note"
+    ]);
+    // Configs aren't created for servers without one
+    for server_name in server_names {
+        match Config::load(&server_name) {
+            Ok(Some(config)) => table.add_row(row![
+                server_name,
+                config
+                    .detection
+                    .detected_fork
+                    .map_or("-", |fork| fork.name()),
+                config
+                    .detection
+                    .detected_game_version
+                    .as_deref()
+                    .unwrap_or("-"),
+                config::format_time(config.created_at.as_deref()),
+                config::format_time(config.last_started_at.as_deref()),
+                config.tags.join(", "),
+                config.note.as_deref().unwrap_or("")
+            ]),
+            Ok(None) => table.add_row(row![server_name, "-", "-", "-", "-", "", "No config yet"]),
+            Err(e) => table.add_row(row![
+                server_name,
+                "-",
+                "-",
+                "-",
+                "-",
+                "",
+                format!("Invalid config: {e}")
+            ]),
+        };
+    }
+    table.printstd();
 
-    let downloads = available_updates.iter().map(|(_, version)| {
-        let url = version.file_url.clone();
-        let save_path = mods_dir.join(version.file_name.clone());
-        (url, save_path)
-    });
+    Ok(())
+}
 
-    network::download_files(client, downloads).await?;
+/// The names of the servers, only the ones tagged with `tag` if given.
+fn tagged_server_names(tag: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let instances = api::InstanceManager::new().list()?;
+    let names = instances.iter().map(|instance| instance.name().to_string());
+    let Some(tag) = tag else {
+        return Ok(names.collect());
+    };
 
-    // Delete old jar files
-    for (jar_path, _) in &available_updates {
-        if let Err(e) = fs::remove_file(jar_path) {
-            // Do not return error here, because we want to delete the rest.
-            eprintln!("Failed to delete old jar file: {e}");
+    let tag = config::normalize_tag(tag)?;
+    let mut tagged = Vec::new();
+    for server_name in names {
+        if let Ok(Some(config)) = Config::load(&server_name)
+            && config.tags.contains(&tag)
+        {
+            tagged.push(server_name);
         }
     }
 
-    println!("Mods updated successfully.");
+    Ok(tagged)
+}
+
+/// Tags the server, for filtering listings and bulk operations with `--tag`.
+pub(crate) fn add_tag(server_name: &str, tag: &str) -> anyhow::Result<()> {
+    let mut config = Config::load_or_create(server_name)?;
+    if config.add_tag(tag)? {
+        config.save(server_name)?;
+        println!("Tagged {server_name} with {}", config::normalize_tag(tag)?);
+    } else {
+        println!(
+            "{server_name} is already tagged with {}",
+            config::normalize_tag(tag)?
+        );
+    }
 
     Ok(())
 }
 
-pub async fn fetch_mod_versions(
-    project_slug: &str,
-    featured: bool,
-    client: &Client,
-) -> anyhow::Result<()> {
-    let response = modrinth::get_project_versions(client, project_slug, featured).await?;
+pub(crate) fn remove_tag(server_name: &str, tag: &str) -> anyhow::Result<()> {
+    let mut config = Config::load_or_create(server_name)?;
+    if config.remove_tag(tag)? {
+        config.save(server_name)?;
+        println!(
+            "Removed the tag {} from {server_name}",
+            config::normalize_tag(tag)?
+        );
+    } else {
+        println!(
+            "{server_name} isn't tagged with {}",
+            config::normalize_tag(tag)?
+        );
+    }
 
-    println!("{response}");
     Ok(())
 }
 
-pub async fn search_mod(
-    name: &str,
-    facets: &[String],
-    index: Option<SearchIndex>,
-    limit: Option<usize>,
+/// Lists the installed mods in the target server's mods directory.
+/// Also checks for updates on Modrinth.
+/// If updates are available, prompts the user to confirm updating.
+/// If the instance is vanilla and has no mods directory, displays a message to inform the user.
+pub(crate) async fn list_mods(
+    server_name: &str,
+    detailed: bool,
+    sort: ModSort,
+    stale: Option<u64>,
+    notify: bool,
     client: &Client,
 ) -> anyhow::Result<()> {
-    let facets = facets.iter().map(|f| f.as_str()).collect::<Vec<_>>();
-    let response = modrinth::search(client, name, &facets, index, limit).await?;
-    println!("{response}");
+    // Check if the server is vanilla
+    if is_vanilla(server_name)? {
+        println!("{server_name} is a vanilla server and should not have any mods installed.");
+        return Ok(());
+    }
+
+    let summary = check_mods(
+        server_name,
+        detailed,
+        sort,
+        stale,
+        &download_cache(),
+        client,
+    )
+    .await?;
+    if notify {
+        notify_mod_updates(server_name, &summary, client).await;
+    }
+    if summary.failed > 0 {
+        anyhow::bail!("Failed to update {} mods", summary.failed);
+    }
 
     Ok(())
 }
 
-pub fn set_config(
-    server_name: &str,
-    max_mem: Option<String>,
-    min_mem: Option<String>,
-    java_home: Option<String>,
+/// Runs [`list_mods`] for every modded server, or every one with the tag. Downloads are
+/// shared between the servers through the download cache, and a failing server doesn't stop
+/// the others.
+pub(crate) async fn list_all_mods(
+    detailed: bool,
+    sort: ModSort,
+    stale: Option<u64>,
+    tag: Option<&str>,
+    notify: bool,
+    client: &Client,
 ) -> anyhow::Result<()> {
-    let mut config = Config::load_or_create(server_name)?;
+    let cache = download_cache();
 
-    if let Some(max_mem) = max_mem {
-        config.max_memory = max_mem;
-    }
+    let mut table = Table::new();
+    table.add_row(row!["Server", "Updated", "Up-to-date", "Failed"]);
 
-    if let Some(min_mem) = min_mem {
-        config.min_memory = min_mem;
-    }
+    for server_name in tagged_server_names(tag)? {
+        match is_vanilla(&server_name) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("Skipping {server_name}: {e}");
+                table.add_row(row![server_name, "-", "-", e]);
+                continue;
+            }
+        }
 
-    if let Some(java_home) = java_home {
-        config.java_home = Some(java_home.to_string());
+        println!("=== {server_name} ===");
+        match check_mods(&server_name, detailed, sort, stale, &cache, client).await {
+            Ok(summary) => {
+                if notify {
+                    notify_mod_updates(&server_name, &summary, client).await;
+                }
+                table.add_row(row![
+                    server_name,
+                    summary.updated,
+                    summary.up_to_date,
+                    summary.failed
+                ]);
+            }
+            Err(e) => {
+                eprintln!("Failed to check the mods of {server_name}: {e}");
+                table.add_row(row![server_name, "-", "-", e]);
+            }
+        }
     }
 
-    config.save(server_name)?;
-
+    table.printstd();
     Ok(())
 }
 
-pub async fn install(
-    command: InstallCommand,
+/// The mod counts of one server after [`check_mods`].
+#[derive(Default)]
+struct ModsSummary {
+    updated: usize,
+    up_to_date: usize,
+    failed: usize,
+    /// The mods that had an update, as `slug version` lines.
+    available: Vec<String>,
+}
+
+async fn notify_mod_updates(server_name: &str, summary: &ModsSummary, client: &Client) {
+    if summary.available.is_empty() {
+        return;
+    }
+    notifier(Some(client))
+        .notify(&Notification::ModUpdates {
+            server: server_name.to_string(),
+            updates: summary.available.clone(),
+        })
+        .await;
+}
+
+/// Checks the server's mods for updates, see [`update_plan::check_updates`].
+async fn plan_mod_updates(
     server_name: &str,
-    accept_eula: bool,
+    server_info: &ServerInfo,
+    mods_dir: &Path,
     client: &Client,
-) -> anyhow::Result<()> {
-    let eula_agreed = accept_eula || Confirm::new()
-                .with_prompt("Do you agree to Minecraft server EULA? Please ensure you have read and understood the EULA at: https://aka.ms/MinecraftEULA")
-                .interact()
-                .unwrap_or(false);
+) -> anyhow::Result<ModUpdatePlan> {
+    let mods = update_plan::scan_mods(mods_dir, &server_info.game_version)?;
+    let pins = Pins::load(&server_dir(server_name)?)?;
+    let loader = format!("{:?}", server_info.server_fork).to_lowercase();
+    update_plan::check_updates(client, mods, &server_info.game_version, &loader, &pins).await
+}
 
-    let server_dir = server_dir(server_name);
-    fs::create_dir_all(&server_dir)?;
+/// The mods of the server with an update, as `slug version` lines, without changing anything.
+async fn find_mod_updates(server_name: &str, client: &Client) -> anyhow::Result<Vec<String>> {
+    try_mods_dir(server_name)?;
+    let plan = api::InstanceManager::new()
+        .get(server_name)?
+        .mods()
+        .plan(client)
+        .await?;
+    Ok(plan.update_lines())
+}
 
-    if eula_agreed {
-        generate_eula_accept_file(server_name)?;
+/// The jars in the mods directory of the server that don't run on servers. Offline, only
+/// their `fabric.mod.json` is read.
+async fn find_client_only_mods(
+    server_name: &str,
+    offline: bool,
+    client: &Client,
+) -> anyhow::Result<Vec<ClientOnlyMod>> {
+    let mods_dir = try_mods_dir(server_name)?;
+    let server_info = ServerInfo::cached_or_detect(server_name)?;
+    let jar_paths = mods::mod_jars(&mods_dir, &server_info.game_version)?;
+    if offline {
+        return Ok(mods::client_only_mods(
+            &jar_paths,
+            &vec![None; jar_paths.len()],
+        ));
     }
 
-    let start = Instant::now();
-    let filename = install_from_command(server_name, command, client).await?;
-    println!("Download complete. Duration: {:?}", start.elapsed());
+    let jar_hashes = jar_paths
+        .iter()
+        .map(|path| {
+            jar_parser::calculate_hashes(&mut fs::File::open(path)?, &modrinth::LOOKUP_ALGORITHMS)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let versions = modrinth::find_versions(client, &jar_hashes).await?;
+    let mut projects = HashMap::new();
+    if versions.iter().any(Option::is_some) {
+        projects = modrinth::get_projects(
+            client,
+            versions.iter().flatten().map(|v| v.project_id.as_str()),
+        )
+        .await?;
+    }
 
-    let config = Config::new_4gb(filename)?;
-    config.save(server_name)?;
-    println!("Config created and saved");
-    println!("Server added: {server_name}");
-    Ok(())
+    let projects: Vec<_> = versions
+        .iter()
+        .map(|version| {
+            version
+                .as_ref()
+                .and_then(|version| projects.get(&version.project_id).cloned())
+        })
+        .collect();
+    Ok(mods::client_only_mods(&jar_paths, &projects))
 }
 
-pub async fn install_mod(
+/// Tells which mods of the server have a version for the game version, like before updating
+/// the server jar to it.
+pub(crate) async fn check_mods_support(
     server_name: &str,
-    version_id: &str,
+    game_version: &str,
     client: &Client,
 ) -> anyhow::Result<()> {
-    // Check if the server is vanilla
-    if is_vanilla(server_name)? {
-        println!("{server_name} is a vanilla server and should not have any mods installed.");
+    let mods_dir = try_mods_dir(server_name)?;
+    let server_info = ServerInfo::cached_or_detect(server_name)?;
+    let jar_paths = mods::mod_jars(&mods_dir, &server_info.game_version)?;
+    if jar_paths.is_empty() {
+        println!("No mods are installed in {server_name}");
         return Ok(());
     }
 
-    println!("Downloading mod version {version_id}...");
-    let mods_dir = mods_dir(server_name);
-    fs::create_dir_all(&mods_dir)?;
-    let file_name = modrinth::download_version(client, version_id, mods_dir).await?;
-    println!("Mod version downloaded: {file_name}");
-
-    Ok(())
-}
-
-pub fn generate_start_script(server_name: &str) -> anyhow::Result<()> {
-    let start_script = Config::load_or_create(server_name)?.create_start_script();
-
-    let filename = if cfg!(target_os = "windows") {
-        "start_script.bat"
+    let jar_hashes = jar_paths
+        .iter()
+        .map(|path| {
+            jar_parser::calculate_hashes(&mut fs::File::open(path)?, &modrinth::LOOKUP_ALGORITHMS)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let versions = modrinth::find_versions(client, &jar_hashes).await?;
+    let project_ids = versions
+        .iter()
+        .flatten()
+        .map(|version| version.project_id.as_str())
+        .collect::<Vec<_>>();
+    let loader = format!("{:?}", server_info.server_fork).to_lowercase();
+    let support = if project_ids.is_empty() {
+        HashMap::new()
     } else {
-        "start_script.sh"
+        modrinth::projects_support(client, &project_ids, &loader, game_version).await?
     };
 
-    let path = try_server_dir(server_name)?.join(filename);
-    let mut file = fs::File::create(&path)?;
-    file.write_all(start_script.as_bytes())?;
+    let fork = server_info.server_fork;
+    let mut unsupported = 0;
+    for (path, version) in jar_paths.iter().zip(&versions) {
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        let status = match version.as_ref().map(|v| support.get(&v.project_id)) {
+            None => "not on Modrinth, so it can't be checked",
+            Some(None) => "gone from Modrinth, so it can't be checked",
+            Some(Some(true)) => "supported",
+            Some(Some(false)) => {
+                unsupported += 1;
+                "has no version for it"
+            }
+        };
+        println!("{file_name}: {status}");
+    }
 
+    if unsupported == 0 {
+        println!("Every mod on Modrinth has a version for {fork:?} {game_version}");
+    } else {
+        println!("{unsupported} mods have no version for {fork:?} {game_version}");
+    }
     Ok(())
 }
 
-pub fn generate_eula_accept_file(server_name: &str) -> anyhow::Result<()> {
-    let eula_path = try_server_dir(server_name)?.join("eula.txt");
-
-    fs::create_dir_all(eula_path.parent().unwrap())?;
+/// Builds the dependency graph of the server's mods from their Modrinth versions, or the
+/// `fabric.mod.json` of jars Modrinth doesn't know.
+async fn build_mod_graph(server_name: &str, client: &Client) -> anyhow::Result<ModGraph> {
+    let mods_dir = try_mods_dir(server_name)?;
+    let server_info = ServerInfo::cached_or_detect(server_name)?;
+    let jar_paths = mods::mod_jars(&mods_dir, &server_info.game_version)?;
 
-    let content = "# This file is generated by mcerv and is generated because the user agreed to the Minecraft EULA (https://aka.ms/MinecraftEULA).\neula=true";
+    let jar_hashes = jar_paths
+        .iter()
+        .map(|path| {
+            jar_parser::calculate_hashes(&mut fs::File::open(path)?, &modrinth::LOOKUP_ALGORITHMS)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let versions = modrinth::find_versions(client, &jar_hashes).await?;
+    let mut projects = HashMap::new();
+    if versions.iter().any(Option::is_some) {
+        projects = modrinth::get_projects(
+            client,
+            versions.iter().flatten().map(|v| v.project_id.as_str()),
+        )
+        .await?;
+    }
+    // Dependencies with only a version ID point at the project of that version
+    let version_projects: HashMap<&str, &str> = versions
+        .iter()
+        .flatten()
+        .map(|v| (v.id.as_str(), v.project_id.as_str()))
+        .collect();
 
-    fs::write(&eula_path, content)?;
+    let mut nodes = Vec::new();
+    for (path, version) in jar_paths.iter().zip(&versions) {
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        let mod_id = match jar_parser::read_mod_metadata(path) {
+            Ok(metadata) => metadata.map(|metadata| metadata.id),
+            Err(e) => {
+                eprintln!("Warning: failed to read the metadata of {file_name}: {e}");
+                None
+            }
+        };
+        // Joined with the ones Modrinth lists, which can be incomplete
+        let depends = jar_parser::read_depends(path)
+            .unwrap_or_else(|e| {
+                eprintln!("Warning: failed to read the dependencies of {file_name}: {e}");
+                None
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|target| Dependency {
+                target,
+                optional: false,
+            });
 
-    Ok(())
-}
+        let node = match version {
+            Some(version) => {
+                let project = projects.get(&version.project_id);
+                let dependencies = version
+                    .dependencies
+                    .iter()
+                    .filter_map(|dependency| {
+                        let optional = match dependency.dependency_type {
+                            DependencyType::Required => false,
+                            DependencyType::Optional => true,
+                            DependencyType::Incompatible
+                            | DependencyType::Embedded
+                            | DependencyType::Unknown => return None,
+                        };
+                        let target = match (&dependency.project_id, &dependency.version_id) {
+                            (Some(project_id), _) => project_id.clone(),
+                            (None, Some(version_id)) => version_projects
+                                .get(version_id.as_str())
+                                .map_or(version_id.clone(), |id| id.to_string()),
+                            (None, None) => return None,
+                        };
+                        Some(Dependency { target, optional })
+                    })
+                    .chain(depends)
+                    .collect();
+                ModNode {
+                    key: version.project_id.clone(),
+                    name: project.map_or(file_name, |project| project.title.clone()),
+                    mod_id,
+                    path: path.clone(),
+                    library: project
+                        .is_some_and(|project| project.categories.iter().any(|c| c == "library")),
+                    dependencies,
+                }
+            }
+            None => ModNode {
+                key: mod_id.clone().unwrap_or_else(|| file_name.clone()),
+                name: file_name,
+                mod_id,
+                path: path.clone(),
+                library: false,
+                dependencies: depends.collect(),
+            },
+        };
+        nodes.push(node);
+    }
 
-pub fn show_server_info(server_name: &str) -> anyhow::Result<()> {
-    let config = Config::load_or_create(server_name)?;
-    let jar_path = server_dir(server_name).join(&config.jar_name);
-    let server_info = ServerInfo::new(jar_path)?;
-    println!("{config}{server_info}");
-    Ok(())
+    Ok(ModGraph::new(nodes))
 }
 
-pub async fn update_server_jar<I, T>(
-    version_args: I,
+pub(crate) async fn print_mod_graph(
     server_name: &str,
+    dot: bool,
+    dependents: Option<&str>,
     client: &Client,
-) -> anyhow::Result<()>
-where
-    I: IntoIterator<Item = T>,
-    T: Into<OsString> + Clone,
-{
-    let start = Instant::now();
+) -> anyhow::Result<()> {
+    let graph = build_mod_graph(server_name, client).await?;
+    if let Some(key) = dependents {
+        let dependents = graph.dependents(key);
+        if dependents.is_empty() {
+            println!("No mod in {server_name} depends on {key}");
+        }
+        for node in dependents {
+            println!("{}", node.name);
+        }
+    } else if dot {
+        print!("{}", graph.dot());
+    } else if graph.nodes().is_empty() {
+        println!("No mods are installed in {server_name}");
+    } else {
+        print!("{}", graph.tree());
+    }
+    Ok(())
+}
 
-    // Find the old jar name before downloading the new one
-    // to prevent multiple jars existing at once
+/// Offers to remove the libraries no other mod needs anymore.
+pub(crate) async fn prune_mods(server_name: &str, client: &Client) -> anyhow::Result<()> {
     let server_dir = try_server_dir(server_name)?;
-    let mut config = Config::load_or_create(server_name)?;
-    let old_jar_name = config.jar_name;
-    let old_jar_path = server_dir.join(old_jar_name);
-
-    let mut archive = jar_parser::archive(&old_jar_path)?;
-    let fork = forks::detect_server_fork(&mut archive)?;
-
-    // Clap parser needs a dummy program name
-    let iter = version_args.into_iter().map(|v| v.into());
-    let dummy_name = format!(
-        "mcerv {}",
-        Cli::command()
-            .get_matches()
-            .subcommand()
-            .expect("Cannot get subcommand when getting dummy name")
-            .0
-    );
-    let argv = std::iter::once(dummy_name.into()).chain(iter);
-    let command = fork.parse_version_args(argv);
-
-    println!("Installing new server jar...");
-    let filename = install_from_command(server_name, command, client).await?;
-
-    println!("Deleting old server jar...");
-    fs::remove_file(&old_jar_path)?;
+    let _lock = lock_server(&server_dir, "mods prune")?;
+    let graph = build_mod_graph(server_name, client).await?;
+    let orphans = graph.orphans();
+    if orphans.is_empty() {
+        println!("Every library in {server_name} is needed by a mod");
+        return Ok(());
+    }
 
-    println!("Updating config...");
-    config.jar_name = filename;
+    println!("Nothing depends on these libraries anymore:");
+    for orphan in &orphans {
+        println!(
+            "  {} ({})",
+            orphan.name,
+            orphan.path.file_name().unwrap().to_string_lossy()
+        );
+    }
+    let should_remove = prompt::confirm(
+        Danger::Destructive,
+        &format!("Do you want to remove {} jars?", orphans.len()),
+    )?;
+    if !should_remove {
+        if !is_interactive() {
+            println!("Pass --yes to remove them.");
+        }
+        return Ok(());
+    }
 
-    config.save(server_name)?;
+    confirm_mods_dir_change(&mods_dir(server_name)?)?;
+    let trash = TrashBatch::begin(&server_dir, "mods prune");
+    let mut event = history::Event::new("mods prune");
+    for orphan in orphans {
+        trash.trash(&orphan.path)?;
+        forget_mod_removal(server_name, &orphan.path)?;
+        event.mods.push(ModChange {
+            old: Some(mods::file_name(&orphan.path)),
+            ..Default::default()
+        });
+    }
+    record_history(&server_dir, &event);
+    finish_trash(server_name, &trash)
+}
 
-    println!("Update complete in {:?}", start.elapsed());
+/// Downloads the server mappings of the server's game version.
+pub(crate) async fn fetch_mappings(server_name: &str, client: &Client) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let mut config = Config::load_or_create(server_name)?;
+    let info = ServerInfo::from_config(server_name, &mut config, false)?;
 
+    println!("Downloading server mappings of {}...", info.game_version);
+    let (path, stats) =
+        vanilla_meta::download_mappings(client, &info.game_version, &server_dir).await?;
+    println!("Downloaded {stats} to {}", path.display());
     Ok(())
 }
 
-pub fn try_mods_dir(server_name: &str) -> Result<PathBuf, DirectoryError> {
-    let dir = mods_dir(server_name);
+/// Compares the server jar and the mods with the hashes mcerv recorded, and with `online`,
+/// the mods with the hashes Modrinth has for their versions. With `repair`, the modified and
+/// missing mods are downloaded again. Fails with a [`VerifyError`] if anything is left that
+/// doesn't match.
+pub(crate) async fn verify_server(
+    server_name: &str,
+    online: bool,
+    repair: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "verify")?;
 
-    if !dir.exists() {
-        return Err(DirectoryError::ModsDirDoesNotExist(dir));
+    // Before detecting the server info, which caches the hash of a changed jar
+    let config = Config::load_or_create(server_name)?;
+    let mut discrepancies = Vec::new();
+    match &config.detection.jar_sha1 {
+        Some(sha1) => discrepancies.extend(verify::compare_server_jar(
+            &server_dir.join(&config.jar_name),
+            sha1,
+        )?),
+        None => println!(
+            "No hash of {} is recorded yet, so it isn't checked",
+            config.jar_name
+        ),
     }
 
-    Ok(dir)
-}
+    let server_info = last_known_server_info(server_name)?;
+    let mods_dir = prepare_mods_dir(&server_dir, server_info.server_fork)?;
+    if let Some(mods_dir) = &mods_dir {
+        let lock = ModLock::load(&server_dir)?;
+        let mut expected = verify::expected_mods(&lock);
+        if online {
+            let version_ids = lock
+                .mods
+                .values()
+                .map(|locked| locked.version_id.as_str())
+                .collect::<Vec<_>>();
+            // Modrinth's hashes win over the lockfile's, which could be edited too
+            for version in modrinth::get_versions_by_id(client, &version_ids).await? {
+                for file in version.install_files() {
+                    expected.insert(
+                        file.file_name.clone(),
+                        ExpectedMod {
+                            sha512: file.sha512.clone(),
+                            version_id: version.id.clone(),
+                        },
+                    );
+                }
+            }
+        } else {
+            let unrecorded = lock
+                .mods
+                .values()
+                .filter(|locked| locked.files.is_empty())
+                .count();
+            if unrecorded > 0 {
+                println!(
+                    "{unrecorded} mods were installed before mcerv recorded their hashes, check them with --online"
+                );
+            }
+        }
 
-pub fn try_server_dir(server_name: &str) -> Result<PathBuf, DirectoryError> {
-    let dir = server_dir(server_name);
+        let jars = mods::mod_jars(mods_dir, &server_info.game_version)?;
+        discrepancies.extend(verify::compare_mods(mods_dir, &expected, &jars)?);
+    }
 
-    if !dir.exists() {
-        return Err(DirectoryError::ServerDirDoesNotExist(dir));
+    if discrepancies.is_empty() {
+        println!("Every file of {server_name} matches what mcerv recorded");
+        return Ok(());
+    }
+    for discrepancy in &discrepancies {
+        println!("{discrepancy}");
     }
 
-    Ok(dir)
-}
+    if let (true, Some(mods_dir)) = (repair, &mods_dir) {
+        confirm_mods_dir_change(mods_dir)?;
+        let trash = TrashBatch::begin(&server_dir, "verify --repair");
+        let mut repaired = Vec::new();
+        let mut disabled = Vec::new();
+        for discrepancy in &discrepancies {
+            let Some(version_id) = &discrepancy.version_id else {
+                continue;
+            };
+            if discrepancy.problem == Problem::Modified {
+                trash.trash(&discrepancy.path)?;
+                if discrepancy.path.extension() == Some(mods::DISABLED_EXTENSION.as_ref()) {
+                    // The download is enabled, so it's disabled again below
+                    disabled.push(discrepancy.path.with_extension(""));
+                }
+            }
+            if !repaired.contains(version_id) {
+                println!("Downloading mod version {version_id} again...");
+                let version =
+                    modrinth::download_version(client, version_id, mods_dir, &download_cache())
+                        .await?;
+                record_mod_change(server_name, &version)?;
+                repaired.push(version_id.clone());
+            }
+        }
+        for jar in &disabled {
+            mods::disable(jar)?;
+        }
+        finish_trash(server_name, &trash)?;
+        discrepancies.retain(|discrepancy| {
+            discrepancy
+                .version_id
+                .as_ref()
+                .is_none_or(|version_id| !repaired.contains(version_id))
+        });
+    }
 
-pub fn mods_dir(server_name: &str) -> PathBuf {
-    server_dir(server_name).join("mods")
+    if discrepancies.is_empty() {
+        return Ok(());
+    }
+    if discrepancies
+        .iter()
+        .any(|discrepancy| discrepancy.path == server_dir.join(&config.jar_name))
+    {
+        println!("Run `mcerv update-server-jar {server_name}` to download the server jar again.");
+    }
+    Err(VerifyError {
+        discrepancies: discrepancies.len(),
+    }
+    .into())
 }
 
-pub fn server_dir(server_name: &str) -> PathBuf {
-    instances_dir().join(server_name)
-}
+/// Flags the client-only mods of the server, asking whether to disable or remove each.
+pub(crate) async fn check_client_only(server_name: &str, client: &Client) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "check client-only")?;
+    let client_only = find_client_only_mods(server_name, false, client).await?;
+    if client_only.is_empty() {
+        println!("No client-only mods found in {server_name}");
+        return Ok(());
+    }
+
+    confirm_mods_dir_change(&mods_dir(server_name)?)?;
+    let trash = TrashBatch::begin(&server_dir, "check client-only");
+    for found in &client_only {
+        let file_name = found.path.file_name().unwrap().to_string_lossy();
+        println!(
+            "{} ({file_name}) is client-only, {}. It may crash the server",
+            found.name, found.source
+        );
+        if !is_interactive() {
+            continue;
+        }
+
+        let choice = Select::new()
+            .with_prompt(format!("What do you want to do with {file_name}?"))
+            .items([
+                "Keep it",
+                "Disable it, renaming it to end with .disabled",
+                "Remove it",
+            ])
+            .default(1)
+            .interact()?;
+        match choice {
+            1 => {
+                let disabled = mods::disable(&found.path)?;
+                println!("Disabled {file_name} as {}", disabled.display());
+            }
+            2 => {
+                trash.trash(&found.path)?;
+                forget_mod_removal(server_name, &found.path)?;
+            }
+            _ => {}
+        }
+    }
+
+    finish_trash(server_name, &trash)
+}
+
+async fn check_mods(
+    server_name: &str,
+    detailed: bool,
+    sort: ModSort,
+    stale: Option<u64>,
+    cache: &DownloadCache,
+    client: &Client,
+) -> anyhow::Result<ModsSummary> {
+    // Process mods
+    let server_info = last_known_server_info(server_name)?;
+    let Some(mods_dir) = prepare_mods_dir(&try_server_dir(server_name)?, server_info.server_fork)?
+    else {
+        anyhow::bail!("{server_name} is a vanilla server and should not have any mods installed");
+    };
+    let _lock = lock_server(&server_dir(server_name)?, "ls-mods")?;
+    print_mods_dir_symlink_notice(&mods_dir);
+    let plan = api::InstanceManager::new()
+        .get(server_name)?
+        .mods()
+        .plan(client)
+        .await?;
+
+    let trash = Arc::new(TrashBatch::begin(&server_dir(server_name)?, "ls-mods"));
+    let mut removed = Vec::new();
+    for duplicate in mods::duplicate_installs(&plan.installed_jars())? {
+        let project_slug = modrinth::project_slug(&plan.projects, &duplicate.project_id);
+        let older = duplicate
+            .older
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "Warning: {project_slug} is installed more than once. The newest is {}, older: {older}",
+            duplicate.keep.file_name().unwrap().to_string_lossy()
+        );
+
+        let should_remove = prompt::confirm(
+            Danger::Destructive,
+            &format!("Do you want to remove {older}?"),
+        )?;
+        if should_remove {
+            confirm_mods_dir_change(&mods_dir)?;
+            for path in duplicate.older {
+                trash.trash(&path)?;
+                removed.push(path);
+            }
+        }
+    }
+
+    let mut summary = ModsSummary::default();
+    let mut rows = Vec::new();
+    let mod_lock = ModLock::load(&server_dir(server_name)?)?;
+    let now = chrono::Local::now();
+
+    let planned_mods = plan
+        .mods
+        .iter()
+        .filter(|planned| !removed.contains(&planned.installed.path))
+        .collect::<Vec<_>>();
+    for planned in &planned_mods {
+        match &planned.status {
+            ModStatus::UpToDate => summary.up_to_date += 1,
+            ModStatus::UpdateAvailable(version_name) => {
+                summary
+                    .available
+                    .push(format!("{} {version_name}", planned.slug));
+            }
+            ModStatus::NotUpdatable(_) | ModStatus::Unknown => {}
+        }
+
+        let age = planned
+            .version
+            .as_ref()
+            .and_then(|version| mod_lock.installed(&version.project_id, &version.id))
+            .and_then(|locked| locked.age(now));
+        rows.push(mods::ModRow {
+            slug: planned.slug.clone(),
+            version_name: planned.version.as_ref().map(|v| v.version_name.clone()),
+            version_id: planned.version.as_ref().map(|v| v.id.clone()),
+            status: planned.status.clone(),
+            constraint: planned
+                .constraint
+                .as_ref()
+                .map(|constraint| constraint.to_string()),
+            age,
+            file_name: planned.installed.file_name(),
+            size: planned.installed.size,
+            sha1: planned.installed.sha1().unwrap_or_default().to_string(),
+        });
+    }
+
+    let installed = rows.len();
+    if let Some(days) = stale {
+        rows = mods::stale_rows(rows, days);
+        println!(
+            "{} mods installed over {days} days ago have an update.",
+            rows.len()
+        );
+    }
+    // Only the listed mods are offered to update
+    let available_updates = planned_mods
+        .iter()
+        .filter_map(|planned| ModUpdate::of(planned))
+        .filter(|update| {
+            rows.iter()
+                .any(|row| update.jar_path.file_name() == Some(row.file_name.as_ref()))
+        })
+        .collect::<Vec<_>>();
+
+    mods::sort_rows(&mut rows, sort);
+    for (heading, rows) in mods::sections(&rows) {
+        println!("{heading}:");
+        if detailed {
+            mods::detailed_table(&rows).printstd();
+        } else {
+            for mod_row in rows {
+                println!("  {}", mod_row.line());
+            }
+        }
+    }
+
+    println!("You have {installed} mods installed.");
+
+    if available_updates.is_empty() {
+        finish_trash(server_name, &trash)?;
+        return Ok(summary);
+    }
+
+    let should_update = prompt::confirm(Danger::Destructive, "Do you want to update the mods?")?;
+
+    if !should_update {
+        finish_trash(server_name, &trash)?;
+        return Ok(summary);
+    }
+    confirm_mods_dir_change(&mods_dir)?;
+
+    let options = ApplyOptions {
+        mods_dir: &mods_dir,
+        cache,
+        trash: &trash,
+    };
+    let (updated, failed) =
+        apply_mod_updates(server_name, available_updates, &options, client).await?;
+    summary.updated = updated;
+    summary.failed = failed;
+
+    println!("Updated {} mods.", summary.updated);
+    finish_trash(server_name, &trash)?;
+    if cancel::token().is_cancelled() {
+        anyhow::bail!(CancelledError);
+    }
+
+    Ok(summary)
+}
+
+/// Where [`apply_mod_updates`] puts the new versions and the replaced jars.
+struct ApplyOptions<'a> {
+    mods_dir: &'a Path,
+    cache: &'a DownloadCache,
+    trash: &'a Arc<TrashBatch>,
+}
+
+/// A jar to replace with the files of a new version.
+struct ModUpdate {
+    jar_path: PathBuf,
+    /// The other files of the installed version, which go with the jar.
+    old_files: Vec<PathBuf>,
+    version: ModVersion,
+}
+
+impl ModUpdate {
+    fn of(planned: &PlannedMod) -> Option<Self> {
+        let jar_path = planned.installed.path.clone();
+        let old_files = planned
+            .version
+            .iter()
+            .flat_map(|old| old.install_files().into_iter().skip(1))
+            .map(|file| jar_path.with_file_name(&file.file_name))
+            .filter(|path| *path != jar_path)
+            .collect();
+        Some(Self {
+            old_files,
+            version: planned.update()?.clone(),
+            jar_path,
+        })
+    }
+}
+
+/// Replaces each jar with its new version, all at the same time, recording the ones that
+/// succeeded. A failed update doesn't stop the others. Returns how many were updated and
+/// how many failed.
+async fn apply_mod_updates(
+    server_name: &str,
+    updates: Vec<ModUpdate>,
+    options: &ApplyOptions<'_>,
+    client: &Client,
+) -> anyhow::Result<(usize, usize)> {
+    println!("Updating mods...");
+    phase("updating mods");
+
+    // Each update finishes or rolls back on Ctrl-C
+    let _scope = cancel::scope();
+    let mut join_set = JoinSet::new();
+    for update in updates {
+        let client = client.clone();
+        let mods_dir = options.mods_dir.to_path_buf();
+        let cache = options.cache.clone();
+        let trash = options.trash.clone();
+        join_set.spawn(async move {
+            let result = update_mod(&client, &mods_dir, &update, &cache, &trash).await;
+            (update.jar_path, update.version, result)
+        });
+    }
+
+    let (mut updated, mut failed) = (0, 0);
+    let mut event = history::Event::new("mods update");
+    // Drained to the end, since returning would cancel the updates still running
+    while let Some(result) = join_set.join_next().await {
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Failed to update a mod: {e}");
+                failed += 1;
+                continue;
+            }
+        };
+        match result {
+            (jar_path, version, Ok(())) => {
+                // One at a time, so the tasks don't overwrite each other's changes
+                let old_version_id = record_mod_change(server_name, &version).unwrap_or_else(|e| {
+                    eprintln!(
+                        "Warning: Failed to record {} in the lockfile: {e:#}",
+                        version.primary_file().file_name
+                    );
+                    None
+                });
+                event.mods.push(ModChange {
+                    old: Some(mods::file_name(&jar_path)),
+                    new: Some(version.primary_file().file_name.clone()),
+                    project_id: Some(version.project_id.clone()),
+                    old_version_id,
+                    new_version_id: Some(version.id.clone()),
+                });
+                updated += 1;
+            }
+            (_, version, Err(e)) => {
+                // Do not return error here, because we want to update the rest.
+                eprintln!(
+                    "Failed to update to {}: {e}",
+                    version.primary_file().file_name
+                );
+                failed += 1;
+            }
+        }
+    }
+    if !event.mods.is_empty() {
+        event.mods.sort_by(|a, b| a.old.cmp(&b.old));
+        event.trash_batch = Some(options.trash.name());
+        record_history(&server_dir(server_name)?, &event);
+    }
+
+    Ok((updated, failed))
+}
+
+/// Replaces the jar and the other files of its version with the files of the new version,
+/// taken from the cache if they're there. The old files go to the trash, and are put back if
+/// a download fails.
+async fn update_mod(
+    client: &Client,
+    mods_dir: &Path,
+    update: &ModUpdate,
+    cache: &DownloadCache,
+    trash: &TrashBatch,
+) -> anyhow::Result<()> {
+    let ModUpdate {
+        jar_path,
+        old_files,
+        version,
+    } = update;
+    let old_paths: Vec<&PathBuf> = std::iter::once(jar_path).chain(old_files).collect();
+    let mut save_paths = Vec::new();
+    let mut downloads = Vec::new();
+    for file in version.install_files() {
+        let save_path = mods_dir.join(&file.file_name);
+
+        // Left behind by an update that was interrupted before deleting the old jar
+        if mods::is_already_downloaded(mods_dir, &file.file_name, &file.hash)? {
+            println!("{} is already downloaded.", file.file_name);
+        } else {
+            // It would be overwritten by the new version
+            if old_paths.contains(&&save_path) && save_path.exists() {
+                trash.trash(&save_path)?;
+            }
+            downloads.push((file.url.as_str(), file.sha512.as_str(), save_path.clone()));
+        }
+        save_paths.push(save_path);
+    }
+
+    let cached = match network::download_files(client, &downloads, cache).await {
+        Ok(cached) => cached,
+        Err(e) => {
+            // Back to the old version, instead of stopping halfway through the swap
+            for (_, _, save_path) in &downloads {
+                match fs::remove_file(save_path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            for old_path in &old_paths {
+                trash.restore(old_path)?;
+            }
+            return Err(e);
+        }
+    };
+    for ((_, _, save_path), cached) in downloads.iter().zip(cached) {
+        if cached {
+            println!(
+                "Using {} from the cache.",
+                save_path.file_name().unwrap().to_string_lossy()
+            );
+        }
+        trash.record_added(save_path)?;
+    }
+    // Undoing removes the leftovers too, except a file the old version has as well
+    for save_path in &save_paths {
+        let downloaded = downloads.iter().any(|(_, _, path)| path == save_path);
+        if !downloaded && !old_paths.contains(&save_path) {
+            trash.record_added(save_path)?;
+        }
+    }
+
+    for old_path in old_paths {
+        if !save_paths.contains(old_path) && old_path.exists() {
+            trash.trash(old_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Purges old trash batches, and tells how to undo the batch if it trashed anything.
+fn finish_trash(server_name: &str, batch: &TrashBatch) -> anyhow::Result<()> {
+    trash::prune(
+        &server_dir(server_name)?,
+        trash::MAX_BATCHES,
+        trash::MAX_SIZE,
+    )?;
+    if !batch.is_empty() {
+        println!(
+            "The replaced jars were moved to the trash. Run `mcerv undo {server_name}` to restore them."
+        );
+    }
+    Ok(())
+}
+
+pub(crate) async fn fetch_mod_versions(
+    project_slug: &str,
+    featured: bool,
+    limit: usize,
+    urls: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let response = modrinth::get_project_versions(client, project_slug, featured).await?;
+
+    let urls_of = urls.then_some(project_slug);
+    response
+        .table(limit, chrono::Local::now(), urls_of)
+        .printstd();
+    if response.0.len() > limit {
+        println!(
+            "Showing the newest {limit} of {} versions. Pass --limit to show more.",
+            response.0.len()
+        );
+    }
+    Ok(())
+}
+
+pub(crate) async fn search_mod(
+    name: &str,
+    facets: &[Vec<String>],
+    client_ok: bool,
+    index: Option<SearchIndex>,
+    limit: Option<usize>,
+    urls: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let mut response = modrinth::search(client, name, facets, client_ok, index, limit).await?;
+    response.urls = urls;
+    println!(
+        "{} for '{name}' (facets: {})",
+        results(response.total_hits),
+        modrinth::facets_summary(facets, client_ok)
+    );
+
+    if response.total_hits > 0 {
+        println!("{response}");
+        return Ok(());
+    }
+
+    // Tell whether the query or the filters are the problem
+    if !client_ok || !facets.is_empty() {
+        let unfiltered = modrinth::search(client, name, &[], true, None, Some(1)).await?;
+        if unfiltered.total_hits > 0 {
+            println!(
+                "0 results with filters; {} without filters. Try --client-ok or drop the facets",
+                results(unfiltered.total_hits)
+            );
+            return Ok(());
+        }
+    }
+    println!("Check the spelling of '{name}'");
+
+    Ok(())
+}
+
+/// Opens the Modrinth page of the project with the slug, or of the server's installed mod
+/// with the name, in the default browser. The link is printed too, for when there's none.
+pub(crate) async fn open_project(
+    name: &str,
+    server_name: Option<&str>,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let project = match server_name {
+        Some(server_name) => installed_project(server_name, name, client).await?,
+        None => modrinth::get_projects(client, [name])
+            .await?
+            .into_values()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No Modrinth project has the slug {name}"))?,
+    };
+
+    let url = modrinth::project_url(&project.slug);
+    println!("{}: {url}", project.title);
+    if is_remote_session() {
+        return Ok(());
+    }
+    if let Err(e) = opener::open_browser(&url) {
+        eprintln!("Couldn't open a browser: {e}");
+    }
+    Ok(())
+}
+
+/// The Modrinth project of the server's installed mod, resolved from its jar's hash like in
+/// `ls-mods`.
+async fn installed_project(
+    server_name: &str,
+    name: &str,
+    client: &Client,
+) -> anyhow::Result<modrinth::Project> {
+    let mods_dir = try_mods_dir(server_name)?;
+    let server_info = ServerInfo::cached_or_detect(server_name)?;
+    let plan = plan_mod_updates(server_name, &server_info, &mods_dir, client).await?;
+
+    let jar_paths: Vec<_> = plan
+        .mods
+        .iter()
+        .map(|planned| planned.installed.path.clone())
+        .collect();
+    let projects: Vec<_> = plan
+        .mods
+        .iter()
+        .map(|planned| {
+            planned
+                .version
+                .as_ref()
+                .and_then(|version| plan.projects.get(&version.project_id).cloned())
+        })
+        .collect();
+    let mod_ids: Vec<_> = jar_paths
+        .iter()
+        .map(|path| {
+            jar_parser::read_mod_metadata(path)
+                .ok()
+                .flatten()
+                .map(|metadata| metadata.id)
+        })
+        .collect();
+    mods::find_installed_project(name, &jar_paths, &mod_ids, &projects)
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("No mod of {server_name} on Modrinth is named {name}"))
+}
+
+/// Over SSH, or on Linux without a display, there's no browser to open links in.
+fn is_remote_session() -> bool {
+    let set = |var| std::env::var_os(var).is_some();
+    set("SSH_CONNECTION")
+        || set("SSH_TTY")
+        || (cfg!(target_os = "linux") && !set("DISPLAY") && !set("WAYLAND_DISPLAY"))
+}
+
+fn results(count: u64) -> String {
+    if count == 1 {
+        "1 result".to_string()
+    } else {
+        format!("{count} results")
+    }
+}
+
+pub(crate) fn set_config(
+    server_name: &str,
+    max_mem: Option<String>,
+    min_mem: Option<String>,
+    java_home: Option<String>,
+    note: Option<String>,
+    start_template: Option<String>,
+) -> anyhow::Result<()> {
+    let mut config = Config::load_or_create(server_name)?;
+
+    if let Some(max_mem) = max_mem {
+        config.max_memory = max_mem;
+    }
+
+    if let Some(min_mem) = min_mem {
+        config.min_memory = min_mem;
+    }
+
+    if let Some(java_home) = java_home {
+        config.java_home = Some(java_home.to_string());
+    }
+
+    // An empty note clears it
+    if let Some(note) = note {
+        config.note = Some(note).filter(|note| !note.is_empty());
+    }
+
+    // Checked before anything is saved, and an empty one restores the default
+    if let Some(template) = start_template {
+        config.start_command_template = match template.trim() {
+            "" => None,
+            template => Some(StartTemplate::parse(template)?),
+        };
+    }
+
+    config.save(server_name)?;
+
+    Ok(())
+}
+
+/// Moves the world of the server to `world_dir`, linking it from the server directory, or
+/// back into the server directory if `world_dir` is empty. With `dry_run`, only prints
+/// what would be done.
+pub(crate) fn set_world_dir(
+    server_name: &str,
+    world_dir: &str,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "set --world-dir")?;
+    if let Some(status) = process::running_process(&server_dir)? {
+        return Err(ServerProcessError::AlreadyRunning(status.pid).into());
+    }
+    let mut config = Config::load_or_create(server_name)?;
+    let target = Some(Path::new(world_dir)).filter(|dir| !dir.as_os_str().is_empty());
+    let plan = world::WorldMove::plan(&server_dir, target)?;
+
+    if dry_run {
+        println!("{plan}");
+        println!("Nothing was changed, since this is a dry run");
+        return Ok(());
+    }
+    plan.apply()?;
+    let mut properties = ServerProperties::load(&server_dir)?;
+    properties.set("level-name", &plan.level_name);
+    properties.save()?;
+    config.world_dir = target.map(Path::to_path_buf);
+    config.save(server_name)?;
+
+    match target {
+        Some(target) => println!("The world of {server_name} is now at {}", target.display()),
+        None => println!("The world of {server_name} is back in its directory"),
+    }
+    Ok(())
+}
+
+/// Regenerates the server's config from its jar, keeping the fields of the old one that
+/// still parse.
+pub(crate) fn repair_config(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "config repair")?;
+    let (config, repair) = Config::repair(server_name)?;
+
+    if let Some(backup) = &repair.backup {
+        println!("Moved the old config to {}", backup.display());
+    }
+    if repair.preserved.is_empty() {
+        println!("None of its fields could be kept");
+    } else {
+        println!("Kept {}", repair.preserved.join(", "));
+    }
+    let detected = match (
+        config.detection.detected_fork,
+        &config.detection.detected_game_version,
+    ) {
+        (Some(fork), Some(game_version)) => format!(" ({fork:?} {game_version})"),
+        _ => String::new(),
+    };
+    println!(
+        "Repaired the config of {server_name}, starting {}{detected}",
+        config.jar_name
+    );
+    Ok(())
+}
+
+/// Pins the mod to versions in the constraint for `ls-mods` updates, or with `None`, unpins it.
+/// A range below the installed version is only pinned when confirmed, since updating within
+/// it would downgrade the mod.
+pub(crate) async fn pin_mod(
+    server_name: &str,
+    slug: &str,
+    constraint: Option<&str>,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "pin-mod")?;
+    let mut pins = Pins::load(&server_dir)?;
+
+    match constraint {
+        Some(constraint) => {
+            if !modrinth::is_valid_slug(slug) {
+                anyhow::bail!("{slug:?} isn't a Modrinth project slug");
+            }
+            let constraint: VersionConstraint = constraint.parse()?;
+            match installed_version(server_name, slug, client).await {
+                Ok(Some(version)) if constraint.is_below(&version.version_number) => {
+                    let downgrade = prompt::confirm(
+                        Danger::Destructive,
+                        &format!(
+                            "{constraint} is below the installed {} of {slug}, so updating would downgrade it. Pin it anyway?",
+                            version.version_number
+                        ),
+                    )?;
+                    if !downgrade {
+                        if !is_interactive() {
+                            println!("Pass --yes to pin it.");
+                        }
+                        return Ok(());
+                    }
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    anyhow::bail!("No mod of {server_name} on Modrinth has the slug {slug}")
+                }
+                Err(e) => {
+                    eprintln!("Warning: Failed to check the installed version of {slug}: {e:#}")
+                }
+            }
+            println!("Pinned {slug} to {constraint}");
+            pins.projects.insert(slug.to_string(), Pin { constraint });
+        }
+        None => {
+            if pins.projects.remove(slug).is_none() {
+                anyhow::bail!("{slug} isn't pinned in {server_name}");
+            }
+            println!("Unpinned {slug}");
+        }
+    }
+
+    pins.save(&server_dir)
+}
+
+/// The installed version of the server's mod with the slug, `None` if none is installed.
+async fn installed_version(
+    server_name: &str,
+    slug: &str,
+    client: &Client,
+) -> anyhow::Result<Option<ModVersion>> {
+    let mods_dir = try_mods_dir(server_name)?;
+    let server_info = ServerInfo::cached_or_detect(server_name)?;
+    let plan = plan_mod_updates(server_name, &server_info, &mods_dir, client).await?;
+    Ok(plan
+        .mods
+        .into_iter()
+        .find(|planned| planned.slug == slug)
+        .and_then(|planned| planned.version))
+}
+
+/// Registers `location` as the directory of the new server, which must not exist yet under
+/// either name.
+pub(crate) fn register_location(server_name: &str, location: &Path) -> anyhow::Result<()> {
+    server_name::validate(server_name)?;
+    let location = std::path::absolute(location)?;
+    let mut registry = Registry::load(&registry_path())?;
+    if let Some(dir) = registry.servers.get(server_name) {
+        anyhow::bail!("{server_name} is already installed in {}", dir.display());
+    }
+    let default_dir = instances_dir().join(server_name);
+    if default_dir.exists() {
+        anyhow::bail!(
+            "{server_name} is already installed in {}",
+            default_dir.display()
+        );
+    }
+    if location.exists() && fs::read_dir(&location)?.next().is_some() {
+        anyhow::bail!("{} already exists and isn't empty", location.display());
+    }
+
+    registry.register(server_name, location);
+    registry.save(&registry_path())
+}
+
+/// Installs the servers of the manifest, a few at a time. A server that fails is removed
+/// again, without affecting the others.
+pub(crate) async fn install_many(manifest_path: &Path, client: &Client) -> anyhow::Result<()> {
+    let manifest = InstallManifest::load(manifest_path)?;
+
+    let eula_agreed = prompt::confirm(Danger::Destructive, &format!("Do you agree to Minecraft server EULA for all {} servers? Please ensure you have read and understood the EULA at: {}", manifest.servers.len(), eula::EULA_URL)).unwrap_or(false);
+
+    // The servers being installed are removed again on Ctrl-C
+    let _scope = cancel::scope();
+    let cache = download_cache();
+    let (cache, mods) = (&cache, &manifest.mods);
+    let results = futures::stream::iter(&manifest.servers)
+        .map(|server| async move {
+            let result = install_manifest_server(server, mods, eula_agreed, cache, client).await;
+            (server, result)
+        })
+        .buffered(manifest.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut table = Table::new();
+    table.add_row(row!["Server", "Fork", "Port", "Result"]);
+    let mut failed = 0;
+    for (server, result) in results {
+        let port = server.port.map_or("-".to_string(), |port| port.to_string());
+        let status = match result {
+            Ok(()) => "installed".to_string(),
+            Err(e) => {
+                failed += 1;
+                format!("failed: {e}")
+            }
+        };
+        table.add_row(row![server.name, server.fork, port, status]);
+    }
+    table.printstd();
+
+    if cancel::token().is_cancelled() {
+        anyhow::bail!(CancelledError);
+    }
+    if failed > 0 {
+        anyhow::bail!(
+            "{failed} of {} servers failed to install",
+            manifest.servers.len()
+        );
+    }
+
+    Ok(())
+}
+
+async fn install_manifest_server(
+    server: &ManifestServer,
+    shared_mods: &[String],
+    eula_agreed: bool,
+    cache: &DownloadCache,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let command = server.install_command()?;
+
+    let server_dir = server_dir(&server.name)?;
+    if server_dir.exists() {
+        anyhow::bail!("{server_dir:?} already exists");
+    }
+    fs::create_dir_all(&server_dir)?;
+    let lock = lock_server(&server_dir, "install-many")?;
+
+    let result = async {
+        if eula_agreed {
+            eula::accept(&server_dir)?;
+        }
+
+        let installer = command.installer()?;
+        let versions = installer.resolve(client).await?;
+        let (filename, _) = versions.install(&server.name, client).await?;
+        let mut config = Config::new_4gb(filename)?;
+        config.start_command_template = forks::forge_start_template(&server_dir, &config.jar_name);
+        if let Some(memory) = &server.memory {
+            config.min_memory = memory.clone();
+            config.max_memory = memory.clone();
+        }
+        config.save(&server.name)?;
+
+        if let Some(port) = server.port {
+            let mut properties = ServerProperties::load(&server_dir)?;
+            properties.set("server-port", &port.to_string());
+            properties.save()?;
+        }
+
+        let mut event = history::Event::new("install-many");
+        event.new_versions = history::jar_versions(&server_dir, &config.jar_name, installer.fork());
+        let mods_dir = mods_dir(&server.name)?;
+        for mod_id in server.mod_ids(shared_mods) {
+            fs::create_dir_all(&mods_dir)?;
+            let version = modrinth::download_version(client, mod_id, &mods_dir, cache).await?;
+            record_mod_change(&server.name, &version)?;
+            event.mods.extend(added_mods(&version));
+        }
+        record_history(&server_dir, &event);
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    // Leave nothing half installed, so the entry can be retried
+    drop(lock);
+    if result.is_err() {
+        fs::remove_dir_all(&server_dir)?;
+    }
+    result
+}
+
+pub(crate) async fn install_mod(
+    server_name: &str,
+    version_id: &str,
+    client: &Client,
+) -> anyhow::Result<()> {
+    // Check if the server is vanilla
+    if is_vanilla(server_name)? {
+        println!("{server_name} is a vanilla server and should not have any mods installed.");
+        return Ok(());
+    }
+
+    println!("Downloading mod version {version_id}...");
+    let mods_dir = mods_dir(server_name)?;
+    confirm_mods_dir_change(&mods_dir)?;
+    fs::create_dir_all(&mods_dir)?;
+    let _lock = lock_server(&server_dir(server_name)?, "install-mod")?;
+    let version =
+        modrinth::download_version(client, version_id, mods_dir, &download_cache()).await?;
+    record_mod_change(server_name, &version)?;
+    let mut event = history::Event::new("install-mod");
+    event.mods.extend(added_mods(&version));
+    record_history(&server_dir(server_name)?, &event);
+    println!("Mod version downloaded: {}", version.install_file_names());
+
+    Ok(())
+}
+
+/// Records the installed or replaced mod in the server's [`ModLock`]. Everything installing
+/// a mod goes through this, so the timestamps match the jars. Returns the ID of the version
+/// it replaced, if mcerv recorded one.
+fn record_mod_change(server_name: &str, version: &ModVersion) -> anyhow::Result<Option<String>> {
+    record_mod_change_in(&server_dir(server_name)?, version)
+}
+
+/// [`record_mod_change`] for the server in `server_dir`.
+fn record_mod_change_in(server_dir: &Path, version: &ModVersion) -> anyhow::Result<Option<String>> {
+    let mut lock = ModLock::load(server_dir)?;
+    let replaced = lock
+        .mods
+        .get(&version.project_id)
+        .map(|locked| locked.version_id.clone())
+        .filter(|version_id| *version_id != version.id);
+    let files = version
+        .install_files()
+        .iter()
+        .map(|file| (file.file_name.clone(), file.sha512.clone()))
+        .collect();
+    lock.record(&version.project_id, &version.id, files, &config::now());
+    lock.save(server_dir)?;
+    Ok(replaced)
+}
+
+/// Appends the event to the server's history at the end of an operation. The operation
+/// already happened, so failing to record it is only a warning.
+pub(crate) fn record_history(server_dir: &Path, event: &history::Event) {
+    if let Err(e) = history::append(server_dir, event) {
+        eprintln!(
+            "Warning: failed to record {} in {}: {e}",
+            event.operation,
+            history::HISTORY_FILE
+        );
+    }
+}
+
+/// The jars the version installed, as added in the history.
+fn added_mods(version: &ModVersion) -> impl Iterator<Item = ModChange> + '_ {
+    version.install_files().into_iter().map(|file| ModChange {
+        new: Some(file.file_name.clone()),
+        project_id: Some(version.project_id.clone()),
+        new_version_id: Some(version.id.clone()),
+        ..Default::default()
+    })
+}
+
+/// Removes the mod at `path` from the server's [`ModLock`], so `verify` doesn't miss it.
+fn forget_mod_removal(server_name: &str, path: &Path) -> anyhow::Result<()> {
+    let server_dir = server_dir(server_name)?;
+    let mut lock = ModLock::load(&server_dir)?;
+    lock.forget_file(&path.file_name().unwrap().to_string_lossy());
+    lock.save(&server_dir)
+}
+
+pub(crate) fn show_cache_stats() -> anyhow::Result<()> {
+    let cache = download_cache();
+    let stats = cache.stats()?;
+    println!("Cache directory: {}", cache.dir().display());
+    println!(
+        "{} files, {}",
+        stats.entries,
+        format::format_bytes(stats.size)
+    );
+    Ok(())
+}
+
+/// Removes the least recently used downloads until the cache is at most `max_size_gb`.
+pub(crate) fn prune_cache(max_size_gb: f64) -> anyhow::Result<()> {
+    // GB as in `format_bytes`
+    let max_size = (max_size_gb * 1024.0 * 1024.0 * 1024.0) as u64;
+    let removed = download_cache().prune(max_size)?;
+    println!(
+        "Removed {} files, freeing {}",
+        removed.entries,
+        format::format_bytes(removed.size)
+    );
+    Ok(())
+}
+
+/// Saves the server's fork, versions, config, shareable properties and mods as a template.
+/// With `latest_mods`, the template installs the newest compatible version of each mod
+/// instead of the installed one.
+pub(crate) async fn save_template(
+    server_name: &str,
+    template_name: &str,
+    latest_mods: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let mut config = Config::load_or_create(server_name)?;
+    let server_info = ServerInfo::from_config(server_name, &mut config, false)?;
+    let fork = server_info.server_fork;
+    let installed = forks::installed_versions(&server_dir, &config.jar_name, fork)?;
+
+    let properties = ServerProperties::load(&server_dir)?;
+    let properties = template::TEMPLATE_PROPERTIES
+        .iter()
+        .filter_map(|key| Some((key.to_string(), properties.get(key)?)))
+        .collect();
+
+    let mut mods = Vec::new();
+    let mods_dir = mods_dir(server_name)?;
+    if !matches!(fork, ServerFork::Vanilla) && mods_dir.exists() {
+        let jar_paths = mods::mod_jars(&mods_dir, &server_info.game_version)?;
+        let jar_hashes = jar_paths
+            .iter()
+            .map(|path| {
+                jar_parser::calculate_hashes(
+                    &mut fs::File::open(path)?,
+                    &modrinth::LOOKUP_ALGORITHMS,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut versions = Vec::new();
+        for (path, version) in jar_paths
+            .iter()
+            .zip(modrinth::find_versions(client, &jar_hashes).await?)
+        {
+            match version {
+                Some(version) => versions.push(version),
+                None => eprintln!(
+                    "Warning: {} isn't on Modrinth, so the template leaves it out",
+                    mods::file_name(path)
+                ),
+            }
+        }
+        let projects =
+            modrinth::get_projects(client, versions.iter().map(|v| v.project_id.as_str())).await?;
+
+        for version in versions {
+            mods.push(TemplateMod {
+                slug: modrinth::project_slug(&projects, &version.project_id).to_string(),
+                version: if latest_mods {
+                    template::LATEST_MOD_VERSION.to_string()
+                } else {
+                    version.id
+                },
+            });
+        }
+    }
+
+    let template = Template {
+        fork: format!("{fork:?}"),
+        versions: forks::version_args(fork, &installed),
+        min_memory: config.min_memory,
+        max_memory: config.max_memory,
+        java_home: config.java_home,
+        start_command_template: config.start_command_template,
+        jvm_args: config.jvm_args,
+        game_args: config.game_args,
+        properties,
+        mods,
+    };
+    template.save(&templates_dir(), template_name)?;
+    println!(
+        "Template {template_name} saved: {} {} with {} mods",
+        template.fork,
+        template.versions.join(" "),
+        template.mods.len()
+    );
+
+    Ok(())
+}
+
+/// Installs a new server from the template. If its port is taken by another server,
+/// the next free one is used. The server is removed again if anything fails.
+pub(crate) async fn apply_template(
+    template_name: &str,
+    server_name: &str,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let template = Template::load(&templates_dir(), template_name)?;
+    let fork: ServerFork = template.fork.parse()?;
+
+    // Clap parser needs a dummy program name
+    let dummy_name = format!("mcerv install {}", template.fork.to_lowercase());
+    let argv = std::iter::once(dummy_name).chain(template.versions.iter().cloned());
+    let command = fork.try_parse_version_args(argv)?;
+
+    let server_dir = server_dir(server_name)?;
+    if server_dir.exists() {
+        anyhow::bail!("{server_dir:?} already exists");
+    }
+
+    let eula_agreed = prompt::confirm(Danger::Destructive, &format!("Do you agree to Minecraft server EULA? Please ensure you have read and understood the EULA at: {}", eula::EULA_URL)).unwrap_or(false);
+
+    let used_ports = server_names()?
+        .iter()
+        .filter_map(|name| self::server_dir(name).ok())
+        .map(|dir| process::configured_port(&dir))
+        .collect::<Vec<_>>();
+    fs::create_dir_all(&server_dir)?;
+    let lock = lock_server(&server_dir, "template apply")?;
+
+    let result = async {
+        if eula_agreed {
+            eula::accept(&server_dir)?;
+        }
+
+        let versions = command.installer()?.resolve(client).await?;
+        let (filename, _) = versions.install(server_name, client).await?;
+        let (info, detection) = JarDetection::detect(&server_dir.join(&filename))?;
+        let game_version = info.game_version;
+        let config = Config {
+            min_memory: template.min_memory.clone(),
+            max_memory: template.max_memory.clone(),
+            start_command_template: template
+                .start_command_template
+                .clone()
+                .or_else(|| forks::forge_start_template(&server_dir, &filename)),
+            jar_name: filename,
+            java_home: template.java_home.clone(),
+            detection,
+            created_at: Some(config::now()),
+            last_started_at: None,
+            note: None,
+            tags: Vec::new(),
+            jvm_args: template.jvm_args.clone(),
+            game_args: template.game_args.clone(),
+            world_dir: None,
+        };
+        config.save(server_name)?;
+
+        let mut properties = ServerProperties::load(&server_dir)?;
+        for (key, value) in &template.properties {
+            properties.set(key, value);
+        }
+        if let Some(port) = template.properties.get("server-port")
+            && let Ok(port) = port.parse()
+        {
+            let free_port = template::next_free_port(port, &used_ports);
+            if free_port != port {
+                println!("Port {port} is used by another server, using {free_port} instead.");
+            }
+            properties.set("server-port", &free_port.to_string());
+        }
+        properties.save()?;
+
+        let mods_dir = mods_dir(server_name)?;
+        let cache = download_cache();
+        let loader = template.fork.to_lowercase();
+        for template_mod in &template.mods {
+            let version_id = if template_mod.is_latest() {
+                modrinth::latest_compatible_version(
+                    client,
+                    &template_mod.slug,
+                    &loader,
+                    &game_version,
+                )
+                .await?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} has no version for {} {game_version}",
+                        template_mod.slug,
+                        template.fork
+                    )
+                })?
+            } else {
+                template_mod.version.clone()
+            };
+
+            fs::create_dir_all(&mods_dir)?;
+            let version =
+                modrinth::download_version(client, &version_id, &mods_dir, &cache).await?;
+            record_mod_change(server_name, &version)?;
+            println!("Mod downloaded: {}", version.install_file_names());
+        }
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    // Leave nothing half installed, so the template can be applied again
+    drop(lock);
+    if result.is_err() {
+        fs::remove_dir_all(&server_dir)?;
+        return result;
+    }
+
+    println!("Server added from template {template_name}: {server_name}");
+    Ok(())
+}
+
+pub(crate) fn list_templates() -> anyhow::Result<()> {
+    let dir = templates_dir();
+    let names = template::names(&dir)?;
+    if names.is_empty() {
+        println!("No templates found.");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Template", "Fork", "Versions", "Memory", "Mods"]);
+    for name in names {
+        match Template::load(&dir, &name) {
+            Ok(template) => table.add_row(row![
+                name,
+                template.fork,
+                template.versions.join(" "),
+                template.max_memory,
+                template.mods.len()
+            ]),
+            Err(e) => table.add_row(row![name, format!("failed to load: {e}")]),
+        };
+    }
+    table.printstd();
+
+    Ok(())
+}
+
+pub(crate) fn delete_template(template_name: &str) -> anyhow::Result<()> {
+    template::delete(&templates_dir(), template_name)?;
+    println!("Template deleted: {template_name}");
+    Ok(())
+}
+
+pub(crate) fn generate_start_script(server_name: &str) -> anyhow::Result<()> {
+    let target = ScriptTarget::native();
+    let start_script = Config::load_or_create(server_name)?.create_script(&target);
+
+    let path = try_server_dir(server_name)?.join(target.file_name());
+    let mut file = fs::File::create(&path)?;
+    file.write_all(start_script.as_bytes())?;
+
+    Ok(())
+}
+
+/// Writes a Dockerfile and a compose file that run the server in a container, with the
+/// instance directory mounted. They go to the server directory unless `output` is given.
+pub(crate) fn export_docker(server_name: &str, output: Option<&Path>) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let mut config = Config::load_or_create(server_name)?;
+    let info = ServerInfo::from_config(server_name, &mut config, false)?;
+    docker::check_exportable(&server_dir, info.server_fork)?;
+
+    let output = output.unwrap_or(&server_dir);
+    fs::create_dir_all(output)?;
+    let instance_dir = if fs::canonicalize(output)? == fs::canonicalize(&server_dir)? {
+        ".".to_string()
+    } else {
+        fs::canonicalize(&server_dir)?.to_string_lossy().to_string()
+    };
+    let server = DockerServer {
+        server_name,
+        fork: info.server_fork,
+        game_version: &info.game_version,
+        config: &config,
+        port: process::configured_port(&server_dir),
+        instance_dir: &instance_dir,
+    };
+
+    for (file_name, content) in [
+        ("Dockerfile", docker::dockerfile(&server)),
+        ("docker-compose.yml", docker::compose_file(&server)),
+    ] {
+        let path = output.join(file_name);
+        fs::write(&path, content)?;
+        println!("Wrote {}", path.display());
+    }
+    println!(
+        "Run `docker compose up -d` in {} to start it.",
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Prints a systemd service that runs the server with `mcerv start`, or with `install`,
+/// writes it to the user's or the system's unit directory.
+pub(crate) fn generate_systemd_unit(
+    server_name: &str,
+    user_service: bool,
+    install: bool,
+    supervision: &SupervisionArgs,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let target = ScriptTarget::SystemdUnit(SystemdUnit {
+        server_name: server_name.to_string(),
+        server_dir: fs::canonicalize(&server_dir)?,
+        mcerv: std::env::current_exe()?,
+        data_dir: fs::canonicalize(data_dir())?,
+        start_args: supervision.to_args(),
+        user_service,
+        user: std::env::var("USER").ok(),
+    });
+    let unit = Config::load_or_create(server_name)?.create_script(&target);
+
+    if !install {
+        print!("{unit}");
+        return Ok(());
+    }
+
+    let unit_dir = if user_service {
+        directories::BaseDirs::new()
+            .ok_or_else(|| anyhow::anyhow!("Could not find the home directory"))?
+            .config_dir()
+            .join("systemd")
+            .join("user")
+    } else {
+        PathBuf::from("/etc/systemd/system")
+    };
+    fs::create_dir_all(&unit_dir)?;
+    let path = unit_dir.join(target.file_name());
+    fs::write(&path, unit)?;
+
+    let systemctl = if user_service {
+        "systemctl --user"
+    } else {
+        "systemctl"
+    };
+    println!("Wrote {}", path.display());
+    println!(
+        "Run `{systemctl} daemon-reload && {systemctl} enable --now {}` to start it.",
+        target.file_name()
+    );
+
+    Ok(())
+}
+
+/// Accepts the EULA in the server's `eula.txt`, creating it if it's missing.
+pub(crate) fn accept_eula(server_name: &str) -> anyhow::Result<()> {
+    if eula::accept(&try_server_dir(server_name)?)? {
+        println!("The EULA was already accepted for {server_name}");
+    } else {
+        println!("Accepted the EULA for {server_name}");
+    }
+    Ok(())
+}
+
+/// Makes sure the EULA is accepted before starting, asking the user if it isn't.
+/// Otherwise the server would write `eula=false` and exit right away.
+fn ensure_eula_accepted(server_name: &str) -> anyhow::Result<()> {
+    if eula::is_accepted(&server_dir(server_name)?) {
+        return Ok(());
+    }
+
+    println!("The Minecraft EULA has not been accepted for {server_name} yet.");
+    let agreed = prompt::confirm(
+        Danger::Destructive,
+        &format!(
+            "Do you agree to Minecraft server EULA? Please ensure you have read and understood the EULA at: {}",
+            eula::EULA_URL
+        ),
+    )?;
+
+    if !agreed {
+        return Err(EulaNotAcceptedError.into());
+    }
+
+    self::accept_eula(server_name)
+}
+
+/// Prints the config and what the jar is. With `refresh`, the jar is detected again
+/// instead of using the cached detection.
+pub(crate) fn show_server_info(server_name: &str, refresh: bool) -> anyhow::Result<()> {
+    let mut config = Config::load_or_create(server_name)?;
+    let server_info = ServerInfo::from_config(server_name, &mut config, refresh)?;
+    println!("{config}{server_info}");
+    Ok(())
+}
+
+/// Launches the server once so `server.properties`, `eula.txt` and the default configs
+/// exist before the first real start, without generating the world.
+pub(crate) async fn bootstrap_server(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    if let Some(status) = process::running_process(&server_dir)? {
+        return Err(ServerProcessError::AlreadyRunning(status.pid).into());
+    }
+
+    let mut config = Config::load_or_create(server_name)?;
+    let server_info = ServerInfo::from_config(server_name, &mut config, false)?;
+    // The Forge shim jar reads its launch arguments from `libraries/`, which the installer creates
+    if server_info.server_fork == ServerFork::Forge && !server_dir.join("libraries").is_dir() {
+        anyhow::bail!("Forge libraries are missing in {server_dir:?}, reinstall the server");
+    }
+
+    println!("Launching {server_name} once to generate its files...");
+    let command = tokio::process::Command::from(config.start_command(&server_dir));
+    let status = bootstrap::run_first_launch(command, bootstrap::BOOTSTRAP_TIMEOUT).await?;
+    if !status.success() {
+        println!("Server exited with {status}");
+    }
+
+    let generated = bootstrap::generated_files(&server_dir);
+    if generated.is_empty() {
+        anyhow::bail!("The server did not generate any files");
+    }
+    println!("Generated: {}", generated.join(", "));
+
+    Ok(())
+}
+
+/// Makes the server send a resource pack to joining players.
+///
+/// `source` is a local file or a URL. A local file is hashed, optionally copied into the
+/// server directory, and served from `public_url`, which is asked for if not given.
+pub(crate) async fn set_resource_pack(
+    server_name: &str,
+    source: &str,
+    public_url: Option<String>,
+    copy: bool,
+    require: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+
+    let (url, sha1) = if source.starts_with("http://") || source.starts_with("https://") {
+        println!("Downloading {source} to compute its hash...");
+        let temp_path =
+            std::env::temp_dir().join(format!("mcerv-resourcepack-{}.zip", std::process::id()));
+        network::download_file(client, &source, &temp_path).await?;
+        let sha1 = jar_parser::calculate_hash(&mut fs::File::open(&temp_path)?);
+        fs::remove_file(&temp_path)?;
+        (source.to_string(), sha1?)
+    } else {
+        let path = PathBuf::from(source);
+        let sha1 = jar_parser::calculate_hash(&mut fs::File::open(&path)?)?;
+
+        if copy {
+            let file_name = path
+                .file_name()
+                .ok_or(anyhow::anyhow!("{source} is not a file"))?;
+            fs::copy(&path, server_dir.join(file_name))?;
+            println!(
+                "Copied the resource pack to {}",
+                server_dir.join(file_name).display()
+            );
+        }
+
+        let url = match public_url {
+            Some(url) => url,
+            None if is_interactive() => Input::new()
+                .with_prompt("The public URL players download the resource pack from")
+                .interact_text()?,
+            None => anyhow::bail!("--url is required for a local resource pack"),
+        };
+        (url, sha1)
+    };
+
+    let mut properties = ServerProperties::load(&server_dir)?;
+    properties.set("resource-pack", &url);
+    properties.set("resource-pack-sha1", &sha1);
+    properties.set("require-resource-pack", &require.to_string());
+    properties.save()?;
+
+    println!("Resource pack set to {url} (sha1 {sha1})");
+    Ok(())
+}
+
+/// Stops the server from sending a resource pack.
+pub(crate) fn clear_resource_pack(server_name: &str) -> anyhow::Result<()> {
+    let mut properties = ServerProperties::load(&try_server_dir(server_name)?)?;
+    for key in [
+        "resource-pack",
+        "resource-pack-sha1",
+        "require-resource-pack",
+    ] {
+        properties.remove(key);
+    }
+    properties.save()?;
+
+    println!("Resource pack cleared");
+    Ok(())
+}
+
+/// Shows the server.properties keys the two servers set differently.
+pub(crate) fn diff_properties(server_a: &str, server_b: &str) -> anyhow::Result<()> {
+    let a = ServerProperties::load(&try_server_dir(server_a)?)?;
+    let b = ServerProperties::load(&try_server_dir(server_b)?)?;
+    let diffs = a.diff(&b);
+    if diffs.is_empty() {
+        println!("{server_a} and {server_b} have the same properties");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Key", server_a, server_b]);
+    for diff in diffs {
+        let left = diff.left.unwrap_or_else(|| "-".to_string());
+        let right = diff.right.unwrap_or_else(|| "-".to_string());
+        table.add_row(row![diff.key, left, right]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Copies the server.properties values of the template server to the others, leaving out
+/// the excluded keys. With `dry_run`, only shows what would change.
+pub(crate) fn sync_properties(
+    from: &str,
+    to: &[ServerName],
+    except: &[String],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let template = ServerProperties::load(&try_server_dir(from)?)?;
+    if template.entries().is_empty() {
+        anyhow::bail!("{from} has no server.properties to copy from, start it once to create one");
+    }
+
+    for server_name in to {
+        if server_name.as_str() == from {
+            continue;
+        }
+        let mut properties = ServerProperties::load(&try_server_dir(server_name)?)?;
+        let changes = properties.sync_from(&template, except);
+        if changes.is_empty() {
+            println!("{server_name} already matches {from}");
+            continue;
+        }
+
+        println!("{server_name}:");
+        for change in &changes {
+            let old = change.left.as_deref().unwrap_or("-");
+            let new = change.right.as_deref().unwrap_or("-");
+            println!("  {}: {old} -> {new}", change.key);
+        }
+        if !dry_run {
+            properties.save()?;
+        }
+    }
+
+    if dry_run {
+        println!("Dry run, nothing was changed");
+    }
+    Ok(())
+}
+
+/// Shows the seed, name and versions of the server's world from its `level.dat`.
+pub(crate) fn show_world_info(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let Some(info) = LevelInfo::load(&server_dir)? else {
+        println!("{server_name} has no world yet, it's generated on the first start");
+        return Ok(());
+    };
+    let unknown = || "unknown".to_string();
+
+    println!(
+        "Level name: {}",
+        info.level_name.clone().unwrap_or_else(unknown)
+    );
+    println!(
+        "Seed: {}",
+        info.seed.map_or_else(unknown, |seed| seed.to_string())
+    );
+    let snapshot = if info.snapshot { " (snapshot)" } else { "" };
+    let data_version = info
+        .data_version
+        .map_or(String::new(), |version| format!(", data version {version}"));
+    println!(
+        "Last opened with: {}{snapshot}{data_version}",
+        info.version_name.clone().unwrap_or_else(unknown)
+    );
+    let last_played = info
+        .last_played
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        });
+    println!("Last played: {}", last_played.unwrap_or_else(unknown));
+
+    if let Ok(server_info) = ServerInfo::cached_or_detect(server_name)
+        && let Some(warning) = info.downgrade_warning(&server_info.game_version)
+    {
+        eprintln!("Warning: {warning}");
+    }
+    Ok(())
+}
+
+/// Moves the world of the server into a backup, so the next start generates a new one,
+/// optionally with `seed`.
+pub(crate) fn reset_world(
+    server_name: &str,
+    seed: Option<String>,
+    confirm_name: Option<&str>,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "world reset")?;
+    if let Some(status) = process::running_process(&server_dir)? {
+        return Err(ServerProcessError::AlreadyRunning(status.pid).into());
+    }
+
+    let dirs = world::world_dirs(&server_dir)?;
+    let backups_dir = world::backups_dir(&server_dir)?;
+    if dirs.is_empty() && seed.is_none() {
+        println!("{server_name} has no world to reset");
+        return Ok(());
+    }
+
+    if !dirs.is_empty() {
+        println!("The world of {server_name} will be reset:");
+        for dir in &dirs {
+            println!("  {}", dir.file_name().unwrap().to_string_lossy());
+        }
+        println!(
+            "It's moved to {}, delete it from there to free the space.",
+            backups_dir.display()
+        );
+    }
+    // Typing the name, since a world can be hours of players' work
+    let confirmed = match confirm_name {
+        Some(name) if name == server_name => true,
+        Some(name) => anyhow::bail!("{name} isn't {server_name}, nothing was reset"),
+        None => prompt::confirm(
+            Danger::Critical(server_name),
+            &format!("Reset the world of {server_name}?"),
+        )?,
+    };
+    if !confirmed {
+        if !is_interactive() {
+            println!("Pass --confirm {server_name} to reset it.");
+        } else {
+            println!("Nothing was reset");
+        }
+        return Ok(());
+    }
+
+    if let Some(seed) = &seed {
+        let mut properties = ServerProperties::load(&server_dir)?;
+        properties.set("level-seed", seed);
+        properties.save()?;
+        println!("The next world is generated with the seed {seed}");
+    }
+    if !dirs.is_empty() {
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let backup_dir = world::back_up(&backups_dir, &dirs, &name)?;
+        println!(
+            "Moved the world to {}, the next start generates a new one",
+            backup_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Warns if the world was last opened with a newer game version than the server's.
+fn warn_world_downgrade(server_name: &str) {
+    let Ok(dir) = server_dir(server_name) else {
+        return;
+    };
+    let Ok(Some(info)) = LevelInfo::load(&dir) else {
+        return;
+    };
+    if let Ok(server_info) = ServerInfo::cached_or_detect(server_name)
+        && let Some(warning) = info.downgrade_warning(&server_info.game_version)
+    {
+        eprintln!("Warning: {warning}");
+    }
+}
+
+/// Lists the crash reports of the server, or summarizes one of them.
+/// Without `latest` or `list`, the report is picked interactively.
+pub(crate) fn show_crash_report(server_name: &str, latest: bool, list: bool) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let reports = crash_report::list(&server_dir)?;
+
+    if reports.is_empty() {
+        println!("{server_name} has no crash reports.");
+        return Ok(());
+    }
+
+    let names: Vec<_> = reports
+        .iter()
+        .rev()
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+
+    if list {
+        for name in names {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    let index = if latest || !is_interactive() {
+        0
+    } else {
+        Select::new()
+            .with_prompt("Select a crash report")
+            .items(&names)
+            .default(0)
+            .interact()?
+    };
+    let path = &reports[reports.len() - 1 - index];
+
+    let fork = ServerInfo::cached_or_detect(server_name)?.server_fork;
+    let report = CrashReport::parse(&fs::read_to_string(path)?, fork);
+
+    println!("{}", path.display());
+    println!("{report}");
+
+    if fork != ServerFork::Vanilla {
+        println!("{}", report.mod_delta(&installed_mods(server_name)?));
+    }
+
+    Ok(())
+}
+
+/// The metadata of the mods in the server's mods directory. Jars without any are skipped.
+fn installed_mods(server_name: &str) -> anyhow::Result<Vec<ModMetadata>> {
+    let dir = mods_dir(server_name)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let game_version = ServerInfo::cached_or_detect(server_name)?.game_version;
+    let mut mods = Vec::new();
+    for path in mods::mod_jars(&dir, &game_version)? {
+        match jar_parser::read_mod_metadata(&path) {
+            Ok(Some(metadata)) => mods.push(metadata),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to read mod metadata of {}: {e}", path.display()),
+        }
+    }
+
+    Ok(mods)
+}
+
+pub(crate) async fn update_server_jar<I, T>(
+    version_args: I,
+    server_name: &str,
+    allow_downgrade: bool,
+    client: &Client,
+) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let start = Instant::now();
+
+    // Find the old jar name before downloading the new one
+    // to prevent multiple jars existing at once
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "update-server-jar")?;
+    let mut config = Config::load_or_create(server_name)?;
+    let old_jar_name = config.jar_name.clone();
+    let old_jar_path = server_dir.join(&old_jar_name);
+    let info = ServerInfo::from_config(server_name, &mut config, false)?;
+
+    // Clap parser needs a dummy program name
+    let iter = version_args.into_iter().map(|v| v.into());
+    let dummy_name = format!(
+        "mcerv {}",
+        Cli::command()
+            .get_matches()
+            .subcommand()
+            .expect("Cannot get subcommand when getting dummy name")
+            .0
+    );
+    let argv = std::iter::once(dummy_name.into()).chain(iter);
+    let command = info.server_fork.parse_version_args(argv);
+    let versions = command.installer()?.resolve(client).await?;
+    let target = versions.game_version();
+    if !allow_downgrade {
+        ensure_not_downgrade(server_name, &info.game_version, target)?;
+    } else if version::is_newer(&info.game_version, target) {
+        println!("Downgrading from {} to {target}", info.game_version);
+    }
+
+    let mut event = history::Event::new("update-server-jar");
+    event.old_versions = history::jar_versions(&server_dir, &old_jar_name, info.server_fork);
+
+    // The old jar is restored on Ctrl-C
+    let _scope = cancel::scope();
+    // Moved away first, since the new jar may have the same name
+    println!("Moving old server jar to the trash...");
+    phase("moving old server jar to the trash");
+    let trash = TrashBatch::begin(&server_dir, "update-server-jar");
+    trash.record_jar_name(&old_jar_name)?;
+    trash.trash(&old_jar_path)?;
+
+    println!("Installing new server jar...");
+    let filename = match versions.install(server_name, client).await {
+        Ok((filename, stats)) => {
+            println!("Downloaded {stats}");
+            filename
+        }
+        Err(e) => {
+            trash::undo(&server_dir)?;
+            if cancel::token().is_cancelled() {
+                anyhow::bail!(CancelledError);
+            }
+            return Err(e);
+        }
+    };
+    trash.record_added(&server_dir.join(&filename))?;
+
+    println!("Updating config...");
+    phase("updating config");
+    config.set_jar(&server_dir, filename);
+
+    config.save(server_name)?;
+    event.new_versions = history::jar_versions(&server_dir, &config.jar_name, info.server_fork);
+    record_history(&server_dir, &event);
+
+    println!("Update complete in {:?}", start.elapsed());
+    finish_trash(server_name, &trash)?;
+
+    Ok(())
+}
+
+/// Packs the server jar, what it downloads on its first start and the mods into a bundle
+/// `bundle apply` installs without network access.
+pub(crate) fn create_bundle(server_name: &str, output: &Path) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "bundle create")?;
+    let mut config = Config::load_or_create(server_name)?;
+    let info = ServerInfo::from_config(server_name, &mut config, false)?;
+
+    let mut paths = vec![PathBuf::from(&config.jar_name)];
+    for runtime_path in bundle::RUNTIME_PATHS {
+        let path = server_dir.join(runtime_path);
+        if path.is_dir() {
+            paths.extend(bundle::files_under(&server_dir, Path::new(runtime_path))?);
+        } else if path.is_file() && runtime_path != config.jar_name {
+            paths.push(PathBuf::from(runtime_path));
+        }
+    }
+    if info.server_fork != ServerFork::Vanilla && !server_dir.join("libraries").is_dir() {
+        eprintln!(
+            "Warning: {server_name} has no libraries directory, which its jar downloads on the \
+            first start. Start it once before bundling, or it can't start offline"
+        );
+    }
+
+    let mods_dir = mods_dir(server_name)?;
+    if mods_dir.is_dir() {
+        for jar in mods::mod_jars(&mods_dir, &info.game_version)? {
+            paths.push(jar.strip_prefix(&server_dir)?.to_path_buf());
+        }
+    }
+    for file in [PINS_FILE, MOD_LOCK_FILE] {
+        if server_dir.join(file).is_file() {
+            paths.push(PathBuf::from(file));
+        }
+    }
+
+    let mut manifest = BundleManifest {
+        format_version: bundle::BUNDLE_FORMAT_VERSION,
+        server_name: server_name.to_string(),
+        fork: info.server_fork,
+        game_version: info.game_version,
+        jar_name: config.jar_name,
+        min_memory: config.min_memory,
+        max_memory: config.max_memory,
+        created_at: config::now(),
+        files: Vec::new(),
+    };
+    bundle::create(output, &server_dir, &mut manifest, &paths)?;
+    println!(
+        "Bundled {} files of {server_name}, {}, into {}",
+        manifest.files.len(),
+        format::format_bytes(manifest.size()),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Installs the server from a bundle of `bundle create`, or updates it if it exists, after
+/// verifying every file. Nothing is downloaded.
+pub(crate) fn apply_bundle(bundle_path: &Path, server_name: &str) -> anyhow::Result<()> {
+    let manifest = bundle::verify(bundle_path)?;
+    println!(
+        "Verified the {} files of {}, a {:?} {} server",
+        manifest.files.len(),
+        manifest.server_name,
+        manifest.fork,
+        manifest.game_version
+    );
+
+    let server_dir = server_dir(server_name)?;
+    if !server_dir.exists() {
+        fs::create_dir_all(&server_dir)?;
+        let lock = lock_server(&server_dir, "bundle apply")?;
+        let result = bundle::extract(bundle_path, &manifest, &server_dir, None).and_then(|_| {
+            let mut config = Config::new_4gb(manifest.jar_name.clone())?;
+            config.min_memory = manifest.min_memory.clone();
+            config.max_memory = manifest.max_memory.clone();
+            config.save(server_name)
+        });
+
+        // Leave nothing half installed, so the bundle can be applied again
+        drop(lock);
+        if result.is_err() {
+            fs::remove_dir_all(&server_dir)?;
+            return result;
+        }
+        println!(
+            "Installed {server_name}. Accept the EULA with `mcerv accept-eula {server_name}` to start it"
+        );
+        return Ok(());
+    }
+
+    let _lock = lock_server(&server_dir, "bundle apply")?;
+    let mut config = Config::load_or_create(server_name)?;
+    let trash = TrashBatch::begin(&server_dir, "bundle apply");
+    let result = (|| {
+        // The mods the server no longer has
+        let mut removed = 0;
+        let mods_dir = mods_dir(server_name)?;
+        confirm_mods_dir_change(&mods_dir)?;
+        if mods_dir.is_dir() {
+            for jar in mods::mod_jars(&mods_dir, &manifest.game_version)? {
+                if !manifest.contains(jar.strip_prefix(&server_dir)?) {
+                    trash.trash(&jar)?;
+                    removed += 1;
+                }
+            }
+        }
+
+        let written = bundle::extract(bundle_path, &manifest, &server_dir, Some(&trash))?;
+        if config.jar_name != manifest.jar_name {
+            trash.record_jar_name(&config.jar_name)?;
+            let old_jar_path = server_dir.join(&config.jar_name);
+            if old_jar_path.is_file() && !manifest.contains(Path::new(&config.jar_name)) {
+                trash.trash(&old_jar_path)?;
+            }
+            config.set_jar(&server_dir, manifest.jar_name.clone());
+            config.save(server_name)?;
+        }
+        anyhow::Ok((written, removed))
+    })();
+
+    let (written, removed) = match result {
+        Ok(changes) => changes,
+        Err(e) => {
+            trash::undo(&server_dir)?;
+            return Err(e);
+        }
+    };
+    println!("Updated {server_name}: {written} files written, {removed} mods removed");
+    finish_trash(server_name, &trash)?;
+
+    Ok(())
+}
+
+/// Restores what the latest operation on the server moved to the trash, and the jar the
+/// config pointed at before it. With `purge`, deletes the trash instead.
+/// Prints the newest `limit` events of the server's history, skipping the lines that
+/// aren't events.
+pub(crate) fn show_history(server_name: &str, limit: Option<usize>) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let (events, warnings) = history::read(&server_dir)?;
+    for warning in warnings {
+        eprintln!("Warning: {warning}");
+    }
+    if events.is_empty() {
+        println!("No history recorded for {server_name} yet.");
+        return Ok(());
+    }
+
+    history::table(&events, limit, chrono::Local::now()).printstd();
+    Ok(())
+}
+
+/// Rolls back the latest mod update of the history that wasn't rolled back yet. The old
+/// jars are taken from the update's trash batch, or downloaded again from their recorded
+/// versions. Mods whose old jar can't be had are only left as they are after confirming.
+pub(crate) async fn undo_mods(server_name: &str, client: &Client) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "undo-mods")?;
+    let (events, warnings) = history::read(&server_dir)?;
+    for warning in warnings {
+        eprintln!("Warning: {warning}");
+    }
+    let Some(update) = rollback::latest_update(&events) else {
+        println!("No mod update of {server_name} to undo.");
+        return Ok(());
+    };
+
+    println!(
+        "Rolling back the mod update of {}:",
+        config::format_time(Some(&update.timestamp))
+    );
+    let located = rollback::locate_old_jars(&server_dir, update)?;
+    for (change, old_jar) in &located {
+        println!("  {change} ({old_jar})");
+    }
+    let unavailable = located
+        .iter()
+        .filter(|(_, old_jar)| *old_jar == OldJar::Unavailable)
+        .count();
+    if unavailable == located.len() {
+        anyhow::bail!("None of the old jars of the update can be restored");
+    }
+    if unavailable > 0 {
+        let should_continue = prompt::confirm(
+            Danger::Destructive,
+            &format!(
+                "{unavailable} mods can't be rolled back. Roll back the other {}?",
+                located.len() - unavailable
+            ),
+        )?;
+        if !should_continue {
+            if !is_interactive() {
+                println!("Pass --yes to roll back the others.");
+            }
+            return Ok(());
+        }
+    }
+
+    confirm_mods_dir_change(&mods_dir(server_name)?)?;
+    let (trash, failed) = roll_back_mods(
+        &server_dir,
+        update,
+        located,
+        modrinth::API_URL,
+        &download_cache(),
+        client,
+    )
+    .await?;
+    finish_trash(server_name, &trash)?;
+    if failed > 0 {
+        anyhow::bail!("{failed} mods couldn't be rolled back");
+    }
+
+    Ok(())
+}
+
+/// Rolls the mods back to their located old jars, returning the trash batch of the new jars
+/// and how many mods failed. A failed mod is left at its new version without stopping the
+/// others, and the rollback of the others is recorded.
+async fn roll_back_mods(
+    server_dir: &Path,
+    update: &history::Event,
+    located: Vec<(ModChange, OldJar)>,
+    api_url: &str,
+    cache: &DownloadCache,
+    client: &Client,
+) -> anyhow::Result<(TrashBatch, usize)> {
+    let mods_dir = server_dir.join("mods");
+    let lock = ModLock::load(server_dir)?;
+    let trash = TrashBatch::begin(server_dir, "undo-mods");
+    let mut event = history::Event::new(rollback::ROLLBACK_OPERATION);
+    event.rolled_back = Some(rollback::update_key(update).to_string());
+    event.trash_batch = Some(trash.name());
+    let mut failed = 0;
+    for (change, old_jar) in located {
+        let result = match old_jar {
+            OldJar::Unavailable => continue,
+            OldJar::Trashed => rollback::trash_new_jars(&mods_dir, &change, &lock, &trash)
+                .and_then(|()| rollback::restore_old_jar(server_dir, update, &change))
+                .and_then(|path| record_restored_mod(server_dir, &change, &path)),
+            OldJar::Download(version_id) => {
+                async {
+                    let version =
+                        modrinth::get_versions_by_id_from(client, api_url, &[&version_id])
+                            .await?
+                            .pop()
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("Modrinth has no version {version_id}")
+                            })?;
+                    // The new jars are only trashed once the old version is downloaded
+                    let mut new_jars = rollback::new_jars(&mods_dir, &change, &lock);
+                    let jar_path = if new_jars.is_empty() {
+                        mods_dir.join(change.old.as_deref().unwrap_or_default())
+                    } else {
+                        new_jars.remove(0)
+                    };
+                    let mod_update = ModUpdate {
+                        jar_path,
+                        old_files: new_jars,
+                        version,
+                    };
+                    update_mod(client, &mods_dir, &mod_update, cache, &trash).await?;
+                    record_mod_change_in(server_dir, &mod_update.version)?;
+                    Ok(())
+                }
+                .await
+            }
+        };
+        match result {
+            Ok(()) => event.mods.push(change.reversed()),
+            Err(e) => {
+                let name = change
+                    .new
+                    .as_deref()
+                    .or(change.old.as_deref())
+                    .unwrap_or("");
+                eprintln!("Warning: Failed to roll back {name}: {e:#}");
+                failed += 1;
+            }
+        }
+    }
+    println!("Rolled back {} mods", event.mods.len());
+    record_history(server_dir, &event);
+
+    Ok((trash, failed))
+}
+
+/// Records the jar a mod was rolled back to from the trash in the server's [`ModLock`].
+fn record_restored_mod(server_dir: &Path, change: &ModChange, path: &Path) -> anyhow::Result<()> {
+    let mut lock = ModLock::load(server_dir)?;
+    if let Some(new) = &change.new {
+        lock.forget_file(new);
+    }
+    if let (Some(project_id), Some(version_id)) = (&change.project_id, &change.old_version_id) {
+        let sha512 = jar_parser::calculate_hash_with(
+            &mut fs::File::open(path)?,
+            jar_parser::HashAlgorithm::Sha512,
+        )?;
+        let files = std::collections::BTreeMap::from([(mods::file_name(path), sha512)]);
+        lock.record(project_id, version_id, files, &config::now());
+    }
+    lock.save(server_dir)
+}
+
+pub(crate) fn undo(server_name: &str, purge: bool) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "undo")?;
+    if purge {
+        let freed = trash::purge(&server_dir)?;
+        println!("Trash purged, freeing {}", format::format_bytes(freed));
+        return Ok(());
+    }
+
+    // Loaded before the old jar is back, so it isn't taken for a manual replacement
+    let mut config = Config::load_or_create(server_name)?;
+    let Some(undone) = trash::undo(&server_dir)? else {
+        println!("Nothing to undo for {server_name}.");
+        return Ok(());
+    };
+
+    println!("Undoing {}...", undone.operation);
+    for path in &undone.removed {
+        println!("Removed {}", path.display());
+    }
+    for path in &undone.restored {
+        println!("Restored {}", path.display());
+    }
+    if let Some(jar_name) = undone.previous_jar_name {
+        println!("Config reverted to {jar_name}");
+        config.set_jar(&server_dir, jar_name);
+        config.save(server_name)?;
+    }
+
+    Ok(())
+}
+
+/// Prints whether each component of the server jar is up to date, and with `update`,
+/// replaces the jar with the latest versions.
+pub(crate) async fn check_server(
+    server_name: &str,
+    update: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let (fork, latest, updates) = find_server_updates(server_name, client).await?;
+
+    println!("{server_name} is a {fork:?} server.");
+    for update in &updates {
+        println!("{update}");
+    }
+
+    if updates.iter().all(|update| update.is_up_to_date()) {
+        println!("The server jar is up-to-date.");
+        return Ok(());
+    }
+    notifier(Some(client))
+        .notify(&Notification::ServerUpdate {
+            server: server_name.to_string(),
+            updates: updates
+                .iter()
+                .filter(|update| !update.is_up_to_date())
+                .map(|update| update.to_string())
+                .collect(),
+        })
+        .await;
+
+    if !update {
+        println!("Run with --update to install the latest versions.");
+        return Ok(());
+    }
+
+    update_server_jar(&latest.version_args, server_name, false, client).await
+}
+
+/// The server's fork, the latest versions of it, and each component of the server jar paired
+/// with its latest version.
+async fn find_server_updates(
+    server_name: &str,
+    client: &Client,
+) -> anyhow::Result<(ServerFork, LatestVersions, Vec<ComponentUpdate>)> {
+    let server_dir = try_server_dir(server_name)?;
+    let mut config = Config::load_or_create(server_name)?;
+    let fork = ServerInfo::from_config(server_name, &mut config, false)?.server_fork;
+
+    let installed = forks::installed_versions(&server_dir, &config.jar_name, fork)?;
+    let latest = forks::fetch_latest_versions(fork, client).await?;
+    let updates = forks::component_updates(&installed, &latest);
+
+    Ok((fork, latest, updates))
+}
+
+/// Starts the server. With `detach`, returns as soon as the server is spawned,
+/// otherwise waits until it exits.
+pub(crate) async fn start_server(
+    server_name: &str,
+    detach: bool,
+    supervision: &SupervisionArgs,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    ensure_can_start(server_name)?;
+    check_mod_conflicts(server_name)?;
+    warn_world_downgrade(server_name);
+    ensure_eula_accepted(server_name)?;
+    // Before detaching, since the detached copy can't ask about a replaced jar
+    let mut config = Config::load_or_create(server_name)?;
+    config.mark_started();
+    config.save(server_name)?;
+
+    if detach {
+        // A detached copy of mcerv runs the server, so it can bridge the console to its stdin
+        let mut wrapper = std::process::Command::new(std::env::current_exe()?);
+        wrapper
+            .arg("console-wrapper")
+            .arg(server_name)
+            .args(supervision.to_args());
+
+        let log_path = process::console_log_path(&server_dir);
+        process::spawn_detached(wrapper, &log_path)?;
+        println!(
+            "Started {server_name} in the background, console output goes to {}",
+            log_path.display()
+        );
+        println!("Use `mcerv attach {server_name}` to watch the console and send commands.");
+        return Ok(());
+    }
+
+    // Restart warnings are written to the server's stdin, so the terminal is forwarded to it
+    let console = if supervision.restart_at.is_empty() {
+        None
+    } else {
+        let (commands, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            use tokio::io::AsyncBufReadExt;
+            let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if commands.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(received)
+    };
+
+    let command = || tokio::process::Command::from(config.start_command(&server_dir));
+    let status = supervisor::supervise(
+        &server_dir,
+        command,
+        supervision.restart_policy(),
+        &supervision.restart_at,
+        console,
+        Some((server_name, &notifier(None))),
+    )
+    .await?;
+    if !status.success() {
+        println!("Server exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Runs the server as the child of a detached mcerv, forwarding the console socket
+/// to its stdin. Its output goes wherever this process's output goes.
+pub(crate) async fn run_console_wrapper(
+    server_name: &str,
+    supervision: &SupervisionArgs,
+) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let config = Config::load_or_create(server_name)?;
+    let command = || tokio::process::Command::from(config.start_command(&server_dir));
+
+    #[cfg(unix)]
+    let (socket_path, console) = {
+        let socket_path = console::console_socket_path(&server_dir);
+        // Left behind if a previous wrapper got killed
+        if socket_path.exists() {
+            fs::remove_file(&socket_path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(&socket_path)?;
+        let (commands, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(console::serve_console(listener, commands));
+        (socket_path, Some(received))
+    };
+    #[cfg(not(unix))]
+    let console = None;
+
+    let status = supervisor::supervise(
+        &server_dir,
+        command,
+        supervision.restart_policy(),
+        &supervision.restart_at,
+        console,
+        Some((server_name, &notifier(None))),
+    )
+    .await;
+    #[cfg(unix)]
+    fs::remove_file(socket_path)?;
+
+    println!("[mcerv] Server exited with {}", status?);
+    Ok(())
+}
+
+/// Follows the console of a server started with `--detach`.
+pub(crate) async fn attach_server(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    if process::running_process(&server_dir)?.is_none() {
+        println!("{server_name} is not running, showing its last console output.");
+    }
+    console::attach(&server_dir).await
+}
+
+/// Stops a server started by mcerv and waits for it to exit.
+pub(crate) async fn stop_server(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let Some(status) = process::running_process(&server_dir)? else {
+        return Err(ServerProcessError::NotRunning.into());
+    };
+    // Tells a supervisor not to restart the server
+    fs::write(supervisor::stop_sentinel_path(&server_dir), "")?;
+
+    println!("Stopping {server_name} (pid {})...", status.pid);
+    if !process::terminate(status.pid, Duration::from_secs(60)).await {
+        return Err(ServerProcessError::StopTimedOut(status.pid).into());
+    }
+    PidFile::remove(&server_dir)?;
+    println!("Stopped {server_name}");
+
+    Ok(())
+}
+
+/// Saves the servers as a group in the global config, started in the order given.
+pub(crate) fn create_group(
+    group_name: &str,
+    server_names: &[ServerName],
+    delay: u64,
+) -> anyhow::Result<()> {
+    let server_names: Vec<String> = server_names.iter().map(ToString::to_string).collect();
+    for server_name in &server_names {
+        try_server_dir(server_name)?;
+    }
+
+    let path = global_config_path();
+    let replaced = GlobalConfig::load(&path)?.groups.contains_key(group_name);
+    GlobalConfig::save_group(&path, group_name, &ServerGroup::new(&server_names, delay))?;
+    if replaced {
+        println!("Replaced group {group_name}: {}", server_names.join(", "));
+    } else {
+        println!("Created group {group_name}: {}", server_names.join(", "));
+    }
+
+    Ok(())
+}
+
+fn load_group(group_name: &str) -> anyhow::Result<ServerGroup> {
+    GlobalConfig::load(&global_config_path())?
+        .groups
+        .remove(group_name)
+        .ok_or_else(|| anyhow::anyhow!("No group named {group_name}"))
+}
+
+/// Starts the members of the group in the background, one after the other. A member that
+/// fails to start doesn't stop the rest, unless `fail_fast`.
+pub(crate) async fn start_group(group_name: &str, fail_fast: bool) -> anyhow::Result<()> {
+    let group = load_group(group_name)?;
+    let mut failed = 0;
+    for (i, member) in group.members.iter().enumerate() {
+        let server_dir = server_dir(&member.server)?;
+        if let Some(status) = process::running_process(&server_dir)? {
+            println!("{} is already running (pid {})", member.server, status.pid);
+            continue;
+        }
+
+        if i > 0 && !member.delay().is_zero() {
+            println!(
+                "Waiting {}s before starting {}...",
+                member.delay, member.server
+            );
+            tokio::time::sleep(member.delay()).await;
+        }
+
+        if let Err(e) = start_server(&member.server, true, &SupervisionArgs::default()).await {
+            eprintln!("Failed to start {}: {e}", member.server);
+            failed += 1;
+            if fail_fast {
+                anyhow::bail!(
+                    "Stopped starting {group_name} after {} failed",
+                    member.server
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "Failed to start {failed} of the {} servers in {group_name}",
+            group.members.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Stops the running members of the group in reverse order.
+pub(crate) async fn stop_group(group_name: &str, fail_fast: bool) -> anyhow::Result<()> {
+    let group = load_group(group_name)?;
+    let mut failed = 0;
+    for member in group.members.iter().rev() {
+        if process::running_process(&server_dir(&member.server)?)?.is_none() {
+            println!("{} isn't running", member.server);
+            continue;
+        }
+
+        if let Err(e) = stop_server(&member.server).await {
+            eprintln!("Failed to stop {}: {e}", member.server);
+            failed += 1;
+            if fail_fast {
+                anyhow::bail!(
+                    "Stopped stopping {group_name} after {} failed",
+                    member.server
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "Failed to stop {failed} of the {} servers in {group_name}",
+            group.members.len()
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn show_group_status(group_name: &str) -> anyhow::Result<()> {
+    let group = load_group(group_name)?;
+
+    let mut table = Table::new();
+    table.add_row(row!["Server", "Status", "PID", "Port"]);
+    for member in &group.members {
+        let server_dir = server_dir(&member.server)?;
+        let port = process::configured_port(&server_dir);
+        match process::running_process(&server_dir)? {
+            Some(status) => table.add_row(row![member.server, "running", status.pid, port]),
+            None => table.add_row(row![member.server, "stopped", "-", port]),
+        };
+    }
+    table.printstd();
+
+    Ok(())
+}
+
+/// Checks the servers, or the ones with the tag, for mod and server jar updates every
+/// `interval`, reporting only the updates that weren't found by the previous check. Failed
+/// checks are retried sooner, backing off. With `once`, checks only once.
+pub(crate) async fn watch_updates(
+    interval: Duration,
+    tag: Option<&str>,
+    once: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let state_path = data_dir().join(WATCH_STATE_FILE);
+    let mut state = WatchState::load(&state_path)?;
+    let notifier = notifier(Some(client));
+
+    let mut failures = 0;
+    loop {
+        let failed = check_for_updates(&mut state, tag, &notifier, client).await?;
+        state.save(&state_path)?;
+
+        if once {
+            if failed > 0 {
+                anyhow::bail!("Failed to check {failed} servers for updates");
+            }
+            return Ok(());
+        }
+
+        let wait = if failed > 0 {
+            failures += 1;
+            watch::backoff(interval, failures)
+        } else {
+            failures = 0;
+            interval
+        };
+        println!("Checking again in {}", format::format_duration(wait));
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Checks each server for updates, printing and notifying the new ones. Returns the number
+/// of servers that couldn't be checked, which are left as they were in the state.
+async fn check_for_updates(
+    state: &mut WatchState,
+    tag: Option<&str>,
+    notifier: &Notifier,
+    client: &Client,
+) -> anyhow::Result<usize> {
+    let mut failed = 0;
+    for server_name in tagged_server_names(tag)? {
+        let found = match find_updates(&server_name, client).await {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("Failed to check {server_name} for updates: {e}");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let new = state.record(&server_name, found);
+        if new.is_empty() {
+            continue;
+        }
+        for update in &new.server {
+            println!("{server_name}: server update {update}");
+        }
+        for update in &new.mods {
+            println!("{server_name}: mod update {update}");
+        }
+
+        if !new.server.is_empty() {
+            notifier
+                .notify(&Notification::ServerUpdate {
+                    server: server_name.clone(),
+                    updates: new.server,
+                })
+                .await;
+        }
+        if !new.mods.is_empty() {
+            notifier
+                .notify(&Notification::ModUpdates {
+                    server: server_name.clone(),
+                    updates: new.mods,
+                })
+                .await;
+        }
+    }
+
+    Ok(failed)
+}
+
+async fn find_updates(server_name: &str, client: &Client) -> anyhow::Result<KnownUpdates> {
+    let (_, _, updates) = find_server_updates(server_name, client).await?;
+    let server = updates
+        .iter()
+        .filter(|update| !update.is_up_to_date())
+        .map(|update| update.to_string())
+        .collect();
+    let mods = if is_vanilla(server_name)? {
+        Vec::new()
+    } else {
+        find_mod_updates(server_name, client).await?
+    };
+
+    Ok(KnownUpdates { mods, server })
+}
+
+/// Offers the setup wizard if no server is installed yet, otherwise prints the help like
+/// clap does for a missing command, and fails with [`MissingCommandError`].
+pub async fn run_without_command(client: &Client) -> anyhow::Result<()> {
+    if wizard::offer(!server_names()?.is_empty(), client).await? {
+        return Ok(());
+    }
+
+    Cli::command().print_help()?;
+    Err(MissingCommandError.into())
+}
+
+pub(crate) async fn modrinth_whoami(client: &Client) -> anyhow::Result<()> {
+    let user = modrinth::whoami(client).await?;
+    println!(
+        "The Modrinth token belongs to {} ({})",
+        user.username, user.id
+    );
+    Ok(())
+}
+
+/// Posts a test notification to every webhook of the global config, failing if any
+/// of them can't be reached.
+pub(crate) async fn test_notifications(client: &Client) -> anyhow::Result<()> {
+    let notifier = notifier(Some(client));
+    if notifier.webhooks().is_empty() {
+        anyhow::bail!(
+            "No webhooks are set up in [notifications] of {}",
+            global_config_path().display()
+        );
+    }
+
+    let failures = notifier.send(&Notification::Test).await;
+    for webhook in notifier.webhooks() {
+        match failures.iter().find(|(url, _)| *url == webhook.url) {
+            Some((_, e)) => println!("[FAIL] {}: {e}", webhook.url),
+            None => println!("[OK] {}", webhook.url),
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} of {} webhooks failed",
+            failures.len(),
+            notifier.webhooks().len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Posts to the webhooks of the global config. Without a client, one is built with the
+/// config's network settings. Nothing is sent if the config can't be loaded, since
+/// notifications never fail a command.
+fn notifier(client: Option<&Client>) -> Notifier {
+    let notifier = GlobalConfig::load(&global_config_path()).and_then(|config| {
+        let client = match client {
+            Some(client) => client.clone(),
+            None => network::build_client(&config.network)?,
+        };
+        Ok(Notifier::new(client, config.notifications))
+    });
+    notifier.unwrap_or_else(|e| {
+        eprintln!("Warning: notifications are disabled: {e}");
+        Notifier::new(Client::new(), NotificationSettings::default())
+    })
+}
+
+/// Serves the Prometheus metrics of every managed server at `/metrics` on the address,
+/// collecting them in the background.
+pub(crate) async fn serve_metrics(listen: SocketAddr) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+
+    let rendered = Arc::new(RwLock::new(String::new()));
+    let collected = rendered.clone();
+    tokio::spawn(async move {
+        let mut collector = Collector::default();
+        loop {
+            let servers = match server_names() {
+                Ok(names) => names
+                    .into_iter()
+                    .filter_map(|name| server_dir(&name).ok().map(|dir| (name, dir)))
+                    .collect(),
+                Err(e) => {
+                    eprintln!("Failed to list the servers: {e}");
+                    Vec::new()
+                }
+            };
+            let metrics = metrics::render(&collector.collect(&servers).await);
+            *collected.write().unwrap() = metrics;
+            tokio::time::sleep(metrics::COLLECT_INTERVAL).await;
+        }
+    });
+
+    metrics::serve(listener, rendered).await?;
+    Ok(())
+}
+
+/// Prints the status of the server, by the name of a managed server or `host[:port]`.
+pub(crate) async fn ping_server(target: &str) -> anyhow::Result<()> {
+    let (host, port) = ping_address(target)?;
+
+    if host.contains(':') {
+        println!("Pinging [{host}]:{port}...");
+    } else {
+        println!("Pinging {host}:{port}...");
+    }
+    let status = ping::ping(&host, port, Duration::from_secs(5)).await?;
+    println!("{status}");
+
+    Ok(())
+}
+
+/// The host and port of the managed server named `target`, or else of `target` itself as
+/// `host[:port]`.
+fn ping_address(target: &str) -> anyhow::Result<(String, u16)> {
+    // Addresses that aren't a valid server name fail to have a directory
+    if let Ok(server_dir) = server_dir(target)
+        && server_dir.is_dir()
+    {
+        return Ok((
+            "localhost".to_string(),
+            process::configured_port(&server_dir),
+        ));
+    }
+
+    // A port after an IPv6 address only goes with brackets, like `[::1]:25565`
+    if let Some(bracketed) = target.strip_prefix('[') {
+        let invalid = || anyhow::anyhow!("{target} isn't an address like [::1]:25565");
+        let (host, rest) = bracketed.split_once(']').ok_or_else(invalid)?;
+        let port = match rest {
+            "" => process::DEFAULT_PORT,
+            rest => rest.strip_prefix(':').ok_or_else(invalid)?.parse()?,
+        };
+        return Ok((host.to_string(), port));
+    }
+    Ok(match target.rsplit_once(':') {
+        Some(_) if target.matches(':').count() > 1 => (target.to_string(), process::DEFAULT_PORT),
+        Some((host, port)) => (host.to_string(), port.parse()?),
+        None => (target.to_string(), process::DEFAULT_PORT),
+    })
+}
+
+/// Prints whether the server, or all servers if `None`, are running.
+pub(crate) async fn show_status(server_name: Option<&str>) -> anyhow::Result<()> {
+    let server_names = match server_name {
+        Some(server_name) => {
+            try_server_dir(server_name)?;
+            vec![server_name.to_string()]
+        }
+        None => server_names()?,
+    };
+
+    let mut table = Table::new();
+    table.add_row(row![
+        "Server", "Status", "PID", "Uptime", "Memory", "Players"
+    ]);
+    for server_name in server_names {
+        let server_dir = server_dir(&server_name)?;
+        match process::running_process(&server_dir)? {
+            Some(status) => {
+                let port = process::configured_port(&server_dir);
+                // Still starting up, or not accepting status requests
+                let players = match ping::ping("localhost", port, Duration::from_secs(1)).await {
+                    Ok(ServerStatus {
+                        players: Some(players),
+                        ..
+                    }) => format!("{}/{}", players.online, players.max),
+                    _ => "-".to_string(),
+                };
+                table.add_row(row![
+                    server_name,
+                    "running",
+                    status.pid,
+                    format::format_duration(status.uptime),
+                    format::format_bytes(status.memory),
+                    players
+                ])
+            }
+            None => table.add_row(row![server_name, "stopped", "-", "-", "-", "-"]),
+        };
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Renames the server. One installed with `--location` stays where it is and is only
+/// registered under the new name, the others are moved in the instances directory.
+pub(crate) fn rename_server(server_name: &str, new_name: &str) -> anyhow::Result<()> {
+    let old_dir = try_server_dir(server_name)?;
+    if server_dir(new_name)?.exists() {
+        anyhow::bail!("{new_name} already exists");
+    }
+    {
+        let _lock = lock_server(&old_dir, "rename")?;
+        if let Some(status) = process::running_process(&old_dir)? {
+            return Err(ServerProcessError::AlreadyRunning(status.pid).into());
+        }
+    }
+
+    let mut registry = Registry::load(&registry_path())?;
+    if registry.rename(server_name, new_name) {
+        registry.save(&registry_path())?;
+    } else {
+        fs::rename(&old_dir, instances_dir().join(new_name))?;
+    }
+
+    let path = global_config_path();
+    for (group_name, mut group) in GlobalConfig::load(&path)?.groups {
+        let mut renamed = false;
+        for member in &mut group.members {
+            if member.server == server_name {
+                member.server = new_name.to_string();
+                renamed = true;
+            }
+        }
+        if renamed {
+            GlobalConfig::save_group(&path, &group_name, &group)?;
+        }
+    }
+
+    println!("Renamed {server_name} to {new_name}");
+    Ok(())
+}
+
+/// Moves the instances directory into `new_data_dir` and records it in the global config,
+/// so later commands find the servers there.
+pub(crate) fn migrate_data(new_data_dir: &Path) -> anyhow::Result<()> {
+    let new_data_dir = std::path::absolute(new_data_dir)?;
+    let old_dir = instances_dir();
+    let new_dir = new_data_dir.join("instances");
+    if new_dir == old_dir {
+        anyhow::bail!("The servers are already in {}", new_dir.display());
+    }
+
+    for server_name in server_names()? {
+        if let Some(status) = process::running_process(&server_dir(&server_name)?)? {
+            anyhow::bail!(
+                "{server_name} is running (pid {}), stop it before moving the servers",
+                status.pid
+            );
+        }
+    }
+
+    if new_dir.exists() {
+        if fs::read_dir(&new_dir)?.next().is_some() {
+            anyhow::bail!("{} already exists and isn't empty", new_dir.display());
+        }
+        fs::remove_dir(&new_dir)?;
+    }
+
+    fs::create_dir_all(&new_data_dir)?;
+    if old_dir.exists() {
+        println!("Moving {} to {}...", old_dir.display(), new_dir.display());
+        world::move_dir(&old_dir, &new_dir)?;
+    } else {
+        fs::create_dir_all(&new_dir)?;
+    }
+    // The servers installed with `--location` stay where they are
+    let old_registry = registry_path();
+    if old_registry.exists() {
+        let new_registry = new_data_dir.join(REGISTRY_FILE);
+        fs::copy(&old_registry, new_registry)?;
+        fs::remove_file(old_registry)?;
+    }
+
+    GlobalConfig::save_data_dir(&global_config_path(), &new_data_dir)?;
+    set_data_dir(Some(new_data_dir.clone()));
+    println!(
+        "Servers are now kept in {}, recorded in {}",
+        new_dir.display(),
+        global_config_path().display()
+    );
+    if std::env::var_os(DATA_DIR_VAR).is_some_and(|dir| !dir.is_empty()) {
+        println!("Note: {DATA_DIR_VAR} is set, and takes precedence over the global config.");
+    }
+
+    Ok(())
+}
+
+/// Lists the leftovers in the server directory that can be removed, and removes them with
+/// `apply`. See [`clean::classify`] for what counts as one.
+pub(crate) fn clean_server(server_name: &str, apply: bool) -> anyhow::Result<()> {
+    let server_dir = try_server_dir(server_name)?;
+    let _lock = lock_server(&server_dir, "clean")?;
+    let config = Config::load_or_create(server_name)?;
+    let patterns = GlobalConfig::load(&global_config_path())?.clean.patterns;
+    let world_dir = metrics::world_dir(&server_dir);
+    let protected = clean::Protected {
+        jar_name: &config.jar_name,
+        level_name: &world_dir.file_name().unwrap_or_default().to_string_lossy(),
+    };
+
+    let files = clean::list_files(&server_dir)?;
+    let report = clean::CleanReport(clean::classify(&files, &protected, &patterns));
+    if report.0.is_empty() {
+        println!("Nothing to clean in {server_name}");
+        return Ok(());
+    }
+    println!("{report}");
+    if !apply {
+        println!("Nothing was removed. Pass --apply to remove them.");
+        return Ok(());
+    }
+
+    for junk in &report.0 {
+        let path = server_dir.join(&junk.path);
+        fs::remove_file(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to remove {}: {e}", path.display()))?;
+    }
+    println!(
+        "Removed {} files, freeing {}",
+        report.0.len(),
+        format::format_bytes(report.total_size())
+    );
+    Ok(())
+}
+
+/// Checks what commonly stops mcerv or the server from working, and fails if any check fails.
+/// Without a server, only the checks not about a particular server are run.
+pub(crate) async fn doctor(
+    server_name: Option<&str>,
+    offline: bool,
+    client: &Client,
+) -> anyhow::Result<()> {
+    let cache_dir = download_cache().dir().to_path_buf();
+    let mut results = vec![
+        doctor::instances_dir_writable(&instances_dir()),
+        doctor::global_config(&global_config_path()),
+        doctor::registry(&registry_path()),
+    ];
+    let mut download_dirs = vec![cache_dir];
+
+    if let Some(server_name) = server_name {
+        let server_dir = try_server_dir(server_name)?;
+        let (config_result, config) = doctor::server_config(&server_dir);
+        let (jar_result, info) = doctor::server_jar(&server_dir);
+        let game_version = info.as_ref().map(|info| info.game_version.as_str());
+        let other_servers: Vec<(String, PathBuf)> = server_names()?
+            .into_iter()
+            .filter(|other| other != server_name)
+            .filter_map(|other| self::server_dir(&other).ok().map(|dir| (other, dir)))
+            .collect();
+
+        results.push(config_result);
+        results.push(jar_result);
+        results.push(doctor::java(
+            config
+                .as_ref()
+                .and_then(|config| config.java_home.as_deref()),
+            game_version,
+        ));
+        results.push(doctor::eula(&server_dir));
+        results.push(doctor::port(&server_dir, &other_servers));
+        if let Some(game_version) = game_version {
+            results.push(doctor::mod_conflicts(&mods_dir(server_name)?, game_version));
+        }
+        if mods_dir(server_name)?.exists() {
+            let found = find_client_only_mods(server_name, offline, client).await;
+            results.push(doctor::client_only_mods(server_name, found));
+        }
+        download_dirs.push(mods_dir(server_name)?);
+        download_dirs.push(server_dir);
+    }
+
+    results.push(doctor::leftover_downloads(&download_dirs));
+    results.push(doctor::disk_space(&data_dir(), doctor::MIN_FREE_SPACE));
+    if !offline {
+        results.push(doctor::reachable(client, "Modrinth", "https://api.modrinth.com/v2").await);
+        results.push(
+            doctor::reachable(
+                client,
+                "Fabric meta",
+                "https://meta.fabricmc.net/v2/versions",
+            )
+            .await,
+        );
+    }
+
+    for result in &results {
+        println!("{result}");
+    }
+
+    let failed = results
+        .iter()
+        .filter(|result| result.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} checks failed", results.len());
+    }
+
+    Ok(())
+}
+
+/// Warns about mods the loader may crash on, offering to disable the older jars of each
+/// duplicate. Never fails the start, the loader tells what went wrong best.
+fn check_mod_conflicts(server_name: &str) -> anyhow::Result<()> {
+    let mods_dir = mods_dir(server_name)?;
+    if !mods_dir.exists() {
+        return Ok(());
+    }
+    let Ok(server_info) = ServerInfo::cached_or_detect(server_name) else {
+        return Ok(());
+    };
+
+    let jars = mods::mod_jars(&mods_dir, &server_info.game_version)?;
+    for conflict in mods::find_conflicts(&jars)? {
+        eprintln!("Warning: {conflict}");
+        let ModConflict::Duplicate { jars, .. } = conflict else {
+            continue;
+        };
+        let (newest, older) = jars.split_last().unwrap();
+        let older_names = older
+            .iter()
+            .map(|jar| jar.file_name().unwrap().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Without a terminal, like under systemd, the jars are left for the user to decide
+        let should_disable = prompt::confirm(
+            Danger::Destructive,
+            &format!(
+                "Do you want to disable {older_names}, keeping {}?",
+                newest.file_name().unwrap().to_string_lossy()
+            ),
+        )?;
+        if should_disable {
+            if let Err(e) = confirm_mods_dir_change(&mods_dir) {
+                eprintln!("Warning: {e}");
+                continue;
+            }
+            for jar in older {
+                mods::disable(jar)?;
+            }
+            println!("Disabled {older_names}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails if the server is already running, or its port is taken.
+fn ensure_can_start(server_name: &str) -> anyhow::Result<()> {
+    let server_dir = server_dir(server_name)?;
+    if let Some(status) = process::running_process(&server_dir)? {
+        return Err(ServerProcessError::AlreadyRunning(status.pid).into());
+    }
+
+    let port = process::configured_port(&server_dir);
+    for other in server_names()? {
+        let other_dir = self::server_dir(&other)?;
+        if other != server_name
+            && process::configured_port(&other_dir) == port
+            && process::running_process(&other_dir)?.is_some()
+        {
+            return Err(ServerProcessError::PortUsedByServer {
+                port,
+                server_name: other,
+            }
+            .into());
+        }
+    }
+
+    if process::is_port_in_use(port) {
+        return Err(ServerProcessError::PortInUse(port).into());
+    }
+
+    Ok(())
+}
+
+/// The names of the servers in the instances directory and the registry, skipping
+/// directories that aren't a valid [`ServerName`].
+fn server_names() -> anyhow::Result<Vec<String>> {
+    let dir = instances_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names: Vec<String> = Registry::load(&registry_path())?
+        .servers
+        .into_keys()
+        .collect();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.path().is_dir() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    names.retain(|name| match server_name::validate(name) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Skipping {name}: {e}. Rename its directory to manage it with mcerv");
+            false
+        }
+    });
+    names.sort();
+    names.dedup();
+
+    Ok(names)
+}
+
+pub(crate) fn try_mods_dir(server_name: &str) -> Result<PathBuf, DirectoryError> {
+    let dir = mods_dir(server_name)?;
+
+    if !dir.exists() {
+        return Err(DirectoryError::ModsDirDoesNotExist(dir));
+    }
+    if !dir.is_dir() {
+        return Err(DirectoryError::ModsDirNotADirectory(dir));
+    }
+
+    Ok(dir)
+}
+
+/// Checks that the mods in the mods directory may be changed. A symlinked one may be shared
+/// with other servers, so changing the mods through it takes `--follow-symlink` or a
+/// confirmation, which holds for the rest of the process.
+pub(crate) fn confirm_mods_dir_change(mods_dir: &Path) -> anyhow::Result<()> {
+    let Some(target) = mods::symlink_target(mods_dir) else {
+        return Ok(());
+    };
+    if FOLLOW_SYMLINK.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let confirmed = prompt::confirm(
+        Danger::Guarded,
+        &format!(
+            "{} is a symlink to {}. Changing its mods changes them for every server \
+             sharing it. Continue?",
+            mods_dir.display(),
+            target.display()
+        ),
+    )?;
+    if !confirmed {
+        return Err(DirectoryError::ModsDirSymlinked(mods_dir.to_path_buf(), target).into());
+    }
+    set_follow_symlink(true);
+    Ok(())
+}
+
+/// Prints a notice if the mods directory is a symlink, so it's clear other servers may see
+/// the same mods.
+fn print_mods_dir_symlink_notice(mods_dir: &Path) {
+    if let Some(target) = mods::symlink_target(mods_dir) {
+        println!(
+            "Notice: {} is a symlink to {}. Other servers sharing it have the same mods, \
+             and changing them asks first unless --follow-symlink is passed",
+            mods_dir.display(),
+            target.display()
+        );
+    }
+}
+
+/// The mods directory of a modded server, created if it doesn't have one yet, like right
+/// after installing. `None` for vanilla servers, which don't load mods.
+fn prepare_mods_dir(server_dir: &Path, fork: ServerFork) -> anyhow::Result<Option<PathBuf>> {
+    if fork == ServerFork::Vanilla {
+        return Ok(None);
+    }
+
+    let dir = server_dir.join("mods");
+    if dir.exists() && !dir.is_dir() {
+        return Err(DirectoryError::ModsDirNotADirectory(dir).into());
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(Some(dir))
+}
+
+pub(crate) fn try_server_dir(server_name: &str) -> Result<PathBuf, DirectoryError> {
+    let dir = server_dir(server_name)?;
+
+    if !dir.exists() {
+        return Err(DirectoryError::ServerDirDoesNotExist(dir));
+    }
+
+    Ok(dir)
+}
+
+pub(crate) fn mods_dir(server_name: &str) -> Result<PathBuf, InvalidServerNameError> {
+    Ok(server_dir(server_name)?.join("mods"))
+}
+
+/// The directory of the server, where the registry says if it was installed with
+/// `--location`. A broken registry is left to `mcerv doctor` to report. Names that aren't a
+/// valid [`ServerName`] are refused, so they can't point outside [`instances_dir`].
+pub(crate) fn server_dir(server_name: &str) -> Result<PathBuf, InvalidServerNameError> {
+    server_name::validate(server_name)?;
+    Ok(Registry::load(&registry_path())
+        .ok()
+        .and_then(|mut registry| registry.remove(server_name))
+        .unwrap_or_else(|| instances_dir().join(server_name)))
+}
+
+pub(crate) fn registry_path() -> PathBuf {
+    data_dir().join(REGISTRY_FILE)
+}
+
+/// Where the servers are kept, overridden by `--data-dir`, [`DATA_DIR_VAR`] or the global
+/// config in that order.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR.read().unwrap().clone() {
+        return dir;
+    }
+    data_dir_from(std::env::var_os(DATA_DIR_VAR))
+}
+
+/// The data directory for the value of [`DATA_DIR_VAR`], the default if it's unset or empty.
+fn data_dir_from(env: Option<OsString>) -> PathBuf {
+    match env {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => proj_dirs().data_dir().to_path_buf(),
+    }
+}
+
+/// Makes [`data_dir`] return `dir`, for the one resolved with [`resolve_data_dir`] at startup.
+pub fn set_data_dir(dir: Option<PathBuf>) {
+    *DATA_DIR.write().unwrap() = dir;
+}
+
+/// The data directory to use, preferring the flag over the environment variable over the
+/// global config. `None` if none of them is set.
+pub(crate) fn resolve_data_dir(
+    flag: Option<PathBuf>,
+    env: Option<OsString>,
+    configured: Option<PathBuf>,
+) -> Option<PathBuf> {
+    flag.or(env.filter(|env| !env.is_empty()).map(PathBuf::from))
+        .or(configured)
+}
 
 pub fn instances_dir() -> PathBuf {
-    proj_dirs().data_dir().join("instances")
+    data_dir().join("instances")
+}
+
+pub(crate) fn templates_dir() -> PathBuf {
+    proj_dirs().config_dir().join("templates")
+}
+
+pub(crate) fn global_config_path() -> PathBuf {
+    proj_dirs().config_dir().join("config.toml")
+}
+
+pub(crate) fn download_cache() -> DownloadCache {
+    DownloadCache::new(proj_dirs().cache_dir().join("downloads"))
 }
 
-pub fn proj_dirs() -> ProjectDirs {
+pub(crate) fn proj_dirs() -> ProjectDirs {
     ProjectDirs::from("", "", "mcerv").expect("Unable to determine project directory")
 }
 
-async fn install_from_command(
-    server_name: &str,
-    command: InstallCommand,
-    client: &Client,
-) -> anyhow::Result<String> {
-    match command {
-        InstallCommand::Vanilla { version_args } => {
-            println!("Fetching versions...");
-            let version = version_args.versions(client).await?;
-            println!("Downloading server jar...");
-            forks::Vanilla::install(server_name, version, client).await
-        }
-        InstallCommand::Fabric { version_args } => {
-            println!("Fetching versions...");
-            let versions = version_args.versions(client).await?;
-            println!("Downloading server jar...");
-            forks::Fabric::install(server_name, versions, client).await
-        }
-        InstallCommand::Forge { version_args } => {
-            println!("Fetching versions...");
-            let versions = version_args.versions(client).await?;
-            println!("Installing server jar...");
-            forks::Forge::install(server_name, versions, client).await
-        }
-    }
-}
-
-fn is_vanilla(server_name: &str) -> anyhow::Result<bool> {
-    let server_jar_name = Config::load_or_create(server_name)?.jar_name;
-    let server_jar_path = server_dir(server_name).join(&server_jar_name);
-    let mut archive = jar_parser::archive(server_jar_path)?;
-    Ok(matches!(
-        forks::detect_server_fork(&mut archive)?,
-        ServerFork::Vanilla
-    ))
+/// Refuses to replace the jar with an older game version than the current jar's or the one
+/// the world was last opened with.
+fn ensure_not_downgrade(server_name: &str, current: &str, target: &str) -> anyhow::Result<()> {
+    match LevelInfo::load(&server_dir(server_name)?) {
+        Ok(Some(LevelInfo {
+            version_name: Some(world_version),
+            ..
+        })) if version::is_newer(&world_version, target) => {
+            return Err(DowngradeError::World {
+                world_version,
+                target: target.to_string(),
+            }
+            .into());
+        }
+        Ok(_) => {}
+        // The jar check still applies
+        Err(e) => eprintln!("Warning: failed to read the world's version: {e}"),
+    }
+
+    if version::is_newer(current, target) {
+        return Err(DowngradeError::Jar {
+            current: current.to_string(),
+            target: target.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Whether the server is vanilla, from the fork cached in its config so the jar isn't
+/// opened, or detected if there's none. Not being able to tell is an error rather than either.
+fn is_vanilla(server_name: &str) -> Result<bool, UnknownForkError> {
+    server_fork(server_name).map(|fork| fork == ServerFork::Vanilla)
+}
+
+/// The fork cached in the server's config, or detected from the jar if there is none.
+fn server_fork(server_name: &str) -> Result<ServerFork, UnknownForkError> {
+    let cached = Config::load(server_name)
+        .ok()
+        .flatten()
+        .and_then(|config| config.detection.detected_fork)
+        .filter(|_| use_detection_cache());
+    let fork = match cached {
+        Some(fork) => Ok(fork),
+        None => ServerInfo::cached_or_detect(server_name).map(|info| info.server_fork),
+    };
+    fork.map_err(|source| UnknownForkError {
+        server_name: server_name.to_string(),
+        source,
+    })
+}
+
+/// The server info for commands that only read the mods, which also work while the jar is
+/// missing, see [`ServerInfo::cached_or_last_known`].
+fn last_known_server_info(server_name: &str) -> Result<ServerInfo, UnknownForkError> {
+    ServerInfo::cached_or_last_known(server_name).map_err(|source| UnknownForkError {
+        server_name: server_name.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::modrinth::VersionFile,
+        system::jar_parser::{FileHash, HashAlgorithm},
+    };
+    use sha2::Digest;
+
+    #[tokio::test]
+    async fn test_update_mod_trashes_old_files() {
+        let mut server = mockito::Server::new_async().await;
+        let content = b"sodium 0.6.14";
+        server
+            .mock("GET", "/sodium-0.6.14.jar")
+            .with_body(content)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mods_dir = dir.path().join("mods");
+        fs::create_dir_all(&mods_dir).unwrap();
+        let jar_path = mods_dir.join("sodium-0.6.13.jar");
+        let old_extra = mods_dir.join("sodium-fabric-api-0.6.13.jar");
+        fs::write(&jar_path, "old").unwrap();
+        fs::write(&old_extra, "old api").unwrap();
+
+        let sha512 = format!("{:x}", sha2::Sha512::digest(content));
+        let update = ModUpdate {
+            jar_path: jar_path.clone(),
+            old_files: vec![old_extra.clone()],
+            version: ModVersion {
+                id: "gl30uZvp".to_string(),
+                project_id: "AANobbMI".to_string(),
+                version_name: "mc1.21.8-0.6.14".to_string(),
+                version_number: "0.6.14".to_string(),
+                files: vec![VersionFile {
+                    url: format!("{}/sodium-0.6.14.jar", server.url()),
+                    file_name: "sodium-0.6.14.jar".to_string(),
+                    hash: FileHash {
+                        algorithm: HashAlgorithm::Sha512,
+                        value: sha512.clone(),
+                    },
+                    sha512,
+                    primary: true,
+                    file_type: None,
+                }],
+                dependencies: Vec::new(),
+            },
+        };
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let trash = TrashBatch::begin(dir.path(), "mods update");
+
+        update_mod(&Client::new(), &mods_dir, &update, &cache, &trash)
+            .await
+            .unwrap();
+        assert!(!jar_path.exists());
+        assert!(!old_extra.exists());
+        assert_eq!(
+            fs::read(mods_dir.join("sodium-0.6.14.jar")).unwrap(),
+            content
+        );
+
+        assert!(trash.restore(&old_extra).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_update_mod_records_leftover_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_dir = dir.path().join("mods");
+        fs::create_dir_all(&mods_dir).unwrap();
+        let jar_path = mods_dir.join("sodium-0.6.13.jar");
+        fs::write(&jar_path, "old").unwrap();
+        // Left by an interrupted update
+        let content = b"sodium 0.6.14";
+        let leftover = mods_dir.join("sodium-0.6.14.jar");
+        fs::write(&leftover, content).unwrap();
+
+        let sha512 = format!("{:x}", sha2::Sha512::digest(content));
+        let update = ModUpdate {
+            jar_path: jar_path.clone(),
+            old_files: Vec::new(),
+            version: ModVersion {
+                id: "gl30uZvp".to_string(),
+                project_id: "AANobbMI".to_string(),
+                version_name: "mc1.21.8-0.6.14".to_string(),
+                version_number: "0.6.14".to_string(),
+                files: vec![VersionFile {
+                    url: "http://127.0.0.1:9/sodium-0.6.14.jar".to_string(),
+                    file_name: "sodium-0.6.14.jar".to_string(),
+                    hash: FileHash {
+                        algorithm: HashAlgorithm::Sha512,
+                        value: sha512.clone(),
+                    },
+                    sha512,
+                    primary: true,
+                    file_type: None,
+                }],
+                dependencies: Vec::new(),
+            },
+        };
+        let cache = DownloadCache::new(dir.path().join("cache"));
+        let trash = TrashBatch::begin(dir.path(), "mods update");
+
+        update_mod(&Client::new(), &mods_dir, &update, &cache, &trash)
+            .await
+            .unwrap();
+        drop(trash);
+        let undone = trash::undo(dir.path()).unwrap().unwrap();
+        assert_eq!(
+            undone.removed,
+            [Path::new("mods").join("sodium-0.6.14.jar")]
+        );
+        assert!(!leftover.exists());
+        assert_eq!(fs::read_to_string(&jar_path).unwrap(), "old");
+    }
+
+    #[tokio::test]
+    async fn test_roll_back_mods_downloads_then_trashes() {
+        let mut server = mockito::Server::new_async().await;
+        let content = b"sodium 0.6.13";
+        let sha512 = format!("{:x}", sha2::Sha512::digest(content));
+        let version = serde_json::json!([{
+            "id": "old-sodium",
+            "project_id": "AANobbMI",
+            "files": [{
+                "url": format!("{}/sodium-0.6.13.jar", server.url()),
+                "filename": "sodium-0.6.13.jar",
+                "hashes": { "sha512": sha512 },
+                "primary": true,
+            }],
+        }]);
+        server
+            .mock("GET", "/versions")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "ids".to_string(),
+                r#"["old-sodium"]"#.to_string(),
+            ))
+            .with_body(version.to_string())
+            .create_async()
+            .await;
+        // Modrinth doesn't know the old lithium version anymore
+        server
+            .mock("GET", "/versions")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "ids".to_string(),
+                r#"["old-lithium"]"#.to_string(),
+            ))
+            .with_body("[]")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/sodium-0.6.13.jar")
+            .with_body(content)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path().join("smp");
+        let mods_dir = server_dir.join("mods");
+        fs::create_dir_all(&mods_dir).unwrap();
+        fs::write(mods_dir.join("sodium-0.6.14.jar"), "new").unwrap();
+        fs::write(mods_dir.join("lithium-0.19.0.jar"), "new").unwrap();
+        let change = |name: &str, old: &str, new: &str| ModChange {
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+            project_id: Some(format!("{name}-project")),
+            old_version_id: Some(format!("old-{name}")),
+            new_version_id: Some(format!("new-{name}")),
+        };
+        let located = vec![
+            (
+                change("sodium", "sodium-0.6.13.jar", "sodium-0.6.14.jar"),
+                OldJar::Download("old-sodium".to_string()),
+            ),
+            (
+                change("lithium", "lithium-0.18.0.jar", "lithium-0.19.0.jar"),
+                OldJar::Download("old-lithium".to_string()),
+            ),
+        ];
+        let mut update = history::Event::new(rollback::UPDATE_OPERATION);
+        update.trash_batch = Some("20250814-180300.412".to_string());
+        let cache = DownloadCache::new(dir.path().join("cache"));
+
+        let (trash, failed) = roll_back_mods(
+            &server_dir,
+            &update,
+            located,
+            &server.url(),
+            &cache,
+            &Client::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(failed, 1);
+        assert_eq!(
+            fs::read(mods_dir.join("sodium-0.6.13.jar")).unwrap(),
+            content
+        );
+        assert!(!mods_dir.join("sodium-0.6.14.jar").exists());
+        // Left at its new version rather than without either
+        assert!(mods_dir.join("lithium-0.19.0.jar").exists());
+        assert!(!trash.is_empty());
+
+        let (events, _) = history::read(&server_dir).unwrap();
+        let [rollback] = events.as_slice() else {
+            panic!("expected only the rollback: {events:?}");
+        };
+        assert_eq!(rollback.rolled_back.as_deref(), Some("20250814-180300.412"));
+        assert_eq!(rollback.mods.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_data_dir() {
+        let flag = Some(PathBuf::from("/flag"));
+        let env = Some(OsString::from("/env"));
+        let configured = Some(PathBuf::from("/configured"));
+
+        let dir = resolve_data_dir(flag.clone(), env.clone(), configured.clone());
+        assert_eq!(dir, flag);
+        let dir = resolve_data_dir(None, env, configured.clone());
+        assert_eq!(dir, Some(PathBuf::from("/env")));
+        let dir = resolve_data_dir(None, Some(OsString::new()), configured.clone());
+        assert_eq!(dir, configured);
+        assert_eq!(resolve_data_dir(None, None, None), None);
+    }
+
+    #[test]
+    fn test_prepare_mods_dir() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A fresh Fabric server has no mods directory yet
+        let mods_dir = prepare_mods_dir(dir.path(), ServerFork::Fabric).unwrap();
+        assert_eq!(mods_dir, Some(dir.path().join("mods")));
+        assert!(dir.path().join("mods").is_dir());
+        assert_eq!(
+            mods::mod_jars(&dir.path().join("mods"), "1.21.8")
+                .unwrap()
+                .len(),
+            0
+        );
+        // Already there is fine too
+        assert!(prepare_mods_dir(dir.path(), ServerFork::Forge).is_ok());
+
+        let vanilla = tempfile::tempdir().unwrap();
+        assert_eq!(
+            prepare_mods_dir(vanilla.path(), ServerFork::Vanilla).unwrap(),
+            None
+        );
+        assert!(!vanilla.path().join("mods").exists());
+
+        let broken = tempfile::tempdir().unwrap();
+        fs::write(broken.path().join("mods"), "").unwrap();
+        let error = prepare_mods_dir(broken.path(), ServerFork::Fabric).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(DirectoryError::ModsDirNotADirectory(_))
+        ));
+    }
+
+    #[test]
+    fn test_ping_address() {
+        assert_eq!(
+            ping_address("127.0.0.1:1").unwrap(),
+            ("127.0.0.1".to_string(), 1)
+        );
+        assert_eq!(
+            ping_address("mc.example.com").unwrap(),
+            ("mc.example.com".to_string(), process::DEFAULT_PORT)
+        );
+        assert!(ping_address("mc.example.com:port").is_err());
+
+        // IPv6 addresses take a port only in brackets
+        assert_eq!(
+            ping_address("::1").unwrap(),
+            ("::1".to_string(), process::DEFAULT_PORT)
+        );
+        assert_eq!(
+            ping_address("[::1]:25566").unwrap(),
+            ("::1".to_string(), 25566)
+        );
+        assert_eq!(
+            ping_address("[2001:db8::1]").unwrap(),
+            ("2001:db8::1".to_string(), process::DEFAULT_PORT)
+        );
+        assert!(ping_address("[::1]25566").is_err());
+        assert!(ping_address("[::1").is_err());
+    }
+
+    #[test]
+    fn test_data_dir_env_var() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            data_dir_from(Some(dir.path().into())),
+            dir.path().to_path_buf()
+        );
+        assert_eq!(
+            data_dir_from(Some(OsString::new())),
+            proj_dirs().data_dir().to_path_buf()
+        );
+        assert_eq!(data_dir_from(None), proj_dirs().data_dir().to_path_buf());
+
+        assert!(server_dir("../survival").is_err());
+        assert!(matches!(
+            try_server_dir(".."),
+            Err(DirectoryError::InvalidServerName(_))
+        ));
+    }
 }
@@ -1,9 +1,41 @@
 use clap::Parser;
-use mcerv::{instances_dir, system::cli::Cli};
+use mcerv::{
+    MissingCommandError, instances_dir, run_without_command, set_assume_yes, set_follow_symlink,
+    set_no_cache, set_non_interactive, set_progress_json, set_wait_for_lock,
+    system::{cancel, cli::Cli},
+};
 use std::fs;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    cli.apply_data_dir()?;
     fs::create_dir_all(instances_dir()).expect("Unable to create instances directory");
-    Cli::parse().command.run().await
+    set_non_interactive(cli.non_interactive);
+    set_assume_yes(cli.yes);
+    set_no_cache(cli.no_cache);
+    set_wait_for_lock(cli.wait);
+    set_follow_symlink(cli.follow_symlink);
+    set_progress_json(cli.progress_json);
+    let client = cli.client()?;
+    cancel::cancel_on_ctrl_c();
+    let result = match cli.command {
+        Some(command) => command.run(&client).await,
+        None => run_without_command(&client).await,
+    };
+    if let Err(e) = &result {
+        if cancel::is_cancelled(e) {
+            eprintln!("{}", cancel::CancelledError);
+            std::process::exit(cancel::CANCELLED_EXIT_CODE);
+        }
+        // Printed like the errors of parsing the arguments
+        if let Some(e) = e.downcast_ref::<clap::Error>() {
+            e.exit();
+        }
+        // The help is printed already
+        if e.is::<MissingCommandError>() {
+            std::process::exit(2);
+        }
+    }
+    result
 }
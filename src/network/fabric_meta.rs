@@ -1,17 +1,89 @@
-use crate::network::{PrintVersionMode, download_file, fetch_text};
+use crate::network::{DownloadStats, PrintVersionMode, VersionEntry, download_file, fetch_text};
 use anyhow::anyhow;
-use prettytable::{Table, row};
 use reqwest::Client;
-use serde::de::DeserializeOwned;
+use serde::{Deserialize, de::DeserializeOwned};
 use std::path::Path;
 
+/// An entry of the fabric meta game, loader or installer version lists.
+/// The endpoints return more fields, but only these are needed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FabricVersion {
+    pub version: String,
+    pub stable: bool,
+}
+
+/// All version lists of fabric meta, newest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FabricVersions {
+    pub game: Vec<FabricVersion>,
+    pub loader: Vec<FabricVersion>,
+    pub installer: Vec<FabricVersion>,
+}
+
+impl FabricVersions {
+    /// Converts the lists to entries labeled by their component.
+    pub fn entries(&self, print_mode: PrintVersionMode) -> Vec<VersionEntry> {
+        [
+            ("Minecraft", &self.game),
+            ("Fabric Loader", &self.loader),
+            ("Installer", &self.installer),
+        ]
+        .into_iter()
+        .flat_map(|(component, versions)| {
+            versions
+                .iter()
+                .filter(move |v| matches!(print_mode, PrintVersionMode::All) || v.stable)
+                .map(move |v| VersionEntry {
+                    component: Some(component),
+                    ..VersionEntry::new(&v.version, v.stable)
+                })
+        })
+        .collect()
+    }
+
+    /// Returns the latest stable (game, loader, installer) versions.
+    pub fn latest_stable(&self) -> anyhow::Result<(String, String, String)> {
+        let latest = |versions: &[FabricVersion], kind: &str| {
+            versions
+                .iter()
+                .find(|v| v.stable)
+                .map(|v| v.version.clone())
+                .ok_or(anyhow!("Failed to find stable {kind} version"))
+        };
+
+        Ok((
+            latest(&self.game, "minecraft")?,
+            latest(&self.loader, "fabric loader")?,
+            latest(&self.installer, "fabric installer")?,
+        ))
+    }
+
+    /// Returns the given versions that don't exist in the lists, labeled by their kind.
+    pub fn unknown_versions(
+        &self,
+        game_version: &str,
+        loader_version: &str,
+        installer_version: &str,
+    ) -> Vec<String> {
+        [
+            ("Minecraft version", game_version, &self.game),
+            ("Fabric loader version", loader_version, &self.loader),
+            ("Installer version", installer_version, &self.installer),
+        ]
+        .into_iter()
+        .filter(|(_, version, versions)| !versions.iter().any(|v| v.version == *version))
+        .map(|(kind, version, _)| format!("{kind} {version}"))
+        .collect()
+    }
+}
+
 pub async fn download_server(
     client: &Client,
     game_version: &str,
     fabric_loader_version: &str,
     installer_version: &str,
     save_dir_path: impl AsRef<Path>,
-) -> anyhow::Result<String> {
+) -> anyhow::Result<(String, DownloadStats)> {
     let url = format!(
         "https://meta.fabricmc.net/v2/versions/loader/{game_version}/{fabric_loader_version}/{installer_version}/server/jar"
     );
@@ -20,89 +92,23 @@ pub async fn download_server(
         "fabric-server-mc.{game_version}-loader.{fabric_loader_version}-launcher.{installer_version}.jar"
     );
 
-    download_file(client, &url, &save_dir_path.as_ref().join(&filename)).await?;
-
-    Ok(filename)
-}
-
-pub async fn versions(
-    client: &reqwest::Client,
-    print_mode: PrintVersionMode,
-) -> anyhow::Result<String> {
-    let mut table = Table::new();
-
-    table.add_row(row![
-        "Minecraft Version",
-        "Fabric Loader Version",
-        "Installer Version"
-    ]);
-
-    let (minecraft_versions, fabric_loader_versions, installer_versions) =
-        get_versions(client).await?;
-
-    let minecraft_versions = filter_and_format(minecraft_versions, print_mode);
-    let loader_versions = filter_and_format(fabric_loader_versions, print_mode);
-    let installer_versions = filter_and_format(installer_versions, print_mode);
-
-    let length = minecraft_versions
-        .len()
-        .max(loader_versions.len())
-        .max(installer_versions.len());
-
-    for i in 0..length {
-        table.add_row(row![
-            minecraft_versions.get(i).unwrap_or(&"-".to_string()),
-            loader_versions.get(i).unwrap_or(&"-".to_string()),
-            installer_versions.get(i).unwrap_or(&"-".to_string())
-        ]);
-    }
-
-    Ok(table.to_string())
-}
-
-pub async fn fetch_latest_stable_versions(
-    client: &reqwest::Client,
-) -> anyhow::Result<(String, String, String)> {
-    let (minecraft_versions, fabric_loader_versions, installer_versions) =
-        get_versions(client).await?;
-
-    let minecraft_version = minecraft_versions
-        .into_iter()
-        .find(|v| v["stable"].as_bool().unwrap())
-        .ok_or(anyhow!("Failed to find stable minecraft version"))?["version"]
-        .as_str()
-        .unwrap()
-        .to_string();
-    let fabric_loader_version = fabric_loader_versions
-        .into_iter()
-        .find(|v| v["stable"].as_bool().unwrap())
-        .ok_or(anyhow!("Failed to find stable fabric loader version"))?["version"]
-        .as_str()
-        .unwrap()
-        .to_string();
-    let installer_version = installer_versions
-        .into_iter()
-        .find(|v| v["stable"].as_bool().unwrap())
-        .ok_or(anyhow!("Failed to find stable fabric installer version"))?["version"]
-        .as_str()
-        .unwrap()
-        .to_string();
+    let stats = download_file(client, &url, &save_dir_path.as_ref().join(&filename)).await?;
 
-    Ok((minecraft_version, fabric_loader_version, installer_version))
+    Ok((filename, stats))
 }
 
-async fn get_versions(
-    client: &reqwest::Client,
-) -> anyhow::Result<(
-    Vec<serde_json::Value>,
-    Vec<serde_json::Value>,
-    Vec<serde_json::Value>,
-)> {
-    tokio::try_join!(
+pub async fn versions(client: &reqwest::Client) -> anyhow::Result<FabricVersions> {
+    let (game, loader, installer) = tokio::try_join!(
         fetch_json(client, "https://meta.fabricmc.net/v2/versions/game"),
         fetch_json(client, "https://meta.fabricmc.net/v2/versions/loader"),
         fetch_json(client, "https://meta.fabricmc.net/v2/versions/installer"),
-    )
+    )?;
+
+    Ok(FabricVersions {
+        game,
+        loader,
+        installer,
+    })
 }
 
 async fn fetch_json<T: DeserializeOwned>(client: &Client, url: &str) -> anyhow::Result<T> {
@@ -111,30 +117,6 @@ async fn fetch_json<T: DeserializeOwned>(client: &Client, url: &str) -> anyhow::
     Ok(result)
 }
 
-fn filter_and_format(
-    versions: Vec<serde_json::Value>,
-    print_mode: PrintVersionMode,
-) -> Vec<String> {
-    versions
-        .into_iter()
-        .filter(|v| match print_mode {
-            PrintVersionMode::All => true,
-            PrintVersionMode::StableOnly => v["stable"].as_bool().unwrap(),
-        })
-        .map(|v| {
-            format!(
-                "{} ({})",
-                v["version"].as_str().unwrap(),
-                if v["stable"].as_bool().unwrap() {
-                    "stable"
-                } else {
-                    "unstable"
-                }
-            )
-        })
-        .collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,7 +124,9 @@ mod tests {
     #[tokio::test]
     async fn test_fetch_latest_stable_versions() {
         let client = reqwest::Client::new();
-        let versions = fetch_latest_stable_versions(&client).await;
+        let versions = versions(&client)
+            .await
+            .and_then(|versions| versions.latest_stable());
 
         assert!(versions.is_ok());
 
@@ -156,14 +140,75 @@ mod tests {
     #[tokio::test]
     async fn test_get_versions() {
         let client = reqwest::Client::new();
-        let versions = get_versions(&client).await;
+        let versions = versions(&client).await;
 
         assert!(versions.is_ok());
 
-        let (minecraft_versions, fabric_loader_versions, installer_versions) = versions.unwrap();
+        let versions = versions.unwrap();
+
+        assert!(!versions.game.is_empty());
+        assert!(!versions.loader.is_empty());
+        assert!(!versions.installer.is_empty());
+    }
+
+    fn fixture_versions() -> FabricVersions {
+        // Trimmed responses of the game, loader and installer endpoints
+        let game = r#"[
+            {"version": "25w31a", "stable": false},
+            {"version": "1.21.8", "stable": true},
+            {"version": "1.21.7", "stable": true}
+        ]"#;
+        let loader = r#"[
+            {"separator": ".", "build": 1, "maven": "net.fabricmc:fabric-loader:0.17.0-beta.1", "version": "0.17.0-beta.1", "stable": false},
+            {"separator": ".", "build": 14, "maven": "net.fabricmc:fabric-loader:0.16.14", "version": "0.16.14", "stable": true}
+        ]"#;
+        let installer = r#"[
+            {"url": "https://maven.fabricmc.net/net/fabricmc/fabric-installer/1.0.3/fabric-installer-1.0.3.jar", "maven": "net.fabricmc:fabric-installer:1.0.3", "version": "1.0.3", "stable": true}
+        ]"#;
+
+        FabricVersions {
+            game: serde_json::from_str(game).unwrap(),
+            loader: serde_json::from_str(loader).unwrap(),
+            installer: serde_json::from_str(installer).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_latest_stable_from_fixture() {
+        let latest = fixture_versions().latest_stable().unwrap();
+        assert_eq!(
+            latest,
+            (
+                "1.21.8".to_string(),
+                "0.16.14".to_string(),
+                "1.0.3".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_entries_from_fixture() {
+        let versions = fixture_versions();
+
+        let stable = versions.entries(PrintVersionMode::StableOnly);
+        assert_eq!(stable.len(), 4);
+        assert!(stable.iter().all(|v| v.stable));
+
+        let all = versions.entries(PrintVersionMode::All);
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[3].component, Some("Fabric Loader"));
+        assert_eq!(all[3].id, "0.17.0-beta.1");
+    }
+
+    #[test]
+    fn test_unknown_versions_from_fixture() {
+        let unknown = fixture_versions().unknown_versions("1.21.8", "0.16.99", "1.0.3");
+        assert_eq!(unknown, ["Fabric loader version 0.16.99"]);
+    }
 
-        assert!(!minecraft_versions.is_empty());
-        assert!(!fabric_loader_versions.is_empty());
-        assert!(!installer_versions.is_empty());
+    #[test]
+    fn test_missing_stable_field_is_rejected() {
+        let result = serde_json::from_str::<Vec<FabricVersion>>(r#"[{"version": "1.21.8"}]"#);
+        assert!(result.is_err());
     }
 }
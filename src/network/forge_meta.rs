@@ -1,40 +1,143 @@
-use std::path::Path;
+use std::{cmp::Ordering, collections::HashMap, path::Path};
 
 use reqwest::Client;
 use roxmltree::Document;
 
-use crate::network::{download_file, fetch_text};
+use crate::network::{DownloadStats, VersionEntry, download_verified_artifact, fetch_text};
+use crate::system::version::McVersion;
+
+const METADATA_URL: &str =
+    "https://maven.minecraftforge.net/net/minecraftforge/forge/maven-metadata.xml";
+const PROMOTIONS_URL: &str =
+    "https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
 
 pub async fn download_installer(
     client: &Client,
     version: &str,
     save_dir_path: impl AsRef<Path>,
-) -> anyhow::Result<String> {
+) -> anyhow::Result<(String, DownloadStats)> {
     let filename = format!("forge-{version}-installer.jar");
     let url =
         format!("https://maven.minecraftforge.net/net/minecraftforge/forge/{version}/{filename}");
 
-    download_file(client, &url, &save_dir_path.as_ref().join(&filename)).await?;
+    let stats =
+        download_verified_artifact(client, &url, &save_dir_path.as_ref().join(&filename)).await?;
 
-    Ok(filename)
+    Ok((filename, stats))
 }
 
-pub async fn versions(client: &Client) -> anyhow::Result<String> {
-    let url = "https://maven.minecraftforge.net/net/minecraftforge/forge/maven-metadata.xml";
-    let text = fetch_text(client, url).await?;
-    let doc = Document::parse(&text)?;
+/// Fetches Forge versions, newest first.
+///
+/// If `game_version` is set, only versions for that Minecraft version are returned.
+/// If `promoted_only` is set, only the builds promoted as latest or recommended are returned.
+pub async fn versions(
+    client: &Client,
+    game_version: Option<&str>,
+    promoted_only: bool,
+) -> anyhow::Result<Vec<VersionEntry>> {
+    let (metadata, promotions) = tokio::try_join!(
+        fetch_text(client, METADATA_URL),
+        fetch_text(client, PROMOTIONS_URL)
+    )?;
+
+    let versions = parse_versions(&metadata)?;
+    let promos = parse_promotions(&promotions)?;
+
+    Ok(filter_versions(
+        versions,
+        &promos,
+        game_version,
+        promoted_only,
+    ))
+}
+
+fn parse_versions(metadata: &str) -> anyhow::Result<Vec<String>> {
+    let doc = Document::parse(metadata)?;
     let versions = doc
         .descendants()
         .filter(|node| node.has_tag_name("version"))
         .filter_map(|node| node.text().map(String::from))
-        .collect::<Vec<_>>();
+        .collect();
+
+    Ok(versions)
+}
+
+/// Parses `promotions_slim.json` into a map like `"1.21.8-recommended" => "58.0.10"`.
+fn parse_promotions(promotions: &str) -> anyhow::Result<HashMap<String, String>> {
+    #[derive(serde::Deserialize)]
+    struct Promotions {
+        promos: HashMap<String, String>,
+    }
+
+    Ok(serde_json::from_str::<Promotions>(promotions)?.promos)
+}
+
+fn filter_versions(
+    mut versions: Vec<String>,
+    promos: &HashMap<String, String>,
+    game_version: Option<&str>,
+    promoted_only: bool,
+) -> Vec<VersionEntry> {
+    // Maven metadata is not ordered reliably, so sort newest first.
+    // Forge does not publish per-version release dates in the metadata.
+    versions.sort_by(|a, b| compare_forge_versions(b, a));
+
+    versions
+        .into_iter()
+        .filter_map(|version| {
+            // Versions are formatted like `1.21.8-58.1.0`
+            let (game, build) = version.split_once('-')?;
+
+            if game_version.is_some_and(|g| g != game) {
+                return None;
+            }
+
+            // Old versions have a suffix like `1.7.10-10.13.4.1614-1.7.10`
+            let is_promoted = |kind: &str| {
+                promos
+                    .get(&format!("{game}-{kind}"))
+                    .is_some_and(|b| build == b || build.starts_with(&format!("{b}-")))
+            };
+
+            let version_type = match (is_promoted("recommended"), is_promoted("latest")) {
+                (true, _) => Some("recommended".to_string()),
+                (false, true) => Some("latest".to_string()),
+                (false, false) if promoted_only => return None,
+                (false, false) => None,
+            };
 
-    Ok(versions.join("\n"))
+            Some(VersionEntry {
+                version_type,
+                ..VersionEntry::new(version, true)
+            })
+        })
+        .collect()
+}
+
+/// Compares versions like `1.21.8-58.1.0` by their numeric parts.
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |s: &str| {
+        s.split(|c: char| !c.is_ascii_digit())
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+
+    parts(a).cmp(&parts(b))
+}
+
+/// Compares Forge versions by their game version, then by their build, so `1.7.10_pre4`
+/// builds come before the `1.7.10` ones.
+fn compare_forge_versions(a: &str, b: &str) -> Ordering {
+    let game_version = |version: &str| McVersion::parse(version.split_once('-')?.0);
+    match (game_version(a), game_version(b)) {
+        (Some(a_game), Some(b_game)) if a_game != b_game => a_game.cmp(&b_game),
+        _ => compare_versions(a, b),
+    }
 }
 
 pub async fn fetch_latest_version(client: &Client) -> anyhow::Result<String> {
-    let url = "https://maven.minecraftforge.net/net/minecraftforge/forge/maven-metadata.xml";
-    let text = fetch_text(client, url).await?;
+    let text = fetch_text(client, METADATA_URL).await?;
     let doc = Document::parse(&text)?;
     let latest_version = doc
         .descendants()
@@ -44,3 +147,116 @@ pub async fn fetch_latest_version(client: &Client) -> anyhow::Result<String> {
 
     Ok(latest_version)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata>
+  <groupId>net.minecraftforge</groupId>
+  <artifactId>forge</artifactId>
+  <versioning>
+    <latest>1.21.8-58.1.0</latest>
+    <release>1.21.8-58.1.0</release>
+    <versions>
+      <version>1.21.8-58.0.10</version>
+      <version>1.21.8-58.1.0</version>
+      <version>1.21.8-58.0.9</version>
+      <version>1.21.7-57.0.3</version>
+      <version>1.7.10-10.13.4.1614-1.7.10</version>
+    </versions>
+  </versioning>
+</metadata>"#;
+
+    const PROMOTIONS: &str = r#"{
+        "homepage": "https://files.minecraftforge.net/net/minecraftforge/forge/",
+        "promos": {
+            "1.7.10-latest": "10.13.4.1614",
+            "1.7.10-recommended": "10.13.4.1614",
+            "1.21.7-latest": "57.0.3",
+            "1.21.8-latest": "58.1.0",
+            "1.21.8-recommended": "58.0.10"
+        }
+    }"#;
+
+    fn filtered(game_version: Option<&str>, promoted_only: bool) -> Vec<(String, Option<String>)> {
+        let versions = parse_versions(METADATA).unwrap();
+        let promos = parse_promotions(PROMOTIONS).unwrap();
+        filter_versions(versions, &promos, game_version, promoted_only)
+            .into_iter()
+            .map(|v| (v.id, v.version_type))
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_versions_by_game_version() {
+        let versions = filtered(Some("1.21.8"), false);
+        let ids = versions
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, ["1.21.8-58.1.0", "1.21.8-58.0.10", "1.21.8-58.0.9"]);
+        assert_eq!(versions[0].1.as_deref(), Some("latest"));
+        assert_eq!(versions[1].1.as_deref(), Some("recommended"));
+        assert_eq!(versions[2].1, None);
+    }
+
+    #[test]
+    fn test_filter_versions_promoted_only() {
+        let versions = filtered(None, true);
+        let ids = versions
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ids,
+            [
+                "1.21.8-58.1.0",
+                "1.21.8-58.0.10",
+                "1.21.7-57.0.3",
+                "1.7.10-10.13.4.1614-1.7.10"
+            ]
+        );
+        assert_eq!(versions[3].1.as_deref(), Some("recommended"));
+    }
+
+    #[test]
+    fn test_filter_versions_unknown_game_version() {
+        assert!(filtered(Some("1.2.3"), false).is_empty());
+    }
+
+    #[test]
+    fn test_compare_forge_versions() {
+        assert_eq!(
+            compare_forge_versions("1.7.10_pre4-10.12.2.1149", "1.7.10-10.13.4.1614-1.7.10"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_forge_versions("1.21.8-58.1.0", "1.21.8-58.0.9"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_forge_versions("1.21.10-60.0.0", "1.21.8-58.1.0"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions("1.21.8-58.1.0", "1.21.8-58.0.9"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("1.9.4-12.17.0.2317", "1.21.8-58.1.0"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("1.21.8-58.1.0", "1.21.8-58.1.0"),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::{error::Error, fmt::Display, sync::RwLock};
+
+// Set from the global config, unless `--no-mirror` is passed
+static MIRRORS: RwLock<Vec<MirrorRule>> = RwLock::new(Vec::new());
+
+/// Downloads and metadata requests to URLs starting with `from` are sent to `to` with the
+/// rest of the URL.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MirrorRule {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug)]
+pub enum MirrorError {
+    /// Nothing tells whether the mirror sent the same file as the original URL would.
+    Unverifiable { url: String, mirror_url: String },
+}
+
+impl Display for MirrorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MirrorError::Unverifiable { url, mirror_url } => write!(
+                f,
+                "Refusing to download {url} from the mirror {mirror_url}, since there is no hash to verify it with. Pass --no-mirror to download it from the original URL"
+            ),
+        }
+    }
+}
+
+impl Error for MirrorError {}
+
+pub fn set_mirrors(rules: Vec<MirrorRule>) {
+    *MIRRORS.write().unwrap() = rules;
+}
+
+pub fn mirrors() -> Vec<MirrorRule> {
+    MIRRORS.read().unwrap().clone()
+}
+
+/// The URL of the mirror the rules send `url` to, if any. The rule with the longest
+/// matching `from` wins.
+pub fn rewrite(url: &str, rules: &[MirrorRule]) -> Option<String> {
+    let rule = rules
+        .iter()
+        .filter(|rule| url.starts_with(&rule.from))
+        .max_by_key(|rule| rule.from.len())?;
+
+    Some(format!("{}{}", rule.to, &url[rule.from.len()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(from: &str, to: &str) -> MirrorRule {
+        MirrorRule {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rules = [rule(
+            "https://cdn.modrinth.com/",
+            "https://mirror.example/modrinth/",
+        )];
+
+        assert_eq!(
+            rewrite("https://cdn.modrinth.com/data/gvQqBUqZ/lithium.jar", &rules).as_deref(),
+            Some("https://mirror.example/modrinth/data/gvQqBUqZ/lithium.jar")
+        );
+        assert_eq!(
+            rewrite("https://meta.fabricmc.net/v2/versions/game", &rules),
+            None
+        );
+        assert_eq!(rewrite("https://cdn.modrinth.com/", &[]), None);
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let rules = [
+            rule("https://cdn.modrinth.com/data/", "https://b.example/"),
+            rule("https://cdn.modrinth.com/", "https://a.example/"),
+        ];
+
+        assert_eq!(
+            rewrite("https://cdn.modrinth.com/data/x.jar", &rules).as_deref(),
+            Some("https://b.example/x.jar")
+        );
+        assert_eq!(
+            rewrite("https://cdn.modrinth.com/icons/x.png", &rules).as_deref(),
+            Some("https://a.example/icons/x.png")
+        );
+    }
+}
@@ -1,15 +1,183 @@
-use reqwest::{Client, StatusCode};
+use crate::{
+    ProgressEvent,
+    network::{
+        mirror::{MirrorError, MirrorRule},
+        rate_limit::Rate,
+    },
+    system::{
+        cache::DownloadCache,
+        cancel, format,
+        jar_parser::{self, FileHash, HashAlgorithm},
+    },
+};
+use prettytable::{Table, row};
+use reqwest::{Certificate, Client, NoProxy, Proxy, StatusCode, Url};
+use serde::Deserialize;
 use std::{
+    cmp::Reverse,
+    error::Error,
+    fmt::Display,
     fs::{self, File},
-    path::PathBuf,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
 };
-use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 
 pub mod fabric_meta;
 pub mod forge_meta;
+pub mod mirror;
 pub mod modrinth;
+pub mod ping;
+pub mod rate_limit;
 pub mod vanilla_meta;
 
+/// Modrinth asks API clients to identify themselves, and to give a way to contact the author.
+pub const USER_AGENT: &str = concat!(
+    "mcerv/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/Bowen951209/mcerv)"
+);
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+const DEFAULT_REQUEST_TIMEOUT: u64 = 60;
+const DEFAULT_INACTIVITY_TIMEOUT: u64 = 60;
+/// How often a download reports its progress, fast enough for a bar to look smooth.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// In seconds. Set by `build_client`, since the downloads and errors only get the client
+static CONNECT_TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_CONNECT_TIMEOUT);
+static REQUEST_TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_REQUEST_TIMEOUT);
+static INACTIVITY_TIMEOUT: AtomicU64 = AtomicU64::new(DEFAULT_INACTIVITY_TIMEOUT);
+
+/// How the HTTP client of a command connects, from the `[network]` table of the global config.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkSettings {
+    /// PEM certificates trusted on top of the built-in roots, for proxies intercepting TLS.
+    pub ca_bundle: Option<PathBuf>,
+    /// Seconds to wait for a connection.
+    pub connect_timeout: u64,
+    /// Seconds an API request may take in total.
+    pub request_timeout: u64,
+    /// Seconds a download may go without receiving anything. Downloads have no total
+    /// timeout, since big server jars can take minutes.
+    pub inactivity_timeout: u64,
+    /// The rate all downloads together are limited to, like `2M`.
+    pub limit_rate: Option<Rate>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            ca_bundle: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            inactivity_timeout: DEFAULT_INACTIVITY_TIMEOUT,
+            limit_rate: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TimeoutError {
+    pub host: String,
+    pub after: Duration,
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Timed out talking to {} after {}s",
+            self.host,
+            self.after.as_secs_f64()
+        )
+    }
+}
+
+impl Error for TimeoutError {}
+
+impl TimeoutError {
+    fn new(url: &str, after: Duration) -> Self {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| url.to_string());
+        Self { host, after }
+    }
+}
+
+fn timeout(setting: &AtomicU64) -> Duration {
+    Duration::from_secs(setting.load(Ordering::Relaxed))
+}
+
+/// Turns the error of a request that timed out into a [`TimeoutError`].
+pub fn map_timeout(e: reqwest::Error) -> anyhow::Error {
+    if !e.is_timeout() {
+        return e.into();
+    }
+
+    let after = if e.is_connect() {
+        timeout(&CONNECT_TIMEOUT)
+    } else {
+        timeout(&REQUEST_TIMEOUT)
+    };
+    let url = e.url().map(Url::as_str).unwrap_or("the server");
+    TimeoutError::new(url, after).into()
+}
+
+/// The proxies set in the environment, like `(HTTPS_PROXY, HTTP_PROXY, ALL_PROXY)`.
+/// The uppercase variables take precedence over the lowercase ones.
+fn proxy_vars(var: impl Fn(&str) -> Option<String>) -> [Option<String>; 3] {
+    ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY"].map(|name| {
+        [name.to_string(), name.to_lowercase()]
+            .iter()
+            .filter_map(|name| var(name))
+            .find(|value| !value.trim().is_empty())
+    })
+}
+
+/// Builds the client every request of a command is sent with, so connections are reused.
+pub fn build_client(settings: &NetworkSettings) -> anyhow::Result<Client> {
+    // The proxies are set up explicitly below instead
+    CONNECT_TIMEOUT.store(settings.connect_timeout, Ordering::Relaxed);
+    REQUEST_TIMEOUT.store(settings.request_timeout, Ordering::Relaxed);
+    INACTIVITY_TIMEOUT.store(settings.inactivity_timeout, Ordering::Relaxed);
+    rate_limit::set_rate(settings.limit_rate);
+
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(timeout(&CONNECT_TIMEOUT))
+        .timeout(timeout(&REQUEST_TIMEOUT))
+        .no_proxy();
+
+    let [https, http, all] = proxy_vars(|name| std::env::var(name).ok());
+    let proxies = [
+        https.map(Proxy::https),
+        http.map(Proxy::http),
+        all.map(Proxy::all),
+    ];
+    for proxy in proxies.into_iter().flatten() {
+        builder = builder.proxy(proxy?.no_proxy(NoProxy::from_env()));
+    }
+
+    if let Some(path) = &settings.ca_bundle {
+        let pem = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read CA bundle {}: {e}", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)?;
+        if certificates.is_empty() {
+            anyhow::bail!("No certificates found in CA bundle {}", path.display());
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
 #[derive(Copy, Clone)]
 pub enum PrintVersionMode {
     All,
@@ -26,12 +194,178 @@ impl PrintVersionMode {
     }
 }
 
+/// A version of a server component listed by the fetch command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionEntry {
+    /// The component the version belongs to, for forks with several versioned parts.
+    pub component: Option<&'static str>,
+    pub id: String,
+    pub stable: bool,
+    /// The type reported by the source, like `release` or `snapshot`.
+    /// Falls back to stable/unstable when there is none.
+    pub version_type: Option<String>,
+    /// Release date in `YYYY-MM-DD` form, if the source provides one.
+    pub release_date: Option<String>,
+}
+
+impl VersionEntry {
+    pub fn new(id: impl Into<String>, stable: bool) -> Self {
+        Self {
+            component: None,
+            id: id.into(),
+            stable,
+            version_type: None,
+            release_date: None,
+        }
+    }
+}
+
+/// Renders the versions as a table, newest first.
+/// `limit` is applied to each component separately.
+pub fn versions_table(versions: &[VersionEntry], limit: Option<usize>) -> String {
+    let mut versions = versions.to_vec();
+    // Undated entries go last, and the stable sort keeps them in the source order
+    versions.sort_by_key(|v| (v.release_date.is_none(), Reverse(v.release_date.clone())));
+
+    let mut components = Vec::new();
+    for version in &versions {
+        if !components.contains(&version.component) {
+            components.push(version.component);
+        }
+    }
+
+    let show_component = components.iter().any(Option::is_some);
+
+    let mut table = Table::new();
+    if show_component {
+        table.add_row(row!["Component", "Version", "Type", "Release Date"]);
+    } else {
+        table.add_row(row!["Version", "Type", "Release Date"]);
+    }
+
+    for component in components {
+        let rows = versions
+            .iter()
+            .filter(|v| v.component == component)
+            .take(limit.unwrap_or(usize::MAX));
+
+        for version in rows {
+            let stability = match &version.version_type {
+                Some(version_type) => version_type.as_str(),
+                None if version.stable => "stable",
+                None => "unstable",
+            };
+            let date = version.release_date.as_deref().unwrap_or("-");
+
+            if show_component {
+                table.add_row(row![component.unwrap_or("-"), version.id, stability, date]);
+            } else {
+                table.add_row(row![version.id, stability, date]);
+            }
+        }
+    }
+
+    table.to_string()
+}
+
+/// How much a download fetched and how long it took.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownloadStats {
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Display for DownloadStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.elapsed.as_secs_f64();
+        write!(
+            f,
+            "{} in {secs:.1}s ({}/s)",
+            format::format_bytes(self.bytes),
+            format::format_bytes((self.bytes as f64 / secs.max(0.001)) as u64)
+        )
+    }
+}
+
+/// Streams the file to `save_path`, emitting [`ProgressEvent`]s while it downloads.
+///
+/// Fails with a [`TimeoutError`] if nothing is received for the inactivity timeout, or a
+/// [`CancelledError`](cancel::CancelledError) on Ctrl-C, and the partially downloaded file
+/// is deleted.
+///
+/// Without a hash, nothing could tell a mirror's file apart from the original, so a URL
+/// a mirror rule applies to fails with [`MirrorError::Unverifiable`].
 pub async fn download_file(
     client: &Client,
     url: &impl AsRef<str>,
     save_path: &impl AsRef<std::path::Path>,
-) -> anyhow::Result<()> {
-    let response = client.get(url.as_ref()).send().await?;
+) -> anyhow::Result<DownloadStats> {
+    download_file_with(
+        client,
+        url.as_ref(),
+        save_path.as_ref(),
+        None,
+        timeout(&INACTIVITY_TIMEOUT),
+        &mirror::mirrors(),
+        &cancel::token(),
+    )
+    .await
+}
+
+/// Like [`download_file`], but the file is verified against `expected`, and deleted with a
+/// [`ChecksumError`] if it doesn't match. Mirror rules are applied.
+pub async fn download_file_checked(
+    client: &Client,
+    url: &str,
+    save_path: &Path,
+    expected: &FileHash,
+) -> anyhow::Result<DownloadStats> {
+    download_file_with(
+        client,
+        url,
+        save_path,
+        Some(expected),
+        timeout(&INACTIVITY_TIMEOUT),
+        &mirror::mirrors(),
+        &cancel::token(),
+    )
+    .await
+}
+
+async fn download_file_with(
+    client: &Client,
+    url: &str,
+    save_path: &Path,
+    expected: Option<&FileHash>,
+    inactivity_timeout: Duration,
+    mirrors: &[MirrorRule],
+    cancel: &CancellationToken,
+) -> anyhow::Result<DownloadStats> {
+    // So Ctrl-C waits for the partial file to be deleted
+    let _scope = cancel::scope();
+    let url = match (mirror::rewrite(url, mirrors), expected) {
+        (Some(mirror_url), Some(_)) => mirror_url,
+        (Some(mirror_url), None) => anyhow::bail!(MirrorError::Unverifiable {
+            url: url.to_string(),
+            mirror_url,
+        }),
+        (None, _) => url.to_string(),
+    };
+    let url = url.as_str();
+
+    let start = Instant::now();
+    let timed_out = || TimeoutError::new(url, inactivity_timeout);
+
+    // Overrides the client's total timeout, the inactivity timeout is applied instead
+    let mut request = client.get(url).timeout(Duration::MAX);
+    if modrinth::is_modrinth_url(url) {
+        request = modrinth::authorize(request);
+    }
+    let request = tokio::time::timeout(inactivity_timeout, request.send());
+    let mut response = cancel::or_cancelled(cancel, request)
+        .await?
+        .map_err(|_| timed_out())?
+        .map_err(map_timeout)?;
     let status = response.status();
 
     if status != StatusCode::OK {
@@ -40,43 +374,233 @@ pub async fn download_file(
 
     fs::create_dir_all(
         save_path
-            .as_ref()
             .parent()
             .expect("save_path parent is not available."),
     )?;
-    let mut file = File::create(save_path.as_ref())?;
-    let content = response.bytes().await?;
-    std::io::copy(&mut content.as_ref(), &mut file)?;
+    let mut file = File::create(save_path)?;
+
+    let file_name = save_path.display().to_string();
+    let total = response.content_length();
+    let progress = |done| {
+        crate::emit(ProgressEvent::DownloadProgress {
+            file: file_name.clone(),
+            done,
+            total,
+        })
+    };
+    progress(0);
+
+    let result = async {
+        let mut bytes = 0;
+        let mut last_progress = Instant::now();
+        loop {
+            let chunk = tokio::time::timeout(inactivity_timeout, response.chunk());
+            let chunk = cancel::or_cancelled(cancel, chunk)
+                .await?
+                .map_err(|_| timed_out())?
+                .map_err(map_timeout)?;
+            let Some(chunk) = chunk else {
+                break;
+            };
+
+            cancel::or_cancelled(cancel, rate_limit::throttle(chunk.len())).await?;
+            file.write_all(&chunk)?;
+            bytes += chunk.len() as u64;
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                progress(bytes);
+                last_progress = Instant::now();
+            }
+        }
+        progress(bytes);
+        anyhow::Ok(bytes)
+    }
+    .await;
+
+    let result = match result {
+        Ok(bytes) => {
+            drop(file);
+            verify_download(url, save_path, expected).map(|()| bytes)
+        }
+        Err(e) => {
+            drop(file);
+            fs::remove_file(save_path)?;
+            Err(e)
+        }
+    };
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            crate::emit(ProgressEvent::DownloadFailed {
+                file: file_name,
+                error: e.to_string(),
+            });
+            return Err(e);
+        }
+    };
+    crate::emit(ProgressEvent::DownloadDone {
+        file: file_name,
+        bytes,
+    });
+
+    Ok(DownloadStats {
+        bytes,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Deletes the file with a [`ChecksumError`] if it doesn't match `expected`.
+fn verify_download(url: &str, path: &Path, expected: Option<&FileHash>) -> anyhow::Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    let actual = jar_parser::calculate_hash_with(&mut File::open(path)?, expected.algorithm)?;
+    if actual != expected.value {
+        fs::remove_file(path)?;
+        anyhow::bail!(ChecksumError::Mismatch {
+            url: url.to_string(),
+            expected: expected.value.clone(),
+            actual,
+        });
+    }
 
     Ok(())
 }
 
-pub async fn download_files(
+#[derive(Debug)]
+pub enum ChecksumError {
+    Mismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Display for ChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksumError::Mismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {url}: expected {expected}, got {actual}. Refusing to use the file"
+            ),
+        }
+    }
+}
+
+impl Error for ChecksumError {}
+
+/// Downloads a maven artifact and verifies it against the checksum file published next to it.
+/// Prefers `.sha256` and falls back to `.sha1`. The download is retried once on mismatch,
+/// and the file is deleted if it still doesn't match.
+pub async fn download_verified_artifact(
     client: &Client,
-    downloads: impl Iterator<Item = (String, PathBuf)>, // (url, save_path) pairs
-) -> anyhow::Result<()> {
-    let mut join_set = JoinSet::new();
+    url: &str,
+    save_path: &impl AsRef<Path>,
+) -> anyhow::Result<DownloadStats> {
+    let (algorithm, value) = fetch_artifact_checksum(client, url).await?;
+    let expected = FileHash { algorithm, value };
 
-    for (url, save_path) in downloads {
-        let client = client.clone();
-        join_set.spawn(async move { download_file(&client, &url, &save_path).await });
+    match download_file_checked(client, url, save_path.as_ref(), &expected).await {
+        Err(e) if e.downcast_ref::<ChecksumError>().is_some() => {
+            println!("Checksum mismatch for {url}, downloading again...");
+            download_file_checked(client, url, save_path.as_ref(), &expected).await
+        }
+        result => result,
     }
+}
 
-    while let Some(result) = join_set.join_next().await {
-        result??;
+/// Copies the file with the sha512 from the cache to `save_path`,
+/// downloading it into the cache first if it's not there yet.
+/// Returns whether the file was already cached.
+pub async fn download_cached(
+    client: &Client,
+    url: &str,
+    sha512: &str,
+    cache: &DownloadCache,
+    save_path: &Path,
+) -> anyhow::Result<bool> {
+    let expected = FileHash {
+        algorithm: HashAlgorithm::Sha512,
+        value: sha512.to_string(),
+    };
+    // Not a path to trust, so it's downloaded straight to `save_path` and checked there
+    if !DownloadCache::accepts(sha512) {
+        download_file_checked(client, url, save_path, &expected).await?;
+        return Ok(false);
+    }
+    if cache.copy_to(sha512, save_path)? {
+        return Ok(true);
     }
 
-    Ok(())
+    let partial_path = cache.partial_path(sha512)?;
+    download_file_checked(client, url, &partial_path, &expected).await?;
+
+    cache.insert(sha512, &partial_path)?;
+    cache.copy_to(sha512, save_path)?;
+    Ok(false)
+}
+
+/// Downloads the files through the cache at once, like [`download_cached`], by URL, sha512
+/// and where each is saved. Returns whether each was already cached.
+///
+/// Every download runs to the end even if one fails, so none is dropped before deleting
+/// its partial file.
+pub async fn download_files(
+    client: &Client,
+    files: &[(&str, &str, PathBuf)],
+    cache: &DownloadCache,
+) -> anyhow::Result<Vec<bool>> {
+    futures::future::join_all(
+        files
+            .iter()
+            .map(|(url, sha512, save_path)| download_cached(client, url, sha512, cache, save_path)),
+    )
+    .await
+    .into_iter()
+    .collect()
 }
 
+async fn fetch_artifact_checksum(
+    client: &Client,
+    url: &str,
+) -> anyhow::Result<(HashAlgorithm, String)> {
+    let mut last_error = None;
+
+    for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Sha1] {
+        match fetch_original_text(client, &format!("{url}.{}", algorithm.extension())).await {
+            Ok(text) => {
+                // Checksum files may contain the file name after the hash
+                let hash = text.split_whitespace().next().unwrap_or_default();
+                return Ok((algorithm, hash.to_lowercase()));
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap())
+}
+
+/// Fetches metadata like a version manifest, from its mirror if the rules have one.
 pub async fn fetch_text(client: &Client, url: &str) -> anyhow::Result<String> {
-    let response = client.get(url).send().await?;
+    match mirror::rewrite(url, &mirror::mirrors()) {
+        Some(mirror_url) => fetch_original_text(client, &mirror_url).await,
+        None => fetch_original_text(client, url).await,
+    }
+}
+
+/// Like [`fetch_text`], but never from a mirror, for checksums that verify what it sends.
+async fn fetch_original_text(client: &Client, url: &str) -> anyhow::Result<String> {
+    let response = client.get(url).send().await.map_err(map_timeout)?;
 
     if !response.status().is_success() {
         anyhow::bail!("Failed to fetch {}: {}", url, response.status());
     }
 
-    let text = response.text().await?;
+    let text = response.text().await.map_err(map_timeout)?;
     Ok(text)
 }
 
@@ -86,3 +610,439 @@ fn display_json_value(json: &serde_json::Value, key: &str) -> String {
         None => format!("{key}: N/A"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+    use sha2::{Digest, Sha256};
+
+    #[tokio::test]
+    async fn test_download_verified_artifact() {
+        let mut server = Server::new_async().await;
+        let content = b"installer";
+        let sha256 = format!("{:x}", Sha256::digest(content));
+
+        server
+            .mock("GET", "/forge-installer.jar")
+            .with_body(content)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/forge-installer.jar.sha256")
+            .with_body(format!("{sha256}  forge-installer.jar"))
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("forge-installer.jar");
+        let url = format!("{}/forge-installer.jar", server.url());
+
+        let stats = download_verified_artifact(&Client::new(), &url, &save_path)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(save_path).unwrap(), content);
+        assert_eq!(stats.bytes, content.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_artifact_mismatch() {
+        let mut server = Server::new_async().await;
+
+        let artifact = server
+            .mock("GET", "/forge-installer.jar")
+            .with_body("truncated")
+            .expect(2)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/forge-installer.jar.sha256")
+            .with_status(404)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/forge-installer.jar.sha1")
+            .with_body("0000000000000000000000000000000000000000")
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("forge-installer.jar");
+        let url = format!("{}/forge-installer.jar", server.url());
+
+        let err = download_verified_artifact(&Client::new(), &url, &save_path)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ChecksumError>(),
+            Some(ChecksumError::Mismatch { .. })
+        ));
+        assert!(!save_path.exists());
+        artifact.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_download_cached() {
+        let mut server = Server::new_async().await;
+        let content = b"lithium";
+        let sha512 = format!("{:x}", sha2::Sha512::digest(content));
+
+        let download = server
+            .mock("GET", "/lithium.jar")
+            .with_body(content)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(cache_dir.path());
+        let first_server = tempfile::tempdir().unwrap();
+        let second_server = tempfile::tempdir().unwrap();
+        let url = format!("{}/lithium.jar", server.url());
+        let client = Client::new();
+
+        let hit = download_cached(
+            &client,
+            &url,
+            &sha512,
+            &cache,
+            &first_server.path().join("lithium.jar"),
+        )
+        .await
+        .unwrap();
+        assert!(!hit);
+
+        let hit = download_cached(
+            &client,
+            &url,
+            &sha512,
+            &cache,
+            &second_server.path().join("lithium.jar"),
+        )
+        .await
+        .unwrap();
+        assert!(hit);
+
+        download.assert_async().await;
+        assert_eq!(
+            fs::read(second_server.path().join("lithium.jar")).unwrap(),
+            content
+        );
+        assert_eq!(fs::read_dir(cache_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_download_cached_mismatch() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/lithium.jar")
+            .with_body("truncated")
+            .create_async()
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(cache_dir.path().join("downloads"));
+        let save_path = cache_dir.path().join("lithium.jar");
+        let url = format!("{}/lithium.jar", server.url());
+        let sha512 = "0".repeat(128);
+
+        let err = download_cached(&Client::new(), &url, &sha512, &cache, &save_path)
+            .await
+            .unwrap_err();
+
+        assert!(err.downcast_ref::<ChecksumError>().is_some());
+        assert!(!save_path.exists());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+        assert_eq!(fs::read_dir(cache.dir()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_cached_skips_unsafe_hash() {
+        let mut server = Server::new_async().await;
+        let download = server
+            .mock("GET", "/lithium.jar")
+            .with_body("lithium")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("downloads"));
+        let save_path = dir.path().join("lithium.jar");
+        let url = format!("{}/lithium.jar", server.url());
+
+        let err = download_cached(&Client::new(), &url, "../../x", &cache, &save_path)
+            .await
+            .unwrap_err();
+
+        download.assert_async().await;
+        assert!(err.downcast_ref::<ChecksumError>().is_some());
+        assert!(!save_path.exists());
+        assert!(!dir.path().join("x").exists());
+        assert!(!cache.dir().exists());
+    }
+
+    #[test]
+    fn test_proxy_vars() {
+        let vars = [
+            ("HTTPS_PROXY", "http://proxy.corp:3128"),
+            ("https_proxy", "http://ignored:3128"),
+            ("http_proxy", "http://lower.corp:8080"),
+            ("ALL_PROXY", " "),
+        ];
+        let var = |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        };
+
+        assert_eq!(
+            proxy_vars(var),
+            [
+                Some("http://proxy.corp:3128".to_string()),
+                Some("http://lower.corp:8080".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_client_ca_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = |path: &Path| NetworkSettings {
+            ca_bundle: Some(path.to_path_buf()),
+            ..NetworkSettings::default()
+        };
+
+        let missing = build_client(&settings(&dir.path().join("missing.pem"))).unwrap_err();
+        assert!(missing.to_string().contains("missing.pem"));
+
+        let empty_path = dir.path().join("empty.pem");
+        fs::write(&empty_path, "").unwrap();
+        assert!(build_client(&settings(&empty_path)).is_err());
+
+        assert!(build_client(&NetworkSettings::default()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_download_inactivity_timeout() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/server.jar")
+            .with_chunked_body(|w| {
+                w.write_all(b"first chunk")?;
+                w.flush()?;
+                std::thread::sleep(Duration::from_secs(2));
+                w.write_all(b"too late")
+            })
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("server.jar");
+        let url = format!("{}/server.jar", server.url());
+
+        let err = download_file_with(
+            &Client::new(),
+            &url,
+            &save_path,
+            None,
+            Duration::from_millis(300),
+            &[],
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+
+        let timeout = err.downcast_ref::<TimeoutError>().unwrap();
+        assert_eq!(timeout.host, "127.0.0.1");
+        assert!(err.to_string().contains("after 0.3s"));
+        assert!(!save_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_cancelled() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/server.jar")
+            .with_chunked_body(|w| {
+                w.write_all(b"first chunk")?;
+                w.flush()?;
+                std::thread::sleep(Duration::from_secs(2));
+                w.write_all(b"never read")
+            })
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("server.jar.part");
+        let url = format!("{}/server.jar", server.url());
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel.cancel();
+        });
+
+        let start = Instant::now();
+        let err = download_file_with(
+            &Client::new(),
+            &url,
+            &save_path,
+            None,
+            Duration::from_secs(5),
+            &[],
+            &token,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(cancel::is_cancelled(&err));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(!save_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_slow_download_without_stall() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/server.jar")
+            .with_chunked_body(|w| {
+                for _ in 0..4 {
+                    w.write_all(b"chunk")?;
+                    w.flush()?;
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Ok(())
+            })
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("server.jar");
+        let url = format!("{}/server.jar", server.url());
+
+        // Takes longer than the timeout in total, but never stalls for that long
+        let stats = download_file_with(
+            &Client::new(),
+            &url,
+            &save_path,
+            None,
+            Duration::from_millis(300),
+            &[],
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.bytes, 20);
+    }
+
+    #[tokio::test]
+    async fn test_download_from_mirror() {
+        let mut server = Server::new_async().await;
+        let content = b"lithium";
+        let mirrored = server
+            .mock("GET", "/mirror/data/lithium.jar")
+            .with_body(content)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let mirrors = [MirrorRule {
+            from: "https://cdn.modrinth.com/".to_string(),
+            to: format!("{}/mirror/", server.url()),
+        }];
+        let url = "https://cdn.modrinth.com/data/lithium.jar";
+        let dir = tempfile::tempdir().unwrap();
+        let save_path = dir.path().join("lithium.jar");
+        let download = |expected: Option<FileHash>| {
+            let save_path = save_path.clone();
+            let mirrors = mirrors.clone();
+            async move {
+                let expected = expected.as_ref();
+                download_file_with(
+                    &Client::new(),
+                    url,
+                    &save_path,
+                    expected,
+                    Duration::from_secs(5),
+                    &mirrors,
+                    &CancellationToken::new(),
+                )
+                .await
+            }
+        };
+
+        let err = download(None).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(MirrorError::Unverifiable { .. })
+        ));
+
+        let sha512 = |value: String| FileHash {
+            algorithm: HashAlgorithm::Sha512,
+            value,
+        };
+        download(Some(sha512(format!("{:x}", sha2::Sha512::digest(content)))))
+            .await
+            .unwrap();
+        assert_eq!(fs::read(&save_path).unwrap(), content);
+
+        // A mirror sending another file
+        let err = download(Some(sha512("0".repeat(128)))).await.unwrap_err();
+        assert!(err.downcast_ref::<ChecksumError>().is_some());
+        assert!(!save_path.exists());
+        mirrored.assert_async().await;
+    }
+
+    #[test]
+    fn test_download_stats_display() {
+        let stats = DownloadStats {
+            bytes: 96_888_422,
+            elapsed: Duration::from_millis(14_200),
+        };
+        assert_eq!(stats.to_string(), "92.4 MB in 14.2s (6.5 MB/s)");
+    }
+
+    #[test]
+    fn test_versions_table_sorts_and_limits() {
+        let mut old = VersionEntry::new("1.21.7", true);
+        old.release_date = Some("2025-06-30".to_string());
+        let mut new = VersionEntry::new("1.21.8", true);
+        new.release_date = Some("2025-07-17".to_string());
+        let snapshot = VersionEntry::new("25w31a", false);
+
+        let table = versions_table(&[old.clone(), new.clone(), snapshot.clone()], Some(2));
+
+        assert!(table.find("1.21.8").unwrap() < table.find("1.21.7").unwrap());
+        assert!(!table.contains("25w31a"));
+        assert!(!table.contains("Component"));
+
+        // An undated entry between the dated ones doesn't keep them from being sorted
+        let table = versions_table(&[old, snapshot, new], None);
+        let position = |id| table.find(id).unwrap();
+        assert!(position("1.21.8") < position("1.21.7"));
+        assert!(position("1.21.7") < position("25w31a"));
+    }
+
+    #[test]
+    fn test_versions_table_limits_each_component() {
+        let entry = |component, id: &str| VersionEntry {
+            component: Some(component),
+            ..VersionEntry::new(id, true)
+        };
+        let versions = [
+            entry("Minecraft", "1.21.8"),
+            entry("Minecraft", "1.21.7"),
+            entry("Fabric Loader", "0.16.14"),
+        ];
+
+        let table = versions_table(&versions, Some(1));
+
+        assert!(table.contains("Component"));
+        assert!(table.contains("1.21.8"));
+        assert!(!table.contains("1.21.7"));
+        assert!(table.contains("0.16.14"));
+    }
+}
@@ -1,9 +1,147 @@
-use std::{collections::HashMap, fmt::Display, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    path::Path,
+    sync::RwLock,
+};
 
 use clap::ValueEnum;
+use futures::StreamExt;
+use reqwest::{
+    RequestBuilder, StatusCode,
+    header::{AUTHORIZATION, HeaderValue},
+};
 use serde::Deserialize;
 
-use crate::network::{display_json_value, download_file};
+use crate::{
+    network::{display_json_value, download_files, map_timeout},
+    system::{
+        cache::DownloadCache,
+        filename, format,
+        jar_parser::{FileHash, HashAlgorithm},
+    },
+};
+use prettytable::{Cell, Table, row};
+
+pub mod facets;
+
+pub(crate) const API_URL: &str = "https://api.modrinth.com/v2";
+const CDN_URL: &str = "https://cdn.modrinth.com/";
+const SITE_URL: &str = "https://modrinth.com";
+/// Project IDs per request of [`get_projects`], which go in the URL.
+const PROJECTS_PER_REQUEST: usize = 100;
+/// How many requests of [`get_projects`] run at a time.
+const CONCURRENT_PROJECT_REQUESTS: usize = 4;
+/// Takes precedence over the token of the global config.
+pub const TOKEN_VAR: &str = "MCERV_MODRINTH_TOKEN";
+
+// Set from MCERV_MODRINTH_TOKEN or the global config, marked sensitive so it's never
+// printed with the request
+static TOKEN: RwLock<Option<HeaderValue>> = RwLock::new(None);
+
+/// Hashes are looked up with the first algorithm, and the ones Modrinth doesn't recognize
+/// with the next. Files uploaded long ago may only have their sha1 indexed.
+pub const LOOKUP_ALGORITHMS: [HashAlgorithm; 2] = [HashAlgorithm::Sha512, HashAlgorithm::Sha1];
+
+#[derive(Debug)]
+pub struct InvalidTokenError;
+
+impl Display for InvalidTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The Modrinth token has characters that can't be sent in a header"
+        )
+    }
+}
+
+impl Error for InvalidTokenError {}
+
+/// A Modrinth personal access token. Its Debug output leaves the token out.
+#[derive(Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct ModrinthToken(String);
+
+impl ModrinthToken {
+    pub fn new(token: String) -> Self {
+        Self(token)
+    }
+}
+
+impl std::fmt::Debug for ModrinthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ModrinthToken(<redacted>)")
+    }
+}
+
+/// The `modrinth` section of the global config.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModrinthSettings {
+    /// Sent with every Modrinth request, for private projects and higher rate limits.
+    pub token: Option<ModrinthToken>,
+}
+
+pub fn set_token(token: Option<&ModrinthToken>) -> Result<(), InvalidTokenError> {
+    let value = match token {
+        Some(token) => {
+            let mut value = HeaderValue::from_str(token.0.trim()).map_err(|_| InvalidTokenError)?;
+            value.set_sensitive(true);
+            Some(value)
+        }
+        None => None,
+    };
+    *TOKEN.write().unwrap() = value;
+    Ok(())
+}
+
+pub fn has_token() -> bool {
+    TOKEN.read().unwrap().is_some()
+}
+
+/// Adds the token as the `Authorization` header, if there is one.
+pub fn authorize(builder: RequestBuilder) -> RequestBuilder {
+    match TOKEN.read().unwrap().clone() {
+        Some(token) => builder.header(AUTHORIZATION, token),
+        None => builder,
+    }
+}
+
+/// Whether the URL is Modrinth's own, so the token can be sent to it. Mirrors never get it.
+pub fn is_modrinth_url(url: &str) -> bool {
+    url.starts_with(API_URL) || url.starts_with(CDN_URL)
+}
+
+// https://docs.modrinth.com/api/operations/getuserfromauth/
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ModrinthUser {
+    pub id: String,
+    pub username: String,
+}
+
+/// The user the token belongs to.
+pub async fn whoami(client: &reqwest::Client) -> anyhow::Result<ModrinthUser> {
+    whoami_from(client, API_URL).await
+}
+
+async fn whoami_from(client: &reqwest::Client, api_url: &str) -> anyhow::Result<ModrinthUser> {
+    if !has_token() {
+        anyhow::bail!(
+            "No Modrinth token is set, set {TOKEN_VAR} or token in [modrinth] of the global config"
+        );
+    }
+
+    let result = authorize(client.get(format!("{api_url}/user")))
+        .send()
+        .await
+        .map_err(map_timeout)?;
+    if result.status() == StatusCode::UNAUTHORIZED {
+        anyhow::bail!("Modrinth rejected the token, it may be expired or revoked");
+    }
+
+    Ok(result.error_for_status()?.json().await?)
+}
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum SearchIndex {
@@ -22,11 +160,18 @@ impl Display for SearchIndex {
 
 // https://docs.modrinth.com/api/operations/searchprojects/
 #[derive(Deserialize)]
-pub struct SearchResponse(serde_json::Value);
+pub struct SearchResponse {
+    pub hits: Vec<serde_json::Value>,
+    /// The number of matching projects, including the ones past the limit.
+    pub total_hits: u64,
+    /// Also show each hit's Modrinth page and icon.
+    #[serde(skip)]
+    pub urls: bool,
+}
 
 impl Display for SearchResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hits = self.0["hits"].as_array().unwrap();
+        let hits = &self.hits;
 
         // The fields we want to display from each hit
         let fields = [
@@ -44,6 +189,12 @@ impl Display for SearchResponse {
             for field in &fields {
                 writeln!(f, "{}", display_json_value(hit, field))?;
             }
+            if self.urls {
+                if let Some(slug) = hit["slug"].as_str() {
+                    writeln!(f, "url: {}", project_url(slug))?;
+                }
+                writeln!(f, "{}", display_json_value(hit, "icon_url"))?;
+            }
             writeln!(f, "=======================================")?;
         }
 
@@ -52,66 +203,242 @@ impl Display for SearchResponse {
 }
 
 // https://docs.modrinth.com/api/operations/getprojectversions/
-#[derive(Deserialize)]
-pub struct ProjectVersionsResponse(serde_json::Value);
+/// The versions of a project, newest first.
+#[derive(Deserialize, Debug)]
+pub struct ProjectVersionsResponse(pub Vec<ProjectVersion>);
 
-impl Display for ProjectVersionsResponse {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let versions = self.0.as_array().unwrap();
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ProjectVersion {
+    pub id: String,
+    pub version_number: String,
+    #[serde(default)]
+    pub version_type: String,
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+    #[serde(default)]
+    pub loaders: Vec<String>,
+    /// In RFC 3339.
+    pub date_published: String,
+    #[serde(default)]
+    pub dependencies: Vec<ModDependency>,
+    pub files: Vec<ProjectVersionFile>,
+}
 
-        // The fields we want to display from each version
-        let fields = [
-            "version_number",
-            "id",
-            "dependencies",
-            "game_versions",
-            "version_type",
-            "loaders",
-        ];
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ProjectVersionFile {
+    pub filename: String,
+    /// In bytes.
+    pub size: u64,
+    #[serde(default)]
+    pub primary: bool,
+}
 
-        for version in versions {
-            for field in &fields {
-                writeln!(f, "{}", display_json_value(version, field))?;
+impl ProjectVersion {
+    /// The size of the primary file, or of the first one if none is primary.
+    pub fn size(&self) -> Option<u64> {
+        self.files
+            .iter()
+            .find(|file| file.primary)
+            .or(self.files.first())
+            .map(|file| file.size)
+    }
+}
+
+impl ProjectVersionsResponse {
+    /// Sorts the versions newest first. Ones with an invalid date go last.
+    fn sort(&mut self) {
+        self.0.sort_by_key(|version| {
+            std::cmp::Reverse(chrono::DateTime::parse_from_rfc3339(&version.date_published).ok())
+        });
+    }
+
+    /// The newest `limit` versions, with when they were published relative to `now`. With
+    /// the project's slug, each version's page is linked too.
+    pub fn table(
+        &self,
+        limit: usize,
+        now: chrono::DateTime<chrono::Local>,
+        urls_of: Option<&str>,
+    ) -> Table {
+        let mut table = Table::new();
+        let mut titles = row![
+            "Version",
+            "ID",
+            "Type",
+            "Game Versions",
+            "Loaders",
+            "Dependencies",
+            "Size",
+            "Published"
+        ];
+        if urls_of.is_some() {
+            titles.add_cell(Cell::new("URL"));
+        }
+        table.add_row(titles);
+        for version in self.0.iter().take(limit) {
+            let required = version
+                .dependencies
+                .iter()
+                .filter(|dependency| dependency.dependency_type == DependencyType::Required)
+                .count();
+            let mut row = row![
+                version.version_number,
+                version.id,
+                version.version_type,
+                version.game_versions.join(", "),
+                version.loaders.join(", "),
+                format!("{required} required"),
+                version.size().map_or("-".to_string(), format::format_bytes),
+                format::format_timestamp_ago(&version.date_published, now)
+                    .unwrap_or_else(|| version.date_published.clone())
+            ];
+            if let Some(slug) = urls_of {
+                row.add_cell(Cell::new(&version_url(slug, &version.id)));
             }
-            writeln!(f, "=======================================")?;
+            table.add_row(row);
         }
-
-        Ok(())
+        table
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct ModVersion {
+    pub id: String,
     pub project_id: String,
     // Prefer using the version name over the version number.
     // Example: Multiple versions might share the version number `1.8.2`,
     // but have distinct names such as `1.8.2-1.21.5 - Fabric` or `1.8.2-1.21.6 - Fabric`.
     pub version_name: String,
-    pub hash: String,
-    pub file_url: String,
+    /// Like `0.100.1+1.21`, what version constraints are matched against.
+    pub version_number: String,
+    pub files: Vec<VersionFile>,
+    pub dependencies: Vec<ModDependency>,
+}
+
+impl ModVersion {
+    /// The file marked primary, or the first one if none is, like Modrinth picks. Versions
+    /// always have a file, see [`parse_version`].
+    pub fn primary_file(&self) -> &VersionFile {
+        self.files
+            .iter()
+            .find(|file| file.primary)
+            .unwrap_or(&self.files[0])
+    }
+
+    /// The files installing the version downloads: the primary one first, then the other
+    /// jars the server loads, see [`VersionFile::is_server_jar`].
+    pub fn install_files(&self) -> Vec<&VersionFile> {
+        let primary = self.primary_file();
+        let mut files = vec![primary];
+        files.extend(
+            self.files
+                .iter()
+                .filter(|file| !std::ptr::eq(*file, primary) && file.is_server_jar()),
+        );
+        files
+    }
+
+    /// The names of the [`ModVersion::install_files`], like `lithium-0.18.0.jar, lithium-assets.zip`.
+    pub fn install_file_names(&self) -> String {
+        self.install_files()
+            .iter()
+            .map(|file| file.file_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether any file of the version has one of the hashes.
+    pub fn has_file_with(&self, hashes: &[FileHash]) -> bool {
+        self.files.iter().any(|file| hashes.contains(&file.hash))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionFile {
+    pub url: String,
     pub file_name: String,
+    /// The hash of the file, with the algorithm the version was looked up with.
+    pub hash: FileHash,
+    /// Kept regardless of the lookup algorithm for the download cache.
+    pub sha512: String,
+    pub primary: bool,
+    /// Like `required-resource-pack` or `optional-resource-pack`, for the files that have one.
+    pub file_type: Option<String>,
+}
+
+/// Jars built next to a mod that aren't one. Modrinth doesn't mark them, and a sources jar
+/// has the `fabric.mod.json` of the mod, so loading it would duplicate the mod.
+const NON_MOD_JAR_SUFFIXES: [&str; 3] = ["-sources.jar", "-dev.jar", "-javadoc.jar"];
+
+impl VersionFile {
+    pub fn is_optional(&self) -> bool {
+        self.file_type
+            .as_deref()
+            .is_some_and(|file_type| file_type.starts_with("optional"))
+    }
+
+    /// Whether a file other than the primary one belongs in the mods directory: a jar that
+    /// isn't optional or one of [`NON_MOD_JAR_SUFFIXES`]. Resource packs don't.
+    pub fn is_server_jar(&self) -> bool {
+        let name = self.file_name.to_ascii_lowercase();
+        name.ends_with(".jar")
+            && !self.is_optional()
+            && !NON_MOD_JAR_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyType {
+    Required,
+    Optional,
+    Incompatible,
+    /// Bundled in the jar, so it isn't installed separately.
+    Embedded,
+    /// Added to Modrinth after this version of mcerv, so the other dependencies still parse.
+    #[serde(other)]
+    Unknown,
 }
 
-/// Searches for mods on Modrinth with the given query and facets.
-/// Will always add server_side facets, but this is fragile becuase of Modrinth API.
+// https://docs.modrinth.com/api/operations/getversion/
+/// Either of the IDs may be missing.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ModDependency {
+    pub version_id: Option<String>,
+    pub project_id: Option<String>,
+    pub dependency_type: DependencyType,
+}
+
+/// Searches for mods on Modrinth with the given query and facet groups.
+/// Facets are OR-ed within a group and AND-ed between groups.
+/// Adds the server_side facet group unless `client_ok` is set, but this is fragile becuase of Modrinth API.
 pub async fn search(
     client: &reqwest::Client,
     query: &str,
-    facets: &[&str],
+    facets: &[Vec<String>],
+    client_ok: bool,
     index: Option<SearchIndex>,
     limit: Option<usize>,
 ) -> anyhow::Result<SearchResponse> {
-    let mut builder = client.get("https://api.modrinth.com/v2/search");
-
-    builder = builder.query(&[("query", query)]);
+    search_from(client, API_URL, query, facets, client_ok, index, limit).await
+}
 
-    let joined = facets
-        .iter()
-        .map(|f| format!(",[\"{f}\"]"))
-        .collect::<Vec<_>>()
-        .join("");
+async fn search_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    query: &str,
+    facets: &[Vec<String>],
+    client_ok: bool,
+    index: Option<SearchIndex>,
+    limit: Option<usize>,
+) -> anyhow::Result<SearchResponse> {
+    let mut builder = authorize(client.get(format!("{api_url}/search")));
 
-    let facets = format!("[[\"server_side:required\",\"server_side:optional\"]{joined}]");
+    builder = builder.query(&[("query", query)]);
 
+    let facets = build_facets(facets, client_ok).to_string();
     builder = builder.query(&[("facets", facets)]);
 
     if let Some(i) = index {
@@ -121,19 +448,66 @@ pub async fn search(
         builder = builder.query(&[("limit", l.to_string())]);
     }
 
-    let result = builder.send().await?.error_for_status()?;
+    let result = builder
+        .send()
+        .await
+        .map_err(map_timeout)?
+        .error_for_status()?;
 
     Ok(serde_json::from_str(&result.text().await?)?)
 }
 
+/// Builds the `facets` parameter of the search endpoint.
+///
+/// Modrinth expects an array of arrays, so it is built structurally instead of by
+/// string concatenation to stay valid when a facet contains quotes.
+pub fn build_facets(groups: &[Vec<String>], client_ok: bool) -> serde_json::Value {
+    let mut facets = Vec::new();
+
+    if !client_ok {
+        facets.push(serde_json::json!([
+            "server_side:required",
+            "server_side:optional"
+        ]));
+    }
+
+    facets.extend(groups.iter().map(|group| serde_json::json!(group)));
+
+    serde_json::Value::Array(facets)
+}
+
+/// Describes the facets the search is filtered by, like
+/// `server_side:required or server_side:optional; license:mit`.
+pub fn facets_summary(groups: &[Vec<String>], client_ok: bool) -> String {
+    let facets = build_facets(groups, client_ok);
+    let groups = facets
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| {
+            group
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|facet| facet.as_str())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        })
+        .collect::<Vec<_>>();
+
+    if groups.is_empty() {
+        "none".to_string()
+    } else {
+        groups.join("; ")
+    }
+}
+
 pub async fn get_project_versions(
     client: &reqwest::Client,
     project_slug: &str,
     featured: bool,
 ) -> anyhow::Result<ProjectVersionsResponse> {
-    let mut builder = client.get(format!(
-        "https://api.modrinth.com/v2/project/{project_slug}/version"
-    ));
+    let mut builder = authorize(client.get(format!("{API_URL}/project/{project_slug}/version")));
 
     // Only filter by Fabric loader
     builder = builder.query(&[
@@ -141,181 +515,1161 @@ pub async fn get_project_versions(
         ("featured", &featured.to_string()),
     ]);
 
-    let result = builder.send().await?.error_for_status()?;
-    let response: ProjectVersionsResponse = serde_json::from_str(&result.text().await?)?;
+    let result = builder
+        .send()
+        .await
+        .map_err(map_timeout)?
+        .error_for_status()?;
+    let mut response: ProjectVersionsResponse = serde_json::from_str(&result.text().await?)?;
+    response.sort();
 
     Ok(response)
 }
 
+/// Returns the ID of the newest version of the project for the loader and game version,
+/// or `None` if there is none.
+pub async fn latest_compatible_version(
+    client: &reqwest::Client,
+    project_slug: &str,
+    loader: &str,
+    game_version: &str,
+) -> anyhow::Result<Option<String>> {
+    latest_compatible_version_from(client, API_URL, project_slug, loader, game_version).await
+}
+
+async fn latest_compatible_version_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    project_slug: &str,
+    loader: &str,
+    game_version: &str,
+) -> anyhow::Result<Option<String>> {
+    let result = authorize(client.get(format!("{api_url}/project/{project_slug}/version")))
+        .query(&[
+            ("loaders", serde_json::json!([loader]).to_string()),
+            (
+                "game_versions",
+                serde_json::json!([game_version]).to_string(),
+            ),
+        ])
+        .send()
+        .await
+        .map_err(map_timeout)?
+        .error_for_status()?;
+
+    let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
+
+    // Newest first
+    let id = response
+        .as_array()
+        .and_then(|versions| versions.first())
+        .and_then(|version| version["id"].as_str())
+        .map(String::from);
+
+    Ok(id)
+}
+
+/// The versions of the project for the loader and game version, newest first.
+pub(crate) async fn compatible_versions_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    project_id: &str,
+    loader: &str,
+    game_version: &str,
+) -> anyhow::Result<Vec<ModVersion>> {
+    let result = authorize(client.get(format!("{api_url}/project/{project_id}/version")))
+        .query(&[
+            ("loaders", serde_json::json!([loader]).to_string()),
+            (
+                "game_versions",
+                serde_json::json!([game_version]).to_string(),
+            ),
+        ])
+        .send()
+        .await
+        .map_err(map_timeout)?
+        .error_for_status()?;
+
+    let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
+
+    response
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|version| parse_version(version, HashAlgorithm::Sha512))
+        .collect()
+}
+
+/// Whether each project has a version for the loader and game version, by project ID.
+/// Projects Modrinth doesn't know are left out.
+///
+/// It's told from the game versions and loaders of the projects, requested together. Only a
+/// project with more than one of each could lack a version for the pair, so only their
+/// versions are requested.
+pub async fn projects_support(
+    client: &reqwest::Client,
+    project_ids: &[impl AsRef<str>],
+    loader: &str,
+    game_version: &str,
+) -> anyhow::Result<HashMap<String, bool>> {
+    projects_support_from(client, API_URL, project_ids, loader, game_version).await
+}
+
+async fn projects_support_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    project_ids: &[impl AsRef<str>],
+    loader: &str,
+    game_version: &str,
+) -> anyhow::Result<HashMap<String, bool>> {
+    let projects = get_projects_from(client, api_url, project_ids).await?;
+
+    let mut support = HashMap::new();
+    let mut ambiguous = Vec::new();
+    for (id, project) in projects {
+        let has_game_version = project.game_versions.iter().any(|v| v == game_version);
+        let has_loader = project.loaders.iter().any(|l| l == loader);
+        if !has_game_version || !has_loader {
+            support.insert(id, false);
+        } else if project.loaders.len() == 1 || project.game_versions.len() == 1 {
+            support.insert(id, true);
+        } else {
+            ambiguous.push(id);
+        }
+    }
+
+    let responses = futures::stream::iter(ambiguous)
+        .map(|id| async move {
+            let versions =
+                compatible_versions_from(client, api_url, &id, loader, game_version).await;
+            (id, versions)
+        })
+        .buffer_unordered(CONCURRENT_PROJECT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await;
+    for (id, versions) in responses {
+        support.insert(id, !versions?.is_empty());
+    }
+
+    Ok(support)
+}
+
+/// A mod of [`resolve_with_dependencies`] without a version to install.
+#[derive(Debug, PartialEq)]
+pub struct UnresolvedMod {
+    /// The slug or project ID.
+    pub project: String,
+    /// The project that requires it, if it's a dependency.
+    pub required_by: Option<String>,
+    pub reason: String,
+}
+
+impl Display for UnresolvedMod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.project)?;
+        if let Some(required_by) = &self.required_by {
+            write!(f, " (required by {required_by})")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// The newest versions of the projects for the loader and game version, and of the
+/// projects they require, each project once. Projects without such a version, or that
+/// failed to be requested, are returned instead of failing the others.
+pub async fn resolve_with_dependencies(
+    client: &reqwest::Client,
+    projects: &[impl AsRef<str>],
+    loader: &str,
+    game_version: &str,
+) -> (Vec<ModVersion>, Vec<UnresolvedMod>) {
+    resolve_with_dependencies_from(client, API_URL, projects, loader, game_version).await
+}
+
+async fn resolve_with_dependencies_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    projects: &[impl AsRef<str>],
+    loader: &str,
+    game_version: &str,
+) -> (Vec<ModVersion>, Vec<UnresolvedMod>) {
+    let (mut resolved, mut unresolved) = (Vec::<ModVersion>::new(), Vec::new());
+    let mut requested = projects
+        .iter()
+        .map(|project| project.as_ref().to_string())
+        .collect::<HashSet<_>>();
+    let mut pending = requested
+        .iter()
+        .map(|project| (project.clone(), None))
+        .collect::<Vec<_>>();
+
+    // One round per level of dependencies
+    while !pending.is_empty() {
+        let responses = futures::stream::iter(std::mem::take(&mut pending))
+            .map(
+                |(project, required_by): (String, Option<String>)| async move {
+                    let versions =
+                        compatible_versions_from(client, api_url, &project, loader, game_version)
+                            .await;
+                    (project, required_by, versions)
+                },
+            )
+            .buffer_unordered(CONCURRENT_PROJECT_REQUESTS)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (project, required_by, versions) in responses {
+            let version = match versions.map(|versions| versions.into_iter().next()) {
+                Ok(Some(version)) => version,
+                Ok(None) => {
+                    unresolved.push(UnresolvedMod {
+                        project,
+                        required_by,
+                        reason: format!("no version for {loader} {game_version}"),
+                    });
+                    continue;
+                }
+                Err(e) => {
+                    unresolved.push(UnresolvedMod {
+                        project,
+                        required_by,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            // Asked for by slug and required by ID
+            if resolved.iter().any(|v| v.project_id == version.project_id) {
+                continue;
+            }
+            requested.insert(version.project_id.clone());
+
+            for dependency in &version.dependencies {
+                if dependency.dependency_type == DependencyType::Required
+                    && let Some(project_id) = &dependency.project_id
+                    && requested.insert(project_id.clone())
+                {
+                    pending.push((project_id.clone(), Some(project.clone())));
+                }
+            }
+            resolved.push(version);
+        }
+    }
+
+    unresolved.sort_by(|a, b| a.project.cmp(&b.project));
+    (resolved, unresolved)
+}
+
+/// Downloads the primary file of the version and its other required files through the
+/// cache, returning the version.
 pub async fn download_version(
     client: &reqwest::Client,
     version_id: &str,
     save_dir_path: impl AsRef<Path>,
-) -> anyhow::Result<String> {
-    let result = client
-        .get(format!("https://api.modrinth.com/v2/version/{version_id}"))
+    cache: &DownloadCache,
+) -> anyhow::Result<ModVersion> {
+    let result = authorize(client.get(format!("{API_URL}/version/{version_id}")))
         .send()
-        .await?
+        .await
+        .map_err(map_timeout)?
         .error_for_status()?;
 
     let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
+    let version = parse_version(&response, HashAlgorithm::Sha512)?;
+    let files = version.install_files();
 
-    let files = response["files"].as_array().unwrap();
-
-    if files.len() > 1 {
-        println!(
-            "Multiple files found for version {version_id}. Downloading the first on the list..."
-        );
+    let downloads: Vec<_> = files
+        .iter()
+        .map(|file| {
+            (
+                file.url.as_str(),
+                file.sha512.as_str(),
+                save_dir_path.as_ref().join(&file.file_name),
+            )
+        })
+        .collect();
+    let cached = download_files(client, &downloads, cache).await?;
+    for (file, cached) in files.iter().zip(cached) {
+        if cached {
+            println!("Using {} from the cache.", file.file_name);
+        }
     }
 
-    let url = files[0]["url"].as_str().unwrap();
-    let file_name = files[0]["filename"].as_str().unwrap();
-    let file_path = save_dir_path.as_ref().join(file_name);
-    download_file(client, &url, &file_path).await?;
+    Ok(version)
+}
+
+/// Whether a project is needed on one side, the client or the server.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SideSupport {
+    Required,
+    Optional,
+    Unsupported,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Project {
+    pub id: String,
+    pub slug: String,
+    pub title: String,
+    pub client_side: SideSupport,
+    pub server_side: SideSupport,
+    /// Like `library` or `optimization`.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Like `approved`, or `archived` for projects that won't get updates.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Every game version any version of the project is for.
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+    /// Every loader any version of the project is for, like `fabric`.
+    #[serde(default)]
+    pub loaders: Vec<String>,
+}
 
-    Ok(file_name.to_string())
+impl Project {
+    pub fn is_archived(&self) -> bool {
+        self.status.as_deref() == Some("archived")
+    }
 }
 
 // https://docs.modrinth.com/api/operations/getprojects/
 // Cannot just return vec like other functions. This response will not guarantee the order.
-/// Returns a map of project IDs to slugs.
-pub async fn get_project_slug_map<I, S>(
+/// Returns a map of project IDs to projects. Repeated IDs are requested once, and many IDs
+/// are split into a few requests at a time.
+pub async fn get_projects<I, S>(
     client: &reqwest::Client,
     project_ids: I,
-) -> anyhow::Result<HashMap<String, String>>
+) -> anyhow::Result<HashMap<String, Project>>
 where
     I: IntoIterator<Item = S>,
     S: AsRef<str>,
 {
-    let project_ids = format!(
-        "[{}]",
-        project_ids
-            .into_iter()
-            .map(|id| format!("\"{}\"", id.as_ref()))
-            .collect::<Vec<_>>()
-            .join(",")
-    );
+    get_projects_from(client, API_URL, project_ids).await
+}
 
-    let result = client
-        .get("https://api.modrinth.com/v2/projects")
-        .query(&[("ids", project_ids)])
-        .send()
-        .await?
-        .error_for_status()?;
+pub(crate) async fn get_projects_from<I, S>(
+    client: &reqwest::Client,
+    api_url: &str,
+    project_ids: I,
+) -> anyhow::Result<HashMap<String, Project>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut unique_ids = Vec::new();
+    for id in project_ids {
+        let id = id.as_ref().to_string();
+        if !unique_ids.contains(&id) {
+            unique_ids.push(id);
+        }
+    }
 
-    let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
+    let responses = futures::stream::iter(unique_ids.chunks(PROJECTS_PER_REQUEST))
+        .map(|ids| get_project_chunk(client, api_url, ids))
+        .buffer_unordered(CONCURRENT_PROJECT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await;
 
-    let array = response.as_array().unwrap();
+    let mut projects = HashMap::new();
+    for response in responses {
+        projects.extend(
+            response?
+                .into_iter()
+                .map(|project| (project.id.clone(), project)),
+        );
+    }
+    Ok(projects)
+}
 
-    let slug_map = array
-        .iter()
-        .map(|project| {
-            let id = project["id"].as_str().unwrap().to_string();
-            let slug = project["slug"].as_str().unwrap().to_string();
-            (id, slug)
-        })
-        .collect::<HashMap<_, _>>();
+async fn get_project_chunk(
+    client: &reqwest::Client,
+    api_url: &str,
+    project_ids: &[String],
+) -> anyhow::Result<Vec<Project>> {
+    let result = authorize(client.get(format!("{api_url}/projects")))
+        .query(&[("ids", serde_json::to_string(project_ids)?)])
+        .send()
+        .await
+        .map_err(map_timeout)?
+        .error_for_status()?;
 
-    Ok(slug_map)
+    Ok(serde_json::from_str(&result.text().await?)?)
 }
 
-// https://docs.modrinth.com/api/operations/versionsfromhashes/
-pub async fn get_versions(
+// https://docs.modrinth.com/api/operations/getversions/
+/// The versions with the IDs, in no particular order. IDs Modrinth doesn't know are left out.
+pub async fn get_versions_by_id(
     client: &reqwest::Client,
-    jar_hashes: &[impl AsRef<str>],
+    version_ids: &[impl AsRef<str>],
 ) -> anyhow::Result<Vec<ModVersion>> {
-    let request_body: serde_json::Value = serde_json::json!({
-        "hashes": jar_hashes.iter().map(|h| h.as_ref()).collect::<Vec<_>>(),
-        "algorithm": "sha1",
-    });
+    get_versions_by_id_from(client, API_URL, version_ids).await
+}
 
-    let result = client
-        .post("https://api.modrinth.com/v2/version_files")
-        .header("Content-Type", "application/json")
-        .json(&request_body)
+pub(crate) async fn get_versions_by_id_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    version_ids: &[impl AsRef<str>],
+) -> anyhow::Result<Vec<ModVersion>> {
+    let version_ids =
+        serde_json::to_string(&version_ids.iter().map(|id| id.as_ref()).collect::<Vec<_>>())?;
+
+    let result = authorize(client.get(format!("{api_url}/versions")))
+        .query(&[("ids", version_ids)])
         .send()
-        .await?
+        .await
+        .map_err(map_timeout)?
         .error_for_status()?;
 
-    let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
-    parse_version_response(response, jar_hashes)
+    let versions: Vec<serde_json::Value> = serde_json::from_str(&result.text().await?)?;
+    versions
+        .iter()
+        .map(|version| parse_version(version, HashAlgorithm::Sha512))
+        .collect()
+}
+
+/// The page of the project on the Modrinth website.
+pub fn project_url(slug: &str) -> String {
+    format!("{SITE_URL}/mod/{slug}")
+}
+
+pub fn version_url(slug: &str, version_id: &str) -> String {
+    format!("{}/version/{version_id}", project_url(slug))
+}
+
+/// The slug of the project, or its ID if Modrinth didn't return it, which works in place of
+/// the slug too.
+pub fn project_slug<'a>(projects: &'a HashMap<String, Project>, project_id: &'a str) -> &'a str {
+    projects
+        .get(project_id)
+        .map_or(project_id, |project| project.slug.as_str())
+}
+
+/// Whether the slug is one Modrinth allows, 3 to 64 letters, digits and a few symbols.
+pub fn is_valid_slug(slug: &str) -> bool {
+    (3..=64).contains(&slug.chars().count())
+        && slug
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_!@$()`.+,\"-'".contains(c))
+}
+
+// https://docs.modrinth.com/api/operations/versionsfromhashes/
+/// The version of each jar, `None` for a jar that isn't on Modrinth.
+pub async fn find_versions(
+    client: &reqwest::Client,
+    jar_hashes: &[Vec<FileHash>],
+) -> anyhow::Result<Vec<Option<ModVersion>>> {
+    find_versions_from(client, API_URL, jar_hashes).await
+}
+
+pub(crate) async fn find_versions_from(
+    client: &reqwest::Client,
+    api_url: &str,
+    jar_hashes: &[Vec<FileHash>],
+) -> anyhow::Result<Vec<Option<ModVersion>>> {
+    let url = format!("{api_url}/version_files");
+    lookup_known_versions(client, &url, serde_json::json!({}), jar_hashes).await
 }
 
 // https://docs.modrinth.com/api/operations/getlatestversionfromhash/
-pub async fn get_latest_versions(
+/// The newest version of each jar's project for the game versions, or `None` for a jar
+/// that isn't on Modrinth or has no version for them.
+pub(crate) async fn find_latest_versions_from(
     client: &reqwest::Client,
-    jar_hashes: &[impl AsRef<str>],
+    api_url: &str,
+    jar_hashes: &[Vec<FileHash>],
     game_versions: &[impl AsRef<str>],
-) -> anyhow::Result<Vec<ModVersion>> {
-    let request_body: serde_json::Value = serde_json::json!({
-        "hashes": jar_hashes.iter().map(|h| h.as_ref()).collect::<Vec<_>>(),
-        "algorithm": "sha1",
+) -> anyhow::Result<Vec<Option<ModVersion>>> {
+    let url = format!("{api_url}/version_files/update");
+    lookup_known_versions(client, &url, latest_request(game_versions), jar_hashes).await
+}
+
+fn latest_request(game_versions: &[impl AsRef<str>]) -> serde_json::Value {
+    serde_json::json!({
         "loaders": ["fabric"], // hardcoded fabric
         "game_versions": game_versions.iter().map(|v| v.as_ref()).collect::<Vec<_>>()
-    });
+    })
+}
+
+/// Looks the hashes up with each of [`LOOKUP_ALGORITHMS`] in turn, only sending the hashes
+/// that are still unknown to the next one. Returns the versions in the order of `jar_hashes`.
+async fn lookup_known_versions(
+    client: &reqwest::Client,
+    url: &str,
+    mut request_body: serde_json::Value,
+    jar_hashes: &[Vec<FileHash>],
+) -> anyhow::Result<Vec<Option<ModVersion>>> {
+    let mut versions: Vec<Option<ModVersion>> = jar_hashes.iter().map(|_| None).collect();
+    let mut any_succeeded = false;
+
+    for algorithm in LOOKUP_ALGORITHMS {
+        let pending = jar_hashes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| versions[*i].is_none())
+            .filter_map(|(i, hashes)| {
+                let hash = hashes.iter().find(|h| h.algorithm == algorithm)?;
+                Some((i, hash.value.as_str()))
+            })
+            .collect::<Vec<_>>();
+
+        if pending.is_empty() {
+            continue;
+        }
+
+        request_body["hashes"] =
+            serde_json::json!(pending.iter().map(|(_, h)| h).collect::<Vec<_>>());
+        request_body["algorithm"] = serde_json::json!(algorithm.extension());
+
+        let response = match post_lookup(client, url, &request_body).await? {
+            Ok(response) => {
+                any_succeeded = true;
+                response
+            }
+            // A single bad hash, like of an archived project, can fail the whole batch
+            Err(status) => {
+                let mut response = serde_json::Map::new();
+                for (_, hash) in &pending {
+                    // The batch was this one hash already
+                    let retried = if pending.len() == 1 {
+                        Err(status)
+                    } else {
+                        request_body["hashes"] = serde_json::json!([hash]);
+                        post_lookup(client, url, &request_body).await?
+                    };
+                    match retried {
+                        Ok(found) => {
+                            any_succeeded = true;
+                            if let serde_json::Value::Object(found) = found {
+                                response.extend(found);
+                            }
+                        }
+                        Err(hash_status) => eprintln!(
+                            "Warning: failed to look up the {} hash {hash} on Modrinth: {hash_status}",
+                            algorithm.extension()
+                        ),
+                    }
+                }
+                // Nothing works, so it's not about a hash
+                if !any_succeeded {
+                    anyhow::bail!("Modrinth responded with {status} to {url}");
+                }
+                serde_json::Value::Object(response)
+            }
+        };
+
+        for (i, hash) in pending {
+            if let Some(value) = response.get(hash) {
+                versions[i] = Some(parse_version(value, algorithm)?);
+            }
+        }
+    }
+
+    Ok(versions)
+}
 
-    let result = client
-        .post("https://api.modrinth.com/v2/version_files/update")
+/// Posts a hash lookup, returning the versions by hash, or the status it failed with.
+async fn post_lookup(
+    client: &reqwest::Client,
+    url: &str,
+    request_body: &serde_json::Value,
+) -> anyhow::Result<Result<serde_json::Value, StatusCode>> {
+    let result = authorize(client.post(url))
         .header("Content-Type", "application/json")
-        .json(&request_body)
+        .json(request_body)
         .send()
-        .await?
-        .error_for_status()?;
+        .await
+        .map_err(map_timeout)?;
+    if !result.status().is_success() {
+        return Ok(Err(result.status()));
+    }
 
-    let response: serde_json::Value = serde_json::from_str(&result.text().await?)?;
-    parse_version_response(response, jar_hashes)
+    Ok(Ok(serde_json::from_str(&result.text().await?)?))
 }
 
-fn parse_version_response(
-    response: serde_json::Value,
-    jar_hashes: &[impl AsRef<str>],
-) -> anyhow::Result<Vec<ModVersion>> {
-    let response_map = response.as_object().unwrap();
-
-    let versions = jar_hashes
+/// Fails for a version without files, which [`ModVersion::primary_file`] relies on.
+fn parse_version(
+    value: &serde_json::Value,
+    algorithm: HashAlgorithm,
+) -> anyhow::Result<ModVersion> {
+    let id = value["id"].as_str().unwrap().to_string();
+    let project_id = value["project_id"].as_str().unwrap().to_string();
+    let version_name = value["name"].as_str().unwrap_or("N/A").to_string();
+    let version_number = value["version_number"].as_str().unwrap_or("").to_string();
+    let files = value["files"]
+        .as_array()
+        .unwrap()
         .iter()
-        .map(|hash| {
-            let value = &response_map[hash.as_ref()];
-            let project_id = value["project_id"].as_str().unwrap().to_string();
-            let version_name = value["name"].as_str().unwrap_or("N/A").to_string();
-            let files = value["files"].as_array().unwrap();
-
-            if files.len() > 1 {
-                println!("Multiple files found for version {version_name}. Using the first one.");
+        .map(|file| {
+            let sha512 = file["hashes"]["sha512"].as_str().unwrap().to_string();
+            // Joined into the mods directory, so it can't be trusted to stay in it
+            let remote_name = file["filename"].as_str().unwrap();
+            let file_name = filename::sanitize_filename(remote_name, &sha512);
+            if file_name != remote_name {
+                eprintln!("Warning: saving {remote_name:?} from Modrinth as {file_name}");
             }
-
-            let file = &files[0];
-            let hash = file["hashes"]["sha1"].as_str().unwrap().to_string();
-            let file_url = file["url"].as_str().unwrap().to_string();
-            let file_name = file["filename"].as_str().unwrap().to_string();
-
-            ModVersion {
-                project_id,
-                version_name,
-                hash,
-                file_url,
+            VersionFile {
+                url: file["url"].as_str().unwrap().to_string(),
                 file_name,
+                hash: FileHash {
+                    algorithm,
+                    value: file["hashes"][algorithm.extension()]
+                        .as_str()
+                        .unwrap()
+                        .to_string(),
+                },
+                sha512,
+                primary: file["primary"].as_bool().unwrap_or(false),
+                file_type: file["file_type"].as_str().map(str::to_string),
             }
         })
-        .collect();
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!files.is_empty(), "Version {id} on Modrinth has no files");
+    let dependencies = serde_json::from_value(value["dependencies"].clone()).unwrap_or_default();
 
-    Ok(versions)
+    Ok(ModVersion {
+        id,
+        project_id,
+        version_name,
+        version_number,
+        files,
+        dependencies,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mockito::{Matcher, Server};
+
+    const SODIUM_SHA1: &str = "0b6e6d9c7e4b5f0b63a3b2e3c9c5b6d4a1f2e3d4";
+    const SODIUM_SHA512: &str = "5f8d3e0c0d1c8e52a0f4b9c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1";
+    const OLD_MOD_SHA1: &str = "c3a1d2b4e5f60718293a4b5c6d7e8f9012345678";
+    const OLD_MOD_SHA512: &str = "0e1d2c3b4a5968778695a4b3c2d1e0f1e2d3c4b5a69788796a5b4c3d2e1f0e1d2c3b4a5968778695a4b3c2d1e0f1e2d3c4b5a69788796a5b4c3d2e1f0e1d2c3";
+
+    fn hashes(sha1: &str, sha512: &str) -> Vec<FileHash> {
+        vec![
+            FileHash {
+                algorithm: HashAlgorithm::Sha512,
+                value: sha512.to_string(),
+            },
+            FileHash {
+                algorithm: HashAlgorithm::Sha1,
+                value: sha1.to_string(),
+            },
+        ]
+    }
+
+    /// A `version_files` response entry, as Modrinth returns it.
+    fn version_json(project_id: &str, name: &str, sha1: &str, sha512: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": format!("{name}-id"),
+            "project_id": project_id,
+            "name": name,
+            "files": [{
+                "hashes": { "sha1": sha1, "sha512": sha512 },
+                "url": format!("https://cdn.modrinth.com/data/{project_id}/{name}.jar"),
+                "filename": format!("{name}.jar"),
+            }]
+        })
+    }
+
+    #[test]
+    fn test_build_facets() {
+        let groups =
+            facets::parse_groups(&["license:mit", "versions:1.21.7,versions:1.21.8"]).unwrap();
+        let facets = build_facets(&groups, false);
+        assert_eq!(
+            facets.to_string(),
+            r#"[["server_side:required","server_side:optional"],["license:mit"],["versions:1.21.7","versions:1.21.8"]]"#
+        );
+
+        let facets = build_facets(&[], true);
+        assert_eq!(facets.to_string(), "[]");
+    }
+
+    #[test]
+    fn test_build_facets_with_quotes_and_spaces() {
+        let groups =
+            facets::parse_groups(&["title:\"mini map\"", " categories:adventure "]).unwrap();
+        let facets = build_facets(&groups, true);
+        let parsed: Vec<Vec<String>> = serde_json::from_str(&facets.to_string()).unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                vec!["title:\"mini map\"".to_string()],
+                vec!["categories:adventure".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_valid_slug() {
+        assert!(is_valid_slug("fabric-api"));
+        assert!(is_valid_slug("xaeros_minimap"));
+        assert!(!is_valid_slug("ab"));
+        assert!(!is_valid_slug("fabric api"));
+        assert!(!is_valid_slug("../lithium"));
+    }
+
+    #[test]
+    fn test_display_search_response() {
+        let response: SearchResponse = serde_json::from_str(
+            r#"{"hits":[{"title":"Lithium","slug":"lithium","project_id":"gvQqBUqZ"}],"total_hits":1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.total_hits, 1);
+        let s = response.to_string();
+        assert!(s.contains("title: \"Lithium\""));
+        assert!(s.contains("slug: \"lithium\""));
+        assert!(s.contains("author: N/A"));
+        assert!(!s.contains("url"));
+
+        let response = SearchResponse {
+            urls: true,
+            ..response
+        };
+        let s = response.to_string();
+        assert!(s.contains("url: https://modrinth.com/mod/lithium"));
+        assert!(s.contains("icon_url: N/A"));
+    }
+
+    #[test]
+    fn test_facets_summary() {
+        let groups =
+            facets::parse_groups(&["license:mit", "versions:1.21.7,versions:1.21.8"]).unwrap();
+        assert_eq!(
+            facets_summary(&groups, false),
+            "server_side:required or server_side:optional; license:mit; versions:1.21.7 or versions:1.21.8"
+        );
+        assert_eq!(facets_summary(&[], true), "none");
+    }
+
+    #[tokio::test]
+    async fn test_search_from_total_hits() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/search")
+            .match_query(Matcher::UrlEncoded("query".into(), "lithum".into()))
+            .with_body(r#"{"hits":[],"offset":0,"limit":10,"total_hits":0}"#)
+            .create_async()
+            .await;
+
+        let response = search_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            "lithum",
+            &[],
+            false,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.total_hits, 0);
+        assert!(response.hits.is_empty());
+    }
+
+    fn version_file(
+        name: &str,
+        primary: Option<bool>,
+        file_type: Option<&str>,
+    ) -> serde_json::Value {
+        let mut file = serde_json::json!({
+            "hashes": { "sha1": format!("{name}-sha1"), "sha512": format!("{name}-sha512") },
+            "url": format!("https://cdn.modrinth.com/data/AANobbMI/{name}"),
+            "filename": name,
+            "file_type": file_type,
+        });
+        if let Some(primary) = primary {
+            file["primary"] = primary.into();
+        }
+        file
+    }
+
+    fn install_file_names(files: Vec<serde_json::Value>) -> Vec<String> {
+        let value = serde_json::json!({
+            "id": "gl30uZvp",
+            "project_id": "AANobbMI",
+            "name": "mc1.21.8-0.6.13",
+            "files": files,
+        });
+        parse_version(&value, HashAlgorithm::Sha1)
+            .unwrap()
+            .install_files()
+            .into_iter()
+            .map(|file| file.file_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_install_files() {
+        // The server jar isn't listed first, and the jars built next to it aren't mods
+        assert_eq!(
+            install_file_names(vec![
+                version_file("sodium-sources.jar", Some(false), None),
+                version_file("sodium.jar", Some(true), None),
+                version_file("sodium-dev.jar", Some(false), None),
+                version_file("sodium-javadoc.jar", Some(false), None),
+                version_file("sodium-fabric-api.jar", Some(false), None),
+            ]),
+            ["sodium.jar", "sodium-fabric-api.jar"]
+        );
+        assert_eq!(
+            install_file_names(vec![
+                version_file(
+                    "pack-optional.zip",
+                    Some(false),
+                    Some("optional-resource-pack")
+                ),
+                version_file("pack.jar", Some(true), None),
+                version_file(
+                    "pack-required.zip",
+                    Some(false),
+                    Some("required-resource-pack")
+                ),
+            ]),
+            ["pack.jar"]
+        );
+        // Like Modrinth, the first file is primary when none is marked
+        assert_eq!(
+            install_file_names(vec![
+                version_file("a.jar", None, None),
+                version_file("b.jar", Some(false), None),
+            ]),
+            ["a.jar", "b.jar"]
+        );
+        assert_eq!(
+            install_file_names(vec![version_file("only.jar", None, None)]),
+            ["only.jar"]
+        );
+    }
+
+    #[test]
+    fn test_parse_version_without_files() {
+        let value = serde_json::json!({
+            "id": "gl30uZvp",
+            "project_id": "AANobbMI",
+            "files": [],
+        });
+        assert!(parse_version(&value, HashAlgorithm::Sha1).is_err());
+    }
+
+    #[test]
+    fn test_parse_version_with_unknown_dependency_type() {
+        let value = serde_json::json!({
+            "id": "gl30uZvp",
+            "project_id": "AANobbMI",
+            "files": [version_file("sodium.jar", Some(true), None)],
+            "dependencies": [
+                { "project_id": "P7dR8mSH", "dependency_type": "required" },
+                { "project_id": "YL57xq9U", "dependency_type": "recommended" },
+            ],
+        });
+        let version = parse_version(&value, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(
+            version
+                .dependencies
+                .iter()
+                .map(|dependency| dependency.dependency_type)
+                .collect::<Vec<_>>(),
+            [DependencyType::Required, DependencyType::Unknown]
+        );
+    }
+
+    #[test]
+    fn test_parse_version_sanitizes_file_names() {
+        let mut file = version_file("../../start_script.sh", Some(true), None);
+        file["hashes"]["sha512"] = "0123456789abcdef0123".into();
+        assert_eq!(install_file_names(vec![file]), ["0123456789abcdef.sh"]);
+    }
+
+    #[test]
+    fn test_has_file_with() {
+        let value = serde_json::json!({
+            "id": "gl30uZvp",
+            "project_id": "AANobbMI",
+            "name": "mc1.21.8-0.6.13",
+            "files": [
+                version_file("sodium.jar", Some(true), None),
+                version_file("sodium-server.jar", Some(false), None),
+            ],
+        });
+        let version = parse_version(&value, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(version.primary_file().file_name, "sodium.jar");
+        assert!(version.has_file_with(&[FileHash {
+            algorithm: HashAlgorithm::Sha1,
+            value: "sodium-server.jar-sha1".to_string(),
+        }]));
+        assert!(!version.has_file_with(&[FileHash {
+            algorithm: HashAlgorithm::Sha512,
+            value: "sodium-server.jar-sha1".to_string(),
+        }]));
+    }
+
+    #[tokio::test]
+    async fn test_get_projects() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/projects")
+            .match_query(Matcher::UrlEncoded(
+                "ids".into(),
+                r#"["1bokaNcj","gvQqBUqZ"]"#.into(),
+            ))
+            .with_body(
+                r#"[
+                    {"id":"gvQqBUqZ","slug":"lithium","title":"Lithium","client_side":"optional","server_side":"optional"},
+                    {"id":"1bokaNcj","slug":"xaeros-minimap","title":"Xaero's Minimap","client_side":"required","server_side":"unsupported","status":"archived"}
+                ]"#,
+            )
+            .create_async()
+            .await;
+
+        let projects = get_projects_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            ["1bokaNcj", "gvQqBUqZ"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects["gvQqBUqZ"].slug, "lithium");
+        assert_eq!(projects["1bokaNcj"].server_side, SideSupport::Unsupported);
+        assert!(projects["1bokaNcj"].is_archived());
+        assert!(!projects["gvQqBUqZ"].is_archived());
+        assert_eq!(project_slug(&projects, "gvQqBUqZ"), "lithium");
+        assert_eq!(project_slug(&projects, "P7dR8mSH"), "P7dR8mSH");
+    }
+
+    #[tokio::test]
+    async fn test_get_projects_chunked() {
+        let ids = (0..150).map(|i| format!("p{i:03}")).collect::<Vec<_>>();
+        let project = |id: &String| {
+            serde_json::json!({
+                "id": id,
+                "slug": format!("{id}-slug"),
+                "title": id,
+                "client_side": "optional",
+                "server_side": "required",
+            })
+        };
+
+        let mut server = Server::new_async().await;
+        let mut mocks = Vec::new();
+        for chunk in ids.chunks(PROJECTS_PER_REQUEST) {
+            let mock = server
+                .mock("GET", "/projects")
+                .match_query(Matcher::UrlEncoded(
+                    "ids".into(),
+                    serde_json::to_string(chunk).unwrap(),
+                ))
+                .with_body(serde_json::Value::from_iter(chunk.iter().map(project)).to_string())
+                .expect(1)
+                .create_async()
+                .await;
+            mocks.push(mock);
+        }
+
+        // Every project twice, like a mod installed twice
+        let projects = get_projects_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            ids.iter().chain(&ids),
+        )
+        .await
+        .unwrap();
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(projects.len(), 150);
+        assert_eq!(projects["p149"].slug, "p149-slug");
+        assert_eq!(projects["p000"].server_side, SideSupport::Required);
+
+        let projects = get_projects_from(&reqwest::Client::new(), &server.url(), [""; 0])
+            .await
+            .unwrap();
+        assert!(projects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_projects_support() {
+        let project = |id: &str, game_versions: &[&str], loaders: &[&str]| {
+            serde_json::json!({
+                "id": id,
+                "slug": id,
+                "title": id,
+                "client_side": "optional",
+                "server_side": "required",
+                "game_versions": game_versions,
+                "loaders": loaders,
+            })
+        };
+        // Only fabric, so a version for 1.21.8 is for fabric
+        let mut ids = (0..120).map(|i| format!("p{i:03}")).collect::<Vec<_>>();
+        let mut projects = ids
+            .iter()
+            .map(|id| project(id, &["1.21.7", "1.21.8"], &["fabric"]))
+            .collect::<Vec<_>>();
+        projects.push(project("old", &["1.20.1"], &["fabric"]));
+        projects.push(project("forge-only", &["1.21.8"], &["forge"]));
+        projects.push(project("both", &["1.21.7", "1.21.8"], &["fabric", "forge"]));
+        ids.extend(["old", "forge-only", "both"].map(String::from));
+
+        let mut server = Server::new_async().await;
+        let mut mocks = Vec::new();
+        for (ids, projects) in ids
+            .chunks(PROJECTS_PER_REQUEST)
+            .zip(projects.chunks(PROJECTS_PER_REQUEST))
+        {
+            let mock = server
+                .mock("GET", "/projects")
+                .match_query(Matcher::UrlEncoded(
+                    "ids".into(),
+                    serde_json::to_string(ids).unwrap(),
+                ))
+                .with_body(serde_json::Value::from(projects).to_string())
+                .expect(1)
+                .create_async()
+                .await;
+            mocks.push(mock);
+        }
+        // Only the project with several of both is looked into, its 1.21.8 versions are for forge
+        let mock = server
+            .mock("GET", "/project/both/version")
+            .match_query(Matcher::Any)
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+        mocks.push(mock);
+
+        let support = projects_support_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &ids,
+            "fabric",
+            "1.21.8",
+        )
+        .await
+        .unwrap();
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(support.len(), 123);
+        assert!(support["p000"]);
+        assert!(!support["old"]);
+        assert!(!support["forge-only"]);
+        assert!(!support["both"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_versions_by_id() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/versions")
+            .match_query(Matcher::UrlEncoded(
+                "ids".into(),
+                r#"["sodium-0.6.13-id","unknown"]"#.into(),
+            ))
+            .with_body(
+                serde_json::json!([version_json(
+                    "AANobbMI",
+                    "sodium-0.6.13",
+                    SODIUM_SHA1,
+                    SODIUM_SHA512
+                )])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let versions = get_versions_by_id_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &["sodium-0.6.13-id", "unknown"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].project_id, "AANobbMI");
+        assert_eq!(versions[0].primary_file().file_name, "sodium-0.6.13.jar");
+        assert_eq!(versions[0].primary_file().sha512, SODIUM_SHA512);
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let token = ModrinthToken::new("mrp_secret".to_string());
+        assert!(
+            !format!(
+                "{:?}",
+                ModrinthSettings {
+                    token: Some(token.clone())
+                }
+            )
+            .contains("mrp_")
+        );
+        set_token(Some(&token)).unwrap();
+
+        let mut server = Server::new_async().await;
+        let whoami = server
+            .mock("GET", "/user")
+            .match_header("authorization", "mrp_secret")
+            .with_body(r#"{"id":"MpxzqsyW","username":"bowen","email":null}"#)
+            .create_async()
+            .await;
+        let search = server
+            .mock("GET", "/search")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "mrp_secret")
+            .with_body(r#"{"hits":[],"total_hits":0}"#)
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new();
+        let user = whoami_from(&client, &server.url()).await.unwrap();
+        assert_eq!(
+            user,
+            ModrinthUser {
+                id: "MpxzqsyW".to_string(),
+                username: "bowen".to_string(),
+            }
+        );
+        search_from(&client, &server.url(), "lithium", &[], false, None, None)
+            .await
+            .unwrap();
+        whoami.assert_async().await;
+        search.assert_async().await;
+
+        // Never printed with the request
+        let request = authorize(client.get(server.url())).build().unwrap();
+        assert!(!format!("{request:?}").contains("mrp_"));
+        // The token is global, so the other tests don't send it
+        set_token(None).unwrap();
+
+        assert!(is_modrinth_url(
+            "https://cdn.modrinth.com/data/gvQqBUqZ/lithium.jar"
+        ));
+        assert!(!is_modrinth_url(
+            "https://mirror.example/modrinth/data/lithium.jar"
+        ));
+    }
 
     #[tokio::test]
     async fn test_search_with_filters() {
         let client = reqwest::Client::new();
         let query = "map";
-        let facets = ["license:mit", "project_type:mod"];
+        let facets = facets::parse_groups(&["license:mit", "project_type:mod"]).unwrap();
 
         let result = search(
             &client,
             query,
             &facets,
+            false,
             Some(SearchIndex::Downloads),
             Some(4),
         )
@@ -328,10 +1682,24 @@ mod tests {
         let client = reqwest::Client::new();
         let query = "map";
 
-        let result = search(&client, query, &[], None, None).await;
+        let result = search(&client, query, &[], false, None, None).await;
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    #[ignore = "requires network access to Modrinth"]
+    async fn test_search_excludes_client_only_mods() {
+        let client = reqwest::Client::new();
+        let response = search(&client, "minimap", &[], false, None, Some(50))
+            .await
+            .unwrap();
+
+        for hit in &response.hits {
+            let server_side = hit["server_side"].as_str().unwrap();
+            assert_ne!(server_side, "unsupported", "{}", hit["slug"]);
+        }
+    }
+
     #[tokio::test]
     async fn test_get_project_versions() {
         let client = reqwest::Client::new();
@@ -340,4 +1708,396 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_project_versions_table() {
+        let mut response: ProjectVersionsResponse = serde_json::from_value(serde_json::json!([
+            {
+                "id": "old", "version_number": "0.6.13", "version_type": "release",
+                "game_versions": ["1.21.7"], "loaders": ["fabric"],
+                "date_published": "2025-08-01T12:00:00Z",
+                "files": [{ "filename": "sodium-0.6.13.jar", "size": 1048576, "primary": true }]
+            },
+            {
+                "id": "new", "version_number": "0.6.14", "version_type": "beta",
+                "game_versions": ["1.21.8"], "loaders": ["fabric", "quilt"],
+                "date_published": "2025-08-14T12:00:00Z",
+                "dependencies": [
+                    { "project_id": "P7dR8mSH", "dependency_type": "required" },
+                    { "project_id": "YL57xq9U", "dependency_type": "optional" }
+                ],
+                "files": [
+                    { "filename": "sodium-sources.jar", "size": 10, "primary": false },
+                    { "filename": "sodium-0.6.14.jar", "size": 2048, "primary": true }
+                ]
+            }
+        ]))
+        .unwrap();
+        response.sort();
+        assert_eq!(response.0[0].id, "new");
+        assert_eq!(response.0[0].size(), Some(2048));
+
+        let now = chrono::DateTime::parse_from_rfc3339("2025-08-17T13:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Local);
+        let table = response.table(1, now, None).to_string();
+        assert!(table.contains("3 days ago"));
+        assert!(table.contains("1 required"));
+        assert!(table.contains("2.0 KB"));
+        assert!(!table.contains("0.6.13"));
+        assert!(!table.contains("https://"));
+
+        let table = response.table(1, now, Some("sodium")).to_string();
+        assert!(table.contains("https://modrinth.com/mod/sodium/version/new"));
+    }
+
+    #[tokio::test]
+    async fn test_find_versions_with_sha512() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/version_files")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "hashes": [SODIUM_SHA512],
+                "algorithm": "sha512",
+            })))
+            .with_body(
+                serde_json::json!({
+                    SODIUM_SHA512: version_json("AANobbMI", "sodium-0.6.13", SODIUM_SHA1, SODIUM_SHA512)
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let versions = find_versions_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &[hashes(SODIUM_SHA1, SODIUM_SHA512)],
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        let version = versions[0].as_ref().unwrap();
+        assert_eq!(version.project_id, "AANobbMI");
+        assert_eq!(
+            version.primary_file().hash,
+            FileHash {
+                algorithm: HashAlgorithm::Sha512,
+                value: SODIUM_SHA512.to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_versions_with_sha1_only() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/version_files")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "hashes": [OLD_MOD_SHA1],
+                "algorithm": "sha1",
+            })))
+            .with_body(
+                serde_json::json!({
+                    OLD_MOD_SHA1: version_json("P7dR8mSH", "old-mod-1.0", OLD_MOD_SHA1, OLD_MOD_SHA512)
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let jar_hashes = [vec![FileHash {
+            algorithm: HashAlgorithm::Sha1,
+            value: OLD_MOD_SHA1.to_string(),
+        }]];
+        let versions = find_versions_from(&reqwest::Client::new(), &server.url(), &jar_hashes)
+            .await
+            .unwrap();
+
+        let file = versions[0].as_ref().unwrap().primary_file();
+        assert_eq!(file.hash.algorithm, HashAlgorithm::Sha1);
+        assert_eq!(file.hash.value, OLD_MOD_SHA1);
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_versions_falls_back_to_sha1() {
+        let mut server = Server::new_async().await;
+        // Modrinth leaves out the hashes it doesn't recognize
+        server
+            .mock("POST", "/version_files/update")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "hashes": [SODIUM_SHA512, OLD_MOD_SHA512],
+                "algorithm": "sha512",
+                "game_versions": ["1.21.8"],
+            })))
+            .with_body(
+                serde_json::json!({
+                    SODIUM_SHA512: version_json("AANobbMI", "sodium-0.7.0", "1111", "2222")
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let fallback = server
+            .mock("POST", "/version_files/update")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "hashes": [OLD_MOD_SHA1],
+                "algorithm": "sha1",
+            })))
+            .with_body(
+                serde_json::json!({
+                    OLD_MOD_SHA1: version_json("P7dR8mSH", "old-mod-1.0", OLD_MOD_SHA1, OLD_MOD_SHA512)
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let jar_hashes = [
+            hashes(SODIUM_SHA1, SODIUM_SHA512),
+            hashes(OLD_MOD_SHA1, OLD_MOD_SHA512),
+        ];
+        let versions = find_latest_versions_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &jar_hashes,
+            &["1.21.8"],
+        )
+        .await
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect::<Vec<_>>();
+
+        fallback.assert_async().await;
+        assert_eq!(versions[0].version_name, "sodium-0.7.0");
+        assert_eq!(
+            versions[0].primary_file().hash.algorithm,
+            HashAlgorithm::Sha512
+        );
+        assert!(!jar_hashes[0].contains(&versions[0].primary_file().hash));
+        // Up to date, found by its sha1
+        assert_eq!(
+            versions[1].primary_file().hash.algorithm,
+            HashAlgorithm::Sha1
+        );
+        assert!(jar_hashes[1].contains(&versions[1].primary_file().hash));
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_versions_with_failing_hash() {
+        const ARCHIVED_SHA1: &str = "3333";
+        const ARCHIVED_SHA512: &str = "4444";
+        let mut server = Server::new_async().await;
+        // The archived project's hash fails the whole batch
+        let batch = server
+            .mock("POST", "/version_files/update")
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "hashes": [SODIUM_SHA512, ARCHIVED_SHA512, OLD_MOD_SHA512],
+            })))
+            .with_status(410)
+            .expect(1)
+            .create_async()
+            .await;
+        for (hash, version) in [
+            (
+                SODIUM_SHA512,
+                version_json("AANobbMI", "sodium-0.7.0", "1111", "2222"),
+            ),
+            (
+                OLD_MOD_SHA512,
+                version_json("P7dR8mSH", "old-mod-1.0", OLD_MOD_SHA1, OLD_MOD_SHA512),
+            ),
+        ] {
+            server
+                .mock("POST", "/version_files/update")
+                .match_body(Matcher::PartialJson(
+                    serde_json::json!({ "hashes": [hash] }),
+                ))
+                .with_body(serde_json::json!({ hash: version }).to_string())
+                .create_async()
+                .await;
+        }
+        let archived = server
+            .mock("POST", "/version_files/update")
+            .match_body(Matcher::Regex(format!("{ARCHIVED_SHA1}|{ARCHIVED_SHA512}")))
+            .with_status(410)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let versions = find_latest_versions_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &[
+                hashes(SODIUM_SHA1, SODIUM_SHA512),
+                hashes(ARCHIVED_SHA1, ARCHIVED_SHA512),
+                hashes(OLD_MOD_SHA1, OLD_MOD_SHA512),
+            ],
+            &["1.21.8"],
+        )
+        .await
+        .unwrap();
+
+        batch.assert_async().await;
+        archived.assert_async().await;
+        assert_eq!(versions[0].as_ref().unwrap().version_name, "sodium-0.7.0");
+        assert!(versions[1].is_none());
+        assert_eq!(versions[2].as_ref().unwrap().version_name, "old-mod-1.0");
+    }
+
+    #[tokio::test]
+    async fn test_find_versions_unknown_hash() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/version_files")
+            .with_body("{}")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let versions = find_versions_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &[hashes(OLD_MOD_SHA1, OLD_MOD_SHA512)],
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(versions.as_slice(), [None]));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_with_dependencies() {
+        let mut server = Server::new_async().await;
+        let mut extra = version_json("PtjYWJkn", "Sodium Extra 0.6.6", "1111", "2222");
+        extra["dependencies"] = serde_json::json!([
+            { "project_id": "AANobbMI", "dependency_type": "required" },
+            { "project_id": "YL57xq9U", "dependency_type": "optional" },
+        ]);
+        let mut sodium = version_json("AANobbMI", "Sodium 0.6.13", "3333", "4444");
+        sodium["dependencies"] = serde_json::json!([
+            { "project_id": "gone", "dependency_type": "required" },
+        ]);
+        let lithium = version_json("gvQqBUqZ", "Lithium 0.18.0", "5555", "6666");
+        for (project, versions) in [
+            ("sodium-extra", serde_json::json!([extra])),
+            // Also on the command line, by slug
+            ("sodium", serde_json::json!([sodium.clone()])),
+            ("AANobbMI", serde_json::json!([sodium])),
+            ("lithium", serde_json::json!([lithium])),
+            ("old-mod", serde_json::json!([])),
+        ] {
+            server
+                .mock("GET", format!("/project/{project}/version").as_str())
+                .match_query(Matcher::Any)
+                .with_body(versions.to_string())
+                .create_async()
+                .await;
+        }
+        server
+            .mock("GET", "/project/gone/version")
+            .match_query(Matcher::Any)
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let (resolved, unresolved) = resolve_with_dependencies_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            &["sodium-extra", "sodium", "lithium", "old-mod"],
+            "fabric",
+            "1.21.8",
+        )
+        .await;
+
+        let mut names = resolved
+            .iter()
+            .map(|v| v.version_name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            ["Lithium 0.18.0", "Sodium 0.6.13", "Sodium Extra 0.6.6"]
+        );
+        assert_eq!(unresolved.len(), 2);
+        assert_eq!(unresolved[0].project, "gone");
+        assert_eq!(unresolved[0].required_by.as_deref(), Some("sodium"));
+        assert_eq!(
+            unresolved[1].to_string(),
+            "old-mod: no version for fabric 1.21.8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compatible_versions() {
+        let mut server = Server::new_async().await;
+        let mut newer = version_json("P7dR8mSH", "Fabric API 0.101.0", "1111", "2222");
+        newer["version_number"] = "0.101.0+1.21".into();
+        let mut older = version_json("P7dR8mSH", "Fabric API 0.100.8", "3333", "4444");
+        older["version_number"] = "0.100.8+1.21".into();
+        server
+            .mock("GET", "/project/P7dR8mSH/version")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("loaders".into(), "[\"fabric\"]".into()),
+                Matcher::UrlEncoded("game_versions".into(), "[\"1.21\"]".into()),
+            ]))
+            .with_body(serde_json::json!([newer, older]).to_string())
+            .create_async()
+            .await;
+
+        let versions = compatible_versions_from(
+            &reqwest::Client::new(),
+            &server.url(),
+            "P7dR8mSH",
+            "fabric",
+            "1.21",
+        )
+        .await
+        .unwrap();
+
+        let numbers: Vec<_> = versions.iter().map(|v| v.version_number.as_str()).collect();
+        assert_eq!(numbers, ["0.101.0+1.21", "0.100.8+1.21"]);
+        assert_eq!(versions[1].primary_file().sha512, "4444");
+    }
+
+    #[tokio::test]
+    async fn test_latest_compatible_version() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/project/lithium/version")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("loaders".into(), r#"["fabric"]"#.into()),
+                Matcher::UrlEncoded("game_versions".into(), r#"["1.21.8"]"#.into()),
+            ]))
+            .with_body(r#"[{"id":"qvNsoO3l","name":"Lithium 0.18.0"},{"id":"77EtzYFA","name":"Lithium 0.17.1"}]"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/project/lithium/version")
+            .match_query(Matcher::UrlEncoded(
+                "game_versions".into(),
+                r#"["1.8.9"]"#.into(),
+            ))
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let client = reqwest::Client::new();
+        let id =
+            latest_compatible_version_from(&client, &server.url(), "lithium", "fabric", "1.21.8")
+                .await
+                .unwrap();
+        assert_eq!(id.as_deref(), Some("qvNsoO3l"));
+
+        let id =
+            latest_compatible_version_from(&client, &server.url(), "lithium", "fabric", "1.8.9")
+                .await
+                .unwrap();
+        assert_eq!(id, None);
+    }
 }
@@ -0,0 +1,259 @@
+use std::{error::Error, fmt::Display};
+
+/// The facet keys the search endpoint filters by.
+/// See https://docs.modrinth.com/api/operations/searchprojects.
+pub const KEYS: [&str; 14] = [
+    "project_type",
+    "categories",
+    "versions",
+    "client_side",
+    "server_side",
+    "open_source",
+    "license",
+    "author",
+    "title",
+    "project_id",
+    "downloads",
+    "follows",
+    "date_created",
+    "date_modified",
+];
+
+/// The keys with ordered values, which can be compared with `>`, `>=`, `<` and `<=`.
+const RANGE_KEYS: [&str; 4] = ["downloads", "follows", "date_created", "date_modified"];
+/// The keys whose values are counts.
+const COUNT_KEYS: [&str; 2] = ["downloads", "follows"];
+
+/// Longest first, so `>=` isn't read as `>`.
+const OPERATORS: [&str; 7] = ["!=", ">=", "<=", ":", "=", ">", "<"];
+const RANGE_OPERATORS: [&str; 4] = [">=", "<=", ">", "<"];
+
+#[derive(Debug, PartialEq)]
+pub enum FacetError {
+    /// A facet without an operator, like `open_source`.
+    MissingOperator(String),
+    UnknownKey {
+        facet: String,
+        key: String,
+    },
+    /// A range operator on a key without ordered values, like `license>=mit`.
+    UnsupportedOperator {
+        facet: String,
+        operator: String,
+    },
+    InvalidValue {
+        facet: String,
+        expected: String,
+    },
+}
+
+impl Display for FacetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FacetError::MissingOperator(facet) => write!(
+                f,
+                "facet '{facet}' isn't key:value, like license:mit or downloads>=1000"
+            ),
+            FacetError::UnknownKey { facet, key } => write!(
+                f,
+                "unknown facet key '{key}' in '{facet}', valid keys are {}",
+                KEYS.join(", ")
+            ),
+            FacetError::UnsupportedOperator { facet, operator } => write!(
+                f,
+                "'{operator}' in '{facet}' only works with {}",
+                RANGE_KEYS.join(", ")
+            ),
+            FacetError::InvalidValue { facet, expected } => {
+                write!(f, "facet '{facet}' should have {expected}")
+            }
+        }
+    }
+}
+
+impl Error for FacetError {}
+
+/// Checks that the facet has a known key, an operator the key supports and a value.
+pub fn validate(facet: &str) -> Result<(), FacetError> {
+    let Some((index, operator)) = facet.char_indices().find_map(|(i, _)| {
+        OPERATORS
+            .into_iter()
+            .find(|op| facet[i..].starts_with(op))
+            .map(|op| (i, op))
+    }) else {
+        return Err(FacetError::MissingOperator(facet.to_string()));
+    };
+    let key = facet[..index].trim();
+    let value = facet[index + operator.len()..].trim();
+
+    if !KEYS.contains(&key) {
+        return Err(FacetError::UnknownKey {
+            facet: facet.to_string(),
+            key: key.to_string(),
+        });
+    }
+    if RANGE_OPERATORS.contains(&operator) && !RANGE_KEYS.contains(&key) {
+        return Err(FacetError::UnsupportedOperator {
+            facet: facet.to_string(),
+            operator: operator.to_string(),
+        });
+    }
+    if value.is_empty() {
+        return Err(FacetError::InvalidValue {
+            facet: facet.to_string(),
+            expected: "a value".to_string(),
+        });
+    }
+    if COUNT_KEYS.contains(&key) && value.parse::<u64>().is_err() {
+        return Err(FacetError::InvalidValue {
+            facet: facet.to_string(),
+            expected: "a number".to_string(),
+        });
+    }
+    if key == "open_source" && !matches!(value, "true" | "false") {
+        return Err(FacetError::InvalidValue {
+            facet: facet.to_string(),
+            expected: "true or false".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Parses the `--facets` arguments into facet groups.
+/// Each argument is one group, and comma-separated values inside it are OR-ed together.
+pub fn parse_groups(args: &[impl AsRef<str>]) -> Result<Vec<Vec<String>>, FacetError> {
+    let groups = args
+        .iter()
+        .map(|arg| {
+            arg.as_ref()
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect::<Vec<_>>();
+
+    for facet in groups.iter().flatten() {
+        validate(facet)?;
+    }
+    Ok(groups)
+}
+
+/// The facet group matching any of the values of the key, or `None` without values.
+pub fn group_of(key: &str, values: &[impl AsRef<str>]) -> Option<Vec<String>> {
+    let group = values
+        .iter()
+        .map(|value| value.as_ref().trim())
+        .filter(|value| !value.is_empty())
+        .map(|value| format!("{key}:{value}"))
+        .collect::<Vec<_>>();
+    (!group.is_empty()).then_some(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_groups() {
+        let groups = parse_groups(&[
+            "versions:1.21.7,versions:1.21.8",
+            "categories:fabric, categories:quilt",
+            "license:mit",
+            " , ",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            groups,
+            vec![
+                vec!["versions:1.21.7", "versions:1.21.8"],
+                vec!["categories:fabric", "categories:quilt"],
+                vec!["license:mit"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_operators() {
+        for facet in [
+            "license:mit",
+            "license=mit",
+            "license!=mit",
+            "downloads>=1000",
+            "downloads<=1000",
+            "follows>10",
+            "date_created<2024-01-01T00:00:00Z",
+            "title:\"mini map\"",
+            "open_source:true",
+            " server_side : required ",
+        ] {
+            assert_eq!(validate(facet), Ok(()), "{facet}");
+        }
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let error = validate("category:fabric").unwrap_err();
+        assert_eq!(
+            error,
+            FacetError::UnknownKey {
+                facet: "category:fabric".to_string(),
+                key: "category".to_string(),
+            }
+        );
+        assert!(error.to_string().contains("categories"));
+
+        assert_eq!(
+            validate("open_source"),
+            Err(FacetError::MissingOperator("open_source".to_string()))
+        );
+        assert_eq!(
+            validate("license>=mit"),
+            Err(FacetError::UnsupportedOperator {
+                facet: "license>=mit".to_string(),
+                operator: ">=".to_string(),
+            })
+        );
+        assert!(matches!(
+            validate("downloads>=many"),
+            Err(FacetError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            validate("open_source:yes"),
+            Err(FacetError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            validate("versions:"),
+            Err(FacetError::InvalidValue { .. })
+        ));
+        assert_eq!(
+            validate("!versions"),
+            Err(FacetError::MissingOperator("!versions".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_groups_rejects_typo() {
+        let result = parse_groups(&["license:mit", "versions:1.21.8,version:1.21.7"]);
+        assert!(matches!(
+            result,
+            Err(FacetError::UnknownKey { key, .. }) if key == "version"
+        ));
+    }
+
+    #[test]
+    fn test_group_of() {
+        assert_eq!(
+            group_of("categories", &["fabric", " quilt "]),
+            Some(vec![
+                "categories:fabric".to_string(),
+                "categories:quilt".to_string()
+            ])
+        );
+        assert_eq!(group_of("license", &[] as &[&str]), None);
+    }
+}
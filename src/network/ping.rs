@@ -0,0 +1,344 @@
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fmt::Display,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Sent as the protocol version when it's only asking for the status.
+const ANY_PROTOCOL_VERSION: i32 = -1;
+/// The handshake's next state for a status request.
+const STATUS_STATE: i32 = 1;
+/// Status responses are small, anything bigger is not a Minecraft server.
+const MAX_PACKET_LENGTH: i32 = 1 << 21;
+
+#[derive(Debug)]
+pub enum PingError {
+    VarIntTooLong,
+    InvalidPacket(String),
+    Timeout,
+}
+
+impl Display for PingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PingError::VarIntTooLong => write!(f, "VarInt is longer than 5 bytes"),
+            PingError::InvalidPacket(reason) => write!(f, "Invalid status response: {reason}"),
+            PingError::Timeout => write!(f, "The server did not respond in time"),
+        }
+    }
+}
+
+impl Error for PingError {}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct StatusVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct StatusPlayers {
+    pub max: u32,
+    pub online: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ServerStatus {
+    pub version: StatusVersion,
+    pub players: Option<StatusPlayers>,
+    /// A plain string, or a text component with `text` and `extra`.
+    #[serde(default)]
+    pub description: serde_json::Value,
+    /// The round trip time of the status request.
+    #[serde(skip)]
+    pub latency: Duration,
+}
+
+impl ServerStatus {
+    /// The MOTD as plain text, without formatting.
+    pub fn motd(&self) -> String {
+        let mut motd = String::new();
+        flatten_text(&self.description, &mut motd);
+        strip_formatting_codes(&motd)
+    }
+}
+
+impl Display for ServerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "MOTD: {}", self.motd())?;
+        writeln!(
+            f,
+            "Version: {} (protocol {})",
+            self.version.name, self.version.protocol
+        )?;
+        match &self.players {
+            Some(players) => writeln!(f, "Players: {}/{}", players.online, players.max)?,
+            None => writeln!(f, "Players: unknown")?,
+        }
+        writeln!(f, "Latency: {}ms", self.latency.as_millis())?;
+        Ok(())
+    }
+}
+
+/// Asks the server at `host:port` for its status with the Server List Ping,
+/// like the multiplayer menu does.
+/// See https://minecraft.wiki/w/Java_Edition_protocol/Server_List_Ping
+pub async fn ping(host: &str, port: u16, timeout: Duration) -> anyhow::Result<ServerStatus> {
+    tokio::time::timeout(timeout, ping_without_timeout(host, port))
+        .await
+        .map_err(|_| PingError::Timeout)?
+}
+
+async fn ping_without_timeout(host: &str, port: u16) -> anyhow::Result<ServerStatus> {
+    let mut stream = TcpStream::connect((host, port)).await?;
+    let start = Instant::now();
+
+    stream.write_all(&handshake_packet(host, port)).await?;
+    // The status request has no fields, only its packet id
+    stream.write_all(&packet(0x00, &[])).await?;
+
+    let length = read_var_int(&mut stream).await?;
+    if !(1..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(PingError::InvalidPacket(format!("packet length {length}")).into());
+    }
+    let mut body = vec![0; length as usize];
+    stream.read_exact(&mut body).await?;
+    let latency = start.elapsed();
+
+    let mut status = parse_status_packet(&body)?;
+    status.latency = latency;
+    Ok(status)
+}
+
+fn handshake_packet(host: &str, port: u16) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_var_int(&mut data, ANY_PROTOCOL_VERSION);
+    write_var_int(&mut data, host.len() as i32);
+    data.extend_from_slice(host.as_bytes());
+    data.extend_from_slice(&port.to_be_bytes());
+    write_var_int(&mut data, STATUS_STATE);
+    packet(0x00, &data)
+}
+
+/// Frames the packet as its VarInt length, VarInt id, then data.
+fn packet(id: i32, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_var_int(&mut body, id);
+    body.extend_from_slice(data);
+
+    let mut packet = Vec::new();
+    write_var_int(&mut packet, body.len() as i32);
+    packet.extend(body);
+    packet
+}
+
+/// Parses the status response body, after its length: the packet id and a JSON string.
+fn parse_status_packet(mut body: &[u8]) -> anyhow::Result<ServerStatus> {
+    let id = decode_var_int(&mut body)?;
+    if id != 0x00 {
+        return Err(PingError::InvalidPacket(format!("packet id {id}")).into());
+    }
+
+    let length = decode_var_int(&mut body)?;
+    let json = usize::try_from(length)
+        .ok()
+        .and_then(|length| body.get(..length))
+        .ok_or(PingError::InvalidPacket("truncated JSON".to_string()))?;
+
+    Ok(serde_json::from_slice(json)?)
+}
+
+pub fn write_var_int(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F | 0x80) as u8);
+        value >>= 7;
+    }
+}
+
+/// Decodes a VarInt from the start of `buf`, advancing it past the VarInt.
+pub fn decode_var_int(buf: &mut &[u8]) -> Result<i32, PingError> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let (&byte, rest) = buf
+            .split_first()
+            .ok_or(PingError::InvalidPacket("truncated VarInt".to_string()))?;
+        *buf = rest;
+
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(PingError::VarIntTooLong)
+}
+
+async fn read_var_int(reader: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<i32> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = reader.read_u8().await?;
+        bytes.push(byte);
+        if byte & 0x80 == 0 || bytes.len() == 5 {
+            return Ok(decode_var_int(&mut bytes.as_slice())?);
+        }
+    }
+}
+
+fn flatten_text(value: &serde_json::Value, out: &mut String) {
+    match value {
+        serde_json::Value::String(text) => out.push_str(text),
+        serde_json::Value::Array(parts) => parts.iter().for_each(|part| flatten_text(part, out)),
+        serde_json::Value::Object(component) => {
+            if let Some(text) = component.get("text") {
+                flatten_text(text, out);
+            }
+            if let Some(extra) = component.get("extra") {
+                flatten_text(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes legacy `§` color and style codes.
+fn strip_formatting_codes(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Captured from a vanilla 1.21.8 server.
+    const VANILLA_STATUS: &str = r#"{"description":{"text":"A Minecraft Server"},"players":{"max":20,"online":0},"version":{"name":"1.21.8","protocol":772},"enforcesSecureChat":true}"#;
+    /// Captured from a Fabric server with a formatted MOTD and players online.
+    const FORMATTED_STATUS: &str = r#"{"version":{"name":"1.21.8","protocol":772},"players":{"max":10,"online":2,"sample":[{"name":"Steve","id":"8667ba71-b85a-4004-af54-457a9734eed7"}]},"description":{"text":"","extra":[{"text":"Welcome to ","color":"gold"},{"text":"§lmcerv","bold":true}]},"favicon":"data:image/png;base64,iVBORw0KGgo="}"#;
+
+    fn status_body(json: &str) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_var_int(&mut body, 0x00);
+        write_var_int(&mut body, json.len() as i32);
+        body.extend_from_slice(json.as_bytes());
+        body
+    }
+
+    #[test]
+    fn test_var_int_round_trip() {
+        let cases: [(i32, &[u8]); 7] = [
+            (0, &[0x00]),
+            (1, &[0x01]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (25565, &[0xdd, 0xc7, 0x01]),
+            (i32::MAX, &[0xff, 0xff, 0xff, 0xff, 0x07]),
+            (-1, &[0xff, 0xff, 0xff, 0xff, 0x0f]),
+        ];
+
+        for (value, bytes) in cases {
+            let mut encoded = Vec::new();
+            write_var_int(&mut encoded, value);
+            assert_eq!(encoded, bytes, "encoding {value}");
+
+            let mut buf = bytes;
+            assert_eq!(decode_var_int(&mut buf).unwrap(), value, "decoding {value}");
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_var_int_errors() {
+        let mut too_long: &[u8] = &[0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(matches!(
+            decode_var_int(&mut too_long),
+            Err(PingError::VarIntTooLong)
+        ));
+
+        let mut truncated: &[u8] = &[0x80];
+        assert!(matches!(
+            decode_var_int(&mut truncated),
+            Err(PingError::InvalidPacket(_))
+        ));
+    }
+
+    #[test]
+    fn test_handshake_packet() {
+        let packet = handshake_packet("localhost", 25565);
+        let mut buf = packet.as_slice();
+        assert_eq!(decode_var_int(&mut buf).unwrap() as usize, buf.len());
+        assert_eq!(decode_var_int(&mut buf).unwrap(), 0x00);
+        assert_eq!(decode_var_int(&mut buf).unwrap(), ANY_PROTOCOL_VERSION);
+        assert_eq!(decode_var_int(&mut buf).unwrap(), 9);
+        assert_eq!(&buf[..9], b"localhost");
+        assert_eq!(&buf[9..], [0x63, 0xdd, 0x01]);
+    }
+
+    #[test]
+    fn test_parse_status_packet() {
+        let status = parse_status_packet(&status_body(VANILLA_STATUS)).unwrap();
+        assert_eq!(status.motd(), "A Minecraft Server");
+        assert_eq!(
+            status.version,
+            StatusVersion {
+                name: "1.21.8".to_string(),
+                protocol: 772
+            }
+        );
+        assert_eq!(status.players, Some(StatusPlayers { max: 20, online: 0 }));
+
+        let status = parse_status_packet(&status_body(FORMATTED_STATUS)).unwrap();
+        assert_eq!(status.motd(), "Welcome to mcerv");
+        assert_eq!(status.players, Some(StatusPlayers { max: 10, online: 2 }));
+    }
+
+    #[test]
+    fn test_parse_plain_string_description() {
+        let json = r#"{"version":{"name":"Paper 1.21.8","protocol":772},"description":"§aHello"}"#;
+        let status = parse_status_packet(&status_body(json)).unwrap();
+        assert_eq!(status.motd(), "Hello");
+        assert_eq!(status.players, None);
+    }
+
+    #[tokio::test]
+    async fn test_ping_local_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Handshake, then the status request
+            for _ in 0..2 {
+                let length = read_var_int(&mut stream).await.unwrap();
+                let mut body = vec![0; length as usize];
+                stream.read_exact(&mut body).await.unwrap();
+            }
+            let body = status_body(VANILLA_STATUS);
+            let mut response = Vec::new();
+            write_var_int(&mut response, body.len() as i32);
+            response.extend(body);
+            stream.write_all(&response).await.unwrap();
+        });
+
+        let status = ping("127.0.0.1", port, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(status.version.name, "1.21.8");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::system::format;
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+static LIMITER: Mutex<Option<TokenBucket>> = Mutex::new(None);
+
+/// A download rate in bytes per second, written like `500K`, `2M` or `1.5M`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate(pub u64);
+
+#[derive(Debug, PartialEq)]
+pub struct RateParseError(String);
+
+impl Display for RateParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' isn't a rate in bytes per second, like 500K, 2M or 1.5M",
+            self.0
+        )
+    }
+}
+
+impl Error for RateParseError {}
+
+impl FromStr for Rate {
+    type Err = RateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || RateParseError(s.to_string());
+        let lower = s.trim().to_lowercase();
+        let number = lower.strip_suffix("/s").unwrap_or(&lower);
+        let number = number.strip_suffix('b').unwrap_or(number);
+        let (number, multiplier) = match number.char_indices().last() {
+            Some((i, 'k')) => (&number[..i], 1024.0),
+            Some((i, 'm')) => (&number[..i], 1024.0 * 1024.0),
+            Some((i, 'g')) => (&number[..i], 1024.0 * 1024.0 * 1024.0),
+            _ => (number, 1.0),
+        };
+        let number = number.trim().parse::<f64>().map_err(|_| error())?;
+        let bytes = number * multiplier;
+        if !bytes.is_finite() || bytes < 1.0 {
+            return Err(error());
+        }
+        Ok(Self(bytes as u64))
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(0) => Err(serde::de::Error::custom("the rate should be more than 0")),
+            Raw::Bytes(bytes) => Ok(Self(bytes)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl Display for Rate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/s", format::format_bytes(self.0))
+    }
+}
+
+/// Paces bytes to a rate, letting a second's worth through at once. Bytes taken beyond
+/// that are owed, so everything sharing the bucket waits its turn.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate: rate.0 as f64,
+            tokens: rate.0 as f64,
+            refilled_at: now,
+        }
+    }
+
+    /// Takes the bytes out of the bucket at `now`, returning how long to wait before
+    /// writing them.
+    pub fn take(&mut self, bytes: u64, now: Instant) -> Duration {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled_at = now.max(self.refilled_at);
+
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Limits all downloads of the process together to the rate, or lifts the limit.
+pub fn set_rate(rate: Option<Rate>) {
+    *LIMITER.lock().unwrap() = rate.map(|rate| TokenBucket::new(rate, Instant::now()));
+}
+
+/// Waits until the bytes of a downloaded chunk may be written under the rate limit.
+pub async fn throttle(bytes: usize) {
+    let wait = match LIMITER.lock().unwrap().as_mut() {
+        Some(bucket) => bucket.take(bytes as u64, Instant::now()),
+        None => return,
+    };
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rate() {
+        for (text, bytes) in [
+            ("1024", 1024),
+            ("500K", 500 * 1024),
+            ("500k", 500 * 1024),
+            ("2M", 2 * 1024 * 1024),
+            ("1.5M", 1024 * 1024 * 3 / 2),
+            ("2MB", 2 * 1024 * 1024),
+            ("1G/s", 1024 * 1024 * 1024),
+        ] {
+            assert_eq!(text.parse(), Ok(Rate(bytes)), "{text}");
+        }
+        for text in ["", "M", "fast", "0", "-1M", "0.0001K", "2T"] {
+            assert!(text.parse::<Rate>().is_err(), "{text}");
+        }
+        assert_eq!(Rate(2 * 1024 * 1024).to_string(), "2.0 MB/s");
+    }
+
+    #[test]
+    fn test_deserialize_rate() {
+        #[derive(Deserialize)]
+        struct Settings {
+            limit_rate: Rate,
+        }
+        let parse = |toml| toml::from_str::<Settings>(toml).map(|s| s.limit_rate);
+
+        assert_eq!(parse("limit_rate = \"2M\"").unwrap(), Rate(2 * 1024 * 1024));
+        assert_eq!(parse("limit_rate = 1000").unwrap(), Rate(1000));
+        assert!(parse("limit_rate = 0").is_err());
+        assert!(parse("limit_rate = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_pacing() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut bucket = TokenBucket::new(Rate(1000), start);
+
+        // A second's worth goes through at once
+        assert_eq!(bucket.take(1000, at(0)), Duration::ZERO);
+        // Two downloads sharing the bucket queue up behind each other
+        assert_eq!(bucket.take(500, at(0)), Duration::from_millis(500));
+        assert_eq!(bucket.take(500, at(0)), Duration::from_millis(1000));
+        // Half a second later, half a second less is owed
+        assert_eq!(bucket.take(250, at(500)), Duration::from_millis(750));
+        // Idling refills it, but only up to a second's worth
+        assert_eq!(bucket.take(1000, at(10_000)), Duration::ZERO);
+        assert_eq!(bucket.take(100, at(10_000)), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_token_bucket_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(Rate(64 * 1024), start);
+
+        // Writing 16 KiB chunks as fast as it lets through, like a download does
+        let mut now = start;
+        for _ in 0..4 * 10 {
+            now += bucket.take(16 * 1024, now);
+        }
+        // 640 KiB, one second of it let through at once
+        assert_eq!(now - start, Duration::from_secs(9));
+    }
+}
@@ -1,76 +1,286 @@
-use crate::network::{PrintVersionMode, download_file};
+use crate::network::{
+    DownloadStats, PrintVersionMode, VersionEntry, download_file, download_file_checked, fetch_text,
+};
+use crate::system::{
+    jar_parser::{FileHash, HashAlgorithm},
+    version::McVersion,
+};
 use anyhow::anyhow;
-use core::panic;
 use reqwest::Client;
-use std::{error::Error, path::Path};
+use serde::Deserialize;
+use std::{
+    cmp::Reverse,
+    error::Error,
+    path::{Path, PathBuf},
+};
 
-const URL: &str = "https://gist.githubusercontent.com/cliffano/77a982a7503669c3e1acb0a0cf6127e9/raw/minecraft-server-jar-downloads.md";
+const MANIFEST_URL: &str = "https://launchermeta.mojang.com/mc/game/version_manifest_v2.json";
+// Third-party list, only used when Mojang's manifest is unreachable
+const GIST_URL: &str = "https://gist.githubusercontent.com/cliffano/77a982a7503669c3e1acb0a0cf6127e9/raw/minecraft-server-jar-downloads.md";
+/// Where the server mappings are saved in the server directory, as `<version>.txt`.
+pub const MAPPINGS_DIR: &str = "mappings";
 
 #[derive(Debug)]
 pub enum DownloadError {
     VersionNotFound,
+    NoServerDownload,
+    NoMappingsDownload,
 }
 
 impl std::fmt::Display for DownloadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DownloadError::VersionNotFound => write!(f, "Version not found"),
+            DownloadError::NoServerDownload => {
+                write!(f, "Version does not provide a server download")
+            }
+            DownloadError::NoMappingsDownload => {
+                write!(f, "Version does not provide server mappings")
+            }
         }
     }
 }
 
 impl Error for DownloadError {}
 
+// https://minecraft.wiki/w/Version_manifest.json
+#[derive(Deserialize)]
+pub struct VersionManifest {
+    pub latest: LatestVersions,
+    pub versions: Vec<ManifestVersion>,
+}
+
+#[derive(Deserialize)]
+pub struct LatestVersions {
+    pub release: String,
+    pub snapshot: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestVersion {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub version_type: String,
+    pub url: String,
+    pub release_time: String,
+}
+
+impl ManifestVersion {
+    /// Mojang marks everything else, including `-pre` and `-rc` builds and april fools
+    /// snapshots, as `snapshot`, `old_beta` or `old_alpha`.
+    pub fn is_release(&self) -> bool {
+        self.version_type == "release"
+    }
+}
+
+impl VersionManifest {
+    /// Converts the manifest versions to entries, classified by their `type` field.
+    pub fn entries(self) -> Vec<VersionEntry> {
+        self.versions
+            .into_iter()
+            .map(|v| VersionEntry {
+                stable: v.is_release(),
+                // `releaseTime` is RFC 3339, keep the date part only
+                release_date: v.release_time.get(..10).map(String::from),
+                version_type: Some(v.version_type),
+                ..VersionEntry::new(v.id, false)
+            })
+            .collect()
+    }
+}
+
+/// A section of `downloads` in a version's detail json, like `server`.
+#[derive(Deserialize)]
+struct Download {
+    url: String,
+    sha1: String,
+}
+
 pub async fn download_server(
     client: &Client,
     version: &str,
     save_dir_path: impl AsRef<Path>,
-) -> anyhow::Result<String> {
-    let content = client.get(URL).send().await?.text().await?;
-    let url = versions_and_download_links(&content)
-        .find(|(v, _)| *v == version)
-        .ok_or(anyhow!(DownloadError::VersionNotFound))?
-        .1;
+) -> anyhow::Result<(String, DownloadStats)> {
+    download_server_from(client, MANIFEST_URL, GIST_URL, version, save_dir_path).await
+}
 
+async fn download_server_from(
+    client: &Client,
+    manifest_url: &str,
+    gist_url: &str,
+    version: &str,
+    save_dir_path: impl AsRef<Path>,
+) -> anyhow::Result<(String, DownloadStats)> {
     let filename = format!("vanilla-{version}.jar");
-    download_file(client, &url, &save_dir_path.as_ref().join(&filename)).await?;
-    Ok(filename)
+    let save_path = save_dir_path.as_ref().join(&filename);
+
+    let manifest = match fetch_manifest(client, manifest_url).await {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            println!("Failed to fetch Mojang version manifest ({e}), falling back to gist...");
+            let content = fetch_text(client, gist_url).await?;
+            let url = versions_and_download_links(&content)
+                .find(|(v, _)| *v == version)
+                .ok_or(anyhow!(DownloadError::VersionNotFound))?
+                .1;
+
+            let stats = download_file(client, &url, &save_path).await?;
+            return Ok((filename, stats));
+        }
+    };
+
+    let server = fetch_download(client, &manifest, version, "server")
+        .await?
+        .ok_or(anyhow!(DownloadError::NoServerDownload))?;
+
+    let expected = FileHash {
+        algorithm: HashAlgorithm::Sha1,
+        value: server.sha1,
+    };
+    let stats = download_file_checked(client, &server.url, &save_path, &expected).await?;
+    Ok((filename, stats))
+}
+
+/// Downloads the server mappings of the version into [`MAPPINGS_DIR`] of the server
+/// directory, returning where they were saved and what the download took.
+pub async fn download_mappings(
+    client: &Client,
+    version: &str,
+    server_dir: &Path,
+) -> anyhow::Result<(PathBuf, DownloadStats)> {
+    download_mappings_from(client, MANIFEST_URL, version, server_dir).await
+}
+
+async fn download_mappings_from(
+    client: &Client,
+    manifest_url: &str,
+    version: &str,
+    server_dir: &Path,
+) -> anyhow::Result<(PathBuf, DownloadStats)> {
+    // The gist only lists server jars, so there's nothing to fall back to
+    let manifest = fetch_manifest(client, manifest_url).await?;
+    let mappings = fetch_download(client, &manifest, version, "server_mappings")
+        .await?
+        .ok_or(anyhow!(DownloadError::NoMappingsDownload))?;
+
+    let save_path = server_dir.join(MAPPINGS_DIR).join(format!("{version}.txt"));
+    let expected = FileHash {
+        algorithm: HashAlgorithm::Sha1,
+        value: mappings.sha1,
+    };
+    let stats = download_file_checked(client, &mappings.url, &save_path, &expected).await?;
+    Ok((save_path, stats))
+}
+
+/// The `downloads.<name>` section of the version's detail json, if it has one.
+async fn fetch_download(
+    client: &Client,
+    manifest: &VersionManifest,
+    version: &str,
+    name: &str,
+) -> anyhow::Result<Option<Download>> {
+    let detail_url = &manifest
+        .versions
+        .iter()
+        .find(|v| v.id == version)
+        .ok_or(anyhow!(DownloadError::VersionNotFound))?
+        .url;
+
+    let detail: serde_json::Value = serde_json::from_str(&fetch_text(client, detail_url).await?)?;
+    Ok(serde_json::from_value(detail["downloads"][name].clone()).ok())
 }
 
 pub async fn versions(
     client: &reqwest::Client,
     print_mode: PrintVersionMode,
-) -> anyhow::Result<String> {
-    let content = client.get(URL).send().await?.text().await?;
-    let versions = versions_and_download_links(&content)
-        .filter_map(|(version, _)| {
-            if matches!(print_mode, PrintVersionMode::StableOnly) && is_unstable_version(version) {
-                None
-            } else {
-                Some(version)
-            }
-        })
-        .collect::<Vec<_>>();
+) -> anyhow::Result<Vec<VersionEntry>> {
+    versions_from(client, MANIFEST_URL, GIST_URL, print_mode).await
+}
 
-    Ok(versions.join("\n"))
+async fn versions_from(
+    client: &Client,
+    manifest_url: &str,
+    gist_url: &str,
+    print_mode: PrintVersionMode,
+) -> anyhow::Result<Vec<VersionEntry>> {
+    let mut versions = match fetch_manifest(client, manifest_url).await {
+        Ok(manifest) => manifest.entries(),
+        Err(e) => {
+            println!("Failed to fetch Mojang version manifest ({e}), falling back to gist...");
+            let content = fetch_text(client, gist_url).await?;
+            versions_and_download_links(&content)
+                .map(|(version, _)| VersionEntry::new(version, is_release_id(version)))
+                .collect()
+        }
+    };
+    // Newest first, with the versions only the manifest can place left where it lists them
+    let ids: Vec<String> = versions.iter().map(|v| v.id.clone()).collect();
+    versions.sort_by_cached_key(|v| Reverse(McVersion::from_manifest(&v.id, &ids)));
+
+    Ok(versions
+        .into_iter()
+        .filter(|v| matches!(print_mode, PrintVersionMode::All) || v.stable)
+        .collect())
 }
 
 pub async fn fetch_latest_stable_version(client: &reqwest::Client) -> anyhow::Result<String> {
-    let content = client.get(URL).send().await?.text().await?;
-    for (version, _) in versions_and_download_links(&content) {
-        if is_stable_version(version) {
-            return Ok(version.to_string());
+    fetch_latest_stable_version_from(client, MANIFEST_URL, GIST_URL).await
+}
+
+async fn fetch_latest_stable_version_from(
+    client: &Client,
+    manifest_url: &str,
+    gist_url: &str,
+) -> anyhow::Result<String> {
+    match fetch_manifest(client, manifest_url).await {
+        Ok(manifest) => Ok(manifest.latest.release),
+        Err(e) => {
+            println!("Failed to fetch Mojang version manifest ({e}), falling back to gist...");
+            let content = fetch_text(client, gist_url).await?;
+            versions_and_download_links(&content)
+                .find(|(version, _)| is_release_id(version))
+                .map(|(version, _)| version.to_string())
+                .ok_or(anyhow!(
+                    "Could not find any stable versions in the fetched data"
+                ))
+        }
+    }
+}
+
+pub async fn fetch_latest_snapshot_version(client: &reqwest::Client) -> anyhow::Result<String> {
+    fetch_latest_snapshot_version_from(client, MANIFEST_URL, GIST_URL).await
+}
+
+async fn fetch_latest_snapshot_version_from(
+    client: &Client,
+    manifest_url: &str,
+    gist_url: &str,
+) -> anyhow::Result<String> {
+    match fetch_manifest(client, manifest_url).await {
+        Ok(manifest) => Ok(manifest.latest.snapshot),
+        Err(e) => {
+            println!("Failed to fetch Mojang version manifest ({e}), falling back to gist...");
+            let content = fetch_text(client, gist_url).await?;
+            // The gist is ordered newest first, like the manifest
+            versions_and_download_links(&content)
+                .next()
+                .map(|(version, _)| version.to_string())
+                .ok_or(anyhow!("Could not find any versions in the fetched data"))
         }
     }
+}
 
-    panic!("Could not find any stable versions in the fetched data");
+pub async fn fetch_manifest(client: &Client, url: &str) -> anyhow::Result<VersionManifest> {
+    let text = fetch_text(client, url).await?;
+    Ok(serde_json::from_str(&text)?)
 }
 
 fn versions_and_download_links(content: &str) -> impl Iterator<Item = (&str, &str)> {
     content.lines().skip(2).filter_map(|line| {
         let mut columns = line.split('|');
-        let version_name = columns.nth(1).unwrap().trim();
-        let server_jar_url = columns.next().unwrap().trim();
+        let version_name = columns.nth(1)?.trim();
+        let server_jar_url = columns.next()?.trim();
 
         if server_jar_url == "Not found" {
             None
@@ -80,10 +290,366 @@ fn versions_and_download_links(content: &str) -> impl Iterator<Item = (&str, &st
     })
 }
 
-fn is_stable_version(version: &str) -> bool {
-    !is_unstable_version(version)
+/// Only used for the gist fallback, which has no version types.
+/// Accepts plain release ids like `1.21` or `1.21.8`, and nothing else.
+fn is_release_id(version: &str) -> bool {
+    McVersion::parse(version).is_some_and(|version| version.is_release())
 }
 
-fn is_unstable_version(version: &str) -> bool {
-    version.contains('-') || version.contains('w')
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::ChecksumError,
+        set_event_sink,
+        system::progress::{JsonSink, ProgressBarSink},
+    };
+    use mockito::Server;
+    use sha1::{Digest, Sha1};
+    use std::{fs, sync::Arc};
+
+    const GIST: &str = "\
+| Minecraft Version | Server Jar Download URL | Client Jar Download URL |
+|-------------------|-------------------------|-------------------------|
+| 25w31a | https://example.com/25w31a/server.jar | Not found |
+| 1.21.8 | https://example.com/1.21.8/server.jar | Not found |
+| 1.21.7 | Not found | Not found |
+";
+
+    fn manifest(server_url: &str) -> String {
+        format!(
+            r#"{{
+                "latest": {{ "release": "1.21.8", "snapshot": "25w31a" }},
+                "versions": [
+                    {{ "id": "25w31a", "type": "snapshot", "url": "{server_url}/25w31a.json", "time": "2025-07-29T12:00:00+00:00", "releaseTime": "2025-07-29T12:00:00+00:00" }},
+                    {{ "id": "1.21.8", "type": "release", "url": "{server_url}/1.21.8.json", "time": "2025-07-17T12:00:00+00:00", "releaseTime": "2025-07-17T12:00:00+00:00" }}
+                ]
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_manifest_classification() {
+        let manifest: VersionManifest = serde_json::from_str(
+            r#"{
+                "latest": { "release": "1.21.8", "snapshot": "25w31a" },
+                "versions": [
+                    { "id": "25w31a", "type": "snapshot", "url": "", "releaseTime": "2025-07-29T12:00:00+00:00" },
+                    { "id": "1.21.8", "type": "release", "url": "", "releaseTime": "2025-07-17T12:00:00+00:00" },
+                    { "id": "1.21.8-rc1", "type": "snapshot", "url": "", "releaseTime": "2025-07-15T12:00:00+00:00" },
+                    { "id": "1.21.8-pre1", "type": "snapshot", "url": "", "releaseTime": "2025-07-10T12:00:00+00:00" },
+                    { "id": "24w14potato", "type": "snapshot", "url": "", "releaseTime": "2024-04-01T12:00:00+00:00" },
+                    { "id": "b1.7.3", "type": "old_beta", "url": "", "releaseTime": "2011-07-08T00:00:00+00:00" },
+                    { "id": "1.0", "type": "release", "url": "", "releaseTime": "2011-11-18T00:00:00+00:00" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let classified = manifest
+            .entries()
+            .into_iter()
+            .map(|v| (v.id, v.stable, v.version_type.unwrap()))
+            .collect::<Vec<_>>();
+
+        let expected = [
+            ("25w31a", false, "snapshot"),
+            ("1.21.8", true, "release"),
+            ("1.21.8-rc1", false, "snapshot"),
+            ("1.21.8-pre1", false, "snapshot"),
+            ("24w14potato", false, "snapshot"),
+            ("b1.7.3", false, "old_beta"),
+            ("1.0", true, "release"),
+        ];
+
+        for ((id, stable, version_type), expected) in classified.iter().zip(expected) {
+            assert_eq!((id.as_str(), *stable, version_type.as_str()), expected);
+        }
+    }
+
+    #[test]
+    fn test_is_release_id() {
+        assert!(is_release_id("1.21.8"));
+        assert!(is_release_id("1.21"));
+        assert!(!is_release_id("1.21.8-rc1"));
+        assert!(!is_release_id("1.21.8-pre1"));
+        assert!(!is_release_id("25w31a"));
+        assert!(!is_release_id("24w14potato"));
+        assert!(!is_release_id("b1.7.3"));
+    }
+
+    #[tokio::test]
+    async fn test_versions_from_manifest() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        server
+            .mock("GET", "/manifest.json")
+            .with_body(manifest(&url))
+            .create_async()
+            .await;
+
+        let client = Client::new();
+        let manifest_url = format!("{url}/manifest.json");
+        let gist_url = format!("{url}/gist.md");
+
+        let stable = versions_from(
+            &client,
+            &manifest_url,
+            &gist_url,
+            PrintVersionMode::StableOnly,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stable.len(), 1);
+        assert_eq!(stable[0].id, "1.21.8");
+        assert_eq!(stable[0].release_date.as_deref(), Some("2025-07-17"));
+
+        let all = versions_from(&client, &manifest_url, &gist_url, PrintVersionMode::All)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(!all[0].stable);
+
+        let latest = fetch_latest_stable_version_from(&client, &manifest_url, &gist_url)
+            .await
+            .unwrap();
+        assert_eq!(latest, "1.21.8");
+
+        let snapshot = fetch_latest_snapshot_version_from(&client, &manifest_url, &gist_url)
+            .await
+            .unwrap();
+        assert_eq!(snapshot, "25w31a");
+    }
+
+    #[tokio::test]
+    async fn test_versions_fall_back_to_gist() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        server
+            .mock("GET", "/manifest.json")
+            .with_status(503)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/gist.md")
+            .with_body(GIST)
+            .create_async()
+            .await;
+
+        let client = Client::new();
+        let manifest_url = format!("{url}/manifest.json");
+        let gist_url = format!("{url}/gist.md");
+
+        let all = versions_from(&client, &manifest_url, &gist_url, PrintVersionMode::All)
+            .await
+            .unwrap();
+        let ids = all.iter().map(|v| v.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, ["25w31a", "1.21.8"]);
+
+        let latest = fetch_latest_stable_version_from(&client, &manifest_url, &gist_url)
+            .await
+            .unwrap();
+        assert_eq!(latest, "1.21.8");
+    }
+
+    #[tokio::test]
+    async fn test_download_server_verifies_hash() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        let jar = b"fake server jar";
+        let sha1 = format!("{:x}", Sha1::digest(jar));
+
+        server
+            .mock("GET", "/manifest.json")
+            .with_body(manifest(&url))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/1.21.8.json")
+            .with_body(format!(
+                r#"{{ "downloads": {{ "server": {{ "url": "{url}/server.jar", "sha1": "{sha1}" }} }} }}"#
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/25w31a.json")
+            .with_body(format!(
+                r#"{{ "downloads": {{ "server": {{ "url": "{url}/server.jar", "sha1": "0000" }} }} }}"#
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/server.jar")
+            .with_body(jar)
+            .expect_at_least(2)
+            .create_async()
+            .await;
+
+        let client = Client::new();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_url = format!("{url}/manifest.json");
+        let gist_url = format!("{url}/gist.md");
+
+        let (filename, stats) =
+            download_server_from(&client, &manifest_url, &gist_url, "1.21.8", &dir)
+                .await
+                .unwrap();
+        assert_eq!(filename, "vanilla-1.21.8.jar");
+        assert_eq!(stats.bytes, jar.len() as u64);
+        assert_eq!(fs::read(dir.path().join(&filename)).unwrap(), jar);
+
+        let err = download_server_from(&client, &manifest_url, &gist_url, "25w31a", &dir)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ChecksumError>(),
+            Some(ChecksumError::Mismatch { .. })
+        ));
+        assert!(!dir.path().join("vanilla-25w31a.jar").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_mappings() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        let mappings = b"net.minecraft.server.Main -> net.minecraft.server.Main:";
+        let sha1 = format!("{:x}", Sha1::digest(mappings));
+
+        server
+            .mock("GET", "/manifest.json")
+            .with_body(manifest(&url))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/1.21.8.json")
+            .with_body(format!(
+                r#"{{ "downloads": {{ "server": {{ "url": "{url}/server.jar", "sha1": "0000" }}, "server_mappings": {{ "url": "{url}/server.txt", "sha1": "{sha1}" }} }} }}"#
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/25w31a.json")
+            .with_body(format!(
+                r#"{{ "downloads": {{ "server": {{ "url": "{url}/server.jar", "sha1": "0000" }} }} }}"#
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/server.txt")
+            .with_body(mappings)
+            .create_async()
+            .await;
+
+        let client = Client::new();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_url = format!("{url}/manifest.json");
+
+        let (path, stats) = download_mappings_from(&client, &manifest_url, "1.21.8", dir.path())
+            .await
+            .unwrap();
+        assert_eq!(path, dir.path().join("mappings").join("1.21.8.txt"));
+        assert_eq!(stats.bytes, mappings.len() as u64);
+        assert_eq!(fs::read(&path).unwrap(), mappings);
+
+        let err = download_mappings_from(&client, &manifest_url, "25w31a", dir.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DownloadError>(),
+            Some(DownloadError::NoMappingsDownload)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_server_progress_events() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        let jar = vec![7; 300_000];
+        let sha1 = format!("{:x}", Sha1::digest(&jar));
+
+        server
+            .mock("GET", "/manifest.json")
+            .with_body(manifest(&url))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/1.21.8.json")
+            .with_body(format!(
+                r#"{{ "downloads": {{ "server": {{ "url": "{url}/server.jar", "sha1": "{sha1}" }} }} }}"#
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/server.jar")
+            .with_body(&jar)
+            .create_async()
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let events_path = dir.path().join("events.ndjson");
+        set_event_sink(Arc::new(JsonSink::new(
+            fs::File::create(&events_path).unwrap(),
+        )));
+        let result = download_server_from(
+            &Client::new(),
+            &format!("{url}/manifest.json"),
+            &format!("{url}/gist.md"),
+            "1.21.8",
+            dir.path().join("server"),
+        )
+        .await;
+        set_event_sink(Arc::new(ProgressBarSink::default()));
+        result.unwrap();
+
+        // Other tests download at the same time, their events are skipped
+        let jar_path = dir.path().join("server").join("vanilla-1.21.8.jar");
+        let events: Vec<serde_json::Value> = fs::read_to_string(&events_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .filter(|event: &serde_json::Value| event["file"] == jar_path.display().to_string())
+            .collect();
+
+        let (first, last) = (&events[0], &events[events.len() - 1]);
+        assert_eq!(first["event"], "download_progress");
+        assert_eq!(first["done"], 0);
+        assert_eq!(first["total"], jar.len());
+        assert_eq!(last["event"], "download_done");
+        assert_eq!(last["bytes"], jar.len());
+
+        let progress = &events[events.len() - 2];
+        assert_eq!(progress["event"], "download_progress");
+        assert_eq!(progress["done"], jar.len());
+    }
+
+    #[tokio::test]
+    async fn test_download_server_falls_back_to_gist() {
+        let mut server = Server::new_async().await;
+        let url = server.url();
+        server
+            .mock("GET", "/manifest.json")
+            .with_status(503)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/gist.md")
+            .with_body(GIST.replace("https://example.com", &url))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/1.21.8/server.jar")
+            .with_body("jar")
+            .create_async()
+            .await;
+
+        let client = Client::new();
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_url = format!("{url}/manifest.json");
+        let gist_url = format!("{url}/gist.md");
+
+        let (filename, _) = download_server_from(&client, &manifest_url, &gist_url, "1.21.8", &dir)
+            .await
+            .unwrap();
+        assert!(dir.path().join(filename).exists());
+    }
 }
@@ -0,0 +1,118 @@
+use std::{
+    path::Path,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+
+/// Makes vanilla, and the forks that pass their arguments on to it, write `server.properties`
+/// and `eula.txt` and exit without generating the world.
+pub const INIT_SETTINGS_ARG: &str = "--initSettings";
+/// How long the first launch may take before it's killed.
+pub const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+/// The files a first launch creates, checked to report what was generated.
+pub const GENERATED_FILES: [&str; 3] = ["server.properties", "eula.txt", "config"];
+
+/// Runs the server once so it writes its default files, echoing its output.
+///
+/// Servers that don't support [`INIT_SETTINGS_ARG`] but get past the EULA check go on to
+/// load the world; they are sent `stop` once they log that they're done starting.
+pub async fn run_first_launch(
+    mut command: Command,
+    timeout: Duration,
+) -> anyhow::Result<ExitStatus> {
+    command
+        .arg(INIT_SETTINGS_ARG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+
+    let mut child = command.spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    let run = async {
+        let mut stop_sent = false;
+        while let Some(line) = stdout.next_line().await? {
+            println!("{line}");
+            // `[12:00:00] [Server thread/INFO]: Done (3.2s)! For help, type "help"`
+            if !stop_sent && line.contains("Done (") {
+                stdin.write_all(b"stop\n").await?;
+                stdin.flush().await?;
+                stop_sent = true;
+            }
+        }
+        anyhow::Ok(child.wait().await?)
+    };
+
+    let result = tokio::time::timeout(timeout, run).await;
+    match result {
+        Ok(status) => status,
+        Err(_) => {
+            child.kill().await?;
+            anyhow::bail!("The server did not finish its first launch in {timeout:?}")
+        }
+    }
+}
+
+/// Which of [`GENERATED_FILES`] exist in the server directory.
+pub fn generated_files(server_dir: &Path) -> Vec<&'static str> {
+    GENERATED_FILES
+        .into_iter()
+        .filter(|file| server_dir.join(file).exists())
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(dir: &Path, script: &str) -> Command {
+        let mut command = Command::new("sh");
+        // `$0` is the init settings argument, appended after the script
+        command.arg("-c").arg(script).current_dir(dir);
+        command
+    }
+
+    #[tokio::test]
+    async fn test_init_settings_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = r#"[ "$0" = "--initSettings" ] && touch server.properties eula.txt"#;
+
+        let status = run_first_launch(sh(dir.path(), script), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(status.success());
+        assert_eq!(
+            generated_files(dir.path()),
+            ["server.properties", "eula.txt"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stop_sent_when_done() {
+        let dir = tempfile::tempdir().unwrap();
+        // Ignores the argument and starts up fully, like an old server would
+        let script = r#"touch server.properties
+echo '[Server thread/INFO]: Done (1.0s)! For help, type "help"'
+read command
+[ "$command" = "stop" ]"#;
+
+        let status = run_first_launch(sh(dir.path(), script), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(status.success());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = run_first_launch(sh(dir.path(), "sleep 10"), Duration::from_millis(100)).await;
+        assert!(result.is_err());
+    }
+}
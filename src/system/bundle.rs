@@ -0,0 +1,447 @@
+use crate::system::{
+    forks::ServerFork,
+    jar_parser::{self, ArchiveError, ExtractLimits, HashAlgorithm},
+    trash::TrashBatch,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// Bumped when bundles change in a way older versions of mcerv can't apply.
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+/// The first entry of the tar.
+const MANIFEST_ENTRY: &str = "manifest.json";
+/// The bundled files are under this in the tar, by their path in the instance directory.
+const FILES_DIR: &str = "files/";
+/// What the server jar downloads on its first start, bundled when they exist, since the
+/// offline machine can't download them: the Forge and Fabric libraries, and the vanilla jar
+/// Fabric's launcher runs.
+pub const RUNTIME_PATHS: [&str; 3] = ["libraries", "server.jar", ".fabric"];
+/// The manifest is read into memory, a few hundred bytes per file.
+const MAX_MANIFEST_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum BundleError {
+    /// The first entry isn't the manifest, like for a tar not made by `bundle create`.
+    MissingManifest,
+    /// Made by a newer mcerv.
+    UnsupportedFormat(u32),
+    /// Absolute, or leaving the instance directory with `..`.
+    InvalidPath(String),
+    HashMismatch(String),
+    MissingFile(String),
+    UnexpectedFile(String),
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::MissingManifest => {
+                write!(f, "Not a bundle, it doesn't start with {MANIFEST_ENTRY}")
+            }
+            BundleError::UnsupportedFormat(version) => write!(
+                f,
+                "The bundle has format version {version}, but this mcerv only applies up to \
+                {BUNDLE_FORMAT_VERSION}. Update mcerv to apply it"
+            ),
+            BundleError::InvalidPath(path) => {
+                write!(f, "The bundle has a file outside the instance: {path}")
+            }
+            BundleError::HashMismatch(path) => write!(
+                f,
+                "{path} in the bundle doesn't match its hash, the bundle may be corrupted"
+            ),
+            BundleError::MissingFile(path) => {
+                write!(f, "{path} is in the manifest but not in the bundle")
+            }
+            BundleError::UnexpectedFile(path) => {
+                write!(f, "{path} is in the bundle but not in its manifest")
+            }
+        }
+    }
+}
+
+impl Error for BundleError {}
+
+/// What `bundle create` packed, written as the first entry of the bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    /// The server the bundle was created from.
+    pub server_name: String,
+    pub fork: ServerFork,
+    pub game_version: String,
+    pub jar_name: String,
+    pub min_memory: String,
+    pub max_memory: String,
+    /// RFC 3339.
+    pub created_at: String,
+    pub files: Vec<BundleFile>,
+}
+
+impl BundleManifest {
+    /// Whether the file, relative to the instance directory, is bundled.
+    pub fn contains(&self, path: &Path) -> bool {
+        let path = bundle_path(path);
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BundleFile {
+    /// Relative to the instance directory with `/` separators, like `mods/lithium.jar`.
+    pub path: String,
+    pub sha512: String,
+    pub size: u64,
+}
+
+/// The files under `dir` relative to `base`, in subdirectories too.
+pub fn files_under(base: &Path, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(base.join(dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(files_under(base, &path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Hashes the files, relative to the server directory, into the manifest and writes the
+/// bundle to `output`. A bundle that couldn't be written completely is removed.
+pub fn create(
+    output: &Path,
+    server_dir: &Path,
+    manifest: &mut BundleManifest,
+    paths: &[PathBuf],
+) -> anyhow::Result<()> {
+    manifest.files = paths
+        .iter()
+        .map(|path| {
+            let mut file = File::open(server_dir.join(path))?;
+            Ok(BundleFile {
+                path: bundle_path(path),
+                sha512: jar_parser::calculate_hash_with(&mut file, HashAlgorithm::Sha512)?,
+                size: file.metadata()?.len(),
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let result = write_tar(output, server_dir, manifest);
+    if result.is_err() {
+        let _ = fs::remove_file(output);
+    }
+    result
+}
+
+fn write_tar(output: &Path, server_dir: &Path, manifest: &BundleManifest) -> anyhow::Result<()> {
+    let mut builder = tar::Builder::new(File::create(output)?);
+
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_ENTRY, manifest_json.as_slice())?;
+
+    for file in &manifest.files {
+        let path = instance_path(&file.path)?;
+        builder
+            .append_path_with_name(server_dir.join(path), format!("{FILES_DIR}{}", file.path))?;
+    }
+    builder.into_inner()?.sync_all()?;
+
+    Ok(())
+}
+
+/// Reads the manifest of the bundle and checks every file against it, without extracting
+/// anything.
+pub fn verify(bundle: &Path) -> anyhow::Result<BundleManifest> {
+    let mut archive = tar::Archive::new(File::open(bundle)?);
+    let mut entries = archive.entries()?;
+
+    let first = entries.next().ok_or(BundleError::MissingManifest)??;
+    if first.path_bytes().as_ref() != MANIFEST_ENTRY.as_bytes() {
+        return Err(BundleError::MissingManifest.into());
+    }
+    let mut manifest_json = String::new();
+    first
+        .take(MAX_MANIFEST_BYTES + 1)
+        .read_to_string(&mut manifest_json)?;
+    if manifest_json.len() as u64 > MAX_MANIFEST_BYTES {
+        return Err(ArchiveError::EntryTooLarge(MANIFEST_ENTRY.to_string()).into());
+    }
+    let manifest: BundleManifest = serde_json::from_str(&manifest_json)
+        .map_err(|e| anyhow::anyhow!("Invalid bundle manifest: {e}"))?;
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(BundleError::UnsupportedFormat(manifest.format_version).into());
+    }
+
+    let mut expected: HashMap<&str, &BundleFile> = HashMap::new();
+    for file in &manifest.files {
+        instance_path(&file.path)?;
+        expected.insert(&file.path, file);
+    }
+
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry_path(&entry)?;
+        let file = expected
+            .remove(path.as_str())
+            .ok_or_else(|| BundleError::UnexpectedFile(path.clone()))?;
+        let sha512 = jar_parser::calculate_hash_with(&mut entry, HashAlgorithm::Sha512)?;
+        if sha512 != file.sha512 {
+            return Err(BundleError::HashMismatch(path).into());
+        }
+    }
+    if let Some(path) = expected.into_keys().min() {
+        return Err(BundleError::MissingFile(path.to_string()).into());
+    }
+
+    Ok(manifest)
+}
+
+/// Extracts the files of a [`verify`]'d bundle into the server directory, returning how many
+/// were written. Files that already have the bundled content are left alone. With a trash
+/// batch, the replaced files are moved to it, so the update can be undone.
+pub fn extract(
+    bundle: &Path,
+    manifest: &BundleManifest,
+    server_dir: &Path,
+    trash: Option<&TrashBatch>,
+) -> anyhow::Result<usize> {
+    let hashes: HashMap<&str, &str> = manifest
+        .files
+        .iter()
+        .map(|file| (file.path.as_str(), file.sha512.as_str()))
+        .collect();
+
+    let limits = ExtractLimits::default();
+    if manifest.files.len() > limits.max_entries {
+        return Err(ArchiveError::TooManyEntries(manifest.files.len()).into());
+    }
+
+    let mut archive = tar::Archive::new(File::open(bundle)?);
+    let mut written = 0;
+    let mut total = 0;
+    for entry in archive.entries()?.skip(1) {
+        let mut entry = entry?;
+        let path = entry_path(&entry)?;
+        // Links could point anywhere, devices and the like aren't files to extract
+        if !entry.header().entry_type().is_file() {
+            return Err(ArchiveError::UnsafePath(path).into());
+        }
+        let dest = server_dir.join(instance_path(&path)?);
+
+        if dest.is_file() {
+            let existing =
+                jar_parser::calculate_hash_with(&mut File::open(&dest)?, HashAlgorithm::Sha512)?;
+            if hashes.get(path.as_str()) == Some(&existing.as_str()) {
+                continue;
+            }
+            match trash {
+                Some(trash) => trash.trash(&dest)?,
+                None => fs::remove_file(&dest)?,
+            }
+        }
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        jar_parser::safe_extract(&mut entry, &path, &dest, &limits, &mut total)?;
+        if let Some(trash) = trash {
+            trash.record_added(&dest)?;
+        }
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// The path of a file entry relative to the instance directory.
+fn entry_path(entry: &tar::Entry<File>) -> anyhow::Result<String> {
+    let path = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+    match path.strip_prefix(FILES_DIR) {
+        Some(path) => Ok(path.to_string()),
+        None => Err(BundleError::UnexpectedFile(path).into()),
+    }
+}
+
+/// The path of a bundled file, or an error if it would be outside the instance directory.
+fn instance_path(path: &str) -> Result<PathBuf, BundleError> {
+    jar_parser::contained_path(path).ok_or_else(|| BundleError::InvalidPath(path.to_string()))
+}
+
+/// Like `mods/lithium.jar`, the same on every platform.
+fn bundle_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> BundleManifest {
+        BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            server_name: "survival".to_string(),
+            fork: ServerFork::Fabric,
+            game_version: "1.21.8".to_string(),
+            jar_name: "fabric-server.jar".to_string(),
+            min_memory: "2G".to_string(),
+            max_memory: "4G".to_string(),
+            created_at: "2025-08-14T18:03:00+02:00".to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    fn server(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_create_and_apply() {
+        let source = server(&[
+            ("fabric-server.jar", "jar"),
+            ("mods/lithium.jar", "lithium"),
+            ("libraries/net/fabricmc/loader.jar", "loader"),
+        ]);
+        let paths = files_under(source.path(), Path::new("")).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let bundle = out.path().join("bundle.tar");
+        let mut manifest = manifest();
+        create(&bundle, source.path(), &mut manifest, &paths).unwrap();
+        assert_eq!(
+            manifest
+                .files
+                .iter()
+                .map(|file| file.path.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "fabric-server.jar",
+                "libraries/net/fabricmc/loader.jar",
+                "mods/lithium.jar"
+            ]
+        );
+
+        assert_eq!(verify(&bundle).unwrap(), manifest);
+
+        // An update only writes what changed
+        let target = server(&[("fabric-server.jar", "jar"), ("mods/lithium.jar", "old")]);
+        let trash = TrashBatch::begin(target.path(), "bundle apply");
+        assert_eq!(
+            extract(&bundle, &manifest, target.path(), Some(&trash)).unwrap(),
+            2
+        );
+        assert_eq!(
+            fs::read_to_string(target.path().join("mods/lithium.jar")).unwrap(),
+            "lithium"
+        );
+        assert!(
+            target
+                .path()
+                .join("libraries/net/fabricmc/loader.jar")
+                .is_file()
+        );
+        assert!(!trash.is_empty());
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_bundle() {
+        let source = server(&[("fabric-server.jar", "jar")]);
+        let out = tempfile::tempdir().unwrap();
+        let bundle = out.path().join("bundle.tar");
+        let mut manifest = manifest();
+        create(
+            &bundle,
+            source.path(),
+            &mut manifest,
+            &[PathBuf::from("fabric-server.jar")],
+        )
+        .unwrap();
+
+        manifest.files[0].sha512 = "0".repeat(128);
+        let tampered = out.path().join("tampered.tar");
+        write_tar(&tampered, source.path(), &manifest).unwrap();
+        let error = verify(&tampered).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BundleError>(),
+            Some(&BundleError::HashMismatch("fabric-server.jar".to_string()))
+        );
+
+        manifest.format_version = BUNDLE_FORMAT_VERSION + 1;
+        write_tar(&tampered, source.path(), &manifest).unwrap();
+        let error = verify(&tampered).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BundleError>(),
+            Some(&BundleError::UnsupportedFormat(BUNDLE_FORMAT_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_verify_caps_manifest() {
+        let out = tempfile::tempdir().unwrap();
+        let bundle = out.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(File::create(&bundle).unwrap());
+        let padding = vec![b' '; MAX_MANIFEST_BYTES as usize + 1];
+        let mut header = tar::Header::new_gnu();
+        header.set_size(padding.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, MANIFEST_ENTRY, padding.as_slice())
+            .unwrap();
+        builder.finish().unwrap();
+
+        let error = verify(&bundle).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ArchiveError>(),
+            Some(&ArchiveError::EntryTooLarge(MANIFEST_ENTRY.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_instance_path() {
+        assert_eq!(
+            instance_path("mods/lithium.jar"),
+            Ok(PathBuf::from("mods").join("lithium.jar"))
+        );
+        for invalid in [
+            "../evil.jar",
+            "/etc/passwd",
+            "mods//x.jar",
+            "C:\\x.jar",
+            "./x",
+            "mods/evil\0.jar",
+            "mods/\n",
+        ] {
+            assert_eq!(
+                instance_path(invalid),
+                Err(BundleError::InvalidPath(invalid.to_string()))
+            );
+        }
+    }
+}
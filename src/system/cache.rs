@@ -0,0 +1,281 @@
+use crate::system::jar_parser::{self, HashAlgorithm};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files that are being written. They're renamed to their hash once complete.
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Downloaded files stored by their sha512, shared by all servers.
+///
+/// A hit touches the entry's modification time, which [`DownloadCache::prune`]
+/// uses as the last time it was used.
+#[derive(Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub size: u64,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+impl DownloadCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether files with the hash can be cached. Hashes come from API responses, and only
+    /// a sha512 in lowercase hex is safe to make a path of.
+    pub fn accepts(sha512: &str) -> bool {
+        sha512.len() == 128
+            && sha512
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    }
+
+    fn entry_path(&self, sha512: &str) -> io::Result<PathBuf> {
+        if !Self::accepts(sha512) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{sha512:?} isn't a sha512 the cache can store"),
+            ));
+        }
+        Ok(self.dir.join(sha512))
+    }
+
+    /// Copies the cached file to `dest`. Returns `false` if it isn't cached, or the entry
+    /// doesn't match its hash anymore, in which case it's removed.
+    pub fn copy_to(&self, sha512: &str, dest: &Path) -> io::Result<bool> {
+        if !Self::accepts(sha512) {
+            return Ok(false);
+        }
+        let path = self.entry_path(sha512)?;
+        if !path.is_file() {
+            return Ok(false);
+        }
+
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::now())?;
+
+        if dest.exists() {
+            fs::remove_file(dest)?;
+        }
+        // A copy, not a hard link, so changing the installed file can't change the entry
+        fs::copy(&path, dest)?;
+        let copied =
+            jar_parser::calculate_hash_with(&mut File::open(dest)?, HashAlgorithm::Sha512)?;
+        if copied != sha512 {
+            fs::remove_file(dest)?;
+            fs::remove_file(&path)?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// A path to write a new entry to before [`DownloadCache::insert`]ing it.
+    /// Unique per process, so concurrent mcerv invocations don't write to the same file.
+    pub fn partial_path(&self, sha512: &str) -> io::Result<PathBuf> {
+        let entry_path = self.entry_path(sha512)?;
+        fs::create_dir_all(&self.dir)?;
+        let mut file_name = entry_path.into_os_string();
+        file_name.push(format!(".{}.{PARTIAL_EXTENSION}", std::process::id()));
+        Ok(file_name.into())
+    }
+
+    /// Moves the file written to [`DownloadCache::partial_path`] into the cache, after the
+    /// caller checked it matches the hash. The rename is atomic, so readers see either no
+    /// entry or the complete one.
+    pub fn insert(&self, sha512: &str, partial_path: &Path) -> io::Result<()> {
+        fs::rename(partial_path, self.entry_path(sha512)?)
+    }
+
+    pub fn stats(&self) -> io::Result<CacheStats> {
+        let entries = self.entries()?;
+        Ok(CacheStats {
+            entries: entries.len(),
+            size: entries.iter().map(|e| e.size).sum(),
+        })
+    }
+
+    /// Removes the least recently used entries until the cache is at most `max_size` bytes.
+    /// Returns the stats of the removed entries.
+    pub fn prune(&self, max_size: u64) -> io::Result<CacheStats> {
+        let mut entries = self.entries()?;
+        entries.sort_by_key(|e| e.last_used);
+
+        let mut size: u64 = entries.iter().map(|e| e.size).sum();
+        let mut removed = CacheStats {
+            entries: 0,
+            size: 0,
+        };
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            fs::remove_file(&entry.path)?;
+            size -= entry.size;
+            removed.entries += 1;
+            removed.size += entry.size;
+        }
+
+        Ok(removed)
+    }
+
+    fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for entry in read_dir {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().is_some_and(|e| e == PARTIAL_EXTENSION) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            entries.push(CacheEntry {
+                path,
+                size: metadata.len(),
+                last_used: metadata.modified()?,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sha512(content: &[u8]) -> String {
+        jar_parser::calculate_hash_with(&mut &content[..], HashAlgorithm::Sha512).unwrap()
+    }
+
+    fn insert(cache: &DownloadCache, content: &[u8]) -> String {
+        let hash = sha512(content);
+        let partial_path = cache.partial_path(&hash).unwrap();
+        fs::write(&partial_path, content).unwrap();
+        cache.insert(&hash, &partial_path).unwrap();
+        hash
+    }
+
+    #[test]
+    fn test_insert_and_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("downloads"));
+        let dest = dir.path().join("lithium.jar");
+
+        assert!(!cache.copy_to(&sha512(b"lithium"), &dest).unwrap());
+
+        let hash = insert(&cache, b"lithium");
+        assert!(cache.copy_to(&hash, &dest).unwrap());
+        assert_eq!(fs::read(&dest).unwrap(), b"lithium");
+        assert_eq!(
+            cache.stats().unwrap(),
+            CacheStats {
+                entries: 1,
+                size: 7
+            }
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsafe_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("downloads"));
+        let outside = dir.path().join("x");
+        fs::write(&outside, b"not cached").unwrap();
+        let dest = dir.path().join("lithium.jar");
+
+        for hash in ["../x", "../../x", "", &sha512(b"x").to_uppercase()] {
+            assert!(!DownloadCache::accepts(hash), "{hash}");
+            assert!(!cache.copy_to(hash, &dest).unwrap(), "{hash}");
+            assert!(cache.partial_path(hash).is_err(), "{hash}");
+        }
+        assert!(!dest.exists());
+        assert!(DownloadCache::accepts(&sha512(b"x")));
+    }
+
+    #[test]
+    fn test_copy_checks_the_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path().join("downloads"));
+        let dest = dir.path().join("lithium.jar");
+        let hash = insert(&cache, b"lithium");
+
+        assert!(cache.copy_to(&hash, &dest).unwrap());
+        // The installed jar doesn't share the entry's file
+        fs::write(&dest, b"changed").unwrap();
+        assert!(cache.copy_to(&hash, &dest).unwrap());
+        assert_eq!(fs::read(&dest).unwrap(), b"lithium");
+
+        // A corrupted entry is dropped, so it's downloaded again
+        fs::write(cache.dir().join(&hash), b"corrupted").unwrap();
+        assert!(!cache.copy_to(&hash, &dest).unwrap());
+        assert!(!dest.exists());
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_stats_skips_partial_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path());
+        insert(&cache, b"sodium");
+        fs::write(cache.partial_path(&sha512(b"lithium")).unwrap(), b"lith").unwrap();
+
+        assert_eq!(cache.stats().unwrap().entries, 1);
+    }
+
+    #[test]
+    fn test_prune_removes_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(dir.path());
+        let now = SystemTime::now();
+
+        let hashes = [b"aaaa", b"bbbb", b"cccc"].map(|content| insert(&cache, content));
+        // `aaaa` was used last, `bbbb` first
+        for (hash, age) in hashes.iter().zip([0, 120, 60]) {
+            File::options()
+                .write(true)
+                .open(dir.path().join(hash))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let removed = cache.prune(8).unwrap();
+
+        assert_eq!(
+            removed,
+            CacheStats {
+                entries: 1,
+                size: 4
+            }
+        );
+        assert!(!dir.path().join(&hashes[1]).exists());
+        assert!(dir.path().join(&hashes[0]).exists());
+        assert_eq!(cache.prune(0).unwrap().entries, 2);
+    }
+}
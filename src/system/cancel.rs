@@ -0,0 +1,120 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// What `mcerv` exits with after Ctrl-C, like a shell does for SIGINT.
+pub const CANCELLED_EXIT_CODE: i32 = 130;
+
+static TOKEN: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+static SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+/// An operation stopped because of Ctrl-C, after cleaning up what it left behind.
+#[derive(Debug)]
+pub struct CancelledError;
+
+impl Display for CancelledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cancelled, cleaned up")
+    }
+}
+
+impl Error for CancelledError {}
+
+/// Cancelled by [`cancel`], for the operations to pass to what they await.
+pub fn token() -> CancellationToken {
+    TOKEN.clone()
+}
+
+/// Cancels the operations holding the [`token`].
+pub fn cancel() {
+    TOKEN.cancel();
+}
+
+/// Whether the error is, or was caused by, a [`CancelledError`].
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<CancelledError>())
+}
+
+/// Marks that an operation cleans up after Ctrl-C itself while the guard lives, so
+/// the process waits for it instead of exiting right away.
+pub fn scope() -> ScopeGuard {
+    SCOPES.fetch_add(1, Ordering::SeqCst);
+    ScopeGuard(())
+}
+
+/// Whether an operation holding a [`scope`] is running.
+pub fn in_scope() -> bool {
+    SCOPES.load(Ordering::SeqCst) > 0
+}
+
+pub struct ScopeGuard(());
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Cancels the [`token`] on Ctrl-C. The process exits right away unless an operation holds
+/// a [`scope`], and on a second Ctrl-C.
+pub fn cancel_on_ctrl_c() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        cancel();
+        if !in_scope() {
+            std::process::exit(CANCELLED_EXIT_CODE);
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(CANCELLED_EXIT_CODE);
+        }
+    });
+}
+
+/// Runs the future until it's done or the token is cancelled.
+pub async fn or_cancelled<T>(
+    token: &CancellationToken,
+    future: impl Future<Output = T>,
+) -> Result<T, CancelledError> {
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(CancelledError),
+        output = future => Ok(output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_or_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(or_cancelled(&token, async { 1 }).await.unwrap(), 1);
+
+        token.cancel();
+        let pending = std::future::pending::<()>();
+        assert!(or_cancelled(&token, pending).await.is_err());
+
+        let error = anyhow::Error::from(CancelledError).context("Failed to install");
+        assert!(is_cancelled(&error));
+        assert!(!is_cancelled(&anyhow::anyhow!("Failed to install")));
+    }
+
+    #[test]
+    fn test_scope() {
+        let outer = scope();
+        let inner = scope();
+        assert!(in_scope());
+        drop(outer);
+        assert!(in_scope());
+        drop(inner);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::system::{format, history::HISTORY_FILE};
+use prettytable::{Table, row};
+use serde::Deserialize;
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// File name patterns of leftovers that are always junk, and why.
+const DEFAULT_PATTERNS: [(&str, &str); 7] = [
+    ("*.jar.disabled", "disabled jar"),
+    ("start_script*.sh*", "old start script"),
+    ("start_script*.bat*", "old start script"),
+    ("*installer.log", "installer log"),
+    ("*installer.jar.log", "installer log"),
+    ("hs_err_pid*.log", "JVM crash dump"),
+    ("*.part", "interrupted download"),
+];
+
+/// Never removed, whatever they match. `gen-start-script` writes the scripts.
+const PROTECTED_NAMES: [&str; 5] = [
+    "mods",
+    "server.properties",
+    "start_script.sh",
+    "start_script.bat",
+    HISTORY_FILE,
+];
+
+/// More junk for `mcerv clean` from the global config, like:
+///
+/// ```toml
+/// [clean]
+/// patterns = ["*.bak", "logs/*.log.gz"]
+/// ```
+///
+/// Patterns with a `/` match the path in the server directory, others the file name. `*`
+/// matches any characters.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanSettings {
+    pub patterns: Vec<String>,
+}
+
+/// What to keep whatever the patterns say, besides the worlds and the mods.
+#[derive(Debug)]
+pub struct Protected<'a> {
+    /// The server jar of the config.
+    pub jar_name: &'a str,
+    /// The `level-name` of server.properties, if the world isn't named `world*`.
+    pub level_name: &'a str,
+}
+
+/// A file that can be removed.
+#[derive(Debug, PartialEq)]
+pub struct Junk {
+    /// Relative to the server directory.
+    pub path: PathBuf,
+    pub size: u64,
+    pub reason: String,
+}
+
+/// Picks the junk out of the files, given as paths relative to the server directory and
+/// their sizes. Files no pattern matches are kept, and so are the protected ones.
+pub fn classify(files: &[(PathBuf, u64)], protected: &Protected, patterns: &[String]) -> Vec<Junk> {
+    files
+        .iter()
+        .filter(|(path, _)| !is_protected(path, protected))
+        .filter_map(|(path, size)| {
+            let reason = DEFAULT_PATTERNS
+                .iter()
+                .find(|(pattern, _)| pattern_matches(pattern, path))
+                .map(|(_, reason)| reason.to_string())
+                .or_else(|| {
+                    patterns
+                        .iter()
+                        .find(|pattern| pattern_matches(pattern, path))
+                        .map(|pattern| format!("matches {pattern}"))
+                })?;
+            Some(Junk {
+                path: path.clone(),
+                size: *size,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn is_protected(path: &Path, protected: &Protected) -> bool {
+    let Some(Component::Normal(first)) = path.components().next() else {
+        // Absolute or `..` paths aren't in the listing of a server directory
+        return true;
+    };
+    let first = first.to_string_lossy();
+    // mcerv's own files, like its config, lock and trash, start with its name
+    first.starts_with("world")
+        || first.trim_start_matches('.').starts_with("mcerv")
+        || first == protected.level_name
+        || PROTECTED_NAMES.contains(&first.as_ref())
+        || path == Path::new(protected.jar_name)
+}
+
+fn pattern_matches(pattern: &str, path: &Path) -> bool {
+    if pattern.contains('/') {
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        wildcard_matches(pattern, &path)
+    } else {
+        path.file_name()
+            .is_some_and(|name| wildcard_matches(pattern, &name.to_string_lossy()))
+    }
+}
+
+/// Whether the text matches the pattern, where `*` stands for any characters.
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`, so the whole text must have been the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// The files in the directory and its subdirectories with their sizes, relative to it.
+/// Symlinks are listed as they are, so nothing outside is ever reached.
+pub fn list_files(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else {
+                files.push((path, entry.metadata()?.len()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The junk of a server, and how much space removing it frees.
+#[derive(Debug)]
+pub struct CleanReport(pub Vec<Junk>);
+
+impl CleanReport {
+    pub fn total_size(&self) -> u64 {
+        self.0.iter().map(|junk| junk.size).sum()
+    }
+}
+
+impl Display for CleanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut table = Table::new();
+        table.add_row(row!["File", "Kind", "Size"]);
+        for junk in &self.0 {
+            table.add_row(row![
+                junk.path.display(),
+                junk.reason,
+                format::format_bytes(junk.size)
+            ]);
+        }
+        write!(f, "{table}")?;
+        write!(
+            f,
+            "{} files, {} in total",
+            self.0.len(),
+            format::format_bytes(self.total_size())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(paths: &[&str]) -> Vec<(PathBuf, u64)> {
+        paths
+            .iter()
+            .map(|path| (PathBuf::from(path), 100))
+            .collect()
+    }
+
+    fn junk_paths(files: &[(PathBuf, u64)], patterns: &[String]) -> Vec<String> {
+        let protected = Protected {
+            jar_name: "fabric-server-launch.jar",
+            level_name: "survival",
+        };
+        classify(files, &protected, patterns)
+            .into_iter()
+            .map(|junk| junk.path.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_defaults() {
+        let files = listing(&[
+            "fabric-server-launch.jar",
+            "server.jar.disabled",
+            "start_script.sh",
+            "start_script.sh.bak",
+            "start_script (1).sh",
+            "forge-1.20.1-47.3.0-installer.jar.log",
+            "installer.log",
+            "hs_err_pid4242.log",
+            "server.jar.part",
+            "libraries/net/example/lib.jar.part",
+            "server.properties",
+            "eula.txt",
+            "logs/latest.log",
+        ]);
+        assert_eq!(
+            junk_paths(&files, &[]),
+            [
+                "server.jar.disabled",
+                "start_script.sh.bak",
+                "start_script (1).sh",
+                "forge-1.20.1-47.3.0-installer.jar.log",
+                "installer.log",
+                "hs_err_pid4242.log",
+                "server.jar.part",
+                "libraries/net/example/lib.jar.part",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_classify_never_touches_protected() {
+        let files = listing(&[
+            "world/region/r.0.0.mca.part",
+            "world_nether/level.dat.bak",
+            "world-backups/world-1/session.lock.part",
+            "survival/level.dat.part",
+            "mods/sodium.jar.disabled",
+            "mods/lithium.jar.part",
+            ".mcerv-trash/1/server.jar.part",
+            "mcerv_config.json",
+            "history.jsonl",
+            "fabric-server-launch.jar",
+            "server.properties",
+        ]);
+        let everything = ["*".to_string()];
+        assert!(junk_paths(&files, &everything).is_empty());
+    }
+
+    #[test]
+    fn test_classify_configured_patterns() {
+        let files = listing(&[
+            "config/sodium.json.bak",
+            "logs/2025-08-01-1.log.gz",
+            "logs/latest.log",
+            "crash-reports/crash.txt",
+        ]);
+        let patterns = ["*.bak".to_string(), "logs/*.log.gz".to_string()];
+        assert_eq!(
+            junk_paths(&files, &patterns),
+            ["config/sodium.json.bak", "logs/2025-08-01-1.log.gz"]
+        );
+
+        let report = CleanReport(classify(
+            &files,
+            &Protected {
+                jar_name: "server.jar",
+                level_name: "world",
+            },
+            &patterns,
+        ));
+        assert_eq!(report.total_size(), 200);
+        assert!(report.to_string().contains("matches logs/*.log.gz"));
+    }
+
+    #[test]
+    fn test_wildcard_matches() {
+        assert!(wildcard_matches("*.part", "server.jar.part"));
+        assert!(wildcard_matches("hs_err_pid*.log", "hs_err_pid1.log"));
+        assert!(wildcard_matches("a*b*c", "abc"));
+        assert!(wildcard_matches("exact", "exact"));
+        assert!(!wildcard_matches("exact", "exactly"));
+        assert!(!wildcard_matches("*.part", "server.part.jar"));
+        // The prefix and suffix can't share characters
+        assert!(!wildcard_matches("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_list_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("libraries/net")).unwrap();
+        fs::write(dir.path().join("server.jar.part"), "12345").unwrap();
+        fs::write(dir.path().join("libraries/net/lib.jar"), "1").unwrap();
+
+        assert_eq!(
+            list_files(dir.path()).unwrap(),
+            [
+                (PathBuf::from("libraries/net/lib.jar"), 1),
+                (PathBuf::from("server.jar.part"), 5),
+            ]
+        );
+    }
+}
@@ -1,23 +1,145 @@
+use crate::network;
 use crate::{
-    network::{fabric_meta, forge_meta, modrinth::SearchIndex, vanilla_meta},
-    system::forks::{FetchCommand, InstallCommand},
+    network::{
+        modrinth::{self, ModrinthToken, SearchIndex, facets},
+        rate_limit::Rate,
+    },
+    system::{
+        forks::{FetchCommand, InstallCommand, ServerFork},
+        global_config::GlobalConfig,
+        installer::{ServerInstaller, VersionChoice},
+        mods::ModSort,
+        schedule::ScheduledTime,
+        server_name::ServerName,
+        supervisor::RestartPolicy,
+        watch::Interval,
+        wizard,
+    },
     *,
 };
-use async_trait::async_trait;
-use clap::{ArgAction, Args, Parser, Subcommand};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
 use reqwest::Client;
 
-#[async_trait]
 pub trait Versions {
-    type V;
-    async fn versions(&self, client: &Client) -> anyhow::Result<Self::V>;
+    /// An installer with the versions of the arguments chosen. Versions that aren't given
+    /// are picked when installing, so this fails with the usual missing argument
+    /// [`clap::Error`] when not interactive.
+    fn installer(&self) -> anyhow::Result<ServerInstaller>;
+
+    /// Whether the versions are all given, so none would be picked interactively.
+    fn has_all_versions(&self) -> bool;
+}
+
+#[derive(Debug)]
+pub struct VersionNotFoundError {
+    pub kind: &'static str,
+    pub version: String,
+    pub closest_matches: Vec<String>,
+}
+
+impl Display for VersionNotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} not found", self.kind, self.version)?;
+        if !self.closest_matches.is_empty() {
+            write!(f, "; closest matches: {}", self.closest_matches.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for VersionNotFoundError {}
+
+/// Returns an error with suggestions if `version` is not one of `known`.
+pub(crate) fn ensure_version_exists(
+    kind: &'static str,
+    version: &str,
+    known: &[impl AsRef<str>],
+) -> Result<(), VersionNotFoundError> {
+    if known.iter().any(|v| v.as_ref() == version) {
+        return Ok(());
+    }
+
+    Err(VersionNotFoundError {
+        kind,
+        version: version.to_string(),
+        closest_matches: closest_matches(version, known, 3),
+    })
+}
+
+/// Returns up to `n` of the `candidates` closest to `target` by edit distance,
+/// preferring longer common prefixes on ties.
+/// A cache size in GB, which a negative one would empty the cache for.
+fn parse_cache_size(s: &str) -> Result<f64, String> {
+    let size: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if size.is_nan() || size < 0.0 {
+        return Err("the size can't be negative".to_string());
+    }
+    Ok(size)
+}
+
+fn closest_matches(target: &str, candidates: &[impl AsRef<str>], n: usize) -> Vec<String> {
+    let common_prefix = |s: &str| {
+        target
+            .chars()
+            .zip(s.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    let mut scored = candidates
+        .iter()
+        .map(|c| c.as_ref())
+        .map(|c| (edit_distance(target, c), usize::MAX - common_prefix(c), c))
+        .collect::<Vec<_>>();
+    // Stable sort keeps the meta API's order (usually newest first) on full ties
+    scored.sort_by_key(|(distance, prefix, _)| (*distance, *prefix));
+
+    scored
+        .into_iter()
+        .take(n)
+        .map(|(_, _, c)| c.to_string())
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// `choice`, or [`VersionChoice::Pick`] if it's `None`.
+/// Fails with the usual missing argument error if it would be picked when not interactive.
+fn choice_or_pick<A: CommandFactory>(
+    choice: Option<VersionChoice>,
+    arg_name: &str,
+) -> Result<VersionChoice, clap::Error> {
+    if let Some(choice) = choice {
+        return Ok(choice);
+    }
+    if !is_interactive() {
+        return Err(A::command().error(
+            ErrorKind::MissingRequiredArgument,
+            format!("the following required arguments were not provided:\n  <{arg_name}>"),
+        ));
+    }
+    Ok(VersionChoice::Pick)
 }
 
 pub trait FetchFilter {}
 
 #[derive(Args, Debug)]
 pub struct VersionsFilter {
-    /// List all versions, stable and unstable.
+    /// List all versions, stable and unstable, without a limit.
     #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
     pub all: bool,
 }
@@ -25,37 +147,361 @@ pub struct VersionsFilter {
 impl FetchFilter for VersionsFilter {}
 
 #[derive(Args, Debug)]
-pub struct YesArgs {
-    #[arg(short, long, action = ArgAction::SetTrue, default_value_t = false)]
-    pub yes: bool,
+pub struct ForgeVersionsFilter {
+    /// List only versions for this Minecraft version. For example: `1.21.8`.
+    #[arg(long)]
+    pub game: Option<String>,
+
+    /// List only the builds promoted as latest or recommended.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub latest_only: bool,
+
+    /// List the versions without a limit.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+    pub all: bool,
+}
+
+impl FetchFilter for ForgeVersionsFilter {}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show how many files the download cache holds and their size
+    Stats,
+    /// Remove the least recently used downloads until the cache fits in the size
+    Prune {
+        /// The size to shrink the cache to, in GB
+        #[arg(long, value_parser = parse_cache_size)]
+        max_size: f64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportCommand {
+    /// Write a Dockerfile and a docker-compose.yml running the server in a container, with
+    /// its directory mounted. The EULA must be accepted
+    Docker {
+        server_name: ServerName,
+        /// Where to write the files, the server directory by default
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GenerateCommand {
+    /// Print a systemd service running `mcerv start` for the server, named
+    /// `mcerv-<server>.service`
+    SystemdUnit {
+        server_name: ServerName,
+        /// A service of your own systemd instance instead of the system's
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        user: bool,
+        /// Write it to ~/.config/systemd/user/ with `--user`, or /etc/systemd/system/
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        install: bool,
+        #[command(flatten)]
+        supervision: SupervisionArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotifyCommand {
+    /// Post a test notification to every webhook, ignoring their event filters
+    Test,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ModsCommand {
+    /// Show which mods depend on which, as an indented tree
+    Graph {
+        server_name: ServerName,
+        /// Print the graph in Graphviz's DOT language instead
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        dot: bool,
+        /// Only list the mods depending on this one, by slug or mod ID
+        #[arg(long, value_name = "MOD", conflicts_with = "dot")]
+        dependents: Option<String>,
+    },
+    /// Remove the libraries no installed mod depends on anymore
+    Prune { server_name: ServerName },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BundleCommand {
+    /// Pack the server jar, the libraries it downloads and the mods into a tar for a
+    /// machine without internet
+    Create {
+        server_name: ServerName,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Install a server from a bundle, or update it if it exists, without the network
+    Apply {
+        bundle: PathBuf,
+        server_name: ServerName,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MappingsCommand {
+    /// Download Mojang's server mappings of the server's game version into
+    /// `mappings/<version>.txt`
+    Fetch { server_name: ServerName },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CheckCommand {
+    /// Find mods that only run on the client, and offer to disable or remove them
+    ClientOnly { server_name: ServerName },
+    /// Check which mods have a version for another game version, like before updating to it
+    ModsSupport {
+        server_name: ServerName,
+        /// Like `1.21.8`
+        game_version: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ModrinthCommand {
+    /// Show the Modrinth user of the token, to check it works
+    Whoami,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GroupCommand {
+    /// Save servers as a group, started in the order given and stopped in reverse
+    Create {
+        group_name: String,
+        #[arg(required = true)]
+        server_names: Vec<ServerName>,
+        /// Seconds to wait before starting each server after the first
+        #[arg(long, default_value_t = 0)]
+        delay: u64,
+    },
+    /// Start the servers of the group in the background
+    Start {
+        group_name: String,
+        /// Don't start the rest of the servers after one fails
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        fail_fast: bool,
+    },
+    /// Stop the servers of the group in reverse order
+    Stop {
+        group_name: String,
+        /// Don't stop the rest of the servers after one fails
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        fail_fast: bool,
+    },
+    /// Show whether each server of the group is running, and its port
+    Status { group_name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Regenerate a broken config from the server jar, keeping the fields that still parse.
+    /// The old config is kept as mcerv_config.json.broken
+    Repair { server_name: ServerName },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    /// Tag the server. Tags are lowercase letters, digits, '-' and '_'
+    Add {
+        server_name: ServerName,
+        tag: String,
+    },
+    /// Remove the tag from the server
+    Remove {
+        server_name: ServerName,
+        tag: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TemplateCommand {
+    /// Save the server's fork, versions, memory, selected server.properties and mods as a template
+    Save {
+        server_name: ServerName,
+        template_name: String,
+        /// Install the newest compatible version of each mod instead of the installed one
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        latest_mods: bool,
+    },
+    /// Install a new server from the template
+    Apply {
+        template_name: String,
+        server_name: ServerName,
+    },
+    /// List the saved templates
+    List,
+    /// Delete the template
+    Delete { template_name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ResourcepackCommand {
+    /// Send a resource pack to players joining the server
+    Set {
+        server_name: ServerName,
+        /// A local resource pack file, or the URL it's downloaded from
+        source: String,
+        /// The public URL players download a local resource pack from.
+        /// Asked for if not given
+        #[arg(long)]
+        url: Option<String>,
+        /// Copy a local resource pack into the server directory
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        copy: bool,
+        /// Kick players who decline the resource pack
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        require: bool,
+    },
+    /// Stop sending a resource pack
+    Clear { server_name: ServerName },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum WorldCommand {
+    /// Show the seed, name and game version of the world
+    Info { server_name: ServerName },
+    /// Back up and delete the world, so the next start generates a new one
+    Reset {
+        server_name: ServerName,
+        /// The seed of the new world
+        #[arg(long)]
+        seed: Option<String>,
+        /// The server's name again, to reset it without asking, which `--yes` doesn't
+        #[arg(long, value_name = "SERVER_NAME")]
+        confirm: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PropCommand {
+    /// Show the server.properties keys two servers set differently
+    Diff {
+        server_a: ServerName,
+        server_b: ServerName,
+    },
+    /// Copy the server.properties values of one server to others
+    Sync {
+        /// The server to copy from
+        #[arg(long)]
+        from: ServerName,
+        /// The servers to copy to
+        #[arg(long, required = true, num_args = 1..)]
+        to: Vec<ServerName>,
+        /// Keys to leave as they are, like server-port,motd,level-seed
+        #[arg(long, value_delimiter = ',')]
+        except: Vec<String>,
+        /// Only show what would change
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+/// The filters of a Modrinth search
+#[derive(Args, Debug, Default)]
+pub struct FacetArgs {
+    /// Example: `open_source:true`, `license:mit`, `downloads>=10000`.
+    ///
+    /// Comma-separated facets in one value are OR-ed, while separate values are AND-ed.
+    /// For example, `--facets versions:1.21.7,versions:1.21.8 license:mit` finds mods
+    /// for either version that are also MIT licensed.
+    ///
+    /// See https://docs.modrinth.com/api/operations/searchprojects for details.
+    ///
+    /// Note: `mcerv` automatically adds `server_side:required` & `server_side:optional`
+    /// unless `--client-ok` is set.
+    #[arg(long, num_args = 0..)]
+    pub facets: Vec<String>,
+    /// Only mods of the category or loader, like `fabric`. Comma-separated values are OR-ed
+    #[arg(long, value_delimiter = ',')]
+    pub category: Vec<String>,
+    /// Only mods for the Minecraft version. Comma-separated values are OR-ed
+    #[arg(long, value_delimiter = ',')]
+    pub game_version: Vec<String>,
+    /// Only mods with the license, like `mit`. Comma-separated values are OR-ed
+    #[arg(long, value_delimiter = ',')]
+    pub license: Vec<String>,
+}
+
+impl FacetArgs {
+    /// The facet groups of `--facets` followed by the ones of the shorthand flags.
+    pub fn groups(&self) -> Result<Vec<Vec<String>>, facets::FacetError> {
+        let mut groups = facets::parse_groups(&self.facets)?;
+        groups.extend(
+            [
+                facets::group_of("categories", &self.category),
+                facets::group_of("versions", &self.game_version),
+                facets::group_of("license", &self.license),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        Ok(groups)
+    }
+}
+
+/// How a started server is kept running, shared by Start and the detached wrapper
+#[derive(Args, Debug, Default)]
+pub struct SupervisionArgs {
+    /// Restart the server when it crashes, up to MAX_RETRIES times in a row
+    #[arg(long, value_name = "MAX_RETRIES", num_args = 0..=1, default_missing_value = "3")]
+    pub restart_on_crash: Option<u32>,
+    /// Restart the server every day at HH:MM local time, warning players beforehand.
+    /// Can be given multiple times
+    #[arg(long, value_name = "HH:MM")]
+    pub restart_at: Vec<ScheduledTime>,
+}
+
+impl SupervisionArgs {
+    pub fn restart_policy(&self) -> Option<RestartPolicy> {
+        self.restart_on_crash.map(RestartPolicy::new)
+    }
+
+    /// The arguments that recreate these options on the command line.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(max_retries) = self.restart_on_crash {
+            args.push(format!("--restart-on-crash={max_retries}"));
+        }
+        for time in &self.restart_at {
+            args.push(format!("--restart-at={time}"));
+        }
+        args
+    }
 }
 
 /// Shared vanilla version arguments for Install and UpdateServerJar
 #[derive(Parser, Debug)]
 pub struct VanillaVersionArgs {
     /// Use the latest stable game version
-    #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "version")]
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with_all = ["version", "latest_snapshot"])]
     pub latest_stable: bool,
 
-    /// Minecraft game version
-    #[arg(
-        required_unless_present = "latest_stable",
-        conflicts_with = "latest_stable"
-    )]
+    /// Use the latest snapshot game version
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "version")]
+    pub latest_snapshot: bool,
+
+    /// Minecraft game version. Picked interactively if not given
     pub version: Option<String>,
 }
 
-#[async_trait]
 impl Versions for VanillaVersionArgs {
-    type V = String;
-    async fn versions(&self, client: &Client) -> anyhow::Result<Self::V> {
-        let version = if self.latest_stable {
-            vanilla_meta::fetch_latest_stable_version(client).await?
+    fn installer(&self) -> anyhow::Result<ServerInstaller> {
+        let choice = if self.latest_stable {
+            Some(VersionChoice::LatestStable)
+        } else if self.latest_snapshot {
+            Some(VersionChoice::LatestSnapshot)
         } else {
-            self.version.clone().unwrap()
+            self.version.clone().map(VersionChoice::Exact)
         };
+        Ok(ServerInstaller::vanilla().game_choice(choice_or_pick::<Self>(choice, "VERSION")?))
+    }
 
-        Ok(version)
+    fn has_all_versions(&self) -> bool {
+        self.latest_stable || self.latest_snapshot || self.version.is_some()
     }
 }
 
@@ -66,40 +512,37 @@ pub struct FabricVersionArgs {
     #[arg(long,action = ArgAction::SetTrue,default_value_t = false)]
     pub latest_stable: bool,
 
-    /// Minecraft game version
-    #[arg(required_unless_present = "latest_stable")]
+    /// Minecraft game version. Picked interactively if not given
     pub game_version: Option<String>,
 
-    /// Fabric loader version
-    #[arg(required_unless_present = "latest_stable")]
+    /// Fabric loader version. Picked interactively if not given
     pub loader_version: Option<String>,
 
-    /// Fabric installer version
-    #[arg(required_unless_present = "latest_stable")]
+    /// Fabric installer version. Picked interactively if not given
     pub installer_version: Option<String>,
 }
 
-#[async_trait]
 impl Versions for FabricVersionArgs {
-    type V = (String, String, String);
-    async fn versions(&self, client: &Client) -> anyhow::Result<Self::V> {
-        let versions = if self.latest_stable {
-            let (game_version, loader_version, installer_version) =
-                fabric_meta::fetch_latest_stable_versions(client).await?;
-            (
-                self.game_version.clone().unwrap_or(game_version),
-                self.loader_version.clone().unwrap_or(loader_version),
-                self.installer_version.clone().unwrap_or(installer_version),
-            )
-        } else {
-            (
-                self.game_version.clone().unwrap(),
-                self.loader_version.clone().unwrap(),
-                self.installer_version.clone().unwrap(),
-            )
+    fn installer(&self) -> anyhow::Result<ServerInstaller> {
+        let choice = |version: &Option<String>, arg_name| {
+            let choice = match version {
+                Some(version) => Some(VersionChoice::Exact(version.clone())),
+                None if self.latest_stable => Some(VersionChoice::LatestStable),
+                None => None,
+            };
+            choice_or_pick::<Self>(choice, arg_name)
         };
+        Ok(ServerInstaller::fabric()
+            .game_choice(choice(&self.game_version, "GAME_VERSION")?)
+            .loader_choice(choice(&self.loader_version, "LOADER_VERSION")?)
+            .installer_choice(choice(&self.installer_version, "INSTALLER_VERSION")?))
+    }
 
-        Ok(versions)
+    fn has_all_versions(&self) -> bool {
+        self.latest_stable
+            || (self.game_version.is_some()
+                && self.loader_version.is_some()
+                && self.installer_version.is_some())
     }
 }
 
@@ -110,22 +553,23 @@ pub struct ForgeVersionArgs {
     #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "version")]
     pub latest: bool,
 
-    /// Forge installer version. For example: `1.21.8-58.1.1`.
-    #[arg(required_unless_present = "latest", conflicts_with = "latest")]
+    /// Forge installer version. For example: `1.21.8-58.1.1`. Picked interactively if not given
+    #[arg(conflicts_with = "latest")]
     pub version: Option<String>,
 }
 
-#[async_trait]
 impl Versions for ForgeVersionArgs {
-    type V = String;
-    async fn versions(&self, client: &Client) -> anyhow::Result<Self::V> {
-        let version = if self.latest {
-            forge_meta::fetch_latest_version(client).await?
+    fn installer(&self) -> anyhow::Result<ServerInstaller> {
+        let choice = if self.latest {
+            Some(VersionChoice::LatestStable)
         } else {
-            self.version.clone().unwrap()
+            self.version.clone().map(VersionChoice::Exact)
         };
+        Ok(ServerInstaller::forge().loader_choice(choice_or_pick::<Self>(choice, "VERSION")?))
+    }
 
-        Ok(version)
+    fn has_all_versions(&self) -> bool {
+        self.latest || self.version.is_some()
     }
 }
 
@@ -134,19 +578,167 @@ impl Versions for ForgeVersionArgs {
 #[command(about = "A Minecraft server instance manager.")]
 #[command(version)]
 pub struct Cli {
+    /// Never prompt. Commands that would prompt fail or use their defaults instead
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub non_interactive: bool,
+
+    /// Answer yes to the prompts, like accepting the EULA or updating mods. Resetting a world
+    /// and changing a shared mods directory still ask, they have their own flags
+    #[arg(short, long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub yes: bool,
+
+    /// Detect the server jar's fork and game version again instead of using the ones cached
+    /// in the config
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub no_cache: bool,
+
+    /// Wait for another mcerv operation changing the same server to finish instead of failing
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub wait: bool,
+
+    /// Change the mods through a symlinked mods directory without asking, even though other
+    /// servers may share it
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub follow_symlink: bool,
+
+    /// A PEM file of CA certificates to trust, like the one of a proxy intercepting TLS.
+    /// Proxies are taken from HTTPS_PROXY, HTTP_PROXY, ALL_PROXY and NO_PROXY
+    #[arg(long, global = true, value_name = "PATH")]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Give up on API requests that take longer than this many seconds. Downloads only
+    /// time out when they stall
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+
+    /// Limit all downloads together to this many bytes per second, like 500K, 2M or 1.5M
+    #[arg(long, global = true, value_name = "RATE")]
+    pub limit_rate: Option<Rate>,
+
+    /// Download from the original URLs even if the global config has mirrors for them
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub no_mirror: bool,
+
+    /// Report the progress of installs and downloads as lines of JSON on stderr, for
+    /// programs wrapping mcerv
+    #[arg(long, global = true, action = ArgAction::SetTrue, default_value_t = false)]
+    pub progress_json: bool,
+
+    /// Keep the servers in `<PATH>/instances`, overriding MCERV_DATA_DIR and the global config
+    #[arg(long, global = true, value_name = "PATH")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Without one, the setup wizard is offered if no server is installed yet
     #[command(subcommand)]
-    pub command: Command,
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// The client the command sends all of its requests with.
+    /// The global config's network settings are overridden by the flags. Also sets up its
+    /// mirrors.
+    pub fn client(&self) -> anyhow::Result<Client> {
+        let config = self.global_config()?;
+        if !self.no_mirror {
+            network::mirror::set_mirrors(config.mirrors);
+        }
+
+        let token = std::env::var(modrinth::TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(ModrinthToken::new)
+            .or(config.modrinth.token);
+        modrinth::set_token(token.as_ref())?;
+
+        let mut settings = config.network;
+        if let Some(ca_bundle) = &self.ca_bundle {
+            settings.ca_bundle = Some(ca_bundle.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            settings.request_timeout = timeout;
+        }
+        if let Some(limit_rate) = self.limit_rate {
+            settings.limit_rate = Some(limit_rate);
+        }
+
+        network::build_client(&settings)
+    }
+
+    /// Points [`data_dir`] at the directory of `--data-dir`, MCERV_DATA_DIR or the global
+    /// config.
+    pub fn apply_data_dir(&self) -> anyhow::Result<()> {
+        let config = self.global_config()?;
+        set_data_dir(resolve_data_dir(
+            self.data_dir.clone(),
+            std::env::var_os(DATA_DIR_VAR),
+            config.data_dir,
+        ));
+        Ok(())
+    }
+
+    /// The global config, or the defaults for `mcerv doctor` if it's invalid, so the doctor
+    /// gets to report what's wrong with it.
+    fn global_config(&self) -> anyhow::Result<GlobalConfig> {
+        match GlobalConfig::load(&global_config_path()) {
+            Err(_) if matches!(self.command, Some(Command::Doctor { .. })) => {
+                Ok(GlobalConfig::default())
+            }
+            result => result,
+        }
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Command {
+    /// Set up a new server by answering a few questions
+    Init,
     /// List the installed servers
-    LsServers,
+    LsServers {
+        /// Also show each server's fork, version, creation and last start time, tags and note
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        detailed: bool,
+        /// Only list the servers with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list the servers of this fork, like `fabric`
+        #[arg(long)]
+        fork: Option<ServerFork>,
+    },
     /// List the mods in the target server and check for updates
     LsMods {
-        server_name: String,
-        #[command(flatten)]
-        want_update: YesArgs,
+        #[arg(required_unless_present = "all")]
+        server_name: Option<ServerName>,
+        /// Check the mods of every server, sharing downloads between them
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "server_name")]
+        all: bool,
+        /// With `--all`, only check the servers with this tag
+        #[arg(long, requires = "all")]
+        tag: Option<String>,
+        /// Also show each jar's file name, size, SHA1 and Modrinth version ID
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        detailed: bool,
+        /// How the mods are sorted within the sections
+        #[arg(long, value_enum, default_value_t = ModSort::Name)]
+        sort: ModSort,
+        /// Only show, and offer to update, the mods with an update whose installed version
+        /// mcerv installed at least this many days ago
+        #[arg(long, value_name = "DAYS")]
+        stale: Option<u64>,
+        /// Post the available updates to the webhooks of the global config
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        notify: bool,
+    },
+    /// Keep a mod's updates within a version range, like `~0.100` for 0.100.x
+    PinMod {
+        server_name: ServerName,
+        /// The project slug, like `fabric-api`
+        slug: String,
+        /// A semver range, matched against the Modrinth version numbers
+        #[arg(long, required_unless_present = "remove")]
+        constraint: Option<String>,
+        /// Unpin the mod, so it updates to the latest version again
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "constraint")]
+        remove: bool,
     },
     /// Get the versions of the mod
     FetchModVersions {
@@ -154,130 +746,684 @@ pub enum Command {
         /// List only featured versions
         #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
         featured: bool,
+        /// The number of versions to show, newest first
+        #[arg(long, default_value_t = 15)]
+        limit: usize,
+        /// Also link each version's Modrinth page
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        urls: bool,
     },
     /// List availible versions for the target Minecraft server fork
     Fetch {
         #[command(subcommand)]
         command: FetchCommand,
+        /// The number of versions to show, newest first. Ignored when `--all` is set
+        #[arg(long, global = true, default_value_t = 30)]
+        limit: usize,
     },
     /// Search for a mod with the given name
     SearchMod {
         name: String,
-        /// Example: `open_source`, `license:mit`.
-        ///
-        /// See https://docs.modrinth.com/api/operations/searchprojects for details.
-        ///
-        /// Note: `mcerv` automatically adds `server_side:required` & `server_side:optional`.
-        #[arg(long, num_args = 0..)]
-        facets: Vec<String>,
+        #[command(flatten)]
+        facets: FacetArgs,
+        /// Also show mods that are not supported on the server side
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        client_ok: bool,
         /// The sorting method used for sorting search results
         #[arg(long)]
         index: Option<SearchIndex>,
         /// The number of results returned by the search
         #[arg(long)]
         limit: Option<usize>,
+        /// Also show each result's Modrinth page and icon
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        urls: bool,
+    },
+    /// Open the Modrinth page of a project in the browser, or print its link without one
+    Open {
+        /// The project slug, or with `--server`, the name of an installed mod
+        name: String,
+        /// Look the mod up among the mods installed in this server
+        #[arg(long)]
+        server: Option<ServerName>,
     },
-    /// Set the max/min memory, or JAVA_HOME of the target server
+    /// Set the max/min memory, JAVA_HOME or note of the target server
     Set {
-        server_name: String,
+        server_name: ServerName,
         #[arg(long)]
         max_memory: Option<String>,
         #[arg(long)]
         min_memory: Option<String>,
         #[arg(long)]
         java_home: Option<String>,
+        /// A note about the server, like what it's for. An empty note removes it
+        #[arg(long)]
+        note: Option<String>,
+        /// The command starting the server, with {java}, {xmx} and {xms} once each and
+        /// optionally {jvm_args}, {jar} and {game_args}. An empty template restores the default
+        #[arg(long, value_name = "TEMPLATE")]
+        start_template: Option<String>,
+        /// Keep the world at this absolute path, like on another filesystem, moving it there
+        /// and linking it from the server directory. An empty path moves it back
+        #[arg(long, value_name = "PATH")]
+        world_dir: Option<String>,
+        /// Only print how the world would be moved
+        #[arg(long, requires = "world_dir", action = ArgAction::SetTrue, default_value_t = false)]
+        dry_run: bool,
     },
     /// Install the server with the given versions
     Install {
         #[command(subcommand)]
         command: InstallCommand,
-        server_name: String,
-        #[command(flatten)]
-        accept_eula: YesArgs,
+        server_name: ServerName,
+        /// Launch the server once after installing, see `bootstrap`
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        bootstrap: bool,
+        /// Also download the game version's server mappings, see `mappings fetch`
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        with_mappings: bool,
+        /// Also install the newest versions of these Modrinth mods for the game version and
+        /// loader, by slug or ID, and the mods they require
+        #[arg(long, value_delimiter = ',', value_name = "SLUG")]
+        with_mods: Vec<String>,
+        /// Install the server in this directory instead of the instances directory
+        #[arg(long, value_name = "PATH")]
+        location: Option<PathBuf>,
+    },
+    /// Install the servers listed in a TOML manifest, a few at a time
+    InstallMany {
+        /// The manifest listing each server's name, fork, versions, port, memory and mods
+        #[arg(long)]
+        manifest: PathBuf,
     },
+    /// Launch the server once to generate server.properties, eula.txt and the default configs,
+    /// without generating the world
+    Bootstrap { server_name: ServerName },
     /// Install a mod to the target server
     InstallMod {
-        server_name: String,
+        server_name: ServerName,
         /// The mod version ID in the form of "IIJJKKLL"
         mod_id: String,
     },
     /// Generate a start script for the target server
-    GenStartScript { server_name: String },
+    GenStartScript { server_name: ServerName },
+    /// Export the server to run it elsewhere
+    Export {
+        #[command(subcommand)]
+        command: ExportCommand,
+    },
+    /// Generate files that run the server under a service manager
+    Generate {
+        #[command(subcommand)]
+        command: GenerateCommand,
+    },
     /// Replace the server jar with the specified version
     UpdateServerJar {
-        server_name: String,
+        server_name: ServerName,
+        /// Replace the jar even with an older game version than the world was last opened with
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        allow_downgrade: bool,
         /// Version arguments specific to the server fork
         #[arg(allow_hyphen_values = true, trailing_var_arg = true)]
         version_args: Vec<String>, // This will be parsed at runtime depending on the server fork
     },
+    /// Check whether the server jar's game and loader versions are the latest stable ones
+    CheckServer {
+        server_name: ServerName,
+        /// Replace the server jar with the latest versions if any is outdated
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        update: bool,
+    },
+    /// Inspect how the mods of a server depend on each other
+    Mods {
+        #[command(subcommand)]
+        command: ModsCommand,
+    },
+    /// Move a server to a machine without internet
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+    /// Check a server for common problems
+    Check {
+        #[command(subcommand)]
+        command: CheckCommand,
+    },
+    /// Compare the server jar and the mods with the hashes mcerv recorded, reporting modified,
+    /// missing and unexpected files
+    Verify {
+        server_name: ServerName,
+        /// Also compare the mods with the hashes Modrinth has for their recorded versions
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        online: bool,
+        /// Download the modified and missing mods again from their recorded versions
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        repair: bool,
+    },
+    /// Show which versions the server was installed and updated through, newest first
+    History {
+        server_name: ServerName,
+        /// The number of events to show
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Get the mappings deobfuscating the server's stack traces
+    Mappings {
+        #[command(subcommand)]
+        command: MappingsCommand,
+    },
+    /// Restore the jars the latest update of the server replaced, and the config's jar
+    Undo {
+        server_name: ServerName,
+        /// Delete everything in the server's trash instead
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        purge_trash: bool,
+    },
+    /// Roll back the latest mod update of the server recorded in its history
+    UndoMods { server_name: ServerName },
     /// Accept the EULA for the target server. This will create or modify the eula.txt file
-    AcceptEula { server_name: String },
+    AcceptEula { server_name: ServerName },
     /// Start the target server
-    Start,
+    Start {
+        server_name: ServerName,
+        /// Run the server in the background. Its console output goes to `logs/mcerv-console.log`
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        detach: bool,
+        #[command(flatten)]
+        supervision: SupervisionArgs,
+    },
+    /// Ask a server for its MOTD, player count and version like the multiplayer menu does
+    Ping {
+        /// The name of a managed server, or `host[:port]`, with IPv6 addresses like `[::1]:25565`
+        target: String,
+    },
+    /// Serve Prometheus metrics of every server at `/metrics`: whether it's up, memory, CPU,
+    /// players and world size
+    Metrics {
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1:9925")]
+        listen: std::net::SocketAddr,
+    },
+    /// Stop a server started by mcerv
+    Stop { server_name: ServerName },
+    /// Watch the console of a server started with `--detach` and send commands to it.
+    /// Press Ctrl-C to detach without stopping the server
+    Attach { server_name: ServerName },
+    /// Runs the server for `start --detach`, bridging `mcerv attach` to its console
+    #[command(hide = true)]
+    ConsoleWrapper {
+        server_name: ServerName,
+        #[command(flatten)]
+        supervision: SupervisionArgs,
+    },
+    /// Show whether the target server, or every server if none is given, is running
+    Status { server_name: Option<ServerName> },
+    /// Move the servers to `<NEW_PATH>/instances` and keep them there from now on
+    MigrateData { new_path: PathBuf },
+    /// Give a stopped server a new name, keeping it in the groups it's in
+    Rename {
+        server_name: ServerName,
+        new_name: ServerName,
+    },
+    /// List the leftovers in the server directory, like interrupted downloads and JVM crash
+    /// dumps, and how much space removing them frees
+    Clean {
+        server_name: ServerName,
+        /// Remove them
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        apply: bool,
+    },
+    /// Check the setup for common problems, failing if any check fails
+    Doctor {
+        /// Also check this server
+        server_name: Option<ServerName>,
+        /// Skip the checks that need the network
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        offline: bool,
+    },
+    /// List the crash reports of the target server, or summarize one of them
+    Crash {
+        server_name: ServerName,
+        /// Summarize the newest crash report
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false, conflicts_with = "list")]
+        latest: bool,
+        /// List the crash reports, newest first
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        list: bool,
+    },
+    /// Set up the resource pack the server sends to players
+    Resourcepack {
+        #[command(subcommand)]
+        command: ResourcepackCommand,
+    },
+    /// Inspect or reset the world of a server
+    World {
+        #[command(subcommand)]
+        command: WorldCommand,
+    },
+    /// Compare and sync server.properties between servers
+    Prop {
+        #[command(subcommand)]
+        command: PropCommand,
+    },
+    /// Manage the cache of downloaded mods
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Start and stop groups of servers together, saved in the global config
+    Group {
+        #[command(subcommand)]
+        command: GroupCommand,
+    },
+    /// Check the servers for mod and server jar updates periodically, reporting only the
+    /// updates that are new since the previous check
+    Watch {
+        /// How long to wait between checks, like `30m`, `6h` or `1d`
+        #[arg(long, default_value = "6h")]
+        interval: Interval,
+        /// Only check the servers with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Check once and exit, for cron jobs and systemd timers
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        once: bool,
+    },
+    /// Check the webhook notifications set up in the global config
+    Notify {
+        #[command(subcommand)]
+        command: NotifyCommand,
+    },
+    /// Check the Modrinth token set with MCERV_MODRINTH_TOKEN or the global config
+    Modrinth {
+        #[command(subcommand)]
+        command: ModrinthCommand,
+    },
+    /// Fix the mcerv config of a server
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Tag servers to filter listings and bulk operations with `--tag`
+    Tag {
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+    /// Save servers as templates and install new servers from them
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
     /// Show the info of the target server
-    Info { server_name: String },
+    Info {
+        server_name: ServerName,
+        /// Detect the server jar's fork and game version again and update the cached ones
+        #[arg(long, action = ArgAction::SetTrue, default_value_t = false)]
+        refresh: bool,
+    },
 }
 
 impl Command {
-    pub async fn run(self) -> anyhow::Result<()> {
+    pub async fn run(self, client: &Client) -> anyhow::Result<()> {
         match self {
-            Command::LsServers => list_servers(),
+            Command::Init => wizard::run(client).await?,
+            Command::LsServers {
+                detailed,
+                tag,
+                fork,
+            } => list_servers(detailed, tag.as_deref(), fork)?,
             Command::LsMods {
                 server_name,
-                want_update,
+                all: _,
+                tag,
+                detailed,
+                sort,
+                stale,
+                notify,
+            } => match server_name {
+                Some(server_name) => {
+                    list_mods(&server_name, detailed, sort, stale, notify, client).await?
+                }
+                // `--all`, clap requires it without a server name
+                None => {
+                    list_all_mods(detailed, sort, stale, tag.as_deref(), notify, client).await?
+                }
+            },
+            Command::PinMod {
+                server_name,
+                slug,
+                constraint,
+                remove: _,
+            } => pin_mod(&server_name, &slug, constraint.as_deref(), client).await?,
+            Command::FetchModVersions {
+                name,
+                featured,
+                limit,
+                urls,
             } => {
-                list_mods(&server_name, want_update.yes, &Client::new()).await?;
+                fetch_mod_versions(&name, featured, limit, urls, client).await?;
             }
-            Command::FetchModVersions { name, featured } => {
-                fetch_mod_versions(&name, featured, &Client::new()).await?;
-            }
-            Command::Fetch { command } => {
-                let s = match command {
-                    FetchCommand::Vanilla { filter } => {
-                        forks::Vanilla::fetch_availables(filter.all, &Client::new()).await?
-                    }
-                    FetchCommand::Fabric { filter } => {
-                        forks::Fabric::fetch_availables(filter.all, &Client::new()).await?
-                    }
-                    FetchCommand::Forge {} => {
-                        forks::Forge::fetch_availables((), &Client::new()).await?
+            Command::Fetch { command, limit } => {
+                let (versions, all) = match command {
+                    FetchCommand::Vanilla { filter } => (
+                        forks::Vanilla::fetch_availables(filter.all, client).await?,
+                        filter.all,
+                    ),
+                    FetchCommand::Fabric { filter } => (
+                        forks::Fabric::fetch_availables(filter.all, client).await?,
+                        filter.all,
+                    ),
+                    FetchCommand::Forge { filter } => {
+                        let all = filter.all;
+                        (forks::Forge::fetch_availables(filter, client).await?, all)
                     }
                 };
-                println!("{s}");
+                let limit = if all { None } else { Some(limit) };
+                println!("{}", network::versions_table(&versions, limit));
             }
             Command::SearchMod {
                 name,
                 facets,
+                client_ok,
                 index,
                 limit,
-            } => search_mod(&name, &facets, index, limit, &Client::new()).await?,
+                urls,
+            } => {
+                search_mod(
+                    &name,
+                    &facets.groups()?,
+                    client_ok,
+                    index,
+                    limit,
+                    urls,
+                    client,
+                )
+                .await?
+            }
+            Command::Open { name, server } => {
+                open_project(&name, server.as_deref(), client).await?
+            }
             Command::Set {
                 server_name,
                 max_memory,
                 min_memory,
                 java_home,
-            } => set_config(&server_name, max_memory, min_memory, java_home)?,
+                note,
+                start_template,
+                world_dir,
+                dry_run,
+            } => {
+                set_config(
+                    &server_name,
+                    max_memory,
+                    min_memory,
+                    java_home,
+                    note,
+                    start_template,
+                )?;
+                if let Some(world_dir) = world_dir {
+                    set_world_dir(&server_name, &world_dir, dry_run)?;
+                }
+            }
+            Command::InstallMany { manifest } => install_many(&manifest, client).await?,
             Command::Install {
                 command,
                 server_name,
-                accept_eula,
-            } => install(command, &server_name, accept_eula.yes, &Client::new()).await?,
+                bootstrap,
+                with_mappings,
+                with_mods,
+                location,
+            } => {
+                let mut installer =
+                    api::ForkInstaller::from_installer(command.installer()?, &server_name)
+                        .bootstrap(bootstrap)
+                        .with_mappings(with_mappings)
+                        .with_mods(with_mods);
+                if let Some(location) = location {
+                    installer = installer.location(location);
+                }
+                installer.install(client).await?;
+            }
+            Command::Bootstrap { server_name } => bootstrap_server(&server_name).await?,
             Command::InstallMod {
                 server_name,
                 mod_id,
-            } => install_mod(&server_name, &mod_id, &Client::new()).await?,
+            } => {
+                api::InstanceManager::new()
+                    .get(&server_name)?
+                    .mods()
+                    .install(&mod_id, client)
+                    .await?
+            }
             Command::GenStartScript { server_name } => generate_start_script(&server_name)?,
+            Command::Export { command } => match command {
+                ExportCommand::Docker {
+                    server_name,
+                    output,
+                } => export_docker(&server_name, output.as_deref())?,
+            },
+            Command::Generate { command } => match command {
+                GenerateCommand::SystemdUnit {
+                    server_name,
+                    user,
+                    install,
+                    supervision,
+                } => generate_systemd_unit(&server_name, user, install, &supervision)?,
+            },
             Command::UpdateServerJar {
                 server_name,
+                allow_downgrade,
                 version_args,
             } => {
-                update_server_jar(&version_args, &server_name, &Client::new()).await?;
+                update_server_jar(&version_args, &server_name, allow_downgrade, client).await?;
             }
-            Command::AcceptEula { server_name } => generate_eula_accept_file(&server_name)?,
-            Command::Start => todo!(),
-            Command::Info { server_name } => show_server_info(&server_name)?,
+            Command::CheckServer {
+                server_name,
+                update,
+            } => check_server(&server_name, update, client).await?,
+            Command::Mods { command } => match command {
+                ModsCommand::Graph {
+                    server_name,
+                    dot,
+                    dependents,
+                } => print_mod_graph(&server_name, dot, dependents.as_deref(), client).await?,
+                ModsCommand::Prune { server_name } => prune_mods(&server_name, client).await?,
+            },
+            Command::Bundle { command } => match command {
+                BundleCommand::Create {
+                    server_name,
+                    output,
+                } => create_bundle(&server_name, &output)?,
+                BundleCommand::Apply {
+                    bundle,
+                    server_name,
+                } => apply_bundle(&bundle, &server_name)?,
+            },
+            Command::Check { command } => match command {
+                CheckCommand::ClientOnly { server_name } => {
+                    check_client_only(&server_name, client).await?
+                }
+                CheckCommand::ModsSupport {
+                    server_name,
+                    game_version,
+                } => check_mods_support(&server_name, &game_version, client).await?,
+            },
+            Command::Verify {
+                server_name,
+                online,
+                repair,
+            } => verify_server(&server_name, online, repair, client).await?,
+            Command::History { server_name, limit } => show_history(&server_name, limit)?,
+            Command::Mappings { command } => match command {
+                MappingsCommand::Fetch { server_name } => {
+                    fetch_mappings(&server_name, client).await?
+                }
+            },
+            Command::Undo {
+                server_name,
+                purge_trash,
+            } => undo(&server_name, purge_trash)?,
+            Command::UndoMods { server_name } => undo_mods(&server_name, client).await?,
+            Command::AcceptEula { server_name } => accept_eula(&server_name)?,
+            Command::Start {
+                server_name,
+                detach,
+                supervision,
+            } => start_server(&server_name, detach, &supervision).await?,
+            Command::Stop { server_name } => stop_server(&server_name).await?,
+            Command::Attach { server_name } => attach_server(&server_name).await?,
+            Command::ConsoleWrapper {
+                server_name,
+                supervision,
+            } => run_console_wrapper(&server_name, &supervision).await?,
+            Command::Status { server_name } => show_status(server_name.as_deref()).await?,
+            Command::MigrateData { new_path } => migrate_data(&new_path)?,
+            Command::Rename {
+                server_name,
+                new_name,
+            } => rename_server(&server_name, &new_name)?,
+            Command::Clean { server_name, apply } => clean_server(&server_name, apply)?,
+            Command::Doctor {
+                server_name,
+                offline,
+            } => doctor(server_name.as_deref(), offline, client).await?,
+            Command::Ping { target } => ping_server(&target).await?,
+            Command::Metrics { listen } => serve_metrics(listen).await?,
+            Command::Crash {
+                server_name,
+                latest,
+                list,
+            } => show_crash_report(&server_name, latest, list)?,
+            Command::Resourcepack { command } => match command {
+                ResourcepackCommand::Set {
+                    server_name,
+                    source,
+                    url,
+                    copy,
+                    require,
+                } => set_resource_pack(&server_name, &source, url, copy, require, client).await?,
+                ResourcepackCommand::Clear { server_name } => clear_resource_pack(&server_name)?,
+            },
+            Command::World { command } => match command {
+                WorldCommand::Info { server_name } => show_world_info(&server_name)?,
+                WorldCommand::Reset {
+                    server_name,
+                    seed,
+                    confirm,
+                } => reset_world(&server_name, seed, confirm.as_deref())?,
+            },
+            Command::Prop { command } => match command {
+                PropCommand::Diff { server_a, server_b } => diff_properties(&server_a, &server_b)?,
+                PropCommand::Sync {
+                    from,
+                    to,
+                    except,
+                    dry_run,
+                } => sync_properties(&from, &to, &except, dry_run)?,
+            },
+            Command::Cache { command } => match command {
+                CacheCommand::Stats => show_cache_stats()?,
+                CacheCommand::Prune { max_size } => prune_cache(max_size)?,
+            },
+            Command::Group { command } => match command {
+                GroupCommand::Create {
+                    group_name,
+                    server_names,
+                    delay,
+                } => create_group(&group_name, &server_names, delay)?,
+                GroupCommand::Start {
+                    group_name,
+                    fail_fast,
+                } => start_group(&group_name, fail_fast).await?,
+                GroupCommand::Stop {
+                    group_name,
+                    fail_fast,
+                } => stop_group(&group_name, fail_fast).await?,
+                GroupCommand::Status { group_name } => show_group_status(&group_name)?,
+            },
+            Command::Watch {
+                interval,
+                tag,
+                once,
+            } => watch_updates(interval.0, tag.as_deref(), once, client).await?,
+            Command::Notify { command } => match command {
+                NotifyCommand::Test => test_notifications(client).await?,
+            },
+            Command::Modrinth { command } => match command {
+                ModrinthCommand::Whoami => modrinth_whoami(client).await?,
+            },
+            Command::Config { command } => match command {
+                ConfigCommand::Repair { server_name } => repair_config(&server_name)?,
+            },
+            Command::Tag { command } => match command {
+                TagCommand::Add { server_name, tag } => add_tag(&server_name, &tag)?,
+                TagCommand::Remove { server_name, tag } => remove_tag(&server_name, &tag)?,
+            },
+            Command::Template { command } => match command {
+                TemplateCommand::Save {
+                    server_name,
+                    template_name,
+                    latest_mods,
+                } => save_template(&server_name, &template_name, latest_mods, client).await?,
+                TemplateCommand::Apply {
+                    template_name,
+                    server_name,
+                } => apply_template(&template_name, &server_name, client).await?,
+                TemplateCommand::List => list_templates()?,
+                TemplateCommand::Delete { template_name } => delete_template(&template_name)?,
+            },
+            Command::Info {
+                server_name,
+                refresh,
+            } => show_server_info(&server_name, refresh)?,
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cache_size() {
+        assert_eq!(parse_cache_size("2.5"), Ok(2.5));
+        assert_eq!(parse_cache_size("0"), Ok(0.0));
+        assert!(parse_cache_size("-1").is_err());
+        assert!(parse_cache_size("NaN").is_err());
+        assert!(parse_cache_size("big").is_err());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("0.16.14", "0.16.14"), 0);
+        assert_eq!(edit_distance("0.16.99", "0.16.14"), 2);
+        assert_eq!(edit_distance("1.21.8", "1.21"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_ensure_version_exists() {
+        let loaders = ["0.17.2", "0.16.14", "0.16.13", "0.15.11"];
+
+        assert!(ensure_version_exists("loader version", "0.16.14", &loaders).is_ok());
+
+        let err = ensure_version_exists("loader version", "0.16.99", &loaders).unwrap_err();
+        assert_eq!(&err.closest_matches[..2], ["0.16.14", "0.16.13"]);
+        assert!(
+            err.to_string()
+                .starts_with("loader version 0.16.99 not found; closest matches: 0.16.14, 0.16.13")
+        );
+    }
+
+    #[test]
+    fn test_closest_matches_misspellings() {
+        let games = ["1.21.8", "1.21.7", "1.20.4", "25w31a"];
+
+        assert_eq!(closest_matches("1.21.9", &games, 1), ["1.21.8"]);
+        assert_eq!(closest_matches("1.2.18", &games, 1), ["1.21.8"]);
+        assert_eq!(closest_matches("1.20,4", &games, 1), ["1.20.4"]);
+        assert_eq!(closest_matches("25w3a", &games, 1), ["25w31a"]);
+    }
+}
@@ -1,11 +1,24 @@
 use crate::{
-    system::jar_parser::{InvalidServerDirError, single_jar},
+    system::{
+        forks::{self, DetectServerInfoError, ServerFork},
+        jar_parser::{InvalidServerDirError, jar_name, single_jar},
+        prompt::{self, Danger},
+        server_info::{JarDetection, ServerInfo},
+        start_template::{StartTemplate, StartValues},
+    },
     try_server_dir,
 };
+use chrono::{DateTime, SecondsFormat};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{
+    env,
+    error::Error,
     fmt::Display,
     fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -14,6 +27,201 @@ pub struct Config {
     pub max_memory: String,
     pub jar_name: String,
     pub java_home: Option<String>,
+    /// Also tells the fork of the old jar when it was manually replaced.
+    #[serde(flatten)]
+    pub detection: JarDetection,
+    /// RFC 3339, `None` for servers set up before it was recorded.
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// RFC 3339, set by `mcerv start`.
+    #[serde(default)]
+    pub last_started_at: Option<String>,
+    /// What the server is for, as the user wrote it.
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Normalized with [`normalize_tag`].
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How the server is started instead of the usual `-jar`, set by `mcerv set --start-template`.
+    #[serde(default)]
+    pub start_command_template: Option<StartTemplate>,
+    /// Filled in for `{jvm_args}` in the start template.
+    #[serde(default)]
+    pub jvm_args: Vec<String>,
+    /// Filled in for `{game_args}` in the start template.
+    #[serde(default)]
+    pub game_args: Vec<String>,
+    /// Where the world is kept outside the server directory, set by `mcerv set --world-dir`.
+    /// The server directory links to it.
+    #[serde(default)]
+    pub world_dir: Option<PathBuf>,
+}
+
+/// The top-level fields `config repair` always regenerates from the jar.
+const REGENERATED_FIELDS: [&str; 6] = [
+    "jar_name",
+    "detected_fork",
+    "detected_game_version",
+    "jar_sha1",
+    "jar_size",
+    "jar_modified",
+];
+/// How much of a long line a [`ConfigParseError`] shows around the column.
+const EXCERPT_WIDTH: usize = 60;
+
+/// A config that isn't valid JSON or isn't a valid config, like after a broken hand edit.
+#[derive(Debug)]
+pub struct ConfigParseError {
+    pub server_name: String,
+    pub path: PathBuf,
+    pub message: String,
+    /// Where it failed, both 1-based. `None` if serde_json doesn't tell.
+    pub position: Option<(usize, usize)>,
+    /// The line it failed on.
+    pub excerpt: Option<String>,
+}
+
+impl ConfigParseError {
+    fn new(server_name: &str, path: &Path, content: &str, error: serde_json::Error) -> Self {
+        let (line, column) = (error.line(), error.column());
+        let message = error.to_string();
+        let message = message
+            .strip_suffix(&format!(" at line {line} column {column}"))
+            .unwrap_or(&message)
+            .to_string();
+        let position = (line > 0).then_some((line, column.max(1)));
+        Self {
+            server_name: server_name.to_string(),
+            path: path.to_path_buf(),
+            message,
+            position,
+            excerpt: position
+                .and_then(|(line, _)| content.lines().nth(line - 1))
+                .map(str::to_string),
+        }
+    }
+}
+
+impl Display for ConfigParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to parse {}", self.path.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, " at line {line}, column {column}")?;
+        }
+        write!(f, ": {}", self.message)?;
+
+        if let (Some(excerpt), Some((line, column))) = (&self.excerpt, self.position) {
+            // Cut around the column, which serde_json counts in bytes
+            let column = excerpt
+                .char_indices()
+                .take_while(|(i, _)| *i < column)
+                .count();
+            let start = column.saturating_sub(EXCERPT_WIDTH / 2);
+            let excerpt: String = excerpt.chars().skip(start).take(EXCERPT_WIDTH).collect();
+            let gutter = line.to_string();
+            write!(
+                f,
+                "\n {gutter} | {excerpt}\n {} | {}^",
+                " ".repeat(gutter.len()),
+                " ".repeat(column.saturating_sub(start + 1))
+            )?;
+        }
+
+        write!(
+            f,
+            "\nRun `mcerv config repair {}` to regenerate it, keeping the fields that still parse",
+            self.server_name
+        )
+    }
+}
+
+impl Error for ConfigParseError {}
+
+/// What `config repair` kept of the old config.
+#[derive(Debug, PartialEq)]
+pub struct ConfigRepair {
+    /// The fields that parsed and were kept, by name.
+    pub preserved: Vec<String>,
+    /// Where the old config was moved, `None` if there was none.
+    pub backup: Option<PathBuf>,
+}
+
+/// The longest tag [`normalize_tag`] accepts.
+const MAX_TAG_LENGTH: usize = 32;
+
+#[derive(Debug)]
+pub struct InvalidTagError(pub String);
+
+impl Display for InvalidTagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid tag '{}': tags are 1 to {MAX_TAG_LENGTH} letters, digits, '-' or '_'",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidTagError {}
+
+/// Lowercases the tag, so `SMP` and `smp` are the same tag.
+pub fn normalize_tag(tag: &str) -> Result<String, InvalidTagError> {
+    let tag = tag.trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LENGTH
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(InvalidTagError(tag));
+    }
+
+    Ok(tag)
+}
+
+/// The top-level fields of the config that parse on their own, even if the whole file
+/// doesn't. Fields are found line by line, like in the pretty-printed configs mcerv writes.
+fn salvage_fields(content: &str) -> serde_json::Map<String, Value> {
+    if let Ok(Value::Object(fields)) = serde_json::from_str(content) {
+        return fields;
+    }
+
+    let mut fields = serde_json::Map::new();
+    // The lines of the field being read, which may span several like an array
+    let mut pending = String::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('"') && line.contains("\":") {
+            pending.clear();
+        }
+        pending.push_str(line);
+
+        let field = pending.trim_end_matches(',');
+        if let Ok(Value::Object(field)) = serde_json::from_str(&format!("{{{field}}}")) {
+            fields.extend(field);
+            pending.clear();
+        }
+    }
+    fields
+}
+
+/// What to do with the only jar in the server directory when it isn't the configured one.
+enum JarChange {
+    /// Not a server jar mcerv recognizes, like a leftover installer.
+    Invalid(anyhow::Error),
+    Adopt(ServerInfo),
+    /// A server jar of another fork than the old one, only adopted once confirmed.
+    ForkChanged(ServerInfo),
+}
+
+fn check_jar_change(old_fork: Option<ServerFork>, new_jar: &Path) -> JarChange {
+    match ServerInfo::new(new_jar) {
+        Ok(info) if old_fork.is_some_and(|fork| fork != info.server_fork) => {
+            JarChange::ForkChanged(info)
+        }
+        Ok(info) => JarChange::Adopt(info),
+        Err(e) => JarChange::Invalid(e),
+    }
 }
 
 impl Config {
@@ -24,15 +232,134 @@ impl Config {
             max_memory: "4G".to_string(),
             jar_name,
             java_home: None,
+            detection: JarDetection::default(),
+            created_at: Some(now()),
+            last_started_at: None,
+            note: None,
+            tags: Vec::new(),
+            start_command_template: None,
+            jvm_args: Vec::new(),
+            game_args: Vec::new(),
+            world_dir: None,
         })
     }
 
+    /// Loads the config without creating it, or `None` if the server doesn't have one yet.
+    pub fn load(server_name: &str) -> anyhow::Result<Option<Config>> {
+        let path = try_server_dir(server_name)?.join("mcerv_config.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        Ok(Some(Self::parse(server_name, &path, &content)?))
+    }
+
+    fn parse(server_name: &str, path: &Path, content: &str) -> Result<Config, ConfigParseError> {
+        serde_json::from_str(content)
+            .map_err(|e| ConfigParseError::new(server_name, path, content, e))
+    }
+
+    /// Regenerates the config of the server from its jar, keeping the fields of the old
+    /// config that still parse. The old config is moved next to it with a `.broken`
+    /// extension.
+    pub fn repair(server_name: &str) -> anyhow::Result<(Config, ConfigRepair)> {
+        Self::repair_dir(&try_server_dir(server_name)?)
+    }
+
+    fn repair_dir(server_dir: &Path) -> anyhow::Result<(Config, ConfigRepair)> {
+        let path = server_dir.join("mcerv_config.json");
+        let fields = match fs::read_to_string(&path) {
+            Ok(content) => salvage_fields(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => serde_json::Map::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // The old jar name is kept if the jar is still there
+        let jar = match fields.get("jar_name").and_then(Value::as_str) {
+            Some(jar_name) if server_dir.join(jar_name).is_file() => server_dir.join(jar_name),
+            _ => single_jar(server_dir)?,
+        };
+        let mut config = Self::new_4gb(jar_name(server_dir, &jar))?;
+        config.start_command_template = forks::forge_start_template(server_dir, &config.jar_name);
+        if let Ok((_, detection)) = JarDetection::detect(&jar) {
+            config.detection = detection;
+        }
+
+        // Kept one at a time, so a field of the wrong type only drops itself
+        let mut value = serde_json::to_value(&config)?;
+        let mut preserved = Vec::new();
+        for (key, field) in fields {
+            if REGENERATED_FIELDS.contains(&key.as_str()) || value.get(&key).is_none() {
+                continue;
+            }
+            let mut candidate = value.clone();
+            candidate[&key] = field;
+            if serde_json::from_value::<Config>(candidate.clone()).is_ok() {
+                value = candidate;
+                preserved.push(key);
+            }
+        }
+        let config: Config = serde_json::from_value(value)?;
+
+        let backup = if path.exists() {
+            let backup = path.with_extension("json.broken");
+            fs::rename(&path, &backup)?;
+            Some(backup)
+        } else {
+            None
+        };
+        config.save_to(&path)?;
+
+        Ok((config, ConfigRepair { preserved, backup }))
+    }
+
+    /// Starts the server with another jar. The start template follows its Forge layout,
+    /// unless it's one of the user's own.
+    pub fn set_jar(&mut self, server_dir: &Path, jar_name: String) {
+        if self
+            .start_command_template
+            .as_ref()
+            .is_none_or(forks::is_forge_start_template)
+        {
+            self.start_command_template = forks::forge_start_template(server_dir, &jar_name);
+        }
+        self.jar_name = jar_name;
+    }
+
+    /// Adds the normalized tag, returning whether the server didn't have it yet.
+    pub fn add_tag(&mut self, tag: &str) -> Result<bool, InvalidTagError> {
+        let tag = normalize_tag(tag)?;
+        if self.tags.contains(&tag) {
+            return Ok(false);
+        }
+
+        self.tags.push(tag);
+        self.tags.sort();
+        Ok(true)
+    }
+
+    /// Removes the tag, returning whether the server had it.
+    pub fn remove_tag(&mut self, tag: &str) -> Result<bool, InvalidTagError> {
+        let tag = normalize_tag(tag)?;
+        let len = self.tags.len();
+        self.tags.retain(|t| *t != tag);
+        Ok(self.tags.len() != len)
+    }
+
+    /// Records that the server is starting now.
+    pub fn mark_started(&mut self) {
+        self.last_started_at = Some(now());
+    }
+
     /// Loads the configuration from the server directory.
     /// If the config file does not exist, creates a new one with default values.
     ///
     /// Behavior:
-    /// - If exactly one jar is found in the server directory, its name is stored in the config.
-    ///   This allows automatic updates if the user manually replaces the jar file.
+    /// - If exactly one jar is found in the server directory and it's a server jar, its name
+    ///   is stored in the config. This allows automatic updates if the user manually replaces
+    ///   the jar file. A jar of another fork than the old one is only stored once confirmed,
+    ///   which `--yes` does.
     /// - If multiple jars are found, the config keeps the previously set jar name.
     ///   If the config is being created for the first time and multiple jars exist, an error is returned.
     pub fn load_or_create(server_name: &str) -> anyhow::Result<Config> {
@@ -41,106 +368,426 @@ impl Config {
 
         if !path.exists() {
             println!("mcerv config file does not exist, creating a new one with default values...");
-            let jar_name = single_jar(&server_dir)?
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .to_string();
-            let config = Self::new_4gb(jar_name)?;
+            let jar = single_jar(&server_dir)?;
+            let mut config = Self::new_4gb(jar_name(&server_dir, &jar))?;
+            config.start_command_template =
+                forks::forge_start_template(&server_dir, &config.jar_name);
+            if let Ok((_, detection)) = JarDetection::detect(&jar) {
+                config.detection = detection;
+            }
             config.save(server_name)?;
             return Ok(config);
         }
 
-        let content = fs::read_to_string(path)?;
-        let mut config: Config = serde_json::from_str(&content)?;
+        let content = fs::read_to_string(&path)?;
+        let mut config = Self::parse(server_name, &path, &content)?;
 
         // If single jar replaced, update config
-        match single_jar(server_dir) {
+        match single_jar(&server_dir) {
             Ok(new_jar) => {
-                let new_jar_name = new_jar.file_name().unwrap().to_string_lossy();
-                let old_jar_name = &config.jar_name;
-                if old_jar_name != &new_jar_name {
-                    println!(
-                        "Detected jar file change: {old_jar_name} -> {new_jar_name}, updating config..."
-                    );
-                    config.jar_name = new_jar_name.to_string();
+                if config.jar_name != jar_name(&server_dir, &new_jar) {
+                    config.adopt_jar(server_name, &new_jar)?;
+                } else if config.detection.detected_fork.is_none()
+                    && let Ok((_, detection)) = JarDetection::detect(&new_jar)
+                {
+                    config.detection = detection;
                     config.save(server_name)?;
                 }
             }
-            Err(e) => {
+            Err(e) => match e.downcast_ref::<InvalidServerDirError>() {
                 // Multiple jars is fine, just keep the old config
-                if !matches!(
-                    e.downcast_ref::<InvalidServerDirError>(),
-                    Some(InvalidServerDirError::MultipleJars)
-                ) {
-                    return Err(e);
+                Some(InvalidServerDirError::MultipleJars) => {}
+                // Only non-server jars like a leftover installer
+                Some(InvalidServerDirError::NoJar)
+                    if !server_dir.join(&config.jar_name).exists() =>
+                {
+                    println!(
+                        "Warning: {} is missing and no valid server jar was found, keeping the config.",
+                        config.jar_name
+                    );
                 }
-            }
+                _ => return Err(e),
+            },
         }
 
         Ok(config)
     }
 
+    /// Stores the jar that replaced the configured one, if it's a server jar
+    /// and, when it's of another fork, the user agrees.
+    fn adopt_jar(&mut self, server_name: &str, new_jar: &Path) -> anyhow::Result<()> {
+        let server_dir = try_server_dir(server_name)?;
+        let old_jar_name = &self.jar_name;
+        let new_jar_name = jar_name(&server_dir, new_jar);
+
+        let info = match check_jar_change(self.detection.detected_fork, new_jar) {
+            JarChange::Invalid(e) => {
+                let reason = match e.downcast_ref::<DetectServerInfoError>() {
+                    Some(DetectServerInfoError::InstallerJar) => "is an installer".to_string(),
+                    _ => format!("is not a server jar ({e})"),
+                };
+                println!(
+                    "Warning: {old_jar_name} is missing and {new_jar_name} {reason}. No valid server jar was found, keeping the config."
+                );
+                return Ok(());
+            }
+            JarChange::Adopt(info) => info,
+            JarChange::ForkChanged(info) => {
+                println!(
+                    "{old_jar_name} was a {:?} server, but {new_jar_name} is a {:?} {} server.",
+                    self.detection.detected_fork.unwrap(),
+                    info.server_fork,
+                    info.game_version
+                );
+                let confirmed = prompt::confirm(
+                    Danger::Destructive,
+                    &format!("Do you want to use {new_jar_name}?"),
+                )?;
+                if !confirmed {
+                    println!(
+                        "Keeping the config. Start the server with --yes to use {new_jar_name}."
+                    );
+                    return Ok(());
+                }
+                info
+            }
+        };
+
+        println!(
+            "Detected jar file change: {old_jar_name} -> {new_jar_name} ({:?} {}), updating config...",
+            info.server_fork, info.game_version
+        );
+        self.set_jar(&server_dir, new_jar_name);
+        self.detection = JarDetection::new(&info, new_jar)?;
+        self.save(server_name)
+    }
+
     pub fn save(&self, server_name: &str) -> anyhow::Result<()> {
-        let path = try_server_dir(server_name)?.join("mcerv_config.json");
-        let file = File::create(&path)?;
+        self.save_to(&try_server_dir(server_name)?.join("mcerv_config.json"))
+    }
+
+    fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path)?;
         serde_json::to_writer_pretty(file, &self)?;
         Ok(())
     }
 
-    pub fn create_start_command(&self) -> String {
-        format!(
-            "java -Xmx{} -Xms{} -jar {} nogui",
-            self.max_memory, self.min_memory, self.jar_name
-        )
+    /// The memory a service manager or container should allow the server: the heap, plus
+    /// half of it for the JVM's own memory. `None` if the max memory can't be read.
+    pub fn memory_limit(&self) -> Option<u64> {
+        Some(memory_bytes(&self.max_memory)? / 2 * 3)
+    }
+
+    /// The arguments that start the server with `java` on Windows or elsewhere, the first
+    /// being `java` itself. Rendered from the start template, or the default one.
+    pub fn start_args(&self, java: &str, windows: bool) -> Vec<String> {
+        let mut game_args = self.game_args.clone();
+        // The level name is the world directory's name, so the server opens it directly.
+        // Templates without {game_args} open it through the link instead
+        if let Some(universe) = self.world_dir.as_deref().and_then(Path::parent) {
+            game_args.extend(["--universe".to_string(), universe.display().to_string()]);
+        }
+        let values = StartValues {
+            java,
+            max_memory: &self.max_memory,
+            min_memory: &self.min_memory,
+            jar: &self.jar_name,
+            jvm_args: &self.jvm_args,
+            game_args: &game_args,
+        };
+        let args = match &self.start_command_template {
+            Some(template) => template.render(&values),
+            None => StartTemplate::default().render(&values),
+        };
+        args.iter()
+            .map(|arg| forks::platform_args_file(arg, windows))
+            .collect()
+    }
+
+    /// The line of a script that starts the server, with each argument quoted by `quote`.
+    fn start_line(&self, windows: bool, quote: fn(&str) -> String) -> String {
+        self.start_args("java", windows)
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Builds the command that starts the server in `server_dir`, the same way
+    /// the start script does.
+    pub fn start_command(&self, server_dir: &Path) -> Command {
+        let java = match &self.java_home {
+            Some(java_home) => Path::new(java_home).join("bin").join("java"),
+            None => PathBuf::from("java"),
+        };
+
+        let args = self.start_args(&java.to_string_lossy(), cfg!(target_os = "windows"));
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]).current_dir(server_dir);
+
+        if let Some(java_home) = &self.java_home {
+            let java_bin = Path::new(java_home).join("bin");
+            let mut paths = vec![java_bin];
+            if let Some(path) = env::var_os("PATH") {
+                paths.extend(env::split_paths(&path));
+            }
+
+            command.env("JAVA_HOME", java_home);
+            if let Ok(path) = env::join_paths(paths) {
+                command.env("PATH", path);
+            }
+        }
+
+        command
     }
 
+    /// The start script of the platform mcerv runs on.
     pub fn create_start_script(&self) -> String {
-        if cfg!(target_os = "windows") {
-            // Windows batch script
-            let java_home_script = match &self.java_home {
-                Some(java_home) => format!(
-                    "\
-set JAVA_HOME={java_home}
-set PATH=%JAVA_HOME%\\bin;%PATH%"
-                ),
-                None => String::new(),
-            };
+        self.create_script(&ScriptTarget::native())
+    }
 
-            format!(
+    /// Writes what starts the server in the target's format.
+    pub fn create_script(&self, target: &ScriptTarget) -> String {
+        match target {
+            ScriptTarget::Batch => self.batch_script(),
+            ScriptTarget::Shell => self.shell_script(),
+            ScriptTarget::SystemdUnit(unit) => self.systemd_unit(unit),
+        }
+    }
+
+    fn batch_script(&self) -> String {
+        let java_home_script = match &self.java_home {
+            Some(java_home) => format!(
                 "\
+set \"JAVA_HOME={}\"
+set \"PATH=%JAVA_HOME%\\bin;%PATH%\"",
+                java_home.replace('%', "%%")
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "\
 @echo off
 {java_home_script}
 
 echo Using Java: %JAVA_HOME%
 java --version
 {start_command}",
-                java_home_script = java_home_script,
-                start_command = self.create_start_command()
-            )
-        } else {
-            // Unix shell script
-            let java_home_script = match &self.java_home {
-                Some(java_home) => format!(
-                    "\
-export JAVA_HOME=\"{java_home}\"
-export PATH=\"$JAVA_HOME/bin:$PATH\""
-                ),
-                None => String::new(),
-            };
+            java_home_script = java_home_script,
+            start_command = self.start_line(true, batch_quote)
+        )
+    }
 
-            format!(
+    fn shell_script(&self) -> String {
+        let java_home_script = match &self.java_home {
+            Some(java_home) => format!(
                 "\
+export JAVA_HOME=\"{}\"
+export PATH=\"$JAVA_HOME/bin:$PATH\"",
+                shell_escape(java_home)
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "\
 #!/usr/bin/env bash
 {java_home_script}
 
 echo Using Java: $JAVA_HOME
 java --version
 {start_command}",
-                java_home_script = java_home_script,
-                start_command = self.create_start_command()
-            )
+            java_home_script = java_home_script,
+            start_command = self.start_line(false, shell_quote)
+        )
+    }
+
+    fn systemd_unit(&self, unit: &SystemdUnit) -> String {
+        let mcerv = systemd_quote(&unit.mcerv.to_string_lossy());
+        let server_name = systemd_quote(&unit.server_name);
+        let start_args: String = unit
+            .start_args
+            .iter()
+            .map(|arg| format!(" {}", systemd_quote(arg)))
+            .collect();
+
+        let mut service = vec![
+            "Type=simple".to_string(),
+            format!(
+                "WorkingDirectory={}",
+                systemd_quote(&unit.server_dir.to_string_lossy())
+            ),
+            systemd_environment(crate::DATA_DIR_VAR, &unit.data_dir.to_string_lossy()),
+        ];
+        if let Some(java_home) = &self.java_home {
+            service.push(systemd_environment("JAVA_HOME", java_home));
+        }
+        service.extend([
+            format!("ExecStart={mcerv} start {server_name}{start_args}"),
+            format!("ExecStop={mcerv} stop {server_name}"),
+            "Restart=on-failure".to_string(),
+            "RestartSec=10".to_string(),
+            // `mcerv stop` waits up to a minute for the world to be saved
+            "TimeoutStopSec=90".to_string(),
+        ]);
+        if let Some(memory_limit) = self.memory_limit() {
+            service.push(format!("MemoryMax={}M", memory_limit / (1024 * 1024)));
+        }
+        if !unit.user_service
+            && let Some(user) = &unit.user
+        {
+            service.push(format!("User={user}"));
         }
+
+        let wanted_by = if unit.user_service {
+            "default.target"
+        } else {
+            "multi-user.target"
+        };
+        format!(
+            "\
+[Unit]
+Description=Minecraft server {name} managed by mcerv
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+{service}
+
+[Install]
+WantedBy={wanted_by}
+",
+            name = unit.server_name.replace('%', "%%"),
+            service = service.join("\n"),
+        )
+    }
+}
+
+/// The formats [`Config::create_script`] can write.
+pub enum ScriptTarget {
+    /// A bash script running java directly.
+    Shell,
+    /// A Windows batch script running java directly.
+    Batch,
+    /// A systemd service running `mcerv start`.
+    SystemdUnit(SystemdUnit),
+}
+
+impl ScriptTarget {
+    /// The start script of the platform mcerv runs on.
+    pub fn native() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Batch
+        } else {
+            Self::Shell
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        match self {
+            ScriptTarget::Shell => "start_script.sh".to_string(),
+            ScriptTarget::Batch => "start_script.bat".to_string(),
+            ScriptTarget::SystemdUnit(unit) => format!("mcerv-{}.service", unit.server_name),
+        }
+    }
+}
+
+/// What a systemd unit needs besides the config.
+pub struct SystemdUnit {
+    pub server_name: String,
+    pub server_dir: PathBuf,
+    /// The mcerv executable the service runs.
+    pub mcerv: PathBuf,
+    /// Passed on with MCERV_DATA_DIR, so the service finds the server.
+    pub data_dir: PathBuf,
+    /// The arguments given to `mcerv start` after the server name, like the restart policy.
+    pub start_args: Vec<String>,
+    /// A service of the user's own systemd instead of the system's.
+    pub user_service: bool,
+    /// Who a system service runs as.
+    pub user: Option<String>,
+}
+
+/// Quotes the word for a systemd unit if it needs to be. `%` starts a specifier and `$` a
+/// variable there, so they're doubled.
+fn systemd_quote(word: &str) -> String {
+    let word = word.replace('%', "%%").replace('$', "$$");
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || "\"'\\;".contains(c)) {
+        format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        word
+    }
+}
+
+/// Quotes the word for a batch script if it needs to be. `%` expands a variable there even
+/// in quotes, so it's doubled.
+fn batch_quote(word: &str) -> String {
+    let word = word.replace('%', "%%");
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || "&|<>^(),;=".contains(c)) {
+        format!("\"{word}\"")
+    } else {
+        word
+    }
+}
+
+/// Quotes the word for a shell script if it needs to be.
+fn shell_quote(word: &str) -> String {
+    if word.is_empty()
+        || word.contains(|c: char| !(c.is_ascii_alphanumeric() || "-_./+,:@%".contains(c)))
+    {
+        format!("\"{}\"", shell_escape(word))
+    } else {
+        word.to_string()
+    }
+}
+
+/// Escapes what expands within double quotes in a shell script.
+fn shell_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '$' | '`' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn systemd_environment(name: &str, value: &str) -> String {
+    format!("Environment={}", systemd_quote(&format!("{name}={value}")))
+}
+
+/// The bytes of a JVM memory size like `4G`, `512m` or `1048576`.
+pub fn memory_bytes(memory: &str) -> Option<u64> {
+    let memory = memory.trim();
+    let (number, multiplier) = match memory.chars().last()?.to_ascii_lowercase() {
+        'k' => (&memory[..memory.len() - 1], 1024),
+        'm' => (&memory[..memory.len() - 1], 1024 * 1024),
+        'g' => (&memory[..memory.len() - 1], 1024 * 1024 * 1024),
+        't' => (&memory[..memory.len() - 1], 1024 * 1024 * 1024 * 1024),
+        _ => (memory, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// The current time in RFC 3339, as the timestamps in the config are stored.
+pub fn now() -> String {
+    chrono::Local::now().to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// Formats a timestamp of the config in local time, like `2025-08-14 18:03`.
+pub fn format_time(timestamp: Option<&str>) -> String {
+    let Some(timestamp) = timestamp else {
+        return "Unknown".to_string();
+    };
+
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        Err(_) => timestamp.to_string(),
     }
 }
 
@@ -154,6 +801,24 @@ impl Display for Config {
             "Java Home: {}",
             self.java_home.as_deref().unwrap_or("Not Set")
         )?;
+        writeln!(f, "Created: {}", format_time(self.created_at.as_deref()))?;
+        writeln!(
+            f,
+            "Last Started: {}",
+            format_time(self.last_started_at.as_deref())
+        )?;
+        if let Some(note) = &self.note {
+            writeln!(f, "Note: {note}")?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f, "Tags: {}", self.tags.join(", "))?;
+        }
+        if let Some(template) = &self.start_command_template {
+            writeln!(f, "Start Template: {template}")?;
+        }
+        if let Some(world_dir) = &self.world_dir {
+            writeln!(f, "World Directory: {}", world_dir.display())?;
+        }
         Ok(())
     }
 }
@@ -170,13 +835,22 @@ mod tests {
             min_memory: "1G".to_string(),
             jar_name: "server.jar".into(),
             java_home: Some("/path/to/java".to_string()),
+            detection: JarDetection::default(),
+            created_at: None,
+            last_started_at: None,
+            note: None,
+            tags: Vec::new(),
+            start_command_template: None,
+            jvm_args: Vec::new(),
+            game_args: Vec::new(),
+            world_dir: None,
         };
 
         let script = config.create_start_script();
         if cfg!(target_os = "windows") {
             assert!(script.contains("@echo off"));
-            assert!(script.contains("set JAVA_HOME=/path/to/java"));
-            assert!(script.contains("set PATH=%JAVA_HOME%\\bin;%PATH%"));
+            assert!(script.contains("set \"JAVA_HOME=/path/to/java\""));
+            assert!(script.contains("set \"PATH=%JAVA_HOME%\\bin;%PATH%\""));
             assert!(script.contains("java -Xmx2G -Xms1G -jar server.jar nogui"));
         } else {
             assert!(script.contains("#!/usr/bin/env bash"));
@@ -192,15 +866,424 @@ mod tests {
             min_memory: "1G".to_string(),
             jar_name: "server.jar".into(),
             java_home: None,
+            detection: JarDetection::default(),
+            created_at: None,
+            last_started_at: None,
+            note: None,
+            tags: Vec::new(),
+            start_command_template: None,
+            jvm_args: Vec::new(),
+            game_args: Vec::new(),
+            world_dir: None,
         };
 
         let script_no_java = config_no_java.create_start_script();
         if cfg!(target_os = "windows") {
             assert!(script_no_java.contains("@echo off"));
-            assert!(!script_no_java.contains("set JAVA_HOME="));
+            assert!(!script_no_java.contains("set \"JAVA_HOME="));
         } else {
             assert!(script_no_java.contains("#!/usr/bin/env bash"));
             assert!(!script_no_java.contains("export JAVA_HOME="));
         }
     }
+
+    #[test]
+    fn test_scripts_quote_paths() {
+        let mut config = Config::new_4gb("my server.jar".to_string()).unwrap();
+        config.java_home = Some("C:\\Program Files\\Eclipse Adoptium\\jdk-21 (100%)".to_string());
+
+        let batch = config.batch_script();
+        assert!(
+            batch.contains(
+                "set \"JAVA_HOME=C:\\Program Files\\Eclipse Adoptium\\jdk-21 (100%%)\"\n"
+            )
+        );
+        assert!(batch.contains("set \"PATH=%JAVA_HOME%\\bin;%PATH%\"\n"));
+        assert!(batch.ends_with("-jar \"my server.jar\" nogui"));
+
+        config.java_home = Some("/opt/java/jdk $HOME \"21\"".to_string());
+        let shell = config.shell_script();
+        assert!(shell.contains("export JAVA_HOME=\"/opt/java/jdk \\$HOME \\\"21\\\"\"\n"));
+        assert!(shell.ends_with("-jar \"my server.jar\" nogui"));
+
+        config.jar_name = "fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar".to_string();
+        assert!(
+            config
+                .batch_script()
+                .ends_with(&format!("-jar {} nogui", config.jar_name))
+        );
+        assert!(
+            config
+                .shell_script()
+                .ends_with(&format!("-jar {} nogui", config.jar_name))
+        );
+        config.jar_name = "`rm -rf ~`.jar".to_string();
+        assert!(
+            config
+                .shell_script()
+                .ends_with("-jar \"\\`rm -rf ~\\`.jar\" nogui")
+        );
+    }
+
+    fn fixture(name: &str) -> String {
+        fs::read_to_string(Path::new("testdata/configs").join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let path = Path::new("/srv/smp/mcerv_config.json");
+        let error = |name| Config::parse("smp", path, &fixture(name)).err().unwrap();
+
+        let e = error("unquoted-value.json");
+        assert_eq!(e.position, Some((3, 17)));
+        assert_eq!(e.excerpt.as_deref(), Some("  \"max_memory\": 8G,"));
+        assert_eq!(
+            e.to_string(),
+            "Failed to parse /srv/smp/mcerv_config.json at line 3, column 17: \
+             invalid type: integer `8`, expected a string\n \
+             3 |   \"max_memory\": 8G,\n   |                 ^\n\
+             Run `mcerv config repair smp` to regenerate it, keeping the fields that still parse"
+        );
+
+        let e = error("trailing-comma.json");
+        assert_eq!(e.message, "trailing comma");
+        assert_eq!(e.position.map(|(line, _)| line), Some(12));
+
+        let e = error("missing-jar-name.json");
+        assert_eq!(e.message, "missing field `jar_name`");
+        assert!(e.to_string().contains("mcerv config repair smp"));
+
+        let e = error("wrong-type.json");
+        assert!(e.message.starts_with("invalid type: string \"smp\""));
+        assert_eq!(e.excerpt.as_deref(), Some("  \"tags\": \"smp\""));
+
+        // The column can end inside a character, one byte into `–` here
+        let e = error("non-ascii.json");
+        assert_eq!(e.position.map(|(line, _)| line), Some(7));
+        let message = e.to_string();
+        let lines: Vec<&str> = message.lines().collect();
+        let column = |line: &str, c| line.chars().position(|other| other == c);
+        assert_eq!(column(lines[2], '^'), column(lines[1], '–'), "{message}");
+
+        // Long lines are cut around the column
+        let content = format!("{{\"note\": \"{}\", oops}}", "a".repeat(200));
+        let e = Config::parse("smp", path, &content).err().unwrap();
+        let excerpt = e.to_string().lines().nth(1).unwrap().to_string();
+        assert!(excerpt.len() < 80 && excerpt.contains("oops"), "{excerpt}");
+    }
+
+    #[test]
+    fn test_repair() {
+        let repair = |name: &str| {
+            let dir = tempfile::tempdir().unwrap();
+            fs::copy("testdata/vanilla-1.21.8.jar", dir.path().join("server.jar")).unwrap();
+            fs::write(dir.path().join("mcerv_config.json"), fixture(name)).unwrap();
+            let (config, repair) = Config::repair_dir(dir.path()).unwrap();
+
+            // The repaired config loads again
+            let content = fs::read_to_string(dir.path().join("mcerv_config.json")).unwrap();
+            assert!(serde_json::from_str::<Config>(&content).is_ok());
+            assert_eq!(
+                repair
+                    .backup
+                    .as_ref()
+                    .map(|backup| fs::read_to_string(backup).unwrap()),
+                Some(fixture(name))
+            );
+            assert_eq!(config.jar_name, "server.jar");
+            assert_eq!(config.detection.detected_fork, Some(ServerFork::Vanilla));
+            assert_eq!(
+                config.detection.detected_game_version.as_deref(),
+                Some("1.21.8")
+            );
+            (config, repair.preserved)
+        };
+
+        let (config, preserved) = repair("trailing-comma.json");
+        assert_eq!(config.max_memory, "8G");
+        assert_eq!(config.note.as_deref(), Some("Survival with friends"));
+        assert_eq!(config.tags, ["smp", "friends"]);
+        // Regenerated from the jar, which is vanilla
+        assert!(!preserved.contains(&"detected_fork".to_string()));
+
+        let (config, preserved) = repair("unquoted-value.json");
+        assert_eq!(config.min_memory, "2G");
+        assert_eq!(config.max_memory, "4G");
+        assert_eq!(
+            config.created_at.as_deref(),
+            Some("2025-08-01T12:00:00+02:00")
+        );
+        assert_eq!(config.tags, ["smp"]);
+        assert!(!preserved.contains(&"max_memory".to_string()));
+
+        let (config, _) = repair("missing-jar-name.json");
+        assert_eq!(config.max_memory, "8G");
+
+        let (config, preserved) = repair("wrong-type.json");
+        assert_eq!(config.note.as_deref(), Some("Survival with friends"));
+        assert!(config.tags.is_empty());
+        assert!(!preserved.contains(&"tags".to_string()));
+    }
+
+    #[test]
+    fn test_start_command() {
+        let config = Config {
+            max_memory: "2G".to_string(),
+            min_memory: "1G".to_string(),
+            jar_name: "server.jar".into(),
+            java_home: Some("/path/to/java".to_string()),
+            detection: JarDetection::default(),
+            created_at: None,
+            last_started_at: None,
+            note: None,
+            tags: Vec::new(),
+            start_command_template: None,
+            jvm_args: Vec::new(),
+            game_args: Vec::new(),
+            world_dir: None,
+        };
+
+        let command = config.start_command(Path::new("/servers/test"));
+        assert_eq!(
+            command.get_program(),
+            Path::new("/path/to/java").join("bin").join("java")
+        );
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, ["-Xmx2G", "-Xms1G", "-jar", "server.jar", "nogui"]);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/servers/test")));
+        assert!(
+            command
+                .get_envs()
+                .any(|(key, value)| key == "JAVA_HOME" && value == Some("/path/to/java".as_ref()))
+        );
+
+        // A java_home with spaces is one entry of PATH
+        let mut config = config;
+        config.java_home = Some("/opt/Eclipse Adoptium/jdk 21".to_string());
+        let command = config.start_command(Path::new("/servers/test"));
+        let path = command
+            .get_envs()
+            .find_map(|(key, value)| (key == "PATH").then_some(value).flatten())
+            .unwrap();
+        assert_eq!(
+            std::env::split_paths(path).next(),
+            Some(Path::new("/opt/Eclipse Adoptium/jdk 21").join("bin"))
+        );
+    }
+
+    #[test]
+    fn test_start_template() {
+        let mut config = Config::new_4gb("server.jar".to_string()).unwrap();
+        config.java_home = Some("/path/to/java".to_string());
+        config.start_command_template = Some(
+            StartTemplate::parse(
+                "{java} {xmx} {xms} {jvm_args} @libraries/net/minecraftforge/forge/1.21.8-58.1.0/unix_args.txt nogui {game_args}",
+            )
+            .unwrap(),
+        );
+        config.jvm_args = vec!["-XX:+UseG1GC".to_string()];
+        config.game_args = vec!["--world".to_string(), "my world".to_string()];
+
+        let command = config.start_command(Path::new("/servers/test"));
+        assert_eq!(
+            command.get_program(),
+            Path::new("/path/to/java").join("bin").join("java")
+        );
+        let args_file = forks::platform_args_file(
+            "@libraries/net/minecraftforge/forge/1.21.8-58.1.0/unix_args.txt",
+            cfg!(target_os = "windows"),
+        );
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "-Xmx4G",
+                "-Xms4G",
+                "-XX:+UseG1GC",
+                &args_file,
+                "nogui",
+                "--world",
+                "my world"
+            ]
+        );
+
+        assert!(config.create_script(&ScriptTarget::Shell).contains(
+            "java -Xmx4G -Xms4G -XX:+UseG1GC @libraries/net/minecraftforge/forge/1.21.8-58.1.0/unix_args.txt nogui --world \"my world\""
+        ));
+
+        config.world_dir = Some(PathBuf::from("/mnt/worlds/survival"));
+        let args = config.start_args("java", false);
+        assert_eq!(args[args.len() - 2..], ["--universe", "/mnt/worlds"]);
+        // Windows starts Forge with its own argument file
+        assert!(config.create_script(&ScriptTarget::Batch).contains(
+            "java -Xmx4G -Xms4G -XX:+UseG1GC @libraries/net/minecraftforge/forge/1.21.8-58.1.0/win_args.txt nogui --world \"my world\""
+        ));
+
+        // A saved template that no longer parses is a parse error
+        let content = serde_json::to_string(&config)
+            .unwrap()
+            .replace("{xms}", "{xms} {xms}");
+        assert!(Config::parse("test", Path::new("mcerv_config.json"), &content).is_err());
+    }
+
+    #[test]
+    fn test_check_jar_change() {
+        let vanilla = Path::new("testdata/vanilla-1.21.8.jar");
+
+        assert!(matches!(
+            check_jar_change(Some(ServerFork::Vanilla), vanilla),
+            JarChange::Adopt(_)
+        ));
+        assert!(matches!(
+            check_jar_change(None, vanilla),
+            JarChange::Adopt(_)
+        ));
+        assert!(matches!(
+            check_jar_change(Some(ServerFork::Fabric), vanilla),
+            JarChange::ForkChanged(ServerInfo {
+                server_fork: ServerFork::Vanilla,
+                ..
+            })
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let not_a_jar = dir.path().join("forge-installer.jar");
+        fs::write(&not_a_jar, "").unwrap();
+        assert!(matches!(
+            check_jar_change(Some(ServerFork::Forge), &not_a_jar),
+            JarChange::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn test_metadata() {
+        // Written before the metadata was recorded
+        let config: Config = serde_json::from_str(
+            r#"{"min_memory":"4G","max_memory":"4G","jar_name":"server.jar","java_home":null}"#,
+        )
+        .unwrap();
+        assert_eq!(config.created_at, None);
+        assert!(config.to_string().contains("Created: Unknown"));
+        assert!(!config.to_string().contains("Note"));
+
+        let mut config = Config::new_4gb("server.jar".to_string()).unwrap();
+        config.note = Some("kids' creative server".to_string());
+        config.mark_started();
+        assert!(DateTime::parse_from_rfc3339(config.created_at.as_deref().unwrap()).is_ok());
+        assert_eq!(format_time(Some("yesterday")), "yesterday");
+
+        let display = config.to_string();
+        assert!(display.contains("Note: kids' creative server"));
+        assert!(!display.contains("Last Started: Unknown"));
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(normalize_tag(" SMP ").unwrap(), "smp");
+        assert_eq!(normalize_tag("mod_pack-2").unwrap(), "mod_pack-2");
+        assert!(normalize_tag("").is_err());
+        assert!(normalize_tag("two words").is_err());
+        assert!(normalize_tag(&"a".repeat(33)).is_err());
+
+        let mut config = Config::new_4gb("server.jar".to_string()).unwrap();
+        assert!(config.add_tag("Production").unwrap());
+        assert!(config.add_tag("smp").unwrap());
+        assert!(!config.add_tag("SMP").unwrap());
+        assert_eq!(config.tags, ["production", "smp"]);
+        assert!(config.to_string().contains("Tags: production, smp"));
+
+        assert!(config.remove_tag("PRODUCTION").unwrap());
+        assert!(!config.remove_tag("production").unwrap());
+        assert_eq!(config.tags, ["smp"]);
+    }
+
+    /// The keys and values of each section of a systemd unit, in order.
+    fn parse_unit(unit: &str) -> Vec<(String, Vec<(String, String)>)> {
+        let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        for line in unit.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.push((name.to_string(), Vec::new()));
+                continue;
+            }
+            let (key, value) = line.split_once('=').expect(line);
+            let (_, entries) = sections.last_mut().expect("entry outside of a section");
+            entries.push((key.to_string(), value.to_string()));
+        }
+        sections
+    }
+
+    fn value<'a>(sections: &'a [(String, Vec<(String, String)>)], key: &str) -> Vec<&'a str> {
+        sections
+            .iter()
+            .flat_map(|(_, entries)| entries)
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let mut config = Config::new_4gb("server.jar".to_string()).unwrap();
+        config.java_home = Some("/opt/java 21".to_string());
+        let unit = SystemdUnit {
+            server_name: "survival".to_string(),
+            server_dir: PathBuf::from("/srv/mcerv/instances/survival"),
+            mcerv: PathBuf::from("/usr/local/bin/mcerv"),
+            data_dir: PathBuf::from("/srv/mcerv"),
+            start_args: vec!["--restart-on-crash=3".to_string()],
+            user_service: false,
+            user: Some("minecraft".to_string()),
+        };
+        let target = ScriptTarget::SystemdUnit(unit);
+        assert_eq!(target.file_name(), "mcerv-survival.service");
+
+        let sections = parse_unit(&config.create_script(&target));
+        let names: Vec<_> = sections.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Unit", "Service", "Install"]);
+        assert_eq!(
+            value(&sections, "ExecStart"),
+            ["/usr/local/bin/mcerv start survival --restart-on-crash=3"]
+        );
+        assert_eq!(
+            value(&sections, "ExecStop"),
+            ["/usr/local/bin/mcerv stop survival"]
+        );
+        assert_eq!(
+            value(&sections, "WorkingDirectory"),
+            ["/srv/mcerv/instances/survival"]
+        );
+        assert_eq!(
+            value(&sections, "Environment"),
+            ["MCERV_DATA_DIR=/srv/mcerv", "\"JAVA_HOME=/opt/java 21\""]
+        );
+        assert_eq!(value(&sections, "MemoryMax"), ["6144M"]);
+        assert_eq!(value(&sections, "Restart"), ["on-failure"]);
+        assert_eq!(value(&sections, "User"), ["minecraft"]);
+        assert_eq!(value(&sections, "WantedBy"), ["multi-user.target"]);
+
+        let ScriptTarget::SystemdUnit(mut unit) = target else {
+            unreachable!()
+        };
+        unit.user_service = true;
+        unit.server_name = "100%".to_string();
+        let sections = parse_unit(&config.create_script(&ScriptTarget::SystemdUnit(unit)));
+        assert!(value(&sections, "User").is_empty());
+        assert_eq!(value(&sections, "WantedBy"), ["default.target"]);
+        assert_eq!(
+            value(&sections, "ExecStop"),
+            ["/usr/local/bin/mcerv stop 100%%"]
+        );
+    }
+
+    #[test]
+    fn test_memory_bytes() {
+        assert_eq!(memory_bytes("4G"), Some(4 * 1024 * 1024 * 1024));
+        assert_eq!(memory_bytes("512m"), Some(512 * 1024 * 1024));
+        assert_eq!(memory_bytes("1048576"), Some(1048576));
+        assert_eq!(memory_bytes("4GB"), None);
+        assert_eq!(memory_bytes(""), None);
+    }
 }
@@ -0,0 +1,268 @@
+use crate::system::{cancel, process};
+use std::{
+    error::Error,
+    fmt::Display,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    fs::File,
+    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+
+/// Sent by the wrapper when the attach is accepted.
+const ACCEPTED: &str = "ok";
+/// Sent by the wrapper when another attach is already active.
+const BUSY: &str = "busy";
+/// The number of existing log lines shown when attaching, like `tail -f` does.
+const TAIL_LINES: usize = 10;
+
+#[derive(Debug)]
+pub enum AttachError {
+    NoConsoleLog(PathBuf),
+    AlreadyAttached,
+}
+
+impl Display for AttachError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachError::NoConsoleLog(path) => write!(
+                f,
+                "No console log found at {:?}, was the server started with --detach?",
+                path
+            ),
+            AttachError::AlreadyAttached => {
+                write!(
+                    f,
+                    "Another mcerv attach is already connected to this server"
+                )
+            }
+        }
+    }
+}
+
+impl Error for AttachError {}
+
+#[cfg(unix)]
+type ConsoleStream = tokio::net::UnixStream;
+// Sending commands is not supported on this platform yet, so this is never constructed
+#[cfg(not(unix))]
+type ConsoleStream = io::DuplexStream;
+
+/// The socket detached servers accept console commands on.
+pub fn console_socket_path(server_dir: &Path) -> PathBuf {
+    server_dir.join(".mcerv.console")
+}
+
+/// Sends lines written to the console socket to `commands`, allowing one attached
+/// client at a time. The supervisor forwards them to the server's stdin.
+#[cfg(unix)]
+pub async fn serve_console(
+    listener: tokio::net::UnixListener,
+    commands: mpsc::UnboundedSender<String>,
+) -> io::Result<()> {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    let attached = Arc::new(AtomicBool::new(false));
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        if attached.swap(true, Ordering::SeqCst) {
+            let _ = stream.write_all(format!("{BUSY}\n").as_bytes()).await;
+            continue;
+        }
+
+        let commands = commands.clone();
+        let attached = attached.clone();
+        tokio::spawn(async move {
+            if stream
+                .write_all(format!("{ACCEPTED}\n").as_bytes())
+                .await
+                .is_ok()
+            {
+                let mut lines = BufReader::new(&mut stream).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if commands.send(line).is_err() {
+                        break;
+                    }
+                }
+            }
+            attached.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+/// Connects to the console socket of a detached server, if it has one.
+#[cfg(unix)]
+async fn connect_console(server_dir: &Path) -> anyhow::Result<Option<ConsoleStream>> {
+    let Ok(mut stream) = tokio::net::UnixStream::connect(console_socket_path(server_dir)).await
+    else {
+        return Ok(None);
+    };
+
+    let mut reply = String::new();
+    BufReader::new(&mut stream).read_line(&mut reply).await?;
+    match reply.trim() {
+        ACCEPTED => Ok(Some(stream)),
+        BUSY => Err(AttachError::AlreadyAttached.into()),
+        reply => anyhow::bail!("Unexpected reply from the server console: {reply}"),
+    }
+}
+
+#[cfg(not(unix))]
+async fn connect_console(_server_dir: &Path) -> anyhow::Result<Option<ConsoleStream>> {
+    Ok(None)
+}
+
+/// Follows the console log of a detached server and forwards typed lines to it.
+/// Ctrl-C detaches without stopping the server.
+pub async fn attach(server_dir: &Path) -> anyhow::Result<()> {
+    let log_path = process::console_log_path(server_dir);
+    if !log_path.exists() {
+        return Err(AttachError::NoConsoleLog(log_path).into());
+    }
+
+    let console = connect_console(server_dir).await?;
+    let attached = console.is_some();
+
+    if attached {
+        println!("Attached, type commands to send them to the server. Press Ctrl-C to detach.");
+    } else {
+        println!("The server is not accepting commands, only showing its console output.");
+        println!("Press Ctrl-C to stop watching.");
+    }
+
+    let (mut console_reader, mut console_writer) = match console {
+        Some(console) => {
+            let (reader, writer) = io::split(console);
+            (Some(reader), Some(writer))
+        }
+        None => (None, None),
+    };
+
+    let mut log = File::open(&log_path).await?;
+    let mut offset = print_tail(&mut log, TAIL_LINES).await?;
+    let mut stdin = BufReader::new(io::stdin()).lines();
+    let mut interval = tokio::time::interval(Duration::from_millis(250));
+    let mut buf = [0u8; 64];
+
+    // Ctrl-C detaches instead of exiting
+    let _scope = cancel::scope();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = interval.tick() => offset = print_appended(&mut log, offset).await?,
+            line = stdin.next_line(), if attached => {
+                let Some(line) = line? else { break };
+                let writer = console_writer.as_mut().unwrap();
+                writer.write_all(format!("{line}\n").as_bytes()).await?;
+            }
+            read = async { console_reader.as_mut().unwrap().read(&mut buf).await }, if attached => {
+                // The wrapper never writes after the handshake, so this only returns once it exits
+                if matches!(read, Ok(0) | Err(_)) {
+                    print_appended(&mut log, offset).await?;
+                    println!("The server has stopped.");
+                    break;
+                }
+            }
+        }
+    }
+
+    println!("Detached.");
+    Ok(())
+}
+
+/// Prints the last `lines` lines of the file and returns the end offset.
+async fn print_tail(file: &mut File, lines: usize) -> io::Result<u64> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).await?;
+
+    let tail: Vec<_> = content.lines().rev().take(lines).collect();
+    for line in tail.into_iter().rev() {
+        println!("{line}");
+    }
+
+    file.stream_position().await
+}
+
+/// Prints whatever was appended to the file since `offset` and returns the new end offset.
+/// Starts over if the file got truncated.
+async fn print_appended(file: &mut File, offset: u64) -> io::Result<u64> {
+    let len = file.metadata().await?.len();
+    let offset = if len < offset { 0 } else { offset };
+    if len == offset {
+        return Ok(offset);
+    }
+
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut appended = Vec::new();
+    file.read_to_end(&mut appended).await?;
+
+    let mut stdout = io::stdout();
+    stdout.write_all(&appended).await?;
+    stdout.flush().await?;
+
+    Ok(offset + appended.len() as u64)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    async fn accepted_stream(socket_path: &Path) -> tokio::net::UnixStream {
+        let mut stream = tokio::net::UnixStream::connect(socket_path).await.unwrap();
+        let mut reply = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut reply)
+            .await
+            .unwrap();
+        assert_eq!(reply.trim(), ACCEPTED);
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_serve_console_forwards_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = console_socket_path(dir.path());
+
+        let (commands, mut received) = mpsc::unbounded_channel();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(serve_console(listener, commands));
+
+        let mut stream = accepted_stream(&socket_path).await;
+        stream.write_all(b"say hello\nlist\n").await.unwrap();
+        assert_eq!(received.recv().await.unwrap(), "say hello");
+        assert_eq!(received.recv().await.unwrap(), "list");
+    }
+
+    #[tokio::test]
+    async fn test_serve_console_refuses_second_attach() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = console_socket_path(dir.path());
+
+        let (commands, _received) = mpsc::unbounded_channel();
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(serve_console(listener, commands));
+
+        let first = accepted_stream(&socket_path).await;
+        assert!(matches!(
+            connect_console(dir.path())
+                .await
+                .unwrap_err()
+                .downcast_ref::<AttachError>(),
+            Some(AttachError::AlreadyAttached)
+        ));
+
+        // Once the first one detaches, attaching works again
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(connect_console(dir.path()).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::system::{forks::ServerFork, jar_parser::ModMetadata};
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Mods that come with the loader or game rather than the mods directory.
+const BUILTIN_MODS: [&str; 6] = [
+    "minecraft",
+    "java",
+    "fabricloader",
+    "forge",
+    "neoforge",
+    "mixinextras",
+];
+
+/// A mod as listed in the system details of a crash report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportedMod {
+    pub id: String,
+    pub version: String,
+}
+
+/// The parts of a crash report that help find what crashed.
+#[derive(Debug, Default, PartialEq)]
+pub struct CrashReport {
+    pub time: Option<String>,
+    pub description: Option<String>,
+    /// The class of the exception, like `java.lang.NullPointerException`.
+    pub exception: Option<String>,
+    /// The message of the exception, if it has one.
+    pub message: Option<String>,
+    pub suspected_mods: Vec<String>,
+    pub mods: Vec<ReportedMod>,
+}
+
+/// How the mods in a crash report differ from the installed ones.
+#[derive(Debug, Default, PartialEq)]
+pub struct ModDelta {
+    /// Installed since the crash.
+    pub added: Vec<String>,
+    /// Removed since the crash.
+    pub removed: Vec<String>,
+    /// `(id, version at the crash, installed version)`
+    pub changed: Vec<(String, String, String)>,
+}
+
+impl ModDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The crash reports in the server's `crash-reports` directory, oldest first.
+pub fn list(server_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = server_dir.join("crash-reports");
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reports = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "txt") {
+            reports.push(path);
+        }
+    }
+    // The file names start with the crash time, `crash-2025-08-14_21.03.52-server.txt`
+    reports.sort();
+
+    Ok(reports)
+}
+
+impl CrashReport {
+    pub fn parse(content: &str, fork: ServerFork) -> Self {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut report = Self {
+            time: header_value(&lines, "Time: "),
+            description: header_value(&lines, "Description: "),
+            ..Default::default()
+        };
+
+        // The exception is the first line after the description
+        if let Some(exception) = lines
+            .iter()
+            .skip_while(|line| !line.starts_with("Description: "))
+            .skip(1)
+            .find(|line| !line.trim().is_empty())
+        {
+            let (class, message) = match exception.split_once(": ") {
+                Some((class, message)) => (class, Some(message.to_string())),
+                None => (*exception, None),
+            };
+            report.exception = Some(class.trim().to_string());
+            report.message = message;
+        }
+
+        report.suspected_mods = suspected_mods(&lines);
+        match fork {
+            ServerFork::Fabric => {
+                report.mods = fabric_mods(&lines);
+                // Fabric names the mods of the mixins that were in the stacktrace
+                for line in &lines {
+                    if let Some((_, rest)) = line.split_once("(from mod ") {
+                        let id = rest.trim_end_matches(')').to_string();
+                        if !report.suspected_mods.contains(&id) {
+                            report.suspected_mods.push(id);
+                        }
+                    }
+                }
+            }
+            ServerFork::Forge => report.mods = forge_mods(&lines),
+            ServerFork::Vanilla => {}
+        }
+
+        report
+    }
+
+    /// Compares the mods at the time of the crash with the installed ones.
+    pub fn mod_delta(&self, installed: &[ModMetadata]) -> ModDelta {
+        let mut delta = ModDelta::default();
+        let reported = self
+            .mods
+            .iter()
+            .filter(|m| !BUILTIN_MODS.contains(&m.id.as_str()));
+
+        for reported in reported.clone() {
+            match installed.iter().find(|m| m.id == reported.id) {
+                Some(m) if m.version != reported.version => {
+                    delta
+                        .changed
+                        .push((m.id.clone(), reported.version.clone(), m.version.clone()))
+                }
+                Some(_) => {}
+                None => delta.removed.push(reported.id.clone()),
+            }
+        }
+
+        for m in installed {
+            if !reported.clone().any(|reported| reported.id == m.id) {
+                delta.added.push(m.id.clone());
+            }
+        }
+
+        delta
+    }
+}
+
+impl Display for CrashReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let or_unknown = |value: &Option<String>| value.clone().unwrap_or("unknown".to_string());
+
+        writeln!(f, "Time: {}", or_unknown(&self.time))?;
+        writeln!(f, "Description: {}", or_unknown(&self.description))?;
+        writeln!(f, "Exception: {}", or_unknown(&self.exception))?;
+        if let Some(message) = &self.message {
+            writeln!(f, "Message: {message}")?;
+        }
+        if self.suspected_mods.is_empty() {
+            writeln!(f, "Suspected Mods: none")?;
+        } else {
+            writeln!(f, "Suspected Mods: {}", self.suspected_mods.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ModDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(
+                f,
+                "The installed mods are the same as at the time of the crash."
+            );
+        }
+
+        writeln!(f, "Mod changes since the crash:")?;
+        for id in &self.added {
+            writeln!(f, "  + {id}")?;
+        }
+        for id in &self.removed {
+            writeln!(f, "  - {id}")?;
+        }
+        for (id, old, new) in &self.changed {
+            writeln!(f, "  ~ {id}: {old} -> {new}")?;
+        }
+        Ok(())
+    }
+}
+
+fn header_value(lines: &[&str], prefix: &str) -> Option<String> {
+    lines
+        .iter()
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(|value| value.trim().to_string())
+}
+
+/// The lines indented below the `\t<header>` line in the system details.
+fn details_block<'a>(lines: &[&'a str], header: &str) -> Vec<&'a str> {
+    lines
+        .iter()
+        .skip_while(|line| line.trim() != header)
+        .skip(1)
+        .take_while(|line| line.starts_with("\t\t"))
+        .copied()
+        .collect()
+}
+
+/// Mods from the `Suspected Mod:` or `Suspected Mods:` section, by their id.
+fn suspected_mods(lines: &[&str]) -> Vec<String> {
+    let mut mods = Vec::new();
+    let mut in_section = false;
+
+    for line in lines {
+        if line.starts_with("Suspected Mod") {
+            in_section = true;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        // Entries are indented once, their details further
+        let Some(entry) = line.strip_prefix('\t') else {
+            break;
+        };
+        if entry.starts_with('\t') {
+            continue;
+        }
+
+        // `Pipez (pipez), Version: 1.2.3`
+        let id = entry
+            .split_once('(')
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .map(|(id, _)| id)
+            .unwrap_or(entry);
+        if entry.trim() != "NO CONFIDENT MATCH" {
+            mods.push(id.trim().to_string());
+        }
+    }
+
+    mods
+}
+
+/// Top level mods of the `Fabric Mods:` list, like `\t\tlithium: Lithium 0.18.0+mc1.21.8`.
+/// Mods nested inside others are indented further and skipped.
+fn fabric_mods(lines: &[&str]) -> Vec<ReportedMod> {
+    details_block(lines, "Fabric Mods:")
+        .into_iter()
+        .filter(|line| !line.starts_with("\t\t\t"))
+        .filter_map(|line| {
+            let (id, name_and_version) = line.trim().split_once(": ")?;
+            let version = name_and_version.rsplit(' ').next()?;
+            Some(ReportedMod {
+                id: id.to_string(),
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Mods of the `Mod List:` table, like `\t\tpipez-1.2.3.jar |Pipez |pipez |1.2.3 |DONE |...`.
+fn forge_mods(lines: &[&str]) -> Vec<ReportedMod> {
+    details_block(lines, "Mod List:")
+        .into_iter()
+        .filter_map(|line| {
+            let columns: Vec<_> = line.split('|').map(str::trim).collect();
+            let (id, version) = (columns.get(2)?, columns.get(3)?);
+            Some(ReportedMod {
+                id: id.to_string(),
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FABRIC_REPORT: &str =
+        include_str!("../../testdata/crash-reports/crash-2025-08-14_21.03.52-fabric-server.txt");
+    const FORGE_REPORT: &str =
+        include_str!("../../testdata/crash-reports/crash-2025-08-20_09.15.40-forge-server.txt");
+
+    fn installed(mods: &[(&str, &str)]) -> Vec<ModMetadata> {
+        mods.iter()
+            .map(|(id, version)| ModMetadata {
+                id: id.to_string(),
+                version: version.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_fabric_report() {
+        let report = CrashReport::parse(FABRIC_REPORT, ServerFork::Fabric);
+
+        assert_eq!(report.time.as_deref(), Some("2025-08-14 21:03:52"));
+        assert_eq!(
+            report.description.as_deref(),
+            Some("Exception in server tick loop")
+        );
+        assert_eq!(
+            report.exception.as_deref(),
+            Some("java.lang.NullPointerException")
+        );
+        assert_eq!(
+            report.suspected_mods,
+            ["betterhoppers", "fabric-lifecycle-events-v1"]
+        );
+
+        let ids: Vec<_> = report.mods.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "betterhoppers",
+                "fabric-api",
+                "fabricloader",
+                "java",
+                "lithium",
+                "minecraft"
+            ]
+        );
+        assert_eq!(report.mods[1].version, "0.129.0+1.21.8");
+    }
+
+    #[test]
+    fn test_parse_forge_report() {
+        let report = CrashReport::parse(FORGE_REPORT, ServerFork::Forge);
+
+        assert_eq!(report.description.as_deref(), Some("Ticking block entity"));
+        assert_eq!(
+            report.exception.as_deref(),
+            Some("java.lang.IllegalStateException")
+        );
+        assert_eq!(
+            report.message.as_deref(),
+            Some("Pipe network has no controller")
+        );
+        assert_eq!(report.suspected_mods, ["pipez"]);
+        assert_eq!(
+            report.mods,
+            [
+                ("minecraft", "1.21.8"),
+                ("pipez", "1.2.3"),
+                ("jei", "23.1.0"),
+                ("forge", "58.1.0")
+            ]
+            .map(|(id, version)| ReportedMod {
+                id: id.to_string(),
+                version: version.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_mod_delta() {
+        let report = CrashReport::parse(FORGE_REPORT, ServerFork::Forge);
+
+        let delta = report.mod_delta(&installed(&[("pipez", "1.2.4"), ("create", "6.0.0")]));
+        assert_eq!(delta.added, ["create"]);
+        assert_eq!(delta.removed, ["jei"]);
+        assert_eq!(
+            delta.changed,
+            [(
+                "pipez".to_string(),
+                "1.2.3".to_string(),
+                "1.2.4".to_string()
+            )]
+        );
+
+        let unchanged = report.mod_delta(&installed(&[("pipez", "1.2.3"), ("jei", "23.1.0")]));
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn test_list() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(list(dir.path()).unwrap().is_empty());
+
+        let reports = dir.path().join("crash-reports");
+        fs::create_dir(&reports).unwrap();
+        for name in [
+            "crash-2025-08-20_09.15.40-server.txt",
+            "crash-2025-08-14_21.03.52-server.txt",
+            "notes.md",
+        ] {
+            fs::write(reports.join(name), "").unwrap();
+        }
+
+        assert_eq!(
+            list(dir.path()).unwrap(),
+            [
+                reports.join("crash-2025-08-14_21.03.52-server.txt"),
+                reports.join("crash-2025-08-20_09.15.40-server.txt")
+            ]
+        );
+    }
+}
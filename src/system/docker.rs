@@ -0,0 +1,253 @@
+use crate::system::{config::Config, doctor, eula, forks::ServerFork};
+use std::{error::Error, fmt::Display, path::Path};
+
+/// The Java used for versions whose requirement can't be told, like snapshots.
+const DEFAULT_JAVA: u32 = 21;
+/// Where the instance directory is mounted in the container.
+const CONTAINER_DIR: &str = "/server";
+
+#[derive(Debug, PartialEq)]
+pub enum DockerExportError {
+    /// The container can't ask to accept it.
+    EulaNotAccepted,
+    /// Forge's shim jar loads the libraries downloaded by its installer.
+    MissingForgeLibraries,
+}
+
+impl Display for DockerExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DockerExportError::EulaNotAccepted => write!(
+                f,
+                "The EULA must be accepted before exporting, since the container can't ask. \
+                Run `mcerv accept-eula` to accept it"
+            ),
+            DockerExportError::MissingForgeLibraries => write!(
+                f,
+                "The libraries directory of the Forge server is missing, reinstall the server \
+                to download it"
+            ),
+        }
+    }
+}
+
+impl Error for DockerExportError {}
+
+/// The server to export, with where the instance directory is mounted from, relative to the
+/// exported files or absolute.
+pub struct DockerServer<'a> {
+    pub server_name: &'a str,
+    pub fork: ServerFork,
+    pub game_version: &'a str,
+    pub config: &'a Config,
+    pub port: u16,
+    pub instance_dir: &'a str,
+}
+
+/// Checks the instance can run in a container as it is.
+pub fn check_exportable(server_dir: &Path, fork: ServerFork) -> Result<(), DockerExportError> {
+    if !eula::is_accepted(server_dir) {
+        return Err(DockerExportError::EulaNotAccepted);
+    }
+    if fork == ServerFork::Forge && !server_dir.join("libraries").is_dir() {
+        return Err(DockerExportError::MissingForgeLibraries);
+    }
+    Ok(())
+}
+
+/// The Eclipse Temurin image with the Java the game version needs.
+pub fn base_image(game_version: &str) -> String {
+    let java = match doctor::required_java(game_version) {
+        // There's no Temurin 16 image, and 17 runs 1.17 too
+        Some(16) => 17,
+        Some(java) => java,
+        None => DEFAULT_JAVA,
+    };
+    format!("eclipse-temurin:{java}-jre")
+}
+
+/// A Dockerfile running the server from the mounted instance directory. The java home of
+/// the config is left out, the image has its own Java.
+pub fn dockerfile(server: &DockerServer) -> String {
+    let command = server
+        .config
+        .start_args("java", false)
+        .iter()
+        .map(|arg| serde_json::to_string(arg).unwrap())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut dockerfile = format!(
+        "\
+# Generated by mcerv for {name}, a {fork:?} {game_version} server
+FROM {image}
+WORKDIR {CONTAINER_DIR}
+",
+        name = server.server_name,
+        fork = server.fork,
+        game_version = server.game_version,
+        image = base_image(server.game_version),
+    );
+    if server.fork == ServerFork::Forge {
+        dockerfile.push_str("# The Forge jar loads libraries/ from the mounted directory\n");
+    }
+    dockerfile.push_str(&format!(
+        "EXPOSE {port}\nCMD [{command}]\n",
+        port = server.port
+    ));
+    dockerfile
+}
+
+/// A compose file building the Dockerfile next to it, with the instance directory mounted,
+/// the port published and the memory limited like [`Config::memory_limit`].
+pub fn compose_file(server: &DockerServer) -> String {
+    let mut service = format!(
+        "    build: .
+    restart: unless-stopped
+    # Lets `docker attach` reach the console
+    stdin_open: true
+    tty: true
+    ports:
+      - \"{port}:{port}\"
+    volumes:
+      - {volume}
+",
+        port = server.port,
+        volume = yaml_string(&format!("{}:{CONTAINER_DIR}", server.instance_dir)),
+    );
+    if let Some(limit) = server.config.memory_limit() {
+        service.push_str(&format!("    mem_limit: {}m\n", limit / (1024 * 1024)));
+    }
+
+    format!(
+        "services:\n  {}:\n{service}",
+        service_name(server.server_name)
+    )
+}
+
+/// Compose service names only allow letters, digits, `.`, `_` and `-`.
+fn service_name(server_name: &str) -> String {
+    server_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Quoted with JSON's escapes, which YAML's double-quoted strings share.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn config(jar_name: &str) -> Config {
+        let mut config = Config::new_4gb(jar_name.to_string()).unwrap();
+        config.min_memory = "2G".to_string();
+        config.java_home = Some("/usr/lib/jvm/java-21".to_string());
+        config
+    }
+
+    #[test]
+    fn test_fabric() {
+        let config = config("fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar");
+        let server = DockerServer {
+            server_name: "Survival SMP",
+            fork: ServerFork::Fabric,
+            game_version: "1.21.8",
+            config: &config,
+            port: 25566,
+            instance_dir: ".",
+        };
+
+        assert_eq!(
+            dockerfile(&server),
+            "\
+# Generated by mcerv for Survival SMP, a Fabric 1.21.8 server
+FROM eclipse-temurin:21-jre
+WORKDIR /server
+EXPOSE 25566
+CMD [\"java\", \"-Xmx4G\", \"-Xms2G\", \"-jar\", \"fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar\", \"nogui\"]
+"
+        );
+        assert_eq!(
+            compose_file(&server),
+            "\
+services:
+  survival-smp:
+    build: .
+    restart: unless-stopped
+    # Lets `docker attach` reach the console
+    stdin_open: true
+    tty: true
+    ports:
+      - \"25566:25566\"
+    volumes:
+      - \".:/server\"
+    mem_limit: 6144m
+"
+        );
+    }
+
+    #[test]
+    fn test_forge() {
+        let config = config("forge-1.18.2-40.2.0-shim.jar");
+        let server = DockerServer {
+            server_name: "modded",
+            fork: ServerFork::Forge,
+            game_version: "1.18.2",
+            config: &config,
+            port: 25565,
+            instance_dir: "/srv/mcerv/instances/modded",
+        };
+
+        assert_eq!(
+            dockerfile(&server),
+            "\
+# Generated by mcerv for modded, a Forge 1.18.2 server
+FROM eclipse-temurin:17-jre
+WORKDIR /server
+# The Forge jar loads libraries/ from the mounted directory
+EXPOSE 25565
+CMD [\"java\", \"-Xmx4G\", \"-Xms2G\", \"-jar\", \"forge-1.18.2-40.2.0-shim.jar\", \"nogui\"]
+"
+        );
+        assert!(
+            compose_file(&server).contains("      - \"/srv/mcerv/instances/modded:/server\"\n")
+        );
+    }
+
+    #[test]
+    fn test_check_exportable() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            check_exportable(dir.path(), ServerFork::Fabric),
+            Err(DockerExportError::EulaNotAccepted)
+        );
+
+        eula::accept(dir.path()).unwrap();
+        assert_eq!(check_exportable(dir.path(), ServerFork::Fabric), Ok(()));
+        assert_eq!(
+            check_exportable(dir.path(), ServerFork::Forge),
+            Err(DockerExportError::MissingForgeLibraries)
+        );
+
+        fs::create_dir(dir.path().join("libraries")).unwrap();
+        assert_eq!(check_exportable(dir.path(), ServerFork::Forge), Ok(()));
+    }
+
+    #[test]
+    fn test_base_image() {
+        assert_eq!(base_image("1.16.5"), "eclipse-temurin:8-jre");
+        assert_eq!(base_image("1.17.1"), "eclipse-temurin:17-jre");
+        assert_eq!(base_image("25w31a"), "eclipse-temurin:21-jre");
+    }
+}
@@ -0,0 +1,486 @@
+use crate::system::{
+    config::Config,
+    eula,
+    global_config::GlobalConfig,
+    jar_parser,
+    mods::{self, ClientOnlyMod, ModConflict},
+    process,
+    registry::Registry,
+    server_info::ServerInfo,
+};
+use reqwest::Client;
+use std::{
+    fmt::Display,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Below this, installing a server or its mods may run out of space.
+pub const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Worth a look, but doesn't fail the command.
+    Warn,
+    Fail,
+}
+
+/// The outcome of one of `mcerv doctor`'s checks.
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckResult {
+    pub fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, message)
+    }
+
+    pub fn warn(name: &'static str, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, message)
+    }
+
+    pub fn fail(name: &'static str, message: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, message)
+    }
+
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        write!(f, "[{status}] {}: {}", self.name, self.message)
+    }
+}
+
+pub fn instances_dir_writable(dir: &Path) -> CheckResult {
+    const NAME: &str = "Instances directory";
+    let probe = dir.join(".mcerv-doctor");
+    let result = fs::create_dir_all(dir).and_then(|_| fs::write(&probe, b""));
+    let _ = fs::remove_file(&probe);
+
+    match result {
+        Ok(()) => CheckResult::pass(NAME, format!("{} is writable", dir.display())),
+        Err(e) => CheckResult::fail(NAME, format!("{} is not writable: {e}", dir.display())),
+    }
+}
+
+pub fn global_config(path: &Path) -> CheckResult {
+    const NAME: &str = "Global config";
+    if !path.exists() {
+        return CheckResult::pass(NAME, "None, using the defaults");
+    }
+
+    match GlobalConfig::load(path) {
+        Ok(_) => CheckResult::pass(NAME, format!("{} is valid", path.display())),
+        Err(e) => CheckResult::fail(NAME, e.to_string()),
+    }
+}
+
+pub fn registry(path: &Path) -> CheckResult {
+    const NAME: &str = "Server registry";
+    let registry = match Registry::load(path) {
+        Ok(registry) => registry,
+        Err(e) => return CheckResult::fail(NAME, e.to_string()),
+    };
+
+    let dangling: Vec<String> = registry
+        .dangling()
+        .into_iter()
+        .map(|(name, dir)| format!("{name} ({})", dir.display()))
+        .collect();
+    if dangling.is_empty() {
+        CheckResult::pass(
+            NAME,
+            format!(
+                "{} servers outside the instances directory",
+                registry.servers.len()
+            ),
+        )
+    } else {
+        CheckResult::fail(
+            NAME,
+            format!("Missing directories: {}", dangling.join(", ")),
+        )
+    }
+}
+
+/// Reads the server's config without creating it, unlike [`Config::load_or_create`].
+pub fn server_config(server_dir: &Path) -> (CheckResult, Option<Config>) {
+    const NAME: &str = "Server config";
+    let path = server_dir.join("mcerv_config.json");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return (
+                CheckResult::warn(NAME, "Not created yet, it will be on the next start"),
+                None,
+            );
+        }
+        Err(e) => {
+            return (
+                CheckResult::fail(NAME, format!("Failed to read it: {e}")),
+                None,
+            );
+        }
+    };
+
+    let config: Config = match serde_json::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => return (CheckResult::fail(NAME, format!("Invalid: {e}")), None),
+    };
+
+    let result = if server_dir.join(&config.jar_name).is_file() {
+        CheckResult::pass(NAME, format!("Valid, starting {}", config.jar_name))
+    } else {
+        CheckResult::fail(NAME, format!("The jar {} doesn't exist", config.jar_name))
+    };
+    (result, Some(config))
+}
+
+pub fn server_jar(server_dir: &Path) -> (CheckResult, Option<ServerInfo>) {
+    const NAME: &str = "Server jar";
+    let jar = match jar_parser::single_jar(server_dir) {
+        Ok(jar) => jar,
+        Err(e) => return (CheckResult::fail(NAME, e.to_string()), None),
+    };
+
+    let file_name = jar.file_name().unwrap().to_string_lossy().to_string();
+    match ServerInfo::new(&jar) {
+        Ok(info) => (
+            CheckResult::pass(
+                NAME,
+                format!(
+                    "{file_name} is {:?} {}",
+                    info.server_fork, info.game_version
+                ),
+            ),
+            Some(info),
+        ),
+        Err(e) => (
+            CheckResult::fail(NAME, format!("{file_name} isn't recognized: {e}")),
+            None,
+        ),
+    }
+}
+
+/// Runs `java -version` from the configured Java home, or from `PATH` without one.
+pub fn java(java_home: Option<&str>, game_version: Option<&str>) -> CheckResult {
+    const NAME: &str = "Java";
+    let java = match java_home {
+        Some(java_home) => Path::new(java_home).join("bin").join("java"),
+        None => PathBuf::from("java"),
+    };
+
+    let output = match Command::new(&java).arg("-version").output() {
+        Ok(output) => output,
+        Err(e) => {
+            return CheckResult::fail(NAME, format!("Failed to run {}: {e}", java.display()));
+        }
+    };
+
+    // Printed to stderr
+    let output = String::from_utf8_lossy(&output.stderr);
+    let Some(version) = parse_java_version(&output) else {
+        return CheckResult::warn(NAME, "Couldn't tell the version of Java");
+    };
+
+    match game_version.and_then(required_java) {
+        Some(required) if version < required => CheckResult::fail(
+            NAME,
+            format!(
+                "Java {version} is too old, Minecraft {} needs Java {required}",
+                game_version.unwrap()
+            ),
+        ),
+        _ => CheckResult::pass(NAME, format!("Java {version}")),
+    }
+}
+
+/// The major version in the output of `java -version`, like 21 or 8 for `1.8.0_392`.
+pub fn parse_java_version(output: &str) -> Option<u32> {
+    let version = output.split('"').nth(1)?;
+    let mut parts = version.split(['.', '_', '-', '+']);
+    let major: u32 = parts.next()?.parse().ok()?;
+    if major == 1 {
+        return parts.next()?.parse().ok();
+    }
+
+    Some(major)
+}
+
+/// The oldest Java that runs the release, or `None` for versions like snapshots.
+pub fn required_java(game_version: &str) -> Option<u32> {
+    let mut parts = game_version.split('.');
+    if parts.next()? != "1" {
+        return None;
+    }
+    let minor: u32 = parts.next()?.parse().ok()?;
+    let patch: u32 = match parts.next() {
+        Some(patch) => patch.parse().ok()?,
+        None => 0,
+    };
+
+    Some(match (minor, patch) {
+        (21.., _) | (20, 5..) => 21,
+        (18.., _) => 17,
+        (17, _) => 16,
+        _ => 8,
+    })
+}
+
+pub fn eula(server_dir: &Path) -> CheckResult {
+    const NAME: &str = "EULA";
+    if eula::is_accepted(server_dir) {
+        CheckResult::pass(NAME, "Accepted")
+    } else {
+        CheckResult::fail(NAME, "Not accepted, the server won't start")
+    }
+}
+
+/// `other_servers` are the names and directories of the other servers.
+pub fn port(server_dir: &Path, other_servers: &[(String, PathBuf)]) -> CheckResult {
+    const NAME: &str = "Port";
+    let port = process::configured_port(server_dir);
+    if let Ok(Some(status)) = process::running_process(server_dir) {
+        return CheckResult::pass(
+            NAME,
+            format!("{port}, used by the running server (pid {})", status.pid),
+        );
+    }
+
+    if process::is_port_in_use(port) {
+        return CheckResult::fail(NAME, format!("{port} is already in use"));
+    }
+
+    let sharing: Vec<&str> = other_servers
+        .iter()
+        .filter(|(_, dir)| process::configured_port(dir) == port)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !sharing.is_empty() {
+        return CheckResult::warn(
+            NAME,
+            format!(
+                "{port} is also configured for {}, they can't run at once",
+                sharing.join(", ")
+            ),
+        );
+    }
+
+    CheckResult::pass(NAME, format!("{port} is free"))
+}
+
+/// Mods with the same ID in more than one jar, which the loader refuses to start with.
+/// Jars with the same mod ID, or declaring they break or conflict with another.
+pub fn mod_conflicts(mods_dir: &Path, game_version: &str) -> CheckResult {
+    const NAME: &str = "Mods";
+    if !mods_dir.exists() {
+        return CheckResult::pass(NAME, "No mods directory");
+    }
+
+    let conflicts = match mods::mod_jars(mods_dir, game_version)
+        .and_then(|jars| Ok((jars.len(), mods::find_conflicts(&jars)?)))
+    {
+        Ok(found) => found,
+        Err(e) => return CheckResult::fail(NAME, format!("Failed to list the mods: {e}")),
+    };
+
+    match conflicts {
+        (jars, conflicts) if conflicts.is_empty() => {
+            CheckResult::pass(NAME, format!("{jars} jars, no duplicates or conflicts"))
+        }
+        (_, conflicts) => {
+            let message = conflicts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ");
+            if conflicts.iter().any(ModConflict::is_fatal) {
+                CheckResult::fail(NAME, message)
+            } else {
+                CheckResult::warn(NAME, message)
+            }
+        }
+    }
+}
+
+pub fn client_only_mods(
+    server_name: &str,
+    found: anyhow::Result<Vec<ClientOnlyMod>>,
+) -> CheckResult {
+    const NAME: &str = "Client-only mods";
+    match found {
+        Ok(found) if found.is_empty() => CheckResult::pass(NAME, "None found"),
+        Ok(found) => {
+            let names: Vec<&str> = found.iter().map(|found| found.name.as_str()).collect();
+            CheckResult::warn(
+                NAME,
+                format!(
+                    "{} may crash the server. Run `mcerv check client-only {server_name}` to disable or remove them",
+                    names.join(", ")
+                ),
+            )
+        }
+        Err(e) => CheckResult::warn(NAME, format!("Failed to check the mods: {e}")),
+    }
+}
+
+/// `.part` files left by downloads that were interrupted.
+pub fn leftover_downloads(dirs: &[PathBuf]) -> CheckResult {
+    const NAME: &str = "Leftover downloads";
+    let mut leftovers = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        leftovers.extend(entries.flatten().map(|entry| entry.path()).filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "part")
+        }));
+    }
+
+    match leftovers.as_slice() {
+        [] => CheckResult::pass(NAME, "None"),
+        [path] => CheckResult::warn(NAME, format!("{} can be deleted", path.display())),
+        paths => CheckResult::warn(
+            NAME,
+            format!(
+                "{} files can be deleted, like {}",
+                paths.len(),
+                paths[0].display()
+            ),
+        ),
+    }
+}
+
+pub fn disk_space(dir: &Path, threshold: u64) -> CheckResult {
+    const NAME: &str = "Disk space";
+    match free_space(dir) {
+        Some(free) if free < threshold => {
+            CheckResult::fail(NAME, format!("Only {:.1} GB free", free as f64 / 1e9))
+        }
+        Some(free) => CheckResult::pass(NAME, format!("{:.1} GB free", free as f64 / 1e9)),
+        None => CheckResult::warn(NAME, "Not checked on this platform"),
+    }
+}
+
+#[cfg(unix)]
+fn free_space(dir: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+pub async fn reachable(client: &Client, name: &'static str, url: &str) -> CheckResult {
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => CheckResult::pass(name, "Reachable"),
+        Ok(response) => CheckResult::fail(name, format!("{url} responded {}", response.status())),
+        Err(e) => CheckResult::fail(name, format!("Failed to reach {url}: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_java_version() {
+        let openjdk = "openjdk version \"21.0.4\" 2024-07-16 LTS\nOpenJDK Runtime Environment";
+        assert_eq!(parse_java_version(openjdk), Some(21));
+        assert_eq!(parse_java_version("java version \"1.8.0_392\""), Some(8));
+        assert_eq!(parse_java_version("openjdk version \"17\""), Some(17));
+        assert_eq!(parse_java_version("no version here"), None);
+    }
+
+    #[test]
+    fn test_required_java() {
+        assert_eq!(required_java("1.21.8"), Some(21));
+        assert_eq!(required_java("1.20.5"), Some(21));
+        assert_eq!(required_java("1.20.4"), Some(17));
+        assert_eq!(required_java("1.18"), Some(17));
+        assert_eq!(required_java("1.17.1"), Some(16));
+        assert_eq!(required_java("1.12.2"), Some(8));
+        assert_eq!(required_java("25w14a"), None);
+    }
+
+    #[test]
+    fn test_leftover_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let dirs = [dir.path().to_path_buf(), dir.path().join("missing")];
+        assert_eq!(leftover_downloads(&dirs).status, CheckStatus::Pass);
+
+        fs::write(dir.path().join("abc.123.part"), b"").unwrap();
+        fs::write(dir.path().join("lithium.jar"), b"").unwrap();
+        let result = leftover_downloads(&dirs);
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert!(result.message.contains("abc.123.part"));
+    }
+
+    #[test]
+    fn test_server_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(server_config(dir.path()).0.status, CheckStatus::Warn);
+
+        fs::write(dir.path().join("mcerv_config.json"), "{").unwrap();
+        assert_eq!(server_config(dir.path()).0.status, CheckStatus::Fail);
+
+        let config = Config::new_4gb("server.jar".to_string()).unwrap();
+        fs::write(
+            dir.path().join("mcerv_config.json"),
+            serde_json::to_string(&config).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(server_config(dir.path()).0.status, CheckStatus::Fail);
+
+        fs::write(dir.path().join("server.jar"), b"").unwrap();
+        let (result, config) = server_config(dir.path());
+        assert_eq!(result.status, CheckStatus::Pass);
+        assert_eq!(config.unwrap().jar_name, "server.jar");
+    }
+
+    #[test]
+    fn test_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("registry.json");
+        assert_eq!(registry(&path).status, CheckStatus::Pass);
+
+        let mut servers = Registry::default();
+        servers.register("survival", dir.path().join("missing"));
+        servers.save(&path).unwrap();
+        let result = registry(&path);
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.message.contains("survival"));
+
+        fs::write(&path, "{").unwrap();
+        assert_eq!(registry(&path).status, CheckStatus::Fail);
+    }
+}
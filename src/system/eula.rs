@@ -0,0 +1,181 @@
+use std::{error::Error, fmt::Display, fs, io, path::Path};
+
+pub const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+
+#[derive(Debug)]
+pub struct EulaNotAcceptedError;
+
+impl Display for EulaNotAcceptedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The Minecraft EULA ({EULA_URL}) must be accepted before starting the server. \
+            Pass --yes or run `mcerv accept-eula` to accept it"
+        )
+    }
+}
+
+impl Error for EulaNotAcceptedError {}
+
+/// Whether `eula.txt` in the server directory says `eula=true`.
+pub fn is_accepted(server_dir: &Path) -> bool {
+    fs::read_to_string(server_dir.join("eula.txt"))
+        .is_ok_and(|content| is_accepted_content(&content))
+}
+
+/// Sets `eula=true` in `eula.txt`, creating it if it's missing. Only the `eula` line of an
+/// existing one is changed, keeping the comments and line endings the server wrote. Returns
+/// whether it was already accepted, in which case the file is left alone.
+pub fn accept(server_dir: &Path) -> io::Result<bool> {
+    let path = server_dir.join("eula.txt");
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if existing.as_deref().is_some_and(is_accepted_content) {
+        return Ok(true);
+    }
+
+    fs::create_dir_all(server_dir)?;
+    fs::write(path, accepted_content(existing.as_deref()))?;
+    Ok(false)
+}
+
+fn is_accepted_content(content: &str) -> bool {
+    content
+        .lines()
+        .filter_map(eula_value)
+        .next_back()
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+/// The value of the line if it sets the `eula` key, like `eula=false` or `eula = false`.
+fn eula_value(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with(['#', '!']) {
+        return None;
+    }
+    let (key, value) = line.split_once(['=', ':'])?;
+    (key.trim() == "eula").then(|| value.trim())
+}
+
+/// The content of `eula.txt` with the EULA accepted, based on the existing content.
+fn accepted_content(existing: Option<&str>) -> String {
+    let Some(existing) = existing else {
+        return format!(
+            "# This file is generated by mcerv and is generated because the user agreed to the Minecraft EULA ({EULA_URL}).\neula=true"
+        );
+    };
+
+    let mut found = false;
+    let mut content = String::with_capacity(existing.len());
+    for line in existing.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if eula_value(text).is_some() {
+            found = true;
+            content.push_str("eula=true");
+            content.push_str(&line[text.len()..]);
+        } else {
+            content.push_str(line);
+        }
+    }
+    if !found {
+        let newline = if existing.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let ends_with_newline = existing.ends_with('\n');
+        if !existing.is_empty() && !ends_with_newline {
+            content.push_str(newline);
+        }
+        content.push_str("eula=true");
+        if ends_with_newline {
+            content.push_str(newline);
+        }
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepted_content_keeps_server_comments() {
+        let existing = "#By changing the setting below to TRUE you are indicating your agreement to our EULA (https://aka.ms/MinecraftEULA).\n#Tue Aug 12 10:00:00 CEST 2025\neula=false\n";
+        assert_eq!(
+            accepted_content(Some(existing)),
+            "#By changing the setting below to TRUE you are indicating your agreement to our EULA (https://aka.ms/MinecraftEULA).\n#Tue Aug 12 10:00:00 CEST 2025\neula=true\n"
+        );
+    }
+
+    #[test]
+    fn test_accepted_content_without_eula_line() {
+        assert_eq!(accepted_content(Some("#comment")), "#comment\neula=true");
+        assert!(accepted_content(None).ends_with("\neula=true"));
+    }
+
+    #[test]
+    fn test_accepted_content_keeps_crlf() {
+        let existing = "#By changing the setting below to TRUE you are indicating your agreement to our EULA (https://aka.ms/MinecraftEULA).\r\n#Tue Aug 12 10:00:00 CEST 2025\r\neula=false\r\n";
+        assert_eq!(
+            accepted_content(Some(existing)),
+            existing.replace("eula=false", "eula=true")
+        );
+        assert_eq!(
+            accepted_content(Some("#comment\r\n")),
+            "#comment\r\neula=true\r\n"
+        );
+        assert_eq!(
+            accepted_content(Some("#comment\r\neula = false")),
+            "#comment\r\neula=true"
+        );
+        // Only the key is looked at, not comments mentioning it
+        assert_eq!(
+            accepted_content(Some("#eula=false\n")),
+            "#eula=false\neula=true\n"
+        );
+    }
+
+    #[test]
+    fn test_accept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eula.txt");
+        assert!(!accept(dir.path()).unwrap());
+        assert!(is_accepted(dir.path()));
+
+        // Left alone when it's already accepted
+        fs::write(&path, "#comment\r\neula = TRUE\r\n").unwrap();
+        assert!(accept(dir.path()).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "#comment\r\neula = TRUE\r\n"
+        );
+
+        fs::write(&path, "#comment\r\n").unwrap();
+        assert!(!is_accepted(dir.path()));
+        assert!(!accept(dir.path()).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "#comment\r\neula=true\r\n"
+        );
+    }
+
+    #[test]
+    fn test_is_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_accepted(dir.path()));
+
+        fs::write(dir.path().join("eula.txt"), "#comment\neula=false\n").unwrap();
+        assert!(!is_accepted(dir.path()));
+
+        accept(dir.path()).unwrap();
+        assert!(is_accepted(dir.path()));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("eula.txt")).unwrap(),
+            "#comment\neula=true\n"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::system::server_name;
+use std::{fs, io, path::Path};
+
+/// The longest file name most file systems allow, in bytes.
+const MAX_LENGTH: usize = 255;
+
+/// Whether a file name from a remote source can be joined to a directory as is: a single
+/// path component that isn't hidden, has no control characters, and means the same file
+/// on every platform.
+pub fn is_safe_filename(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_LENGTH
+        && !name.starts_with('.')
+        && !name.ends_with(['.', ' '])
+        && !name
+            .chars()
+            .any(|c| matches!(c, '/' | '\\' | ':') || c.is_control())
+        && !server_name::is_windows_reserved(name)
+}
+
+/// The file name to save a remote file as. Unsafe names, see [`is_safe_filename`], are
+/// replaced by one made of the file's hash, keeping a plain extension like `jar`.
+pub fn sanitize_filename(name: &str, hash: &str) -> String {
+    if is_safe_filename(name) {
+        return name.to_string();
+    }
+
+    let mut stem: String = hash
+        .chars()
+        .filter(char::is_ascii_hexdigit)
+        .take(16)
+        .collect();
+    if stem.is_empty() {
+        stem = "download".to_string();
+    }
+    let extension = name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .filter(|extension| {
+            (1..=8).contains(&extension.len())
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        .unwrap_or("jar");
+    format!("{stem}.{}", extension.to_ascii_lowercase())
+}
+
+/// Removes the file, if there is one.
+pub fn remove_if_exists(path: impl AsRef<Path>) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_safe_names() {
+        for name in [
+            "sodium-fabric-0.6.13+mc1.21.8.jar",
+            "Lithium 0.18.jar",
+            "fabric-api-0.129.0+1.21.8.jar",
+            "console.jar",
+        ] {
+            assert_eq!(sanitize_filename(name, HASH), name);
+        }
+    }
+
+    #[test]
+    fn test_malicious_names() {
+        let mods_dir = Path::new("instances/smp/mods");
+        for name in [
+            "../../start_script.sh",
+            "..",
+            ".",
+            "",
+            "/etc/cron.d/evil",
+            "C:\\Windows\\evil.dll",
+            "C:evil.jar",
+            "..\\..\\evil.jar",
+            "mods/../../evil.jar",
+            ".hidden.jar",
+            "...jar",
+            "evil.jar\0.txt",
+            "evil\n.jar",
+            "evil.jar.",
+            "evil.jar ",
+            "NUL.jar",
+            "com1.jar",
+            "file.jar:stream",
+            &"a".repeat(MAX_LENGTH + 1),
+        ] {
+            let sanitized = sanitize_filename(name, HASH);
+            assert!(
+                is_safe_filename(&sanitized),
+                "{name:?} became {sanitized:?}"
+            );
+            assert_eq!(
+                mods_dir.join(&sanitized).parent(),
+                Some(mods_dir),
+                "{name:?} left the mods directory"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fallback_name() {
+        assert_eq!(
+            sanitize_filename("../../start_script.sh", HASH),
+            "0123456789abcdef.sh"
+        );
+        assert_eq!(
+            sanitize_filename("../evil.JAR", HASH),
+            "0123456789abcdef.jar"
+        );
+        assert_eq!(sanitize_filename("..", HASH), "0123456789abcdef.jar");
+        assert_eq!(
+            sanitize_filename("/etc/passwd", HASH),
+            "0123456789abcdef.jar"
+        );
+        // The hash is remote too
+        assert_eq!(sanitize_filename("..", "../../x"), "download.jar");
+    }
+
+    #[test]
+    fn test_remove_if_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.sh");
+
+        assert!(remove_if_exists(&path).is_ok());
+
+        fs::write(&path, "").unwrap();
+        assert!(remove_if_exists(&path).is_ok());
+        assert!(!path.exists());
+    }
+}
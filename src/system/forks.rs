@@ -1,22 +1,35 @@
 use crate::{
     network::{
-        PrintVersionMode,
+        DownloadStats, PrintVersionMode, VersionEntry,
         fabric_meta::{self},
         forge_meta, vanilla_meta,
     },
     server_dir,
     system::cli,
+    system::filename::remove_if_exists,
+    system::installer::ServerInstaller,
     system::jar_parser,
+    system::start_template::StartTemplate,
+    system::version,
 };
 use anyhow::anyhow;
 use async_trait::async_trait;
 use clap::Parser;
 use clap::Subcommand;
+use indicatif::{ProgressBar, ProgressStyle};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use std::{
     error::Error,
     fmt::Display,
-    io::{Read, Seek},
+    fs::File,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
     process::Command,
 };
 use zip::ZipArchive;
@@ -24,7 +37,8 @@ use zip::ZipArchive;
 /// This macro defines server forks by:
 /// 1. Creating [`ServerFork`] enum for matching convenience.
 /// 2. Creating empty structs for [`Fork`] implementations.
-/// 3. Creating [`detect_fork_from_main_class`] function.
+/// 3. Creating [`detect_fork_from_main_class`] function, and [`ServerFork`] methods
+///    dispatching to each fork's [`Fork`] implementation.
 /// 4. Creating [`FetchCommands`] and [`InstallCommands`] enums for CLI.
 ///
 /// # Usage
@@ -46,7 +60,8 @@ macro_rules! __define_forks {
             $variant:ident => ( $version_args:ty $(,$fetch_filter:ty)? ) ),*
         $(,)?
     ) => {
-        #[derive(Debug, Clone, Copy)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+        #[non_exhaustive]
         pub enum ServerFork {
             $($variant),*
         }
@@ -54,17 +69,60 @@ macro_rules! __define_forks {
         use std::ffi::OsString;
         impl ServerFork {
             pub fn parse_version_args<I, T>(&self, command: I) -> InstallCommand
+            where I: IntoIterator<Item = T>,
+                  T: Into<OsString> + Clone
+            {
+                self.try_parse_version_args(command).unwrap_or_else(|e| e.exit())
+            }
+
+            pub fn try_parse_version_args<I, T>(&self, command: I) -> Result<InstallCommand, clap::Error>
             where I: IntoIterator<Item = T>,
                   T: Into<OsString> + Clone
             {
                 match self {
                     $(
-                        ServerFork::$variant => InstallCommand::$variant {
-                                version_args: <$version_args>::try_parse_from(command).unwrap_or_else(|e| e.exit())
-                        },
+                        ServerFork::$variant => Ok(InstallCommand::$variant {
+                                version_args: <$version_args>::try_parse_from(command)?
+                        }),
                     )*
                 }
             }
+
+            /// The name of the fork like `Fabric`, which [`ServerFork::from_str`] parses back.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        ServerFork::$variant => stringify!($variant),
+                    )*
+                }
+            }
+
+            /// The game version in the jar of this fork, see [`Fork::game_version`].
+            pub(crate) fn jar_game_version<R: Read + Seek>(
+                &self,
+                archive: &mut ZipArchive<R>
+            ) -> anyhow::Result<String> {
+                match self {
+                    $(
+                        ServerFork::$variant => $variant::game_version(archive),
+                    )*
+                }
+            }
+        }
+
+        impl std::str::FromStr for ServerFork {
+            type Err = DetectServerInfoError;
+
+            /// Parses the fork name case-insensitively, like `fabric`.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(
+                    if s.eq_ignore_ascii_case(stringify!($variant)) {
+                        return Ok(ServerFork::$variant);
+                    }
+                )*
+
+                Err(DetectServerInfoError::UnknownServerFork)
+            }
         }
 
         $(
@@ -93,6 +151,26 @@ macro_rules! __define_forks {
             )*
         }
 
+        impl InstallCommand {
+            /// An installer with the versions of the arguments chosen, see [`cli::Versions::installer`].
+            pub fn installer(&self) -> anyhow::Result<ServerInstaller> {
+                match self {
+                    $(
+                        InstallCommand::$variant { version_args } => cli::Versions::installer(version_args),
+                    )*
+                }
+            }
+
+            /// Whether the versions are all given, so none would be picked interactively.
+            pub fn has_all_versions(&self) -> bool {
+                match self {
+                    $(
+                        InstallCommand::$variant { version_args } => cli::Versions::has_all_versions(version_args),
+                    )*
+                }
+            }
+        }
+
         #[derive(Subcommand)]
         pub enum FetchCommand {
             $(
@@ -187,7 +265,7 @@ macro_rules! define_forks {
 define_forks!(
     Vanilla => (cli::VanillaVersionArgs, cli::VersionsFilter),
     Fabric => (cli::FabricVersionArgs, cli::VersionsFilter),
-    Forge => (cli::ForgeVersionArgs, ()),
+    Forge => (cli::ForgeVersionArgs, cli::ForgeVersionsFilter),
 );
 
 #[derive(Debug, Clone)]
@@ -195,6 +273,7 @@ pub enum DetectServerInfoError {
     MainClassNotFound,
     UnknownServerFork,
     GameVersionNotFound,
+    InstallerJar,
 }
 
 impl Display for DetectServerInfoError {
@@ -212,12 +291,145 @@ impl Display for DetectServerInfoError {
             DetectServerInfoError::GameVersionNotFound => {
                 write!(f, "Game version not found in install.properties")
             }
+            DetectServerInfoError::InstallerJar => {
+                write!(f, "This is an installer, not a server jar")
+            }
         }
     }
 }
 
 impl Error for DetectServerInfoError {}
 
+#[derive(Debug)]
+pub enum ForgeInstallError {
+    JavaNotFound(io::Error),
+    InstallerFailed(ExitStatus, PathBuf),
+    /// Neither a shim jar nor the argument files were made.
+    NoLaunchFiles(PathBuf),
+}
+
+impl Display for ForgeInstallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForgeInstallError::JavaNotFound(e) => write!(
+                f,
+                "Failed to run the Forge installer ({e}). Java is required on PATH to install Forge"
+            ),
+            ForgeInstallError::InstallerFailed(status, log_path) => write!(
+                f,
+                "Forge installer failed with status: {status}. See {log_path:?} for the full log"
+            ),
+            ForgeInstallError::NoLaunchFiles(log_path) => write!(
+                f,
+                "The Forge installer made neither a shim jar nor argument files to start the server with. See {log_path:?} for the full log"
+            ),
+        }
+    }
+}
+
+impl Error for ForgeInstallError {}
+
+#[derive(Debug)]
+pub enum InvalidServerJarError {
+    NotAJar(PathBuf),
+    WrongFork {
+        path: PathBuf,
+        expected: ServerFork,
+        actual: ServerFork,
+    },
+}
+
+impl Display for InvalidServerJarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidServerJarError::NotAJar(path) => {
+                write!(f, "Downloaded file is not a valid server jar: {path:?}")
+            }
+            InvalidServerJarError::WrongFork {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Downloaded jar {path:?} is a {actual:?} server, expected {expected:?}"
+            ),
+        }
+    }
+}
+
+impl Error for InvalidServerJarError {}
+
+/// The version of one component of a server jar, like the game or the Fabric loader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentVersion {
+    pub component: &'static str,
+    pub version: String,
+}
+
+impl ComponentVersion {
+    fn new(component: &'static str, version: impl Into<String>) -> Self {
+        Self {
+            component,
+            version: version.into(),
+        }
+    }
+}
+
+/// The latest stable versions of a fork's components.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatestVersions {
+    pub components: Vec<ComponentVersion>,
+    /// The `update-server-jar` version arguments that install these versions.
+    pub version_args: Vec<String>,
+}
+
+/// A component's installed version next to its latest one.
+#[derive(Debug, PartialEq)]
+pub struct ComponentUpdate {
+    pub component: &'static str,
+    /// `None` if it can't be told from the jar.
+    pub installed: Option<String>,
+    pub latest: String,
+}
+
+impl ComponentUpdate {
+    /// A game version newer than the latest stable one, like a snapshot, is up-to-date too.
+    pub fn is_up_to_date(&self) -> bool {
+        let Some(installed) = &self.installed else {
+            return false;
+        };
+        *installed == self.latest
+            || (self.component == "game" && version::is_newer(installed, &self.latest))
+    }
+}
+
+impl Display for ComponentUpdate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let installed = self.installed.as_deref().unwrap_or("unknown");
+        if self.is_up_to_date() {
+            write!(f, "{}: `{installed}` [OK] up-to-date", self.component)
+        } else {
+            write!(f, "{}: `{installed}` -> `{}`", self.component, self.latest)
+        }
+    }
+}
+
+/// Pairs each of the latest components with its installed version.
+pub fn component_updates(
+    installed: &[ComponentVersion],
+    latest: &LatestVersions,
+) -> Vec<ComponentUpdate> {
+    latest
+        .components
+        .iter()
+        .map(|latest| ComponentUpdate {
+            component: latest.component,
+            installed: component(installed, latest.component).map(String::from),
+            latest: latest.version.clone(),
+        })
+        .collect()
+}
+
 #[async_trait]
 pub trait Fork {
     type FetchConfig;
@@ -227,14 +439,37 @@ pub trait Fork {
 
     fn game_version<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<String>;
 
+    /// Installs the server jar, returning its file name and what its download took.
     async fn install(
         server_name: &str,
         version: Self::Version,
         client: &Client,
-    ) -> anyhow::Result<String>;
+    ) -> anyhow::Result<(String, DownloadStats)>;
+
+    async fn fetch_availables(
+        config: Self::FetchConfig,
+        client: &Client,
+    ) -> anyhow::Result<Vec<VersionEntry>>;
+
+    /// The versions of the jar's components that can be told from it. Versions that are
+    /// only recorded in the name mcerv downloaded the jar as are read from `jar_name`.
+    fn installed_versions<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        jar_name: &str,
+    ) -> anyhow::Result<Vec<ComponentVersion>>;
 
-    async fn fetch_availables(config: Self::FetchConfig, client: &Client)
-    -> anyhow::Result<String>;
+    async fn latest_versions(client: &Client) -> anyhow::Result<LatestVersions>;
+
+    /// The install version arguments that reinstall the components of [`Fork::installed_versions`].
+    fn version_args(installed: &[ComponentVersion]) -> Vec<String>;
+}
+
+/// The version of the component, if it's among the versions.
+fn component<'a>(versions: &'a [ComponentVersion], component: &str) -> Option<&'a str> {
+    versions
+        .iter()
+        .find(|v| v.component == component)
+        .map(|v| v.version.as_str())
 }
 
 #[async_trait]
@@ -262,15 +497,43 @@ impl Fork for Vanilla {
         server_name: &str,
         version: Self::Version,
         client: &Client,
-    ) -> anyhow::Result<String> {
-        let server_dir = server_dir(server_name);
-        vanilla_meta::download_server(client, &version, &server_dir).await
+    ) -> anyhow::Result<(String, DownloadStats)> {
+        let server_dir = server_dir(server_name)?;
+        let (filename, stats) =
+            vanilla_meta::download_server(client, &version, &server_dir).await?;
+        verify_server_jar(server_dir.join(&filename), ServerFork::Vanilla)?;
+        Ok((filename, stats))
     }
 
-    async fn fetch_availables(all: bool, client: &Client) -> anyhow::Result<String> {
+    async fn fetch_availables(all: bool, client: &Client) -> anyhow::Result<Vec<VersionEntry>> {
         let mode = PrintVersionMode::from_all_flag(all);
         vanilla_meta::versions(client, mode).await
     }
+
+    fn installed_versions<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        _jar_name: &str,
+    ) -> anyhow::Result<Vec<ComponentVersion>> {
+        Ok(vec![ComponentVersion::new(
+            "game",
+            ServerFork::Vanilla.game_version(archive)?,
+        )])
+    }
+
+    async fn latest_versions(client: &Client) -> anyhow::Result<LatestVersions> {
+        let version = vanilla_meta::fetch_latest_stable_version(client).await?;
+        Ok(LatestVersions {
+            components: vec![ComponentVersion::new("game", &version)],
+            version_args: vec![version],
+        })
+    }
+
+    fn version_args(installed: &[ComponentVersion]) -> Vec<String> {
+        match component(installed, "game") {
+            Some(version) => vec![version.to_string()],
+            None => vec!["--latest-stable".to_string()],
+        }
+    }
 }
 
 #[async_trait]
@@ -298,20 +561,94 @@ impl Fork for Fabric {
         server_name: &str,
         version: Self::Version,
         client: &Client,
-    ) -> anyhow::Result<String> {
-        let server_dir = server_dir(server_name);
-        fabric_meta::download_server(client, &version.0, &version.1, &version.2, &server_dir).await
+    ) -> anyhow::Result<(String, DownloadStats)> {
+        let server_dir = server_dir(server_name)?;
+        let (game_version, loader_version, installer_version) = &version;
+        let (filename, stats) = fabric_meta::download_server(
+            client,
+            game_version,
+            loader_version,
+            installer_version,
+            &server_dir,
+        )
+        .await?;
+
+        if let Err(e) = verify_server_jar(server_dir.join(&filename), ServerFork::Fabric) {
+            // Fabric meta may return junk for mismatched versions, find out which one is wrong
+            if let Ok(versions) = fabric_meta::versions(client).await {
+                let unknown =
+                    versions.unknown_versions(game_version, loader_version, installer_version);
+                for version in unknown {
+                    println!("{version} was not found in fabric meta, it is likely wrong");
+                }
+            }
+            return Err(e);
+        }
+
+        Ok((filename, stats))
     }
 
-    async fn fetch_availables(all: bool, client: &Client) -> anyhow::Result<String> {
+    async fn fetch_availables(all: bool, client: &Client) -> anyhow::Result<Vec<VersionEntry>> {
         let mode = PrintVersionMode::from_all_flag(all);
-        fabric_meta::versions(client, mode).await
+        Ok(fabric_meta::versions(client).await?.entries(mode))
+    }
+
+    fn installed_versions<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        jar_name: &str,
+    ) -> anyhow::Result<Vec<ComponentVersion>> {
+        let content = jar_parser::read_file(archive, "install.properties")?;
+        let install_properties = jar_parser::parse_properties(&content);
+
+        let mut versions = vec![ComponentVersion::new("game", Self::game_version(archive)?)];
+        if let Some(loader_version) = install_properties.get("fabric-loader-version") {
+            versions.push(ComponentVersion::new("loader", loader_version));
+        }
+        // Named by `fabric_meta::download_server`, like `fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar`
+        if let Some(installer_version) = jar_name
+            .strip_suffix(".jar")
+            .and_then(|name| name.rsplit_once("-launcher."))
+            .map(|(_, version)| version)
+        {
+            versions.push(ComponentVersion::new("installer", installer_version));
+        }
+
+        Ok(versions)
+    }
+
+    async fn latest_versions(client: &Client) -> anyhow::Result<LatestVersions> {
+        let (game_version, loader_version, installer_version) =
+            fabric_meta::versions(client).await?.latest_stable()?;
+        Ok(LatestVersions {
+            components: vec![
+                ComponentVersion::new("game", &game_version),
+                ComponentVersion::new("loader", &loader_version),
+                ComponentVersion::new("installer", &installer_version),
+            ],
+            version_args: vec![game_version, loader_version, installer_version],
+        })
+    }
+
+    fn version_args(installed: &[ComponentVersion]) -> Vec<String> {
+        let mut args = Vec::new();
+        for name in ["game", "loader", "installer"] {
+            match component(installed, name) {
+                Some(version) => args.push(version.to_string()),
+                // The positional arguments after a missing one can't be given
+                None => break,
+            }
+        }
+        if args.len() < 3 {
+            // Fills in the missing versions
+            args.push("--latest-stable".to_string());
+        }
+        args
     }
 }
 
 #[async_trait]
 impl Fork for Forge {
-    type FetchConfig = ();
+    type FetchConfig = cli::ForgeVersionsFilter;
     type Version = String;
 
     fn is_this_fork(main_class: &str) -> bool {
@@ -319,87 +656,458 @@ impl Fork for Forge {
     }
 
     fn game_version<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<String> {
-        // Game version property is stored in `bootstrap-shim.list`
-        // The line format goes like:
-        // HASH net.minecraftforge:forge:1.21.8-58.1.0:server net/minecraftforge/forge/1.21.8-58.1.0/forge-1.21.8-58.1.0-server.jar
-
-        let content = jar_parser::read_file(archive, "bootstrap-shim.list")?;
-        let line = content
-            .lines()
-            .find(|line| line.contains("net.minecraftforge:forge:") && line.contains(":server"))
-            .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
-        let long_version = line
-            .split(':')
-            .nth(2)
-            .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
-        let game_version = long_version
-            .split('-')
-            .next()
-            .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
-
-        Ok(game_version.to_string())
+        let (game_version, _) = forge_version(archive)?;
+        Ok(game_version)
     }
 
     async fn install(
         server_name: &str,
         version: Self::Version,
         client: &Client,
-    ) -> anyhow::Result<String> {
-        let server_dir = server_dir(server_name);
-        let installer_name = forge_meta::download_installer(client, &version, &server_dir).await?;
-
-        let status = Command::new("java")
-            .arg("-jar")
-            .arg(&installer_name)
-            .arg("--installServer")
-            .current_dir(&server_dir)
-            .status()
-            .expect("Failed to execute Forge installer");
-
-        if !status.success() {
-            anyhow::bail!("Forge installer failed with status: {:?}", status);
-        }
+    ) -> anyhow::Result<(String, DownloadStats)> {
+        let server_dir = server_dir(server_name)?;
+        println!("Downloading Forge installer...");
+        crate::phase("downloading forge installer");
+        let (installer_name, stats) =
+            forge_meta::download_installer(client, &version, &server_dir).await?;
+
+        // Most of the install time is spent here, not in mcerv's download
+        println!(
+            "Running Forge installer, which downloads the libraries. This can take a few minutes..."
+        );
+        crate::phase("installing forge");
+        let log_path = server_dir.join("forge-install.log");
+        let command = forge_installer_command(&server_dir, &installer_name);
+        run_forge_installer(command, &log_path).await?;
 
         // Delete the installer jar
         std::fs::remove_file(server_dir.join(installer_name))?;
 
-        // Delete default start scripts generated by Forge installer
+        let layout = ForgeLayout::detect(&server_dir, &version)
+            .ok_or(anyhow!(ForgeInstallError::NoLaunchFiles(log_path)))?;
+
+        // Delete default start scripts generated by Forge installer.
+        // Older installers don't create all of them.
         // See https://github.com/Bowen951209/mcerv/issues/19#issuecomment-3268600074
-        std::fs::remove_file(server_dir.join("run.bat"))?;
-        std::fs::remove_file(server_dir.join("run.sh"))?;
-        std::fs::remove_file(server_dir.join("user_jvm_args.txt"))?;
+        for file in ["run.bat", "run.sh"] {
+            remove_if_exists(server_dir.join(file))?;
+        }
+        // The argument files layout starts with it
+        if layout == ForgeLayout::Shim {
+            remove_if_exists(server_dir.join(USER_JVM_ARGS_FILE))?;
+        }
 
         println!("Removed installer stuff");
 
-        // Return the server jar file name
-        Ok(format!("forge-{version}-shim.jar"))
+        // Return the file the config starts the server with
+        let filename = layout.launch_file(&version);
+        if layout == ForgeLayout::Shim {
+            verify_server_jar(server_dir.join(&filename), ServerFork::Forge)?;
+        }
+        Ok((filename, stats))
+    }
+
+    async fn fetch_availables(
+        filter: cli::ForgeVersionsFilter,
+        client: &Client,
+    ) -> anyhow::Result<Vec<VersionEntry>> {
+        forge_meta::versions(client, filter.game.as_deref(), filter.latest_only).await
+    }
+
+    fn installed_versions<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        _jar_name: &str,
+    ) -> anyhow::Result<Vec<ComponentVersion>> {
+        let (game_version, build) = forge_version(archive)?;
+        Ok(vec![
+            ComponentVersion::new("game", game_version),
+            ComponentVersion::new("forge build", build),
+        ])
+    }
+
+    async fn latest_versions(client: &Client) -> anyhow::Result<LatestVersions> {
+        let version = forge_meta::fetch_latest_version(client).await?;
+        let (game_version, build) = version
+            .split_once('-')
+            .ok_or(anyhow!("Invalid Forge version: {version}"))?;
+        Ok(LatestVersions {
+            components: vec![
+                ComponentVersion::new("game", game_version),
+                ComponentVersion::new("forge build", build),
+            ],
+            version_args: vec![version.clone()],
+        })
+    }
+
+    fn version_args(installed: &[ComponentVersion]) -> Vec<String> {
+        match (
+            component(installed, "game"),
+            component(installed, "forge build"),
+        ) {
+            (Some(game_version), Some(build)) => vec![format!("{game_version}-{build}")],
+            _ => vec!["--latest".to_string()],
+        }
+    }
+}
+
+/// Reads the (game version, forge build) of a Forge shim jar.
+fn forge_version<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<(String, String)> {
+    // Versions are stored in `bootstrap-shim.list`
+    // The line format goes like:
+    // HASH net.minecraftforge:forge:1.21.8-58.1.0:server net/minecraftforge/forge/1.21.8-58.1.0/forge-1.21.8-58.1.0-server.jar
+
+    let content = jar_parser::read_file(archive, "bootstrap-shim.list")?;
+    let line = content
+        .lines()
+        .find(|line| line.contains("net.minecraftforge:forge:") && line.contains(":server"))
+        .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
+    let long_version = line
+        .split(':')
+        .nth(2)
+        .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
+    let (game_version, build) = long_version
+        .split_once('-')
+        .ok_or(anyhow!(DetectServerInfoError::GameVersionNotFound))?;
+
+    Ok((game_version.to_string(), build.to_string()))
+}
+
+/// Checks that the file at `path` is a server jar of the `expected` fork.
+/// The file is deleted if it isn't, so a broken download doesn't get picked up later.
+pub fn verify_server_jar(path: impl AsRef<Path>, expected: ServerFork) -> anyhow::Result<()> {
+    let path = path.as_ref();
+
+    let actual = jar_parser::archive(path).and_then(|mut archive| detect_server_fork(&mut archive));
+    let error = match actual {
+        Ok(actual) if actual == expected => return Ok(()),
+        Ok(actual) => InvalidServerJarError::WrongFork {
+            path: path.to_path_buf(),
+            expected,
+            actual,
+        },
+        Err(_) => InvalidServerJarError::NotAJar(path.to_path_buf()),
+    };
+
+    std::fs::remove_file(path)?;
+    anyhow::bail!(error)
+}
+
+/// Where the Forge installer puts the libraries and argument files of each version.
+const FORGE_LIBRARIES_DIR: &str = "libraries/net/minecraftforge/forge";
+/// The JVM arguments file of the argument files layout, for the user to edit.
+const USER_JVM_ARGS_FILE: &str = "user_jvm_args.txt";
+
+/// How the Forge installer lays out what starts the server, which depends on the version.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForgeLayout {
+    /// A runnable `forge-<version>-shim.jar`.
+    Shim,
+    /// No runnable jar, only the argument files `java` is given as `@unix_args.txt`
+    /// or `@win_args.txt`.
+    ArgsFile,
+}
+
+impl ForgeLayout {
+    /// The layout of the installed version, `None` if it has neither.
+    pub fn detect(server_dir: &Path, version: &str) -> Option<Self> {
+        [ForgeLayout::Shim, ForgeLayout::ArgsFile]
+            .into_iter()
+            .find(|layout| server_dir.join(layout.launch_file(version)).is_file())
+    }
+
+    /// The file the config points at, relative to the server directory. The Unix argument
+    /// file stands for both, but see [`platform_args_file`].
+    pub fn launch_file(self, version: &str) -> String {
+        match self {
+            ForgeLayout::Shim => format!("forge-{version}-shim.jar"),
+            ForgeLayout::ArgsFile => format!("{FORGE_LIBRARIES_DIR}/{version}/unix_args.txt"),
+        }
+    }
+}
+
+/// The game version and Forge build of an argument file like
+/// `libraries/net/minecraftforge/forge/1.20.1-47.4.0/unix_args.txt`, which an instance of the
+/// argument files layout has instead of a server jar.
+pub fn forge_args_file_version(path: &Path) -> Option<(String, String)> {
+    let file_name = path.file_name()?.to_str()?;
+    if file_name != "unix_args.txt" && file_name != "win_args.txt" {
+        return None;
+    }
+    let version_dir = path.parent()?;
+    if !version_dir.parent()?.ends_with(FORGE_LIBRARIES_DIR) {
+        return None;
+    }
+
+    let (game_version, build) = version_dir.file_name()?.to_str()?.split_once('-')?;
+    Some((game_version.to_string(), build.to_string()))
+}
+
+/// Finds the argument file of the only Forge version installed in the server directory.
+/// `None` if there are none, or more than one, like after an update left the old one behind.
+pub fn find_forge_args_file(server_dir: &Path) -> Option<PathBuf> {
+    let mut args_files = std::fs::read_dir(server_dir.join(FORGE_LIBRARIES_DIR))
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path().join("unix_args.txt")))
+        .filter(|path| path.is_file());
+    let args_file = args_files.next()?;
+    args_files.next().is_none().then_some(args_file)
+}
+
+/// The Forge argument file in a start argument like `@libraries/.../unix_args.txt`, swapped
+/// for the one of Windows if `windows`. Other arguments are returned as they are.
+pub fn platform_args_file(arg: &str, windows: bool) -> String {
+    let Some(path) = arg.strip_prefix('@') else {
+        return arg.to_string();
+    };
+    if forge_args_file_version(Path::new(path)).is_none() {
+        return arg.to_string();
+    }
+
+    let (from, to) = if windows {
+        ("unix_args.txt", "win_args.txt")
+    } else {
+        ("win_args.txt", "unix_args.txt")
+    };
+    match path.strip_suffix(from) {
+        Some(dir) => format!("@{dir}{to}"),
+        None => arg.to_string(),
+    }
+}
+
+/// The start template launching a Forge server of the argument files layout, the way its
+/// installer's `run.sh` does, or `None` if `jar_name` isn't an argument file.
+pub fn forge_start_template(server_dir: &Path, jar_name: &str) -> Option<StartTemplate> {
+    forge_args_file_version(Path::new(jar_name))?;
+    // Older installers don't create it
+    let user_jvm_args = if server_dir.join(USER_JVM_ARGS_FILE).is_file() {
+        format!(" @{USER_JVM_ARGS_FILE}")
+    } else {
+        String::new()
+    };
+
+    StartTemplate::parse(&format!(
+        "{{java}} {{xmx}} {{xms}}{user_jvm_args} {{jvm_args}} @{jar_name} nogui {{game_args}}"
+    ))
+    .ok()
+}
+
+/// Whether the template is one [`forge_start_template`] made, which is replaced along with
+/// the jar.
+pub fn is_forge_start_template(template: &StartTemplate) -> bool {
+    template
+        .as_str()
+        .split_whitespace()
+        .any(|word| word.starts_with(&format!("@{FORGE_LIBRARIES_DIR}/")))
+}
+
+fn forge_installer_command(server_dir: &Path, installer_name: &str) -> Command {
+    let mut command = Command::new("java");
+    command
+        .arg("-jar")
+        .arg(installer_name)
+        .arg("--installServer")
+        .current_dir(server_dir);
+    command
+}
+
+/// Runs the Forge installer, writing its output to `log_path` and showing
+/// only the last line in a spinner.
+async fn run_forge_installer(mut command: Command, log_path: &Path) -> anyhow::Result<()> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!(ForgeInstallError::JavaNotFound(e)))?;
+
+    let mut log = File::create(log_path)?;
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+
+    // Would be mixed into the JSON lines on stderr
+    let spinner = if crate::progress_json() {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new_spinner()
+    };
+    spinner
+        .set_style(ProgressStyle::with_template("{spinner} Forge installer: {wide_msg}").unwrap());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let (mut stdout_done, mut stderr_done) = (false, false);
+    while !(stdout_done && stderr_done) {
+        let line = tokio::select! {
+            line = stdout.next_line(), if !stdout_done => line?,
+            line = stderr.next_line(), if !stderr_done => line?,
+        };
+
+        match line {
+            Some(line) => {
+                writeln!(log, "{line}")?;
+                spinner.set_message(line);
+            }
+            None if !stdout_done => stdout_done = true,
+            None => stderr_done = true,
+        }
     }
 
-    async fn fetch_availables(_config: (), client: &Client) -> anyhow::Result<String> {
-        forge_meta::versions(client).await
+    let status = child.wait().await?;
+    spinner.finish_and_clear();
+
+    if !status.success() {
+        anyhow::bail!(ForgeInstallError::InstallerFailed(
+            status,
+            log_path.to_path_buf()
+        ));
     }
+
+    Ok(())
 }
 
+/// The Main-Class of the Forge and Fabric installers.
+const INSTALLER_MAIN_CLASSES: [&str; 2] = [
+    "net.minecraftforge.installer.SimpleInstaller",
+    "net.fabricmc.installer.Main",
+];
+
+/// Detects the fork of the jar, or of the server jar nested in it if it's a bundler.
 pub fn detect_server_fork<R: Read + Seek>(
     archive: &mut ZipArchive<R>,
+) -> anyhow::Result<ServerFork> {
+    match detect_jar_fork(archive) {
+        Err(e) => match nested_server_jar(archive)? {
+            Some(mut inner) => detect_jar_fork(&mut inner),
+            None => Err(e),
+        },
+        fork => fork,
+    }
+}
+
+/// The server jar a bundler like vanilla's since 1.18 nests in `META-INF/versions/`, as
+/// listed in `META-INF/versions.list`. `None` if the jar isn't a bundler.
+pub(crate) fn nested_server_jar<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> anyhow::Result<Option<ZipArchive<io::Cursor<Vec<u8>>>>> {
+    let Ok(list) = jar_parser::read_file(archive, "META-INF/versions.list") else {
+        return Ok(None);
+    };
+    // The line format goes like, separated by tabs:
+    // HASH 1.21.8 1.21.8/server-1.21.8.jar
+    let Some(path) = list.lines().find_map(|line| line.split('\t').nth(2)) else {
+        return Ok(None);
+    };
+
+    jar_parser::nested_archive(archive, &format!("META-INF/versions/{path}")).map(Some)
+}
+
+/// The fork of the jar itself, see [`detect_server_fork`].
+pub(crate) fn detect_jar_fork<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
 ) -> anyhow::Result<ServerFork> {
     let content = jar_parser::read_file(archive, "META-INF/MANIFEST.MF")?;
     let manifest = jar_parser::parse_manifest(&content);
-    let main_class = manifest
-        .get("Main-Class")
-        .ok_or(anyhow!(DetectServerInfoError::MainClassNotFound))?;
+    let Some(main_class) = manifest.get("Main-Class") else {
+        return detect_fork_from_files(archive);
+    };
+
+    // They share their package with the server jars
+    if INSTALLER_MAIN_CLASSES.contains(&main_class) {
+        anyhow::bail!(DetectServerInfoError::InstallerJar);
+    }
 
     detect_fork_from_main_class(main_class)
 }
 
+/// Tells the fork of a jar without a Main-Class by the files each fork reads its game
+/// version from.
+fn detect_fork_from_files<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> anyhow::Result<ServerFork> {
+    // Paperclip's, which has a version.json as well
+    if archive.index_for_name("patch.properties").is_some() {
+        anyhow::bail!(DetectServerInfoError::UnknownServerFork);
+    }
+
+    let fork = [
+        ("bootstrap-shim.list", ServerFork::Forge),
+        ("install.properties", ServerFork::Fabric),
+        ("version.json", ServerFork::Vanilla),
+    ]
+    .into_iter()
+    .find(|(file, _)| archive.index_for_name(file).is_some())
+    .map(|(_, fork)| fork);
+    fork.ok_or(anyhow!(DetectServerInfoError::MainClassNotFound))
+}
+
+impl ServerFork {
+    /// Detects the game version of a jar of this fork, or of the server jar nested in it if
+    /// it's a bundler.
+    pub fn game_version<R: Read + Seek>(
+        &self,
+        archive: &mut ZipArchive<R>,
+    ) -> anyhow::Result<String> {
+        match self.jar_game_version(archive) {
+            Err(e) => match nested_server_jar(archive)? {
+                Some(mut inner) => self.jar_game_version(&mut inner),
+                None => Err(e),
+            },
+            version => version,
+        }
+    }
+}
+
+#[deprecated(note = "use `ServerFork::game_version`")]
 pub fn detect_game_version<R: Read + Seek>(
     archive: &mut ZipArchive<R>,
     fork: ServerFork,
 ) -> anyhow::Result<String> {
+    fork.game_version(archive)
+}
+
+/// The installed versions of the server's jar, or of its Forge argument file.
+pub fn installed_versions(
+    server_dir: &Path,
+    jar_name: &str,
+    fork: ServerFork,
+) -> anyhow::Result<Vec<ComponentVersion>> {
+    if let Some((game_version, build)) = forge_args_file_version(Path::new(jar_name)) {
+        return Ok(vec![
+            ComponentVersion::new("game", game_version),
+            ComponentVersion::new("forge build", build),
+        ]);
+    }
+
+    let mut archive = jar_parser::archive(server_dir.join(jar_name))?;
+    detect_installed_versions(&mut archive, fork, jar_name)
+}
+
+pub fn detect_installed_versions<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    fork: ServerFork,
+    jar_name: &str,
+) -> anyhow::Result<Vec<ComponentVersion>> {
+    match fork {
+        ServerFork::Fabric => Fabric::installed_versions(archive, jar_name),
+        ServerFork::Forge => Forge::installed_versions(archive, jar_name),
+        ServerFork::Vanilla => Vanilla::installed_versions(archive, jar_name),
+    }
+}
+
+pub async fn fetch_latest_versions(
+    fork: ServerFork,
+    client: &Client,
+) -> anyhow::Result<LatestVersions> {
     match fork {
-        ServerFork::Fabric => Fabric::game_version(archive),
-        ServerFork::Forge => Forge::game_version(archive),
-        ServerFork::Vanilla => Vanilla::game_version(archive),
+        ServerFork::Fabric => Fabric::latest_versions(client).await,
+        ServerFork::Forge => Forge::latest_versions(client).await,
+        ServerFork::Vanilla => Vanilla::latest_versions(client).await,
+    }
+}
+
+pub fn version_args(fork: ServerFork, installed: &[ComponentVersion]) -> Vec<String> {
+    match fork {
+        ServerFork::Fabric => Fabric::version_args(installed),
+        ServerFork::Forge => Forge::version_args(installed),
+        ServerFork::Vanilla => Vanilla::version_args(installed),
     }
 }
 
@@ -408,6 +1116,139 @@ mod tests {
     use super::*;
     use crate::system::jar_parser::archive;
 
+    #[tokio::test]
+    async fn test_forge_installer_without_java() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let empty_path = tempfile::tempdir().unwrap();
+
+        let mut command = forge_installer_command(server_dir.path(), "installer.jar");
+        command.env("PATH", empty_path.path());
+
+        let err = run_forge_installer(command, &server_dir.path().join("forge-install.log"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ForgeInstallError>(),
+            Some(ForgeInstallError::JavaNotFound(_))
+        ));
+        assert!(err.to_string().contains("Java is required on PATH"));
+    }
+
+    #[test]
+    fn test_forge_layout() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let version = "1.20.1-47.4.0";
+        assert_eq!(ForgeLayout::detect(server_dir.path(), version), None);
+
+        let args_file = server_dir
+            .path()
+            .join(ForgeLayout::ArgsFile.launch_file(version));
+        std::fs::create_dir_all(args_file.parent().unwrap()).unwrap();
+        std::fs::write(&args_file, "").unwrap();
+        assert_eq!(
+            ForgeLayout::detect(server_dir.path(), version),
+            Some(ForgeLayout::ArgsFile)
+        );
+        assert_eq!(find_forge_args_file(server_dir.path()), Some(args_file));
+
+        // The shim jar is what starts the server when there is one
+        std::fs::write(
+            server_dir
+                .path()
+                .join(ForgeLayout::Shim.launch_file(version)),
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            ForgeLayout::detect(server_dir.path(), version),
+            Some(ForgeLayout::Shim)
+        );
+    }
+
+    #[test]
+    fn test_forge_args_file_version() {
+        assert_eq!(
+            forge_args_file_version(Path::new(
+                "libraries/net/minecraftforge/forge/1.20.1-47.4.0/win_args.txt"
+            )),
+            Some(("1.20.1".to_string(), "47.4.0".to_string()))
+        );
+        assert_eq!(
+            forge_args_file_version(Path::new("forge-1.21.8-58.1.0-shim.jar")),
+            None
+        );
+        assert_eq!(
+            forge_args_file_version(Path::new("libraries/net/other/1.20.1-47.4.0/unix_args.txt")),
+            None
+        );
+
+        let arg = "@libraries/net/minecraftforge/forge/1.20.1-47.4.0/unix_args.txt";
+        let windows_arg = "@libraries/net/minecraftforge/forge/1.20.1-47.4.0/win_args.txt";
+        assert_eq!(platform_args_file(arg, true), windows_arg);
+        assert_eq!(platform_args_file(windows_arg, false), arg);
+        assert_eq!(platform_args_file(arg, false), arg);
+        assert_eq!(
+            platform_args_file("@user_jvm_args.txt", true),
+            "@user_jvm_args.txt"
+        );
+    }
+
+    #[test]
+    fn test_forge_start_template() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let args_file = ForgeLayout::ArgsFile.launch_file("1.20.1-47.4.0");
+        assert_eq!(
+            forge_start_template(server_dir.path(), "forge-1.21.8-58.1.0-shim.jar"),
+            None
+        );
+
+        let template = forge_start_template(server_dir.path(), &args_file).unwrap();
+        assert_eq!(
+            template.as_str(),
+            format!("{{java}} {{xmx}} {{xms}} {{jvm_args}} @{args_file} nogui {{game_args}}")
+        );
+        assert!(is_forge_start_template(&template));
+        assert!(!is_forge_start_template(&StartTemplate::default()));
+
+        std::fs::write(server_dir.path().join(USER_JVM_ARGS_FILE), "").unwrap();
+        let template = forge_start_template(server_dir.path(), &args_file).unwrap();
+        assert!(template.as_str().contains(" @user_jvm_args.txt "));
+    }
+
+    #[test]
+    fn test_verify_server_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.jar");
+
+        std::fs::copy("testdata/vanilla-1.21.8.jar", &path).unwrap();
+        assert!(verify_server_jar(&path, ServerFork::Vanilla).is_ok());
+
+        let err = verify_server_jar(&path, ServerFork::Fabric).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvalidServerJarError>(),
+            Some(InvalidServerJarError::WrongFork {
+                actual: ServerFork::Vanilla,
+                ..
+            })
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_verify_server_jar_junk_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.jar");
+        std::fs::write(&path, r#"{"code":"bad_request"}"#).unwrap();
+
+        let err = verify_server_jar(&path, ServerFork::Fabric).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvalidServerJarError>(),
+            Some(InvalidServerJarError::NotAJar(_))
+        ));
+        assert!(!path.exists());
+    }
+
     #[test]
     fn test_detect_vanilla_fork() {
         let jar_path = "testdata/vanilla-1.21.8.jar";
@@ -435,11 +1276,117 @@ mod tests {
         assert!(matches!(fork, ServerFork::Forge));
     }
 
+    #[test]
+    fn test_detect_wrapped_main_class() {
+        let jar_path = "testdata/vanilla-1.21.8-wrapped-manifest.jar";
+        let mut archive = archive(jar_path).unwrap();
+
+        assert_eq!(
+            detect_server_fork(&mut archive).unwrap(),
+            ServerFork::Vanilla
+        );
+        assert_eq!(
+            ServerFork::Vanilla.game_version(&mut archive).unwrap(),
+            "1.21.8"
+        );
+    }
+
+    #[test]
+    fn test_detect_bundler_jar() {
+        let jar_path = "testdata/vanilla-1.21.8-bundler.jar";
+        let mut archive = archive(jar_path).unwrap();
+
+        assert_eq!(
+            detect_server_fork(&mut archive).unwrap(),
+            ServerFork::Vanilla
+        );
+        assert_eq!(
+            ServerFork::Vanilla.game_version(&mut archive).unwrap(),
+            "1.21.8"
+        );
+        assert_eq!(
+            detect_installed_versions(&mut archive, ServerFork::Vanilla, "server.jar").unwrap(),
+            [ComponentVersion::new("game", "1.21.8")]
+        );
+
+        // Not a bundler
+        let mut forge = jar_parser::archive("testdata/forge-1.21.8-58.1.0-shim.jar").unwrap();
+        assert!(nested_server_jar(&mut forge).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_detect_fork_without_main_class() {
+        fn jar(files: &[&str]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+            let mut buffer = io::Cursor::new(Vec::new());
+            let mut writer = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::SimpleFileOptions::default();
+            writer.start_file("META-INF/MANIFEST.MF", options).unwrap();
+            writer.write_all(b"Manifest-Version: 1.0\r\n").unwrap();
+            for file in files {
+                writer.start_file(*file, options).unwrap();
+            }
+            writer.finish().unwrap();
+            ZipArchive::new(buffer).unwrap()
+        }
+
+        for (files, fork) in [
+            (&["version.json"][..], ServerFork::Vanilla),
+            (&["install.properties"], ServerFork::Fabric),
+            (&["bootstrap-shim.list", "version.json"], ServerFork::Forge),
+        ] {
+            assert_eq!(detect_server_fork(&mut jar(files)).unwrap(), fork);
+        }
+
+        let err = detect_server_fork(&mut jar(&["patch.properties", "version.json"])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DetectServerInfoError>(),
+            Some(DetectServerInfoError::UnknownServerFork)
+        ));
+        let err = detect_server_fork(&mut jar(&[])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DetectServerInfoError>(),
+            Some(DetectServerInfoError::MainClassNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_detect_installer_jar() {
+        let mut buffer = io::Cursor::new(Vec::new());
+        let mut writer = zip::ZipWriter::new(&mut buffer);
+        writer
+            .start_file(
+                "META-INF/MANIFEST.MF",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        writer
+            .write_all(b"Manifest-Version: 1.0\r\nMain-Class: net.minecraftforge.installer.SimpleInstaller\r\n")
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut archive = ZipArchive::new(buffer).unwrap();
+        let err = detect_server_fork(&mut archive).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<DetectServerInfoError>(),
+            Some(DetectServerInfoError::InstallerJar)
+        ));
+    }
+
+    #[test]
+    fn test_fork_name() {
+        for fork in [ServerFork::Vanilla, ServerFork::Fabric, ServerFork::Forge] {
+            assert_eq!(fork.name().parse::<ServerFork>().unwrap(), fork);
+        }
+        assert_eq!("fabric".parse::<ServerFork>().unwrap(), ServerFork::Fabric);
+        assert!("paper".parse::<ServerFork>().is_err());
+    }
+
     #[test]
     fn test_detect_game_version_vanilla() {
         let jar_path = "testdata/vanilla-1.21.8.jar";
         let mut archive = archive(jar_path).unwrap();
-        let version = detect_game_version(&mut archive, ServerFork::Vanilla).unwrap();
+        let version = ServerFork::Vanilla.game_version(&mut archive).unwrap();
 
         assert_eq!(version, "1.21.8")
     }
@@ -448,7 +1395,7 @@ mod tests {
     fn test_detect_game_version_fabric() {
         let jar_path = "testdata/fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar";
         let mut archive = archive(jar_path).unwrap();
-        let version = detect_game_version(&mut archive, ServerFork::Fabric).unwrap();
+        let version = ServerFork::Fabric.game_version(&mut archive).unwrap();
 
         assert_eq!(version, "1.21.8")
     }
@@ -457,8 +1404,93 @@ mod tests {
     fn test_detect_game_version_forge() {
         let jar_path = "testdata/forge-1.21.8-58.1.0-shim.jar";
         let mut archive = archive(jar_path).unwrap();
-        let version = detect_game_version(&mut archive, ServerFork::Forge).unwrap();
+        let version = ServerFork::Forge.game_version(&mut archive).unwrap();
 
         assert_eq!(version, "1.21.8")
     }
+
+    #[test]
+    fn test_installed_versions_fabric() {
+        let jar_name = "fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar";
+        let mut archive = archive(format!("testdata/{jar_name}")).unwrap();
+
+        assert_eq!(
+            detect_installed_versions(&mut archive, ServerFork::Fabric, jar_name).unwrap(),
+            [
+                ComponentVersion::new("game", "1.21.8"),
+                ComponentVersion::new("loader", "0.16.14"),
+                ComponentVersion::new("installer", "1.0.3"),
+            ]
+        );
+
+        // Renamed by the user, the installer version is lost
+        let versions = detect_installed_versions(&mut archive, ServerFork::Fabric, "server.jar");
+        assert_eq!(versions.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_installed_versions_forge() {
+        let jar_name = "forge-1.21.8-58.1.0-shim.jar";
+        let mut archive = archive(format!("testdata/{jar_name}")).unwrap();
+
+        assert_eq!(
+            detect_installed_versions(&mut archive, ServerFork::Forge, jar_name).unwrap(),
+            [
+                ComponentVersion::new("game", "1.21.8"),
+                ComponentVersion::new("forge build", "58.1.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_args() {
+        let fabric = [
+            ComponentVersion::new("game", "1.21.8"),
+            ComponentVersion::new("loader", "0.16.14"),
+        ];
+        assert_eq!(
+            version_args(ServerFork::Fabric, &fabric),
+            ["1.21.8", "0.16.14", "--latest-stable"]
+        );
+
+        let forge = [
+            ComponentVersion::new("game", "1.21.8"),
+            ComponentVersion::new("forge build", "58.1.0"),
+        ];
+        assert_eq!(version_args(ServerFork::Forge, &forge), ["1.21.8-58.1.0"]);
+        assert_eq!(version_args(ServerFork::Vanilla, &forge), ["1.21.8"]);
+    }
+
+    #[test]
+    fn test_component_updates() {
+        let installed = [
+            ComponentVersion::new("game", "1.21.8"),
+            ComponentVersion::new("loader", "0.16.14"),
+        ];
+        let latest = LatestVersions {
+            components: vec![
+                ComponentVersion::new("game", "1.21.8"),
+                ComponentVersion::new("loader", "0.17.2"),
+                ComponentVersion::new("installer", "1.1.0"),
+            ],
+            version_args: vec![],
+        };
+
+        let lines = component_updates(&installed, &latest)
+            .iter()
+            .map(|update| update.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "game: `1.21.8` [OK] up-to-date",
+                "loader: `0.16.14` -> `0.17.2`",
+                "installer: `unknown` -> `1.1.0`",
+            ]
+        );
+
+        // A snapshot past the latest release isn't downgraded by --update
+        let snapshot = [ComponentVersion::new("game", "25w31a")];
+        assert!(component_updates(&snapshot, &latest)[0].is_up_to_date());
+    }
 }
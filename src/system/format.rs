@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+/// Formats a byte count like `92.4 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Formats a duration like `2d 3h 4m`, leaving out leading zero units.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Formats how long ago something happened in its largest unit, like `3 days ago`.
+pub fn format_ago(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let (hours, days) = (minutes / 60, minutes / (60 * 24));
+    let (count, unit) = if minutes == 0 {
+        return "just now".to_string();
+    } else if hours == 0 {
+        (minutes, "minute")
+    } else if days == 0 {
+        (hours, "hour")
+    } else if days < 7 {
+        (days, "day")
+    } else if days < 30 {
+        (days / 7, "week")
+    } else if days < 365 {
+        (days / 30, "month")
+    } else {
+        (days / 365, "year")
+    };
+
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+/// How long before `now` the RFC 3339 timestamp was, like [`format_ago`]. A time in the
+/// future counts as just now, and `None` is returned for an invalid timestamp.
+pub fn format_timestamp_ago(
+    timestamp: &str,
+    now: chrono::DateTime<chrono::Local>,
+) -> Option<String> {
+    let time = chrono::DateTime::parse_from_rfc3339(timestamp).ok()?;
+    let duration = (now.fixed_offset() - time).to_std().unwrap_or_default();
+    Some(format_ago(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(96_888_422), "92.4 MB");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+        assert_eq!(format_duration(Duration::from_secs(61)), "1m 1s");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 120)),
+            "3h 2m"
+        );
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 86400 + 3600 + 60)),
+            "2d 1h 1m"
+        );
+    }
+
+    #[test]
+    fn test_format_ago() {
+        let minute = Duration::from_secs(60);
+        let (hour, day) = (minute * 60, minute * 60 * 24);
+        assert_eq!(format_ago(Duration::from_secs(59)), "just now");
+        assert_eq!(format_ago(minute), "1 minute ago");
+        assert_eq!(format_ago(minute * 59), "59 minutes ago");
+        assert_eq!(format_ago(hour), "1 hour ago");
+        assert_eq!(format_ago(hour * 23 + minute * 59), "23 hours ago");
+        assert_eq!(format_ago(day), "1 day ago");
+        assert_eq!(format_ago(day * 6 + hour * 23), "6 days ago");
+        assert_eq!(format_ago(day * 7), "1 week ago");
+        assert_eq!(format_ago(day * 29), "4 weeks ago");
+        assert_eq!(format_ago(day * 45), "1 month ago");
+        assert_eq!(format_ago(day * 364), "12 months ago");
+        assert_eq!(format_ago(day * 800), "2 years ago");
+    }
+
+    #[test]
+    fn test_format_timestamp_ago() {
+        let now = chrono::DateTime::parse_from_rfc3339("2025-08-20T12:00:00+02:00")
+            .unwrap()
+            .with_timezone(&chrono::Local);
+        assert_eq!(
+            format_timestamp_ago("2025-08-17T10:00:00Z", now).as_deref(),
+            Some("3 days ago")
+        );
+        assert_eq!(
+            format_timestamp_ago("2025-08-21T12:00:00Z", now).as_deref(),
+            Some("just now")
+        );
+        assert_eq!(format_timestamp_ago("yesterday", now), None);
+    }
+}
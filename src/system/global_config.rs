@@ -0,0 +1,189 @@
+use crate::{
+    network::{NetworkSettings, mirror::MirrorRule, modrinth::ModrinthSettings},
+    system::{clean::CleanSettings, group::ServerGroup, notify::NotificationSettings},
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Settings shared by every server, from `config.toml` in mcerv's config directory, like:
+///
+/// ```toml
+/// data_dir = "/mnt/big/mcerv"
+/// mirrors = [{ from = "https://cdn.modrinth.com/", to = "https://mirror.example/modrinth/" }]
+///
+/// [network]
+/// ca_bundle = "/etc/ssl/corp-ca.pem"
+/// connect_timeout = 10
+/// limit_rate = "2M"
+///
+/// [modrinth]
+/// token = "mrp_..."
+///
+/// [clean]
+/// patterns = ["*.bak"]
+///
+/// [groups.network]
+/// members = [{ server = "velocity" }, { server = "lobby", delay = 5 }]
+///
+/// [[notifications.webhooks]]
+/// url = "https://discord.com/api/webhooks/..."
+/// format = "discord"
+/// events = ["crash", "mod_updates", "server_update"]
+/// ```
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlobalConfig {
+    pub network: NetworkSettings,
+    /// Rewrites the URLs of metadata and of downloads that can be verified, for when a host is
+    /// slow or blocked.
+    pub mirrors: Vec<MirrorRule>,
+    /// Where the servers are kept instead of the default data directory, see
+    /// [`crate::data_dir`].
+    pub data_dir: Option<PathBuf>,
+    /// Servers started and stopped together with `mcerv group`, by name.
+    pub groups: BTreeMap<String, ServerGroup>,
+    /// Webhooks posted to when updates are found or a supervised server crashes.
+    pub notifications: NotificationSettings,
+    /// MCERV_MODRINTH_TOKEN takes precedence over its token.
+    pub modrinth: ModrinthSettings,
+    /// Junk `mcerv clean` removes besides the built-in patterns.
+    pub clean: CleanSettings,
+}
+
+impl GlobalConfig {
+    /// Loads the config, or the defaults if there is none.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid global config {}: {e}", path.display()))
+    }
+
+    /// Records the data directory in the config at `path`, keeping the other settings.
+    pub fn save_data_dir(path: &Path, data_dir: &Path) -> anyhow::Result<()> {
+        update(path, |table| {
+            table.insert(
+                "data_dir".to_string(),
+                toml::Value::String(data_dir.to_string_lossy().to_string()),
+            );
+            Ok(())
+        })
+    }
+
+    /// Adds the group to the config at `path`, replacing the one with the same name.
+    pub fn save_group(path: &Path, name: &str, group: &ServerGroup) -> anyhow::Result<()> {
+        update(path, |table| {
+            let groups = table
+                .entry("groups")
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let Some(groups) = groups.as_table_mut() else {
+                anyhow::bail!("groups in the global config isn't a table");
+            };
+            groups.insert(name.to_string(), toml::Value::try_from(group)?);
+            Ok(())
+        })
+    }
+}
+
+/// Changes the config at `path` as a TOML table, so the settings it doesn't change are kept.
+fn update(
+    path: &Path,
+    change: impl FnOnce(&mut toml::Table) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut table = match fs::read_to_string(path) {
+        Ok(content) => content
+            .parse::<toml::Table>()
+            .map_err(|e| anyhow::anyhow!("Invalid global config {}: {e}", path.display()))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(e.into()),
+    };
+    change(&mut table)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string(&table)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::rate_limit::Rate;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.network.connect_timeout, 10);
+
+        fs::write(&path, "[network]\nconnect_timeout = 3\n").unwrap();
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.network.connect_timeout, 3);
+        assert_eq!(
+            config.network.inactivity_timeout,
+            NetworkSettings::default().inactivity_timeout
+        );
+
+        fs::write(
+            &path,
+            "mirrors = [{ from = \"https://cdn.modrinth.com/\", to = \"https://m.example/\" }]\n",
+        )
+        .unwrap();
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.mirrors[0].to, "https://m.example/");
+
+        fs::write(&path, "[network]\nlimit_rate = \"1.5M\"\n").unwrap();
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.network.limit_rate, Some(Rate(1024 * 1024 * 3 / 2)));
+
+        fs::write(&path, "[clean]\npatterns = [\"*.bak\"]\n").unwrap();
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.clean.patterns, ["*.bak"]);
+
+        fs::write(&path, "[network]\nconect_timeout = 3\n").unwrap();
+        assert!(GlobalConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_save_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcerv").join("config.toml");
+
+        GlobalConfig::save_data_dir(&path, Path::new("/mnt/old")).unwrap();
+        fs::write(
+            &path,
+            fs::read_to_string(&path).unwrap() + "[network]\nconnect_timeout = 3\n",
+        )
+        .unwrap();
+        GlobalConfig::save_data_dir(&path, Path::new("/mnt/big")).unwrap();
+
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.data_dir, Some(PathBuf::from("/mnt/big")));
+        assert_eq!(config.network.connect_timeout, 3);
+    }
+
+    #[test]
+    fn test_save_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "data_dir = \"/mnt/big\"\n").unwrap();
+
+        let group = ServerGroup::new(&["velocity".to_string(), "lobby".to_string()], 5);
+        GlobalConfig::save_group(&path, "network", &group).unwrap();
+
+        let config = GlobalConfig::load(&path).unwrap();
+        assert_eq!(config.groups["network"], group);
+        assert_eq!(config.data_dir, Some(PathBuf::from("/mnt/big")));
+    }
+}
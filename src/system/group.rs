@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Servers started together in order, like a proxy and the servers behind it, and stopped
+/// in reverse order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerGroup {
+    pub members: Vec<GroupMember>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GroupMember {
+    pub server: String,
+    /// Seconds to wait before starting this member, like for the one before it to come up.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub delay: u64,
+}
+
+fn is_zero(delay: &u64) -> bool {
+    *delay == 0
+}
+
+impl ServerGroup {
+    /// A group of the servers in order, waiting `delay` seconds before starting each one
+    /// after the first.
+    pub fn new(servers: &[String], delay: u64) -> Self {
+        let members = servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| GroupMember {
+                server: server.clone(),
+                delay: if i == 0 { 0 } else { delay },
+            })
+            .collect();
+        Self { members }
+    }
+}
+
+impl GroupMember {
+    pub fn delay(&self) -> Duration {
+        Duration::from_secs(self.delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let servers = ["velocity", "lobby", "games"].map(String::from);
+        let group = ServerGroup::new(&servers, 5);
+
+        let delays: Vec<_> = group
+            .members
+            .iter()
+            .map(|member| (member.server.as_str(), member.delay))
+            .collect();
+        assert_eq!(delays, [("velocity", 0), ("lobby", 5), ("games", 5)]);
+    }
+
+    #[test]
+    fn test_delay_optional() {
+        let group: ServerGroup = toml::from_str(
+            "members = [{ server = \"velocity\" }, { server = \"lobby\", delay = 5 }]",
+        )
+        .unwrap();
+        assert_eq!(group.members[0].delay(), Duration::ZERO);
+        assert_eq!(group.members[1].delay(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::system::{
+    config,
+    forks::{self, ServerFork},
+    format,
+};
+use chrono::{DateTime, Local};
+use prettytable::{Table, row};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// In the server directory. One [`Event`] per line, only ever appended to.
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Something mcerv changed in a server, like:
+///
+/// ```json
+/// {"timestamp":"2025-08-14T18:03:00+02:00","operation":"update-server-jar","old_versions":{"game":"1.21.4","loader":"0.16.9"},"new_versions":{"game":"1.21.8","loader":"0.16.14"}}
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    /// In RFC 3339.
+    pub timestamp: String,
+    pub operation: String,
+    /// The versions of the server jar's components, by component.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub old_versions: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub new_versions: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mods: Vec<ModChange>,
+    /// The trash batch the operation moved the replaced jars to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_batch: Option<String>,
+    /// The trash batch of the update this one rolled back, or its timestamp for older ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rolled_back: Option<String>,
+}
+
+/// A mod jar that was added, replaced or removed, by file name, and the Modrinth versions
+/// of the jars if they're known.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct ModChange {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_version_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_version_id: Option<String>,
+}
+
+impl ModChange {
+    /// The change undoing this one.
+    pub fn reversed(&self) -> Self {
+        Self {
+            old: self.new.clone(),
+            new: self.old.clone(),
+            project_id: self.project_id.clone(),
+            old_version_id: self.new_version_id.clone(),
+            new_version_id: self.old_version_id.clone(),
+        }
+    }
+}
+
+impl Display for ModChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{old} -> {new}"),
+            (None, Some(new)) => write!(f, "+ {new}"),
+            (Some(old), None) => write!(f, "- {old}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl Event {
+    /// An event of the operation happening now.
+    pub fn new(operation: &str) -> Self {
+        Self {
+            timestamp: config::now(),
+            operation: operation.to_string(),
+            old_versions: BTreeMap::new(),
+            new_versions: BTreeMap::new(),
+            mods: Vec::new(),
+            trash_batch: None,
+            rolled_back: None,
+        }
+    }
+
+    /// What changed, like `game 1.21.4 -> 1.21.8; + lithium.jar`.
+    pub fn changes(&self) -> String {
+        let mut changes = Vec::new();
+        let components = self.old_versions.keys().chain(
+            self.new_versions
+                .keys()
+                .filter(|component| !self.old_versions.contains_key(*component)),
+        );
+        for component in components {
+            let old = self.old_versions.get(component);
+            let new = self.new_versions.get(component);
+            changes.push(match (old, new) {
+                (Some(old), Some(new)) if old == new => format!("{component} {new}"),
+                (Some(old), Some(new)) => format!("{component} {old} -> {new}"),
+                (None, Some(new)) => format!("{component} {new}"),
+                (Some(old), None) => format!("{component} {old} -> ?"),
+                (None, None) => unreachable!(),
+            });
+        }
+        changes.extend(self.mods.iter().map(ModChange::to_string));
+        changes.join("; ")
+    }
+}
+
+/// The component versions of the server jar by component, or none if they can't be told.
+pub fn jar_versions(
+    server_dir: &Path,
+    jar_name: &str,
+    fork: ServerFork,
+) -> BTreeMap<String, String> {
+    forks::installed_versions(server_dir, jar_name, fork)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|v| (v.component.to_string(), v.version))
+        .collect()
+}
+
+/// Appends the event to the history of the server.
+pub fn append(server_dir: &Path, event: &Event) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(server_dir.join(HISTORY_FILE))?;
+    writeln!(file, "{}", serde_json::to_string(event)?)
+}
+
+/// The events of the server's history, oldest first, and a warning for each line that
+/// isn't one. A server without a history has no events.
+pub fn read(server_dir: &Path) -> io::Result<(Vec<Event>, Vec<String>)> {
+    let content = match fs::read_to_string(server_dir.join(HISTORY_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+        Err(e) => return Err(e),
+    };
+
+    let (mut events, mut warnings) = (Vec::new(), Vec::new());
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(event) => events.push(event),
+            Err(e) => warnings.push(format!("skipped line {} of {HISTORY_FILE}: {e}", i + 1)),
+        }
+    }
+    Ok((events, warnings))
+}
+
+/// The newest `limit` events, newest first, with how long before `now` they were.
+pub fn table(events: &[Event], limit: Option<usize>, now: DateTime<Local>) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Time", "Operation", "Changes"]);
+    for event in events.iter().rev().take(limit.unwrap_or(usize::MAX)) {
+        let mut time = config::format_time(Some(&event.timestamp));
+        if let Some(ago) = format::format_timestamp_ago(&event.timestamp, now) {
+            time = format!("{time} ({ago})");
+        }
+        table.add_row(row![time, event.operation, event.changes()]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read(dir.path()).unwrap(), (Vec::new(), Vec::new()));
+
+        let mut install = Event::new("install");
+        install.new_versions = BTreeMap::from([("game".to_string(), "1.21.4".to_string())]);
+        append(dir.path(), &install).unwrap();
+        // Written by something else, or cut off by a crash
+        let path = dir.path().join(HISTORY_FILE);
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str("{\"timestamp\":\"2025-\n\n");
+        fs::write(&path, content).unwrap();
+        let mut update = Event::new("install-mod");
+        update.mods.push(ModChange {
+            new: Some("lithium.jar".to_string()),
+            ..Default::default()
+        });
+        append(dir.path(), &update).unwrap();
+
+        let (events, warnings) = read(dir.path()).unwrap();
+        assert_eq!(events, [install, update]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("line 2"));
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut event = Event::new("update-server-jar");
+        event.old_versions = BTreeMap::from([
+            ("game".to_string(), "1.21.4".to_string()),
+            ("loader".to_string(), "0.16.9".to_string()),
+        ]);
+        event.new_versions = BTreeMap::from([
+            ("game".to_string(), "1.21.8".to_string()),
+            ("loader".to_string(), "0.16.9".to_string()),
+            ("installer".to_string(), "1.1.0".to_string()),
+        ]);
+        event.mods = vec![
+            ModChange {
+                old: Some("sodium-0.6.13.jar".to_string()),
+                new: Some("sodium-0.6.14.jar".to_string()),
+                ..Default::default()
+            },
+            ModChange {
+                old: Some("lithium.jar".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(
+            event.changes(),
+            "game 1.21.4 -> 1.21.8; loader 0.16.9; installer 1.1.0; \
+             sodium-0.6.13.jar -> sodium-0.6.14.jar; - lithium.jar"
+        );
+    }
+
+    #[test]
+    fn test_table_newest_first() {
+        let events = ["install", "install-mod", "mods update"].map(Event::new);
+        let table = table(&events, Some(2), Local::now()).to_string();
+        assert!(table.find("mods update").unwrap() < table.find("install-mod").unwrap());
+        assert!(table.contains("(just now)"));
+        assert!(!table.contains("| install "));
+    }
+}
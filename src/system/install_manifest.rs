@@ -0,0 +1,275 @@
+use crate::system::forks::{InstallCommand, ServerFork};
+use serde::Deserialize;
+use std::{error::Error, fmt::Display, fs, path::Path};
+
+/// How many servers `install-many` installs at once if the manifest doesn't say.
+pub const DEFAULT_CONCURRENCY: usize = 3;
+
+/// The servers `install-many` installs, like:
+///
+/// ```toml
+/// mods = ["AANobbMI"]
+///
+/// [[servers]]
+/// name = "lobby"
+/// fork = "fabric"
+/// versions = ["--latest-stable"]
+/// port = 25566
+/// memory = "2G"
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct InstallManifest {
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Modrinth version IDs installed in every server with `shared_mods`.
+    #[serde(default)]
+    pub mods: Vec<String>,
+    pub servers: Vec<ManifestServer>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestServer {
+    pub name: String,
+    pub fork: String,
+    /// The version arguments of `install <fork>`.
+    #[serde(default)]
+    pub versions: Vec<String>,
+    pub port: Option<u16>,
+    /// Both the min and max memory, like `6G`.
+    pub memory: Option<String>,
+    #[serde(default = "default_shared_mods")]
+    pub shared_mods: bool,
+    /// Modrinth version IDs installed in this server only.
+    #[serde(default)]
+    pub mods: Vec<String>,
+}
+
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+fn default_shared_mods() -> bool {
+    true
+}
+
+#[derive(Debug)]
+pub enum InstallManifestError {
+    DuplicateName(String),
+    DuplicatePort(u16),
+    InvalidFork(String),
+    InvalidVersions(String),
+    MissingVersions,
+}
+
+impl Display for InstallManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallManifestError::DuplicateName(name) => {
+                write!(f, "Server {name} is listed more than once")
+            }
+            InstallManifestError::DuplicatePort(port) => {
+                write!(f, "Port {port} is used by more than one server")
+            }
+            InstallManifestError::InvalidFork(fork) => write!(f, "Unknown server fork: {fork}"),
+            InstallManifestError::InvalidVersions(reason) => {
+                write!(f, "Invalid version arguments: {reason}")
+            }
+            InstallManifestError::MissingVersions => write!(
+                f,
+                "Versions are missing, and they can't be picked interactively when installing many servers"
+            ),
+        }
+    }
+}
+
+impl Error for InstallManifestError {}
+
+impl InstallManifest {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let manifest: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Checks what would make the servers clash. Problems of a single server are
+    /// only reported when it's installed, so they don't stop the others.
+    fn validate(&self) -> Result<(), InstallManifestError> {
+        for (i, server) in self.servers.iter().enumerate() {
+            let others = &self.servers[..i];
+            if others.iter().any(|other| other.name == server.name) {
+                return Err(InstallManifestError::DuplicateName(server.name.clone()));
+            }
+            if let Some(port) = server.port
+                && others.iter().any(|other| other.port == Some(port))
+            {
+                return Err(InstallManifestError::DuplicatePort(port));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ManifestServer {
+    /// Parses the versions like `mcerv install <fork>` would.
+    pub fn install_command(&self) -> Result<InstallCommand, InstallManifestError> {
+        let fork: ServerFork = self
+            .fork
+            .parse()
+            .map_err(|_| InstallManifestError::InvalidFork(self.fork.clone()))?;
+
+        // Clap parser needs a dummy program name
+        let dummy_name = format!("mcerv install {}", self.fork.to_lowercase());
+        let argv = std::iter::once(dummy_name).chain(self.versions.iter().cloned());
+        let command = fork.try_parse_version_args(argv).map_err(|e| {
+            let message = e.to_string();
+            let reason = message.lines().next().unwrap_or_default();
+            InstallManifestError::InvalidVersions(reason.trim_start_matches("error: ").to_string())
+        })?;
+
+        if !command.has_all_versions() {
+            return Err(InstallManifestError::MissingVersions);
+        }
+
+        Ok(command)
+    }
+
+    /// The Modrinth version IDs to install, with the shared ones first.
+    pub fn mod_ids<'a>(&'a self, shared: &'a [String]) -> impl Iterator<Item = &'a String> {
+        let shared = if self.shared_mods { shared } else { &[] };
+        shared.iter().chain(&self.mods)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+mods = ["AANobbMI", "gvQqBUqZ"]
+
+[[servers]]
+name = "lobby"
+fork = "Fabric"
+versions = ["1.21.8", "0.16.14", "1.0.3"]
+port = 25566
+memory = "2G"
+
+[[servers]]
+name = "survival"
+fork = "fabric"
+versions = ["--latest-stable"]
+port = 25567
+mods = ["P7dR8mSH"]
+
+[[servers]]
+name = "limbo"
+fork = "vanilla"
+versions = ["1.21.8"]
+shared_mods = false
+"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest: InstallManifest = toml::from_str(MANIFEST).unwrap();
+        manifest.validate().unwrap();
+
+        assert_eq!(manifest.concurrency, DEFAULT_CONCURRENCY);
+        assert_eq!(manifest.servers.len(), 3);
+        assert_eq!(manifest.servers[0].memory.as_deref(), Some("2G"));
+
+        let mod_ids = |i: usize| {
+            manifest.servers[i]
+                .mod_ids(&manifest.mods)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(mod_ids(1), ["AANobbMI", "gvQqBUqZ", "P7dR8mSH"]);
+        assert!(mod_ids(2).is_empty());
+    }
+
+    #[test]
+    fn test_install_command() {
+        let manifest: InstallManifest = toml::from_str(MANIFEST).unwrap();
+
+        assert!(matches!(
+            manifest.servers[0].install_command(),
+            Ok(InstallCommand::Fabric { .. })
+        ));
+        assert!(matches!(
+            manifest.servers[1].install_command(),
+            Ok(InstallCommand::Fabric { .. })
+        ));
+        assert!(matches!(
+            manifest.servers[2].install_command(),
+            Ok(InstallCommand::Vanilla { .. })
+        ));
+    }
+
+    #[test]
+    fn test_install_command_errors() {
+        let server = |fork: &str, versions: &[&str]| ManifestServer {
+            name: "lobby".to_string(),
+            fork: fork.to_string(),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            port: None,
+            memory: None,
+            shared_mods: true,
+            mods: Vec::new(),
+        };
+
+        assert!(matches!(
+            server("paper", &["1.21.8"]).install_command(),
+            Err(InstallManifestError::InvalidFork(_))
+        ));
+        assert!(matches!(
+            server("fabric", &["1.21.8"]).install_command(),
+            Err(InstallManifestError::MissingVersions)
+        ));
+        assert!(matches!(
+            server("forge", &["--latest", "1.21.8-58.1.0"]).install_command(),
+            Err(InstallManifestError::InvalidVersions(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_duplicates() {
+        let manifest: InstallManifest = toml::from_str(
+            r#"
+[[servers]]
+name = "lobby"
+fork = "vanilla"
+port = 25566
+
+[[servers]]
+name = "hub"
+fork = "vanilla"
+port = 25566
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            manifest.validate(),
+            Err(InstallManifestError::DuplicatePort(25566))
+        ));
+
+        let manifest: InstallManifest = toml::from_str(
+            r#"
+[[servers]]
+name = "lobby"
+fork = "vanilla"
+
+[[servers]]
+name = "lobby"
+fork = "fabric"
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            manifest.validate(),
+            Err(InstallManifestError::DuplicateName(_))
+        ));
+    }
+}
@@ -0,0 +1,627 @@
+use crate::{
+    added_mods, bootstrap_server, confirm_mods_dir_change, download_cache, eula, is_interactive,
+    lock_server, mods_dir,
+    network::{
+        self, DownloadStats, PrintVersionMode, VersionEntry, fabric_meta, forge_meta,
+        modrinth::{self, ModVersion},
+        vanilla_meta,
+    },
+    phase, record_history, record_mod_change, register_location, registry_path, server_dir,
+    system::{
+        cancel::{self, CancelledError},
+        cli::ensure_version_exists,
+        config::Config,
+        forks::{self, Fork, ServerFork},
+        history,
+        prompt::{self, Danger},
+        registry::Registry,
+        server_name,
+    },
+};
+use dialoguer::Select;
+use reqwest::Client;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Which version of a component to install.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum VersionChoice {
+    #[default]
+    LatestStable,
+    /// Only for the game version of Vanilla.
+    LatestSnapshot,
+    /// Validated against the fork's meta API.
+    Exact(String),
+    /// Picked from a list, which fails when not interactive.
+    Pick,
+}
+
+/// Installs a server without going through the command line, see [`crate::api::ForkInstaller`].
+/// Versions that aren't chosen are the latest stable ones. Forge versions like
+/// `1.21.8-58.1.1` are chosen as the loader, and an exact game version only narrows down
+/// which of them is the latest.
+#[derive(Debug, Clone)]
+pub struct ServerInstaller {
+    fork: ServerFork,
+    game: VersionChoice,
+    loader: VersionChoice,
+    installer: VersionChoice,
+    server_name: Option<String>,
+    eula_accepted: bool,
+    bootstrap: bool,
+    with_mappings: bool,
+    mods: Vec<String>,
+    location: Option<PathBuf>,
+}
+
+impl ServerInstaller {
+    pub fn fork(&self) -> ServerFork {
+        self.fork
+    }
+
+    pub fn new(fork: ServerFork) -> Self {
+        Self {
+            fork,
+            game: VersionChoice::default(),
+            loader: VersionChoice::default(),
+            installer: VersionChoice::default(),
+            server_name: None,
+            eula_accepted: false,
+            bootstrap: false,
+            with_mappings: false,
+            mods: Vec::new(),
+            location: None,
+        }
+    }
+
+    pub fn vanilla() -> Self {
+        Self::new(ServerFork::Vanilla)
+    }
+
+    pub fn fabric() -> Self {
+        Self::new(ServerFork::Fabric)
+    }
+
+    pub fn forge() -> Self {
+        Self::new(ServerFork::Forge)
+    }
+
+    pub fn game(self, version: impl Into<String>) -> Self {
+        self.game_choice(VersionChoice::Exact(version.into()))
+    }
+
+    pub fn latest_stable_game(self) -> Self {
+        self.game_choice(VersionChoice::LatestStable)
+    }
+
+    pub fn latest_snapshot_game(self) -> Self {
+        self.game_choice(VersionChoice::LatestSnapshot)
+    }
+
+    pub fn game_choice(mut self, choice: VersionChoice) -> Self {
+        self.game = choice;
+        self
+    }
+
+    /// The Fabric loader version, or the Forge version like `1.21.8-58.1.1`.
+    pub fn loader(self, version: impl Into<String>) -> Self {
+        self.loader_choice(VersionChoice::Exact(version.into()))
+    }
+
+    pub fn latest_stable_loader(self) -> Self {
+        self.loader_choice(VersionChoice::LatestStable)
+    }
+
+    pub fn loader_choice(mut self, choice: VersionChoice) -> Self {
+        self.loader = choice;
+        self
+    }
+
+    /// The Fabric installer version.
+    pub fn installer(self, version: impl Into<String>) -> Self {
+        self.installer_choice(VersionChoice::Exact(version.into()))
+    }
+
+    pub fn latest_stable_installer(self) -> Self {
+        self.installer_choice(VersionChoice::LatestStable)
+    }
+
+    pub fn installer_choice(mut self, choice: VersionChoice) -> Self {
+        self.installer = choice;
+        self
+    }
+
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Accepts the Minecraft EULA for the server. Otherwise it's asked about when
+    /// interactive, and the server can't start until it's accepted.
+    pub fn eula_accepted(mut self, eula_accepted: bool) -> Self {
+        self.eula_accepted = eula_accepted;
+        self
+    }
+
+    /// Launches the server once after installing, see `mcerv bootstrap`.
+    pub fn bootstrap(mut self, bootstrap: bool) -> Self {
+        self.bootstrap = bootstrap;
+        self
+    }
+
+    /// Also downloads the server mappings of the game version, see `mcerv mappings fetch`.
+    pub fn with_mappings(mut self, with_mappings: bool) -> Self {
+        self.with_mappings = with_mappings;
+        self
+    }
+
+    /// Also installs the newest versions of the Modrinth projects, by slug or ID, for the
+    /// game version and loader, and the projects they require.
+    pub fn with_mods(mut self, mods: Vec<String>) -> Self {
+        self.mods = mods;
+        self
+    }
+
+    /// Installs the server into this directory instead of the instances directory.
+    pub fn location(mut self, location: impl Into<PathBuf>) -> Self {
+        self.location = Some(location.into());
+        self
+    }
+
+    /// Resolves the chosen versions without downloading anything.
+    pub async fn resolve(&self, client: &Client) -> anyhow::Result<ResolvedVersions> {
+        println!("Fetching versions...");
+        phase("fetching versions");
+        match self.fork {
+            ServerFork::Vanilla => self.resolve_vanilla(client).await,
+            ServerFork::Fabric => self.resolve_fabric(client).await,
+            ServerFork::Forge => self.resolve_forge(client).await,
+        }
+    }
+
+    async fn resolve_vanilla(&self, client: &Client) -> anyhow::Result<ResolvedVersions> {
+        let version = match &self.game {
+            VersionChoice::LatestStable => {
+                vanilla_meta::fetch_latest_stable_version(client).await?
+            }
+            VersionChoice::LatestSnapshot => {
+                vanilla_meta::fetch_latest_snapshot_version(client).await?
+            }
+            choice => {
+                let known = vanilla_meta::versions(client, PrintVersionMode::All).await?;
+                choose("Minecraft version", choice, known)?
+            }
+        };
+        Ok(ResolvedVersions::Vanilla(version))
+    }
+
+    async fn resolve_fabric(&self, client: &Client) -> anyhow::Result<ResolvedVersions> {
+        let fabric_versions = fabric_meta::versions(client).await?;
+        let known = fabric_versions.entries(PrintVersionMode::All);
+        let choices = [&self.game, &self.loader, &self.installer];
+        if choices.contains(&&VersionChoice::LatestSnapshot) {
+            anyhow::bail!("Only the game version of Vanilla can be the latest snapshot");
+        }
+        let latest_stable = if choices.contains(&&VersionChoice::LatestStable) {
+            let (game_version, loader_version, installer_version) =
+                fabric_versions.latest_stable()?;
+            [game_version, loader_version, installer_version]
+        } else {
+            Default::default()
+        };
+
+        let mut versions = Vec::new();
+        for (((kind, component), choice), latest_stable) in FABRIC_COMPONENTS
+            .into_iter()
+            .zip(choices)
+            .zip(latest_stable)
+        {
+            let version = match choice {
+                VersionChoice::LatestStable => latest_stable,
+                choice => {
+                    let candidates = known
+                        .iter()
+                        .filter(|v| v.component == Some(component))
+                        .cloned()
+                        .collect();
+                    choose(kind, choice, candidates)?
+                }
+            };
+            versions.push(version);
+        }
+
+        let [game_version, loader_version, installer_version] = versions.try_into().unwrap();
+        Ok(ResolvedVersions::Fabric((
+            game_version,
+            loader_version,
+            installer_version,
+        )))
+    }
+
+    async fn resolve_forge(&self, client: &Client) -> anyhow::Result<ResolvedVersions> {
+        let game_version = match &self.game {
+            VersionChoice::Exact(game_version) => Some(game_version.as_str()),
+            _ => None,
+        };
+        if [&self.game, &self.loader].contains(&&VersionChoice::LatestSnapshot) {
+            anyhow::bail!("Only the game version of Vanilla can be the latest snapshot");
+        }
+        let version = match (&self.loader, game_version) {
+            (VersionChoice::LatestStable, None) => forge_meta::fetch_latest_version(client).await?,
+            (VersionChoice::LatestStable, Some(game_version)) => {
+                // Sorted newest first
+                forge_meta::versions(client, Some(game_version), false)
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("No Forge version for {game_version}"))?
+                    .id
+            }
+            (choice, game_version) => {
+                let known = forge_meta::versions(client, game_version, false).await?;
+                choose("Forge version", choice, known)?
+            }
+        };
+        Ok(ResolvedVersions::Forge(version))
+    }
+
+    /// Installs the server, returning the file name of its jar.
+    pub async fn install(self, client: &Client) -> anyhow::Result<String> {
+        let Some(server_name) = self.server_name.as_deref() else {
+            anyhow::bail!("The server needs a name to be installed");
+        };
+        server_name::validate(server_name)?;
+        if !self.mods.is_empty() && self.fork == ServerFork::Vanilla {
+            anyhow::bail!("Vanilla servers can't have mods installed");
+        }
+        let eula_agreed = self.eula_accepted
+            || prompt::confirm(
+                Danger::Destructive,
+                &format!(
+                    "Do you agree to Minecraft server EULA? Please ensure you have read and understood the EULA at: {}",
+                    eula::EULA_URL
+                ),
+            )
+            .unwrap_or(false);
+
+        let token = cancel::token();
+        let scope = cancel::scope();
+        let versions = cancel::or_cancelled(&token, self.resolve(client)).await??;
+
+        if let Some(location) = &self.location {
+            register_location(server_name, location)?;
+        }
+        let server_dir = server_dir(server_name)?;
+        let existed = server_dir.exists();
+        let install = async {
+            fs::create_dir_all(&server_dir)?;
+            let _lock = lock_server(&server_dir, "install")?;
+
+            if eula_agreed {
+                eula::accept(&server_dir)?;
+            }
+
+            let start = Instant::now();
+            let game_version = versions.game_version().to_string();
+            // The mods are resolved while the jar downloads
+            let loader = format!("{:?}", self.fork).to_lowercase();
+            let (installed, (mod_versions, unresolved)) = tokio::join!(
+                versions.install(server_name, client),
+                modrinth::resolve_with_dependencies(client, &self.mods, &loader, &game_version)
+            );
+            let (filename, stats) = installed?;
+            println!("Downloaded {stats}");
+            println!("Install complete. Duration: {:?}", start.elapsed());
+
+            let mut config = Config::new_4gb(filename.clone())?;
+            config.start_command_template = forks::forge_start_template(&server_dir, &filename);
+            config.save(server_name)?;
+            let mut event = history::Event::new("install");
+            event.new_versions = history::jar_versions(&server_dir, &filename, self.fork);
+            let mut failed = Vec::new();
+            if !mod_versions.is_empty() {
+                // The server itself is installed by now, so failed mods are reported instead
+                match install_mods(server_name, &mod_versions, client).await {
+                    Ok((installed, failures)) => {
+                        event.mods = installed.into_iter().flat_map(added_mods).collect();
+                        failed = failures;
+                    }
+                    Err(e) => failed.push(format!("all mods: {e:#}")),
+                }
+            }
+            record_history(&server_dir, &event);
+            if !unresolved.is_empty() {
+                eprintln!("Failed to resolve {} mods:", unresolved.len());
+                for unresolved in &unresolved {
+                    eprintln!("  {unresolved}");
+                }
+            }
+            if !failed.is_empty() {
+                eprintln!("Failed to download {} mods:", failed.len());
+                for failed in &failed {
+                    eprintln!("  {failed}");
+                }
+            }
+
+            if self.with_mappings {
+                println!("Downloading server mappings...");
+                phase("downloading server mappings");
+                match vanilla_meta::download_mappings(client, &game_version, &server_dir).await {
+                    Ok((path, stats)) => println!("Downloaded {stats} to {}", path.display()),
+                    // The server works without them
+                    Err(e) => eprintln!("Warning: Failed to download the server mappings: {e:#}"),
+                }
+            }
+            anyhow::Ok(filename)
+        };
+        let filename = match remove_if_cancelled(&token, &server_dir, existed, install).await {
+            // Otherwise the failed install keeps the name taken
+            Err(e) if self.location.is_some() => {
+                let unregistered = Registry::load(&registry_path()).and_then(|mut registry| {
+                    registry.remove(server_name);
+                    registry.save(&registry_path())
+                });
+                if let Err(e) = unregistered {
+                    eprintln!("Warning: Failed to unregister {server_name}: {e:#}");
+                }
+                return Err(e);
+            }
+            result => result?,
+        };
+        drop(scope);
+        println!("Config created and saved");
+        println!("Server added: {server_name}");
+
+        if self.bootstrap {
+            bootstrap_server(server_name).await?;
+        }
+        Ok(filename)
+    }
+}
+
+/// Downloads the files of the versions into the server's mods directory at once. Returns
+/// the versions that were installed, and a message for each one that failed.
+async fn install_mods<'a>(
+    server_name: &str,
+    versions: &'a [ModVersion],
+    client: &Client,
+) -> anyhow::Result<(Vec<&'a ModVersion>, Vec<String>)> {
+    println!("Downloading {} mods...", versions.len());
+    phase("downloading mods");
+    let mods_dir = mods_dir(server_name)?;
+    confirm_mods_dir_change(&mods_dir)?;
+    fs::create_dir_all(&mods_dir)?;
+    let cache = download_cache();
+    let downloads = versions.iter().map(|version| {
+        let files = version
+            .install_files()
+            .into_iter()
+            .map(|file| {
+                (
+                    file.url.as_str(),
+                    file.sha512.as_str(),
+                    mods_dir.join(&file.file_name),
+                )
+            })
+            .collect::<Vec<_>>();
+        let cache = &cache;
+        async move { network::download_files(client, &files, cache).await }
+    });
+    let results = futures::future::join_all(downloads).await;
+
+    let mut installed = Vec::new();
+    let mut failed = Vec::new();
+    for (version, result) in versions.iter().zip(results) {
+        let names = version.install_file_names();
+        match result.and_then(|_| record_mod_change(server_name, version)) {
+            Ok(_) => {
+                println!("Mod downloaded: {names}");
+                installed.push(version);
+            }
+            Err(e) => failed.push(format!("{names}: {e:#}")),
+        }
+    }
+    Ok((installed, failed))
+}
+
+/// Runs the install into `server_dir`, which is removed again if the install is cancelled
+/// and it didn't exist before. A cancelled install fails with [`CancelledError`], whatever
+/// failed after Ctrl-C, like a Forge installer it interrupted.
+async fn remove_if_cancelled<T>(
+    token: &CancellationToken,
+    server_dir: &Path,
+    existed: bool,
+    install: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    match install.await {
+        Err(_) if token.is_cancelled() => {
+            if !existed && server_dir.exists() {
+                fs::remove_dir_all(server_dir)?;
+            }
+            Err(CancelledError.into())
+        }
+        result => result,
+    }
+}
+
+/// (kind, component in fabric meta) of each fabric version
+const FABRIC_COMPONENTS: [(&str, &str); 3] = [
+    ("Minecraft version", "Minecraft"),
+    ("loader version", "Fabric Loader"),
+    ("installer version", "Installer"),
+];
+
+/// The exact version if it's known, or one picked from the known versions.
+fn choose(
+    kind: &'static str,
+    choice: &VersionChoice,
+    known: Vec<VersionEntry>,
+) -> anyhow::Result<String> {
+    match choice {
+        VersionChoice::Exact(version) => {
+            let ids = known.iter().map(|v| v.id.as_str()).collect::<Vec<_>>();
+            ensure_version_exists(kind, version, &ids)?;
+            Ok(version.clone())
+        }
+        _ => pick_version(kind, known),
+    }
+}
+
+/// Asks the user to pick one of the versions, stable ones on top.
+fn pick_version(kind: &str, versions: Vec<VersionEntry>) -> anyhow::Result<String> {
+    if !is_interactive() {
+        anyhow::bail!("No {kind} was given, and it can't be picked when not interactive");
+    }
+
+    let (stable, unstable): (Vec<_>, Vec<_>) = versions.into_iter().partition(|v| v.stable);
+    let versions = stable.into_iter().chain(unstable).collect::<Vec<_>>();
+
+    let items = versions
+        .iter()
+        .map(|v| {
+            let label = match &v.version_type {
+                Some(version_type) => version_type.as_str(),
+                None if v.stable => "stable",
+                None => "unstable",
+            };
+            format!("{} ({label})", v.id)
+        })
+        .collect::<Vec<_>>();
+
+    let selection = Select::new()
+        .with_prompt(kind)
+        .items(&items)
+        .default(0)
+        .max_length(15)
+        .interact()?;
+
+    Ok(versions[selection].id.clone())
+}
+
+/// The versions an installer resolved to, before anything is downloaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedVersions {
+    Vanilla(String),
+    /// The game, loader and installer versions.
+    Fabric((String, String, String)),
+    /// Like `1.21.8-58.1.0`.
+    Forge(String),
+}
+
+impl ResolvedVersions {
+    pub fn game_version(&self) -> &str {
+        match self {
+            ResolvedVersions::Vanilla(version) => version,
+            ResolvedVersions::Fabric((game_version, _, _)) => game_version,
+            ResolvedVersions::Forge(version) => version
+                .split_once('-')
+                .map_or(version, |(game_version, _)| game_version),
+        }
+    }
+
+    /// Downloads the server jar into the server's directory, returning its file name and
+    /// what the download took.
+    pub async fn install(
+        self,
+        server_name: &str,
+        client: &Client,
+    ) -> anyhow::Result<(String, DownloadStats)> {
+        match self {
+            ResolvedVersions::Vanilla(version) => {
+                println!("Downloading server jar...");
+                phase("downloading server jar");
+                forks::Vanilla::install(server_name, version, client).await
+            }
+            ResolvedVersions::Fabric(versions) => {
+                println!("Downloading server jar...");
+                phase("downloading server jar");
+                forks::Fabric::install(server_name, versions, client).await
+            }
+            ResolvedVersions::Forge(version) => {
+                forks::Forge::install(server_name, version, client).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::cli::{FabricVersionArgs, Versions};
+
+    #[test]
+    fn test_fabric_args() {
+        let args = FabricVersionArgs {
+            latest_stable: true,
+            game_version: Some("1.21.8".to_string()),
+            loader_version: None,
+            installer_version: Some("1.1.0".to_string()),
+        };
+        let installer = args.installer().unwrap();
+        assert_eq!(installer.fork, ServerFork::Fabric);
+        assert_eq!(installer.game, VersionChoice::Exact("1.21.8".to_string()));
+        assert_eq!(installer.loader, VersionChoice::LatestStable);
+        assert_eq!(
+            installer.installer,
+            VersionChoice::Exact("1.1.0".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_if_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path().join("smp");
+        let token = CancellationToken::new();
+        let install = |token: CancellationToken| {
+            let server_dir = server_dir.clone();
+            async move {
+                fs::create_dir_all(&server_dir)?;
+                fs::write(server_dir.join("server.jar.part"), "")?;
+                token.cancel();
+                cancel::or_cancelled(&token, std::future::pending::<()>()).await?;
+                anyhow::Ok(())
+            }
+        };
+
+        let err = remove_if_cancelled(&token, &server_dir, false, install(token.clone()))
+            .await
+            .unwrap_err();
+        assert!(cancel::is_cancelled(&err));
+        assert!(!server_dir.exists());
+
+        // An instance that existed before is kept
+        fs::create_dir_all(&server_dir).unwrap();
+        let token = CancellationToken::new();
+        let err = remove_if_cancelled(&token, &server_dir, true, install(token.clone()))
+            .await
+            .unwrap_err();
+        assert!(cancel::is_cancelled(&err));
+        assert!(server_dir.exists());
+
+        // Other errors are passed on
+        let token = CancellationToken::new();
+        let failing = async { anyhow::bail!("No space left") };
+        let err = remove_if_cancelled::<()>(&token, &server_dir, false, failing)
+            .await
+            .unwrap_err();
+        assert!(!cancel::is_cancelled(&err));
+    }
+
+    #[test]
+    fn test_game_version() {
+        assert_eq!(
+            ResolvedVersions::Forge("1.21.8-58.1.0".to_string()).game_version(),
+            "1.21.8"
+        );
+        let fabric = (
+            "1.21.8".to_string(),
+            "0.17.2".to_string(),
+            "1.1.0".to_string(),
+        );
+        assert_eq!(ResolvedVersions::Fabric(fabric).game_version(), "1.21.8");
+    }
+}
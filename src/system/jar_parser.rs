@@ -1,12 +1,15 @@
+use crate::system::forks::{self, ServerFork};
 use anyhow::anyhow;
+use serde::Deserialize;
 use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha512};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
     fmt::Display,
     fs::{self, File},
-    io::{self, BufReader, Read, Seek},
-    path::{Path, PathBuf},
+    io::{self, BufReader, Cursor, Read, Seek},
+    path::{Component, Path, PathBuf},
 };
 use zip::ZipArchive;
 
@@ -29,50 +32,228 @@ impl Display for InvalidServerDirError {
 
 impl Error for InvalidServerDirError {}
 
-/// Returns the first `.jar` file found in the server directory.
+/// A file read from an archive is held in memory, so files like `MANIFEST.MF` are capped.
+pub const MAX_READ_SIZE: u64 = 16 * 1024 * 1024;
+/// A jar nested in another, like the server jar in the vanilla bundler, is read into memory
+/// whole as well.
+pub const MAX_NESTED_JAR_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Archives from elsewhere are refused when they don't fit these.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    pub max_entries: usize,
+    /// Decompressed, counted while extracting rather than trusting the archive.
+    pub max_entry_size: u64,
+    pub max_total_size: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_entry_size: 512 * 1024 * 1024,
+            max_total_size: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArchiveError {
+    /// Absolute, leaving the destination with `..`, or a symlink.
+    UnsafePath(String),
+    TooManyEntries(usize),
+    EntryTooLarge(String),
+    TooLarge,
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::UnsafePath(path) => {
+                write!(f, "The archive has an entry outside its directory: {path}")
+            }
+            ArchiveError::TooManyEntries(count) => {
+                write!(f, "The archive has too many entries: {count}")
+            }
+            ArchiveError::EntryTooLarge(path) => {
+                write!(f, "{path} in the archive is too large when decompressed")
+            }
+            ArchiveError::TooLarge => write!(f, "The archive is too large when decompressed"),
+        }
+    }
+}
+
+impl Error for ArchiveError {}
+
+/// Jars that are never the server jar, even if they're the only one.
+const NON_SERVER_JAR_SUFFIXES: [&str; 2] = ["-installer.jar", "-sources.jar"];
+
+/// Returns the server jar in the server directory.
+///
+/// Jars named like [`NON_SERVER_JAR_SUFFIXES`] are skipped. If more than one jar is left,
+/// only those whose Main-Class is of a known fork are kept. The vanilla jar the Fabric
+/// launcher runs, `server.jar`, is dropped if a Fabric jar is there as well.
 ///
 /// # Errors
-/// - If there are multiple `.jar` files, returns [`InvalidServerDirError::MultipleJars`].
+/// - If there are multiple candidates left, returns [`InvalidServerDirError::MultipleJars`].
 /// - If no `.jar` file is found, returns [`InvalidServerDirError::NoJar`].
 /// - If trouble reading the directory, returns the underlying [`io::Error`].
+///
+/// A Forge server without a jar has its argument file returned instead, see
+/// [`forks::find_forge_args_file`].
 pub fn single_jar(server_dir: impl AsRef<Path>) -> anyhow::Result<PathBuf> {
-    let mut jars = jar_files(server_dir)?.into_iter();
-    let jar = jars.next();
+    let server_dir = server_dir.as_ref();
+    let mut candidates = jar_files(server_dir)?;
+    candidates.retain(|path| {
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        !NON_SERVER_JAR_SUFFIXES
+            .iter()
+            .any(|suffix| file_name.ends_with(suffix))
+    });
 
-    if jars.next().is_some() {
-        anyhow::bail!(InvalidServerDirError::MultipleJars);
+    if candidates.len() > 1 {
+        let mut server_jars = candidates
+            .into_iter()
+            .filter_map(|path| {
+                let fork = archive(&path)
+                    .and_then(|mut archive| forks::detect_server_fork(&mut archive))
+                    .ok()?;
+                Some((path, fork))
+            })
+            .collect::<Vec<_>>();
+        if server_jars
+            .iter()
+            .any(|(_, fork)| *fork == ServerFork::Fabric)
+        {
+            server_jars.retain(|(_, fork)| *fork != ServerFork::Vanilla);
+        }
+
+        if server_jars.len() != 1 {
+            anyhow::bail!(InvalidServerDirError::MultipleJars);
+        }
+        candidates = server_jars.into_iter().map(|(path, _)| path).collect();
     }
 
-    jar.ok_or(anyhow::anyhow!(InvalidServerDirError::NoJar))
+    candidates
+        .pop()
+        .or_else(|| forks::find_forge_args_file(server_dir))
+        .ok_or(anyhow!(InvalidServerDirError::NoJar))
 }
 
-/// Returns all `.jar` files found in the server directory.
-pub fn jar_files(server_dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
-    let server_dir = server_dir.as_ref();
+/// The config's name for a jar [`single_jar`] found, relative to the server directory.
+pub fn jar_name(server_dir: &Path, jar: &Path) -> String {
+    let relative = jar.strip_prefix(server_dir).unwrap_or(jar);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns all `.jar` files found in the directory, skipping directories and other files.
+/// Entries that can't be read, like broken symlinks, are skipped with a warning.
+pub fn jar_files(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
     let mut jars = vec![];
 
-    for entry in fs::read_dir(server_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
+    for entry in fs::read_dir(dir)? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                eprintln!("Warning: failed to read an entry of {}: {e}", dir.display());
+                continue;
+            }
+        };
         if path.extension() != Some(std::ffi::OsStr::new("jar")) {
             continue;
         }
-        jars.push(path);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => jars.push(path),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: skipping {}: {e}", path.display()),
+        }
     }
 
     Ok(jars)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// The extension of checksum files published next to maven artifacts,
+    /// which is also the name Modrinth uses for the algorithm.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+/// A hex encoded hash and the algorithm it was calculated with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHash {
+    pub algorithm: HashAlgorithm,
+    pub value: String,
+}
+
 // Calculate the SHA1 hash of the file contents.
 pub fn calculate_hash(file: &mut File) -> std::io::Result<String> {
-    let mut hasher = Sha1::new();
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    hasher.update(&buffer);
-    Ok(format!("{:x}", hasher.finalize()))
+    calculate_hash_with(file, HashAlgorithm::Sha1)
+}
+
+/// Calculates the hash of the reader's contents without loading it all into memory.
+pub fn calculate_hash_with(
+    reader: &mut impl Read,
+    algorithm: HashAlgorithm,
+) -> std::io::Result<String> {
+    match algorithm {
+        HashAlgorithm::Sha1 => hash_reader::<Sha1>(reader),
+        HashAlgorithm::Sha256 => hash_reader::<Sha256>(reader),
+        HashAlgorithm::Sha512 => hash_reader::<Sha512>(reader),
+    }
+}
+
+/// Calculates the hash of the file once for each algorithm.
+pub fn calculate_hashes(
+    file: &mut File,
+    algorithms: &[HashAlgorithm],
+) -> std::io::Result<Vec<FileHash>> {
+    algorithms
+        .iter()
+        .map(|&algorithm| {
+            file.rewind()?;
+            Ok(FileHash {
+                algorithm,
+                value: calculate_hash_with(file, algorithm)?,
+            })
+        })
+        .collect()
+}
+
+fn hash_reader<D: Digest>(reader: &mut impl Read) -> std::io::Result<String> {
+    let mut hasher = D::new();
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
 }
 
 pub fn read_file<R: Read + Seek>(
@@ -84,17 +265,307 @@ pub fn read_file<R: Read + Seek>(
         .map_err(|_| anyhow!("{} not found in JAR", file_name))?;
 
     let mut content = String::new();
-    file_in_jar.read_to_string(&mut content)?;
+    file_in_jar
+        .by_ref()
+        .take(MAX_READ_SIZE + 1)
+        .read_to_string(&mut content)?;
+    if content.len() as u64 > MAX_READ_SIZE {
+        return Err(ArchiveError::EntryTooLarge(file_name.to_string()).into());
+    }
 
     Ok(content)
 }
 
+/// Opens a jar stored in the archive, in memory.
+pub fn nested_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    file_name: &str,
+) -> anyhow::Result<ZipArchive<Cursor<Vec<u8>>>> {
+    let mut file_in_jar = archive
+        .by_name(file_name)
+        .map_err(|_| anyhow!("{} not found in JAR", file_name))?;
+
+    let mut content = Vec::new();
+    file_in_jar
+        .by_ref()
+        .take(MAX_NESTED_JAR_SIZE + 1)
+        .read_to_end(&mut content)?;
+    if content.len() as u64 > MAX_NESTED_JAR_SIZE {
+        return Err(ArchiveError::EntryTooLarge(file_name.to_string()).into());
+    }
+
+    Ok(ZipArchive::new(Cursor::new(content))?)
+}
+
+/// Writes the archive entry `name` to `dest`, returning its size. `total` is the size of the
+/// entries extracted before it, and grows by this one. An entry over the limits is removed
+/// again.
+pub fn safe_extract(
+    entry: &mut impl Read,
+    name: &str,
+    dest: &Path,
+    limits: &ExtractLimits,
+    total: &mut u64,
+) -> anyhow::Result<u64> {
+    let remaining = limits.max_total_size.saturating_sub(*total);
+    let limit = limits.max_entry_size.min(remaining);
+    let size = io::copy(&mut entry.take(limit + 1), &mut File::create(dest)?)?;
+    if size > limit {
+        fs::remove_file(dest)?;
+        return Err(if size > limits.max_entry_size {
+            ArchiveError::EntryTooLarge(name.to_string()).into()
+        } else {
+            ArchiveError::TooLarge.into()
+        });
+    }
+    *total += size;
+
+    Ok(size)
+}
+
+/// The relative path of an archive entry, or `None` if it would be outside the destination.
+pub fn contained_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for part in name.split('/') {
+        // Like `C:` or a `\` separated path, which would be one part here
+        if part.contains(['\\', ':']) || part.chars().any(char::is_control) {
+            return None;
+        }
+        match Path::new(part).components().next() {
+            Some(Component::Normal(_)) => path.push(part),
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
 pub fn archive(jar_path: impl AsRef<Path>) -> anyhow::Result<ZipArchive<BufReader<File>>> {
     let jar_file = File::open(jar_path)?;
     let archive = ZipArchive::new(BufReader::new(jar_file))?;
     Ok(archive)
 }
 
+/// The id and version a mod jar declares for its loader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModMetadata {
+    pub id: String,
+    pub version: String,
+}
+
+#[derive(Deserialize)]
+struct FabricModJson {
+    id: String,
+    version: String,
+    /// `*` for both sides, `client` or `server`.
+    #[serde(default)]
+    environment: Option<String>,
+    /// The mod IDs it needs, with the versions of each.
+    #[serde(default)]
+    depends: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    breaks: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    conflicts: BTreeMap<String, serde_json::Value>,
+}
+
+/// The mods a Fabric mod can't run with, by mod ID with the versions of each, any of which
+/// matches.
+#[derive(Debug, Default, PartialEq)]
+pub struct FabricIncompatibilities {
+    /// The loader refuses to start with these.
+    pub breaks: BTreeMap<String, Vec<String>>,
+    /// The loader only warns about these.
+    pub conflicts: BTreeMap<String, Vec<String>>,
+}
+
+/// The side a Fabric mod runs on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModEnvironment {
+    Both,
+    Client,
+    Server,
+}
+
+/// Reads the mod metadata from `fabric.mod.json`, or `META-INF/mods.toml` for Forge mods.
+/// Returns `None` if the jar has neither.
+pub fn read_mod_metadata(jar_path: impl AsRef<Path>) -> anyhow::Result<Option<ModMetadata>> {
+    let mut archive = archive(jar_path)?;
+
+    if let Ok(content) = read_file(&mut archive, "fabric.mod.json") {
+        let mod_json: FabricModJson = serde_json::from_str(&content)?;
+        return Ok(Some(ModMetadata {
+            id: mod_json.id,
+            version: mod_json.version,
+        }));
+    }
+
+    let Ok(content) = read_file(&mut archive, "META-INF/mods.toml") else {
+        return Ok(None);
+    };
+    let Some(mut metadata) = parse_mods_toml(&content) else {
+        return Ok(None);
+    };
+
+    // Forge substitutes this with the version in the manifest
+    if metadata.version == "${file.jarVersion}" {
+        let manifest = parse_manifest(&read_file(&mut archive, "META-INF/MANIFEST.MF")?);
+        if let Some(version) = manifest.get("Implementation-Version") {
+            metadata.version = version.trim().to_string();
+        }
+    }
+
+    Ok(Some(metadata))
+}
+
+fn read_fabric_mod_json(jar_path: impl AsRef<Path>) -> anyhow::Result<Option<FabricModJson>> {
+    let mut archive = archive(jar_path)?;
+    let Ok(content) = read_file(&mut archive, "fabric.mod.json") else {
+        return Ok(None);
+    };
+    Ok(Some(serde_json::from_str(&content)?))
+}
+
+/// Reads the side the mod runs on from `fabric.mod.json`. Returns `None` if the jar has
+/// none, like Forge mods.
+pub fn read_fabric_environment(
+    jar_path: impl AsRef<Path>,
+) -> anyhow::Result<Option<ModEnvironment>> {
+    let Some(mod_json) = read_fabric_mod_json(jar_path)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(match mod_json.environment.as_deref() {
+        Some("client") => ModEnvironment::Client,
+        Some("server") => ModEnvironment::Server,
+        _ => ModEnvironment::Both,
+    }))
+}
+
+/// Reads the mod IDs the mod depends on from `fabric.mod.json`, or the mandatory
+/// dependencies of `META-INF/mods.toml` for Forge mods. Returns `None` if the jar has neither.
+pub fn read_depends(jar_path: impl AsRef<Path>) -> anyhow::Result<Option<Vec<String>>> {
+    let mut archive = archive(jar_path)?;
+    if let Ok(content) = read_file(&mut archive, "fabric.mod.json") {
+        let mod_json: FabricModJson = serde_json::from_str(&content)?;
+        return Ok(Some(mod_json.depends.into_keys().collect()));
+    }
+
+    let Ok(content) = read_file(&mut archive, "META-INF/mods.toml") else {
+        return Ok(None);
+    };
+    Ok(Some(parse_mods_toml_depends(&content)))
+}
+
+/// Reads `breaks` and `conflicts` from `fabric.mod.json`. Returns `None` if the jar has none.
+pub fn read_fabric_incompatibilities(
+    jar_path: impl AsRef<Path>,
+) -> anyhow::Result<Option<FabricIncompatibilities>> {
+    Ok(
+        read_fabric_mod_json(jar_path)?.map(|mod_json| FabricIncompatibilities {
+            breaks: version_predicates(mod_json.breaks),
+            conflicts: version_predicates(mod_json.conflicts),
+        }),
+    )
+}
+
+/// A predicate is a string, or an array of strings any of which matches.
+fn version_predicates(
+    relations: BTreeMap<String, serde_json::Value>,
+) -> BTreeMap<String, Vec<String>> {
+    relations
+        .into_iter()
+        .map(|(id, predicates)| {
+            let predicates = match predicates {
+                serde_json::Value::String(predicate) => vec![predicate],
+                serde_json::Value::Array(predicates) => predicates
+                    .iter()
+                    .filter_map(|predicate| predicate.as_str().map(str::to_string))
+                    .collect(),
+                _ => vec!["*".to_string()],
+            };
+            (id, predicates)
+        })
+        .collect()
+}
+
+/// Reads `modId` and `version` of the first `[[mods]]` table in a Forge `mods.toml`.
+fn parse_mods_toml(content: &str) -> Option<ModMetadata> {
+    let mut in_mods = false;
+    let (mut id, mut version) = (None, None);
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            if in_mods {
+                break;
+            }
+            in_mods = line == "[[mods]]";
+            continue;
+        }
+        if !in_mods {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string();
+        match key.trim() {
+            "modId" => id = Some(value),
+            "version" => version = Some(value),
+            _ => {}
+        }
+    }
+
+    Some(ModMetadata {
+        id: id?,
+        version: version.unwrap_or_default(),
+    })
+}
+
+/// Reads the `modId` of each `[[dependencies.<mod>]]` table in a Forge `mods.toml` that is
+/// `mandatory`, or of the `required` type like NeoForge has it.
+fn parse_mods_toml_depends(content: &str) -> Vec<String> {
+    let mut depends = Vec::new();
+    let mut dependency: Option<(Option<String>, bool)> = None;
+    let mut finish = |dependency: &mut Option<(Option<String>, bool)>| {
+        if let Some((Some(id), true)) = dependency.take()
+            && !depends.contains(&id)
+        {
+            depends.push(id);
+        }
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            finish(&mut dependency);
+            if line.starts_with("[[dependencies.") {
+                dependency = Some((None, false));
+            }
+            continue;
+        }
+        let (Some((id, required)), Some((key, value))) = (&mut dependency, line.split_once('='))
+        else {
+            continue;
+        };
+        // Without a trailing comment, mod IDs and these values have no `#`
+        let value = value.split('#').next().unwrap_or_default();
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        match key.trim() {
+            "modId" => *id = Some(value.to_string()),
+            "mandatory" => *required = value == "true",
+            "type" => *required = value.eq_ignore_ascii_case("required"),
+            _ => {}
+        }
+    }
+    finish(&mut dependency);
+
+    depends
+}
+
 pub fn parse_properties(content: &str) -> HashMap<String, String> {
     let mut map = HashMap::new();
 
@@ -108,15 +579,242 @@ pub fn parse_properties(content: &str) -> HashMap<String, String> {
     map
 }
 
-pub fn parse_manifest(content: &str) -> HashMap<String, String> {
-    let mut map = HashMap::new();
+/// The main attributes of a `MANIFEST.MF`, looked up case-insensitively like the JVM does.
+#[derive(Debug, Default)]
+pub struct Manifest(HashMap<String, String>);
+
+impl Manifest {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+/// Parses the main section of a manifest, the lines of `Name: value` before the first
+/// blank line. The sections after it, like the digests of signed jars, are skipped.
+///
+/// Lines are wrapped at 72 bytes, a line starting with a space continues the one before it.
+pub fn parse_manifest(content: &str) -> Manifest {
+    let mut attributes = HashMap::new();
+    let mut current: Option<(String, String)> = None;
 
-    // Manifest format is `key: value`
     for line in content.lines() {
-        if let Some((key, value)) = line.split_once(": ") {
-            map.insert(key.to_string(), value.to_string());
+        if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some((_, value)) = &mut current {
+                value.push_str(continuation);
+            }
+            continue;
+        }
+
+        attributes.extend(current.take());
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            current = Some((name.trim().to_ascii_lowercase(), value.to_string()));
         }
     }
+    attributes.extend(current);
 
-    map
+    Manifest(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(
+            "Manifest-Version: 1.0\r\nmain-class: net.minecraft.bund\r\n ler.Main\r\nImplementation-Version:  1.0\r\n\r\nName: a.class\r\nMain-Class: other\r\n",
+        );
+        assert_eq!(
+            manifest.get("Main-Class"),
+            Some("net.minecraft.bundler.Main")
+        );
+        assert_eq!(
+            manifest.get("MAIN-CLASS"),
+            Some("net.minecraft.bundler.Main")
+        );
+        // Only the separating space is dropped
+        assert_eq!(manifest.get("Implementation-Version"), Some(" 1.0"));
+        assert_eq!(manifest.get("Name"), None);
+    }
+
+    #[test]
+    fn test_parse_mods_toml() {
+        let content = r#"
+modLoader="javafml"
+loaderVersion="[58,)"
+
+[[mods]]
+modId="pipez"
+version="${file.jarVersion}"
+displayName="Pipez"
+
+[[dependencies.pipez]]
+modId="forge"
+"#;
+        assert_eq!(
+            parse_mods_toml(content),
+            Some(ModMetadata {
+                id: "pipez".to_string(),
+                version: "${file.jarVersion}".to_string(),
+            })
+        );
+        assert_eq!(parse_mods_toml("modLoader=\"javafml\""), None);
+    }
+
+    #[test]
+    fn test_parse_mods_toml_depends() {
+        let content = r#"
+modLoader="javafml"
+[[mods]]
+modId="create"
+[[dependencies.create]]
+    modId="forge"
+    mandatory=true
+[[dependencies.create]]
+    modId="flywheel"
+    mandatory=true # Renders the contraptions
+[[dependencies.create]]
+    modId="jei"
+    mandatory=false
+[[dependencies.create]]
+    modId="ponder"
+    type="required"
+[[dependencies.create]]
+    modId="curios"
+    type="optional"
+"#;
+        assert_eq!(
+            parse_mods_toml_depends(content),
+            ["forge", "flywheel", "ponder"]
+        );
+    }
+
+    #[test]
+    fn test_calculate_hash_with() {
+        let content = b"hello world";
+
+        assert_eq!(
+            calculate_hash_with(&mut &content[..], HashAlgorithm::Sha1).unwrap(),
+            "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+        );
+        assert_eq!(
+            calculate_hash_with(&mut &content[..], HashAlgorithm::Sha256).unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(
+            calculate_hash_with(&mut &content[..], HashAlgorithm::Sha512).unwrap(),
+            "309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f"
+        );
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in entries {
+            writer.start_file(*name, options).unwrap();
+            io::Write::write_all(&mut writer, content).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_contained_path() {
+        assert_eq!(
+            contained_path("config/a.toml"),
+            Some(PathBuf::from("config").join("a.toml"))
+        );
+        for name in [
+            "../evil.sh",
+            "config/../../evil.sh",
+            "/etc/evil",
+            "..\\evil.sh",
+            "C:/evil.sh",
+            "config//evil.sh",
+            "./evil.sh",
+            "config/",
+            "config/evil\0.sh",
+        ] {
+            assert_eq!(contained_path(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_safe_extract_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("a");
+        let limits = ExtractLimits {
+            max_entries: 3,
+            max_entry_size: 1024,
+            max_total_size: 1536,
+        };
+        let extract = |content: &[u8], total: &mut u64| {
+            safe_extract(&mut &content[..], "a", &dest, &limits, total)
+                .map_err(|e| e.downcast::<ArchiveError>().unwrap())
+        };
+
+        let mut total = 0;
+        let kilobyte = [0; 1024];
+        assert_eq!(extract(&kilobyte, &mut total), Ok(1024));
+        assert_eq!(total, 1024);
+        assert_eq!(fs::read(&dest).unwrap(), kilobyte);
+        assert_eq!(
+            extract(&[0; 1025], &mut 0),
+            Err(ArchiveError::EntryTooLarge("a".to_string()))
+        );
+        assert_eq!(extract(&kilobyte, &mut total), Err(ArchiveError::TooLarge));
+        assert!(!dest.exists());
+        assert_eq!(total, 1024);
+    }
+
+    #[test]
+    fn test_read_file_cap() {
+        let large = vec![b'a'; MAX_READ_SIZE as usize + 1];
+        let mut archive = zip(&[("META-INF/MANIFEST.MF", &large), ("version.json", b"{}")]);
+        let error = read_file(&mut archive, "META-INF/MANIFEST.MF").unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ArchiveError::EntryTooLarge(
+                "META-INF/MANIFEST.MF".to_string()
+            ))
+        );
+        assert_eq!(read_file(&mut archive, "version.json").unwrap(), "{}");
+    }
+
+    fn server_dir(name: &str) -> PathBuf {
+        Path::new("testdata/server-dirs").join(name)
+    }
+
+    #[test]
+    fn test_single_jar_skips_installer() {
+        let jar = single_jar(server_dir("forge-with-installer")).unwrap();
+        assert_eq!(jar.file_name().unwrap(), "forge-1.21.8-58.1.0-shim.jar");
+    }
+
+    #[test]
+    fn test_single_jar_prefers_fabric_launcher() {
+        let jar = single_jar(server_dir("fabric-with-server-jar")).unwrap();
+        assert_eq!(jar.file_name().unwrap(), "fabric-server-launch.jar");
+    }
+
+    #[test]
+    fn test_single_jar_only_installer() {
+        let err = single_jar(server_dir("only-installer")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(InvalidServerDirError::NoJar)
+        ));
+    }
+
+    #[test]
+    fn test_single_jar_multiple_servers() {
+        let err = single_jar(server_dir("multiple-servers")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(InvalidServerDirError::MultipleJars)
+        ));
+    }
 }
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{File, TryLockError},
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
+
+/// Held by the operation changing the server, so two mcerv processes don't change it at once.
+pub const LOCK_FILE: &str = ".mcerv.lock";
+
+/// Who holds the lock, written into the lock file for the error of whoever else wants it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub operation: String,
+    pub started: String,
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// The owner is `None` if the lock file couldn't be read.
+    Contended(Option<LockOwner>),
+    Io(io::Error),
+}
+
+impl Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Contended(Some(owner)) => write!(
+                f,
+                "Another mcerv operation is in progress ({} with pid {}, started {}). Pass --wait to wait for it",
+                owner.operation, owner.pid, owner.started
+            ),
+            LockError::Contended(None) => write!(
+                f,
+                "Another mcerv operation is in progress. Pass --wait to wait for it"
+            ),
+            LockError::Io(e) => write!(f, "Failed to lock the server directory: {e}"),
+        }
+    }
+}
+
+impl Error for LockError {}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> Self {
+        LockError::Io(e)
+    }
+}
+
+/// An advisory lock on the server directory, released when dropped, including when the
+/// operation panics. The operating system releases it as well if the process dies.
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+}
+
+impl InstanceLock {
+    /// Locks the server directory for `operation`. If another process holds the lock,
+    /// fails with [`LockError::Contended`], or with `wait`, blocks until it's released.
+    pub fn acquire(server_dir: &Path, operation: &str, wait: bool) -> Result<Self, LockError> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(server_dir.join(LOCK_FILE))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) if wait => {
+                if let Some(owner) = read_owner(&mut file) {
+                    println!(
+                        "Waiting for {} (pid {}) to finish...",
+                        owner.operation, owner.pid
+                    );
+                }
+                file.lock()?;
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(LockError::Contended(read_owner(&mut file)));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let owner = LockOwner {
+            pid: std::process::id(),
+            operation: operation.to_string(),
+            started: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(&serde_json::to_vec(&owner).map_err(io::Error::from)?)?;
+        file.flush()?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Kept in place, since removing it could race with the next process opening it
+        let _ = self.file.unlock();
+    }
+}
+
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn test_mutual_exclusion() {
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path().to_path_buf();
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        let holder = thread::spawn(move || {
+            let _lock = InstanceLock::acquire(&server_dir, "install", false).unwrap();
+            locked_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+        locked_rx.recv().unwrap();
+
+        let contender = {
+            let server_dir = dir.path().to_path_buf();
+            thread::spawn(move || InstanceLock::acquire(&server_dir, "ls-mods", false))
+        };
+        match contender.join().unwrap() {
+            Err(LockError::Contended(Some(owner))) => {
+                assert_eq!(owner.pid, std::process::id());
+                assert_eq!(owner.operation, "install");
+            }
+            other => panic!("Expected the lock to be contended, got {other:?}"),
+        }
+
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        assert!(InstanceLock::acquire(dir.path(), "ls-mods", false).is_ok());
+    }
+
+    #[test]
+    fn test_wait_blocks_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = InstanceLock::acquire(dir.path(), "install", false).unwrap();
+        let released = Arc::new(AtomicBool::new(false));
+
+        let waiter = {
+            let server_dir = dir.path().to_path_buf();
+            let released = released.clone();
+            thread::spawn(move || {
+                let _lock = InstanceLock::acquire(&server_dir, "ls-mods", true).unwrap();
+                released.load(Ordering::SeqCst)
+            })
+        };
+
+        thread::sleep(Duration::from_millis(100));
+        released.store(true, Ordering::SeqCst);
+        drop(lock);
+
+        assert!(
+            waiter.join().unwrap(),
+            "Acquired before the lock was released"
+        );
+    }
+
+    #[test]
+    fn test_released_on_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path().to_path_buf();
+
+        let result = thread::spawn(move || {
+            let _lock = InstanceLock::acquire(&server_dir, "update-server-jar", false).unwrap();
+            panic!("The operation failed");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert!(InstanceLock::acquire(dir.path(), "ls-mods", false).is_ok());
+    }
+}
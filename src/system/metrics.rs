@@ -0,0 +1,396 @@
+use crate::{
+    network::ping,
+    system::{process, properties::ServerProperties},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// How often the metrics are collected.
+pub const COLLECT_INTERVAL: Duration = Duration::from_secs(15);
+/// World directories can be large, so their sizes are sampled less often.
+pub const WORLD_SIZE_INTERVAL: Duration = Duration::from_secs(300);
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// The gauges of one managed server. Everything but `up` is `None` when it couldn't be
+/// collected, and is left out of the exposition then.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InstanceMetrics {
+    pub server: String,
+    pub up: bool,
+    pub memory: Option<u64>,
+    pub cpu: Option<f32>,
+    pub players_online: Option<u32>,
+    pub players_max: Option<u32>,
+    pub world_size: Option<u64>,
+}
+
+/// Collects the metrics of the servers, keeping what's needed between collections: the
+/// previous CPU times and the sampled world sizes.
+pub struct Collector {
+    system: System,
+    world_sizes: HashMap<String, (Instant, u64)>,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self {
+            system: System::new(),
+            world_sizes: HashMap::new(),
+        }
+    }
+}
+
+impl Collector {
+    /// Collects the metrics of every server, by name and directory. A failing server only
+    /// loses its own gauges.
+    pub async fn collect(&mut self, servers: &[(String, PathBuf)]) -> Vec<InstanceMetrics> {
+        let mut metrics = Vec::with_capacity(servers.len());
+        for (server, dir) in servers {
+            metrics.push(self.collect_instance(server, dir).await);
+        }
+        metrics
+    }
+
+    async fn collect_instance(&mut self, server: &str, dir: &Path) -> InstanceMetrics {
+        let mut metrics = InstanceMetrics {
+            server: server.to_string(),
+            world_size: self.world_size(server, dir).await,
+            ..Default::default()
+        };
+
+        let status = match process::running_process(dir) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("Failed to read the process of {server}: {e}");
+                None
+            }
+        };
+        let Some(status) = status else {
+            return metrics;
+        };
+        metrics.up = true;
+
+        // CPU usage is measured since the previous refresh, so the first one reads zero
+        let pid = Pid::from_u32(status.pid);
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            true,
+            ProcessRefreshKind::nothing().with_memory().with_cpu(),
+        );
+        match self.system.process(pid) {
+            Some(process) => {
+                metrics.memory = Some(process.memory());
+                metrics.cpu = Some(process.cpu_usage());
+            }
+            None => metrics.memory = Some(status.memory),
+        }
+
+        let port = process::configured_port(dir);
+        match ping::ping("localhost", port, PING_TIMEOUT).await {
+            Ok(status) => {
+                if let Some(players) = status.players {
+                    metrics.players_online = Some(players.online);
+                    metrics.players_max = Some(players.max);
+                }
+            }
+            // Still starting up, or not answering the ping
+            Err(e) => eprintln!("Failed to ping {server} on port {port}: {e}"),
+        }
+
+        metrics
+    }
+
+    async fn world_size(&mut self, server: &str, dir: &Path) -> Option<u64> {
+        if let Some((sampled_at, size)) = self.world_sizes.get(server)
+            && sampled_at.elapsed() < WORLD_SIZE_INTERVAL
+        {
+            return Some(*size);
+        }
+
+        let world_dir = world_dir(dir);
+        if !world_dir.is_dir() {
+            return None;
+        }
+        let size = tokio::task::spawn_blocking(move || dir_size(&world_dir))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|size| size);
+        match size {
+            Ok(size) => {
+                self.world_sizes
+                    .insert(server.to_string(), (Instant::now(), size));
+                Some(size)
+            }
+            Err(e) => {
+                eprintln!("Failed to measure the world of {server}: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// The world directory of the server, from `level-name` in server.properties.
+pub fn world_dir(server_dir: &Path) -> PathBuf {
+    let level_name = ServerProperties::load(server_dir)
+        .ok()
+        .and_then(|properties| properties.get("level-name"))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "world".to_string());
+    server_dir.join(level_name)
+}
+
+/// The total size of the files in the directory and its subdirectories. Symlinked
+/// directories, like a `mods` folder shared between servers, are followed, but every
+/// directory is counted once.
+pub fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut visited = HashSet::new();
+    visited.insert(fs::canonicalize(dir)?);
+    visited_dir_size(dir, &mut visited)
+}
+
+fn visited_dir_size(dir: &Path, visited: &mut HashSet<PathBuf>) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() || (file_type.is_symlink() && path.is_dir()) {
+            if visited.insert(fs::canonicalize(&path)?) {
+                size += visited_dir_size(&path, visited)?;
+            }
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Renders the metrics in the Prometheus text exposition format.
+pub fn render(metrics: &[InstanceMetrics]) -> String {
+    let mut output = String::new();
+    let mut gauge = |name: &str, help: &str, value: &dyn Fn(&InstanceMetrics) -> Option<f64>| {
+        let _ = writeln!(output, "# HELP {name} {help}");
+        let _ = writeln!(output, "# TYPE {name} gauge");
+        for instance in metrics {
+            if let Some(value) = value(instance) {
+                let _ = writeln!(
+                    output,
+                    "{name}{{server=\"{}\"}} {value}",
+                    escape_label(&instance.server)
+                );
+            }
+        }
+    };
+
+    gauge(
+        "mcerv_server_up",
+        "Whether the server process is running.",
+        &|m| Some(if m.up { 1.0 } else { 0.0 }),
+    );
+    gauge(
+        "mcerv_server_memory_bytes",
+        "Resident memory of the server process.",
+        &|m| m.memory.map(|memory| memory as f64),
+    );
+    gauge(
+        "mcerv_server_cpu_percent",
+        "CPU usage of the server process since the previous collection, 100 per core.",
+        &|m| m.cpu.map(f64::from),
+    );
+    gauge(
+        "mcerv_server_players_online",
+        "Players online, from the Server List Ping.",
+        &|m| m.players_online.map(f64::from),
+    );
+    gauge(
+        "mcerv_server_players_max",
+        "Maximum players, from the Server List Ping.",
+        &|m| m.players_max.map(f64::from),
+    );
+    gauge(
+        "mcerv_server_world_size_bytes",
+        "Size of the world directory, sampled periodically.",
+        &|m| m.world_size.map(|size| size as f64),
+    );
+
+    output
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Answers `GET /metrics` with the latest rendered metrics until the listener fails.
+pub async fn serve(listener: TcpListener, metrics: Arc<RwLock<String>>) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream, &metrics).await {
+                eprintln!("Failed to answer a metrics request: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_request(mut stream: TcpStream, metrics: &RwLock<String>) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() > MAX_REQUEST_SIZE {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.read().unwrap().clone()),
+        (Some("GET"), _) => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "Only GET is supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::process::PidFile;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_size_counts_symlinked_dirs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared-mods");
+        fs::create_dir(&shared).unwrap();
+        fs::write(shared.join("sodium.jar"), [0; 100]).unwrap();
+        for server in ["test", "prod"] {
+            fs::create_dir(dir.path().join(server)).unwrap();
+            fs::write(dir.path().join(server).join("server.jar"), [0; 10]).unwrap();
+            std::os::unix::fs::symlink(&shared, dir.path().join(server).join("mods")).unwrap();
+        }
+
+        assert_eq!(dir_size(&dir.path().join("test")).unwrap(), 110);
+        assert_eq!(dir_size(dir.path()).unwrap(), 120);
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = [InstanceMetrics {
+            server: "quote\"d".to_string(),
+            up: true,
+            memory: Some(1024),
+            ..Default::default()
+        }];
+        let output = render(&metrics);
+        assert!(output.contains("mcerv_server_up{server=\"quote\\\"d\"} 1\n"));
+        assert!(output.contains("mcerv_server_memory_bytes{server=\"quote\\\"d\"} 1024\n"));
+        assert!(!output.contains("mcerv_server_cpu_percent{"));
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A running server, which is this test, not answering pings on its port
+        let running = dir.path().join("survival");
+        fs::create_dir_all(running.join("lobby").join("region")).unwrap();
+        fs::write(running.join("lobby").join("level.dat"), [0; 100]).unwrap();
+        fs::write(
+            running.join("lobby").join("region").join("r.0.0.mca"),
+            [0; 400],
+        )
+        .unwrap();
+        let unused_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        fs::write(
+            running.join("server.properties"),
+            format!("level-name=lobby\nserver-port={unused_port}\n"),
+        )
+        .unwrap();
+        PidFile::for_process(std::process::id())
+            .unwrap()
+            .save(&running)
+            .unwrap();
+
+        // A stopped server with a broken pid file
+        let stopped = dir.path().join("creative");
+        fs::create_dir_all(&stopped).unwrap();
+        fs::write(PidFile::path(&stopped), "not json").unwrap();
+
+        let servers = [
+            ("survival".to_string(), running),
+            ("creative".to_string(), stopped),
+        ];
+        let mut collector = Collector::default();
+        let metrics = Arc::new(RwLock::new(render(&collector.collect(&servers).await)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/metrics", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, metrics));
+
+        let response = reqwest::get(&url).await.unwrap();
+        assert!(response.status().is_success());
+        let body = response.text().await.unwrap();
+
+        let mut samples = HashMap::new();
+        for line in body.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                let mut words = comment.split(' ');
+                assert!(matches!(words.next(), Some("HELP" | "TYPE")), "{line}");
+                assert!(words.next().unwrap().starts_with("mcerv_"), "{line}");
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let (name, labels) = series.split_once('{').unwrap();
+            let server = labels
+                .strip_prefix("server=\"")
+                .and_then(|labels| labels.strip_suffix("\"}"))
+                .unwrap();
+            samples.insert((name, server), value.parse::<f64>().unwrap());
+        }
+
+        assert_eq!(samples[&("mcerv_server_up", "survival")], 1.0);
+        assert_eq!(samples[&("mcerv_server_up", "creative")], 0.0);
+        assert!(samples[&("mcerv_server_memory_bytes", "survival")] > 0.0);
+        assert!(samples.contains_key(&("mcerv_server_cpu_percent", "survival")));
+        assert_eq!(
+            samples[&("mcerv_server_world_size_bytes", "survival")],
+            500.0
+        );
+        assert!(!samples.contains_key(&("mcerv_server_players_online", "survival")));
+        assert!(!samples.contains_key(&("mcerv_server_memory_bytes", "creative")));
+    }
+}
@@ -1,5 +1,48 @@
+pub(crate) mod bootstrap;
+pub(crate) mod bundle;
+pub(crate) mod cache;
+pub mod cancel;
+pub(crate) mod clean;
 pub mod cli;
 pub mod config;
+pub(crate) mod console;
+pub(crate) mod crash_report;
+pub(crate) mod docker;
+pub(crate) mod doctor;
+pub(crate) mod eula;
+pub(crate) mod filename;
 pub mod forks;
-pub mod jar_parser;
-pub mod server_info;
+pub(crate) mod format;
+pub(crate) mod global_config;
+pub(crate) mod group;
+pub(crate) mod history;
+pub(crate) mod install_manifest;
+pub(crate) mod installer;
+pub(crate) mod jar_parser;
+pub(crate) mod lock;
+pub(crate) mod metrics;
+pub(crate) mod mod_lock;
+pub(crate) mod modgraph;
+pub(crate) mod mods;
+pub(crate) mod nbt;
+pub(crate) mod notify;
+pub(crate) mod pins;
+pub(crate) mod process;
+pub(crate) mod progress;
+pub(crate) mod prompt;
+pub(crate) mod properties;
+pub(crate) mod registry;
+pub(crate) mod rollback;
+pub(crate) mod schedule;
+pub(crate) mod server_info;
+pub(crate) mod server_name;
+pub(crate) mod start_template;
+pub(crate) mod supervisor;
+pub(crate) mod template;
+pub(crate) mod trash;
+pub(crate) mod update_plan;
+pub(crate) mod verify;
+pub(crate) mod version;
+pub(crate) mod watch;
+pub(crate) mod wizard;
+pub(crate) mod world;
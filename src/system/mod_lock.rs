@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+/// In the server directory. Written by mcerv whenever it installs or replaces a mod.
+pub const MOD_LOCK_FILE: &str = "mcerv-mods.lock";
+
+/// The mods mcerv installed in a server by Modrinth project ID, like:
+///
+/// ```toml
+/// [P7dR8mSH]
+/// version_id = "6VvyY7Sa"
+/// installed_at = "2025-08-14T18:03:00+02:00"
+/// updated_at = "2025-09-02T09:41:12+02:00"
+///
+/// [P7dR8mSH.files]
+/// "fabric-api-0.129.0+1.21.8.jar" = "<sha512>"
+/// ```
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct ModLock {
+    pub mods: BTreeMap<String, LockedMod>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockedMod {
+    pub version_id: String,
+    /// When mcerv first installed the project, in RFC 3339.
+    pub installed_at: String,
+    /// When mcerv last replaced it with another version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// The SHA512 of each file the version installed in the mods directory, by file name.
+    /// Empty for mods installed before they were recorded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+impl LockedMod {
+    /// How long the version has been installed, or `None` if the timestamp is invalid.
+    pub fn age(&self, now: chrono::DateTime<chrono::Local>) -> Option<Duration> {
+        let since = self.updated_at.as_deref().unwrap_or(&self.installed_at);
+        let since = chrono::DateTime::parse_from_rfc3339(since).ok()?;
+        // A clock that went back counts as just installed
+        Some((now.fixed_offset() - since).to_std().unwrap_or_default())
+    }
+}
+
+impl ModLock {
+    /// Loads the lockfile of the server, or an empty one if there is none.
+    pub fn load(server_dir: &Path) -> anyhow::Result<Self> {
+        let path = server_dir.join(MOD_LOCK_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, server_dir: &Path) -> anyhow::Result<()> {
+        fs::write(server_dir.join(MOD_LOCK_FILE), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Records that the version of the project was installed at `now` with the files, replacing
+    /// whichever version was before.
+    pub fn record(
+        &mut self,
+        project_id: &str,
+        version_id: &str,
+        files: BTreeMap<String, String>,
+        now: &str,
+    ) {
+        match self.mods.get_mut(project_id) {
+            Some(locked) if locked.version_id == version_id => locked.files = files,
+            Some(locked) => {
+                locked.version_id = version_id.to_string();
+                locked.updated_at = Some(now.to_string());
+                locked.files = files;
+            }
+            None => {
+                self.mods.insert(
+                    project_id.to_string(),
+                    LockedMod {
+                        version_id: version_id.to_string(),
+                        installed_at: now.to_string(),
+                        updated_at: None,
+                        files,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Removes the entry whose version installed the file, after mcerv removed it.
+    pub fn forget_file(&mut self, file_name: &str) {
+        self.mods
+            .retain(|_, locked| !locked.files.contains_key(file_name));
+    }
+
+    /// The entry of the project, if it's for the installed version. A jar replaced without
+    /// mcerv doesn't get the age of the one before.
+    pub fn installed(&self, project_id: &str, version_id: &str) -> Option<&LockedMod> {
+        self.mods
+            .get(project_id)
+            .filter(|locked| locked.version_id == version_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut lock = ModLock::load(dir.path()).unwrap();
+        lock.record(
+            "P7dR8mSH",
+            "old",
+            BTreeMap::new(),
+            "2025-08-14T18:03:00+02:00",
+        );
+        // Installing the same version again changes nothing
+        lock.record(
+            "P7dR8mSH",
+            "old",
+            BTreeMap::new(),
+            "2025-08-20T10:00:00+02:00",
+        );
+        assert_eq!(lock.mods["P7dR8mSH"].updated_at, None);
+
+        let files = BTreeMap::from([("fabric-api.jar".to_string(), "abc".to_string())]);
+        lock.record(
+            "P7dR8mSH",
+            "new",
+            files.clone(),
+            "2025-09-02T09:41:12+02:00",
+        );
+        lock.save(dir.path()).unwrap();
+
+        let lock = ModLock::load(dir.path()).unwrap();
+        let locked = lock.installed("P7dR8mSH", "new").unwrap();
+        assert_eq!(locked.installed_at, "2025-08-14T18:03:00+02:00");
+        assert_eq!(
+            locked.updated_at.as_deref(),
+            Some("2025-09-02T09:41:12+02:00")
+        );
+        assert_eq!(locked.files, files);
+        assert!(lock.installed("P7dR8mSH", "old").is_none());
+
+        let now = chrono::DateTime::parse_from_rfc3339("2025-09-12T09:41:12+02:00")
+            .unwrap()
+            .with_timezone(&chrono::Local);
+        assert_eq!(
+            locked.age(now),
+            Some(Duration::from_secs(10 * 24 * 60 * 60))
+        );
+
+        let mut lock = lock;
+        lock.forget_file("fabric-api.jar");
+        assert!(lock.mods.is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+use std::{collections::HashSet, fmt::Write as _, path::PathBuf};
+
+/// Dependencies on these are provided by the loader, so they're never installed as mods.
+const PROVIDED: [&str; 5] = ["minecraft", "java", "fabricloader", "forge", "neoforge"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    /// The Modrinth project ID, or a mod ID from the metadata of the jar.
+    pub target: String,
+    pub optional: bool,
+}
+
+/// An installed mod.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModNode {
+    /// The Modrinth project ID, or the mod ID or file name of jars Modrinth doesn't know.
+    pub key: String,
+    pub name: String,
+    /// The mod ID of its `fabric.mod.json` or `mods.toml`, which other jars depend on it by.
+    pub mod_id: Option<String>,
+    pub path: PathBuf,
+    /// Installed for other mods to use, like Fabric API.
+    pub library: bool,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Where a dependency points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target<'a> {
+    Installed(usize),
+    Missing(&'a str),
+}
+
+/// The installed mods and what they depend on. Cycles are allowed.
+#[derive(Debug)]
+pub struct ModGraph {
+    nodes: Vec<ModNode>,
+}
+
+impl ModGraph {
+    pub fn new(mut nodes: Vec<ModNode>) -> Self {
+        nodes.sort_by_key(|node| node.name.to_lowercase());
+        Self { nodes }
+    }
+
+    pub fn nodes(&self) -> &[ModNode] {
+        &self.nodes
+    }
+
+    fn resolve(&self, target: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.key == target)
+            .or_else(|| {
+                self.nodes
+                    .iter()
+                    .position(|node| node.mod_id.as_deref() == Some(target))
+            })
+    }
+
+    /// The dependencies of the node, leaving out the ones the loader provides.
+    /// A dependency listed both by Modrinth and the jar is one edge, required if either says so.
+    fn edges(&self, i: usize) -> Vec<(Target<'_>, bool)> {
+        let mut edges: Vec<(Target<'_>, bool)> = Vec::new();
+        for dependency in &self.nodes[i].dependencies {
+            let target = match self.resolve(&dependency.target) {
+                // A jar depending on itself, like by its own mod ID
+                Some(j) if j == i => continue,
+                Some(j) => Target::Installed(j),
+                None if PROVIDED.contains(&dependency.target.as_str()) => continue,
+                None => Target::Missing(&dependency.target),
+            };
+            match edges.iter_mut().find(|(edge, _)| *edge == target) {
+                Some((_, optional)) => *optional &= dependency.optional,
+                None => edges.push((target, dependency.optional)),
+            }
+        }
+        edges
+    }
+
+    /// The installed mods depending on the node.
+    pub fn dependents(&self, key: &str) -> Vec<&ModNode> {
+        let Some(target) = self.resolve(key) else {
+            return Vec::new();
+        };
+        (0..self.nodes.len())
+            .filter(|&i| {
+                self.edges(i)
+                    .iter()
+                    .any(|(edge, _)| *edge == Target::Installed(target))
+            })
+            .map(|i| &self.nodes[i])
+            .collect()
+    }
+
+    /// The libraries no mod that isn't a library needs, directly or through other libraries.
+    /// Optional dependencies count as needed.
+    pub fn orphans(&self) -> Vec<&ModNode> {
+        let mut needed: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| !self.nodes[i].library)
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !needed.insert(i) {
+                continue;
+            }
+            for (edge, _) in self.edges(i) {
+                if let Target::Installed(j) = edge {
+                    stack.push(j);
+                }
+            }
+        }
+
+        (0..self.nodes.len())
+            .filter(|i| !needed.contains(i))
+            .map(|i| &self.nodes[i])
+            .collect()
+    }
+
+    /// The mods nothing depends on, each with its dependencies indented below it. A mod is
+    /// only expanded the first time it's shown.
+    pub fn tree(&self) -> String {
+        let mut dependent = vec![false; self.nodes.len()];
+        for i in 0..self.nodes.len() {
+            for (edge, _) in self.edges(i) {
+                if let Target::Installed(j) = edge {
+                    dependent[j] = true;
+                }
+            }
+        }
+
+        let mut output = String::new();
+        let mut shown = vec![false; self.nodes.len()];
+        let roots = (0..self.nodes.len()).filter(|&i| !dependent[i]);
+        for i in roots {
+            self.write_subtree(&mut output, i, false, &mut Vec::new(), &mut shown);
+        }
+        // Mods only depending on each other in a cycle have no root
+        for i in 0..self.nodes.len() {
+            if !shown[i] {
+                self.write_subtree(&mut output, i, false, &mut Vec::new(), &mut shown);
+            }
+        }
+        output
+    }
+
+    fn write_subtree(
+        &self,
+        output: &mut String,
+        i: usize,
+        optional: bool,
+        path: &mut Vec<usize>,
+        shown: &mut [bool],
+    ) {
+        let indent = "  ".repeat(path.len());
+        let optional = if optional { " (optional)" } else { "" };
+        let name = &self.nodes[i].name;
+        if path.contains(&i) {
+            let _ = writeln!(output, "{indent}{name}{optional} (cycle)");
+            return;
+        }
+        if shown[i] {
+            let _ = writeln!(output, "{indent}{name}{optional} (shown above)");
+            return;
+        }
+        let _ = writeln!(output, "{indent}{name}{optional}");
+        shown[i] = true;
+
+        path.push(i);
+        for (edge, optional) in self.edges(i) {
+            match edge {
+                Target::Installed(j) => self.write_subtree(output, j, optional, path, shown),
+                Target::Missing(target) => {
+                    let kind = if optional { "optional, " } else { "" };
+                    let _ = writeln!(output, "{indent}  {target} ({kind}not installed)");
+                }
+            }
+        }
+        path.pop();
+    }
+
+    /// The graph in Graphviz's DOT language, with optional dependencies dashed.
+    pub fn dot(&self) -> String {
+        let mut output = String::from("digraph mods {\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                output,
+                "  {} [label={}];",
+                dot_id(&node.key),
+                dot_id(&node.name)
+            );
+        }
+
+        let mut missing = Vec::new();
+        for i in 0..self.nodes.len() {
+            for (edge, optional) in self.edges(i) {
+                let target = match edge {
+                    Target::Installed(j) => &self.nodes[j].key,
+                    Target::Missing(target) => {
+                        if !missing.contains(&target) {
+                            missing.push(target);
+                        }
+                        target
+                    }
+                };
+                let style = if optional { " [style=dashed]" } else { "" };
+                let _ = writeln!(
+                    output,
+                    "  {} -> {}{style};",
+                    dot_id(&self.nodes[i].key),
+                    dot_id(target)
+                );
+            }
+        }
+        for target in missing {
+            let _ = writeln!(
+                output,
+                "  {} [label={}, style=dotted];",
+                dot_id(target),
+                dot_id(&format!("{target} (not installed)"))
+            );
+        }
+
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// Quoted, so any ID or name works.
+fn dot_id(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(key: &str, library: bool, dependencies: &[(&str, bool)]) -> ModNode {
+        ModNode {
+            key: key.to_string(),
+            name: key.to_string(),
+            mod_id: None,
+            path: PathBuf::from(format!("{key}.jar")),
+            library,
+            dependencies: dependencies
+                .iter()
+                .map(|(target, optional)| Dependency {
+                    target: target.to_string(),
+                    optional: *optional,
+                })
+                .collect(),
+        }
+    }
+
+    fn keys(nodes: Vec<&ModNode>) -> Vec<&str> {
+        nodes.iter().map(|node| node.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tree() {
+        let graph = ModGraph::new(vec![
+            node(
+                "sodium-extra",
+                false,
+                &[("sodium", false), ("reeses", true)],
+            ),
+            node(
+                "sodium",
+                false,
+                &[("fabric-api", false), ("minecraft", false)],
+            ),
+            node("lithium", false, &[("fabric-api", false), ("cloth", false)]),
+            node("fabric-api", true, &[]),
+        ]);
+        assert_eq!(
+            graph.tree(),
+            "\
+lithium
+  fabric-api
+  cloth (not installed)
+sodium-extra
+  sodium
+    fabric-api (shown above)
+  reeses (optional, not installed)
+"
+        );
+        assert_eq!(keys(graph.dependents("fabric-api")), ["lithium", "sodium"]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let graph = ModGraph::new(vec![
+            node("a", true, &[("b", false)]),
+            node("b", true, &[("c", true)]),
+            node("c", true, &[("a", false)]),
+        ]);
+        assert_eq!(graph.tree(), "a\n  b\n    c (optional)\n      a (cycle)\n");
+        // Nothing outside the cycle needs them
+        assert_eq!(keys(graph.orphans()), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_orphans() {
+        let mut kotlin = node("fabric-language-kotlin", true, &[("fabric-api", false)]);
+        kotlin.mod_id = Some("fabric_language_kotlin".to_string());
+        let graph = ModGraph::new(vec![
+            node("fabric-api", true, &[]),
+            kotlin,
+            node("cloth-config", true, &[("fabric-api", false)]),
+            // Known by its mod ID, from a fabric.mod.json
+            node("my-mod.jar", false, &[("fabric_language_kotlin", false)]),
+            node("lithium", false, &[("owo-lib", true)]),
+            node(
+                "owo-lib",
+                true,
+                &[("cloth-config", false), ("owo-lib", false)],
+            ),
+            node("architectury", true, &[("unused", false)]),
+            node("unused", true, &[("architectury", false)]),
+        ]);
+        assert_eq!(keys(graph.orphans()), ["architectury", "unused"]);
+    }
+
+    #[test]
+    fn test_edges_from_modrinth_and_jar() {
+        let mut api = node("P7dR8mSH", true, &[]);
+        api.mod_id = Some("fabric-api".to_string());
+        // Optional on Modrinth, but required by the fabric.mod.json
+        let graph = ModGraph::new(vec![
+            api,
+            node(
+                "sodium",
+                false,
+                &[("P7dR8mSH", true), ("fabric-api", false)],
+            ),
+        ]);
+        assert_eq!(graph.edges(1), [(Target::Installed(0), false)]);
+        assert!(graph.orphans().is_empty());
+    }
+
+    #[test]
+    fn test_dot() {
+        let graph = ModGraph::new(vec![
+            node(
+                "my \"mod\"",
+                false,
+                &[("fabric-api", true), ("cloth", false)],
+            ),
+            node("fabric-api", true, &[]),
+        ]);
+        assert_eq!(
+            graph.dot(),
+            "\
+digraph mods {
+  \"fabric-api\" [label=\"fabric-api\"];
+  \"my \\\"mod\\\"\" [label=\"my \\\"mod\\\"\"];
+  \"my \\\"mod\\\"\" -> \"fabric-api\" [style=dashed];
+  \"my \\\"mod\\\"\" -> \"cloth\";
+  \"cloth\" [label=\"cloth (not installed)\", style=dotted];
+}
+"
+        );
+    }
+}
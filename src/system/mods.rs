@@ -0,0 +1,972 @@
+use crate::{
+    network::{
+        forge_meta::compare_versions,
+        modrinth::{Project, SideSupport},
+    },
+    system::{
+        format,
+        jar_parser::{self, FileHash, ModEnvironment, ModMetadata},
+    },
+};
+use clap::ValueEnum;
+use prettytable::{Table, row};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Loaders skip jars ending in this.
+pub const DISABLED_EXTENSION: &str = "disabled";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A jar in the mods directory and the Modrinth project its hash resolved to.
+pub struct InstalledJar {
+    pub path: PathBuf,
+    pub project_id: String,
+    pub up_to_date: bool,
+}
+
+/// How an installed mod compares to its versions on Modrinth.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModStatus {
+    /// With the name of the newest version.
+    UpdateAvailable(String),
+    UpToDate,
+    /// The project won't get updates, with why, like `archived`.
+    NotUpdatable(String),
+    /// The jar's hash isn't on Modrinth, like for mods from elsewhere.
+    Unknown,
+}
+
+/// The headings of the listing's sections, in the order of [`ModStatus::section`].
+const SECTIONS: [&str; 4] = [
+    "Updates available",
+    "Up to date",
+    "No longer updatable",
+    "Unknown, not on Modrinth",
+];
+
+impl ModStatus {
+    fn section(&self) -> usize {
+        match self {
+            ModStatus::UpdateAvailable(_) => 0,
+            ModStatus::UpToDate => 1,
+            ModStatus::NotUpdatable(_) => 2,
+            ModStatus::Unknown => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ModSort {
+    /// By project slug
+    #[default]
+    Name,
+    /// By section, then by project slug
+    Status,
+    /// Biggest first
+    Size,
+}
+
+/// A mod of the `ls-mods` listing.
+pub struct ModRow {
+    /// The project slug, or the file name for jars Modrinth doesn't know.
+    pub slug: String,
+    pub version_name: Option<String>,
+    pub version_id: Option<String>,
+    pub status: ModStatus,
+    /// The version constraint the mod is pinned to, like `~0.100`.
+    pub constraint: Option<String>,
+    /// How long mcerv has had the installed version installed, `None` if it didn't install it.
+    pub age: Option<Duration>,
+    pub file_name: String,
+    pub size: u64,
+    pub sha1: String,
+}
+
+impl ModRow {
+    /// The line of the short listing, like ``lithium: `mc1.21.8-0.18.0` [OK] up-to-date``.
+    pub fn line(&self) -> String {
+        let version = self.version_name.as_deref().unwrap_or("-");
+        let within = self
+            .constraint
+            .as_ref()
+            .map_or(String::new(), |constraint| format!(" within {constraint}"));
+        match &self.status {
+            ModStatus::UpdateAvailable(update) => {
+                format!("{}: `{version}` -> `{update}`{within}", self.slug)
+            }
+            ModStatus::UpToDate => {
+                format!("{}: `{version}` [OK] up-to-date{within}", self.slug)
+            }
+            ModStatus::NotUpdatable(reason) => {
+                format!("{}: `{version}` no longer updatable ({reason})", self.slug)
+            }
+            ModStatus::Unknown => self.slug.clone(),
+        }
+    }
+}
+
+pub fn sort_rows(rows: &mut [ModRow], sort: ModSort) {
+    match sort {
+        ModSort::Name => rows.sort_by(|a, b| a.slug.cmp(&b.slug)),
+        ModSort::Status => {
+            rows.sort_by(|a, b| (a.status.section(), &a.slug).cmp(&(b.status.section(), &b.slug)))
+        }
+        ModSort::Size => rows.sort_by(|a, b| b.size.cmp(&a.size).then(a.slug.cmp(&b.slug))),
+    }
+}
+
+/// The non-empty sections of the listing with their headings, like `Up to date (12)`.
+/// The rows keep their order within a section.
+pub fn sections(rows: &[ModRow]) -> Vec<(String, Vec<&ModRow>)> {
+    SECTIONS
+        .iter()
+        .enumerate()
+        .map(|(section, heading)| {
+            let rows = rows
+                .iter()
+                .filter(|row| row.status.section() == section)
+                .collect::<Vec<_>>();
+            (format!("{heading} ({})", rows.len()), rows)
+        })
+        .filter(|(_, rows)| !rows.is_empty())
+        .collect()
+}
+
+/// The table of `ls-mods --detailed`, telling apart builds that share a version name.
+pub fn detailed_table(rows: &[&ModRow]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Mod",
+        "Version",
+        "Installed",
+        "Update",
+        "File",
+        "Size",
+        "SHA1",
+        "Version ID"
+    ]);
+
+    for mod_row in rows {
+        let update = match &mod_row.status {
+            ModStatus::UpdateAvailable(update) => update,
+            _ => "-",
+        };
+        table.add_row(row![
+            mod_row.slug,
+            mod_row.version_name.as_deref().unwrap_or("-"),
+            mod_row.age.map_or("-".to_string(), format::format_ago),
+            update,
+            mod_row.file_name,
+            format::format_bytes(mod_row.size),
+            mod_row.sha1.get(..10).unwrap_or(&mod_row.sha1),
+            mod_row.version_id.as_deref().unwrap_or("-")
+        ]);
+    }
+
+    table
+}
+
+/// The rows of `ls-mods --stale`: the mods with an update whose installed version is at
+/// least `days` old. Mods mcerv didn't install have no age, so they're left out.
+pub fn stale_rows(rows: Vec<ModRow>, days: u64) -> Vec<ModRow> {
+    let min_age = Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY));
+    rows.into_iter()
+        .filter(|row| matches!(row.status, ModStatus::UpdateAvailable(_)))
+        .filter(|row| row.age.is_some_and(|age| age >= min_age))
+        .collect()
+}
+
+/// Jars of the same project installed more than once.
+#[derive(Debug, PartialEq)]
+pub struct DuplicateInstall {
+    pub project_id: String,
+    pub keep: PathBuf,
+    pub older: Vec<PathBuf>,
+}
+
+/// What tells that a mod only runs on the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientOnlySource {
+    /// The project's `server_side` is `unsupported`.
+    Modrinth,
+    /// For jars Modrinth doesn't know, the `environment` of their `fabric.mod.json`.
+    FabricModJson,
+}
+
+impl Display for ClientOnlySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientOnlySource::Modrinth => write!(f, "unsupported on servers according to Modrinth"),
+            ClientOnlySource::FabricModJson => write!(f, "its fabric.mod.json says client only"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ClientOnlyMod {
+    pub path: PathBuf,
+    /// The project's title, or the file name for jars Modrinth doesn't know.
+    pub name: String,
+    pub source: ClientOnlySource,
+}
+
+/// The jars that don't run on a server, with the Modrinth project each resolved to, if any.
+pub fn client_only_mods(jars: &[PathBuf], projects: &[Option<Project>]) -> Vec<ClientOnlyMod> {
+    jars.iter()
+        .zip(projects)
+        .filter_map(|(path, project)| {
+            let (name, source) = match project {
+                Some(project) if project.server_side == SideSupport::Unsupported => {
+                    (project.title.clone(), ClientOnlySource::Modrinth)
+                }
+                Some(_) => return None,
+                None => match jar_parser::read_fabric_environment(path) {
+                    Ok(Some(ModEnvironment::Client)) => (
+                        path.file_name().unwrap().to_string_lossy().to_string(),
+                        ClientOnlySource::FabricModJson,
+                    ),
+                    _ => return None,
+                },
+            };
+            Some(ClientOnlyMod {
+                path: path.clone(),
+                name,
+                source,
+            })
+        })
+        .collect()
+}
+
+/// The Modrinth project of the installed mod named `query`, by its slug, title or mod ID, or
+/// else the start of its file name. Jars Modrinth doesn't know are skipped. `mod_ids` and
+/// `projects` go with the jars in order.
+pub fn find_installed_project<'a>(
+    query: &str,
+    jars: &[PathBuf],
+    mod_ids: &[Option<String>],
+    projects: &'a [Option<Project>],
+) -> Option<&'a Project> {
+    let query = query.to_lowercase();
+    let known = || {
+        jars.iter()
+            .zip(mod_ids)
+            .zip(projects)
+            .filter_map(|((path, mod_id), project)| Some((path, mod_id, project.as_ref()?)))
+    };
+    known()
+        .find(|(_, mod_id, project)| {
+            project.slug.to_lowercase() == query
+                || project.title.to_lowercase() == query
+                || mod_id.as_deref() == Some(query.as_str())
+        })
+        .or_else(|| known().find(|(path, ..)| file_name(path).to_lowercase().starts_with(&query)))
+        .map(|(_, _, project)| project)
+}
+
+/// Renames the jar so the loader skips it, returning the new path.
+pub fn disable(jar: &Path) -> io::Result<PathBuf> {
+    let mut file_name = jar.file_name().unwrap().to_os_string();
+    file_name.push(format!(".{DISABLED_EXTENSION}"));
+    let disabled = jar.with_file_name(file_name);
+    if disabled.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", disabled.display()),
+        ));
+    }
+
+    fs::rename(jar, &disabled)?;
+    Ok(disabled)
+}
+
+/// Mods the loader can't start with, or warns about, found from the jars alone.
+#[derive(Debug, PartialEq)]
+pub enum ModConflict {
+    /// The same mod ID in more than one jar, oldest first by version and then modification
+    /// time.
+    Duplicate { mod_id: String, jars: Vec<PathBuf> },
+    /// The `fabric.mod.json` of `jar` lists the mod of `other` in `breaks`, which stops the
+    /// loader, or in `conflicts`, which it only warns about.
+    Declared {
+        jar: PathBuf,
+        other: PathBuf,
+        breaks: bool,
+    },
+}
+
+impl ModConflict {
+    /// Whether the loader refuses to start with it.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            ModConflict::Duplicate { .. } => true,
+            ModConflict::Declared { breaks, .. } => *breaks,
+        }
+    }
+}
+
+impl Display for ModConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModConflict::Duplicate { mod_id, jars } => {
+                let jars = jars.iter().map(|jar| file_name(jar)).collect::<Vec<_>>();
+                write!(
+                    f,
+                    "{mod_id} is installed more than once: {}",
+                    jars.join(", ")
+                )
+            }
+            ModConflict::Declared { jar, other, breaks } => {
+                let relation = if *breaks { "breaks" } else { "conflicts with" };
+                write!(f, "{} {relation} {}", file_name(jar), file_name(other))
+            }
+        }
+    }
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().to_string()
+}
+
+/// Finds the duplicate mod IDs and the incompatibilities the jars declare among each other,
+/// without the network. Jars without readable metadata are skipped.
+pub fn find_conflicts(jars: &[PathBuf]) -> io::Result<Vec<ModConflict>> {
+    let installed: Vec<(&PathBuf, ModMetadata)> = jars
+        .iter()
+        .filter_map(|jar| Some((jar, jar_parser::read_mod_metadata(jar).ok()??)))
+        .collect();
+
+    let mut by_id: BTreeMap<&str, Vec<(&PathBuf, &str, SystemTime)>> = BTreeMap::new();
+    for (jar, metadata) in &installed {
+        by_id
+            .entry(&metadata.id)
+            .or_default()
+            .push((jar, &metadata.version, modified(jar)?));
+    }
+
+    let mut conflicts = Vec::new();
+    for (mod_id, mut group) in by_id {
+        if group.len() < 2 {
+            continue;
+        }
+        group.sort_by(|(_, a_version, a_modified), (_, b_version, b_modified)| {
+            compare_versions(a_version, b_version).then(a_modified.cmp(b_modified))
+        });
+        conflicts.push(ModConflict::Duplicate {
+            mod_id: mod_id.to_string(),
+            jars: group.into_iter().map(|(jar, _, _)| jar.clone()).collect(),
+        });
+    }
+
+    for (jar, _) in &installed {
+        let Ok(Some(incompatibilities)) = jar_parser::read_fabric_incompatibilities(jar) else {
+            continue;
+        };
+        let relations = [
+            (incompatibilities.breaks, true),
+            (incompatibilities.conflicts, false),
+        ];
+        for (relation, breaks) in relations {
+            for (other_id, predicates) in relation {
+                for (other, metadata) in &installed {
+                    if metadata.id == other_id
+                        && other != jar
+                        && predicates
+                            .iter()
+                            .any(|predicate| version_matches(&metadata.version, predicate))
+                    {
+                        conflicts.push(ModConflict::Declared {
+                            jar: (*jar).clone(),
+                            other: (*other).clone(),
+                            breaks,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+/// Whether the version matches a Fabric version predicate, like `>=0.5 <0.6`, `1.2.x` or
+/// `^1.2.0`. Anything that can't be told matches, so a declared incompatibility is never
+/// missed.
+fn version_matches(version: &str, predicate: &str) -> bool {
+    predicate.split_whitespace().all(|term| {
+        let (operator, bound) = match term.find(|c: char| c.is_ascii_digit()) {
+            Some(i) => term.split_at(i),
+            None => return true,
+        };
+        let ordering = compare_versions(version, bound);
+        let prefix = |len: usize| {
+            let parts = |s: &str| {
+                s.split(|c: char| !c.is_ascii_digit())
+                    .filter(|p| !p.is_empty())
+                    .take(len)
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            };
+            parts(version) == parts(bound)
+        };
+        match operator {
+            ">=" => ordering != Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            "<" => ordering == Ordering::Less,
+            "~" => ordering != Ordering::Less && prefix(2),
+            "^" => ordering != Ordering::Less && prefix(1),
+            "" | "=" if bound.contains(['x', 'X', '*']) => prefix(
+                bound
+                    .split('.')
+                    .take_while(|p| p.parse::<u64>().is_ok())
+                    .count(),
+            ),
+            "" | "=" => ordering == Ordering::Equal,
+            _ => true,
+        }
+    })
+}
+
+/// Where the mods directory links to if it's a symlink, or a junction on Windows, like a
+/// `mods` folder shared between servers.
+pub fn symlink_target(mods_dir: &Path) -> Option<PathBuf> {
+    let metadata = fs::symlink_metadata(mods_dir).ok()?;
+    if !metadata.file_type().is_symlink() {
+        return None;
+    }
+    Some(fs::read_link(mods_dir).unwrap_or_else(|_| mods_dir.to_path_buf()))
+}
+
+/// The jars in the mods directory, and in its subdirectory named after the game version,
+/// like `mods/1.21.8/`, which some launchers use.
+pub fn mod_jars(mods_dir: &Path, game_version: &str) -> io::Result<Vec<PathBuf>> {
+    let mut jars = jar_parser::jar_files(mods_dir)?;
+    let version_dir = mods_dir.join(game_version);
+    if version_dir.is_dir() {
+        jars.extend(jar_parser::jar_files(version_dir)?);
+    }
+    jars.sort();
+
+    Ok(jars)
+}
+
+/// Whether `file_name` in the mods directory already has the expected hash, like after an
+/// update that was interrupted before the old jar was deleted.
+pub fn is_already_downloaded(
+    mods_dir: &Path,
+    file_name: &str,
+    expected: &FileHash,
+) -> io::Result<bool> {
+    let path = mods_dir.join(file_name);
+    if !path.is_file() {
+        return Ok(false);
+    }
+
+    let actual = jar_parser::calculate_hash_with(&mut File::open(path)?, expected.algorithm)?;
+    Ok(actual == expected.value)
+}
+
+/// Groups the jars resolving to the same project. The up-to-date jar is kept,
+/// or the most recently modified one if none is.
+pub fn duplicate_installs(jars: &[InstalledJar]) -> io::Result<Vec<DuplicateInstall>> {
+    let mut project_ids = Vec::new();
+    for jar in jars {
+        if !project_ids.contains(&jar.project_id) {
+            project_ids.push(jar.project_id.clone());
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for project_id in project_ids {
+        let mut group = jars
+            .iter()
+            .filter(|jar| jar.project_id == project_id)
+            .map(|jar| Ok((jar.up_to_date, modified(&jar.path)?, &jar.path)))
+            .collect::<io::Result<Vec<_>>>()?;
+        if group.len() < 2 {
+            continue;
+        }
+
+        group.sort_by_key(|(up_to_date, modified, _)| (*up_to_date, *modified));
+        let (_, _, keep) = group.pop().unwrap();
+        duplicates.push(DuplicateInstall {
+            project_id,
+            keep: keep.clone(),
+            older: group.into_iter().map(|(_, _, path)| path.clone()).collect(),
+        });
+    }
+
+    Ok(duplicates)
+}
+
+fn modified(path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::jar_parser::HashAlgorithm;
+    use std::{io::Write, time::Duration};
+
+    fn mod_row(slug: &str, status: ModStatus, size: u64) -> ModRow {
+        ModRow {
+            slug: slug.to_string(),
+            version_name: Some("mc1.21.8-0.18.0".to_string()),
+            version_id: Some("gl30uZvp".to_string()),
+            status,
+            constraint: None,
+            age: None,
+            file_name: format!("{slug}.jar"),
+            size,
+            sha1: "3f786850e387550fdab836ed7e6dc881de23001b".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_mod_rows() {
+        let mut row = mod_row("lithium", ModStatus::UpToDate, 786_432);
+        assert_eq!(row.line(), "lithium: `mc1.21.8-0.18.0` [OK] up-to-date");
+
+        row.status = ModStatus::UpdateAvailable("mc1.21.8-0.18.1".to_string());
+        assert_eq!(
+            row.line(),
+            "lithium: `mc1.21.8-0.18.0` -> `mc1.21.8-0.18.1`"
+        );
+        row.constraint = Some("~0.18".to_string());
+        assert_eq!(
+            row.line(),
+            "lithium: `mc1.21.8-0.18.0` -> `mc1.21.8-0.18.1` within ~0.18"
+        );
+        row.constraint = None;
+
+        row.status = ModStatus::NotUpdatable("archived".to_string());
+        assert_eq!(
+            row.line(),
+            "lithium: `mc1.21.8-0.18.0` no longer updatable (archived)"
+        );
+
+        let table = detailed_table(&[&row]).to_string();
+        assert!(table.contains("768.0 KB"));
+        assert!(table.contains("| - "));
+        assert!(table.contains("3f786850e3 "));
+        assert!(!table.contains("3f786850e38"));
+        assert!(table.contains("gl30uZvp"));
+    }
+
+    #[test]
+    fn test_stale_rows() {
+        let day = Duration::from_secs(SECONDS_PER_DAY);
+        let update = || ModStatus::UpdateAvailable("new".to_string());
+        let mut rows = vec![
+            mod_row("sodium", update(), 1),
+            mod_row("lithium", update(), 1),
+            mod_row("fabric-api", ModStatus::UpToDate, 1),
+            // Installed without mcerv
+            mod_row("custom-mod", update(), 1),
+        ];
+        rows[0].age = Some(day * 45);
+        rows[1].age = Some(day * 3);
+        rows[2].age = Some(day * 90);
+
+        let stale = stale_rows(rows, 30);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].slug, "sodium");
+        assert!(
+            detailed_table(&[&stale[0]])
+                .to_string()
+                .contains("| 1 month ago ")
+        );
+        // Too many days to count in seconds is more than any mod's age
+        assert!(stale_rows(stale, u64::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_sort_and_sections() {
+        let mut rows = vec![
+            mod_row("sodium", ModStatus::UpToDate, 1),
+            mod_row("custom-mod", ModStatus::Unknown, 3),
+            mod_row("lithium", ModStatus::UpdateAvailable("new".to_string()), 2),
+            mod_row("fabric-api", ModStatus::UpToDate, 4),
+        ];
+        let slugs = |rows: &[&ModRow]| rows.iter().map(|r| r.slug.clone()).collect::<Vec<_>>();
+
+        sort_rows(&mut rows, ModSort::Size);
+        assert_eq!(
+            slugs(&rows.iter().collect::<Vec<_>>()),
+            ["fabric-api", "custom-mod", "lithium", "sodium"]
+        );
+
+        sort_rows(&mut rows, ModSort::Name);
+        let sections = sections(&rows);
+        let headings = sections.iter().map(|(h, _)| h.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            [
+                "Updates available (1)",
+                "Up to date (2)",
+                "Unknown, not on Modrinth (1)"
+            ]
+        );
+        assert_eq!(slugs(&sections[1].1), ["fabric-api", "sodium"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared-mods");
+        fs::create_dir(&shared).unwrap();
+        let mods_dir = dir.path().join("mods");
+        std::os::unix::fs::symlink(&shared, &mods_dir).unwrap();
+
+        assert_eq!(symlink_target(&mods_dir), Some(shared.clone()));
+        assert_eq!(symlink_target(&shared), None);
+        assert_eq!(symlink_target(&dir.path().join("missing")), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_symlink_target_junction() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared-mods");
+        fs::create_dir(&shared).unwrap();
+        let mods_dir = dir.path().join("mods");
+        // Junctions don't need the privilege symlinks do
+        let status = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(&mods_dir)
+            .arg(&shared)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        assert!(symlink_target(&mods_dir).is_some());
+        assert_eq!(symlink_target(&shared), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mod_jars_skips_oddities() {
+        let mods_dir = Path::new("testdata/mods-dir");
+        // The fixture's broken symlink must stay broken
+        assert!(!mods_dir.join("broken.jar").exists());
+        assert!(mods_dir.join("broken.jar").is_symlink());
+
+        let names = mod_jars(mods_dir, "1.21.8")
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(mods_dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            [
+                Path::new("1.21.8/sodium-0.7.0.jar"),
+                Path::new("lithium-0.18.0.jar"),
+            ]
+        );
+    }
+
+    /// The mods directory after an update downloaded `sodium-0.7.0.jar`
+    /// but was interrupted before deleting `sodium-0.6.13.jar`.
+    fn interrupted_update() -> (tempfile::TempDir, FileHash) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("sodium-0.6.13.jar"), b"old sodium").unwrap();
+        fs::write(dir.path().join("sodium-0.7.0.jar"), b"new sodium").unwrap();
+
+        let hash = FileHash {
+            algorithm: HashAlgorithm::Sha512,
+            value: jar_parser::calculate_hash_with(&mut &b"new sodium"[..], HashAlgorithm::Sha512)
+                .unwrap(),
+        };
+        (dir, hash)
+    }
+
+    #[test]
+    fn test_is_already_downloaded() {
+        let (dir, hash) = interrupted_update();
+
+        assert!(is_already_downloaded(dir.path(), "sodium-0.7.0.jar", &hash).unwrap());
+        assert!(!is_already_downloaded(dir.path(), "sodium-0.6.13.jar", &hash).unwrap());
+        assert!(!is_already_downloaded(dir.path(), "sodium-0.7.1.jar", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_installs_keeps_up_to_date_jar() {
+        let (dir, _) = interrupted_update();
+        // The old jar is the most recently modified, but not up to date
+        File::options()
+            .write(true)
+            .open(dir.path().join("sodium-0.6.13.jar"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let jars = [
+            InstalledJar {
+                path: dir.path().join("sodium-0.6.13.jar"),
+                project_id: "AANobbMI".to_string(),
+                up_to_date: false,
+            },
+            InstalledJar {
+                path: dir.path().join("sodium-0.7.0.jar"),
+                project_id: "AANobbMI".to_string(),
+                up_to_date: true,
+            },
+        ];
+
+        assert_eq!(
+            duplicate_installs(&jars).unwrap(),
+            [DuplicateInstall {
+                project_id: "AANobbMI".to_string(),
+                keep: dir.path().join("sodium-0.7.0.jar"),
+                older: vec![dir.path().join("sodium-0.6.13.jar")],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_installs_keeps_newest_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let mut jars = Vec::new();
+        for (i, name) in ["lithium-a.jar", "lithium-b.jar", "fabric-api.jar"]
+            .iter()
+            .enumerate()
+        {
+            let path = dir.path().join(name);
+            File::create(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(i as u64 * 60))
+                .unwrap();
+            jars.push(InstalledJar {
+                path,
+                project_id: if i < 2 { "gvQqBUqZ" } else { "P7dR8mSH" }.to_string(),
+                up_to_date: false,
+            });
+        }
+
+        let duplicates = duplicate_installs(&jars).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].keep, dir.path().join("lithium-a.jar"));
+        assert_eq!(duplicates[0].older, [dir.path().join("lithium-b.jar")]);
+    }
+
+    fn jar_with_mod_json(path: &Path, mod_json: serde_json::Value) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        writer
+            .start_file("fabric.mod.json", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        write!(writer, "{mod_json}").unwrap();
+        writer.finish().unwrap();
+    }
+
+    fn fabric_jar(path: &Path, environment: &str) {
+        jar_with_mod_json(
+            path,
+            serde_json::json!({"id": "x", "version": "1.0.0", "environment": environment}),
+        );
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = |name: &str, mod_json: serde_json::Value| {
+            let path = dir.path().join(name);
+            jar_with_mod_json(&path, mod_json);
+            path
+        };
+        // Written newest first, the versions decide which is older
+        let sodium_new = jar(
+            "sodium-0.5.9.jar",
+            serde_json::json!({"id": "sodium", "version": "0.5.9"}),
+        );
+        let sodium_old = jar(
+            "sodium-0.5.8.jar",
+            serde_json::json!({"id": "sodium", "version": "0.5.8"}),
+        );
+        let optifabric = jar(
+            "optifabric.jar",
+            serde_json::json!({
+                "id": "optifabric",
+                "version": "1.14.3",
+                "breaks": {"sodium": "*", "lithium": "<0.11"},
+                "conflicts": {"iris": [">=1.0 <1.5", "2.x"]},
+            }),
+        );
+        let lithium = jar(
+            "lithium.jar",
+            serde_json::json!({"id": "lithium", "version": "0.11.2"}),
+        );
+        let iris = jar(
+            "iris.jar",
+            serde_json::json!({"id": "iris", "version": "1.4.0+mc1.20.1"}),
+        );
+        let mut jars = vec![sodium_new.clone(), sodium_old.clone(), optifabric.clone()];
+        jars.extend([lithium, iris.clone(), dir.path().join("missing.jar")]);
+
+        let conflicts = find_conflicts(&jars).unwrap();
+        assert_eq!(
+            conflicts,
+            [
+                ModConflict::Duplicate {
+                    mod_id: "sodium".to_string(),
+                    jars: vec![sodium_old, sodium_new.clone()],
+                },
+                ModConflict::Declared {
+                    jar: optifabric.clone(),
+                    other: sodium_new,
+                    breaks: true,
+                },
+                ModConflict::Declared {
+                    jar: optifabric.clone(),
+                    other: jars[1].clone(),
+                    breaks: true,
+                },
+                ModConflict::Declared {
+                    jar: optifabric,
+                    other: iris,
+                    breaks: false,
+                },
+            ]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "sodium is installed more than once: sodium-0.5.8.jar, sodium-0.5.9.jar"
+        );
+        assert_eq!(
+            conflicts[3].to_string(),
+            "optifabric.jar conflicts with iris.jar"
+        );
+        assert!(!conflicts[3].is_fatal());
+    }
+
+    #[test]
+    fn test_version_matches() {
+        assert!(version_matches("0.5.8", "*"));
+        assert!(version_matches("0.5.8", ">=0.5 <0.6"));
+        assert!(!version_matches("0.6.0", ">=0.5 <0.6"));
+        assert!(version_matches("1.2.7", "1.2.x"));
+        assert!(!version_matches("1.3.0", "1.2.x"));
+        assert!(version_matches("1.2.7", "~1.2.3"));
+        assert!(!version_matches("1.3.0", "~1.2.3"));
+        assert!(version_matches("1.9.0", "^1.2.3"));
+        assert!(!version_matches("2.0.0", "^1.2.3"));
+        assert!(version_matches("0.16.14", "0.16.14"));
+        assert!(!version_matches("0.16.13", "=0.16.14"));
+    }
+
+    fn project(title: &str, server_side: SideSupport) -> Project {
+        Project {
+            id: "AANobbMI".to_string(),
+            slug: title.to_lowercase(),
+            title: title.to_string(),
+            client_side: SideSupport::Required,
+            server_side,
+            categories: Vec::new(),
+            status: None,
+            game_versions: Vec::new(),
+            loaders: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_installed_project() {
+        let jars = [
+            PathBuf::from("lithium-patch.jar"),
+            PathBuf::from("lithium-fabric-0.18.0.jar"),
+            PathBuf::from("XaerosMinimap-25.2.jar"),
+            PathBuf::from("custom.jar"),
+            PathBuf::from("sodium-extra-0.6.jar"),
+            PathBuf::from("sodium-fabric-0.6.jar"),
+        ];
+        let mod_ids = [
+            None,
+            Some("lithium".to_string()),
+            Some("xaerominimap".to_string()),
+            Some("custom".to_string()),
+            Some("sodium-extra".to_string()),
+            Some("sodium".to_string()),
+        ];
+        let projects = [
+            None,
+            Some(project("Lithium", SideSupport::Optional)),
+            Some(project("Xaero's Minimap", SideSupport::Unsupported)),
+            None,
+            Some(project("Sodium Extra", SideSupport::Optional)),
+            Some(project("Sodium", SideSupport::Optional)),
+        ];
+        let find = |query| {
+            find_installed_project(query, &jars, &mod_ids, &projects)
+                .map(|project| project.title.as_str())
+        };
+
+        assert_eq!(find("Lithium"), Some("Lithium"));
+        assert_eq!(find("xaero's minimap"), Some("Xaero's Minimap"));
+        assert_eq!(find("xaerominimap"), Some("Xaero's Minimap"));
+        assert_eq!(find("xaeros"), Some("Xaero's Minimap"));
+        // Not on Modrinth, so it has no page
+        assert_eq!(find("custom"), None);
+        assert_eq!(find("iris"), None);
+        // An exact name beats a jar whose file name only starts with it
+        assert_eq!(find("sodium"), Some("Sodium"));
+        assert_eq!(find("sodium-"), Some("Sodium Extra"));
+    }
+
+    #[test]
+    fn test_client_only_mods() {
+        let dir = tempfile::tempdir().unwrap();
+        let jars: Vec<PathBuf> = [
+            "xaeros-minimap.jar",
+            "lithium.jar",
+            "zoomify.jar",
+            "ledger.jar",
+        ]
+        .iter()
+        .map(|name| dir.path().join(name))
+        .collect();
+        // On Modrinth, whatever the jars say
+        fabric_jar(&jars[0], "*");
+        fabric_jar(&jars[1], "client");
+        // Unknown to Modrinth
+        fabric_jar(&jars[2], "client");
+        fabric_jar(&jars[3], "server");
+
+        let projects = [
+            Some(project("Xaero's Minimap", SideSupport::Unsupported)),
+            Some(project("Lithium", SideSupport::Optional)),
+            None,
+            None,
+        ];
+        assert_eq!(
+            client_only_mods(&jars, &projects),
+            [
+                ClientOnlyMod {
+                    path: jars[0].clone(),
+                    name: "Xaero's Minimap".to_string(),
+                    source: ClientOnlySource::Modrinth,
+                },
+                ClientOnlyMod {
+                    path: jars[2].clone(),
+                    name: "zoomify.jar".to_string(),
+                    source: ClientOnlySource::FabricModJson,
+                },
+            ]
+        );
+
+        let disabled = disable(&jars[2]).unwrap();
+        assert_eq!(disabled, dir.path().join("zoomify.jar.disabled"));
+        assert!(!jars[2].exists());
+        assert!(jar_parser::jar_files(dir.path()).unwrap().len() == 3);
+    }
+}
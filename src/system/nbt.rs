@@ -0,0 +1,294 @@
+use flate2::read::GzDecoder;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+/// How deep lists and compounds may nest, like in Minecraft.
+const MAX_DEPTH: usize = 512;
+/// Arrays are read in chunks of at most this many elements, so a corrupt length can't
+/// allocate all the memory up front.
+const MAX_PREALLOCATED: usize = 4096;
+
+/// A value of Minecraft's Named Binary Tag format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// The value of the key, if this is a compound with it.
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of any integer tag.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value.into()),
+            Tag::Short(value) => Some(value.into()),
+            Tag::Int(value) => Some(value.into()),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a gzip-compressed NBT file, like `level.dat`, returning its root tag.
+pub fn read_gzip_file(path: &Path) -> io::Result<Tag> {
+    let file = BufReader::new(File::open(path)?);
+    read(GzDecoder::new(file))
+}
+
+/// Reads uncompressed NBT, returning the root tag. Its name is usually empty and dropped.
+pub fn read(mut reader: impl Read) -> io::Result<Tag> {
+    let id = read_u8(&mut reader)?;
+    if id != 10 {
+        return Err(invalid(format!(
+            "The root tag has type {id}, not a compound"
+        )));
+    }
+    read_string(&mut reader)?;
+    read_payload(&mut reader, id, 0)
+}
+
+fn read_payload(reader: &mut impl Read, id: u8, depth: usize) -> io::Result<Tag> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("The tags nest too deeply".to_string()));
+    }
+
+    Ok(match id {
+        1 => Tag::Byte(i8::from_be_bytes(read_bytes(reader)?)),
+        2 => Tag::Short(i16::from_be_bytes(read_bytes(reader)?)),
+        3 => Tag::Int(i32::from_be_bytes(read_bytes(reader)?)),
+        4 => Tag::Long(i64::from_be_bytes(read_bytes(reader)?)),
+        5 => Tag::Float(f32::from_be_bytes(read_bytes(reader)?)),
+        6 => Tag::Double(f64::from_be_bytes(read_bytes(reader)?)),
+        7 => Tag::ByteArray(read_array(reader, i8::from_be_bytes)?),
+        8 => Tag::String(read_string(reader)?),
+        9 => {
+            let element_id = read_u8(reader)?;
+            let len = read_len(reader)?;
+            let mut elements = Vec::with_capacity(len.min(MAX_PREALLOCATED));
+            for _ in 0..len {
+                elements.push(read_payload(reader, element_id, depth + 1)?);
+            }
+            Tag::List(elements)
+        }
+        10 => {
+            let mut entries = HashMap::new();
+            loop {
+                let id = read_u8(reader)?;
+                if id == 0 {
+                    break;
+                }
+                let name = read_string(reader)?;
+                entries.insert(name, read_payload(reader, id, depth + 1)?);
+            }
+            Tag::Compound(entries)
+        }
+        11 => Tag::IntArray(read_array(reader, i32::from_be_bytes)?),
+        12 => Tag::LongArray(read_array(reader, i64::from_be_bytes)?),
+        id => return Err(invalid(format!("Unknown tag type {id}"))),
+    })
+}
+
+fn read_array<const N: usize, T>(
+    reader: &mut impl Read,
+    parse: impl Fn([u8; N]) -> T,
+) -> io::Result<Vec<T>> {
+    let len = read_len(reader)?;
+    let mut elements = Vec::with_capacity(len.min(MAX_PREALLOCATED));
+    for _ in 0..len {
+        elements.push(parse(read_bytes(reader)?));
+    }
+    Ok(elements)
+}
+
+fn read_len(reader: &mut impl Read) -> io::Result<usize> {
+    let len = i32::from_be_bytes(read_bytes(reader)?);
+    // Empty lists of Minecraft sometimes have a negative length
+    Ok(len.max(0) as usize)
+}
+
+/// Java's modified UTF-8, which only differs from UTF-8 for null and characters outside
+/// the BMP. Those are replaced.
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = u16::from_be_bytes(read_bytes(reader)?);
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    Ok(u8::from_be_bytes(read_bytes(reader)?))
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writes the named tag, for building test files.
+    pub(crate) fn write_named(output: &mut Vec<u8>, name: &str, tag: &Tag) {
+        output.push(tag_id(tag));
+        write_string(output, name);
+        write_payload(output, tag);
+    }
+
+    fn tag_id(tag: &Tag) -> u8 {
+        match tag {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    fn write_string(output: &mut Vec<u8>, value: &str) {
+        output.extend((value.len() as u16).to_be_bytes());
+        output.extend(value.as_bytes());
+    }
+
+    fn write_payload(output: &mut Vec<u8>, tag: &Tag) {
+        match tag {
+            Tag::Byte(value) => output.extend(value.to_be_bytes()),
+            Tag::Short(value) => output.extend(value.to_be_bytes()),
+            Tag::Int(value) => output.extend(value.to_be_bytes()),
+            Tag::Long(value) => output.extend(value.to_be_bytes()),
+            Tag::Float(value) => output.extend(value.to_be_bytes()),
+            Tag::Double(value) => output.extend(value.to_be_bytes()),
+            Tag::ByteArray(values) => {
+                output.extend((values.len() as i32).to_be_bytes());
+                output.extend(values.iter().map(|value| *value as u8));
+            }
+            Tag::String(value) => write_string(output, value),
+            Tag::List(elements) => {
+                output.push(elements.first().map_or(0, tag_id));
+                output.extend((elements.len() as i32).to_be_bytes());
+                for element in elements {
+                    write_payload(output, element);
+                }
+            }
+            Tag::Compound(entries) => {
+                for (name, tag) in entries {
+                    write_named(output, name, tag);
+                }
+                output.push(0);
+            }
+            Tag::IntArray(values) => {
+                output.extend((values.len() as i32).to_be_bytes());
+                for value in values {
+                    output.extend(value.to_be_bytes());
+                }
+            }
+            Tag::LongArray(values) => {
+                output.extend((values.len() as i32).to_be_bytes());
+                for value in values {
+                    output.extend(value.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn compound<const N: usize>(entries: [(&str, Tag); N]) -> Tag {
+        Tag::Compound(
+            entries
+                .into_iter()
+                .map(|(name, tag)| (name.to_string(), tag))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_read() {
+        let root = compound([
+            ("name", Tag::String("Bananrama".to_string())),
+            ("health", Tag::Float(20.0)),
+            ("seed", Tag::Long(-4_172_144_997_902_289_642)),
+            (
+                "pos",
+                Tag::List(vec![Tag::Double(0.5), Tag::Double(64.0), Tag::Double(-3.5)]),
+            ),
+            ("empty", Tag::List(Vec::new())),
+            ("heights", Tag::LongArray(vec![1, 2, 3])),
+            ("uuid", Tag::IntArray(vec![1, -2, 3, -4])),
+            ("flags", Tag::ByteArray(vec![-1, 0, 1])),
+            (
+                "nested",
+                compound([("level", Tag::Short(3)), ("on", Tag::Byte(1))]),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        write_named(&mut bytes, "", &root);
+
+        let tag = read(bytes.as_slice()).unwrap();
+        assert_eq!(tag, root);
+        assert_eq!(tag.get("name").and_then(Tag::as_str), Some("Bananrama"));
+        assert_eq!(
+            tag.get("nested").and_then(|nested| nested.get("level")),
+            Some(&Tag::Short(3))
+        );
+        assert_eq!(
+            tag.get("seed").and_then(Tag::as_i64),
+            Some(-4_172_144_997_902_289_642)
+        );
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        // A string as the root
+        let mut bytes = Vec::new();
+        write_named(&mut bytes, "", &Tag::String("level".to_string()));
+        assert!(read(bytes.as_slice()).is_err());
+
+        // Cut off in the middle of the compound
+        let mut bytes = Vec::new();
+        write_named(&mut bytes, "", &compound([("seed", Tag::Long(1))]));
+        bytes.truncate(bytes.len() - 4);
+        assert!(read(bytes.as_slice()).is_err());
+
+        // Unknown tag type 13
+        assert!(read([10, 0, 0, 13, 0, 0].as_slice()).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// Discord rejects messages longer than this.
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+/// The `notifications` section of the global config, like:
+///
+/// ```toml
+/// [[notifications.webhooks]]
+/// url = "https://discord.com/api/webhooks/..."
+/// format = "discord"
+/// events = ["crash", "mod_updates"]
+/// ```
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationSettings {
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// The events posted to this webhook, every event if empty.
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+}
+
+impl Webhook {
+    fn wants(&self, kind: EventKind) -> bool {
+        kind == EventKind::Test || self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The notification as a JSON object, with its `event` and a readable `message`.
+    #[default]
+    Generic,
+    /// A Discord message.
+    Discord,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    ModUpdates,
+    Crash,
+    ServerUpdate,
+    /// Sent by `mcerv notify test`, to every webhook.
+    Test,
+}
+
+/// Something worth telling the server's owner about.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// `ls-mods --notify` found updates, as `mod version` lines.
+    ModUpdates {
+        server: String,
+        updates: Vec<String>,
+    },
+    /// The supervisor is restarting a crashed server.
+    Crash {
+        server: String,
+        status: String,
+        restart: u32,
+        max_retries: u32,
+    },
+    /// `check-server` found newer versions of the server jar's components.
+    ServerUpdate {
+        server: String,
+        updates: Vec<String>,
+    },
+    Test,
+}
+
+impl Notification {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Notification::ModUpdates { .. } => EventKind::ModUpdates,
+            Notification::Crash { .. } => EventKind::Crash,
+            Notification::ServerUpdate { .. } => EventKind::ServerUpdate,
+            Notification::Test => EventKind::Test,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Notification::ModUpdates { server, updates } => {
+                format!(
+                    "Mod updates are available for {server}:\n{}",
+                    bullets(updates)
+                )
+            }
+            Notification::Crash {
+                server,
+                status,
+                restart,
+                max_retries,
+            } => format!("{server} crashed with {status}, restarting ({restart}/{max_retries})"),
+            Notification::ServerUpdate { server, updates } => {
+                format!(
+                    "A server update is available for {server}:\n{}",
+                    bullets(updates)
+                )
+            }
+            Notification::Test => "This is a test notification from mcerv.".to_string(),
+        }
+    }
+
+    /// The JSON body posted to a webhook of the format.
+    pub fn payload(&self, format: WebhookFormat) -> serde_json::Value {
+        match format {
+            WebhookFormat::Generic => {
+                let mut payload = serde_json::to_value(self).unwrap();
+                payload["message"] = self.message().into();
+                payload
+            }
+            WebhookFormat::Discord => {
+                let mut content = self.message();
+                if content.len() > DISCORD_MAX_LENGTH {
+                    let mut end = DISCORD_MAX_LENGTH - 3;
+                    while !content.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    content.truncate(end);
+                    content.push_str("...");
+                }
+                serde_json::json!({ "username": "mcerv", "content": content })
+            }
+        }
+    }
+}
+
+fn bullets(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|line| format!("- {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Posts notifications to the webhooks of the global config.
+#[derive(Clone)]
+pub struct Notifier {
+    client: Client,
+    settings: NotificationSettings,
+}
+
+impl Notifier {
+    pub fn new(client: Client, settings: NotificationSettings) -> Self {
+        Self { client, settings }
+    }
+
+    /// Posts the notification to every webhook that wants it, returning the failed ones with
+    /// why.
+    pub async fn send(&self, notification: &Notification) -> Vec<(&str, anyhow::Error)> {
+        let mut failures = Vec::new();
+        for webhook in &self.settings.webhooks {
+            if !webhook.wants(notification.kind()) {
+                continue;
+            }
+            if let Err(e) = self.post(webhook, notification).await {
+                failures.push((webhook.url.as_str(), e));
+            }
+        }
+        failures
+    }
+
+    /// Like [`Notifier::send`], but only warns about the failures, so a notification never
+    /// fails the command that sent it.
+    pub async fn notify(&self, notification: &Notification) {
+        for (url, e) in self.send(notification).await {
+            eprintln!(
+                "Warning: failed to send a notification to {}: {e}",
+                redact(url)
+            );
+        }
+    }
+
+    pub fn webhooks(&self) -> &[Webhook] {
+        &self.settings.webhooks
+    }
+
+    async fn post(&self, webhook: &Webhook, notification: &Notification) -> anyhow::Result<()> {
+        self.client
+            .post(&webhook.url)
+            .json(&notification.payload(webhook.format))
+            .send()
+            .await
+            .map_err(reqwest::Error::without_url)?
+            .error_for_status()
+            .map_err(reqwest::Error::without_url)?;
+        Ok(())
+    }
+}
+
+/// The webhook URL without its path, which holds the token of webhooks like Discord's.
+pub fn redact(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => format!(
+            "{}://{}/...",
+            url.scheme(),
+            url.host_str().unwrap_or_default()
+        ),
+        Err(_) => "an invalid URL".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Server};
+
+    fn webhook(url: String, format: WebhookFormat, events: Vec<EventKind>) -> Webhook {
+        Webhook {
+            url,
+            format,
+            events,
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings: NotificationSettings = toml::from_str(
+            "[[webhooks]]\nurl = \"https://example.com/hook\"\n\n\
+             [[webhooks]]\nurl = \"https://discord.example/hook\"\nformat = \"discord\"\nevents = [\"crash\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.webhooks,
+            [
+                webhook(
+                    "https://example.com/hook".to_string(),
+                    WebhookFormat::Generic,
+                    vec![]
+                ),
+                webhook(
+                    "https://discord.example/hook".to_string(),
+                    WebhookFormat::Discord,
+                    vec![EventKind::Crash]
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let mut server = Server::new_async().await;
+        let generic = server
+            .mock("POST", "/generic")
+            .match_body(Matcher::Json(serde_json::json!({
+                "event": "mod_updates",
+                "server": "survival",
+                "updates": ["sodium 0.6.13"],
+                "message": "Mod updates are available for survival:\n- sodium 0.6.13",
+            })))
+            .create_async()
+            .await;
+        let discord = server
+            .mock("POST", "/discord")
+            .expect(0)
+            .create_async()
+            .await;
+        let failing = server
+            .mock("POST", "/failing")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let notifier = Notifier::new(
+            Client::new(),
+            NotificationSettings {
+                webhooks: vec![
+                    webhook(
+                        format!("{}/generic", server.url()),
+                        WebhookFormat::Generic,
+                        vec![],
+                    ),
+                    webhook(
+                        format!("{}/discord", server.url()),
+                        WebhookFormat::Discord,
+                        vec![EventKind::Crash],
+                    ),
+                    webhook(
+                        format!("{}/failing", server.url()),
+                        WebhookFormat::Generic,
+                        vec![EventKind::ModUpdates],
+                    ),
+                ],
+            },
+        );
+        let failures = notifier
+            .send(&Notification::ModUpdates {
+                server: "survival".to_string(),
+                updates: vec!["sodium 0.6.13".to_string()],
+            })
+            .await;
+
+        generic.assert_async().await;
+        discord.assert_async().await;
+        failing.assert_async().await;
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].0.ends_with("/failing"));
+    }
+
+    #[test]
+    fn test_discord_payload() {
+        let notification = Notification::ServerUpdate {
+            server: "survival".to_string(),
+            updates: vec!["é".repeat(1500)],
+        };
+        let payload = notification.payload(WebhookFormat::Discord);
+        let content = payload["content"].as_str().unwrap();
+        assert!(content.len() <= DISCORD_MAX_LENGTH);
+        assert!(content.starts_with("A server update is available for survival:\n- é"));
+        assert!(content.ends_with("..."));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("https://discord.com/api/webhooks/123/secret-token"),
+            "https://discord.com/..."
+        );
+        assert_eq!(redact("not a url"), "an invalid URL");
+    }
+}
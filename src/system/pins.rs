@@ -0,0 +1,275 @@
+use semver::{Op, Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, error::Error, fmt::Display, fs, io, path::Path, str::FromStr};
+
+/// In the server directory, next to its config.
+pub const PINS_FILE: &str = "mcerv-pins.toml";
+/// Pre-release tags that really are pre-releases, unlike the `-fabric` or `-1.21` many mods
+/// put there.
+const PRE_RELEASE_TAGS: [&str; 5] = ["alpha", "beta", "rc", "pre", "snapshot"];
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidConstraintError {
+    constraint: String,
+    reason: String,
+}
+
+impl Display for InvalidConstraintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid version constraint {:?}: {}. Expected a range like ~0.100 or >=1.2, <2",
+            self.constraint, self.reason
+        )
+    }
+}
+
+impl Error for InvalidConstraintError {}
+
+/// The versions a mod may update to, like `~0.100` to stay on 0.100.x.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionConstraint {
+    raw: String,
+    req: VersionReq,
+}
+
+impl VersionConstraint {
+    /// Whether the Modrinth version number is in the range. Version numbers no version can be
+    /// read from never are.
+    pub fn matches(&self, version_number: &str) -> bool {
+        parse_version_number(version_number).is_some_and(|version| self.req.matches(&version))
+    }
+
+    /// Whether every version in the range is older than the version number, so updating
+    /// within it would downgrade the mod.
+    pub fn is_below(&self, version_number: &str) -> bool {
+        let Some(version) = parse_version_number(version_number) else {
+            return false;
+        };
+        // Pre-releases are only matched when a comparator asks for them
+        let version = Version::new(version.major, version.minor, version.patch);
+        self.req.comparators.iter().any(|comparator| {
+            let lowest = Version::new(
+                comparator.major,
+                comparator.minor.unwrap_or(0),
+                comparator.patch.unwrap_or(0),
+            );
+            match comparator.op {
+                Op::Greater | Op::GreaterEq => false,
+                Op::Less | Op::LessEq => !comparator.matches(&version),
+                // The others allow a range starting at their version
+                _ => !comparator.matches(&version) && lowest < version,
+            }
+        })
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = InvalidConstraintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw = s.trim().to_string();
+        let req = VersionReq::parse(&raw).map_err(|e| InvalidConstraintError {
+            constraint: raw.clone(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self { raw, req })
+    }
+}
+
+impl TryFrom<String> for VersionConstraint {
+    type Error = InvalidConstraintError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<VersionConstraint> for String {
+    fn from(constraint: VersionConstraint) -> Self {
+        constraint.raw
+    }
+}
+
+impl Display for VersionConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// The version of a Modrinth version number. Many aren't semver, like `mc1.21.8-0.18.0` or
+/// `1.8.2-1.21.5`, so without a semver version the first dotted number that isn't a game
+/// version prefixed with `mc` is read, padded like `0.100` to `0.100.0`.
+pub fn parse_version_number(version_number: &str) -> Option<Version> {
+    let version_number = version_number.trim();
+    let stripped = version_number.strip_prefix('v').unwrap_or(version_number);
+    if let Ok(mut version) = Version::parse(stripped) {
+        // Often the game version, like the `1.21` of `5.2.1-1.21` or `0.100.1+1.21`
+        version.build = semver::BuildMetadata::EMPTY;
+        let pre = version.pre.as_str().to_lowercase();
+        if !pre.is_empty() && !PRE_RELEASE_TAGS.iter().any(|tag| pre.starts_with(tag)) {
+            version.pre = semver::Prerelease::EMPTY;
+        }
+        return Some(version);
+    }
+
+    version_number
+        .split(['-', '+', '_', ' '])
+        .find_map(dotted_number)
+}
+
+fn dotted_number(part: &str) -> Option<Version> {
+    let part = part.strip_prefix('v').unwrap_or(part);
+    let numbers = part
+        .split('.')
+        .map(|number| number.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match numbers[..] {
+        [major] => Some(Version::new(major, 0, 0)),
+        [major, minor] => Some(Version::new(major, minor, 0)),
+        [major, minor, patch, ..] => Some(Version::new(major, minor, patch)),
+        [] => None,
+    }
+}
+
+/// The version constraints of a server's mods by project slug, like:
+///
+/// ```toml
+/// [fabric-api]
+/// constraint = "~0.100"
+/// ```
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct Pins {
+    pub projects: BTreeMap<String, Pin>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Pin {
+    pub constraint: VersionConstraint,
+}
+
+impl Pins {
+    /// Loads the pins of the server, or none if it has no pins file.
+    pub fn load(server_dir: &Path) -> anyhow::Result<Self> {
+        let path = server_dir.join(PINS_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {e}", path.display()))
+    }
+
+    /// Saves the pins, removing the file if there are none left.
+    pub fn save(&self, server_dir: &Path) -> anyhow::Result<()> {
+        let path = server_dir.join(PINS_FILE);
+        if self.projects.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn constraint(&self, slug: &str) -> Option<&VersionConstraint> {
+        self.projects.get(slug).map(|pin| &pin.constraint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(constraint: &str) -> VersionConstraint {
+        constraint.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_version_number() {
+        for (version_number, expected) in [
+            ("0.100.1+1.21", "0.100.1"),
+            ("v2.3.4", "2.3.4"),
+            ("mc1.21.8-0.18.0", "0.18.0"),
+            ("1.8.2-1.21.5", "1.8.2"),
+            ("5.2.1-fabric", "5.2.1"),
+            ("3.0.0-beta.2+mc1.21", "3.0.0-beta.2"),
+            ("fabric-0.5", "0.5.0"),
+            ("Sodium 0.6.13 for 1.21.8", "0.6.13"),
+            ("1.2.3.4", "1.2.3"),
+        ] {
+            assert_eq!(
+                parse_version_number(version_number),
+                Some(Version::parse(expected).unwrap()),
+                "{version_number}"
+            );
+        }
+        assert_eq!(parse_version_number("release"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        let fabric_api = constraint("~0.100");
+        assert!(fabric_api.matches("0.100.8+1.21"));
+        assert!(!fabric_api.matches("0.101.0+1.21"));
+        assert!(!fabric_api.matches("0.99.0+1.20.6"));
+
+        let lithium = constraint(">=0.17, <0.18");
+        assert!(lithium.matches("mc1.21.8-0.17.3"));
+        assert!(!lithium.matches("mc1.21.8-0.18.0"));
+
+        // Pre-releases only match when asked for
+        assert!(!constraint("^3").matches("3.1.0-beta.1"));
+        assert!(constraint(">=3.1.0-beta.1, <4").matches("3.1.0-beta.2"));
+        assert!(constraint("=1.8.2").matches("1.8.2-1.21.5"));
+        assert!(!constraint("*").matches("release"));
+
+        assert!("~0.100 nonsense".parse::<VersionConstraint>().is_err());
+    }
+
+    #[test]
+    fn test_is_below() {
+        assert!(constraint("~0.99").is_below("0.100.8+1.21"));
+        assert!(!constraint("~0.100").is_below("0.100.8+1.21"));
+        assert!(!constraint("~0.101").is_below("0.100.8+1.21"));
+        assert!(constraint(">=0.16, <0.17").is_below("mc1.21.8-0.17.3"));
+        assert!(!constraint(">=0.17").is_below("mc1.21.8-0.16.0"));
+        assert!(constraint("=1.8.1").is_below("1.8.2-1.21.5"));
+        assert!(!constraint("^3").is_below("3.1.0-beta.1"));
+        assert!(!constraint("*").is_below("release"));
+    }
+
+    #[test]
+    fn test_load_save() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Pins::load(dir.path()).unwrap(), Pins::default());
+
+        let mut pins = Pins::default();
+        pins.projects.insert(
+            "fabric-api".to_string(),
+            Pin {
+                constraint: constraint(" ~0.100"),
+            },
+        );
+        pins.save(dir.path()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(PINS_FILE)).unwrap(),
+            "[fabric-api]\nconstraint = \"~0.100\"\n"
+        );
+        let loaded = Pins::load(dir.path()).unwrap();
+        assert_eq!(loaded, pins);
+        assert_eq!(
+            loaded.constraint("fabric-api").unwrap().to_string(),
+            "~0.100"
+        );
+
+        pins.projects.clear();
+        pins.save(dir.path()).unwrap();
+        assert!(!dir.path().join(PINS_FILE).exists());
+    }
+}
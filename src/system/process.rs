@@ -0,0 +1,296 @@
+use crate::system::properties::ServerProperties;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, Signal, System};
+
+/// The port Minecraft servers use when `server-port` is not set.
+pub const DEFAULT_PORT: u16 = 25565;
+
+#[derive(Debug)]
+pub enum ServerProcessError {
+    AlreadyRunning(u32),
+    NotRunning,
+    /// The port is used by another server managed by mcerv.
+    PortUsedByServer {
+        port: u16,
+        server_name: String,
+    },
+    /// The port is used by something mcerv doesn't manage.
+    PortInUse(u16),
+    StopTimedOut(u32),
+}
+
+impl Display for ServerProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerProcessError::AlreadyRunning(pid) => {
+                write!(f, "Server is already running with pid {pid}")
+            }
+            ServerProcessError::NotRunning => write!(f, "Server is not running"),
+            ServerProcessError::PortUsedByServer { port, server_name } => {
+                write!(
+                    f,
+                    "Port {port} is already used by running server {server_name}"
+                )
+            }
+            ServerProcessError::PortInUse(port) => {
+                write!(f, "Port {port} is already in use by another program")
+            }
+            ServerProcessError::StopTimedOut(pid) => {
+                write!(f, "Server with pid {pid} did not stop in time")
+            }
+        }
+    }
+}
+
+impl Error for ServerProcessError {}
+
+/// The pid file written when mcerv starts a server.
+///
+/// The process start time is recorded too, so a reused pid isn't mistaken for the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PidFile {
+    pub pid: u32,
+    /// Seconds since the unix epoch, as reported by the OS.
+    pub start_time: u64,
+}
+
+/// A running server process.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessStatus {
+    pub pid: u32,
+    pub uptime: Duration,
+    /// Resident memory in bytes.
+    pub memory: u64,
+}
+
+impl PidFile {
+    pub fn path(server_dir: &Path) -> PathBuf {
+        server_dir.join("mcerv.pid")
+    }
+
+    /// Creates a pid file for a running process, or `None` if it's not running.
+    pub fn for_process(pid: u32) -> Option<Self> {
+        let system = refreshed_system(pid);
+        let process = system.process(Pid::from_u32(pid))?;
+
+        Some(Self {
+            pid,
+            start_time: process.start_time(),
+        })
+    }
+
+    pub fn load(server_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = Self::path(server_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    pub fn save(&self, server_dir: &Path) -> anyhow::Result<()> {
+        fs::write(Self::path(server_dir), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(server_dir: &Path) -> anyhow::Result<()> {
+        let path = Self::path(server_dir);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the status of the server's process, or `None` if it's not running.
+/// Stale pid files, whose process is gone or whose pid got reused, are removed.
+pub fn running_process(server_dir: &Path) -> anyhow::Result<Option<ProcessStatus>> {
+    let Some(pid_file) = PidFile::load(server_dir)? else {
+        return Ok(None);
+    };
+
+    let system = refreshed_system(pid_file.pid);
+    let status = system
+        .process(Pid::from_u32(pid_file.pid))
+        .filter(|process| process.start_time() == pid_file.start_time)
+        .map(|process| ProcessStatus {
+            pid: pid_file.pid,
+            uptime: Duration::from_secs(process.run_time()),
+            memory: process.memory(),
+        });
+
+    if status.is_none() {
+        PidFile::remove(server_dir)?;
+    }
+
+    Ok(status)
+}
+
+/// Spawns the command in its own session, so it survives mcerv exiting and
+/// doesn't receive the terminal's Ctrl-C. Output is appended to `log_path`.
+pub fn spawn_detached(mut command: Command, log_path: &Path) -> io::Result<Child> {
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+
+    command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: `setsid` is async-signal-safe and touches no memory of the parent
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    command.spawn()
+}
+
+/// Asks the process to shut down gracefully, like `stop` in the console does,
+/// and waits up to `timeout` for it to exit. Returns whether it exited.
+pub async fn terminate(pid: u32, timeout: Duration) -> bool {
+    let system = refreshed_system(pid);
+    let Some(process) = system.process(Pid::from_u32(pid)) else {
+        return true;
+    };
+
+    // The JVM saves the world in its shutdown hook when terminated.
+    // SIGTERM is not available on Windows, so the process is killed there.
+    if process.kill_with(Signal::Term).is_none() {
+        process.kill();
+    }
+
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+        if refreshed_system(pid).process(Pid::from_u32(pid)).is_none() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    false
+}
+
+/// Reads `server-port` from `server.properties`, defaulting to [`DEFAULT_PORT`].
+pub fn configured_port(server_dir: &Path) -> u16 {
+    ServerProperties::load(server_dir)
+        .ok()
+        .and_then(|properties| properties.get("server-port"))
+        .and_then(|port| port.trim().parse().ok())
+        .unwrap_or(DEFAULT_PORT)
+}
+
+/// The console log of servers started with `--detach`.
+pub fn console_log_path(server_dir: &Path) -> PathBuf {
+    server_dir.join("logs").join("mcerv-console.log")
+}
+
+/// Whether something on this machine is already listening on the port.
+pub fn is_port_in_use(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_err()
+}
+
+fn refreshed_system(pid: u32) -> System {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[Pid::from_u32(pid)]),
+        true,
+        ProcessRefreshKind::nothing().with_memory(),
+    );
+    system
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = PidFile::for_process(std::process::id()).unwrap();
+        pid_file.save(dir.path()).unwrap();
+
+        let status = running_process(dir.path()).unwrap().unwrap();
+        assert_eq!(status.pid, std::process::id());
+        assert!(PidFile::path(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_reused_pid_is_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pid_file = PidFile::for_process(std::process::id()).unwrap();
+        // Same pid, but a different process start time
+        pid_file.start_time -= 1;
+        pid_file.save(dir.path()).unwrap();
+
+        assert_eq!(running_process(dir.path()).unwrap(), None);
+        assert!(!PidFile::path(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_no_pid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(running_process(dir.path()).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_detached() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = console_log_path(dir.path());
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2"]);
+        let status = spawn_detached(command, &log_path).unwrap().wait().unwrap();
+        assert!(status.success());
+
+        let log = fs::read_to_string(log_path).unwrap();
+        assert!(log.contains("out"));
+        assert!(log.contains("err"));
+    }
+
+    #[test]
+    fn test_configured_port() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(configured_port(dir.path()), DEFAULT_PORT);
+
+        fs::write(
+            dir.path().join("server.properties"),
+            "#Minecraft server properties\nmotd=hi\nserver-port=25600\n",
+        )
+        .unwrap();
+        assert_eq!(configured_port(dir.path()), 25600);
+    }
+}
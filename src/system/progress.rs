@@ -0,0 +1,114 @@
+use crate::{EventSink, ProgressEvent};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{collections::HashMap, io::Write, path::Path, sync::Mutex};
+
+/// Draws a bar with the speed and ETA for every download. The bars are hidden when stderr
+/// isn't a terminal, and the phases are left to the messages on stdout.
+#[derive(Default)]
+pub struct ProgressBarSink {
+    bars: Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl EventSink for ProgressBarSink {
+    fn emit(&self, event: ProgressEvent) {
+        let mut bars = self.bars.lock().unwrap();
+        match event {
+            ProgressEvent::Phase { .. } => {}
+            ProgressEvent::DownloadProgress { file, done, total } => bars
+                .entry(file)
+                .or_insert_with_key(|file| download_bar(file, total))
+                .set_position(done),
+            ProgressEvent::DownloadDone { file, .. }
+            | ProgressEvent::DownloadFailed { file, .. } => {
+                if let Some(bar) = bars.remove(&file) {
+                    bar.finish_and_clear();
+                }
+            }
+        }
+    }
+}
+
+fn download_bar(file: &str, total: Option<u64>) -> ProgressBar {
+    let (bar, template) = match total {
+        Some(length) => (
+            ProgressBar::new(length),
+            "{msg} [{bar:30}] {percent}% of {total_bytes}, {bytes_per_sec}, ETA {eta}",
+        ),
+        // Without a length, neither the percent nor the ETA can be told
+        None => (ProgressBar::no_length(), "{msg} {bytes}, {bytes_per_sec}"),
+    };
+    bar.set_style(
+        ProgressStyle::with_template(template)
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    if let Some(name) = Path::new(file).file_name() {
+        bar.set_message(name.to_string_lossy().to_string());
+    }
+    bar
+}
+
+/// Writes every event as a line of JSON, for programs wrapping mcerv.
+pub struct JsonSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonSink {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+}
+
+impl EventSink for JsonSink {
+    fn emit(&self, event: ProgressEvent) {
+        let mut writer = self.writer.lock().unwrap();
+        // Progress is best effort, a closed pipe shouldn't fail the operation
+        if let Ok(line) = serde_json::to_string(&event) {
+            let _ = writeln!(writer, "{line}");
+            let _ = writer.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A writer the test can read back after handing it to the sink.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let buffer = SharedBuffer::default();
+        let sink = JsonSink::new(buffer.clone());
+        sink.emit(ProgressEvent::Phase {
+            name: "installing forge".to_string(),
+        });
+        sink.emit(ProgressEvent::DownloadProgress {
+            file: "server.jar".to_string(),
+            done: 1048576,
+            total: Some(9437184),
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "{\"event\":\"phase\",\"name\":\"installing forge\"}\n\
+             {\"event\":\"download_progress\",\"file\":\"server.jar\",\"done\":1048576,\"total\":9437184}\n"
+        );
+    }
+}
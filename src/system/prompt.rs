@@ -0,0 +1,195 @@
+use dialoguer::{Confirm, Input};
+
+/// How bad answering yes by mistake is, which decides the default answer and whether
+/// `--yes` answers for the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Danger<'a> {
+    /// Easily undone. Defaults to yes, also without a terminal.
+    Safe,
+    /// Changes the server, like updating its mods. Defaults to no.
+    Destructive,
+    /// Destructive beyond the server asked about, like changing a mods directory other
+    /// servers share. `--yes` doesn't answer it, its own flag does.
+    Guarded,
+    /// Loses what can't be had again, like a world. The name has to be typed, and `--yes`
+    /// doesn't answer it.
+    Critical(&'a str),
+}
+
+/// Where prompts are asked, a fake one in tests.
+pub trait Terminal {
+    fn is_interactive(&self) -> bool;
+
+    fn confirm(&self, message: &str, default: bool) -> anyhow::Result<bool>;
+
+    /// The line the user typed.
+    fn input(&self, message: &str) -> anyhow::Result<String>;
+}
+
+/// The user's terminal, unless `--non-interactive` is passed.
+struct Stdin;
+
+impl Terminal for Stdin {
+    fn is_interactive(&self) -> bool {
+        crate::is_interactive()
+    }
+
+    fn confirm(&self, message: &str, default: bool) -> anyhow::Result<bool> {
+        Ok(Confirm::new()
+            .with_prompt(message)
+            .default(default)
+            .interact()?)
+    }
+
+    fn input(&self, message: &str) -> anyhow::Result<String> {
+        Ok(Input::<String>::new()
+            .with_prompt(message)
+            .allow_empty(true)
+            .interact_text()?)
+    }
+}
+
+/// Asks the user to confirm, or answers for them with `--yes` or without a terminal, see
+/// [`Danger`].
+pub fn confirm(danger: Danger, message: &str) -> anyhow::Result<bool> {
+    confirm_on(&Stdin, crate::assume_yes(), danger, message)
+}
+
+fn confirm_on(
+    terminal: &impl Terminal,
+    assume_yes: bool,
+    danger: Danger,
+    message: &str,
+) -> anyhow::Result<bool> {
+    if assume_yes && matches!(danger, Danger::Safe | Danger::Destructive) {
+        return Ok(true);
+    }
+    if !terminal.is_interactive() {
+        return Ok(danger == Danger::Safe);
+    }
+    match danger {
+        Danger::Safe => terminal.confirm(message, true),
+        Danger::Destructive | Danger::Guarded => terminal.confirm(message, false),
+        Danger::Critical(name) => {
+            let typed = terminal.input(&format!("{message} Type {name} to confirm"))?;
+            Ok(typed.trim() == name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Answers with `answer`, or the default if it's `None`, and records what it was asked.
+    struct FakeTerminal {
+        interactive: bool,
+        answer: Option<&'static str>,
+        asked: RefCell<Vec<String>>,
+    }
+
+    impl FakeTerminal {
+        fn new(interactive: bool, answer: Option<&'static str>) -> Self {
+            Self {
+                interactive,
+                answer,
+                asked: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Terminal for FakeTerminal {
+        fn is_interactive(&self) -> bool {
+            self.interactive
+        }
+
+        fn confirm(&self, message: &str, default: bool) -> anyhow::Result<bool> {
+            self.asked
+                .borrow_mut()
+                .push(format!("{message} (default {default})"));
+            Ok(self.answer.map_or(default, |answer| answer == "y"))
+        }
+
+        fn input(&self, message: &str) -> anyhow::Result<String> {
+            self.asked.borrow_mut().push(message.to_string());
+            Ok(self.answer.unwrap_or_default().to_string())
+        }
+    }
+
+    const LEVELS: [Danger; 4] = [
+        Danger::Safe,
+        Danger::Destructive,
+        Danger::Guarded,
+        Danger::Critical("smp"),
+    ];
+
+    #[test]
+    fn test_defaults() {
+        let defaults: Vec<_> = LEVELS
+            .iter()
+            .map(|&danger| {
+                let terminal = FakeTerminal::new(true, None);
+                confirm_on(&terminal, false, danger, "Continue?").unwrap()
+            })
+            .collect();
+        assert_eq!(defaults, [true, false, false, false]);
+    }
+
+    #[test]
+    fn test_assume_yes() {
+        for (danger, expected, asks) in [
+            (Danger::Safe, true, false),
+            (Danger::Destructive, true, false),
+            (Danger::Guarded, false, true),
+            (Danger::Critical("smp"), false, true),
+        ] {
+            let terminal = FakeTerminal::new(true, None);
+            let answer = confirm_on(&terminal, true, danger, "Continue?").unwrap();
+            assert_eq!(answer, expected, "{danger:?}");
+            assert_eq!(!terminal.asked.borrow().is_empty(), asks, "{danger:?}");
+        }
+    }
+
+    #[test]
+    fn test_non_interactive() {
+        for assume_yes in [false, true] {
+            let answers: Vec<_> = LEVELS
+                .iter()
+                .map(|&danger| {
+                    let terminal = FakeTerminal::new(false, Some("y"));
+                    let answer = confirm_on(&terminal, assume_yes, danger, "Continue?").unwrap();
+                    assert!(terminal.asked.borrow().is_empty());
+                    answer
+                })
+                .collect();
+            assert_eq!(answers, [true, assume_yes, false, false]);
+        }
+    }
+
+    #[test]
+    fn test_critical_takes_the_name() {
+        let critical = Danger::Critical("smp");
+        let terminal = FakeTerminal::new(true, Some(" smp \n"));
+        assert!(confirm_on(&terminal, false, critical, "Reset the world?").unwrap());
+        assert_eq!(
+            terminal.asked.borrow().as_slice(),
+            ["Reset the world? Type smp to confirm"]
+        );
+
+        for typed in ["y", "SMP", ""] {
+            let terminal = FakeTerminal::new(true, Some(typed));
+            assert!(!confirm_on(&terminal, false, critical, "Reset the world?").unwrap());
+        }
+    }
+
+    #[test]
+    fn test_answers() {
+        for danger in [Danger::Safe, Danger::Destructive, Danger::Guarded] {
+            let terminal = FakeTerminal::new(true, Some("n"));
+            assert!(!confirm_on(&terminal, false, danger, "Continue?").unwrap());
+            let terminal = FakeTerminal::new(true, Some("y"));
+            assert!(confirm_on(&terminal, false, danger, "Continue?").unwrap());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A key the two properties files don't agree on, `None` where it's missing.
+#[derive(Debug, PartialEq)]
+pub struct PropertyDiff {
+    pub key: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// `server.properties`, edited in place so comments and the order of keys are kept.
+pub struct ServerProperties {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl ServerProperties {
+    pub fn path(server_dir: &Path) -> PathBuf {
+        server_dir.join("server.properties")
+    }
+
+    /// Loads the server's properties. A missing file is treated as empty.
+    pub fn load(server_dir: &Path) -> io::Result<Self> {
+        let path = Self::path(server_dir);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self::parse(path, &content))
+    }
+
+    fn parse(path: PathBuf, content: &str) -> Self {
+        Self {
+            path,
+            lines: content.lines().map(str::to_string).collect(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines.iter().find_map(|line| {
+            let (k, v) = split_line(line)?;
+            (k == key).then(|| unescape(v))
+        })
+    }
+
+    /// Sets the value of the key, adding it at the end if it's not there.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let new_line = format!("{key}={}", escape(value));
+        match self.position(key) {
+            Some(index) => self.lines[index] = new_line,
+            None => self.lines.push(new_line),
+        }
+    }
+
+    /// Removes the key, returning whether it was there.
+    pub fn remove(&mut self, key: &str) -> bool {
+        match self.position(key) {
+            Some(index) => {
+                self.lines.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The keys with their values, in the order of the file.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.lines
+            .iter()
+            .filter_map(|line| {
+                let (key, value) = split_line(line)?;
+                Some((key.to_string(), unescape(value)))
+            })
+            .collect()
+    }
+
+    /// The keys with different values, in the order of this file, then the keys only the
+    /// other has.
+    pub fn diff(&self, other: &ServerProperties) -> Vec<PropertyDiff> {
+        let mut keys: Vec<String> = self.entries().into_iter().map(|(key, _)| key).collect();
+        for (key, _) in other.entries() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        keys.into_iter()
+            .filter_map(|key| {
+                let (left, right) = (self.get(&key), other.get(&key));
+                (left != right).then_some(PropertyDiff { key, left, right })
+            })
+            .collect()
+    }
+
+    /// Copies the values of every key of `from` but the excluded ones, returning what
+    /// changed, with `left` the old value. Keys only this file has are kept.
+    pub fn sync_from(
+        &mut self,
+        from: &ServerProperties,
+        except: &[impl AsRef<str>],
+    ) -> Vec<PropertyDiff> {
+        let mut changes = Vec::new();
+        for (key, value) in from.entries() {
+            if except.iter().any(|excluded| excluded.as_ref() == key) {
+                continue;
+            }
+            let old = self.get(&key);
+            if old.as_deref() != Some(value.as_str()) {
+                self.set(&key, &value);
+                changes.push(PropertyDiff {
+                    key,
+                    left: old,
+                    right: Some(value),
+                });
+            }
+        }
+        changes
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+        fs::write(&self.path, content)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| split_line(line).is_some_and(|(k, _)| k == key))
+    }
+}
+
+/// Splits `key=value`, skipping comments and blank lines.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+        return None;
+    }
+    trimmed
+        .split_once('=')
+        .map(|(key, value)| (key.trim(), value))
+}
+
+/// Escapes like Java's `Properties.store`, which the server writes the file with.
+/// For example, `https://example.com` becomes `https\://example.com`.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | ':' | '=' | '#' | '!') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                unescaped.push(next);
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "#Minecraft server properties\n#Tue Aug 12 10:00:00 CEST 2025\nmotd=A Minecraft Server\nresource-pack=https\\://example.com/pack.zip\nserver-port=25565\n";
+
+    fn properties() -> ServerProperties {
+        ServerProperties::parse(PathBuf::from("server.properties"), CONTENT)
+    }
+
+    #[test]
+    fn test_get_unescapes() {
+        let properties = properties();
+        assert_eq!(
+            properties.get("resource-pack").as_deref(),
+            Some("https://example.com/pack.zip")
+        );
+        assert_eq!(properties.get("server-port").as_deref(), Some("25565"));
+        assert_eq!(properties.get("missing"), None);
+    }
+
+    #[test]
+    fn test_set_keeps_order_and_comments() {
+        let mut properties = properties();
+        properties.set("resource-pack", "https://example.com/other.zip");
+        properties.set("require-resource-pack", "true");
+
+        assert_eq!(
+            properties.lines,
+            [
+                "#Minecraft server properties",
+                "#Tue Aug 12 10:00:00 CEST 2025",
+                "motd=A Minecraft Server",
+                "resource-pack=https\\://example.com/other.zip",
+                "server-port=25565",
+                "require-resource-pack=true",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut properties = properties();
+        assert!(properties.remove("resource-pack"));
+        assert!(!properties.remove("resource-pack"));
+        assert_eq!(properties.get("resource-pack"), None);
+        assert_eq!(properties.lines.len(), 4);
+    }
+
+    #[test]
+    fn test_diff() {
+        let other = ServerProperties::parse(
+            PathBuf::from("server.properties"),
+            "motd=Lobby\nserver-port=25565\npvp=false\n",
+        );
+        assert_eq!(
+            properties().diff(&other),
+            [
+                PropertyDiff {
+                    key: "motd".to_string(),
+                    left: Some("A Minecraft Server".to_string()),
+                    right: Some("Lobby".to_string()),
+                },
+                PropertyDiff {
+                    key: "resource-pack".to_string(),
+                    left: Some("https://example.com/pack.zip".to_string()),
+                    right: None,
+                },
+                PropertyDiff {
+                    key: "pvp".to_string(),
+                    left: None,
+                    right: Some("false".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_from_keeps_comments_and_exclusions() {
+        let template = ServerProperties::parse(
+            PathBuf::from("server.properties"),
+            "#Lobby\nmotd=Lobby\nserver-port=25566\nresource-pack=https\\://example.com/pack.zip\npvp=false\n",
+        );
+        let mut properties = properties();
+        let changes = properties.sync_from(&template, &["server-port", "motd"]);
+
+        assert_eq!(
+            changes,
+            [PropertyDiff {
+                key: "pvp".to_string(),
+                left: None,
+                right: Some("false".to_string()),
+            }]
+        );
+        assert_eq!(
+            properties.lines,
+            [
+                "#Minecraft server properties",
+                "#Tue Aug 12 10:00:00 CEST 2025",
+                "motd=A Minecraft Server",
+                "resource-pack=https\\://example.com/pack.zip",
+                "server-port=25565",
+                "pvp=false",
+            ]
+        );
+        assert!(
+            properties
+                .sync_from(&template, &["server-port", "motd"])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut properties = ServerProperties::load(dir.path()).unwrap();
+        properties.set("motd", "hi");
+        properties.save().unwrap();
+
+        let loaded = ServerProperties::load(dir.path()).unwrap();
+        assert_eq!(loaded.get("motd").as_deref(), Some("hi"));
+    }
+}
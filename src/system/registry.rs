@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// In the data directory, next to the instances directory.
+pub const REGISTRY_FILE: &str = "registry.json";
+
+/// The servers installed outside of the instances directory with `install --location`, by
+/// name. Saved as a JSON object of absolute paths.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct Registry {
+    pub servers: BTreeMap<String, PathBuf>,
+}
+
+impl Registry {
+    /// Loads the registry, or an empty one if there is none.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid server registry {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    pub fn register(&mut self, server_name: &str, dir: PathBuf) {
+        self.servers.insert(server_name.to_string(), dir);
+    }
+
+    /// Forgets the server, returning where it was registered.
+    pub fn remove(&mut self, server_name: &str) -> Option<PathBuf> {
+        self.servers.remove(server_name)
+    }
+
+    /// Registers the server's directory under the new name, returning whether it was registered.
+    pub fn rename(&mut self, server_name: &str, new_name: &str) -> bool {
+        match self.servers.remove(server_name) {
+            Some(dir) => {
+                self.register(new_name, dir);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The registered servers whose directories don't exist anymore.
+    pub fn dangling(&self) -> Vec<(&str, &Path)> {
+        self.servers
+            .iter()
+            .filter(|(_, dir)| !dir.is_dir())
+            .map(|(name, dir)| (name.as_str(), dir.as_path()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REGISTRY_FILE);
+        assert_eq!(Registry::load(&path).unwrap(), Registry::default());
+
+        let mut registry = Registry::default();
+        registry.register("survival", dir.path().join("nvme").join("survival"));
+        registry.register("creative", dir.path().to_path_buf());
+        registry.save(&path).unwrap();
+
+        let mut registry = Registry::load(&path).unwrap();
+        assert_eq!(
+            registry.dangling(),
+            [(
+                "survival",
+                dir.path().join("nvme").join("survival").as_path()
+            )]
+        );
+
+        assert!(registry.rename("creative", "kids"));
+        assert!(!registry.rename("creative", "kids"));
+        assert_eq!(registry.remove("kids").as_deref(), Some(dir.path()));
+
+        fs::write(&path, "[").unwrap();
+        assert!(Registry::load(&path).is_err());
+    }
+}
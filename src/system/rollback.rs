@@ -0,0 +1,298 @@
+use crate::system::{
+    history::{Event, ModChange},
+    mod_lock::ModLock,
+    trash::{self, TrashBatch},
+};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// The operation of the mod updates of `ls-mods --update` in the history.
+pub const UPDATE_OPERATION: &str = "mods update";
+pub const ROLLBACK_OPERATION: &str = "rollback";
+
+/// Where the jar a mod was updated from can be had again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OldJar {
+    /// Still in the trash batch of the update.
+    Trashed,
+    /// Gone from the trash, but its Modrinth version can be downloaded again.
+    Download(String),
+    Unavailable,
+}
+
+impl Display for OldJar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OldJar::Trashed => write!(f, "from the trash"),
+            OldJar::Download(version_id) => write!(f, "downloading version {version_id}"),
+            OldJar::Unavailable => write!(f, "gone from the trash, and its version is unknown"),
+        }
+    }
+}
+
+/// What a rollback records as the update it rolled back. Timestamps only have seconds, so
+/// two updates can share one, while their trash batches are named apart.
+pub fn update_key(update: &Event) -> &str {
+    update
+        .trash_batch
+        .as_deref()
+        .unwrap_or(update.timestamp.as_str())
+}
+
+/// The latest mod update of the history that wasn't rolled back yet. Rollbacks of older
+/// versions of mcerv recorded the timestamp of the update.
+pub fn latest_update(events: &[Event]) -> Option<&Event> {
+    let rolled_back = events
+        .iter()
+        .filter_map(|event| event.rolled_back.as_deref())
+        .collect::<HashSet<_>>();
+    events.iter().rev().find(|event| {
+        event.operation == UPDATE_OPERATION
+            && !rolled_back.contains(update_key(event))
+            && !rolled_back.contains(event.timestamp.as_str())
+    })
+}
+
+/// Where each jar the update replaced can be had again.
+pub fn locate_old_jars(
+    server_dir: &Path,
+    update: &Event,
+) -> anyhow::Result<Vec<(ModChange, OldJar)>> {
+    let mut located = Vec::new();
+    for change in &update.mods {
+        let Some(old) = &change.old else {
+            continue;
+        };
+        let trashed = match &update.trash_batch {
+            Some(batch) => trash::find(server_dir, batch, old)?.is_some(),
+            None => false,
+        };
+        let old_jar = match &change.old_version_id {
+            _ if trashed => OldJar::Trashed,
+            Some(version_id) => OldJar::Download(version_id.clone()),
+            None => OldJar::Unavailable,
+        };
+        located.push((change.clone(), old_jar));
+    }
+    Ok(located)
+}
+
+/// Moves the jars of the version the mod was updated to into the trash batch, see
+/// [`new_jars`].
+pub fn trash_new_jars(
+    mods_dir: &Path,
+    change: &ModChange,
+    lock: &ModLock,
+    trash: &TrashBatch,
+) -> anyhow::Result<()> {
+    for path in new_jars(mods_dir, change, lock) {
+        trash.trash(&path)?;
+    }
+    Ok(())
+}
+
+/// The jars of the version the mod was updated to that are still there. They're the files
+/// the lockfile has for the version, or the jar the update recorded otherwise.
+pub fn new_jars(mods_dir: &Path, change: &ModChange, lock: &ModLock) -> Vec<PathBuf> {
+    let locked_files = change
+        .project_id
+        .as_deref()
+        .zip(change.new_version_id.as_deref())
+        .and_then(|(project_id, version_id)| lock.installed(project_id, version_id))
+        .map(|locked| locked.files.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut file_names = locked_files;
+    if let Some(new) = &change.new
+        && !file_names.contains(new)
+    {
+        file_names.push(new.clone());
+    }
+    file_names
+        .into_iter()
+        .map(|file_name| mods_dir.join(file_name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Moves the jar the mod was updated from back from the update's trash batch, returning
+/// where it's back at.
+pub fn restore_old_jar(
+    server_dir: &Path,
+    update: &Event,
+    change: &ModChange,
+) -> anyhow::Result<PathBuf> {
+    let (Some(batch), Some(old)) = (&update.trash_batch, &change.old) else {
+        anyhow::bail!("The update didn't trash a jar to restore");
+    };
+    trash::take(server_dir, batch, old)?
+        .ok_or_else(|| anyhow::anyhow!("{old} is gone from the trash"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{history, jar_parser};
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+    };
+
+    /// The files of the server directory and their hashes, without the trash and history.
+    fn hash_set(server_dir: &Path) -> BTreeSet<(PathBuf, String)> {
+        let mut files = BTreeSet::new();
+        let mut dirs = vec![server_dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.ends_with(trash::TRASH_DIR) || path.ends_with(history::HISTORY_FILE) {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let hash = jar_parser::calculate_hash(&mut fs::File::open(&path).unwrap());
+                    let relative = path.strip_prefix(server_dir).unwrap().to_path_buf();
+                    files.insert((relative, hash.unwrap()));
+                }
+            }
+        }
+        files
+    }
+
+    /// Replaces the jar like `ls-mods --update`, returning the change it records.
+    fn update(server_dir: &Path, old: &str, new: &str, new_extra: Option<&str>) -> ModChange {
+        let mods_dir = server_dir.join("mods");
+        let batch = TrashBatch::begin(server_dir, UPDATE_OPERATION);
+        for file_name in std::iter::once(new).chain(new_extra) {
+            fs::write(mods_dir.join(file_name), file_name).unwrap();
+            batch.record_added(&mods_dir.join(file_name)).unwrap();
+        }
+        batch.trash(&mods_dir.join(old)).unwrap();
+        ModChange {
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+            project_id: Some(format!("{old}-project")),
+            old_version_id: Some(format!("{old}-version")),
+            new_version_id: Some(format!("{new}-version")),
+        }
+    }
+
+    #[test]
+    fn test_update_then_undo_restores_mods() {
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path();
+        let mods_dir = server_dir.join("mods");
+        fs::create_dir_all(&mods_dir).unwrap();
+        for name in ["sodium-0.6.13.jar", "lithium-0.18.0.jar", "own.jar"] {
+            fs::write(mods_dir.join(name), name).unwrap();
+        }
+        let original = hash_set(server_dir);
+
+        let mut event = Event::new(UPDATE_OPERATION);
+        event.mods = vec![
+            update(server_dir, "sodium-0.6.13.jar", "sodium-0.6.14.jar", None),
+            update(
+                server_dir,
+                "lithium-0.18.0.jar",
+                "lithium-0.19.0.jar",
+                Some("lithium-api.jar"),
+            ),
+        ];
+        // Each update above was its own batch, so the second one is undone from the trash
+        // and the first one is downloaded again
+        let mut batches = fs::read_dir(server_dir.join(trash::TRASH_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        batches.sort();
+        event.trash_batch = Some(batches[1].clone());
+        let mut lock = ModLock::default();
+        lock.record(
+            "lithium-0.18.0.jar-project",
+            "lithium-0.19.0.jar-version",
+            BTreeMap::from([
+                ("lithium-0.19.0.jar".to_string(), String::new()),
+                ("lithium-api.jar".to_string(), String::new()),
+            ]),
+            "2025-08-14T18:03:00+02:00",
+        );
+        assert_ne!(hash_set(server_dir), original);
+
+        let located = locate_old_jars(server_dir, &event).unwrap();
+        assert_eq!(
+            located
+                .iter()
+                .map(|(_, old)| old.clone())
+                .collect::<Vec<_>>(),
+            [
+                OldJar::Download("sodium-0.6.13.jar-version".to_string()),
+                OldJar::Trashed
+            ]
+        );
+
+        let rollback = TrashBatch::begin(server_dir, ROLLBACK_OPERATION);
+        for (change, old_jar) in &located {
+            trash_new_jars(&mods_dir, change, &lock, &rollback).unwrap();
+            match old_jar {
+                OldJar::Trashed => {
+                    let path = restore_old_jar(server_dir, &event, change).unwrap();
+                    assert_eq!(path, mods_dir.join("lithium-0.18.0.jar"));
+                }
+                // Stands in for the download
+                _ => fs::write(mods_dir.join("sodium-0.6.13.jar"), "sodium-0.6.13.jar").unwrap(),
+            }
+        }
+
+        assert_eq!(hash_set(server_dir), original);
+        // Nothing is left to roll back from the batch
+        assert_eq!(
+            trash::find(server_dir, &batches[1], "lithium-0.18.0.jar").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_locate_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut event = Event::new(UPDATE_OPERATION);
+        event.trash_batch = Some("20250814-180300.000".to_string());
+        event.mods = vec![ModChange {
+            old: Some("own.jar".to_string()),
+            new: Some("own-2.jar".to_string()),
+            ..Default::default()
+        }];
+
+        let located = locate_old_jars(dir.path(), &event).unwrap();
+        assert_eq!(located[0].1, OldJar::Unavailable);
+        assert!(restore_old_jar(dir.path(), &event, &located[0].0).is_err());
+    }
+
+    #[test]
+    fn test_latest_update() {
+        let mut first = Event::new(UPDATE_OPERATION);
+        first.timestamp = "2025-08-14T18:03:00+02:00".to_string();
+        first.trash_batch = Some("20250814-180300.000".to_string());
+        // In the same second as the first one
+        let mut second = first.clone();
+        second.trash_batch = Some("20250814-180300.412".to_string());
+        let mut rollback = Event::new(ROLLBACK_OPERATION);
+        rollback.rolled_back = Some(update_key(&second).to_string());
+        let install = Event::new("install-mod");
+
+        let events = [first.clone(), second.clone(), install.clone()];
+        assert_eq!(latest_update(&events), Some(&second));
+        let events = [first.clone(), second.clone(), rollback, install.clone()];
+        assert_eq!(latest_update(&events), Some(&first));
+        assert_eq!(latest_update(&[]), None);
+
+        let mut legacy = Event::new(ROLLBACK_OPERATION);
+        legacy.rolled_back = Some("2025-08-14T18:03:00+02:00".to_string());
+        let mut old = Event::new(UPDATE_OPERATION);
+        old.timestamp = "2025-08-14T18:03:00+02:00".to_string();
+        assert_eq!(latest_update(&[old, legacy, install]), None);
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{Duration as ChronoDuration, NaiveDateTime, NaiveTime};
+use std::{error::Error, fmt::Display, str::FromStr, time::Duration};
+
+/// The warnings broadcast before a scheduled restart, with how long before it they're sent.
+pub const RESTART_WARNINGS: [(Duration, &str); 3] = [
+    (Duration::from_secs(5 * 60), "5 minutes"),
+    (Duration::from_secs(60), "1 minute"),
+    (Duration::from_secs(10), "10 seconds"),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidTimeError(String);
+
+impl Display for InvalidTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid time {:?}, expected HH:MM like 04:30", self.0)
+    }
+}
+
+impl Error for InvalidTimeError {}
+
+/// A time of day, in local time, at which the server is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ScheduledTime(NaiveTime);
+
+impl ScheduledTime {
+    pub fn new(hour: u32, minute: u32) -> Option<Self> {
+        NaiveTime::from_hms_opt(hour, minute, 0).map(Self)
+    }
+}
+
+impl FromStr for ScheduledTime {
+    type Err = InvalidTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTimeError(s.to_string());
+        let (hour, minute) = s.trim().split_once(':').ok_or_else(invalid)?;
+        if minute.len() != 2 {
+            return Err(invalid());
+        }
+
+        let hour = hour.parse().map_err(|_| invalid())?;
+        let minute = minute.parse().map_err(|_| invalid())?;
+        Self::new(hour, minute).ok_or_else(invalid)
+    }
+}
+
+impl Display for ScheduledTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+/// The first of the scheduled times strictly after `now`, which may be tomorrow.
+/// Returns `None` if nothing is scheduled.
+pub fn next_occurrence(times: &[ScheduledTime], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    times
+        .iter()
+        .map(|time| {
+            let today = now.date().and_time(time.0);
+            if today > now {
+                today
+            } else {
+                today + ChronoDuration::days(1)
+            }
+        })
+        .min()
+}
+
+/// How long from now until the next scheduled time, in local time.
+pub fn until_next(times: &[ScheduledTime]) -> Option<Duration> {
+    let now = chrono::Local::now().naive_local();
+    next_occurrence(times, now).map(|next| (next - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 12, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn times(times: &[&str]) -> Vec<ScheduledTime> {
+        times.iter().map(|time| time.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("04:30".parse(), Ok(ScheduledTime::new(4, 30).unwrap()));
+        assert_eq!("4:05".parse(), Ok(ScheduledTime::new(4, 5).unwrap()));
+        assert!("24:00".parse::<ScheduledTime>().is_err());
+        assert!("12:60".parse::<ScheduledTime>().is_err());
+        assert!("12:5".parse::<ScheduledTime>().is_err());
+        assert!("noon".parse::<ScheduledTime>().is_err());
+        assert_eq!(ScheduledTime::new(4, 5).unwrap().to_string(), "04:05");
+    }
+
+    #[test]
+    fn test_next_occurrence_later_today() {
+        let next = next_occurrence(&times(&["04:00"]), at(1, 3, 59));
+        assert_eq!(next, Some(at(1, 4, 0)));
+    }
+
+    #[test]
+    fn test_next_occurrence_wraps_past_midnight() {
+        assert_eq!(
+            next_occurrence(&times(&["00:00"]), at(1, 23, 59)),
+            Some(at(2, 0, 0))
+        );
+        // Exactly at the scheduled time means the next one is tomorrow
+        assert_eq!(
+            next_occurrence(&times(&["04:00"]), at(1, 4, 0)),
+            Some(at(2, 4, 0))
+        );
+        // Across the end of the month
+        assert_eq!(
+            next_occurrence(&times(&["04:00"]), at(31, 12, 0)),
+            Some(
+                NaiveDate::from_ymd_opt(2026, 1, 1)
+                    .unwrap()
+                    .and_hms_opt(4, 0, 0)
+                    .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_next_occurrence_multiple_times() {
+        let schedule = times(&["18:00", "04:00", "12:00"]);
+        assert_eq!(next_occurrence(&schedule, at(1, 1, 0)), Some(at(1, 4, 0)));
+        assert_eq!(next_occurrence(&schedule, at(1, 5, 0)), Some(at(1, 12, 0)));
+        assert_eq!(next_occurrence(&schedule, at(1, 12, 0)), Some(at(1, 18, 0)));
+        assert_eq!(next_occurrence(&schedule, at(1, 20, 0)), Some(at(2, 4, 0)));
+    }
+
+    #[test]
+    fn test_next_occurrence_empty() {
+        assert_eq!(next_occurrence(&[], at(1, 0, 0)), None);
+    }
+}
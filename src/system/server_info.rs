@@ -1,11 +1,22 @@
-use crate::system::{
-    forks::{self, ServerFork},
-    jar_parser,
+use crate::{
+    server_dir,
+    system::{
+        config::Config,
+        forks::{self, ServerFork},
+        jar_parser::{self, HashAlgorithm},
+    },
+    use_detection_cache,
 };
+use serde::{Deserialize, Serialize};
 use std::{
+    error::Error,
     fmt::{Debug, Display},
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek},
     path::Path,
+    time::UNIX_EPOCH,
 };
+use zip::ZipArchive;
 
 #[derive(Debug)]
 pub struct ServerInfo {
@@ -13,18 +24,180 @@ pub struct ServerInfo {
     pub game_version: String,
 }
 
+/// The server info detected from a jar and what identified the jar then. Stored in the
+/// config, so commands don't have to unzip the jar every time.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct JarDetection {
+    // Configs written before the game version was cached only have the fork
+    #[serde(alias = "fork")]
+    pub detected_fork: Option<ServerFork>,
+    pub detected_game_version: Option<String>,
+    pub jar_sha1: Option<String>,
+    /// Checked before the hash, which takes long to calculate for big jars.
+    pub jar_size: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    pub jar_modified: Option<u64>,
+}
+
 impl ServerInfo {
+    /// Detects the info of the server jar, or of the Forge argument file that an instance
+    /// without one starts from, which tells the versions by its path.
     pub fn new(jar_path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let mut archive = jar_parser::archive(jar_path)?;
+        let jar_path = jar_path.as_ref();
+        if let Some((game_version, _)) = forks::forge_args_file_version(jar_path) {
+            return Ok(Self {
+                server_fork: ServerFork::Forge,
+                game_version,
+            });
+        }
+
+        Self::from_archive(&mut jar_parser::archive(jar_path)?)
+    }
+
+    /// Detects the info of the opened jar. A bundler's nested server jar is read at most
+    /// once, for both the fork and the game version.
+    pub fn from_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Self> {
+        match Self::from_jar(archive) {
+            Err(e) => match forks::nested_server_jar(archive)? {
+                Some(mut inner) => Self::from_jar(&mut inner),
+                None => Err(e),
+            },
+            info => info,
+        }
+    }
 
-        let server_fork = forks::detect_server_fork(&mut archive)?;
-        let game_version = forks::detect_game_version(&mut archive, server_fork)?;
+    fn from_jar<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<Self> {
+        let server_fork = forks::detect_jar_fork(archive)?;
+        let game_version = server_fork.jar_game_version(archive)?;
 
         Ok(Self {
             server_fork,
             game_version,
         })
     }
+
+    /// The info of the server's configured jar, cached in its config until the jar changes.
+    pub fn cached_or_detect(server_name: &str) -> anyhow::Result<Self> {
+        Self::cached_or_detect_with(server_name, false)
+    }
+
+    /// Like [`ServerInfo::cached_or_detect`], but with `refresh`, the jar is detected again
+    /// even if it didn't change. With `--no-cache`, the config is neither read nor written.
+    pub fn cached_or_detect_with(server_name: &str, refresh: bool) -> anyhow::Result<Self> {
+        let mut config = Config::load_or_create(server_name)?;
+        Self::from_config(server_name, &mut config, refresh)
+    }
+
+    /// Like [`ServerInfo::cached_or_detect_with`], but with the config the command already
+    /// loaded, so neither it nor the jar is read again.
+    pub fn from_config(
+        server_name: &str,
+        config: &mut Config,
+        refresh: bool,
+    ) -> anyhow::Result<Self> {
+        let jar_path = server_dir(server_name)?.join(&config.jar_name);
+        if !use_detection_cache() {
+            return Self::new(jar_path);
+        }
+
+        let old_detection = config.detection.clone();
+        let info = config.detection.server_info(&jar_path, refresh)?;
+        if config.detection != old_detection {
+            config.save(server_name)?;
+        }
+
+        Ok(info)
+    }
+
+    /// Like [`ServerInfo::cached_or_detect`], but if the jar is missing, like in the middle
+    /// of an update, the info last cached in the config is used with a warning. Only for
+    /// reading, since the jar can come back as something else.
+    pub fn cached_or_last_known(server_name: &str) -> anyhow::Result<Self> {
+        let mut config = Config::load_or_create(server_name)?;
+        let jar_path = server_dir(server_name)?.join(&config.jar_name);
+        if !jar_path.exists() && use_detection_cache() {
+            let info = config.detection.last_known(&jar_path)?;
+            eprintln!(
+                "Warning: {} is missing, assuming {server_name} is still {:?} {}",
+                config.jar_name, info.server_fork, info.game_version
+            );
+            return Ok(info);
+        }
+
+        Self::cached_or_detect(server_name)
+    }
+}
+
+impl JarDetection {
+    /// Detects the server info of the jar, along with what identifies the jar.
+    pub fn detect(jar_path: &Path) -> anyhow::Result<(ServerInfo, Self)> {
+        let info = ServerInfo::new(jar_path)?;
+        let detection = Self::new(&info, jar_path)?;
+        Ok((info, detection))
+    }
+
+    pub fn new(info: &ServerInfo, jar_path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(jar_path)?;
+        Ok(Self {
+            detected_fork: Some(info.server_fork),
+            detected_game_version: Some(info.game_version.clone()),
+            jar_sha1: Some(sha1(jar_path)?),
+            jar_size: Some(metadata.len()),
+            jar_modified: Some(modified_millis(&metadata)?),
+        })
+    }
+
+    /// Returns the cached info if the jar is still the one it was detected from, otherwise
+    /// detects it again and replaces the cache.
+    ///
+    /// A jar with another size or modification time is only detected again if its hash
+    /// changed as well, like when it was copied over with the same content.
+    pub fn server_info(&mut self, jar_path: &Path, refresh: bool) -> anyhow::Result<ServerInfo> {
+        if !refresh && let Some(info) = self.cached() {
+            let metadata = fs::metadata(jar_path)?;
+            let size = metadata.len();
+            let modified = modified_millis(&metadata)?;
+            if self.jar_size == Some(size) && self.jar_modified == Some(modified) {
+                return Ok(info);
+            }
+            if self.jar_sha1.as_deref() == Some(sha1(jar_path)?.as_str()) {
+                self.jar_size = Some(size);
+                self.jar_modified = Some(modified);
+                return Ok(info);
+            }
+        }
+
+        let (info, detection) = Self::detect(jar_path)?;
+        *self = detection;
+        Ok(info)
+    }
+
+    /// The cached info if the jar is missing, otherwise like [`JarDetection::server_info`].
+    pub fn last_known(&mut self, jar_path: &Path) -> anyhow::Result<ServerInfo> {
+        if !jar_path.exists()
+            && let Some(info) = self.cached()
+        {
+            return Ok(info);
+        }
+        self.server_info(jar_path, false)
+    }
+
+    fn cached(&self) -> Option<ServerInfo> {
+        Some(ServerInfo {
+            server_fork: self.detected_fork?,
+            game_version: self.detected_game_version.clone()?,
+        })
+    }
+}
+
+fn sha1(path: &Path) -> io::Result<String> {
+    jar_parser::calculate_hash_with(&mut File::open(path)?, HashAlgorithm::Sha1)
+}
+
+fn modified_millis(metadata: &Metadata) -> io::Result<u64> {
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH);
+    Ok(modified.map_or(0, |duration| duration.as_millis() as u64))
 }
 
 impl Display for ServerInfo {
@@ -34,3 +207,256 @@ impl Display for ServerInfo {
         Ok(())
     }
 }
+
+/// Replacing the server jar with an older game version, which can corrupt the world.
+#[derive(Debug, PartialEq)]
+pub enum DowngradeError {
+    /// The world was last opened with a newer version.
+    World {
+        world_version: String,
+        target: String,
+    },
+    /// The current jar is a newer version.
+    Jar { current: String, target: String },
+}
+
+impl Display for DowngradeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DowngradeError::World {
+                world_version,
+                target,
+            } => write!(
+                f,
+                "The world was last opened with {world_version}, which is newer than {target}"
+            )?,
+            DowngradeError::Jar { current, target } => {
+                write!(f, "The server is {current}, which is newer than {target}")?
+            }
+        }
+        write!(
+            f,
+            ". Downgrading can corrupt the world, pass --allow-downgrade to do it anyway"
+        )
+    }
+}
+
+impl Error for DowngradeError {}
+
+/// The fork of a server couldn't be told, as opposed to it being vanilla or modded.
+#[derive(Debug)]
+pub struct UnknownForkError {
+    pub server_name: String,
+    pub source: anyhow::Error,
+}
+
+impl Display for UnknownForkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Couldn't determine the fork of {}: {}",
+            self.server_name, self.source
+        )
+    }
+}
+
+impl Error for UnknownForkError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::Cell,
+        io::Write,
+        rc::Rc,
+        time::{Duration, SystemTime},
+    };
+
+    fn set_modified(path: &Path, modified: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    /// Counts the bytes read through it.
+    struct CountingReader<R> {
+        inner: R,
+        read: Rc<Cell<u64>>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.set(self.read.get() + n as u64);
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_bundler_read_once() {
+        // A bundler whose own manifest doesn't tell the fork, so both the fork and the
+        // version come from the nested jar
+        let nested_path = "META-INF/versions/1.21.8/server-1.21.8.jar";
+        let mut nested = Vec::new();
+        jar_parser::archive("testdata/vanilla-1.21.8-bundler.jar")
+            .unwrap()
+            .by_name(nested_path)
+            .unwrap()
+            .read_to_end(&mut nested)
+            .unwrap();
+        let mut bundler = io::Cursor::new(Vec::new());
+        let mut writer = zip::ZipWriter::new(&mut bundler);
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("META-INF/MANIFEST.MF", options).unwrap();
+        writer.write_all(b"Manifest-Version: 1.0\r\n").unwrap();
+        writer
+            .start_file("META-INF/versions.list", options)
+            .unwrap();
+        writer
+            .write_all(b"hash\t1.21.8\t1.21.8/server-1.21.8.jar\n")
+            .unwrap();
+        // Stored, so reading it reads as many bytes as it has
+        let stored = options.compression_method(zip::CompressionMethod::Stored);
+        writer.start_file(nested_path, stored).unwrap();
+        writer.write_all(&nested).unwrap();
+        writer.finish().unwrap();
+        let bundler = bundler.into_inner();
+
+        let counting_archive = || {
+            let read = Rc::new(Cell::new(0));
+            let reader = CountingReader {
+                inner: io::Cursor::new(bundler.clone()),
+                read: read.clone(),
+            };
+            (ZipArchive::new(reader).unwrap(), read)
+        };
+
+        let (mut archive, read) = counting_archive();
+        let info = ServerInfo::from_archive(&mut archive).unwrap();
+        assert_eq!(info.server_fork, ServerFork::Vanilla);
+        assert_eq!(info.game_version, "1.21.8");
+        let read_once = read.get();
+
+        // Detecting the fork and the version apart reads the nested jar twice
+        let (mut archive, read) = counting_archive();
+        let fork = forks::detect_server_fork(&mut archive).unwrap();
+        fork.game_version(&mut archive).unwrap();
+        assert!(read.get() >= read_once + nested.len() as u64);
+    }
+
+    #[test]
+    fn test_forge_args_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let args_file = dir
+            .path()
+            .join("libraries/net/minecraftforge/forge/1.20.1-47.4.0/unix_args.txt");
+        fs::create_dir_all(args_file.parent().unwrap()).unwrap();
+        fs::write(&args_file, "--launchTarget forgeserver").unwrap();
+
+        // Found without a server jar
+        let found = jar_parser::single_jar(dir.path()).unwrap();
+        assert_eq!(
+            jar_parser::jar_name(dir.path(), &found),
+            "libraries/net/minecraftforge/forge/1.20.1-47.4.0/unix_args.txt"
+        );
+
+        let (info, detection) = JarDetection::detect(&found).unwrap();
+        assert_eq!(info.server_fork, ServerFork::Forge);
+        assert_eq!(info.game_version, "1.20.1");
+        assert!(detection.jar_sha1.is_some());
+    }
+
+    #[test]
+    fn test_cached_until_jar_swapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        fs::copy("testdata/vanilla-1.21.8.jar", &jar_path).unwrap();
+
+        let mut detection = JarDetection::default();
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.server_fork, ServerFork::Vanilla);
+        assert_eq!(detection.detected_game_version.as_deref(), Some("1.21.8"));
+
+        // Proves the jar isn't opened while it's unchanged
+        detection.detected_game_version = Some("cached".to_string());
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.game_version, "cached");
+
+        // Another modification time, but the same content
+        set_modified(&jar_path, SystemTime::now() + Duration::from_secs(60));
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.game_version, "cached");
+
+        fs::copy(
+            "testdata/fabric-server-mc.1.21.8-loader.0.16.14-launcher.1.0.3.jar",
+            &jar_path,
+        )
+        .unwrap();
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.server_fork, ServerFork::Fabric);
+        assert_eq!(detection.detected_fork, Some(ServerFork::Fabric));
+    }
+
+    #[test]
+    fn test_hash_checked_when_size_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        fs::copy("testdata/vanilla-1.21.8.jar", &jar_path).unwrap();
+        let (_, mut detection) = JarDetection::detect(&jar_path).unwrap();
+
+        detection.jar_size = Some(0);
+        detection.detected_game_version = Some("cached".to_string());
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.game_version, "cached");
+
+        detection.jar_size = Some(0);
+        detection.jar_sha1 = Some("outdated".to_string());
+        let info = detection.server_info(&jar_path, false).unwrap();
+        assert_eq!(info.game_version, "1.21.8");
+    }
+
+    #[test]
+    fn test_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        fs::copy("testdata/vanilla-1.21.8.jar", &jar_path).unwrap();
+        let (_, mut detection) = JarDetection::detect(&jar_path).unwrap();
+
+        detection.detected_game_version = Some("cached".to_string());
+        let info = detection.server_info(&jar_path, true).unwrap();
+        assert_eq!(info.game_version, "1.21.8");
+    }
+
+    #[test]
+    fn test_last_known() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("server.jar");
+        fs::copy("testdata/vanilla-1.21.8.jar", &jar_path).unwrap();
+        let (_, mut detection) = JarDetection::detect(&jar_path).unwrap();
+
+        fs::remove_file(&jar_path).unwrap();
+        assert!(detection.server_info(&jar_path, false).is_err());
+        let info = detection.last_known(&jar_path).unwrap();
+        assert_eq!(info.server_fork, ServerFork::Vanilla);
+        assert_eq!(info.game_version, "1.21.8");
+
+        // Nothing is known before the first detection
+        assert!(JarDetection::default().last_known(&jar_path).is_err());
+    }
+
+    #[test]
+    fn test_old_config_fork_alias() {
+        let detection: JarDetection = serde_json::from_str(r#"{"fork":"Forge"}"#).unwrap();
+        assert_eq!(detection.detected_fork, Some(ServerFork::Forge));
+        assert!(detection.cached().is_none());
+    }
+}
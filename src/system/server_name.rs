@@ -0,0 +1,272 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    ops::Deref,
+    path::{Component, Path},
+    str::FromStr,
+};
+
+/// The longest server name, well below the file name limits of common file systems.
+pub const MAX_LENGTH: usize = 64;
+/// Names Windows reserves for devices, also with any extension like `nul.txt`.
+const WINDOWS_RESERVED: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// The name of a server, which is also the name of its directory. Only ASCII letters,
+/// digits, '.', '_' and '-', so it can't point outside the instances directory or change
+/// when a file system normalizes unicode.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ServerName(String);
+
+#[derive(Debug, PartialEq)]
+pub enum InvalidServerNameError {
+    Empty,
+    TooLong(usize),
+    InvalidCharacter(String, char),
+    LeadingDot(String),
+    /// Windows drops trailing dots, so `smp.` would be the directory of `smp`.
+    TrailingDot(String),
+    Reserved(String),
+}
+
+impl Display for InvalidServerNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidServerNameError::Empty => write!(f, "The server name can't be empty"),
+            InvalidServerNameError::TooLong(len) => write!(
+                f,
+                "The server name is {len} characters long, the limit is {MAX_LENGTH}"
+            ),
+            InvalidServerNameError::InvalidCharacter(name, c) => write!(
+                f,
+                "Invalid server name '{name}': {c:?} isn't allowed, only ASCII letters, digits, '.', '_' and '-'"
+            ),
+            InvalidServerNameError::LeadingDot(name) => {
+                write!(f, "Invalid server name '{name}': it can't start with '.'")
+            }
+            InvalidServerNameError::TrailingDot(name) => {
+                write!(f, "Invalid server name '{name}': it can't end with '.'")
+            }
+            InvalidServerNameError::Reserved(name) => write!(
+                f,
+                "Invalid server name '{name}': Windows reserves it for a device"
+            ),
+        }
+    }
+}
+
+impl Error for InvalidServerNameError {}
+
+impl ServerName {
+    pub fn new(name: impl Into<String>) -> Result<Self, InvalidServerNameError> {
+        let name = name.into();
+        validate(&name)?;
+        Ok(Self(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Checks that the name is a valid [`ServerName`].
+pub fn validate(name: &str) -> Result<(), InvalidServerNameError> {
+    if name.is_empty() {
+        return Err(InvalidServerNameError::Empty);
+    }
+    let len = name.chars().count();
+    if len > MAX_LENGTH {
+        return Err(InvalidServerNameError::TooLong(len));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        return Err(InvalidServerNameError::InvalidCharacter(
+            name.to_string(),
+            c,
+        ));
+    }
+    // Also rules out `.` and `..`
+    if name.starts_with('.') {
+        return Err(InvalidServerNameError::LeadingDot(name.to_string()));
+    }
+    if name.ends_with('.') {
+        return Err(InvalidServerNameError::TrailingDot(name.to_string()));
+    }
+    if is_windows_reserved(name) {
+        return Err(InvalidServerNameError::Reserved(name.to_string()));
+    }
+
+    // What the rules above should already guarantee
+    let mut components = Path::new(name).components();
+    debug_assert!(
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ),
+        "{name} isn't a single directory name"
+    );
+    Ok(())
+}
+
+/// Whether Windows reserves the name for a device, ignoring its extension.
+pub(crate) fn is_windows_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name);
+    WINDOWS_RESERVED.contains(&stem.to_ascii_lowercase().as_str())
+}
+
+impl FromStr for ServerName {
+    type Err = InvalidServerNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Deref for ServerName {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for ServerName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for ServerName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<ServerName> for String {
+    fn from(name: ServerName) -> Self {
+        name.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid() {
+        for name in [
+            "smp",
+            "Survival-1.21",
+            "creative_2",
+            "a",
+            "smp.old",
+            "con-test",
+        ] {
+            assert_eq!(ServerName::new(name).unwrap().as_str(), name);
+        }
+        assert!(ServerName::new("a".repeat(MAX_LENGTH)).is_ok());
+        assert_eq!(
+            ServerName::new("a".repeat(MAX_LENGTH + 1)),
+            Err(InvalidServerNameError::TooLong(MAX_LENGTH + 1))
+        );
+        assert_eq!(ServerName::new(""), Err(InvalidServerNameError::Empty));
+    }
+
+    #[test]
+    fn test_traversal() {
+        for name in [
+            "..",
+            ".",
+            "../../etc/something",
+            "../smp",
+            "smp/../..",
+            "/etc",
+            "smp/world",
+            "..\\..\\windows",
+            "C:",
+            "C:\\smp",
+            ".hidden",
+            "smp\0",
+            " smp",
+            "smp\n",
+        ] {
+            assert!(ServerName::new(name).is_err(), "{name:?} was accepted");
+        }
+        assert_eq!(
+            ServerName::new("../../etc"),
+            Err(InvalidServerNameError::InvalidCharacter(
+                "../../etc".to_string(),
+                '/'
+            ))
+        );
+        assert_eq!(
+            ServerName::new(".."),
+            Err(InvalidServerNameError::LeadingDot("..".to_string()))
+        );
+        assert_eq!(
+            ServerName::new("smp."),
+            Err(InvalidServerNameError::TrailingDot("smp.".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_windows_reserved() {
+        for name in [
+            "CON",
+            "con",
+            "Nul",
+            "nul.txt",
+            "PRN",
+            "aux",
+            "COM1",
+            "lpt9",
+            "com1.tar.gz",
+        ] {
+            assert_eq!(
+                ServerName::new(name),
+                Err(InvalidServerNameError::Reserved(name.to_string())),
+            );
+        }
+        for name in ["console", "nullspace", "com10", "lpt"] {
+            assert!(ServerName::new(name).is_ok(), "{name} was rejected");
+        }
+    }
+
+    #[test]
+    fn test_unicode() {
+        for name in [
+            // Composed and decomposed é look the same, but are different directory names
+            "caf\u{e9}",
+            "cafe\u{301}",
+            // Fullwidth letters, which NFKC turns into "smp"
+            "\u{ff53}\u{ff4d}\u{ff50}",
+            // One dot leader twice, which NFKC turns into ".."
+            "\u{2024}\u{2024}",
+            // Fullwidth solidus
+            "smp\u{ff0f}..",
+            // Cyrillic "а" that looks like a Latin one
+            "\u{430}dmin",
+            // Right-to-left override
+            "smp\u{202e}rav",
+            // Zero-width space
+            "sm\u{200b}p",
+        ] {
+            assert!(
+                matches!(
+                    ServerName::new(name),
+                    Err(InvalidServerNameError::InvalidCharacter(..))
+                ),
+                "{name:?} was accepted"
+            );
+        }
+        // Counted in characters, not bytes
+        assert_eq!(
+            ServerName::new("\u{e9}".repeat(MAX_LENGTH + 1)),
+            Err(InvalidServerNameError::TooLong(MAX_LENGTH + 1))
+        );
+    }
+}
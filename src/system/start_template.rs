@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt::Display, str::FromStr};
+
+/// How servers start without a template of their own.
+pub const DEFAULT_TEMPLATE: &str = "{java} {xmx} {xms} {jvm_args} -jar {jar} nogui {game_args}";
+/// Replaced by one argument each, or part of one.
+const VALUE_PLACEHOLDERS: [&str; 4] = ["java", "xmx", "xms", "jar"];
+/// Replaced by any number of arguments, so they have to be words of their own.
+const LIST_PLACEHOLDERS: [&str; 2] = ["jvm_args", "game_args"];
+/// mcerv sets these itself, so a template that leaves them out would ignore `mcerv set`.
+const REQUIRED_PLACEHOLDERS: [&str; 3] = ["java", "xmx", "xms"];
+
+/// The command line that starts a server, split into arguments at whitespace, with
+/// `{java}`, `{xmx}`, `{xms}`, `{jvm_args}`, `{jar}` and `{game_args}` filled in by
+/// [`StartTemplate::render`]. Like `{java} {xmx} {xms} @libraries/.../unix_args.txt nogui`
+/// for Forge's argument files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct StartTemplate(String);
+
+#[derive(Debug, PartialEq)]
+pub enum StartTemplateError {
+    Unclosed,
+    UnknownPlaceholder(String),
+    MissingPlaceholder(&'static str),
+    RepeatedPlaceholder(String),
+    /// A list placeholder inside a word, like `-D{jvm_args}`.
+    NotAWord(String),
+}
+
+impl Display for StartTemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartTemplateError::Unclosed => write!(f, "A '{{' in the start template isn't closed"),
+            StartTemplateError::UnknownPlaceholder(name) => write!(
+                f,
+                "Unknown placeholder {{{name}}} in the start template, the known ones are {{java}}, {{xmx}}, {{xms}}, {{jvm_args}}, {{jar}} and {{game_args}}"
+            ),
+            StartTemplateError::MissingPlaceholder(name) => {
+                write!(f, "The start template needs {{{name}}}")
+            }
+            StartTemplateError::RepeatedPlaceholder(name) => {
+                write!(f, "{{{name}}} is in the start template more than once")
+            }
+            StartTemplateError::NotAWord(name) => write!(
+                f,
+                "{{{name}}} can be any number of arguments, so it has to be separated by spaces"
+            ),
+        }
+    }
+}
+
+impl Error for StartTemplateError {}
+
+/// What a [`StartTemplate`] is filled in with.
+pub struct StartValues<'a> {
+    pub java: &'a str,
+    pub max_memory: &'a str,
+    pub min_memory: &'a str,
+    pub jar: &'a str,
+    pub jvm_args: &'a [String],
+    pub game_args: &'a [String],
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+impl StartTemplate {
+    pub fn parse(template: &str) -> Result<Self, StartTemplateError> {
+        let mut seen = Vec::new();
+        for word in template.split_whitespace() {
+            let segments = segments(word)?;
+            for segment in &segments {
+                let Segment::Placeholder(name) = segment else {
+                    continue;
+                };
+                if !VALUE_PLACEHOLDERS.contains(name) && !LIST_PLACEHOLDERS.contains(name) {
+                    return Err(StartTemplateError::UnknownPlaceholder(name.to_string()));
+                }
+                if LIST_PLACEHOLDERS.contains(name) && segments.len() > 1 {
+                    return Err(StartTemplateError::NotAWord(name.to_string()));
+                }
+                if seen.contains(name) {
+                    return Err(StartTemplateError::RepeatedPlaceholder(name.to_string()));
+                }
+                seen.push(*name);
+            }
+        }
+        if let Some(missing) = REQUIRED_PLACEHOLDERS
+            .into_iter()
+            .find(|name| !seen.contains(name))
+        {
+            return Err(StartTemplateError::MissingPlaceholder(missing));
+        }
+
+        Ok(Self(template.to_string()))
+    }
+
+    /// The arguments of the command, the first being the program. `{xmx}` and `{xms}` are
+    /// the `-Xmx` and `-Xms` flags.
+    pub fn render(&self, values: &StartValues) -> Vec<String> {
+        let mut args = Vec::new();
+        for word in self.0.split_whitespace() {
+            // Checked when parsed
+            let segments = segments(word).unwrap();
+            match segments.as_slice() {
+                [Segment::Placeholder("jvm_args")] => args.extend_from_slice(values.jvm_args),
+                [Segment::Placeholder("game_args")] => args.extend_from_slice(values.game_args),
+                _ => args.push(
+                    segments
+                        .iter()
+                        .map(|segment| match segment {
+                            Segment::Literal(text) => text.to_string(),
+                            Segment::Placeholder("java") => values.java.to_string(),
+                            Segment::Placeholder("xmx") => format!("-Xmx{}", values.max_memory),
+                            Segment::Placeholder("xms") => format!("-Xms{}", values.min_memory),
+                            Segment::Placeholder(_) => values.jar.to_string(),
+                        })
+                        .collect(),
+                ),
+            }
+        }
+        args
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Splits the word into text and `{placeholder}`s.
+fn segments(word: &str) -> Result<Vec<Segment<'_>>, StartTemplateError> {
+    let mut segments = Vec::new();
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or(StartTemplateError::Unclosed)?;
+        segments.push(Segment::Placeholder(&rest[start + 1..start + end]));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    Ok(segments)
+}
+
+impl Default for StartTemplate {
+    fn default() -> Self {
+        Self(DEFAULT_TEMPLATE.to_string())
+    }
+}
+
+impl FromStr for StartTemplate {
+    type Err = StartTemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for StartTemplate {
+    type Error = StartTemplateError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<StartTemplate> for String {
+    fn from(template: StartTemplate) -> Self {
+        template.0
+    }
+}
+
+impl Display for StartTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'a>(jvm_args: &'a [String], game_args: &'a [String]) -> StartValues<'a> {
+        StartValues {
+            java: "/opt/jdk 21/bin/java",
+            max_memory: "4G",
+            min_memory: "2G",
+            jar: "my server.jar",
+            jvm_args,
+            game_args,
+        }
+    }
+
+    #[test]
+    fn test_default() {
+        let args = StartTemplate::default().render(&values(&[], &[]));
+        assert_eq!(
+            args,
+            [
+                "/opt/jdk 21/bin/java",
+                "-Xmx4G",
+                "-Xms2G",
+                "-jar",
+                "my server.jar",
+                "nogui"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let template = StartTemplate::parse(
+            "{java} {xmx} {xms} {jvm_args} @libraries/net/minecraftforge/forge/1.21.8-58.1.0/unix_args.txt nogui {game_args}",
+        )
+        .unwrap();
+        let jvm_args = [
+            "-XX:+UseG1GC".to_string(),
+            "--add-modules=jdk.incubator.vector".to_string(),
+        ];
+        let game_args = ["--port".to_string(), "25566".to_string()];
+        assert_eq!(
+            template.render(&values(&jvm_args, &game_args)),
+            [
+                "/opt/jdk 21/bin/java",
+                "-Xmx4G",
+                "-Xms2G",
+                "-XX:+UseG1GC",
+                "--add-modules=jdk.incubator.vector",
+                "@libraries/net/minecraftforge/forge/1.21.8-58.1.0/unix_args.txt",
+                "nogui",
+                "--port",
+                "25566"
+            ]
+        );
+
+        // Placeholders can be part of a word
+        let template = StartTemplate::parse("{java} {xmx} {xms} -Dmcerv.jar={jar}").unwrap();
+        assert_eq!(
+            template.render(&values(&[], &[]))[3],
+            "-Dmcerv.jar=my server.jar"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (template, error) in [
+            (
+                "{java} {xmx} {xms} {jvm} -jar {jar}",
+                StartTemplateError::UnknownPlaceholder("jvm".to_string()),
+            ),
+            (
+                "{java} {xmx} {XMS}",
+                StartTemplateError::UnknownPlaceholder("XMS".to_string()),
+            ),
+            (
+                "{java} {xmx} -jar {jar}",
+                StartTemplateError::MissingPlaceholder("xms"),
+            ),
+            (
+                "java -Xmx4G -Xms4G -jar server.jar",
+                StartTemplateError::MissingPlaceholder("java"),
+            ),
+            (
+                "{java} {xmx} {xms} {xmx}",
+                StartTemplateError::RepeatedPlaceholder("xmx".to_string()),
+            ),
+            (
+                "{java} {xmx} {xms} -jar {jar} {jar}",
+                StartTemplateError::RepeatedPlaceholder("jar".to_string()),
+            ),
+            (
+                "{java} {xmx} {xms} -D{jvm_args}",
+                StartTemplateError::NotAWord("jvm_args".to_string()),
+            ),
+            ("{java} {xmx} {xms", StartTemplateError::Unclosed),
+            ("", StartTemplateError::MissingPlaceholder("java")),
+        ] {
+            assert_eq!(StartTemplate::parse(template), Err(error), "{template}");
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let template: StartTemplate =
+            serde_json::from_str(r#""{java} {xmx} {xms} -jar {jar}""#).unwrap();
+        assert_eq!(template.as_str(), "{java} {xmx} {xms} -jar {jar}");
+        assert!(serde_json::from_str::<StartTemplate>(r#""{java} -jar {jar}""#).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use crate::system::{
+    filename::remove_if_exists,
+    notify::{Notification, Notifier},
+    process::{self, PidFile},
+    schedule::{self, RESTART_WARNINGS, ScheduledTime},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{io::AsyncWriteExt, process::Command, sync::mpsc};
+
+/// A server that stays up this long is considered healthy again, resetting the crash counter.
+const HEALTHY_UPTIME: Duration = Duration::from_secs(10 * 60);
+/// The longest time waited before restarting.
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// How long a server gets to exit after `stop` at a scheduled restart before it's terminated,
+/// and after that before it's killed.
+const SCHEDULED_STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How a crashed server is brought back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    /// The number of restarts allowed before giving up, counting only recent crashes.
+    pub max_retries: u32,
+    /// The wait before the first restart, doubled for each consecutive crash.
+    pub backoff: Duration,
+}
+
+impl RestartPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            backoff: Duration::from_secs(5),
+        }
+    }
+
+    /// The wait before restarting after the `crashes`th consecutive crash.
+    pub fn backoff_for(&self, crashes: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(crashes.saturating_sub(1)))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Written by `mcerv stop`, so the supervisor doesn't restart a server that was stopped on purpose.
+pub fn stop_sentinel_path(server_dir: &Path) -> PathBuf {
+    server_dir.join("mcerv.stop")
+}
+
+/// The newest crash report written after `since`.
+pub fn newest_crash_report(server_dir: &Path, since: SystemTime) -> Option<PathBuf> {
+    fs::read_dir(server_dir.join("crash-reports"))
+        .ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            (modified >= since).then(|| (modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Runs the server until it exits, tracking it in the pid file.
+///
+/// With a restart policy, the server is respawned after exiting with a non-zero status,
+/// unless `mcerv stop` was used. At the scheduled times, players are warned and the server
+/// is stopped and started again, terminated if it doesn't stop within
+/// [`SCHEDULED_STOP_TIMEOUT`]. Lines received on `console` are written to the server's stdin.
+/// Restarts after crashes are notified with `notify`, the server's name and its notifier.
+pub async fn supervise(
+    server_dir: &Path,
+    command: impl Fn() -> Command,
+    policy: Option<RestartPolicy>,
+    schedule: &[ScheduledTime],
+    console: Option<mpsc::UnboundedReceiver<String>>,
+    notify: Option<(&str, &Notifier)>,
+) -> anyhow::Result<ExitStatus> {
+    supervise_with(
+        server_dir,
+        command,
+        policy,
+        || scheduled_restart_events(schedule),
+        SCHEDULED_STOP_TIMEOUT,
+        console,
+        notify,
+    )
+    .await
+}
+
+/// Like [`supervise`], with the console lines of the next scheduled restart from
+/// `restart_events` for each run of the server.
+async fn supervise_with(
+    server_dir: &Path,
+    command: impl Fn() -> Command,
+    policy: Option<RestartPolicy>,
+    restart_events: impl Fn() -> Vec<(Instant, String)>,
+    stop_timeout: Duration,
+    mut console: Option<mpsc::UnboundedReceiver<String>>,
+    notify: Option<(&str, &Notifier)>,
+) -> anyhow::Result<ExitStatus> {
+    let sentinel_path = stop_sentinel_path(server_dir);
+    remove_if_exists(&sentinel_path)?;
+
+    let mut crashes = 0;
+    loop {
+        let mut events = restart_events().into_iter().peekable();
+        let mut command = command();
+        if console.is_some() || events.peek().is_some() {
+            command.stdin(Stdio::piped());
+        }
+
+        let started = Instant::now();
+        let started_at = SystemTime::now();
+        let mut child = command.spawn()?;
+        if let Some(pid_file) = child.id().and_then(PidFile::for_process) {
+            pid_file.save(server_dir)?;
+        }
+
+        let mut stdin = child.stdin.take();
+        let mut scheduled_stop = false;
+        // When the server is terminated, then killed, if it's still running after `stop`
+        let mut stop_deadline = None;
+        let status = loop {
+            let deadline = stop_deadline.map_or_else(Instant::now, |(at, _)| at);
+            let line = tokio::select! {
+                status = child.wait() => break status,
+                Some(line) = async { console.as_mut()?.recv().await }, if console.is_some() => line,
+                _ = sleep_until_next(events.peek()), if events.peek().is_some() => {
+                    let (_, line) = events.next().unwrap();
+                    if line == "stop" {
+                        println!("[mcerv] Scheduled restart");
+                        scheduled_stop = true;
+                        stop_deadline = Some((Instant::now() + stop_timeout, false));
+                    }
+                    line
+                }
+                _ = tokio::time::sleep_until(deadline.into()), if stop_deadline.is_some() => {
+                    let (_, terminated) = stop_deadline.unwrap();
+                    if terminated {
+                        println!("[mcerv] The server is still running, killing it");
+                        child.start_kill()?;
+                        stop_deadline = None;
+                    } else {
+                        println!("[mcerv] Not stopped in {stop_timeout:?}, terminating the server");
+                        // Only signals it, `wait` reports the exit
+                        if let Some(pid) = child.id() {
+                            process::terminate(pid, Duration::ZERO).await;
+                        }
+                        stop_deadline = Some((Instant::now() + stop_timeout, true));
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(stdin) = stdin.as_mut() {
+                // The server may be exiting, which `wait` reports
+                let _ = stdin.write_all(format!("{line}\n").as_bytes()).await;
+                let _ = stdin.flush().await;
+            }
+        };
+        PidFile::remove(server_dir)?;
+        let status = status?;
+
+        if sentinel_path.exists() {
+            remove_if_exists(&sentinel_path)?;
+            return Ok(status);
+        }
+        if scheduled_stop {
+            crashes = 0;
+            continue;
+        }
+
+        let Some(policy) = policy else {
+            return Ok(status);
+        };
+        if status.success() {
+            return Ok(status);
+        }
+
+        if started.elapsed() >= HEALTHY_UPTIME {
+            crashes = 0;
+        }
+        crashes += 1;
+
+        println!("[mcerv] Server crashed with {status}");
+        if let Some(report) = newest_crash_report(server_dir, started_at) {
+            println!("[mcerv] Crash report: {}", report.display());
+        }
+        if crashes > policy.max_retries {
+            println!("[mcerv] Giving up after {} restarts", policy.max_retries);
+            return Ok(status);
+        }
+
+        let backoff = policy.backoff_for(crashes);
+        println!(
+            "[mcerv] Restarting in {backoff:?} ({crashes}/{})",
+            policy.max_retries
+        );
+        if let Some((server_name, notifier)) = notify {
+            // A slow webhook mustn't hold up the restart
+            let notifier = notifier.clone();
+            let notification = Notification::Crash {
+                server: server_name.to_string(),
+                status: status.to_string(),
+                restart: crashes,
+                max_retries: policy.max_retries,
+            };
+            tokio::spawn(async move { notifier.notify(&notification).await });
+        }
+        if wait_for_sentinel(&sentinel_path, backoff).await {
+            remove_if_exists(&sentinel_path)?;
+            return Ok(status);
+        }
+    }
+}
+
+/// The console lines sent for the next scheduled restart, with when to send them:
+/// the warnings that are still ahead, then `stop`.
+fn scheduled_restart_events(schedule: &[ScheduledTime]) -> Vec<(Instant, String)> {
+    let Some(until) = schedule::until_next(schedule) else {
+        return Vec::new();
+    };
+    let restart_at = Instant::now() + until;
+
+    let mut events: Vec<_> = RESTART_WARNINGS
+        .iter()
+        .filter(|(before, _)| *before < until)
+        .map(|(before, remaining)| {
+            (
+                restart_at - *before,
+                format!("say Server restarting in {remaining}"),
+            )
+        })
+        .collect();
+    events.push((restart_at, "stop".to_string()));
+    events
+}
+
+async fn sleep_until_next(event: Option<&(Instant, String)>) {
+    if let Some((at, _)) = event {
+        tokio::time::sleep_until((*at).into()).await;
+    }
+}
+
+/// Waits for `timeout`, returning early with `true` if the stop sentinel appears.
+async fn wait_for_sentinel(sentinel_path: &Path, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if sentinel_path.exists() {
+            return true;
+        }
+        tokio::time::sleep(timeout.min(Duration::from_millis(500))).await;
+    }
+    sentinel_path.exists()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn policy(max_retries: u32) -> RestartPolicy {
+        RestartPolicy {
+            max_retries,
+            backoff: Duration::from_millis(10),
+        }
+    }
+
+    /// A command that appends a line to `runs.txt` and runs `script`.
+    fn counted(dir: &Path, script: &str) -> impl Fn() -> Command {
+        let dir = dir.to_path_buf();
+        let script = format!("echo run >> runs.txt; {script}");
+        move || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&script).current_dir(&dir);
+            command
+        }
+    }
+
+    fn runs(dir: &Path) -> usize {
+        fs::read_to_string(dir.join("runs.txt"))
+            .unwrap()
+            .lines()
+            .count()
+    }
+
+    #[test]
+    fn test_backoff_for() {
+        let policy = RestartPolicy::new(5);
+        assert_eq!(policy.backoff_for(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff_for(3), Duration::from_secs(20));
+        assert_eq!(policy.backoff_for(30), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_until_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = supervise(
+            dir.path(),
+            counted(dir.path(), "exit 1"),
+            Some(policy(2)),
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(runs(dir.path()), 3);
+        assert!(!PidFile::path(dir.path()).exists());
+    }
+
+    #[tokio::test]
+    async fn test_clean_exit_is_not_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = supervise(
+            dir.path(),
+            counted(dir.path(), "exit 0"),
+            Some(policy(2)),
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(status.success());
+        assert_eq!(runs(dir.path()), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stop_sentinel_prevents_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        // Like `mcerv stop`, write the sentinel before the server exits with an error
+        let script = "touch mcerv.stop; exit 143";
+        let status = supervise(
+            dir.path(),
+            counted(dir.path(), script),
+            Some(policy(2)),
+            &[],
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status.code(), Some(143));
+        assert_eq!(runs(dir.path()), 1);
+        assert!(!stop_sentinel_path(dir.path()).exists());
+    }
+
+    #[tokio::test]
+    async fn test_console_reaches_restarted_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let (commands, received) = mpsc::unbounded_channel();
+        // Echoes a line from stdin, crashing the first time
+        let script = "read line; echo $line >> echoed.txt; [ $(wc -l < runs.txt) -gt 1 ]";
+
+        commands.send("first".to_string()).unwrap();
+        let supervisor = supervise(
+            dir.path(),
+            counted(dir.path(), script),
+            Some(policy(1)),
+            &[],
+            Some(received),
+            None,
+        );
+        let sender = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            commands.send("second".to_string()).unwrap();
+        };
+        let (status, _) = tokio::join!(supervisor, sender);
+
+        assert!(status.unwrap().success());
+        let echoed = fs::read_to_string(dir.path().join("echoed.txt")).unwrap();
+        assert_eq!(echoed, "first\nsecond\n");
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_restart_terminates_hung_server() {
+        let dir = tempfile::tempdir().unwrap();
+        // Ignores `stop` the first time, and exits on its own the second
+        let script = "[ $(wc -l < runs.txt) -gt 1 ] && exit 0; exec sleep 30";
+        let restarted = std::cell::Cell::new(false);
+        let restart_events = || {
+            if restarted.replace(true) {
+                return Vec::new();
+            }
+            let at = Instant::now() + Duration::from_millis(100);
+            vec![
+                (at, "say Server restarting in 10 seconds".to_string()),
+                (at, "stop".to_string()),
+            ]
+        };
+
+        let started = Instant::now();
+        let status = supervise_with(
+            dir.path(),
+            counted(dir.path(), script),
+            None,
+            restart_events,
+            Duration::from_millis(200),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(status.success());
+        assert_eq!(runs(dir.path()), 2);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!PidFile::path(dir.path()).exists());
+    }
+
+    #[test]
+    fn test_newest_crash_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let since = SystemTime::now() - Duration::from_secs(1);
+        assert_eq!(newest_crash_report(dir.path(), since), None);
+
+        let reports = dir.path().join("crash-reports");
+        fs::create_dir(&reports).unwrap();
+        fs::write(reports.join("crash-1.txt"), "").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(reports.join("crash-2.txt"), "").unwrap();
+
+        assert_eq!(
+            newest_crash_report(dir.path(), since),
+            Some(reports.join("crash-2.txt"))
+        );
+        assert_eq!(
+            newest_crash_report(dir.path(), SystemTime::now() + Duration::from_secs(60)),
+            None
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::system::start_template::StartTemplate;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The `server.properties` keys a template keeps. World specific ones like `level-seed`
+/// are left out, so every server made from the template gets its own world.
+pub const TEMPLATE_PROPERTIES: [&str; 12] = [
+    "server-port",
+    "motd",
+    "max-players",
+    "difficulty",
+    "gamemode",
+    "hardcore",
+    "pvp",
+    "online-mode",
+    "white-list",
+    "view-distance",
+    "simulation-distance",
+    "spawn-protection",
+];
+
+/// The mod version that resolves to the newest one compatible with the server.
+pub const LATEST_MOD_VERSION: &str = "latest";
+
+/// A server saved by `template save`, to install copies of it with `template apply`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Template {
+    pub fork: String,
+    /// The version arguments of `install <fork>`.
+    pub versions: Vec<String>,
+    pub min_memory: String,
+    pub max_memory: String,
+    pub java_home: Option<String>,
+    /// Like the start template of the server's config, Forge's own if it's `None`.
+    #[serde(default)]
+    pub start_command_template: Option<StartTemplate>,
+    #[serde(default)]
+    pub jvm_args: Vec<String>,
+    #[serde(default)]
+    pub game_args: Vec<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+    #[serde(default)]
+    pub mods: Vec<TemplateMod>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TemplateMod {
+    pub slug: String,
+    /// A Modrinth version ID, or [`LATEST_MOD_VERSION`].
+    pub version: String,
+}
+
+impl TemplateMod {
+    pub fn is_latest(&self) -> bool {
+        self.version == LATEST_MOD_VERSION
+    }
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    InvalidName(String),
+    AlreadyExists(String),
+    NotFound(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::InvalidName(name) => write!(
+                f,
+                "Invalid template name: {name}. Use letters, digits, '-' and '_' only"
+            ),
+            TemplateError::AlreadyExists(name) => write!(
+                f,
+                "Template {name} already exists. Delete it first to replace it"
+            ),
+            TemplateError::NotFound(name) => write!(f, "Template {name} does not exist"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+fn template_path(dir: &Path, name: &str) -> Result<PathBuf, TemplateError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(TemplateError::InvalidName(name.to_string()));
+    }
+
+    Ok(dir.join(format!("{name}.json")))
+}
+
+impl Template {
+    pub fn save(&self, dir: &Path, name: &str) -> anyhow::Result<()> {
+        let path = template_path(dir, name)?;
+        if path.exists() {
+            return Err(TemplateError::AlreadyExists(name.to_string()).into());
+        }
+
+        fs::create_dir_all(dir)?;
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    pub fn load(dir: &Path, name: &str) -> anyhow::Result<Self> {
+        let path = template_path(dir, name)?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(TemplateError::NotFound(name.to_string()).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+pub fn delete(dir: &Path, name: &str) -> anyhow::Result<()> {
+    let path = template_path(dir, name)?;
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(TemplateError::NotFound(name.to_string()).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The names of the saved templates, sorted.
+pub fn names(dir: &Path) -> io::Result<Vec<String>> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut names = Vec::new();
+    for entry in read_dir {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            names.push(path.file_stem().unwrap().to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+/// The first port from `port` up that no other server is configured with.
+pub fn next_free_port(port: u16, used: &[u16]) -> u16 {
+    (port..=u16::MAX)
+        .find(|port| !used.contains(port))
+        .unwrap_or(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template {
+            fork: "Fabric".to_string(),
+            versions: vec!["1.21.8".into(), "0.16.14".into(), "1.0.3".into()],
+            min_memory: "6G".to_string(),
+            max_memory: "6G".to_string(),
+            java_home: None,
+            start_command_template: None,
+            jvm_args: vec!["-XX:+UseZGC".to_string()],
+            game_args: vec!["--nogui".to_string()],
+            properties: BTreeMap::from([("server-port".to_string(), "25600".to_string())]),
+            mods: vec![
+                TemplateMod {
+                    slug: "lithium".to_string(),
+                    version: LATEST_MOD_VERSION.to_string(),
+                },
+                TemplateMod {
+                    slug: "ferrite-core".to_string(),
+                    version: "CtMpt7Jr".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_save_load_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates_dir = dir.path().join("templates");
+        assert!(names(&templates_dir).unwrap().is_empty());
+
+        template().save(&templates_dir, "fabric-perf").unwrap();
+        assert!(template().save(&templates_dir, "fabric-perf").is_err());
+        assert_eq!(names(&templates_dir).unwrap(), ["fabric-perf"]);
+
+        let loaded = Template::load(&templates_dir, "fabric-perf").unwrap();
+        assert_eq!(loaded, template());
+        assert!(loaded.mods[0].is_latest());
+        assert!(!loaded.mods[1].is_latest());
+
+        delete(&templates_dir, "fabric-perf").unwrap();
+        assert!(Template::load(&templates_dir, "fabric-perf").is_err());
+        assert!(delete(&templates_dir, "fabric-perf").is_err());
+    }
+
+    #[test]
+    fn test_load_older_template() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("vanilla.json"),
+            r#"{"fork": "Vanilla", "versions": ["1.21.8"], "min_memory": "2G", "max_memory": "4G", "java_home": null}"#,
+        )
+        .unwrap();
+        let loaded = Template::load(dir.path(), "vanilla").unwrap();
+        assert_eq!(loaded.start_command_template, None);
+        assert!(loaded.jvm_args.is_empty() && loaded.game_args.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["", "../escape", "a/b", "a.b"] {
+            let result = template().save(dir.path(), name);
+            assert!(matches!(
+                result.unwrap_err().downcast_ref(),
+                Some(TemplateError::InvalidName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_next_free_port() {
+        assert_eq!(next_free_port(25600, &[]), 25600);
+        assert_eq!(next_free_port(25600, &[25565, 25600, 25601]), 25602);
+    }
+}
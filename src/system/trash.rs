@@ -0,0 +1,455 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Where replaced jars go instead of being deleted, one directory per operation.
+pub const TRASH_DIR: &str = ".mcerv-trash";
+/// How many operations can be undone. Older batches are purged.
+pub const MAX_BATCHES: usize = 5;
+/// Older batches are purged once the trash is bigger than this, though the latest one is
+/// always kept.
+pub const MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+const MANIFEST_FILE: &str = "batch.json";
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct BatchManifest {
+    operation: String,
+    trashed: Vec<TrashedFile>,
+    /// Files the operation created, removed again when it's undone.
+    added: Vec<PathBuf>,
+    /// The `jar_name` of the config before the operation changed it.
+    previous_jar_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TrashedFile {
+    /// The name in the batch directory.
+    name: String,
+    /// Relative to the server directory, like `mods/lithium-0.17.1.jar`.
+    original: PathBuf,
+}
+
+/// What one operation moved out of the server directory, restorable with `undo`.
+/// Shared by the tasks of the operation, so it can be written to concurrently.
+pub struct TrashBatch {
+    server_dir: PathBuf,
+    dir: PathBuf,
+    manifest: Mutex<BatchManifest>,
+}
+
+/// What undoing a batch changed.
+#[derive(Debug, PartialEq)]
+pub struct UndoneBatch {
+    pub operation: String,
+    pub restored: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub previous_jar_name: Option<String>,
+}
+
+impl TrashBatch {
+    /// Starts the batch of an operation. Nothing is written until something is recorded.
+    pub fn begin(server_dir: &Path, operation: &str) -> Self {
+        let trash_dir = server_dir.join(TRASH_DIR);
+        // Named by the time, so they sort by age
+        let name = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+        let mut dir = trash_dir.join(&name);
+        let mut i = 1;
+        while dir.exists() {
+            dir = trash_dir.join(format!("{name}-{i}"));
+            i += 1;
+        }
+
+        Self {
+            server_dir: server_dir.to_path_buf(),
+            dir,
+            manifest: Mutex::new(BatchManifest {
+                operation: operation.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Moves the file into the batch.
+    pub fn trash(&self, path: &Path) -> io::Result<()> {
+        let mut manifest = self.manifest.lock().unwrap();
+        fs::create_dir_all(&self.dir)?;
+
+        let name = format!(
+            "{}-{}",
+            manifest.trashed.len(),
+            path.file_name().unwrap().to_string_lossy()
+        );
+        fs::rename(path, self.dir.join(&name))?;
+        manifest.trashed.push(TrashedFile {
+            name,
+            original: self.relative(path),
+        });
+
+        self.save(&manifest)
+    }
+
+    /// Moves the file trashed from `path` back, for an operation that failed halfway.
+    /// Returns `false` if it wasn't trashed.
+    pub fn restore(&self, path: &Path) -> io::Result<bool> {
+        let mut manifest = self.manifest.lock().unwrap();
+        let original = self.relative(path);
+        let Some(i) = manifest
+            .trashed
+            .iter()
+            .rposition(|file| file.original == original)
+        else {
+            return Ok(false);
+        };
+
+        fs::rename(self.dir.join(&manifest.trashed[i].name), path)?;
+        manifest.trashed.remove(i);
+        self.save(&manifest)?;
+        Ok(true)
+    }
+
+    /// Records a file the operation created, so undoing it removes the file.
+    pub fn record_added(&self, path: &Path) -> io::Result<()> {
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.added.push(self.relative(path));
+        self.save(&manifest)
+    }
+
+    /// Records the config's jar name before the operation replaced the jar.
+    pub fn record_jar_name(&self, jar_name: &str) -> io::Result<()> {
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.previous_jar_name = Some(jar_name.to_string());
+        self.save(&manifest)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.manifest.lock().unwrap().trashed.is_empty()
+    }
+
+    /// The name of the batch directory in the trash, which [`take`] finds it by.
+    pub fn name(&self) -> String {
+        self.dir.file_name().unwrap().to_string_lossy().to_string()
+    }
+
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.server_dir)
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+
+    // Saved after every change, so an interrupted operation can still be undone
+    fn save(&self, manifest: &BatchManifest) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string_pretty(manifest)?;
+        fs::write(self.dir.join(MANIFEST_FILE), content)
+    }
+}
+
+/// The batch directories of the server, oldest first.
+fn batches(server_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let read_dir = match fs::read_dir(server_dir.join(TRASH_DIR)) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut batches = Vec::new();
+    for entry in read_dir {
+        let path = entry?.path();
+        if path.join(MANIFEST_FILE).is_file() {
+            batches.push(path);
+        }
+    }
+    batches.sort();
+
+    Ok(batches)
+}
+
+/// Restores the files of the latest batch and removes the ones the operation added.
+/// Returns `None` if the trash is empty.
+pub fn undo(server_dir: &Path) -> anyhow::Result<Option<UndoneBatch>> {
+    let Some(dir) = batches(server_dir)?.pop() else {
+        return Ok(None);
+    };
+    let manifest: BatchManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE))?)?;
+
+    // Removed first, since an added file may have replaced a trashed one of the same name
+    let mut removed = Vec::new();
+    for path in manifest.added {
+        match fs::remove_file(server_dir.join(&path)) {
+            Ok(()) => removed.push(path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut restored = Vec::new();
+    for file in manifest.trashed.into_iter().rev() {
+        let original = server_dir.join(&file.original);
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(dir.join(&file.name), original)?;
+        restored.push(file.original);
+    }
+    restored.reverse();
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(Some(UndoneBatch {
+        operation: manifest.operation,
+        restored,
+        removed,
+        previous_jar_name: manifest.previous_jar_name,
+    }))
+}
+
+/// Where the file named `file_name` was trashed from by the batch, if it's still there.
+pub fn find(server_dir: &Path, batch: &str, file_name: &str) -> anyhow::Result<Option<PathBuf>> {
+    let Some((dir, manifest)) = load_batch(server_dir, batch)? else {
+        return Ok(None);
+    };
+    Ok(position(&dir, &manifest, file_name).map(|i| manifest.trashed[i].original.clone()))
+}
+
+/// Moves the file named `file_name` the batch trashed back to where it was, leaving the
+/// rest of the batch. Returns the path it's back at, or `None` if it isn't in the batch.
+pub fn take(server_dir: &Path, batch: &str, file_name: &str) -> anyhow::Result<Option<PathBuf>> {
+    let Some((dir, mut manifest)) = load_batch(server_dir, batch)? else {
+        return Ok(None);
+    };
+    let Some(i) = position(&dir, &manifest, file_name) else {
+        return Ok(None);
+    };
+
+    let file = manifest.trashed.remove(i);
+    let original = server_dir.join(&file.original);
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(dir.join(&file.name), &original)?;
+    // Undoing the batch mustn't remove the file it's restored as
+    manifest.added.retain(|added| *added != file.original);
+    fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(Some(original))
+}
+
+fn load_batch(server_dir: &Path, batch: &str) -> anyhow::Result<Option<(PathBuf, BatchManifest)>> {
+    let dir = server_dir.join(TRASH_DIR).join(batch);
+    let content = match fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some((dir, serde_json::from_str(&content)?)))
+}
+
+/// The latest file named `file_name` the batch trashed that is still in its directory.
+fn position(dir: &Path, manifest: &BatchManifest, file_name: &str) -> Option<usize> {
+    manifest.trashed.iter().rposition(|file| {
+        file.original
+            .file_name()
+            .is_some_and(|name| name == file_name)
+            && dir.join(&file.name).is_file()
+    })
+}
+
+/// Removes the oldest batches beyond `max_batches`, or while the trash is bigger than
+/// `max_size`. The latest batch is kept regardless of its size.
+pub fn prune(server_dir: &Path, max_batches: usize, max_size: u64) -> io::Result<()> {
+    let batches = batches(server_dir)?;
+    let sizes = batches
+        .iter()
+        .map(|dir| dir_size(dir))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut count = batches.len();
+    let mut size: u64 = sizes.iter().sum();
+    for (dir, batch_size) in batches.iter().zip(sizes) {
+        if count <= 1 || (count <= max_batches && size <= max_size) {
+            break;
+        }
+        fs::remove_dir_all(dir)?;
+        count -= 1;
+        size -= batch_size;
+    }
+
+    Ok(())
+}
+
+/// Deletes the whole trash, returning how many bytes it freed.
+pub fn purge(server_dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for dir in batches(server_dir)? {
+        size += dir_size(&dir)?;
+    }
+
+    match fs::remove_dir_all(server_dir.join(TRASH_DIR)) {
+        Ok(()) => Ok(size),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        size += entry?.metadata()?.len();
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// The files of the server directory and their content, without the trash.
+    fn snapshot(server_dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        let mut dirs = vec![server_dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.ends_with(TRASH_DIR) {
+                    continue;
+                }
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let relative = path.strip_prefix(server_dir).unwrap().to_path_buf();
+                    files.insert(relative, fs::read(&path).unwrap());
+                }
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn test_update_then_undo_restores_prior_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let server_dir = dir.path();
+        let mods_dir = server_dir.join("mods");
+        fs::create_dir(&mods_dir).unwrap();
+        fs::write(server_dir.join("server-1.21.7.jar"), b"old server").unwrap();
+        fs::write(mods_dir.join("lithium-0.17.1.jar"), b"old lithium").unwrap();
+        // Updated to a jar of the same name
+        fs::write(mods_dir.join("sodium.jar"), b"old sodium").unwrap();
+        let before = snapshot(server_dir);
+
+        let batch = TrashBatch::begin(server_dir, "update");
+        batch.record_jar_name("server-1.21.7.jar").unwrap();
+        batch.trash(&server_dir.join("server-1.21.7.jar")).unwrap();
+        fs::write(server_dir.join("server-1.21.8.jar"), b"new server").unwrap();
+        batch
+            .record_added(&server_dir.join("server-1.21.8.jar"))
+            .unwrap();
+
+        batch.trash(&mods_dir.join("lithium-0.17.1.jar")).unwrap();
+        fs::write(mods_dir.join("lithium-0.18.0.jar"), b"new lithium").unwrap();
+        batch
+            .record_added(&mods_dir.join("lithium-0.18.0.jar"))
+            .unwrap();
+
+        batch.trash(&mods_dir.join("sodium.jar")).unwrap();
+        fs::write(mods_dir.join("sodium.jar"), b"new sodium").unwrap();
+        batch.record_added(&mods_dir.join("sodium.jar")).unwrap();
+        assert_ne!(snapshot(server_dir), before);
+
+        let undone = undo(server_dir).unwrap().unwrap();
+
+        assert_eq!(snapshot(server_dir), before);
+        assert_eq!(undone.operation, "update");
+        assert_eq!(
+            undone.previous_jar_name.as_deref(),
+            Some("server-1.21.7.jar")
+        );
+        assert_eq!(undone.restored.len(), 3);
+        assert!(undo(server_dir).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("mods").join("lithium-0.17.0.jar");
+        fs::create_dir_all(jar.parent().unwrap()).unwrap();
+        fs::write(&jar, b"old").unwrap();
+
+        let batch = TrashBatch::begin(dir.path(), "update");
+        batch.trash(&jar).unwrap();
+        assert!(batch.restore(&jar).unwrap());
+        assert_eq!(fs::read(&jar).unwrap(), b"old");
+        assert!(batch.is_empty());
+        assert!(!batch.restore(&jar).unwrap());
+    }
+
+    #[test]
+    fn test_undo_latest_batch_only() {
+        let dir = tempfile::tempdir().unwrap();
+        for content in ["first", "second"] {
+            let path = dir.path().join(format!("{content}.jar"));
+            fs::write(&path, content).unwrap();
+            TrashBatch::begin(dir.path(), content).trash(&path).unwrap();
+        }
+
+        let undone = undo(dir.path()).unwrap().unwrap();
+        assert_eq!(undone.operation, "second");
+        assert!(dir.path().join("second.jar").exists());
+        assert!(!dir.path().join("first.jar").exists());
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            let path = dir.path().join(format!("{i}.jar"));
+            fs::write(&path, vec![0; 100]).unwrap();
+            TrashBatch::begin(dir.path(), "update")
+                .trash(&path)
+                .unwrap();
+        }
+
+        prune(dir.path(), 3, u64::MAX).unwrap();
+        assert_eq!(batches(dir.path()).unwrap().len(), 3);
+
+        // The latest batch is kept even if it alone is too big
+        prune(dir.path(), 3, 50).unwrap();
+        assert_eq!(batches(dir.path()).unwrap().len(), 1);
+        assert_eq!(
+            undo(dir.path()).unwrap().unwrap().restored,
+            [Path::new("3.jar")]
+        );
+    }
+
+    #[test]
+    fn test_purge() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(purge(dir.path()).unwrap(), 0);
+
+        let path = dir.path().join("old.jar");
+        fs::write(&path, b"old").unwrap();
+        TrashBatch::begin(dir.path(), "update")
+            .trash(&path)
+            .unwrap();
+
+        assert!(purge(dir.path()).unwrap() >= 3);
+        assert!(!dir.path().join(TRASH_DIR).exists());
+        assert!(undo(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_empty_batch_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let batch = TrashBatch::begin(dir.path(), "update");
+        assert!(batch.is_empty());
+        assert!(!dir.path().join(TRASH_DIR).exists());
+    }
+}
@@ -0,0 +1,391 @@
+use crate::{
+    network::modrinth::{self, ModVersion, Project},
+    system::{
+        jar_parser::{self, FileHash, HashAlgorithm},
+        mods::{self, InstalledJar, ModStatus},
+        pins::{Pins, VersionConstraint},
+    },
+};
+use reqwest::Client;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A jar of the mods directory, hashed for looking it up on Modrinth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledMod {
+    pub path: PathBuf,
+    /// With each of [`modrinth::LOOKUP_ALGORITHMS`].
+    pub hashes: Vec<FileHash>,
+    pub size: u64,
+}
+
+impl InstalledMod {
+    pub fn file_name(&self) -> String {
+        self.path.file_name().unwrap().to_string_lossy().to_string()
+    }
+
+    pub fn sha1(&self) -> Option<&str> {
+        self.hashes
+            .iter()
+            .find(|hash| hash.algorithm == HashAlgorithm::Sha1)
+            .map(|hash| hash.value.as_str())
+    }
+}
+
+/// The jars of the mods directory and of its directory for the game version, see
+/// [`mods::mod_jars`].
+pub fn scan_mods(mods_dir: &Path, game_version: &str) -> io::Result<Vec<InstalledMod>> {
+    mods::mod_jars(mods_dir, game_version)?
+        .into_iter()
+        .map(|path| {
+            let hashes = jar_parser::calculate_hashes(
+                &mut File::open(&path)?,
+                &modrinth::LOOKUP_ALGORITHMS,
+            )?;
+            let size = fs::metadata(&path)?.len();
+            Ok(InstalledMod { path, hashes, size })
+        })
+        .collect()
+}
+
+/// An installed mod with what Modrinth knows about it.
+#[derive(Debug)]
+pub struct PlannedMod {
+    pub installed: InstalledMod,
+    /// The project slug, or the file name for jars Modrinth doesn't know.
+    pub slug: String,
+    /// The installed version, `None` for a jar Modrinth doesn't know.
+    pub version: Option<ModVersion>,
+    /// The latest version for the game version, within the constraint if it's pinned.
+    pub latest: Option<ModVersion>,
+    pub constraint: Option<VersionConstraint>,
+    pub status: ModStatus,
+}
+
+impl PlannedMod {
+    /// The version the mod would be updated to, whose files have the URLs downloaded.
+    pub fn update(&self) -> Option<&ModVersion> {
+        match self.status {
+            ModStatus::UpdateAvailable(_) => self.latest.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// The mods of a server and their updates, checked once and shared by listing, updating,
+/// notifying and watching.
+#[derive(Debug, Default)]
+pub struct ModUpdatePlan {
+    pub mods: Vec<PlannedMod>,
+    /// The installed versions' projects, by ID. Projects gone from Modrinth are missing.
+    pub projects: HashMap<String, Project>,
+}
+
+impl ModUpdatePlan {
+    /// The mods with an update and the versions they'd be updated to.
+    pub fn updates(&self) -> impl Iterator<Item = (&PlannedMod, &ModVersion)> {
+        self.mods
+            .iter()
+            .filter_map(|planned| Some((planned, planned.update()?)))
+    }
+
+    /// The mods with an update, as `slug version` lines.
+    pub fn update_lines(&self) -> Vec<String> {
+        self.updates()
+            .map(|(planned, update)| format!("{} {}", planned.slug, update.version_name))
+            .collect()
+    }
+
+    /// The mods Modrinth knows, for finding the ones installed more than once.
+    pub fn installed_jars(&self) -> Vec<InstalledJar> {
+        self.mods
+            .iter()
+            .filter_map(|planned| {
+                Some(InstalledJar {
+                    path: planned.installed.path.clone(),
+                    project_id: planned.version.as_ref()?.project_id.clone(),
+                    up_to_date: planned.update().is_none(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Looks the mods up on Modrinth and decides what each would be updated to. Pinned mods are
+/// only updated within their constraint.
+pub async fn check_updates(
+    client: &Client,
+    mods: Vec<InstalledMod>,
+    game_version: &str,
+    loader: &str,
+    pins: &Pins,
+) -> anyhow::Result<ModUpdatePlan> {
+    check_updates_from(client, modrinth::API_URL, mods, game_version, loader, pins).await
+}
+
+async fn check_updates_from(
+    client: &Client,
+    api_url: &str,
+    mods: Vec<InstalledMod>,
+    game_version: &str,
+    loader: &str,
+    pins: &Pins,
+) -> anyhow::Result<ModUpdatePlan> {
+    if mods.is_empty() {
+        return Ok(ModUpdatePlan::default());
+    }
+
+    let jar_hashes = mods
+        .iter()
+        .map(|installed| installed.hashes.clone())
+        .collect::<Vec<_>>();
+    let game_versions = [game_version];
+    let (latest_versions, versions) = tokio::join!(
+        modrinth::find_latest_versions_from(client, api_url, &jar_hashes, &game_versions),
+        modrinth::find_versions_from(client, api_url, &jar_hashes)
+    );
+    let (latest_versions, versions) = (latest_versions?, versions?);
+
+    let projects = modrinth::get_projects_from(
+        client,
+        api_url,
+        versions.iter().flatten().map(|v| v.project_id.as_str()),
+    )
+    .await?;
+
+    let mut planned_mods = Vec::new();
+    for ((installed, version), mut latest) in mods.into_iter().zip(versions).zip(latest_versions) {
+        let slug = match &version {
+            Some(version) => modrinth::project_slug(&projects, &version.project_id).to_string(),
+            None => installed.file_name(),
+        };
+        let constraint = version
+            .as_ref()
+            .and_then(|_| pins.constraint(&slug))
+            .cloned();
+        if let (Some(version), Some(constraint)) = (&version, &constraint) {
+            // The newest version in the range instead of the latest
+            latest = modrinth::compatible_versions_from(
+                client,
+                api_url,
+                &version.project_id,
+                loader,
+                game_version,
+            )
+            .await?
+            .into_iter()
+            .find(|version| constraint.matches(&version.version_number));
+        }
+
+        let status = match (&version, &latest) {
+            (None, _) => ModStatus::Unknown,
+            (Some(_), Some(latest)) if !latest.has_file_with(&installed.hashes) => {
+                ModStatus::UpdateAvailable(latest.version_name.clone())
+            }
+            (Some(version), _) => match projects.get(&version.project_id) {
+                Some(project) if project.is_archived() => {
+                    ModStatus::NotUpdatable("archived".to_string())
+                }
+                Some(_) => ModStatus::UpToDate,
+                None => ModStatus::NotUpdatable("gone from Modrinth".to_string()),
+            },
+        };
+        planned_mods.push(PlannedMod {
+            installed,
+            slug,
+            version,
+            latest,
+            constraint,
+            status,
+        });
+    }
+
+    Ok(ModUpdatePlan {
+        mods: planned_mods,
+        projects,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::pins::Pin;
+    use mockito::{Matcher, Server};
+
+    fn installed(name: &str) -> InstalledMod {
+        InstalledMod {
+            path: PathBuf::from(format!("mods/{name}.jar")),
+            hashes: vec![
+                FileHash {
+                    algorithm: HashAlgorithm::Sha512,
+                    value: format!("{name}-sha512"),
+                },
+                FileHash {
+                    algorithm: HashAlgorithm::Sha1,
+                    value: format!("{name}-sha1"),
+                },
+            ],
+            size: 1024,
+        }
+    }
+
+    fn version(project_id: &str, name: &str, number: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": format!("{name}-id"),
+            "project_id": project_id,
+            "name": name,
+            "version_number": number,
+            "files": [{
+                "hashes": { "sha1": format!("{name}-sha1"), "sha512": format!("{name}-sha512") },
+                "url": format!("https://cdn.modrinth.com/data/{project_id}/{name}.jar"),
+                "filename": format!("{name}.jar"),
+                "primary": true,
+            }]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_check_updates() {
+        let mut server = Server::new_async().await;
+        let installed_hashes = serde_json::json!({
+            "sodium-0.6.13-sha512": version("AANobbMI", "sodium-0.6.13", "0.6.13"),
+            "lithium-0.18.0-sha512": version("gvQqBUqZ", "lithium-0.18.0", "0.18.0"),
+            "fabric-api-0.128.0-sha512": version("P7dR8mSH", "fabric-api-0.128.0", "0.128.0"),
+        });
+        server
+            .mock("POST", "/version_files")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "algorithm": "sha512" }),
+            ))
+            .with_body(installed_hashes.to_string())
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/version_files/update")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "algorithm": "sha512" }),
+            ))
+            .with_body(
+                serde_json::json!({
+                    "sodium-0.6.13-sha512": version("AANobbMI", "sodium-0.6.14", "0.6.14"),
+                    "lithium-0.18.0-sha512": version("gvQqBUqZ", "lithium-0.18.0", "0.18.0"),
+                    "fabric-api-0.128.0-sha512": version("P7dR8mSH", "fabric-api-0.130.0", "0.130.0"),
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        // Modrinth knows none of the remaining sha1 hashes
+        server
+            .mock("POST", Matcher::Regex("^/version_files".to_string()))
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "algorithm": "sha1" }),
+            ))
+            .with_body("{}")
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/projects")
+            .match_query(Matcher::Any)
+            .with_body(
+                r#"[
+                    {"id":"AANobbMI","slug":"sodium","title":"Sodium","client_side":"required","server_side":"unsupported"},
+                    {"id":"gvQqBUqZ","slug":"lithium","title":"Lithium","client_side":"optional","server_side":"optional","status":"archived"},
+                    {"id":"P7dR8mSH","slug":"fabric-api","title":"Fabric API","client_side":"optional","server_side":"optional"}
+                ]"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/project/P7dR8mSH/version")
+            .match_query(Matcher::Any)
+            .with_body(
+                serde_json::json!([
+                    version("P7dR8mSH", "fabric-api-0.130.0", "0.130.0"),
+                    version("P7dR8mSH", "fabric-api-0.129.0", "0.129.0"),
+                ])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let mut pins = Pins::default();
+        pins.projects.insert(
+            "fabric-api".to_string(),
+            Pin {
+                constraint: "~0.129".parse().unwrap(),
+            },
+        );
+        let mods = [
+            "sodium-0.6.13",
+            "lithium-0.18.0",
+            "fabric-api-0.128.0",
+            "own",
+        ]
+        .map(installed)
+        .to_vec();
+
+        let plan = check_updates_from(
+            &Client::new(),
+            &server.url(),
+            mods,
+            "1.21.8",
+            "fabric",
+            &pins,
+        )
+        .await
+        .unwrap();
+
+        let statuses = plan
+            .mods
+            .iter()
+            .map(|planned| (planned.slug.as_str(), planned.status.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                (
+                    "sodium",
+                    ModStatus::UpdateAvailable("sodium-0.6.14".to_string())
+                ),
+                ("lithium", ModStatus::NotUpdatable("archived".to_string())),
+                (
+                    "fabric-api",
+                    ModStatus::UpdateAvailable("fabric-api-0.129.0".to_string())
+                ),
+                ("own.jar", ModStatus::Unknown),
+            ]
+        );
+        assert_eq!(
+            plan.update_lines(),
+            ["sodium sodium-0.6.14", "fabric-api fabric-api-0.129.0"]
+        );
+        let (_, update) = plan.updates().next().unwrap();
+        assert_eq!(
+            update.primary_file().url,
+            "https://cdn.modrinth.com/data/AANobbMI/sodium-0.6.14.jar"
+        );
+        assert_eq!(plan.installed_jars().len(), 3);
+        assert_eq!(plan.mods[0].installed.sha1(), Some("sodium-0.6.13-sha1"));
+    }
+
+    #[tokio::test]
+    async fn test_check_no_mods() {
+        // Nothing is requested
+        let plan = check_updates_from(
+            &Client::new(),
+            "http://127.0.0.1:1",
+            Vec::new(),
+            "1.21.8",
+            "fabric",
+            &Pins::default(),
+        )
+        .await
+        .unwrap();
+        assert!(plan.mods.is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::system::{
+    jar_parser::{self, HashAlgorithm},
+    mod_lock::ModLock,
+};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    /// The file doesn't have the recorded hash.
+    Modified,
+    Missing,
+    /// A jar nothing recorded.
+    Unexpected,
+}
+
+/// A file of the instance that isn't what mcerv recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub path: PathBuf,
+    pub problem: Problem,
+    /// The Modrinth version the file was installed from, which `--repair` downloads again.
+    pub version_id: Option<String>,
+}
+
+impl Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problem = match self.problem {
+            Problem::Modified => "is modified",
+            Problem::Missing => "is missing",
+            Problem::Unexpected => "is not recorded",
+        };
+        write!(f, "{} {problem}", self.path.display())
+    }
+}
+
+#[derive(Debug)]
+pub struct VerifyError {
+    pub discrepancies: usize,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files don't match what mcerv recorded",
+            self.discrepancies
+        )
+    }
+}
+
+impl Error for VerifyError {}
+
+/// What a file in the mods directory should be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedMod {
+    pub sha512: String,
+    pub version_id: String,
+}
+
+/// The files of the lockfile by their name in the mods directory.
+pub fn expected_mods(lock: &ModLock) -> BTreeMap<String, ExpectedMod> {
+    lock.mods
+        .values()
+        .flat_map(|locked| {
+            locked.files.iter().map(|(file_name, sha512)| {
+                (
+                    file_name.clone(),
+                    ExpectedMod {
+                        sha512: sha512.clone(),
+                        version_id: locked.version_id.clone(),
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// Compares the `jars` of the mods directory with the expected files. A file that was
+/// disabled, renamed to end with `.disabled`, is compared instead of missing.
+pub fn compare_mods(
+    mods_dir: &Path,
+    expected: &BTreeMap<String, ExpectedMod>,
+    jars: &[PathBuf],
+) -> io::Result<Vec<Discrepancy>> {
+    let mut discrepancies = Vec::new();
+    for (file_name, expected_mod) in expected {
+        let path = mods_dir.join(file_name);
+        let disabled = mods_dir.join(format!("{file_name}.disabled"));
+        let path = if !path.exists() && disabled.exists() {
+            disabled
+        } else {
+            path
+        };
+
+        let problem = if !path.exists() {
+            Problem::Missing
+        } else if sha512(&path)? != expected_mod.sha512 {
+            Problem::Modified
+        } else {
+            continue;
+        };
+        discrepancies.push(Discrepancy {
+            path,
+            problem,
+            version_id: Some(expected_mod.version_id.clone()),
+        });
+    }
+
+    for jar in jars {
+        let recorded = jar
+            .strip_prefix(mods_dir)
+            .ok()
+            .and_then(|relative| relative.to_str())
+            .is_some_and(|relative| expected.contains_key(&relative.replace('\\', "/")));
+        if !recorded {
+            discrepancies.push(Discrepancy {
+                path: jar.clone(),
+                problem: Problem::Unexpected,
+                version_id: None,
+            });
+        }
+    }
+
+    Ok(discrepancies)
+}
+
+/// Compares the server jar with the SHA1 cached in its config.
+pub fn compare_server_jar(jar_path: &Path, sha1: &str) -> io::Result<Option<Discrepancy>> {
+    let problem = if !jar_path.exists() {
+        Problem::Missing
+    } else if jar_parser::calculate_hash(&mut File::open(jar_path)?)? != sha1 {
+        Problem::Modified
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(Discrepancy {
+        path: jar_path.to_path_buf(),
+        problem,
+        version_id: None,
+    }))
+}
+
+fn sha512(path: &Path) -> io::Result<String> {
+    jar_parser::calculate_hash_with(&mut File::open(path)?, HashAlgorithm::Sha512)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_compare_mods() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_dir = dir.path();
+        for (name, content) in [
+            ("lithium.jar", "lithium"),
+            ("sodium.jar", "tampered"),
+            ("ferritecore.jar.disabled", "ferritecore"),
+            ("own.jar", "own"),
+        ] {
+            fs::write(mods_dir.join(name), content).unwrap();
+        }
+
+        let hash = |content: &str| {
+            jar_parser::calculate_hash_with(&mut content.as_bytes(), HashAlgorithm::Sha512).unwrap()
+        };
+        let expected = ["lithium", "sodium", "ferritecore", "fabric-api"]
+            .into_iter()
+            .map(|name| {
+                (
+                    format!("{name}.jar"),
+                    ExpectedMod {
+                        sha512: hash(name),
+                        version_id: format!("{name}-id"),
+                    },
+                )
+            })
+            .collect();
+        let jars = jar_parser::jar_files(mods_dir).unwrap();
+
+        let mut discrepancies = compare_mods(mods_dir, &expected, &jars).unwrap();
+        discrepancies.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            discrepancies,
+            [
+                Discrepancy {
+                    path: mods_dir.join("fabric-api.jar"),
+                    problem: Problem::Missing,
+                    version_id: Some("fabric-api-id".to_string()),
+                },
+                Discrepancy {
+                    path: mods_dir.join("own.jar"),
+                    problem: Problem::Unexpected,
+                    version_id: None,
+                },
+                Discrepancy {
+                    path: mods_dir.join("sodium.jar"),
+                    problem: Problem::Modified,
+                    version_id: Some("sodium-id".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_server_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = dir.path().join("server.jar");
+        fs::write(&jar, "server").unwrap();
+        let sha1 = jar_parser::calculate_hash(&mut File::open(&jar).unwrap()).unwrap();
+
+        assert_eq!(compare_server_jar(&jar, &sha1).unwrap(), None);
+        fs::write(&jar, "modified").unwrap();
+        assert_eq!(
+            compare_server_jar(&jar, &sha1).unwrap().unwrap().problem,
+            Problem::Modified
+        );
+        fs::remove_file(&jar).unwrap();
+        assert_eq!(
+            compare_server_jar(&jar, &sha1).unwrap().unwrap().problem,
+            Problem::Missing
+        );
+    }
+}
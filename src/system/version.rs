@@ -0,0 +1,361 @@
+use std::fmt::Display;
+
+/// The releases snapshots led up to, with the year and ISO week they came out in. A
+/// snapshot belongs to the first release after its week.
+const SNAPSHOT_RELEASES: [([u64; 3], u64, u64); 38] = [
+    ([1, 1, 0], 12, 2),
+    ([1, 2, 1], 12, 9),
+    ([1, 3, 1], 12, 31),
+    ([1, 4, 2], 12, 43),
+    ([1, 4, 6], 12, 51),
+    ([1, 5, 0], 13, 11),
+    ([1, 6, 1], 13, 27),
+    ([1, 7, 2], 13, 43),
+    ([1, 7, 4], 13, 50),
+    ([1, 8, 0], 14, 36),
+    ([1, 9, 0], 16, 9),
+    ([1, 9, 3], 16, 19),
+    ([1, 10, 0], 16, 23),
+    ([1, 11, 0], 16, 46),
+    ([1, 11, 1], 16, 51),
+    ([1, 12, 0], 17, 23),
+    ([1, 13, 0], 18, 29),
+    ([1, 14, 0], 19, 17),
+    ([1, 15, 0], 19, 50),
+    ([1, 16, 0], 20, 26),
+    ([1, 16, 2], 20, 33),
+    ([1, 17, 0], 21, 23),
+    ([1, 18, 0], 21, 48),
+    ([1, 19, 0], 22, 23),
+    ([1, 19, 1], 22, 30),
+    ([1, 19, 3], 22, 49),
+    ([1, 19, 4], 23, 11),
+    ([1, 20, 0], 23, 23),
+    ([1, 20, 2], 23, 38),
+    ([1, 20, 3], 23, 49),
+    ([1, 20, 5], 24, 17),
+    ([1, 21, 0], 24, 24),
+    ([1, 21, 2], 24, 43),
+    ([1, 21, 4], 24, 49),
+    ([1, 21, 5], 25, 13),
+    ([1, 21, 6], 25, 25),
+    ([1, 21, 9], 25, 40),
+    ([1, 21, 11], 25, 50),
+];
+
+/// Ordered like the versions came out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    Snapshot,
+    PreRelease,
+    ReleaseCandidate,
+    Release,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    /// The release, or the one a snapshot or pre-release led up to.
+    release: [u64; 3],
+    stage: Stage,
+    /// The number of a pre-release, or the year, week and letter of a snapshot.
+    build: [u64; 3],
+    /// For versions only the manifest can place, how far after the version it places them.
+    after: usize,
+}
+
+/// A Minecraft version, ordered by when it came out: `1.20.4-rc1` before `1.20.4`, and
+/// `24w40a` after `1.21.1` but before `1.21.2-pre1`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct McVersion {
+    key: SortKey,
+    /// Ties versions like `1.21` and `1.21.0`, so the order is consistent with equality.
+    id: String,
+}
+
+impl McVersion {
+    /// Parses releases like `1.21.8`, pre-releases like `1.21-pre1`, `1.20.4-rc1` or
+    /// `1.14 Pre-Release 2`, and snapshots like `24w40a` or `26.1-snapshot-1`. `None` for
+    /// april fools versions, old alphas and betas, and weekly snapshots newer than this
+    /// version of mcerv knows.
+    pub fn parse(id: &str) -> Option<Self> {
+        Some(Self {
+            key: parse_key(id.trim())?,
+            id: id.trim().to_string(),
+        })
+    }
+
+    /// Like [`McVersion::parse`], but a version that can't be parsed is placed by its position
+    /// in `manifest_ids`, which is ordered newest first like Mojang's version manifest.
+    /// Versions the manifest doesn't list either come before all others.
+    pub fn from_manifest(id: &str, manifest_ids: &[impl AsRef<str>]) -> Self {
+        if let Some(version) = Self::parse(id) {
+            return version;
+        }
+
+        let zero = SortKey {
+            release: [0; 3],
+            stage: Stage::Snapshot,
+            build: [0; 3],
+            after: 0,
+        };
+        let key = match manifest_ids.iter().position(|other| other.as_ref() == id) {
+            Some(i) => {
+                let older = manifest_ids[i + 1..]
+                    .iter()
+                    .enumerate()
+                    .find_map(|(distance, other)| Some((parse_key(other.as_ref())?, distance)));
+                match older {
+                    Some((key, distance)) => SortKey {
+                        after: distance + 1,
+                        ..key
+                    },
+                    None => SortKey {
+                        after: manifest_ids.len() - i,
+                        ..zero
+                    },
+                }
+            }
+            None => zero,
+        };
+        Self {
+            key,
+            id: id.to_string(),
+        }
+    }
+
+    /// Whether it's a full release, not a snapshot or pre-release.
+    pub fn is_release(&self) -> bool {
+        self.key.stage == Stage::Release && self.key.after == 0
+    }
+}
+
+/// Whether `a` came out after `b`. `false` if either can't be parsed.
+pub fn is_newer(a: &str, b: &str) -> bool {
+    matches!((McVersion::parse(a), McVersion::parse(b)), (Some(a), Some(b)) if a > b)
+}
+
+impl Display for McVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+fn parse_key(id: &str) -> Option<SortKey> {
+    if let Some(key) = parse_weekly_snapshot(id) {
+        return key;
+    }
+
+    let (numbers, suffix) = id.split_once(['-', ' ', '_']).unwrap_or((id, ""));
+    let numbers: Vec<&str> = numbers.split('.').collect();
+    if numbers.len() < 2 || numbers.len() > 3 {
+        return None;
+    }
+    let mut release = [0; 3];
+    for (i, number) in numbers.into_iter().enumerate() {
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        release[i] = number.parse().ok()?;
+    }
+
+    // Like `pre1`, `rc1`, `snapshot-1` or the old ` Pre-Release 2`
+    let suffix = suffix.to_lowercase().replace(['-', ' ', '_'], "");
+    let (stage, build) = if suffix.is_empty() {
+        (Stage::Release, "0")
+    } else if let Some(build) = suffix.strip_prefix("prerelease") {
+        (Stage::PreRelease, build)
+    } else if let Some(build) = suffix.strip_prefix("pre") {
+        (Stage::PreRelease, build)
+    } else if let Some(build) = suffix.strip_prefix("rc") {
+        (Stage::ReleaseCandidate, build)
+    } else if let Some(build) = suffix.strip_prefix("snapshot") {
+        (Stage::Snapshot, build)
+    } else {
+        return None;
+    };
+    let build = if build.is_empty() {
+        0
+    } else {
+        build.parse().ok()?
+    };
+
+    Some(SortKey {
+        release,
+        stage,
+        build: [build, 0, 0],
+        after: 0,
+    })
+}
+
+/// Parses snapshots like `24w40a`. `None` if it isn't one, `Some(None)` if it's newer than
+/// the known releases.
+fn parse_weekly_snapshot(id: &str) -> Option<Option<SortKey>> {
+    let (year, rest) = id.split_once('w')?;
+    if year.len() != 2 || rest.len() != 3 || !rest.is_char_boundary(2) {
+        return None;
+    }
+    let (week, letter) = rest.split_at(2);
+    let letter = letter.chars().next()?;
+    if !letter.is_ascii_lowercase() {
+        return None;
+    }
+    let (year, week): (u64, u64) = (year.parse().ok()?, week.parse().ok()?);
+
+    let release = SNAPSHOT_RELEASES
+        .iter()
+        .find(|(_, release_year, release_week)| (year, week) < (*release_year, *release_week))
+        .map(|(release, _, _)| *release);
+    Some(release.map(|release| SortKey {
+        release,
+        stage: Stage::Snapshot,
+        build: [year, week, letter as u64],
+        after: 0,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Oldest first.
+    const HISTORY: [&str; 45] = [
+        "1.0",
+        "11w47a",
+        "12w01a",
+        "1.1",
+        "1.2.1",
+        "1.2.5",
+        "12w49a",
+        "1.4.6",
+        "1.7.10",
+        "1.8",
+        "15w31a",
+        "1.9",
+        "1.12.2",
+        "18w50a",
+        "1.14 Pre-Release 2",
+        "1.14",
+        "1.14.4-pre1",
+        "1.14.4",
+        "20w06a",
+        "1.16-rc1",
+        "1.16",
+        "1.16.1",
+        "20w27a",
+        "1.16.2-pre1",
+        "1.16.2",
+        "1.20.4-rc1",
+        "1.20.4",
+        "24w03a",
+        "1.20.5",
+        "24w21b",
+        "1.21-pre1",
+        "1.21-pre2",
+        "1.21-rc1",
+        "1.21",
+        "1.21.1",
+        "24w33a",
+        "24w40a",
+        "1.21.2-pre1",
+        "1.21.2",
+        "1.21.8",
+        "25w31a",
+        "1.21.9",
+        "1.21.10",
+        "26.1-snapshot-1",
+        "26.1",
+    ];
+
+    fn parse(id: &str) -> McVersion {
+        McVersion::parse(id).unwrap_or_else(|| panic!("{id} isn't parsed"))
+    }
+
+    #[test]
+    fn test_order() {
+        let versions = HISTORY.map(parse);
+        for pair in versions.windows(2) {
+            assert!(
+                pair[0] < pair[1],
+                "{} should come before {}",
+                pair[0],
+                pair[1]
+            );
+        }
+
+        let mut shuffled = versions.to_vec();
+        shuffled.reverse();
+        shuffled.swap(3, 17);
+        shuffled.sort();
+        assert_eq!(shuffled, versions);
+
+        assert!(parse("24w40a") < parse("24w40b"));
+        assert!(parse("1.21.8-rc1") < parse("1.21.8"));
+        assert!(parse("26.1-snapshot-2") < parse("26.1-pre-1"));
+        // The same version, only ordered by how it's written
+        assert_eq!(parse("1.21").key, parse("1.21.0").key);
+        assert_ne!(parse("1.21"), parse("1.21.0"));
+    }
+
+    #[test]
+    fn test_unparsable() {
+        for id in [
+            "24w14potato",
+            "25w14craftmine",
+            "23w13a_or_b",
+            "1.RV-Pre1",
+            "b1.7.3",
+            "a1.0.4",
+            "rd-132211",
+            "3D Shareware v1.34",
+            "1.2.3.4",
+            "release",
+            "1",
+            // Newer than the known releases
+            "99w01a",
+        ] {
+            assert_eq!(McVersion::parse(id), None, "{id}");
+        }
+        assert!(parse("1.21.8").is_release());
+        assert!(!parse("1.21.8-pre1").is_release());
+        assert!(!parse("25w31a").is_release());
+    }
+
+    #[test]
+    fn test_from_manifest() {
+        let manifest = [
+            "1.21.8",
+            "25w14craftmine",
+            "25w14a",
+            "1.21.5",
+            "24w14potato",
+            "24w14a",
+            "b1.8.1",
+            "b1.7.3",
+        ];
+        let version = |id: &str| McVersion::from_manifest(id, &manifest);
+
+        let ordered = [
+            "b1.7.3",
+            "b1.8.1",
+            "24w14a",
+            "24w14potato",
+            "1.21.5",
+            "25w14a",
+            "25w14craftmine",
+            "1.21.8",
+        ]
+        .map(version);
+        for pair in ordered.windows(2) {
+            assert!(
+                pair[0] < pair[1],
+                "{} should come before {}",
+                pair[0],
+                pair[1]
+            );
+        }
+
+        assert!(version("24w14potato") < version("1.20.5"));
+        assert!(!version("24w14potato").is_release());
+        assert!(version("unknown") < version("b1.7.3"));
+    }
+}
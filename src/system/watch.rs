@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+/// In the data directory, so restarting `mcerv watch` doesn't notify about the same updates.
+pub const WATCH_STATE_FILE: &str = "watch.json";
+/// The wait before retrying after the first failed check, doubled for each one after it.
+const MIN_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidIntervalError(String);
+
+impl Display for InvalidIntervalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid interval {:?}, expected a number of s, m, h or d like 6h",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidIntervalError {}
+
+/// How long to wait between checks, like `30m` or `6h`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval(pub Duration);
+
+impl FromStr for Interval {
+    type Err = InvalidIntervalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidIntervalError(s.to_string());
+        let s = s.trim();
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+
+        let count: u64 = s[..s.len() - 1].parse().map_err(|_| invalid())?;
+        if count == 0 {
+            return Err(invalid());
+        }
+        Ok(Self(Duration::from_secs(count * seconds)))
+    }
+}
+
+/// The updates a server had at its last check, as `name version` lines.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct KnownUpdates {
+    #[serde(default)]
+    pub mods: Vec<String>,
+    /// The components of the server jar, like vanilla or the Fabric loader.
+    #[serde(default)]
+    pub server: Vec<String>,
+}
+
+impl KnownUpdates {
+    pub fn is_empty(&self) -> bool {
+        self.mods.is_empty() && self.server.is_empty()
+    }
+}
+
+/// The updates each server had at its last check, by name.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(transparent)]
+pub struct WatchState {
+    pub servers: BTreeMap<String, KnownUpdates>,
+}
+
+impl WatchState {
+    /// Loads the state, or an empty one if there is none.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid watch state {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    /// Records the updates found for the server, returning the ones it didn't have at its
+    /// last check.
+    pub fn record(&mut self, server_name: &str, found: KnownUpdates) -> KnownUpdates {
+        let known = self.servers.remove(server_name).unwrap_or_default();
+        let new = KnownUpdates {
+            mods: new_lines(&found.mods, &known.mods),
+            server: new_lines(&found.server, &known.server),
+        };
+        self.servers.insert(server_name.to_string(), found);
+        new
+    }
+}
+
+fn new_lines(found: &[String], known: &[String]) -> Vec<String> {
+    found
+        .iter()
+        .filter(|line| !known.contains(line))
+        .cloned()
+        .collect()
+}
+
+/// The wait before checking again after `failures` checks in a row failed, never longer
+/// than the interval.
+pub fn backoff(interval: Duration, failures: u32) -> Duration {
+    MIN_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!("90s".parse(), Ok(Interval(Duration::from_secs(90))));
+        assert_eq!("6h".parse(), Ok(Interval(Duration::from_secs(6 * 60 * 60))));
+        assert_eq!(
+            " 1d".parse(),
+            Ok(Interval(Duration::from_secs(24 * 60 * 60)))
+        );
+
+        for invalid in ["", "6", "h", "0m", "-1h", "6w", "1.5h"] {
+            assert_eq!(
+                invalid.parse::<Interval>(),
+                Err(InvalidIntervalError(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WATCH_STATE_FILE);
+        let found = |mods: &[&str]| KnownUpdates {
+            mods: mods.iter().map(|line| line.to_string()).collect(),
+            server: Vec::new(),
+        };
+
+        let mut state = WatchState::load(&path).unwrap();
+        let new = state.record("survival", found(&["sodium 0.6.13", "lithium 0.15.0"]));
+        assert_eq!(new, found(&["sodium 0.6.13", "lithium 0.15.0"]));
+        state.save(&path).unwrap();
+
+        // A restarted watcher only reports what changed
+        let mut state = WatchState::load(&path).unwrap();
+        let new = state.record("survival", found(&["sodium 0.6.14", "lithium 0.15.0"]));
+        assert_eq!(new, found(&["sodium 0.6.14"]));
+        assert!(
+            state
+                .record("survival", found(&["sodium 0.6.14"]))
+                .is_empty()
+        );
+
+        // Updated in the meantime, so the same version is new again
+        state.record("survival", found(&[]));
+        assert_eq!(
+            state.record("survival", found(&["sodium 0.6.14"])),
+            found(&["sodium 0.6.14"])
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_secs(6 * 60 * 60);
+        assert_eq!(backoff(interval, 1), Duration::from_secs(60));
+        assert_eq!(backoff(interval, 3), Duration::from_secs(4 * 60));
+        assert_eq!(backoff(interval, 100), interval);
+        assert_eq!(backoff(Duration::from_secs(30), 1), Duration::from_secs(30));
+    }
+}
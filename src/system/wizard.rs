@@ -0,0 +1,283 @@
+use crate::{
+    install_mod, is_interactive,
+    network::{VersionEntry, modrinth},
+    server_dir, set_config,
+    system::{
+        cli::ForgeVersionsFilter,
+        config::memory_bytes,
+        eula::EULA_URL,
+        forks::{Fabric, Forge, Fork, ServerFork, Vanilla},
+        installer::ServerInstaller,
+        prompt::{self, Danger},
+    },
+};
+use dialoguer::{Input, Select};
+use reqwest::Client;
+
+const FORKS: [(ServerFork, &str); 3] = [
+    (
+        ServerFork::Vanilla,
+        "Vanilla, the official server without mods",
+    ),
+    (
+        ServerFork::Fabric,
+        "Fabric, lightweight and quick to update mods",
+    ),
+    (
+        ServerFork::Forge,
+        "Forge, the classic mod loader for big modpacks",
+    ),
+];
+/// The newest versions offered, the rest can be installed with `mcerv install`.
+const VERSIONS_SHOWN: usize = 20;
+const MODS_SHOWN: usize = 10;
+
+/// Walks through installing a server: the fork, game version, name, memory, EULA and
+/// optionally a few mods. Then installs it.
+pub async fn run(client: &Client) -> anyhow::Result<()> {
+    if !is_interactive() {
+        anyhow::bail!("mcerv init asks questions, use `mcerv install` to install without them");
+    }
+    println!("Let's set up a Minecraft server. Press Ctrl-C to stop at any time.");
+
+    let fork = pick_fork()?;
+    let (game_version, installer) = pick_version(fork, client).await?;
+    let server_name = ask_server_name(fork)?;
+    let memory = ask_memory()?;
+
+    let eula_agreed = prompt::confirm(
+        Danger::Destructive,
+        &format!(
+            "Do you agree to the Minecraft EULA at {EULA_URL}? The server can't start without it"
+        ),
+    )?;
+    if !eula_agreed {
+        anyhow::bail!("The EULA wasn't accepted, nothing was installed");
+    }
+
+    let mods = match fork {
+        ServerFork::Vanilla => Vec::new(),
+        _ => pick_mods(fork, &game_version, client).await?,
+    };
+
+    installer
+        .server_name(&server_name)
+        .eula_accepted(true)
+        .install(client)
+        .await?;
+    set_config(
+        &server_name,
+        Some(memory.clone()),
+        Some(memory),
+        None,
+        None,
+        None,
+    )?;
+    for (title, version_id) in mods {
+        // The server is installed already, a missing mod can be added later
+        if let Err(e) = install_mod(&server_name, &version_id, client).await {
+            eprintln!("Failed to install {title}: {e}");
+        }
+    }
+
+    println!();
+    println!("{server_name} is ready. Start it with:");
+    println!("  mcerv start {server_name}");
+    Ok(())
+}
+
+fn pick_fork() -> anyhow::Result<ServerFork> {
+    let items = FORKS.map(|(_, description)| description);
+    let selection = Select::new()
+        .with_prompt("Which server do you want?")
+        .items(items)
+        .default(0)
+        .interact()?;
+    Ok(FORKS[selection].0)
+}
+
+/// Picks a stable game version, newest first, returning it with the installer that
+/// installs it.
+async fn pick_version(
+    fork: ServerFork,
+    client: &Client,
+) -> anyhow::Result<(String, ServerInstaller)> {
+    println!("Fetching the versions...");
+    let versions = match fork {
+        ServerFork::Vanilla => Vanilla::fetch_availables(false, client).await?,
+        ServerFork::Fabric => Fabric::fetch_availables(false, client)
+            .await?
+            .into_iter()
+            .filter(|version| version.component == Some("Minecraft"))
+            .collect(),
+        ServerFork::Forge => {
+            let filter = ForgeVersionsFilter {
+                game: None,
+                latest_only: true,
+                all: false,
+            };
+            Forge::fetch_availables(filter, client).await?
+        }
+    };
+    let versions: Vec<VersionEntry> = versions
+        .into_iter()
+        .filter(|version| version.stable)
+        .take(VERSIONS_SHOWN)
+        .collect();
+    if versions.is_empty() {
+        anyhow::bail!("No stable {fork:?} versions were found");
+    }
+
+    let items: Vec<_> = versions.iter().map(|version| &version.id).collect();
+    let selection = Select::new()
+        .with_prompt("Which version?")
+        .items(&items)
+        .default(0)
+        .max_length(10)
+        .interact()?;
+    let version = versions[selection].id.clone();
+
+    Ok(match fork {
+        ServerFork::Vanilla => (version.clone(), ServerInstaller::vanilla().game(version)),
+        ServerFork::Fabric => (
+            version.clone(),
+            ServerInstaller::fabric()
+                .game(version)
+                .latest_stable_loader()
+                .latest_stable_installer(),
+        ),
+        ServerFork::Forge => (
+            // Like `1.21.8-58.1.0`
+            version
+                .split_once('-')
+                .map_or(version.clone(), |(game_version, _)| {
+                    game_version.to_string()
+                }),
+            ServerInstaller::forge().loader(version),
+        ),
+    })
+}
+
+fn ask_server_name(fork: ServerFork) -> anyhow::Result<String> {
+    let name = Input::<String>::new()
+        .with_prompt("Name the server")
+        .default(format!("{fork:?}").to_lowercase())
+        .validate_with(|name: &String| validate_server_name(name))
+        .interact_text()?;
+    Ok(name.trim().to_string())
+}
+
+fn validate_server_name(name: &str) -> Result<(), String> {
+    let name = name.trim();
+    let dir = server_dir(name).map_err(|e| e.to_string())?;
+    if dir.exists() {
+        return Err(format!("{name} already exists"));
+    }
+    Ok(())
+}
+
+fn ask_memory() -> anyhow::Result<String> {
+    let memory = Input::<String>::new()
+        .with_prompt("How much memory can the server use? Like 2G or 4096M")
+        .default("4G".to_string())
+        .validate_with(|memory: &String| {
+            memory_bytes(memory)
+                .map(|_| ())
+                .ok_or("Expected a number with K, M or G, like 4G")
+        })
+        .interact_text()?;
+    Ok(memory.trim().to_string())
+}
+
+/// Searches Modrinth for mods until an empty search, returning the title and version ID of
+/// the picked ones.
+async fn pick_mods(
+    fork: ServerFork,
+    game_version: &str,
+    client: &Client,
+) -> anyhow::Result<Vec<(String, String)>> {
+    let loader = format!("{fork:?}").to_lowercase();
+    let facets = [
+        vec![format!("categories:{loader}")],
+        vec![format!("versions:{game_version}")],
+    ];
+
+    let mut mods = Vec::new();
+    loop {
+        let query = Input::<String>::new()
+            .with_prompt("Search for a mod to add, or leave empty to continue")
+            .allow_empty(true)
+            .interact_text()?;
+        if query.trim().is_empty() {
+            return Ok(mods);
+        }
+
+        let response =
+            match modrinth::search(client, &query, &facets, false, None, Some(MODS_SHOWN)).await {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("Failed to search for {query}: {e}");
+                    continue;
+                }
+            };
+        let hits: Vec<(&str, &str)> = response
+            .hits
+            .iter()
+            .filter_map(|hit| Some((hit["title"].as_str()?, hit["slug"].as_str()?)))
+            .collect();
+        if hits.is_empty() {
+            println!("No mods for {fork:?} {game_version} match {query}");
+            continue;
+        }
+
+        let mut items: Vec<_> = hits.iter().map(|(title, _)| *title).collect();
+        items.push("None of these");
+        let selection = Select::new()
+            .with_prompt("Which one?")
+            .items(&items)
+            .default(0)
+            .interact()?;
+        let Some((title, slug)) = hits.get(selection) else {
+            continue;
+        };
+
+        match modrinth::latest_compatible_version(client, slug, &loader, game_version).await {
+            Ok(Some(version_id)) => {
+                println!("{title} will be installed");
+                mods.push((title.to_string(), version_id));
+            }
+            Ok(None) => println!("{title} has no version for {fork:?} {game_version}"),
+            Err(e) => eprintln!("Failed to find a version of {title}: {e}"),
+        }
+    }
+}
+
+/// Offers the wizard when mcerv is run without a command before any server is installed,
+/// returning whether it ran.
+pub async fn offer(has_servers: bool, client: &Client) -> anyhow::Result<bool> {
+    if has_servers || !is_interactive() {
+        return Ok(false);
+    }
+
+    let start = prompt::confirm(
+        Danger::Safe,
+        "No servers are installed yet. Set one up now?",
+    )?;
+    if start {
+        run(client).await?;
+    }
+    Ok(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_server_name() {
+        assert!(validate_server_name("").is_err());
+        assert!(validate_server_name("   ").is_err());
+        assert!(validate_server_name("../survival").is_err());
+        assert!(validate_server_name("..").is_err());
+    }
+}
@@ -0,0 +1,552 @@
+use crate::system::{
+    metrics::{dir_size, world_dir},
+    nbt::{self, Tag},
+    version,
+};
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Next to the world, where `world reset` moves the old worlds. In the server directory
+/// unless the world is kept outside of it.
+pub const WORLD_BACKUPS_DIR: &str = "world-backups";
+
+/// What `level.dat` says about a world.
+#[derive(Debug, PartialEq)]
+pub struct LevelInfo {
+    pub level_name: Option<String>,
+    pub seed: Option<i64>,
+    /// The game version the world was last opened with, like `1.21.8`.
+    pub version_name: Option<String>,
+    /// The data version of that game version, which increases with every version.
+    pub data_version: Option<i64>,
+    pub snapshot: bool,
+    /// Milliseconds since the Unix epoch.
+    pub last_played: Option<i64>,
+}
+
+impl LevelInfo {
+    /// Reads the `level.dat` of the world of the server, or `None` if it was never generated.
+    pub fn load(server_dir: &Path) -> anyhow::Result<Option<Self>> {
+        let path = world_dir(server_dir).join("level.dat");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let root = nbt::read_gzip_file(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+        Self::from_nbt(&root)
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("{} has no Data tag", path.display()))
+    }
+
+    fn from_nbt(root: &Tag) -> Option<Self> {
+        let data = root.get("Data")?;
+        let version = data.get("Version");
+        Some(Self {
+            level_name: data
+                .get("LevelName")
+                .and_then(Tag::as_str)
+                .map(str::to_string),
+            // Moved into WorldGenSettings in 1.16
+            seed: data
+                .get("WorldGenSettings")
+                .and_then(|settings| settings.get("seed"))
+                .or_else(|| data.get("RandomSeed"))
+                .and_then(Tag::as_i64),
+            version_name: version
+                .and_then(|version| version.get("Name"))
+                .and_then(Tag::as_str)
+                .map(str::to_string),
+            data_version: data.get("DataVersion").and_then(Tag::as_i64),
+            snapshot: version
+                .and_then(|version| version.get("Snapshot"))
+                .and_then(Tag::as_i64)
+                .is_some_and(|snapshot| snapshot != 0),
+            last_played: data.get("LastPlayed").and_then(Tag::as_i64),
+        })
+    }
+
+    /// A warning if the world was last opened with a newer version than `game_version`.
+    /// Opening it with an older version can corrupt its chunks.
+    pub fn downgrade_warning(&self, game_version: &str) -> Option<String> {
+        let version_name = self.version_name.as_deref()?;
+        version::is_newer(version_name, game_version).then(|| {
+            format!(
+                "The world was last opened with {version_name}, but the server is {game_version}. \
+                Opening it with an older version can corrupt it, back it up first"
+            )
+        })
+    }
+}
+
+/// The directories of the world, its dimensions like `world_nether` that Bukkit-based
+/// servers keep next to it included.
+pub fn world_dirs(server_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let world_dir = world_dir(server_dir);
+    let Some(level_name) = world_dir.file_name().map(|name| name.to_string_lossy()) else {
+        return Ok(Vec::new());
+    };
+    let dimension_prefix = format!("{level_name}_");
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(server_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // A world kept outside the server directory is linked to
+        if entry.path().is_dir() && (name == level_name || name.starts_with(&dimension_prefix)) {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// The [`WORLD_BACKUPS_DIR`] of the server: next to its world if that's linked to from
+/// outside the server directory, so backing it up doesn't move it across filesystems.
+pub fn backups_dir(server_dir: &Path) -> io::Result<PathBuf> {
+    let world_dir = world_dir(server_dir);
+    if !is_link(&world_dir) {
+        return Ok(server_dir.join(WORLD_BACKUPS_DIR));
+    }
+    let target = fs::canonicalize(world_dir)?;
+    Ok(target.parent().unwrap_or(&target).join(WORLD_BACKUPS_DIR))
+}
+
+/// Moves the world directories into a new directory of `backups_dir`, returning it. For a
+/// linked world, its target is moved and an empty one left for the link.
+pub fn back_up(backups_dir: &Path, dirs: &[PathBuf], name: &str) -> io::Result<PathBuf> {
+    let mut backup_dir = backups_dir.join(name);
+    let mut i = 1;
+    while backup_dir.exists() {
+        backup_dir = backups_dir.join(format!("{name}-{i}"));
+        i += 1;
+    }
+
+    fs::create_dir_all(&backup_dir)?;
+    for dir in dirs {
+        let backup = backup_dir.join(dir.file_name().unwrap());
+        if is_link(dir) {
+            let target = fs::canonicalize(dir)?;
+            move_dir(&target, &backup)?;
+            fs::create_dir(target)?;
+        } else {
+            fs::rename(dir, backup)?;
+        }
+    }
+    Ok(backup_dir)
+}
+
+/// Moving the world of a server out of the server directory, or back into it, for
+/// `mcerv set --world-dir`.
+#[derive(Debug, PartialEq)]
+pub struct WorldMove {
+    /// Where the world is now, `None` if it wasn't generated yet.
+    pub from: Option<PathBuf>,
+    pub to: PathBuf,
+    /// The link the server directory has to the world now, removed first.
+    pub old_link: Option<PathBuf>,
+    /// The link the server directory has to the world afterwards.
+    pub new_link: Option<PathBuf>,
+    /// The `level-name` of server.properties afterwards.
+    pub level_name: String,
+}
+
+impl WorldMove {
+    /// Plans moving the world of the server to `target`, an absolute path outside the
+    /// server directory, or back into the server directory if `target` is `None`.
+    pub fn plan(server_dir: &Path, target: Option<&Path>) -> anyhow::Result<Self> {
+        let current = world_dir(server_dir);
+        let old_link = is_link(&current).then(|| current.clone());
+        let from = match &old_link {
+            Some(link) => fs::canonicalize(link).ok(),
+            None => current.is_dir().then(|| current.clone()),
+        };
+
+        let Some(target) = target else {
+            anyhow::ensure!(
+                old_link.is_some(),
+                "The world is already in the server directory"
+            );
+            let level_name = current.file_name().unwrap().to_string_lossy().to_string();
+            return Ok(Self {
+                from,
+                to: current,
+                old_link,
+                new_link: None,
+                level_name,
+            });
+        };
+
+        anyhow::ensure!(
+            target.is_absolute(),
+            "The world directory {} should be an absolute path",
+            target.display()
+        );
+        let Some(level_name) = target.file_name().map(|name| name.to_string_lossy()) else {
+            anyhow::bail!("{} can't be a world directory", target.display());
+        };
+        // Through its closest existing ancestor, since it may not exist yet
+        let canonical_server_dir = fs::canonicalize(server_dir)?;
+        let inside_server_dir = target
+            .ancestors()
+            .find_map(|ancestor| fs::canonicalize(ancestor).ok())
+            .is_some_and(|ancestor| ancestor.starts_with(&canonical_server_dir));
+        anyhow::ensure!(
+            !inside_server_dir,
+            "The world directory should be outside the server directory"
+        );
+        let new_link = server_dir.join(level_name.as_ref());
+        anyhow::ensure!(
+            new_link == current || fs::symlink_metadata(&new_link).is_err(),
+            "{} is in the way of the link to the world",
+            new_link.display()
+        );
+        if let Ok(canonical) = fs::canonicalize(target) {
+            anyhow::ensure!(
+                from.as_ref() != Some(&canonical),
+                "The world is already at {}",
+                target.display()
+            );
+            anyhow::ensure!(
+                fs::read_dir(&canonical).is_ok_and(|mut entries| entries.next().is_none()),
+                "{} already exists and isn't an empty directory",
+                target.display()
+            );
+        }
+
+        Ok(Self {
+            from,
+            to: target.to_path_buf(),
+            old_link,
+            new_link: Some(new_link),
+            level_name: level_name.to_string(),
+        })
+    }
+
+    /// Moves the world and links it. If the world can't be moved, it's left where it was,
+    /// with its old link.
+    pub fn apply(&self) -> io::Result<()> {
+        if let Some(link) = &self.old_link {
+            remove_link(link)?;
+        }
+        let moved = match &self.from {
+            Some(from) => move_dir(from, &self.to),
+            // An existing empty directory is used as it is
+            None => fs::create_dir_all(&self.to),
+        };
+        if let Err(e) = moved {
+            if let (Some(link), Some(from)) = (&self.old_link, &self.from) {
+                link_dir(from, link)?;
+            }
+            return Err(e);
+        }
+        if let Some(link) = &self.new_link {
+            link_dir(&self.to, link)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for WorldMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(link) = &self.old_link {
+            writeln!(f, "Remove the link {}", link.display())?;
+        }
+        match &self.from {
+            Some(from) => writeln!(f, "Move {} to {}", from.display(), self.to.display())?,
+            None => writeln!(f, "Create {}", self.to.display())?,
+        }
+        if let Some(link) = &self.new_link {
+            writeln!(f, "Link {} to {}", link.display(), self.to.display())?;
+        }
+        write!(f, "Set level-name to {}", self.level_name)
+    }
+}
+
+/// Whether the path is a symlink, or a junction on Windows.
+fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Links `link` to the directory `target` with a symlink, or a junction on Windows, which
+/// doesn't need the privilege symlinks do.
+pub fn link_dir(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, link);
+    #[cfg(windows)]
+    {
+        let status = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(link)
+            .arg(target)
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "mklink /J {} {} failed",
+                link.display(),
+                target.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn remove_link(link: &Path) -> io::Result<()> {
+    // Junctions and directory symlinks on Windows are removed like directories
+    fs::remove_file(link).or_else(|_| fs::remove_dir(link))
+}
+
+/// Moves the directory like [`fs::rename`], but copies it and deletes the original when
+/// `to` is on another filesystem.
+pub fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_then_delete(from, to),
+        result => result,
+    }
+}
+
+/// Copies into a `.partial` directory next to `to` first, so an interrupted copy never
+/// looks like a complete one, and only deletes the original once the copy has its size.
+fn copy_then_delete(from: &Path, to: &Path) -> io::Result<()> {
+    let mut partial = to.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+
+    copy_dir(from, &partial)?;
+    let (expected, copied) = (dir_size(from)?, dir_size(&partial)?);
+    if expected != copied {
+        fs::remove_dir_all(&partial)?;
+        return Err(io::Error::other(format!(
+            "Copied {copied} of the {expected} bytes of {}",
+            from.display()
+        )));
+    }
+    if to.exists() {
+        // Only an empty directory, which the plan checked
+        fs::remove_dir(to)?;
+    }
+    fs::rename(&partial, to)?;
+    fs::remove_dir_all(from)
+}
+
+/// Symlinks are copied as symlinks, so a mods directory shared with other servers isn't
+/// copied into each of them.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_link(&entry.path(), &dest)?;
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_link(link: &Path, dest: &Path) -> io::Result<()> {
+    let target = fs::read_link(link)?;
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, dest);
+    #[cfg(windows)]
+    {
+        if link.is_dir() {
+            link_dir(&target, dest)
+        } else {
+            std::os::windows::fs::symlink_file(target, dest)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::nbt::tests::{compound, write_named};
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    fn write_level_dat(world_dir: &Path, data: Tag) {
+        let mut bytes = Vec::new();
+        write_named(&mut bytes, "", &compound([("Data", data)]));
+        fs::create_dir_all(world_dir).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        fs::write(world_dir.join("level.dat"), encoder.finish().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(LevelInfo::load(dir.path()).unwrap(), None);
+
+        fs::write(dir.path().join("server.properties"), "level-name=lobby\n").unwrap();
+        write_level_dat(
+            &dir.path().join("lobby"),
+            compound([
+                ("LevelName", Tag::String("Lobby".to_string())),
+                ("LastPlayed", Tag::Long(1_755_000_000_000)),
+                ("DataVersion", Tag::Int(4440)),
+                (
+                    "Version",
+                    compound([
+                        ("Name", Tag::String("1.21.8".to_string())),
+                        ("Snapshot", Tag::Byte(0)),
+                    ]),
+                ),
+                ("WorldGenSettings", compound([("seed", Tag::Long(-42))])),
+            ]),
+        );
+
+        let info = LevelInfo::load(dir.path()).unwrap().unwrap();
+        assert_eq!(
+            info,
+            LevelInfo {
+                level_name: Some("Lobby".to_string()),
+                seed: Some(-42),
+                version_name: Some("1.21.8".to_string()),
+                data_version: Some(4440),
+                snapshot: false,
+                last_played: Some(1_755_000_000_000),
+            }
+        );
+        assert!(info.downgrade_warning("1.21.1").is_some());
+        assert!(info.downgrade_warning("1.21.8").is_none());
+        assert!(info.downgrade_warning("1.21.10").is_none());
+        assert!(info.downgrade_warning("25w31a").is_none());
+    }
+
+    #[test]
+    fn test_world_dirs_and_back_up() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["world", "world_nether", "world_the_end", "worlds", "mods"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("world.zip"), "").unwrap();
+
+        let dirs = world_dirs(dir.path()).unwrap();
+        assert_eq!(
+            dirs,
+            ["world", "world_nether", "world_the_end"].map(|name| dir.path().join(name))
+        );
+
+        let backups_dir = backups_dir(dir.path()).unwrap();
+        assert_eq!(backups_dir, dir.path().join(WORLD_BACKUPS_DIR));
+        let backup = back_up(&backups_dir, &dirs, "reset").unwrap();
+        assert!(backup.join("world_nether").is_dir());
+        assert!(world_dirs(dir.path()).unwrap().is_empty());
+        assert_eq!(
+            back_up(&backups_dir, &[], "reset").unwrap(),
+            dir.path().join(WORLD_BACKUPS_DIR).join("reset-1")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_world_out_and_back() {
+        let server = tempfile::tempdir().unwrap();
+        let external = tempfile::tempdir().unwrap();
+        let target = external.path().join("survival");
+        fs::create_dir_all(server.path().join("world/region")).unwrap();
+        fs::write(server.path().join("world/region/r.0.0.mca"), "chunks").unwrap();
+
+        assert!(WorldMove::plan(server.path(), Some(Path::new("survival"))).is_err());
+        assert!(WorldMove::plan(server.path(), Some(&server.path().join("worlds/a"))).is_err());
+        assert!(WorldMove::plan(server.path(), None).is_err());
+
+        let plan = WorldMove::plan(server.path(), Some(&target)).unwrap();
+        assert_eq!(plan.level_name, "survival");
+        plan.apply().unwrap();
+        assert!(!server.path().join("world").exists());
+        assert_eq!(
+            fs::read_to_string(server.path().join("survival/region/r.0.0.mca")).unwrap(),
+            "chunks"
+        );
+        assert!(target.join("region/r.0.0.mca").is_file());
+        // Now that the world is linked, it's found as `survival`
+        fs::write(
+            server.path().join("server.properties"),
+            "level-name=survival\n",
+        )
+        .unwrap();
+        assert!(WorldMove::plan(server.path(), Some(&target)).is_err());
+        assert_eq!(
+            world_dirs(server.path()).unwrap(),
+            [server.path().join("survival")]
+        );
+
+        // Backed up next to it, leaving an empty world for the link
+        let backups_dir = backups_dir(server.path()).unwrap();
+        assert_eq!(
+            backups_dir,
+            fs::canonicalize(external.path())
+                .unwrap()
+                .join(WORLD_BACKUPS_DIR)
+        );
+        let backup = back_up(&backups_dir, &world_dirs(server.path()).unwrap(), "reset").unwrap();
+        assert!(backup.join("survival/region/r.0.0.mca").is_file());
+        assert!(server.path().join("survival").is_dir());
+        fs::write(target.join("level.dat"), "new world").unwrap();
+
+        let plan = WorldMove::plan(server.path(), None).unwrap();
+        plan.apply().unwrap();
+        assert!(!is_link(&server.path().join("survival")));
+        assert!(server.path().join("survival/level.dat").is_file());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_copy_then_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("world");
+        fs::create_dir_all(from.join("region")).unwrap();
+        fs::write(from.join("level.dat"), "level").unwrap();
+        fs::write(from.join("region/r.0.0.mca"), "chunks").unwrap();
+        let to = dir.path().join("elsewhere/survival");
+        fs::create_dir_all(&to).unwrap();
+        // Left over from an interrupted copy
+        fs::create_dir_all(dir.path().join("elsewhere/survival.partial/stale")).unwrap();
+
+        copy_then_delete(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(
+            fs::read_to_string(to.join("region/r.0.0.mca")).unwrap(),
+            "chunks"
+        );
+        assert!(!to.join("stale").exists());
+        assert!(!dir.path().join("elsewhere/survival.partial").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_then_delete_keeps_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared-mods");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("lithium.jar"), "jar").unwrap();
+        let from = dir.path().join("instances");
+        fs::create_dir_all(from.join("survival")).unwrap();
+        std::os::unix::fs::symlink(&shared, from.join("survival/mods")).unwrap();
+        let to = dir.path().join("elsewhere/instances");
+        fs::create_dir_all(dir.path().join("elsewhere")).unwrap();
+
+        copy_then_delete(&from, &to).unwrap();
+        assert_eq!(fs::read_link(to.join("survival/mods")).unwrap(), shared);
+        assert!(shared.join("lithium.jar").is_file());
+    }
+}
@@ -0,0 +1,85 @@
+use mcerv::api::{ForkInstaller, InstanceManager, MirrorRule, ServerFork, set_mirrors};
+use sha1::{Digest, Sha1};
+use std::io::{Cursor, Write};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// A vanilla server jar, as far as detecting its fork and version goes.
+fn vanilla_jar(version: &str) -> Vec<u8> {
+    let mut jar = ZipWriter::new(Cursor::new(Vec::new()));
+    jar.start_file("META-INF/MANIFEST.MF", SimpleFileOptions::default())
+        .unwrap();
+    writeln!(jar, "Main-Class: net.minecraft.server.Main").unwrap();
+    jar.start_file("version.json", SimpleFileOptions::default())
+        .unwrap();
+    write!(jar, r#"{{ "name": "{version}" }}"#).unwrap();
+    jar.finish().unwrap().into_inner()
+}
+
+#[tokio::test]
+async fn test_install_vanilla() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+    let jar = vanilla_jar("1.21.8");
+    let sha1 = format!("{:x}", Sha1::digest(&jar));
+
+    // Mojang's hosts are sent to the mock server, so this runs offline
+    set_mirrors(vec![
+        MirrorRule {
+            from: "https://launchermeta.mojang.com/".to_string(),
+            to: format!("{url}/"),
+        },
+        MirrorRule {
+            from: "https://piston-data.mojang.com/".to_string(),
+            to: format!("{url}/"),
+        },
+    ]);
+    server
+        .mock("GET", "/mc/game/version_manifest_v2.json")
+        .with_body(
+            r#"{
+                "latest": { "release": "1.21.8", "snapshot": "1.21.8" },
+                "versions": [{
+                    "id": "1.21.8",
+                    "type": "release",
+                    "url": "https://launchermeta.mojang.com/v1/packages/1.21.8.json",
+                    "releaseTime": "2025-07-17T12:04:20+00:00"
+                }]
+            }"#,
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/packages/1.21.8.json")
+        .with_body(format!(
+            r#"{{ "downloads": {{ "server": {{ "url": "https://piston-data.mojang.com/v1/objects/server.jar", "sha1": "{sha1}" }} }} }}"#
+        ))
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/objects/server.jar")
+        .with_body(&jar)
+        .create_async()
+        .await;
+
+    let data_dir = tempfile::tempdir().unwrap();
+    let manager = InstanceManager::with_data_dir(data_dir.path());
+
+    let client = reqwest::Client::new();
+    let instance = ForkInstaller::new(ServerFork::Vanilla, "smp")
+        .game("1.21.8")
+        .eula_accepted(true)
+        .install(&client)
+        .await
+        .unwrap();
+
+    let info = instance.info().unwrap();
+    assert_eq!(info.game_version, "1.21.8");
+    assert!(instance.dir().join(&info.jar_name).is_file());
+    assert!(instance.dir().join("mcerv_config.json").is_file());
+    assert!(
+        std::fs::read_to_string(instance.dir().join("eula.txt"))
+            .unwrap()
+            .contains("eula=true")
+    );
+    assert_eq!(manager.list().unwrap(), [instance]);
+}